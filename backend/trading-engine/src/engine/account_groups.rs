@@ -0,0 +1,219 @@
+use crate::{engine::TradingEngine, types::*};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Limits on the combined book of a group's accounts, checked on top of
+/// each account's own. Quantities are face value; unset means no limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupLimits {
+    /// Net position per symbol, counting resting orders that would add to it.
+    #[serde(default)]
+    pub max_net_position: Option<Decimal>,
+    /// Sum of absolute positions across symbols plus all resting orders.
+    #[serde(default)]
+    pub max_gross_exposure: Option<Decimal>,
+    #[serde(default)]
+    pub max_open_order_value: Option<Decimal>,
+}
+
+/// Accounts of one legal entity whose exposure is limited together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountGroup {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub accounts: Vec<Uuid>,
+    #[serde(default)]
+    pub limits: GroupLimits,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SymbolExposure {
+    pub symbol: String,
+    pub net_position: Decimal,
+    pub gross_position: Decimal,
+    pub open_buy_quantity: Decimal,
+    pub open_sell_quantity: Decimal,
+}
+
+impl SymbolExposure {
+    /// The largest net position the group could reach if every resting
+    /// order on one side filled.
+    fn worst_case_net(&self) -> Decimal {
+        (self.net_position + self.open_buy_quantity)
+            .abs()
+            .max((self.net_position - self.open_sell_quantity).abs())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupExposure {
+    pub group_id: String,
+    pub accounts: Vec<Uuid>,
+    pub symbols: Vec<SymbolExposure>,
+    pub gross_exposure: Decimal,
+    pub open_order_value: Decimal,
+    pub limits: GroupLimits,
+}
+
+/// Account groups, with each account in at most one group.
+#[derive(Default)]
+pub struct AccountGroupRegistry {
+    groups: DashMap<String, AccountGroup>,
+    membership: DashMap<Uuid, String>,
+}
+
+impl AccountGroupRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates or replaces a group. Fails if an account already belongs
+    /// to a different group.
+    pub fn upsert(&self, group: AccountGroup) -> crate::types::Result<()> {
+        for account_id in &group.accounts {
+            if let Some(other) = self.membership.get(account_id) {
+                if *other != group.id {
+                    return Err(TradingError::InvalidRequest(format!(
+                        "Account {} already belongs to group {}",
+                        account_id, *other
+                    )));
+                }
+            }
+        }
+        self.remove(&group.id);
+        for account_id in &group.accounts {
+            self.membership.insert(*account_id, group.id.clone());
+        }
+        self.groups.insert(group.id.clone(), group);
+        Ok(())
+    }
+
+    pub fn remove(&self, group_id: &str) -> Option<AccountGroup> {
+        let (_, group) = self.groups.remove(group_id)?;
+        for account_id in &group.accounts {
+            self.membership.remove(account_id);
+        }
+        Some(group)
+    }
+
+    pub fn get(&self, group_id: &str) -> Option<AccountGroup> {
+        self.groups.get(group_id).map(|group| group.clone())
+    }
+
+    pub fn group_of(&self, account_id: Uuid) -> Option<AccountGroup> {
+        let group_id = self.membership.get(&account_id)?.clone();
+        self.get(&group_id)
+    }
+
+    pub fn all(&self) -> Vec<AccountGroup> {
+        let mut groups: Vec<AccountGroup> = self.groups.iter().map(|group| group.clone()).collect();
+        groups.sort_by(|a, b| a.id.cmp(&b.id));
+        groups
+    }
+}
+
+impl TradingEngine {
+    pub fn account_groups(&self) -> &AccountGroupRegistry {
+        &self.account_groups
+    }
+
+    pub async fn group_exposure(&self, group_id: &str) -> crate::types::Result<GroupExposure> {
+        let group = self
+            .account_groups
+            .get(group_id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown account group {}", group_id)))?;
+        Ok(self.exposure_of(&group).await)
+    }
+
+    async fn exposure_of(&self, group: &AccountGroup) -> GroupExposure {
+        let mut symbols: BTreeMap<String, SymbolExposure> = BTreeMap::new();
+        for &account_id in &group.accounts {
+            for position in self.position_manager.get_positions(Some(account_id)).await {
+                let exposure = symbols.entry(position.symbol.clone()).or_default();
+                exposure.net_position += position.quantity;
+                exposure.gross_position += position.quantity.abs();
+            }
+        }
+
+        let mut open_order_value = Decimal::ZERO;
+        for entry in self.orders.iter() {
+            let order = entry.value();
+            if !order.is_open() || !group.accounts.contains(&order.account_id) {
+                continue;
+            }
+            let exposure = symbols.entry(order.symbol.clone()).or_default();
+            match order.side {
+                OrderSide::Buy => exposure.open_buy_quantity += order.remaining_quantity,
+                OrderSide::Sell => exposure.open_sell_quantity += order.remaining_quantity,
+            }
+            open_order_value += notional_value(order.remaining_quantity, order.price.unwrap_or_default());
+        }
+
+        let symbols: Vec<SymbolExposure> = symbols
+            .into_iter()
+            .map(|(symbol, exposure)| SymbolExposure { symbol, ..exposure })
+            .collect();
+        let gross_exposure = symbols
+            .iter()
+            .map(|exposure| exposure.net_position.abs() + exposure.open_buy_quantity + exposure.open_sell_quantity)
+            .sum();
+        GroupExposure {
+            group_id: group.id.clone(),
+            accounts: group.accounts.clone(),
+            symbols,
+            gross_exposure,
+            open_order_value,
+            limits: group.limits.clone(),
+        }
+    }
+
+    /// Checks the group limits of the order's account as if the order
+    /// were resting alongside the group's other orders.
+    pub(crate) async fn check_group_limits(&self, order: &Order) -> crate::types::Result<()> {
+        let Some(group) = self.account_groups.group_of(order.account_id) else {
+            return Ok(());
+        };
+        let exposure = self.exposure_of(&group).await;
+        let limits = &group.limits;
+        let breach = |message: String| {
+            Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::Group,
+                message: format!("Group {}: {}", group.id, message),
+            })
+        };
+
+        if let Some(max) = limits.max_net_position {
+            let mut symbol = exposure
+                .symbols
+                .iter()
+                .find(|exposure| exposure.symbol == order.symbol)
+                .cloned()
+                .unwrap_or_default();
+            match order.side {
+                OrderSide::Buy => symbol.open_buy_quantity += order.quantity,
+                OrderSide::Sell => symbol.open_sell_quantity += order.quantity,
+            }
+            let worst = symbol.worst_case_net();
+            if worst > max {
+                return breach(format!("net position in {} could reach {}, limit {}", order.symbol, worst, max));
+            }
+        }
+        if let Some(max) = limits.max_gross_exposure {
+            let gross = exposure.gross_exposure + order.quantity;
+            if gross > max {
+                return breach(format!("gross exposure {} exceeds limit {}", gross, max));
+            }
+        }
+        if let Some(max) = limits.max_open_order_value {
+            let value = exposure.open_order_value + notional_value(order.quantity, order.price.unwrap_or_default());
+            if value > max {
+                return breach(format!("open order value {} exceeds limit {}", value, max));
+            }
+        }
+        Ok(())
+    }
+}
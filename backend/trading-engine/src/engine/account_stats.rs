@@ -0,0 +1,88 @@
+use crate::types::*;
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccountTradingStats {
+    pub account_id: Uuid,
+    pub maker_trades: u64,
+    pub taker_trades: u64,
+    pub maker_volume: Decimal,
+    pub taker_volume: Decimal,
+    pub maker_notional: Decimal,
+    pub taker_notional: Decimal,
+}
+
+impl AccountTradingStats {
+    /// Share of the account's traded volume that added liquidity.
+    pub fn maker_ratio(&self) -> Option<Decimal> {
+        let total = self.maker_volume + self.taker_volume;
+        if total.is_zero() {
+            None
+        } else {
+            Some(self.maker_volume / total)
+        }
+    }
+}
+
+/// Per-account liquidity attribution, updated from executed trades.
+pub struct AccountStatsTracker {
+    stats: DashMap<Uuid, AccountTradingStats>,
+}
+
+impl AccountStatsTracker {
+    pub fn new() -> Self {
+        Self {
+            stats: DashMap::new(),
+        }
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let account_id = trade.account_for(&side);
+            let mut stats = self.stats.entry(account_id).or_insert_with(|| AccountTradingStats {
+                account_id,
+                ..Default::default()
+            });
+
+            match trade.liquidity_role(&side) {
+                LiquidityRole::Maker => {
+                    stats.maker_trades += 1;
+                    stats.maker_volume += trade.quantity;
+                    stats.maker_notional += trade.notional();
+                }
+                LiquidityRole::Taker => {
+                    stats.taker_trades += 1;
+                    stats.taker_volume += trade.quantity;
+                    stats.taker_notional += trade.notional();
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, account_id: Uuid) -> AccountTradingStats {
+        self.stats
+            .get(&account_id)
+            .map(|stats| stats.clone())
+            .unwrap_or(AccountTradingStats {
+                account_id,
+                ..Default::default()
+            })
+    }
+
+    /// Accounts with the largest traded volume, largest first.
+    pub fn top_by_volume(&self, limit: usize) -> Vec<AccountTradingStats> {
+        let mut stats: Vec<AccountTradingStats> = self.stats.iter().map(|entry| entry.value().clone()).collect();
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.maker_volume + stats.taker_volume));
+        stats.truncate(limit);
+        stats
+    }
+}
+
+impl Default for AccountStatsTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    engine::{fixings::FixingStore, matching::BookOrder, TradingEngine},
+    types::*,
+};
+use chrono::{Months, NaiveDate};
+use rust_decimal::Decimal;
+
+/// What a quoted order book is built from: the outright and spread books,
+/// and the day accrued interest is computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookVersion {
+    generation: u64,
+    date: NaiveDate,
+}
+
+/// Reads of the books for the feeds before giving up on an unchanged
+/// book sequence.
+const BOOK_READ_ATTEMPTS: usize = 8;
+
+/// Months between coupons, if the bond pays a whole number of coupons a
+/// year that divides twelve. Money-market instruments pay none.
+fn coupon_months(bond: &Bond) -> Option<u32> {
+    if bond.bond_type.is_money_market() {
+        return None;
+    }
+    match bond.coupon_frequency {
+        0 => None,
+        frequency if 12 % frequency == 0 => Some(12 / frequency),
+        _ => None,
+    }
+}
+
+/// Coupon dates strictly after `after` up to maturity, stepping back from
+/// maturity in whole coupon periods. Empty for discount instruments.
+pub fn coupon_dates(bond: &Bond, after: NaiveDate) -> Vec<NaiveDate> {
+    let maturity = bond.maturity_date.date_naive();
+    let Some(months) = coupon_months(bond) else {
+        return Vec::new();
+    };
+    let mut dates: Vec<NaiveDate> = (0..)
+        .map_while(|periods| maturity.checked_sub_months(Months::new(months * periods)))
+        .take_while(|date| *date > after)
+        .collect();
+    dates.reverse();
+    dates
+}
+
+/// The coupon period containing `settlement`: the last coupon date on or
+/// before it and the next one after. None for discount instruments and
+/// bonds already past maturity.
+pub fn coupon_period(bond: &Bond, settlement: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let maturity = bond.maturity_date.date_naive();
+    let months = coupon_months(bond)?;
+    if settlement >= maturity {
+        return None;
+    }
+    let mut next = maturity;
+    for periods in 1.. {
+        let previous = maturity.checked_sub_months(Months::new(months * periods))?;
+        if previous <= settlement {
+            return Some((previous, next));
+        }
+        next = previous;
+    }
+    None
+}
+
+/// Interest accrued per 100 of face value from the last coupon to
+/// `settlement`, under the bond's day count and at the current period's
+/// coupon rate.
+pub fn accrued_interest(bond: &Bond, settlement: NaiveDate, fixings: &FixingStore) -> Decimal {
+    match coupon_period(bond, settlement) {
+        Some((last_coupon, next_coupon)) => {
+            fixings.coupon_rate(bond, last_coupon)
+                * bond
+                    .day_count
+                    .accrual_fraction(last_coupon, settlement, last_coupon, next_coupon, bond.coupon_frequency)
+        }
+        None => Decimal::ZERO,
+    }
+}
+
+pub fn to_clean(convention: QuoteConvention, price: Decimal, accrued: Decimal) -> Decimal {
+    match convention {
+        QuoteConvention::Clean => price,
+        QuoteConvention::Dirty => price - accrued,
+    }
+}
+
+pub fn to_quoted(convention: QuoteConvention, clean_price: Decimal, accrued: Decimal) -> Decimal {
+    match convention {
+        QuoteConvention::Clean => clean_price,
+        QuoteConvention::Dirty => clean_price + accrued,
+    }
+}
+
+impl TradingEngine {
+    /// Quoting convention and accrued interest for a trade in `symbol`
+    /// agreed now. Unlisted symbols quote clean.
+    pub fn quote_basis(&self, symbol: &str) -> (QuoteConvention, Decimal) {
+        let Some(bond) = self.instruments.get(symbol) else {
+            return (QuoteConvention::Clean, Decimal::ZERO);
+        };
+        let settlement = self
+            .time_provider
+            .settlement_date(self.time_provider.now().date_naive(), self.config.settlement_cycle_days);
+        (bond.quote_convention, accrued_interest(&bond, settlement, &self.fixings))
+    }
+
+    /// Index ratio for a trade in `symbol` agreed now, if it is an
+    /// inflation-linked bond with index data for the settlement date.
+    pub fn settlement_index_ratio(&self, symbol: &str) -> Option<Decimal> {
+        let bond = self.instruments.get(symbol)?;
+        let settlement = self
+            .time_provider
+            .settlement_date(self.time_provider.now().date_naive(), self.config.settlement_cycle_days);
+        self.inflation_indices.index_ratio(&bond, settlement)
+    }
+
+    pub fn to_quoted_price(&self, symbol: &str, clean_price: Decimal) -> Decimal {
+        let (convention, accrued) = self.quote_basis(symbol);
+        let scale = self.matching_engine.tick_scale(symbol);
+        scale.round_price(to_quoted(convention, clean_price, accrued))
+    }
+
+    pub fn book_version(&self) -> BookVersion {
+        BookVersion {
+            generation: self.book_sequence(),
+            date: self.time_provider.now().date_naive(),
+        }
+    }
+
+    /// Advances with every change to the outright or spread books, so two
+    /// reads at the same sequence saw the same books.
+    pub fn book_sequence(&self) -> u64 {
+        self.matching_engine.generation() + self.spread_book.generation()
+    }
+
+    /// The result of `read` with the book sequence it saw, retried while
+    /// the books change underneath it. After the last attempt the result
+    /// is returned with the sequence read just before it.
+    fn read_at_book_sequence<T>(&self, read: impl Fn() -> T) -> (u64, T) {
+        let mut sequence = self.book_sequence();
+        for _ in 1..BOOK_READ_ATTEMPTS {
+            let value = read();
+            let after = self.book_sequence();
+            if after == sequence {
+                return (sequence, value);
+            }
+            sequence = after;
+        }
+        (sequence, read())
+    }
+
+    /// The order book with prices in the instrument's quoting convention.
+    pub fn quoted_orderbook(&self, symbol: &str) -> Option<OrderBook> {
+        let mut book = self.get_orderbook(symbol)?;
+        let (convention, accrued) = self.quote_basis(symbol);
+        if convention != QuoteConvention::Clean {
+            let scale = self.matching_engine.tick_scale(symbol);
+            for level in book.bids.iter_mut().chain(book.asks.iter_mut()) {
+                level.price = scale.round_price(to_quoted(convention, level.price, accrued));
+            }
+        }
+        Some(book)
+    }
+
+    /// `quoted_orderbook` with the book sequence it reflects.
+    pub fn quoted_orderbook_at(&self, symbol: &str) -> (u64, Option<OrderBook>) {
+        self.read_at_book_sequence(|| self.quoted_orderbook(symbol))
+    }
+
+    /// Resting orders at the best `levels` prices of each side, priced in
+    /// the instrument's quoting convention, with the book sequence they
+    /// reflect.
+    pub fn quoted_resting_orders(&self, symbol: &str, levels: usize) -> (u64, (Vec<BookOrder>, Vec<BookOrder>)) {
+        let (convention, accrued) = self.quote_basis(symbol);
+        let scale = self.matching_engine.tick_scale(symbol);
+        self.read_at_book_sequence(|| {
+            let (mut bids, mut asks) = self.matching_engine.resting_orders(symbol, levels);
+            if convention != QuoteConvention::Clean {
+                for order in bids.iter_mut().chain(asks.iter_mut()) {
+                    order.price = scale.round_price(to_quoted(convention, order.price, accrued));
+                }
+            }
+            (bids, asks)
+        })
+    }
+
+    /// Converts an order entered in the instrument's convention to a clean
+    /// price for matching, keeping the entered price in `quoted_price`
+    /// metadata. The clean price is rounded to the book's tick scale.
+    pub(crate) fn normalize_order_price(&self, order: &mut Order) {
+        let (convention, accrued) = self.quote_basis(&order.symbol);
+        if convention == QuoteConvention::Clean {
+            return;
+        }
+        if let Some(price) = order.price {
+            order.metadata.insert("quoted_price".to_string(), price.to_string());
+            let clean = to_clean(convention, price, accrued);
+            order.price = Some(self.matching_engine.tick_scale(&order.symbol).round_price(clean));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_accrued_interest_from_last_coupon() {
+        let bond = Bond {
+            isin: "IN0020230085".to_string(),
+            symbol: "GS2033".to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2033, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.18),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Dirty,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        let fixings = FixingStore::new();
+        let settlement = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(
+            coupon_period(&bond, settlement),
+            NaiveDate::from_ymd_opt(2023, 12, 15).zip(NaiveDate::from_ymd_opt(2024, 6, 15))
+        );
+        // 76 days of a 7.18 coupon on 30E/360
+        let accrued = accrued_interest(&bond, settlement, &fixings);
+        assert_eq!(accrued, dec!(7.18) * (dec!(76) / dec!(360)));
+        assert_eq!(to_clean(bond.quote_convention, to_quoted(bond.quote_convention, dec!(99.50), accrued), accrued), dec!(99.50));
+
+        // Nothing accrues on a coupon date
+        let coupon_day = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        assert_eq!(accrued_interest(&bond, coupon_day, &fixings), Decimal::ZERO);
+
+        // ACT/ACT ICMA: 77 of the period's 183 days
+        let icma = Bond { day_count: DayCount::ActualActualIcma, ..bond.clone() };
+        assert_eq!(
+            accrued_interest(&icma, settlement, &fixings).round_dp(12),
+            (dec!(7.18) * dec!(77) / dec!(366)).round_dp(12)
+        );
+
+        let bill = Bond { coupon_rate: Decimal::ZERO, coupon_frequency: 0, ..bond };
+        assert_eq!(accrued_interest(&bill, settlement, &fixings), Decimal::ZERO);
+    }
+}
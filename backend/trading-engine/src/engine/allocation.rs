@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+
+/// How incoming quantity is shared among the resting orders at a price
+/// level. Configured per instrument.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MatchingAlgorithm {
+    #[default]
+    PriceTime,
+    ProRata,
+    SizeTime,
+}
+
+impl MatchingAlgorithm {
+    pub fn allocator(&self) -> &'static dyn MatchAllocator {
+        match self {
+            MatchingAlgorithm::PriceTime => &PriceTimeAllocator,
+            MatchingAlgorithm::ProRata => &ProRataAllocator,
+            MatchingAlgorithm::SizeTime => &SizeTimeAllocator,
+        }
+    }
+}
+
+/// How resting orders from the aggressor's own firm are treated at a
+/// price level. Configured per instrument.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum FirmPreference {
+    #[default]
+    None,
+    /// The firm's own orders are allocated first; the rest of the level
+    /// shares whatever they leave.
+    BrokerPriority,
+    /// The firm's own orders are passed over and never trade with it.
+    AntiInternalization,
+}
+
+/// A resting order as seen by an allocator, with its quantity in ticks.
+/// Levels are passed in time priority, so index order is arrival order.
+#[derive(Debug, Clone, Copy)]
+pub struct RestingInterest {
+    pub remaining: u64,
+    pub priority: u64,
+    /// Entered by another account of the aggressor's firm.
+    pub same_firm: bool,
+    /// Whether the aggressor may trade with it at all; orders from
+    /// counterparties without credit line headroom are passed over.
+    pub eligible: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    pub index: usize,
+    pub quantity: u64,
+}
+
+pub trait MatchAllocator: Send + Sync {
+    /// Splits `quantity` across one price level. Returned fills are in
+    /// execution order, never exceed an order's remaining quantity and
+    /// never total more than `quantity`. `lot` is the number of ticks in
+    /// one whole unit.
+    fn allocate(&self, level: &[RestingInterest], quantity: u64, lot: u64) -> Vec<Allocation>;
+
+    /// Splits `quantity` as [`allocate`](Self::allocate) would among the
+    /// eligible orders, after applying `preference` to the aggressor's own
+    /// firm's orders.
+    fn allocate_with_preference(
+        &self,
+        level: &[RestingInterest],
+        quantity: u64,
+        lot: u64,
+        preference: FirmPreference,
+    ) -> Vec<Allocation> {
+        match preference {
+            FirmPreference::None if level.iter().all(|resting| resting.eligible) => {
+                self.allocate(level, quantity, lot)
+            }
+            FirmPreference::None => allocate_among(self, level, |resting| resting.eligible, quantity, lot),
+            FirmPreference::AntiInternalization => {
+                allocate_among(self, level, |resting| resting.eligible && !resting.same_firm, quantity, lot)
+            }
+            FirmPreference::BrokerPriority => {
+                let mut allocations =
+                    allocate_among(self, level, |resting| resting.eligible && resting.same_firm, quantity, lot);
+                let filled: u64 = allocations.iter().map(|allocation| allocation.quantity).sum();
+                allocations.extend(allocate_among(
+                    self,
+                    level,
+                    |resting| resting.eligible && !resting.same_firm,
+                    quantity - filled,
+                    lot,
+                ));
+                allocations
+            }
+        }
+    }
+}
+
+/// Runs `allocator` over the orders `include` keeps, as if they were the
+/// whole level, and maps the fills back to their indices in `level`.
+fn allocate_among<A: MatchAllocator + ?Sized>(
+    allocator: &A,
+    level: &[RestingInterest],
+    include: impl Fn(&RestingInterest) -> bool,
+    quantity: u64,
+    lot: u64,
+) -> Vec<Allocation> {
+    let indices: Vec<usize> = (0..level.len()).filter(|&index| include(&level[index])).collect();
+    if indices.is_empty() || quantity == 0 {
+        return Vec::new();
+    }
+    let subset: Vec<RestingInterest> = indices.iter().map(|&index| level[index]).collect();
+    allocator
+        .allocate(&subset, quantity, lot)
+        .into_iter()
+        .map(|allocation| Allocation {
+            index: indices[allocation.index],
+            quantity: allocation.quantity,
+        })
+        .collect()
+}
+
+/// Strict FIFO within the level.
+pub struct PriceTimeAllocator;
+
+impl MatchAllocator for PriceTimeAllocator {
+    fn allocate(&self, level: &[RestingInterest], quantity: u64, _lot: u64) -> Vec<Allocation> {
+        fill_in_order(level, 0..level.len(), quantity)
+    }
+}
+
+/// The first order at the level is filled ahead of everyone else; what is
+/// left is shared in proportion to resting size, rounded down to whole
+/// units, with any residue handed out in time priority.
+pub struct ProRataAllocator;
+
+impl MatchAllocator for ProRataAllocator {
+    fn allocate(&self, level: &[RestingInterest], quantity: u64, lot: u64) -> Vec<Allocation> {
+        let Some(top) = level.first() else {
+            return Vec::new();
+        };
+
+        let mut filled = vec![0u64; level.len()];
+        filled[0] = top.remaining.min(quantity);
+        let mut left = quantity - filled[0];
+
+        let rest_total: u128 = level[1..].iter().map(|resting| u128::from(resting.remaining)).sum();
+        if left > 0 && rest_total > 0 {
+            let pool = u128::from(left);
+            let lot = lot.max(1);
+            for (index, resting) in level.iter().enumerate().skip(1) {
+                // At most `pool`, so it fits back into a u64
+                let share = (pool * u128::from(resting.remaining) / rest_total) as u64;
+                let share = (share - share % lot).min(resting.remaining);
+                filled[index] = share;
+                left -= share;
+            }
+        }
+
+        for (index, resting) in level.iter().enumerate() {
+            if left == 0 {
+                break;
+            }
+            let extra = (resting.remaining - filled[index]).min(left);
+            filled[index] += extra;
+            left -= extra;
+        }
+
+        filled
+            .into_iter()
+            .enumerate()
+            .filter(|(_, quantity)| *quantity > 0)
+            .map(|(index, quantity)| Allocation { index, quantity })
+            .collect()
+    }
+}
+
+/// Largest resting order first, ties broken by time priority.
+pub struct SizeTimeAllocator;
+
+impl MatchAllocator for SizeTimeAllocator {
+    fn allocate(&self, level: &[RestingInterest], quantity: u64, _lot: u64) -> Vec<Allocation> {
+        let mut order: Vec<usize> = (0..level.len()).collect();
+        order.sort_by(|&a, &b| {
+            level[b]
+                .remaining
+                .cmp(&level[a].remaining)
+                .then(level[a].priority.cmp(&level[b].priority))
+        });
+        fill_in_order(level, order, quantity)
+    }
+}
+
+fn fill_in_order(
+    level: &[RestingInterest],
+    indices: impl IntoIterator<Item = usize>,
+    quantity: u64,
+) -> Vec<Allocation> {
+    let mut left = quantity;
+    let mut allocations = Vec::new();
+    for index in indices {
+        if left == 0 {
+            break;
+        }
+        let fill = level[index].remaining.min(left);
+        if fill > 0 {
+            allocations.push(Allocation { index, quantity: fill });
+            left -= fill;
+        }
+    }
+    allocations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(sizes: &[u64]) -> Vec<RestingInterest> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &remaining)| RestingInterest {
+                remaining,
+                priority: i as u64,
+                same_firm: false,
+                eligible: true,
+            })
+            .collect()
+    }
+
+    fn with_firm(sizes: &[u64], same_firm: &[usize]) -> Vec<RestingInterest> {
+        let mut level = level(sizes);
+        for &index in same_firm {
+            level[index].same_firm = true;
+        }
+        level
+    }
+
+    #[test]
+    fn test_pro_rata_fills_top_order_then_shares_by_size() {
+        let level = level(&[100, 300, 100]);
+        let allocations = ProRataAllocator.allocate(&level, 300, 1);
+
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation { index: 0, quantity: 100 },
+                Allocation { index: 1, quantity: 150 },
+                Allocation { index: 2, quantity: 50 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pro_rata_residue_goes_by_time_priority() {
+        let level = level(&[10, 100, 100, 100]);
+        let allocations = ProRataAllocator.allocate(&level, 20, 1);
+
+        // 10 left after the top order: 3 each pro-rata, 1 residue to the
+        // earliest of the rest.
+        let quantities: Vec<u64> = allocations.iter().map(|a| a.quantity).collect();
+        assert_eq!(quantities, vec![10, 4, 3, 3]);
+
+        // The same in ten-thousandths: shares still round to whole units
+        let level: Vec<RestingInterest> = level
+            .iter()
+            .map(|resting| RestingInterest {
+                remaining: resting.remaining * 10_000,
+                ..*resting
+            })
+            .collect();
+        let allocations = ProRataAllocator.allocate(&level, 200_000, 10_000);
+        let quantities: Vec<u64> = allocations.iter().map(|a| a.quantity).collect();
+        assert_eq!(quantities, vec![100_000, 40_000, 30_000, 30_000]);
+    }
+
+    #[test]
+    fn test_size_time_prefers_larger_then_earlier() {
+        let level = level(&[100, 500, 500]);
+        let allocations = SizeTimeAllocator.allocate(&level, 700, 1);
+
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation { index: 1, quantity: 500 },
+                Allocation { index: 2, quantity: 200 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_broker_priority_fills_same_firm_first() {
+        let level = with_firm(&[100, 200, 100], &[2]);
+        let allocations =
+            PriceTimeAllocator.allocate_with_preference(&level, 250, 1, FirmPreference::BrokerPriority);
+
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation { index: 2, quantity: 100 },
+                Allocation { index: 0, quantity: 100 },
+                Allocation { index: 1, quantity: 50 },
+            ]
+        );
+
+        // The rest of the level still shares by the instrument's algorithm
+        let allocations = ProRataAllocator.allocate_with_preference(&level, 400, 1, FirmPreference::BrokerPriority);
+        let quantities: Vec<(usize, u64)> = allocations.iter().map(|a| (a.index, a.quantity)).collect();
+        assert_eq!(quantities, vec![(2, 100), (0, 100), (1, 200)]);
+    }
+
+    #[test]
+    fn test_anti_internalization_skips_same_firm() {
+        let level = with_firm(&[100, 200, 100], &[0]);
+        let allocations =
+            SizeTimeAllocator.allocate_with_preference(&level, 400, 1, FirmPreference::AntiInternalization);
+
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation { index: 1, quantity: 200 },
+                Allocation { index: 2, quantity: 100 },
+            ]
+        );
+        assert_eq!(
+            PriceTimeAllocator.allocate_with_preference(&level, 50, 1, FirmPreference::None),
+            vec![Allocation { index: 0, quantity: 50 }]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use crate::{
+    engine::{accrued, fixings::FixingStore, money_market, TradingEngine},
+    types::*,
+};
+use chrono::NaiveDate;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+
+const YIELD_SEARCH_LOW: f64 = -0.5;
+const YIELD_SEARCH_HIGH: f64 = 2.0;
+/// Yield bump for numerical duration.
+const DURATION_BUMP: f64 = 1e-4;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkoutKind {
+    Maturity,
+    Call,
+    Put,
+}
+
+/// Yield assuming the bond is redeemed on `date` at `price`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkoutYield {
+    pub kind: WorkoutKind,
+    pub date: NaiveDate,
+    pub price: Decimal,
+    pub yield_pct: Option<Decimal>,
+}
+
+/// Price and yield of a bond for one settlement date. Prices are per 100
+/// face.
+#[derive(Debug, Clone, Serialize)]
+pub struct BondAnalytics {
+    pub symbol: String,
+    pub settlement_date: NaiveDate,
+    pub day_count: DayCount,
+    pub quote_convention: QuoteConvention,
+    pub clean_price: Decimal,
+    pub accrued_interest: Decimal,
+    pub dirty_price: Decimal,
+    /// Coupon rate of the current period, in percent.
+    pub current_coupon_rate: Decimal,
+    /// For inflation-linked bonds, the index ratio on the settlement date
+    /// and the dirty price scaled by it, which is the cash per 100 face.
+    /// Their yield is real.
+    pub index_ratio: Option<Decimal>,
+    pub indexed_dirty_price: Option<Decimal>,
+    /// Percent a year, compounded at the coupon frequency, semi-annually for
+    /// zero-coupon bonds, or the money-market yield for T-bills, CDs and CP.
+    pub yield_to_maturity: Option<Decimal>,
+    /// For money-market instruments, the discount to face annualized, and
+    /// the yield comparable with a semi-annual coupon bond.
+    pub discount_yield: Option<Decimal>,
+    pub bond_equivalent_yield: Option<Decimal>,
+    /// Lowest yield over maturity and the remaining call dates, which is
+    /// what a holder can count on. Puts are the holder's choice and only
+    /// raise the yield, so they are listed but never the worst.
+    pub yield_to_worst: Option<Decimal>,
+    pub workouts: Vec<WorkoutYield>,
+}
+
+/// Cash flows per 100 face after `settlement` if the bond is redeemed at
+/// `redemption_price` on `redemption`, each with its time in coupon periods
+/// from settlement, and the number of periods a year. Coupon periods are
+/// counted whole from the next coupon, with part periods measured by the
+/// bond's day count. Redemption between coupon dates also pays the interest
+/// accrued to it. Zero-coupon bonds and STRIPS have one flow, compounded
+/// semi-annually like the coupon bonds they are stripped from.
+fn cash_flows(
+    bond: &Bond,
+    settlement: NaiveDate,
+    redemption: NaiveDate,
+    redemption_price: Decimal,
+    fixings: &FixingStore,
+) -> Option<(Vec<(f64, f64)>, f64)> {
+    if settlement >= redemption || redemption > bond.maturity_date.date_naive() {
+        return None;
+    }
+    let redemption_price = redemption_price.to_f64()?;
+
+    let Some((last_coupon, next_coupon)) = accrued::coupon_period(bond, settlement) else {
+        let years = bond.day_count.year_fraction(settlement, redemption).to_f64()?;
+        return Some((vec![(years * 2.0, redemption_price)], 2.0));
+    };
+    let frequency = bond.coupon_frequency as f64;
+    let periods_to = |start: NaiveDate, date: NaiveDate, end: NaiveDate| {
+        bond.day_count
+            .accrual_fraction(start, date, start, end, bond.coupon_frequency)
+            .to_f64()
+            .map(|fraction| fraction * frequency)
+    };
+    let to_next = periods_to(settlement, next_coupon, next_coupon)?;
+
+    let mut flows = Vec::new();
+    let mut period_start = last_coupon;
+    for (period, payment_date) in accrued::coupon_dates(bond, settlement).into_iter().enumerate() {
+        let coupon = fixings.coupon_rate(bond, period_start).to_f64()? / frequency;
+        let time = to_next + period as f64;
+        if payment_date >= redemption {
+            if payment_date == redemption {
+                flows.push((time, coupon + redemption_price));
+            } else {
+                let part = periods_to(period_start, redemption, payment_date)?;
+                flows.push((time - 1.0 + part, coupon * part + redemption_price));
+            }
+            break;
+        }
+        flows.push((time, coupon));
+        period_start = payment_date;
+    }
+    Some((flows, frequency))
+}
+
+/// Dirty price per 100 face at an annual yield of `rate` (0.07 for 7%),
+/// compounded at the coupon frequency, if the bond is redeemed at
+/// `redemption_price` on `redemption`. Floating coupons use their fixing,
+/// or the projected rate for periods not yet fixed.
+pub fn dirty_price_to(
+    bond: &Bond,
+    rate: f64,
+    settlement: NaiveDate,
+    redemption: NaiveDate,
+    redemption_price: Decimal,
+    fixings: &FixingStore,
+) -> Option<f64> {
+    if bond.bond_type.is_money_market() {
+        let days = (redemption - settlement).num_days();
+        let price = money_market::price_at_yield(
+            Decimal::from_f64_retain(rate * 100.0)?,
+            days,
+            money_market::year_basis(bond.day_count),
+        )?;
+        return (price * redemption_price / Decimal::ONE_HUNDRED).to_f64();
+    }
+    let (flows, frequency) = cash_flows(bond, settlement, redemption, redemption_price, fixings)?;
+    let discount = 1.0 + rate / frequency;
+    Some(flows.iter().map(|(time, amount)| amount / discount.powf(*time)).sum())
+}
+
+/// Dirty price per 100 face at an annual yield of `rate`, held to maturity.
+pub fn dirty_price_at_yield(bond: &Bond, rate: f64, settlement: NaiveDate, fixings: &FixingStore) -> Option<f64> {
+    let maturity = bond.maturity_date.date_naive();
+    dirty_price_to(bond, rate, settlement, maturity, Decimal::ONE_HUNDRED, fixings)
+}
+
+/// The yield in percent at which the bond is worth `clean_price`, or None
+/// if no yield between -50% and 200% fits.
+pub fn yield_to_maturity(
+    bond: &Bond,
+    clean_price: Decimal,
+    settlement: NaiveDate,
+    fixings: &FixingStore,
+) -> Option<Decimal> {
+    let maturity = bond.maturity_date.date_naive();
+    yield_to(bond, clean_price, settlement, maturity, Decimal::ONE_HUNDRED, fixings)
+}
+
+/// The yield at which the bond is worth `clean_price` if redeemed at
+/// `redemption_price` on `redemption`.
+pub fn yield_to(
+    bond: &Bond,
+    clean_price: Decimal,
+    settlement: NaiveDate,
+    redemption: NaiveDate,
+    redemption_price: Decimal,
+    fixings: &FixingStore,
+) -> Option<Decimal> {
+    if bond.bond_type.is_money_market() {
+        let days = (redemption - settlement).num_days();
+        let price = clean_price * Decimal::ONE_HUNDRED / redemption_price;
+        return money_market::money_market_yield(price, days, money_market::year_basis(bond.day_count))
+            .map(|rate| rate.round_dp(6));
+    }
+    let target = (clean_price + accrued::accrued_interest(bond, settlement, fixings)).to_f64()?;
+    let price = |rate: f64| dirty_price_to(bond, rate, settlement, redemption, redemption_price, fixings);
+
+    // Price falls as yield rises
+    let (mut low, mut high) = (YIELD_SEARCH_LOW, YIELD_SEARCH_HIGH);
+    if target > price(low)? || target < price(high)? {
+        return None;
+    }
+    while high - low > 1e-12 {
+        let mid = (low + high) / 2.0;
+        if price(mid)? > target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Decimal::from_f64_retain((low + high) / 2.0 * 100.0).map(|rate| rate.round_dp(6))
+}
+
+/// Yields to maturity and to every call and put date after `settlement`,
+/// in date order.
+pub fn workout_yields(
+    bond: &Bond,
+    clean_price: Decimal,
+    settlement: NaiveDate,
+    fixings: &FixingStore,
+) -> Vec<WorkoutYield> {
+    let maturity = RedemptionOption {
+        date: bond.maturity_date.date_naive(),
+        price: Decimal::ONE_HUNDRED,
+    };
+    let options = std::iter::once((WorkoutKind::Maturity, &maturity))
+        .chain(bond.call_schedule.iter().map(|call| (WorkoutKind::Call, call)))
+        .chain(bond.put_schedule.iter().map(|put| (WorkoutKind::Put, put)));
+    let mut workouts: Vec<WorkoutYield> = options
+        .filter(|(_, option)| option.date > settlement && option.date <= maturity.date)
+        .map(|(kind, option)| WorkoutYield {
+            kind,
+            date: option.date,
+            price: option.price,
+            yield_pct: yield_to(bond, clean_price, settlement, option.date, option.price, fixings),
+        })
+        .collect();
+    workouts.sort_by_key(|workout| workout.date);
+    workouts
+}
+
+/// Modified duration in years at a yield of `yield_pct`, from the price
+/// change for a small bump either side.
+pub fn modified_duration(bond: &Bond, yield_pct: Decimal, settlement: NaiveDate, fixings: &FixingStore) -> Option<Decimal> {
+    let rate = yield_pct.to_f64()? / 100.0;
+    let price = dirty_price_at_yield(bond, rate, settlement, fixings)?;
+    let down = dirty_price_at_yield(bond, rate - DURATION_BUMP, settlement, fixings)?;
+    let up = dirty_price_at_yield(bond, rate + DURATION_BUMP, settlement, fixings)?;
+    Decimal::from_f64_retain((down - up) / (2.0 * DURATION_BUMP * price)).map(|duration| duration.round_dp(6))
+}
+
+/// Lowest yield over maturity and the calls in `workouts`.
+pub fn yield_to_worst(workouts: &[WorkoutYield]) -> Option<Decimal> {
+    workouts
+        .iter()
+        .filter(|workout| workout.kind != WorkoutKind::Put)
+        .filter_map(|workout| workout.yield_pct)
+        .min()
+}
+
+impl TradingEngine {
+    /// Analytics for `symbol` settling on the next settlement date. `price`
+    /// is in the instrument's quoting convention; without one the reference
+    /// price is used.
+    pub fn bond_analytics(&self, symbol: &str, price: Option<Decimal>) -> crate::types::Result<BondAnalytics> {
+        let bond = self
+            .instruments
+            .get(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        let settlement_date = self
+            .time_provider
+            .settlement_date(self.time_provider.now().date_naive(), self.config.settlement_cycle_days);
+        let accrued_interest = accrued::accrued_interest(&bond, settlement_date, &self.fixings);
+        let current_coupon_rate = match accrued::coupon_period(&bond, settlement_date) {
+            Some((period_start, _)) => self.fixings.coupon_rate(&bond, period_start),
+            None => bond.coupon_rate,
+        };
+        let clean_price = match price {
+            Some(price) => accrued::to_clean(bond.quote_convention, price, accrued_interest),
+            None => self.reference_price(symbol)?.price,
+        };
+        let index_ratio = self.inflation_indices.index_ratio(&bond, settlement_date);
+        let workouts = workout_yields(&bond, clean_price, settlement_date, &self.fixings);
+        let days = money_market::days_to_maturity(&bond, settlement_date);
+        let (discount_yield, bond_equivalent_yield) = if bond.bond_type.is_money_market() {
+            (
+                money_market::discount_yield(clean_price, days, money_market::year_basis(bond.day_count)),
+                money_market::bond_equivalent_yield(clean_price, days),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(BondAnalytics {
+            symbol: bond.symbol.clone(),
+            settlement_date,
+            day_count: bond.day_count,
+            quote_convention: bond.quote_convention,
+            clean_price,
+            accrued_interest,
+            dirty_price: clean_price + accrued_interest,
+            current_coupon_rate,
+            index_ratio,
+            indexed_dirty_price: index_ratio.map(|ratio| (clean_price + accrued_interest) * ratio),
+            yield_to_maturity: yield_to_maturity(&bond, clean_price, settlement_date, &self.fixings),
+            discount_yield,
+            bond_equivalent_yield,
+            yield_to_worst: yield_to_worst(&workouts),
+            workouts,
+        })
+    }
+}
+
+impl TradingEngine {
+    /// Top of book, last trade and today's volume for `symbol`, with yields
+    /// and duration at the last traded price, or the mid when it has not
+    /// traded. Sides missing here come from a fresh external quote. Prices
+    /// are in the instrument's quoting convention.
+    pub fn market_data(&self, symbol: &str) -> crate::types::Result<MarketData> {
+        let bond = self
+            .instruments
+            .get(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        let now = self.time_provider.now();
+        let bid = self.matching_engine.get_best_bid(symbol);
+        let ask = self.matching_engine.get_best_ask(symbol);
+
+        let (mut last_price, mut volume) = (None, Decimal::ZERO);
+        for trade in self.trades.read().iter().filter(|trade| trade.symbol == symbol) {
+            last_price = Some(trade.price);
+            if trade.timestamp.date_naive() == now.date_naive() {
+                volume += trade.quantity;
+            }
+        }
+        // A symbol quiet on this venue shows the external feed's quote
+        let external = self.reference_prices.external_quote(symbol, now);
+        let bid = bid.or_else(|| external.as_ref()?.bid_price);
+        let ask = ask.or_else(|| external.as_ref()?.ask_price);
+        let last_price = last_price.or_else(|| external.as_ref()?.last_price);
+        let mid = bid.zip(ask).map(|(bid, ask)| (bid + ask) / Decimal::TWO);
+
+        let settlement_date = self.time_provider.settlement_date(now.date_naive(), self.config.settlement_cycle_days);
+        let (yield_to_maturity, yield_to_worst, duration) = match last_price.or(mid) {
+            Some(clean_price) => {
+                let ytm = yield_to_maturity(&bond, clean_price, settlement_date, &self.fixings);
+                let workouts = workout_yields(&bond, clean_price, settlement_date, &self.fixings);
+                let duration = ytm.and_then(|ytm| modified_duration(&bond, ytm, settlement_date, &self.fixings));
+                (ytm, yield_to_worst(&workouts), duration)
+            }
+            None => (None, None, None),
+        };
+
+        let quoted = |price: Option<Decimal>| price.map(|price| self.to_quoted_price(symbol, price));
+        Ok(MarketData {
+            symbol: symbol.to_string(),
+            bid_price: quoted(bid),
+            ask_price: quoted(ask),
+            last_price: quoted(last_price),
+            volume,
+            timestamp: now,
+            yield_to_maturity,
+            yield_to_worst,
+            duration,
+            accrued_interest: Some(accrued::accrued_interest(&bond, settlement_date, &self.fixings)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_yield_to_maturity_round_trips_price() {
+        let bond = Bond {
+            isin: "IN0020230085".to_string(),
+            symbol: "GS2033".to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2033, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.18),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        let fixings = FixingStore::new();
+
+        // On a coupon date a bond priced at par yields its coupon
+        let coupon_day = NaiveDate::from_ymd_opt(2023, 12, 15).unwrap();
+        assert_eq!(yield_to_maturity(&bond, dec!(100), coupon_day, &fixings), Some(dec!(7.18)));
+
+        let settlement = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let ytm = yield_to_maturity(&bond, dec!(98.25), settlement, &fixings).unwrap();
+        assert!(ytm > dec!(7.18));
+        let dirty = dirty_price_at_yield(&bond, ytm.to_f64().unwrap() / 100.0, settlement, &fixings).unwrap();
+        let clean = dirty - accrued::accrued_interest(&bond, settlement, &fixings).to_f64().unwrap();
+        assert!((clean - 98.25).abs() < 1e-4);
+
+        // A STRIPS maturing in a year compounds twice: 100 / 93 = (1 + y/2)^2
+        let strip = Bond {
+            coupon_rate: Decimal::ZERO,
+            coupon_frequency: 0,
+            day_count: DayCount::ThirtyE360,
+            maturity_date: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+            ..bond.clone()
+        };
+        let settlement = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let ytm = yield_to_maturity(&strip, dec!(93), settlement, &fixings).unwrap();
+        let expected = 200.0 * ((100.0f64 / 93.0).sqrt() - 1.0);
+        assert!((ytm.to_f64().unwrap() - expected).abs() < 1e-4);
+        assert!(yield_to_maturity(&strip, dec!(250), settlement, &fixings).is_none());
+
+        // A T-bill yields simple interest on the days to maturity, whatever
+        // coupon fields it carries
+        let bill = Bond {
+            bond_type: BondType::TreasuryBill,
+            day_count: DayCount::Actual365Fixed,
+            maturity_date: Utc.with_ymd_and_hms(2024, 5, 31, 0, 0, 0).unwrap(),
+            ..bond
+        };
+        assert_eq!(accrued::accrued_interest(&bill, settlement, &fixings), Decimal::ZERO);
+        let ytm = yield_to_maturity(&bill, dec!(98.30), settlement, &fixings).unwrap();
+        assert_eq!(ytm, dec!(6.936604));
+        let dirty = dirty_price_at_yield(&bill, ytm.to_f64().unwrap() / 100.0, settlement, &fixings).unwrap();
+        assert!((dirty - 98.30).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_premium_callable_yields_worst_to_call() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let callable = Bond {
+            isin: "INE002A08534".to_string(),
+            symbol: "RIL2030".to_string(),
+            issuer: "Reliance Industries".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2030, 9, 30, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(8.50),
+            face_value: dec!(100),
+            bond_type: BondType::CorporateBond,
+            rating: Some("AAA".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 1,
+            day_count: DayCount::ActualActualIcma,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: vec![
+                RedemptionOption { date: date(2026, 9, 30), price: dec!(101) },
+                // Between coupon dates: redemption also pays accrued interest
+                RedemptionOption { date: date(2028, 3, 31), price: dec!(100) },
+            ],
+            put_schedule: vec![RedemptionOption { date: date(2027, 9, 30), price: dec!(100) }],
+        };
+        let fixings = FixingStore::new();
+        let settlement = date(2024, 9, 30);
+
+        let workouts = workout_yields(&callable, dec!(104), settlement, &fixings);
+        let kinds: Vec<WorkoutKind> = workouts.iter().map(|workout| workout.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![WorkoutKind::Call, WorkoutKind::Put, WorkoutKind::Call, WorkoutKind::Maturity]
+        );
+        let yield_at = |index: usize| workouts[index].yield_pct.unwrap();
+        // Above par, the earliest call is worst
+        assert!(yield_at(0) < yield_at(3));
+        assert_eq!(yield_to_worst(&workouts), Some(yield_at(0)));
+
+        // At par on a coupon date with a par call, yield to that call is the coupon
+        let at_par = yield_to(&callable, dec!(100), settlement, date(2027, 9, 30), dec!(100), &fixings);
+        assert_eq!(at_par, Some(dec!(8.5)));
+
+        // Five years of annual coupons at 8.5%
+        let duration = modified_duration(&callable, yield_at(3), settlement, &fixings).unwrap();
+        assert!(duration > dec!(3) && duration < dec!(6));
+    }
+}
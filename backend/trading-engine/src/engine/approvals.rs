@@ -0,0 +1,349 @@
+//! Four-eyes approval for accounts that require it. Their orders are
+//! staged in `PendingApproval` instead of going to the book, and only go
+//! live once one of the account's approvers, other than the person who
+//! entered the order, confirms them. Staged orders nobody decides on
+//! expire.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalPolicy {
+    pub account_id: Uuid,
+    /// Users who may approve the account's orders.
+    pub approvers: Vec<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApprovalPolicyRequest {
+    pub approvers: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Expired,
+    /// Cancelled by its owner before anyone decided.
+    Withdrawn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StagedOrder {
+    pub order: Order,
+    pub status: ApprovalStatus,
+    pub staged_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApprovalDecision {
+    pub approver_id: Uuid,
+    pub approve: bool,
+    pub note: Option<String>,
+}
+
+#[derive(Default)]
+pub struct ApprovalQueue {
+    policies: DashMap<Uuid, ApprovalPolicy>,
+    staged: DashMap<Uuid, StagedOrder>,
+}
+
+impl ApprovalQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(&self, account_id: Uuid) -> Option<ApprovalPolicy> {
+        self.policies.get(&account_id).map(|policy| policy.clone())
+    }
+
+    pub fn policies(&self) -> Vec<ApprovalPolicy> {
+        let mut policies: Vec<ApprovalPolicy> = self.policies.iter().map(|policy| policy.clone()).collect();
+        policies.sort_by_key(|policy| policy.account_id);
+        policies
+    }
+
+    pub fn remove_policy(&self, account_id: Uuid) -> Option<ApprovalPolicy> {
+        self.policies.remove(&account_id).map(|(_, policy)| policy)
+    }
+
+    pub(crate) fn requires_approval(&self, account_id: Uuid) -> bool {
+        self.policies.contains_key(&account_id)
+    }
+
+    pub fn get(&self, order_id: Uuid) -> Option<StagedOrder> {
+        self.staged.get(&order_id).map(|staged| staged.clone())
+    }
+
+    /// Orders awaiting a decision, oldest first. With `approver_id`, only
+    /// those that user may decide.
+    pub fn pending(&self, account_id: Option<Uuid>, approver_id: Option<Uuid>) -> Vec<StagedOrder> {
+        let mut pending: Vec<StagedOrder> = self
+            .staged
+            .iter()
+            .filter(|staged| staged.status == ApprovalStatus::Pending)
+            .filter(|staged| account_id.is_none_or(|id| staged.order.account_id == id))
+            .filter(|staged| approver_id.is_none_or(|id| self.authorize(&staged.order, id).is_ok()))
+            .map(|staged| staged.clone())
+            .collect();
+        pending.sort_by_key(|staged| staged.staged_at);
+        pending
+    }
+
+    fn authorize(&self, order: &Order, approver_id: Uuid) -> Result<()> {
+        if approver_id == order.user_id {
+            return Err(TradingError::PermissionDenied(
+                "An order cannot be approved by the user who entered it".to_string(),
+            ));
+        }
+        let allowed = self
+            .policies
+            .get(&order.account_id)
+            .is_some_and(|policy| policy.approvers.contains(&approver_id));
+        if !allowed {
+            return Err(TradingError::PermissionDenied(format!(
+                "User {} may not approve orders for account {}",
+                approver_id, order.account_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Moves a pending order to `status`. `None` if it was already decided.
+    fn decide(
+        &self,
+        order_id: Uuid,
+        status: ApprovalStatus,
+        decided_by: Option<Uuid>,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Option<StagedOrder> {
+        let mut staged = self.staged.get_mut(&order_id)?;
+        if staged.status != ApprovalStatus::Pending {
+            return None;
+        }
+        staged.status = status;
+        staged.decided_by = decided_by;
+        staged.decided_at = Some(now);
+        staged.note = note;
+        Some(staged.clone())
+    }
+}
+
+impl TradingEngine {
+    pub fn approvals(&self) -> &ApprovalQueue {
+        &self.approvals
+    }
+
+    pub fn set_approval_policy(&self, account_id: Uuid, request: ApprovalPolicyRequest) -> Result<ApprovalPolicy> {
+        if request.approvers.is_empty() {
+            return Err(TradingError::InvalidRequest("An approval policy needs at least one approver".to_string()));
+        }
+        let policy = ApprovalPolicy {
+            account_id,
+            approvers: request.approvers,
+            updated_at: self.time_provider.now(),
+        };
+        self.approvals.policies.insert(account_id, policy.clone());
+        Ok(policy)
+    }
+
+    /// Holds an order for approval. It is validated now so malformed orders
+    /// fail straight away; risk checks wait until it is released.
+    pub(crate) async fn stage_order(&self, mut order: Order, timestamp: DateTime<Utc>) -> Result<Uuid> {
+        if let Err(e) = self.validate_order(&order).await {
+            self.reject_new_order(order, &e, timestamp);
+            return Err(e);
+        }
+        order.timestamp = timestamp;
+        order.remaining_quantity = order.quantity;
+        order.status = OrderStatus::PendingApproval;
+        let staged = StagedOrder {
+            order: order.clone(),
+            status: ApprovalStatus::Pending,
+            staged_at: timestamp,
+            expires_at: timestamp + Duration::milliseconds(self.config.order_approval_timeout_ms as i64),
+            decided_by: None,
+            decided_at: None,
+            note: None,
+        };
+        info!("Order {} staged for approval", order.id);
+        self.orders.insert(order.id, order.clone());
+        self.approvals.staged.insert(order.id, staged.clone());
+        self.event_journal
+            .publish(EngineEvent::OrderApprovalUpdated(staged), vec![order.account_id]);
+        Ok(order.id)
+    }
+
+    /// An approver's decision on a staged order. Approved orders go through
+    /// the full order checks and onto the book as of now.
+    pub async fn decide_staged_order(&self, order_id: Uuid, decision: ApprovalDecision) -> Result<StagedOrder> {
+        let staged = self
+            .approvals
+            .get(order_id)
+            .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+        self.approvals.authorize(&staged.order, decision.approver_id)?;
+        let now = self.time_provider.now();
+        if staged.status == ApprovalStatus::Pending && staged.expires_at <= now {
+            self.expire_staged_order(order_id, now);
+            return Err(TradingError::InvalidRequest("The order's approval window has closed".to_string()));
+        }
+
+        let status = match decision.approve {
+            true => ApprovalStatus::Approved,
+            false => ApprovalStatus::Rejected,
+        };
+        let staged = self
+            .approvals
+            .decide(order_id, status, Some(decision.approver_id), decision.note, now)
+            .ok_or_else(|| TradingError::InvalidRequest("The order has already been decided".to_string()))?;
+        self.event_journal.publish(
+            EngineEvent::OrderApprovalUpdated(staged.clone()),
+            vec![staged.order.account_id],
+        );
+
+        // Out of the live map so the release or rejection below records the
+        // order afresh rather than finding it staged
+        self.orders.remove(&order_id);
+        let mut order = staged.order.clone();
+        order.status = OrderStatus::Pending;
+        if decision.approve {
+            info!("Order {} approved by {}", order_id, decision.approver_id);
+            if let Err(e) = self.enter_order_at(order, now).await {
+                warn!("Approved order {} failed its checks: {}", order_id, e);
+            }
+        } else {
+            let reason = staged.note.clone().unwrap_or_else(|| "no reason given".to_string());
+            let error = TradingError::PermissionDenied(format!("Declined by approver: {}", reason));
+            self.reject_new_order(order, &error, now);
+        }
+        Ok(staged)
+    }
+
+    /// Expires staged orders past their approval window. Returns how many.
+    pub fn expire_staged_orders(&self) -> usize {
+        let now = self.time_provider.now();
+        let due: Vec<Uuid> = self
+            .approvals
+            .staged
+            .iter()
+            .filter(|staged| staged.status == ApprovalStatus::Pending && staged.expires_at <= now)
+            .map(|staged| staged.order.id)
+            .collect();
+        due.into_iter()
+            .filter(|order_id| self.expire_staged_order(*order_id, now))
+            .count()
+    }
+
+    fn expire_staged_order(&self, order_id: Uuid, now: DateTime<Utc>) -> bool {
+        let Some(staged) = self.approvals.decide(order_id, ApprovalStatus::Expired, None, None, now) else {
+            return false;
+        };
+        if let Some(mut order) = self.orders.get_mut(&order_id) {
+            order.status = OrderStatus::Expired;
+            order.cancel_reason = Some(CancelReason::Expired);
+            order.remaining_quantity = Decimal::ZERO;
+        }
+        let account_id = staged.order.account_id;
+        self.event_journal
+            .publish(EngineEvent::OrderApprovalUpdated(staged), vec![account_id]);
+        self.event_journal
+            .publish(EngineEvent::OrderExpired { order_id, expiry: now }, vec![account_id]);
+        true
+    }
+
+    /// Withdraws a staged order cancelled for `cancel_reason`. Returns false
+    /// if it was no longer pending.
+    pub(crate) fn withdraw_staged_order(&self, order_id: Uuid, cancel_reason: CancelReason) -> bool {
+        let now = self.time_provider.now();
+        let Some(staged) = self.approvals.decide(order_id, ApprovalStatus::Withdrawn, None, None, now) else {
+            return false;
+        };
+        if let Some(mut order) = self.orders.get_mut(&order_id) {
+            order.status = OrderStatus::Cancelled;
+            order.cancel_reason = Some(cancel_reason);
+        }
+        let account_id = staged.order.account_id;
+        self.event_journal
+            .publish(EngineEvent::OrderApprovalUpdated(staged), vec![account_id]);
+        self.event_journal
+            .publish(EngineEvent::OrderCancelled { order_id, cancel_reason }, vec![account_id]);
+        self.metrics.increment_orders_cancelled();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::OrderBuilder,
+        utils::time::{SimulatedClock, TimeProvider},
+    };
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, account_id: Uuid, user_id: Uuid) -> Order {
+        OrderBuilder::new(side).price(dec!(99.2500)).account(account_id).user(user_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_staged_orders_wait_for_a_second_user_or_expire() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let config = Arc::new(Config::default());
+        let engine = TradingEngine::with_time_provider(config, Arc::new(TimeProvider::with_clock(clock.clone())))
+            .await
+            .unwrap();
+        let (account, trader, supervisor) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .set_approval_policy(account, ApprovalPolicyRequest { approvers: vec![trader, supervisor] })
+            .unwrap();
+        let seller = Uuid::new_v4();
+        engine.submit_order(order(OrderSide::Sell, seller, Uuid::new_v4())).await.unwrap();
+
+        let staged_id = engine.submit_order(order(OrderSide::Buy, account, trader)).await.unwrap();
+        assert_eq!(engine.get_order(&staged_id).unwrap().status, OrderStatus::PendingApproval);
+        assert!(engine.get_position(account, "GSEC10Y").await.is_none());
+        assert_eq!(engine.approvals().pending(None, Some(supervisor)).len(), 1);
+        assert!(engine.approvals().pending(None, Some(trader)).is_empty());
+
+        let decision = |approver_id| ApprovalDecision {
+            approver_id,
+            approve: true,
+            note: None,
+        };
+        let own = engine.decide_staged_order(staged_id, decision(trader)).await;
+        assert!(matches!(own, Err(TradingError::PermissionDenied(_))));
+        let approved = engine.decide_staged_order(staged_id, decision(supervisor)).await.unwrap();
+        assert_eq!((approved.status, approved.decided_by), (ApprovalStatus::Approved, Some(supervisor)));
+        assert_eq!(engine.get_order(&staged_id).unwrap().status, OrderStatus::Filled);
+        assert_eq!(engine.get_position(account, "GSEC10Y").await.unwrap().quantity, dec!(100000));
+        assert!(engine.decide_staged_order(staged_id, decision(supervisor)).await.is_err());
+
+        let lapsing = engine.submit_order(order(OrderSide::Buy, account, trader)).await.unwrap();
+        clock.advance(Duration::minutes(14));
+        assert_eq!(engine.expire_staged_orders(), 0);
+        clock.advance(Duration::minutes(2));
+        assert_eq!(engine.expire_staged_orders(), 1);
+        assert_eq!(engine.get_order(&lapsing).unwrap().status, OrderStatus::Expired);
+        assert_eq!(engine.approvals().get(lapsing).unwrap().status, ApprovalStatus::Expired);
+        assert!(engine.approvals().pending(Some(account), None).is_empty());
+    }
+}
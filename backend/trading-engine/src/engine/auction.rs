@@ -0,0 +1,396 @@
+use crate::{
+    engine::{instruments::InstrumentStatus, matching::Unfilled, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{cmp::Ordering, collections::HashMap};
+use tracing::info;
+use uuid::Uuid;
+
+/// Where the auction would uncross if it ended now.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndicativePrice {
+    pub symbol: String,
+    pub price: Option<Decimal>,
+    pub matched_volume: Decimal,
+    pub imbalance_side: Option<OrderSide>,
+    pub imbalance_quantity: Decimal,
+    pub buy_volume: Decimal,
+    pub sell_volume: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A pairing of one buy and one sell order at the uncrossing price.
+#[derive(Debug, Clone)]
+pub struct AuctionFill {
+    pub buy_order_id: Uuid,
+    pub sell_order_id: Uuid,
+    pub quantity: Decimal,
+}
+
+/// Orders collected while a symbol is in its call auction. Nothing here
+/// matches until the auction is uncrossed.
+pub struct AuctionBook {
+    orders: DashMap<String, Vec<Order>>,
+}
+
+impl AuctionBook {
+    pub fn new() -> Self {
+        Self {
+            orders: DashMap::new(),
+        }
+    }
+
+    pub fn add(&self, order: Order) {
+        self.orders.entry(order.symbol.clone()).or_default().push(order);
+    }
+
+    pub fn remove(&self, symbol: &str, order_id: Uuid) -> Option<Order> {
+        let mut orders = self.orders.get_mut(symbol)?;
+        let index = orders.iter().position(|order| order.id == order_id)?;
+        Some(orders.remove(index))
+    }
+
+    pub fn orders(&self, symbol: &str) -> Vec<Order> {
+        self.orders
+            .get(symbol)
+            .map(|orders| orders.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn take(&self, symbol: &str) -> Vec<Order> {
+        self.orders
+            .remove(symbol)
+            .map(|(_, orders)| orders)
+            .unwrap_or_default()
+    }
+
+    /// Equilibrium price: the price that maximises executable volume, then
+    /// minimises the leftover imbalance, then sits closest to the reference
+    /// price, then the lower price.
+    pub fn indicative(
+        &self,
+        symbol: &str,
+        reference_price: Option<Decimal>,
+        timestamp: DateTime<Utc>,
+    ) -> IndicativePrice {
+        let orders = self.orders(symbol);
+        let mut candidates: Vec<Decimal> = orders.iter().filter_map(|order| order.price).collect();
+        candidates.sort();
+        candidates.dedup();
+
+        let best = candidates
+            .into_iter()
+            .map(|price| {
+                let (buy, sell) = volumes_at(&orders, price);
+                (price, buy, sell)
+            })
+            .filter(|(_, buy, sell)| (*buy).min(*sell) > Decimal::ZERO)
+            .min_by(|a, b| compare_candidates(a, b, reference_price));
+
+        let (price, buy_volume, sell_volume) = match best {
+            Some((price, buy, sell)) => (Some(price), buy, sell),
+            None => (None, side_total(&orders, OrderSide::Buy), side_total(&orders, OrderSide::Sell)),
+        };
+        let matched_volume = if price.is_some() {
+            buy_volume.min(sell_volume)
+        } else {
+            Decimal::ZERO
+        };
+        let imbalance_side = match buy_volume.cmp(&sell_volume) {
+            Ordering::Greater => Some(OrderSide::Buy),
+            Ordering::Less => Some(OrderSide::Sell),
+            Ordering::Equal => None,
+        };
+
+        IndicativePrice {
+            symbol: symbol.to_string(),
+            price,
+            matched_volume,
+            imbalance_side,
+            imbalance_quantity: (buy_volume - sell_volume).abs(),
+            buy_volume,
+            sell_volume,
+            timestamp,
+        }
+    }
+
+    /// Pairs executable orders at `price` in price-then-time priority, up
+    /// to `volume`.
+    pub fn uncross(orders: &[Order], price: Decimal, volume: Decimal) -> Vec<AuctionFill> {
+        let mut buys: Vec<(Uuid, Decimal)> = executable(orders, OrderSide::Buy, price);
+        let mut sells: Vec<(Uuid, Decimal)> = executable(orders, OrderSide::Sell, price);
+
+        let mut fills = Vec::new();
+        let mut left = volume;
+        let (mut b, mut s) = (0, 0);
+        while left > Decimal::ZERO && b < buys.len() && s < sells.len() {
+            let quantity = buys[b].1.min(sells[s].1).min(left);
+            fills.push(AuctionFill {
+                buy_order_id: buys[b].0,
+                sell_order_id: sells[s].0,
+                quantity,
+            });
+            buys[b].1 -= quantity;
+            sells[s].1 -= quantity;
+            left -= quantity;
+            if buys[b].1 <= Decimal::ZERO {
+                b += 1;
+            }
+            if sells[s].1 <= Decimal::ZERO {
+                s += 1;
+            }
+        }
+        fills
+    }
+}
+
+impl Default for AuctionBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn crosses(order: &Order, price: Decimal) -> bool {
+    match (order.price, &order.side) {
+        (None, _) => true,
+        (Some(limit), OrderSide::Buy) => limit >= price,
+        (Some(limit), OrderSide::Sell) => limit <= price,
+    }
+}
+
+fn volumes_at(orders: &[Order], price: Decimal) -> (Decimal, Decimal) {
+    orders
+        .iter()
+        .filter(|order| crosses(order, price))
+        .fold((Decimal::ZERO, Decimal::ZERO), |(buy, sell), order| match order.side {
+            OrderSide::Buy => (buy + order.remaining_quantity, sell),
+            OrderSide::Sell => (buy, sell + order.remaining_quantity),
+        })
+}
+
+fn side_total(orders: &[Order], side: OrderSide) -> Decimal {
+    orders
+        .iter()
+        .filter(|order| order.side == side)
+        .map(|order| order.remaining_quantity)
+        .sum()
+}
+
+fn compare_candidates(
+    (a_price, a_buy, a_sell): &(Decimal, Decimal, Decimal),
+    (b_price, b_buy, b_sell): &(Decimal, Decimal, Decimal),
+    reference_price: Option<Decimal>,
+) -> Ordering {
+    let distance = |price: &Decimal| reference_price.map(|reference| (*price - reference).abs());
+
+    b_buy
+        .min(b_sell)
+        .cmp(a_buy.min(a_sell))
+        .then((a_buy - a_sell).abs().cmp(&(b_buy - b_sell).abs()))
+        .then(distance(a_price).cmp(&distance(b_price)))
+        .then(a_price.cmp(b_price))
+}
+
+/// Orders on `side` willing to trade at `price`, best-priced first with
+/// market orders ahead of limits, then by arrival.
+fn executable(orders: &[Order], side: OrderSide, price: Decimal) -> Vec<(Uuid, Decimal)> {
+    let mut eligible: Vec<&Order> = orders
+        .iter()
+        .filter(|order| order.side == side && crosses(order, price))
+        .collect();
+    eligible.sort_by(|a, b| {
+        let by_price = match (a.price, b.price) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a_price), Some(b_price)) => match side {
+                OrderSide::Buy => b_price.cmp(&a_price),
+                OrderSide::Sell => a_price.cmp(&b_price),
+            },
+        };
+        by_price.then(a.timestamp.cmp(&b.timestamp))
+    });
+    eligible
+        .into_iter()
+        .map(|order| (order.id, order.remaining_quantity))
+        .collect()
+}
+
+impl TradingEngine {
+    /// Moves a symbol into its call auction. Orders resting on the
+    /// continuous book are carried into the auction with their arrival
+    /// times intact.
+    pub fn start_auction(&self, symbol: &str) -> crate::types::Result<IndicativePrice> {
+        let instrument = self
+            .instruments
+            .set_status(symbol, InstrumentStatus::Auction, None)?;
+        self.publish_status_change(&instrument);
+
+        let resting = self.matching_engine.close_book(symbol);
+        self.matching_engine.open_book(symbol);
+        for order_id in resting {
+            if let Some(order) = self.get_order(&order_id) {
+                self.auction_book.add(order);
+            }
+        }
+
+        info!("Instrument {} entered auction", symbol);
+        Ok(self.publish_indicative(symbol))
+    }
+
+    /// Ends the auction: executes everything crossable at the equilibrium
+    /// price, returns the symbol to continuous trading and places the
+    /// leftover limit orders on the book. Unfilled market orders are
+    /// cancelled.
+    pub async fn uncross_auction(&self, symbol: &str) -> crate::types::Result<Vec<Trade>> {
+        self.ensure_in_auction(symbol)?;
+        let indicative = self.get_auction_indicative(symbol)?;
+        let mut orders: HashMap<Uuid, Order> = self
+            .auction_book
+            .take(symbol)
+            .into_iter()
+            .map(|order| (order.id, order))
+            .collect();
+
+        let mut trades = Vec::new();
+        if let Some(price) = indicative.price {
+            let snapshot: Vec<Order> = orders.values().cloned().collect();
+            for fill in AuctionBook::uncross(&snapshot, price, indicative.matched_volume) {
+                let (Some(buy), Some(sell)) = (
+                    orders.get(&fill.buy_order_id).cloned(),
+                    orders.get(&fill.sell_order_id).cloned(),
+                ) else {
+                    continue;
+                };
+                // The order that arrived last is the one that completed the
+                // cross, so it is treated as the aggressor.
+                let (aggressor, resting) = if buy.timestamp >= sell.timestamp {
+                    (buy, sell)
+                } else {
+                    (sell, buy)
+                };
+
+                let mut trade = self
+                    .matching_engine
+                    .build_trade(&aggressor, &resting, fill.quantity, price);
+                trade.trade_type = TradeType::Auction;
+                self.matching_engine.publish_trade(&trade, &aggressor, &resting);
+
+                for order_id in [fill.buy_order_id, fill.sell_order_id] {
+                    if let Some(order) = orders.get_mut(&order_id) {
+                        order.remaining_quantity -= fill.quantity;
+                        order.filled_quantity += fill.quantity;
+                        order.fills.record(fill.quantity, price, order.filled_quantity);
+                    }
+                }
+                trades.push(trade);
+            }
+        }
+        self.record_trades(&trades).await?;
+
+        let instrument = self
+            .instruments
+            .set_status(symbol, InstrumentStatus::Active, None)?;
+        self.publish_status_change(&instrument);
+
+        let mut leftovers: Vec<Order> = orders
+            .into_values()
+            .filter(|order| order.remaining_quantity > Decimal::ZERO)
+            .collect();
+        leftovers.sort_by_key(|order| order.timestamp);
+        for order in leftovers {
+            if order.price.is_none() {
+                self.cancel_auction_leftover(&order);
+                continue;
+            }
+            let continuous = self.process_order(order, Unfilled::Rest).await?;
+            self.record_trades(&continuous).await?;
+            trades.extend(continuous);
+        }
+
+        info!(
+            "Auction for {} uncrossed at {:?}, {} trades",
+            symbol,
+            indicative.price,
+            trades.len()
+        );
+        Ok(trades)
+    }
+
+    pub fn get_auction_indicative(&self, symbol: &str) -> crate::types::Result<IndicativePrice> {
+        self.ensure_in_auction(symbol)?;
+        Ok(self
+            .auction_book
+            .indicative(symbol, self.last_trade_price(symbol), self.time_provider.now()))
+    }
+
+    pub(crate) fn in_auction(&self, symbol: &str) -> bool {
+        self.instruments.status(symbol) == Some(InstrumentStatus::Auction)
+    }
+
+    /// Queues an accepted order in the auction book instead of matching it.
+    pub(crate) fn enter_auction(&self, order: &Order) {
+        self.auction_book.add(order.clone());
+        self.publish_indicative(&order.symbol);
+    }
+
+    /// Takes an order out of the auction book, returning whether it was
+    /// there.
+    pub(crate) fn leave_auction(&self, symbol: &str, order_id: Uuid) -> bool {
+        if self.auction_book.remove(symbol, order_id).is_none() {
+            return false;
+        }
+        if self.in_auction(symbol) {
+            self.publish_indicative(symbol);
+        }
+        true
+    }
+
+    fn ensure_in_auction(&self, symbol: &str) -> crate::types::Result<()> {
+        match self.instruments.status(symbol) {
+            Some(InstrumentStatus::Auction) => Ok(()),
+            Some(status) => Err(TradingError::InvalidRequest(format!(
+                "{} is {:?}, not in auction",
+                symbol, status
+            ))),
+            None => Err(TradingError::InstrumentNotFound(symbol.to_string())),
+        }
+    }
+
+    fn publish_indicative(&self, symbol: &str) -> IndicativePrice {
+        let indicative = self
+            .auction_book
+            .indicative(symbol, self.last_trade_price(symbol), self.time_provider.now());
+        self.event_journal
+            .publish(EngineEvent::AuctionIndicative(indicative.clone()), Vec::new());
+        indicative
+    }
+
+    pub(crate) fn last_trade_price(&self, symbol: &str) -> Option<Decimal> {
+        self.trades
+            .read()
+            .iter()
+            .rev()
+            .find(|trade| trade.symbol == symbol)
+            .map(|trade| trade.price)
+    }
+
+    fn cancel_auction_leftover(&self, order: &Order) {
+        if let Some(mut stored) = self.orders.get_mut(&order.id) {
+            stored.status = OrderStatus::Cancelled;
+            stored.cancel_reason = Some(CancelReason::AuctionUncrossed);
+        }
+        self.event_journal.publish(
+            EngineEvent::OrderCancelled {
+                order_id: order.id,
+                cancel_reason: CancelReason::AuctionUncrossed,
+            },
+            vec![order.account_id],
+        );
+        self.metrics.increment_orders_cancelled();
+    }
+}
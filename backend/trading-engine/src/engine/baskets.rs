@@ -0,0 +1,281 @@
+//! Basket orders: a list of orders across symbols entered as one unit and
+//! tracked under one basket ID. An all-or-nothing basket has every leg
+//! pre-checked first and is turned away whole if any leg fails.
+
+use crate::{
+    engine::{feature_flags::FeatureFlag, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::info;
+use uuid::Uuid;
+
+/// Order metadata key the basket ID is recorded under on each leg.
+pub const BASKET_ID_KEY: &str = "basket_id";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum BasketStatus {
+    /// An all-or-nothing basket with a leg that failed its checks. None of
+    /// its legs were entered.
+    Rejected,
+    /// Some leg is still open or awaiting approval.
+    Working,
+    /// No leg is open any more.
+    Done,
+}
+
+#[derive(Debug, Clone)]
+struct BasketLeg {
+    order_id: Uuid,
+    symbol: String,
+    side: OrderSide,
+    quantity: Decimal,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Basket {
+    id: Uuid,
+    all_or_nothing: bool,
+    created_at: DateTime<Utc>,
+    rejected: bool,
+    legs: Vec<BasketLeg>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BasketLegView {
+    pub order_id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub filled_quantity: Decimal,
+    /// None for a leg that was never entered.
+    pub status: Option<OrderStatus>,
+    pub error: Option<String>,
+}
+
+/// A basket's aggregate execution progress across its legs.
+#[derive(Debug, Clone, Serialize)]
+pub struct BasketView {
+    pub id: Uuid,
+    pub all_or_nothing: bool,
+    pub created_at: DateTime<Utc>,
+    pub status: BasketStatus,
+    pub total_quantity: Decimal,
+    pub filled_quantity: Decimal,
+    /// Filled quantity as a fraction of the total.
+    pub fill_ratio: Decimal,
+    pub open_legs: usize,
+    pub legs: Vec<BasketLegView>,
+}
+
+#[derive(Default)]
+pub struct BasketBook {
+    baskets: DashMap<Uuid, Basket>,
+}
+
+impl BasketBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TradingEngine {
+    /// Enters `orders` as one basket. Without all-or-nothing each leg is
+    /// submitted on its own and a rejected leg leaves the rest working.
+    /// With it, every leg is checked against the account as it stands
+    /// before any is submitted.
+    pub async fn submit_basket(&self, orders: Vec<Order>, all_or_nothing: bool) -> Result<BasketView> {
+        if orders.is_empty() {
+            return Err(TradingError::InvalidRequest("A basket needs at least one order".to_string()));
+        }
+        let id = Uuid::new_v4();
+        let mut legs: Vec<BasketLeg> = orders
+            .iter()
+            .map(|order| BasketLeg {
+                order_id: order.id,
+                symbol: order.symbol.clone(),
+                side: order.side.clone(),
+                quantity: order.quantity,
+                error: None,
+            })
+            .collect();
+
+        let mut rejected = false;
+        if all_or_nothing {
+            for (leg, order) in legs.iter_mut().zip(&orders) {
+                if let Err(e) = self.pre_check_basket_leg(order).await {
+                    leg.error = Some(e.to_string());
+                    rejected = true;
+                }
+            }
+        }
+
+        if rejected {
+            info!("Basket {} rejected: a leg failed its pre-trade checks", id);
+        } else {
+            for (leg, mut order) in legs.iter_mut().zip(orders) {
+                order.metadata.insert(BASKET_ID_KEY.to_string(), id.to_string());
+                if let Err(e) = self.submit_order(order).await {
+                    leg.error = Some(e.to_string());
+                }
+            }
+        }
+
+        let basket = Basket {
+            id,
+            all_or_nothing,
+            created_at: self.time_provider.now(),
+            rejected,
+            legs,
+        };
+        let view = self.basket_view(&basket);
+        self.baskets.baskets.insert(id, basket);
+        Ok(view)
+    }
+
+    pub fn basket(&self, id: Uuid) -> Result<BasketView> {
+        let basket = self
+            .baskets
+            .baskets
+            .get(&id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown basket {}", id)))?;
+        Ok(self.basket_view(&basket))
+    }
+
+    /// The checks an order meets on entry, without recording or publishing
+    /// anything.
+    async fn pre_check_basket_leg(&self, order: &Order) -> Result<()> {
+        self.validate_order(order).await?;
+        self.risk_manager.check_order(order).await?;
+        if self.feature_enabled(FeatureFlag::GroupRiskLimits, order) {
+            self.check_group_limits(order).await?;
+        }
+        self.check_tenor_limits(order).await
+    }
+
+    fn basket_view(&self, basket: &Basket) -> BasketView {
+        let legs: Vec<BasketLegView> = basket
+            .legs
+            .iter()
+            .map(|leg| {
+                let order = (!basket.rejected).then(|| self.get_order(&leg.order_id)).flatten();
+                BasketLegView {
+                    order_id: leg.order_id,
+                    symbol: leg.symbol.clone(),
+                    side: leg.side.clone(),
+                    quantity: leg.quantity,
+                    filled_quantity: order.as_ref().map_or(Decimal::ZERO, |order| order.filled_quantity),
+                    status: order.map(|order| order.status),
+                    error: leg.error.clone(),
+                }
+            })
+            .collect();
+
+        let total_quantity: Decimal = legs.iter().map(|leg| leg.quantity).sum();
+        let filled_quantity: Decimal = legs.iter().map(|leg| leg.filled_quantity).sum();
+        let open_legs = legs
+            .iter()
+            .filter(|leg| {
+                matches!(
+                    leg.status,
+                    Some(OrderStatus::Pending | OrderStatus::PartiallyFilled | OrderStatus::PendingApproval)
+                )
+            })
+            .count();
+        let status = if basket.rejected {
+            BasketStatus::Rejected
+        } else if open_legs > 0 {
+            BasketStatus::Working
+        } else {
+            BasketStatus::Done
+        };
+        BasketView {
+            id: basket.id,
+            all_or_nothing: basket.all_or_nothing,
+            created_at: basket.created_at,
+            status,
+            total_quantity,
+            filled_quantity,
+            fill_ratio: if total_quantity > Decimal::ZERO {
+                filled_quantity / total_quantity
+            } else {
+                Decimal::ZERO
+            },
+            open_legs,
+            legs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(symbol: &str, side: OrderSide, quantity: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side)
+            .symbol(symbol)
+            .quantity(quantity)
+            .price(dec!(99.00))
+            .account(account_id)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_all_or_nothing_basket_is_rejected_whole() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_position_size = dec!(500000);
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let legs = vec![
+            order("GSEC10Y", OrderSide::Buy, dec!(100000), account),
+            order("GSEC5Y", OrderSide::Buy, dec!(900000), account),
+        ];
+        let rejected = engine.submit_basket(legs.clone(), true).await.unwrap();
+        assert_eq!(rejected.status, BasketStatus::Rejected);
+        assert!(rejected.legs[0].error.is_none() && rejected.legs[1].error.is_some());
+        assert!(legs.iter().all(|leg| engine.get_order(&leg.id).is_none()));
+
+        // Without all-or-nothing the passing leg works on its own
+        let basket = engine.submit_basket(legs, false).await.unwrap();
+        assert_eq!((basket.status, basket.open_legs), (BasketStatus::Working, 1));
+        assert!(basket.legs[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_basket_progress_follows_leg_fills() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        engine
+            .submit_order(order("GSEC10Y", OrderSide::Sell, dec!(50000), Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        let basket = engine
+            .submit_basket(
+                vec![
+                    order("GSEC10Y", OrderSide::Buy, dec!(100000), account),
+                    order("GSEC5Y", OrderSide::Buy, dec!(100000), account),
+                ],
+                true,
+            )
+            .await
+            .unwrap();
+        let leg = engine.get_order(&basket.legs[0].order_id).unwrap();
+        assert_eq!(leg.metadata.get(BASKET_ID_KEY), Some(&basket.id.to_string()));
+
+        let progress = engine.basket(basket.id).unwrap();
+        assert_eq!(progress.status, BasketStatus::Working);
+        assert_eq!((progress.filled_quantity, progress.total_quantity), (dec!(50000), dec!(200000)));
+        assert_eq!(progress.fill_ratio, dec!(0.25));
+    }
+}
@@ -0,0 +1,574 @@
+//! Orders priced as a spread to a benchmark rather than as a cash price:
+//! a yield spread in basis points over a benchmark security or over the
+//! benchmark curve at the bond's tenor, or a price offset from the bond's
+//! own settlement price (trade at settlement). They match on spread in a
+//! book of their own, one per bond and benchmark. The cash price each
+//! spread stands for is re-marked from the benchmark as it moves, and a
+//! fill is priced at the benchmark's mark when it executes.
+
+use crate::{
+    engine::{
+        analytics,
+        price_time_book::{depth, PriceTimeBook, RestingOrder},
+        EngineEvent, TradingEngine,
+    },
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Benchmark {
+    /// The yield of another bond, e.g. +35bps over GSEC10Y.
+    Security { symbol: String },
+    /// The benchmark curve's yield at the bond's tenor.
+    Curve,
+    /// The bond's own settlement price; the spread is a price offset.
+    Settlement,
+}
+
+impl Benchmark {
+    fn key(&self) -> &str {
+        match self {
+            Benchmark::Security { symbol } => symbol,
+            Benchmark::Curve => "curve",
+            Benchmark::Settlement => "settlement",
+        }
+    }
+
+    /// A wider yield spread is a lower price; a settlement offset is a
+    /// price already.
+    fn is_yield_spread(&self) -> bool {
+        !matches!(self, Benchmark::Settlement)
+    }
+}
+
+/// What a spread stood for when last marked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BenchmarkMark {
+    /// The benchmark's yield in percent, or the settlement price.
+    pub benchmark_value: Decimal,
+    /// Clean.
+    pub price: Decimal,
+    pub as_of: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkOrder {
+    pub id: Uuid,
+    pub client_order_id: String,
+    pub symbol: String,
+    pub benchmark: Benchmark,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    /// Basis points over a yield benchmark, or a price offset from the
+    /// settlement price.
+    pub spread: Decimal,
+    pub filled_quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    pub status: OrderStatus,
+    pub timestamp: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub mark: Option<BenchmarkMark>,
+    /// Why the order stopped working, once cancelled.
+    #[serde(default)]
+    pub cancel_reason: Option<CancelReason>,
+}
+
+impl BenchmarkOrder {
+    /// Book the order trades in.
+    pub fn key(&self) -> String {
+        benchmark_key(&self.symbol, &self.benchmark)
+    }
+
+    /// Stand-in outright order, used to build trades.
+    fn trade_order(&self, price: Decimal) -> Order {
+        Order {
+            id: self.id,
+            client_order_id: self.client_order_id.clone(),
+            symbol: self.symbol.clone(),
+            side: self.side.clone(),
+            order_type: OrderType::Limit,
+            quantity: self.quantity,
+            price: Some(price),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: self.quantity,
+            status: OrderStatus::Pending,
+            timestamp: self.timestamp,
+            user_id: self.user_id,
+            account_id: self.account_id,
+            time_in_force: TimeInForce::ImmediateOrCancel,
+            metadata: HashMap::new(),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        }
+    }
+}
+
+impl RestingOrder for BenchmarkOrder {
+    type Pricing = BenchmarkMark;
+    /// The bond and its benchmark.
+    type Book = (String, Benchmark);
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn key(&self) -> String {
+        benchmark_key(&self.symbol, &self.benchmark)
+    }
+
+    fn book(&self) -> (String, Benchmark) {
+        (self.symbol.clone(), self.benchmark.clone())
+    }
+
+    fn side(&self) -> &OrderSide {
+        &self.side
+    }
+
+    /// The spread as a price would rank it.
+    fn level(&self) -> Decimal {
+        if self.benchmark.is_yield_spread() {
+            -self.spread
+        } else {
+            self.spread
+        }
+    }
+
+    fn remaining(&self) -> Decimal {
+        self.remaining_quantity
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self.status, OrderStatus::Pending | OrderStatus::PartiallyFilled)
+    }
+
+    fn fill(&mut self, quantity: Decimal) {
+        self.filled_quantity += quantity;
+        self.remaining_quantity -= quantity;
+        self.status = if self.remaining_quantity <= Decimal::ZERO {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+    }
+
+    fn priced(&mut self, mark: &BenchmarkMark) {
+        self.mark = Some(mark.clone());
+    }
+
+    fn cancel(&mut self, reason: CancelReason) {
+        self.status = OrderStatus::Cancelled;
+        self.cancel_reason = Some(reason);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchmarkOrderRequest {
+    pub client_order_id: String,
+    pub symbol: String,
+    pub benchmark: Benchmark,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub spread: Decimal,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkExecution {
+    pub order: BenchmarkOrder,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkBookSummary {
+    pub key: String,
+    pub symbol: String,
+    pub benchmark: Benchmark,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    /// Cash prices the best spreads were last marked at.
+    pub bid_price: Option<Decimal>,
+    pub ask_price: Option<Decimal>,
+    pub bid_quantity: Decimal,
+    pub ask_quantity: Decimal,
+}
+
+/// Resting benchmark orders, one spread-time book per bond and benchmark.
+pub type BenchmarkBook = PriceTimeBook<BenchmarkOrder>;
+
+pub fn benchmark_key(symbol: &str, benchmark: &Benchmark) -> String {
+    format!("{}@{}", symbol, benchmark.key())
+}
+
+impl TradingEngine {
+    /// Accepts a benchmark order. It passes the same checks as an outright
+    /// order at the cash price its spread stands for, trades against
+    /// opposing orders in its book at their spreads, with cash prices taken
+    /// from the benchmark as it stands, and rests any remainder.
+    pub async fn submit_benchmark_order(
+        &self,
+        request: BenchmarkOrderRequest,
+    ) -> crate::types::Result<BenchmarkExecution> {
+        self.await_event_consumers().await?;
+        let mut order = self.build_benchmark_order(request)?;
+        info!("Submitting benchmark order {} on {}", order.id, order.key());
+
+        let price = order.mark.as_ref().map_or(Decimal::ZERO, |mark| mark.price);
+        if let Err(e) = self.check_new_order(&order.trade_order(price)).await {
+            self.reject_benchmark_order(order, &e);
+            return Err(e);
+        }
+
+        let (symbol, benchmark, account_id) = (order.symbol.clone(), order.benchmark.clone(), order.account_id);
+        let mut reserved = HashMap::new();
+        let trades = self.benchmark_book.submit(
+            &mut order,
+            |resting| self.benchmark_mark(&symbol, &benchmark, resting.spread).ok(),
+            |resting, quantity, mark| {
+                self.config.ccp_enabled
+                    || self.credit_lines.reserve(
+                        account_id,
+                        resting.account_id,
+                        notional_value(quantity, mark.price),
+                        &mut reserved,
+                    )
+            },
+            |order, fills| {
+                let mut trades = Vec::new();
+                for fill in fills {
+                    let aggressor = order.trade_order(fill.pricing.price);
+                    let resting = fill.resting.trade_order(fill.pricing.price);
+                    let mut trade = self
+                        .matching_engine
+                        .build_trade(&aggressor, &resting, fill.quantity, fill.pricing.price);
+                    trade.trade_type = TradeType::BenchmarkSpread;
+                    self.matching_engine.publish_trade(&trade, &aggressor, &resting);
+                    trades.push(trade);
+                    self.publish_benchmark_order(&fill.resting);
+                }
+                trades
+            },
+        );
+        self.record_trades(&trades).await?;
+        self.publish_benchmark_order(&order);
+
+        Ok(BenchmarkExecution { order, trades })
+    }
+
+    /// Keeps a benchmark order that failed its checks, with why.
+    fn reject_benchmark_order(&self, mut order: BenchmarkOrder, error: &TradingError) {
+        let reject_reason = error.reject_reason();
+        self.metrics.increment_order_rejects(&reject_reason);
+        order.status = OrderStatus::Rejected(reject_reason);
+        order.remaining_quantity = Decimal::ZERO;
+        self.benchmark_book.record(order.clone());
+        self.publish_benchmark_order(&order);
+    }
+
+    pub fn cancel_benchmark_order(&self, order_id: Uuid) -> crate::types::Result<bool> {
+        if self.benchmark_book.get(&order_id).is_none() {
+            return Err(TradingError::OrderNotFound(order_id.to_string()));
+        }
+        match self.benchmark_book.cancel(order_id, CancelReason::ClientRequest) {
+            Some(order) => {
+                self.publish_benchmark_order(&order);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn get_benchmark_orders(&self, account_id: Option<Uuid>) -> Vec<BenchmarkOrder> {
+        self.benchmark_book
+            .orders(|order| account_id.is_none_or(|id| order.account_id == id))
+    }
+
+    pub fn benchmark_books(&self) -> Vec<BenchmarkBookSummary> {
+        let best = |level: Option<&VecDeque<BenchmarkOrder>>| -> (Option<Decimal>, Option<Decimal>) {
+            let order = level.and_then(|level| level.front());
+            (
+                order.map(|order| order.spread),
+                order.and_then(|order| order.mark.as_ref()).map(|mark| mark.price),
+            )
+        };
+        self.benchmark_book.summaries(|key, book| {
+            let (best_bid, bid_price) = best(book.bids.values().next_back());
+            let (best_ask, ask_price) = best(book.asks.values().next());
+            let (symbol, benchmark) = book.description.clone();
+            BenchmarkBookSummary {
+                key: key.to_string(),
+                symbol,
+                benchmark,
+                best_bid,
+                best_ask,
+                bid_price,
+                ask_price,
+                bid_quantity: depth(&book.bids),
+                ask_quantity: depth(&book.asks),
+            }
+        })
+    }
+
+    /// Re-marks every resting benchmark order from its benchmark, publishing
+    /// those whose cash price moved. Returns how many did.
+    pub fn reprice_benchmark_orders(&self) -> usize {
+        let mut changed = 0;
+        for order in self.benchmark_book.resting(|_| true) {
+            let Ok(mark) = self.benchmark_mark(&order.symbol, &order.benchmark, order.spread) else {
+                continue;
+            };
+            let repriced = self.benchmark_book.update(order.id, |resting| {
+                if resting.mark.as_ref().is_some_and(|current| current.price == mark.price) {
+                    return false;
+                }
+                resting.mark = Some(mark);
+                true
+            });
+            if let Some(order) = repriced {
+                changed += 1;
+                self.publish_benchmark_order(&order);
+            }
+        }
+        changed
+    }
+
+    /// The cash price `spread` over the benchmark stands for now.
+    pub fn benchmark_mark(
+        &self,
+        symbol: &str,
+        benchmark: &Benchmark,
+        spread: Decimal,
+    ) -> crate::types::Result<BenchmarkMark> {
+        let bond = self
+            .instruments
+            .get(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        let settlement = self.settlement_today();
+        let no_mark = || TradingError::InvalidRequest(format!("No mark for {}", benchmark_key(symbol, benchmark)));
+
+        let (benchmark_value, price) = match benchmark {
+            Benchmark::Settlement => {
+                let close = self.reference_prices.previous_close(symbol).ok_or_else(no_mark)?;
+                (close, close + spread)
+            }
+            Benchmark::Security { symbol: benchmark_symbol } => {
+                let benchmark_bond = self
+                    .instruments
+                    .get(benchmark_symbol)
+                    .ok_or_else(|| TradingError::InstrumentNotFound(benchmark_symbol.clone()))?;
+                let price = self.reference_price(benchmark_symbol)?.price;
+                let yield_pct = analytics::yield_to_maturity(&benchmark_bond, price, settlement, &self.fixings)
+                    .ok_or_else(no_mark)?;
+                let spread_price = self.clean_price_at_yield(&bond, yield_pct + spread / Decimal::ONE_HUNDRED, settlement);
+                (yield_pct, spread_price.ok_or_else(no_mark)?)
+            }
+            Benchmark::Curve => {
+                let yield_pct = self.curve_yield(&bond, settlement).ok_or_else(no_mark)?;
+                let spread_price = self.clean_price_at_yield(&bond, yield_pct + spread / Decimal::ONE_HUNDRED, settlement);
+                (yield_pct, spread_price.ok_or_else(no_mark)?)
+            }
+        };
+        let price = price.round_dp(self.matching_engine.tick_scale(symbol).price_decimals);
+        if price <= Decimal::ZERO {
+            return Err(TradingError::InvalidOrder(format!("Spread {} prices {} at {}", spread, symbol, price)));
+        }
+        Ok(BenchmarkMark {
+            benchmark_value: benchmark_value.round_dp(6),
+            price,
+            as_of: self.time_provider.now(),
+        })
+    }
+
+    fn build_benchmark_order(&self, request: BenchmarkOrderRequest) -> crate::types::Result<BenchmarkOrder> {
+        if request.quantity <= Decimal::ZERO {
+            return Err(TradingError::InvalidOrder("Quantity must be positive".to_string()));
+        }
+        self.check_instrument_tradable(&request.symbol)?;
+        if self.in_auction(&request.symbol) {
+            return Err(TradingError::InstrumentNotTradable(format!("{} is in auction", request.symbol)));
+        }
+        if let Benchmark::Security { symbol } = &request.benchmark {
+            if *symbol == request.symbol {
+                return Err(TradingError::InvalidOrder("A bond cannot be its own benchmark".to_string()));
+            }
+        }
+        let mark = self.benchmark_mark(&request.symbol, &request.benchmark, request.spread)?;
+
+        Ok(BenchmarkOrder {
+            id: Uuid::new_v4(),
+            client_order_id: request.client_order_id,
+            symbol: request.symbol,
+            benchmark: request.benchmark,
+            side: request.side,
+            quantity: request.quantity,
+            spread: request.spread,
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: request.quantity,
+            status: OrderStatus::Pending,
+            timestamp: self.time_provider.now(),
+            user_id: request.user_id,
+            account_id: request.account_id,
+            mark: Some(mark),
+            cancel_reason: None,
+        })
+    }
+
+    fn publish_benchmark_order(&self, order: &BenchmarkOrder) {
+        self.event_journal.publish(
+            EngineEvent::BenchmarkOrderUpdated(order.clone()),
+            vec![order.account_id],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        engine::{allocation::MatchingAlgorithm, credit_lines::CreditLineRequest},
+        utils::daycount::DayCount,
+    };
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn bond(symbol: &str, isin: &str, bond_type: BondType, maturity_year: i32) -> Bond {
+        Bond {
+            isin: isin.to_string(),
+            symbol: symbol.to_string(),
+            issuer: "Benchmark Test".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(maturity_year, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.10),
+            face_value: dec!(100),
+            bond_type,
+            rating: Some("AAA".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        }
+    }
+
+    fn request(side: OrderSide, spread: Decimal) -> BenchmarkOrderRequest {
+        BenchmarkOrderRequest {
+            client_order_id: "BMK-1".to_string(),
+            symbol: "CORP5Y".to_string(),
+            benchmark: Benchmark::Security {
+                symbol: "GSEC10Y".to_string(),
+            },
+            side,
+            quantity: dec!(100000),
+            spread,
+            user_id: Uuid::new_v4(),
+            account_id: Uuid::new_v4(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spread_orders_reprice_and_fill_at_the_benchmark_mark() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(bond("GSEC10Y", "IN0020240027", BondType::GovernmentSecurity, 2034), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine
+            .list_instrument(bond("CORP5Y", "INE000A01011", BondType::CorporateBond, 2029), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine.reference_prices().set_external("GSEC10Y", dec!(100.00), Utc::now());
+
+        let bid = engine.submit_benchmark_order(request(OrderSide::Buy, dec!(40))).await.unwrap().order;
+        let marked_at = bid.mark.unwrap().price;
+        // Offered tighter than the bid: a higher price, so no cross
+        let ask = engine.submit_benchmark_order(request(OrderSide::Sell, dec!(35))).await.unwrap();
+        assert!(ask.trades.is_empty());
+
+        // A cheaper benchmark cheapens the bond at the same spread
+        engine.reference_prices().set_external("GSEC10Y", dec!(99.00), Utc::now());
+        assert_eq!(engine.reprice_benchmark_orders(), 2);
+        let book = &engine.benchmark_books()[0];
+        assert_eq!((book.best_bid, book.best_ask), (Some(dec!(40)), Some(dec!(35))));
+        assert!(book.bid_price.unwrap() < marked_at);
+
+        let hit = engine.submit_benchmark_order(request(OrderSide::Sell, dec!(45))).await.unwrap();
+        assert_eq!(hit.trades.len(), 1);
+        let trade = &hit.trades[0];
+        let benchmark = Benchmark::Security {
+            symbol: "GSEC10Y".to_string(),
+        };
+        assert_eq!(trade.price, engine.benchmark_mark("CORP5Y", &benchmark, dec!(40)).unwrap().price);
+        assert_eq!((trade.trade_type.clone(), trade.quantity), (TradeType::BenchmarkSpread, dec!(100000)));
+        assert_eq!(engine.get_benchmark_orders(Some(bid.account_id))[0].status, OrderStatus::Filled);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_benchmark_order_records_why() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(bond("GSEC10Y", "IN0020240027", BondType::GovernmentSecurity, 2034), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine
+            .list_instrument(bond("CORP5Y", "INE000A01011", BondType::CorporateBond, 2029), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine.reference_prices().set_external("GSEC10Y", dec!(100.00), Utc::now());
+
+        let bid = engine.submit_benchmark_order(request(OrderSide::Buy, dec!(40))).await.unwrap().order;
+        assert_eq!(bid.cancel_reason, None);
+        assert!(engine.cancel_benchmark_order(bid.id).unwrap());
+
+        let cancelled = &engine.get_benchmark_orders(Some(bid.account_id))[0];
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(cancelled.cancel_reason, Some(CancelReason::ClientRequest));
+        assert_eq!(engine.benchmark_books()[0].best_bid, None);
+        assert!(!engine.cancel_benchmark_order(bid.id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_orders_pass_order_checks_and_credit_lines() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(bond("GSEC10Y", "IN0020240027", BondType::GovernmentSecurity, 2034), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine
+            .list_instrument(bond("CORP5Y", "INE000A01011", BondType::CorporateBond, 2029), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine.reference_prices().set_external("GSEC10Y", dec!(100.00), Utc::now());
+
+        // Finer than the bond's quantity decimals, like any outright order
+        let odd_lot = BenchmarkOrderRequest {
+            quantity: dec!(100.00001),
+            ..request(OrderSide::Buy, dec!(40))
+        };
+        assert!(engine.submit_benchmark_order(odd_lot).await.is_err());
+        assert!(matches!(engine.get_benchmark_orders(None)[0].status, OrderStatus::Rejected(_)));
+
+        let bid = engine.submit_benchmark_order(request(OrderSide::Buy, dec!(40))).await.unwrap().order;
+        let seller = request(OrderSide::Sell, dec!(40));
+        engine
+            .credit_lines()
+            .set_line(
+                seller.account_id,
+                CreditLineRequest {
+                    counterparty_account_id: bid.account_id,
+                    limit: Decimal::ZERO,
+                },
+            )
+            .unwrap();
+        let blocked = engine.submit_benchmark_order(seller).await.unwrap();
+        assert!(blocked.trades.is_empty());
+        assert_eq!(blocked.order.status, OrderStatus::Pending);
+        assert_eq!(engine.benchmark_books()[0].ask_quantity, dec!(100000));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Ceilings on one symbol's book. Orders that would rest beyond either are
+/// rejected; orders that only take liquidity are always accepted.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BookLimits {
+    pub max_resting_orders: usize,
+    pub max_bytes: usize,
+}
+
+/// Size of one symbol's book. Bytes are an estimate of the heap held by
+/// the resting orders and their price levels.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct BookFootprint {
+    pub resting_orders: usize,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub estimated_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookUsage {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub footprint: BookFootprint,
+    pub limits: BookLimits,
+    /// The larger of the order and memory utilization, as a fraction.
+    pub utilization: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookPurge {
+    pub symbol: String,
+    pub cutoff: DateTime<Utc>,
+    pub cancelled: Vec<Uuid>,
+    pub usage: BookUsage,
+}
+
+/// Per-symbol overrides of the configured book limits.
+#[derive(Default)]
+pub struct BookLimitRegistry {
+    overrides: DashMap<String, BookLimits>,
+}
+
+impl BookLimitRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, symbol: &str, limits: BookLimits) {
+        self.overrides.insert(symbol.to_string(), limits);
+    }
+
+    pub fn clear(&self, symbol: &str) -> bool {
+        self.overrides.remove(symbol).is_some()
+    }
+
+    fn get(&self, symbol: &str) -> Option<BookLimits> {
+        self.overrides.get(symbol).map(|limits| *limits)
+    }
+}
+
+fn utilization(used: usize, limit: usize) -> Decimal {
+    if limit == 0 {
+        return Decimal::ZERO;
+    }
+    (Decimal::from(used as u64) / Decimal::from(limit as u64)).round_dp(4)
+}
+
+impl TradingEngine {
+    pub fn book_limits(&self) -> &BookLimitRegistry {
+        &self.book_limits
+    }
+
+    pub fn book_limits_for(&self, symbol: &str) -> BookLimits {
+        self.book_limits.get(symbol).unwrap_or(BookLimits {
+            max_resting_orders: self.config.max_resting_orders_per_symbol,
+            max_bytes: self.config.max_book_bytes_per_symbol,
+        })
+    }
+
+    pub fn book_usage(&self, symbol: &str) -> BookUsage {
+        let footprint = self.matching_engine.book_footprint(symbol);
+        let limits = self.book_limits_for(symbol);
+        BookUsage {
+            symbol: symbol.to_string(),
+            utilization: utilization(footprint.resting_orders, limits.max_resting_orders)
+                .max(utilization(footprint.estimated_bytes, limits.max_bytes)),
+            footprint,
+            limits,
+        }
+    }
+
+    /// Usage of every book with resting orders, fullest first.
+    pub fn book_usages(&self) -> Vec<BookUsage> {
+        let mut usages: Vec<BookUsage> = self
+            .matching_engine
+            .symbols()
+            .iter()
+            .map(|symbol| self.book_usage(symbol))
+            .filter(|usage| usage.footprint.resting_orders > 0)
+            .collect();
+        usages.sort_by_key(|usage| std::cmp::Reverse(usage.utilization));
+        usages
+    }
+
+    /// Rejects an order that would rest on a book already at a ceiling.
+    /// Marketable limit orders are let through even if a remainder rests.
+    pub(crate) fn check_book_capacity(&self, order: &Order) -> crate::types::Result<()> {
+        if order.price.is_none() {
+            return Ok(());
+        }
+        if matches!(
+            order.time_in_force,
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+        ) {
+            return Ok(());
+        }
+        if self.is_marketable(order) {
+            return Ok(());
+        }
+
+        let usage = self.book_usage(&order.symbol);
+        if usage.footprint.resting_orders >= usage.limits.max_resting_orders {
+            return Err(TradingError::BookCapacityExceeded(format!(
+                "{} has {} resting orders, limit {}",
+                order.symbol, usage.footprint.resting_orders, usage.limits.max_resting_orders
+            )));
+        }
+        if usage.footprint.estimated_bytes >= usage.limits.max_bytes {
+            return Err(TradingError::BookCapacityExceeded(format!(
+                "{} book holds about {} bytes, limit {}",
+                order.symbol, usage.footprint.estimated_bytes, usage.limits.max_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Cancels good-till-cancel orders in `symbol` entered more than
+    /// `max_age` ago, then releases the book's spare capacity.
+    pub async fn purge_stale_orders(&self, symbol: &str, max_age: Duration) -> crate::types::Result<BookPurge> {
+        if max_age < Duration::zero() {
+            return Err(TradingError::InvalidRequest("Age must not be negative".to_string()));
+        }
+        let cutoff = self.time_provider.now() - max_age;
+        let stale: Vec<Uuid> = self
+            .orders
+            .iter()
+            .filter(|entry| {
+                entry.symbol == symbol
+                    && entry.is_open()
+                    && entry.time_in_force == TimeInForce::GoodTillCancel
+                    && entry.timestamp < cutoff
+            })
+            .map(|entry| *entry.key())
+            .collect();
+
+        let mut cancelled = Vec::with_capacity(stale.len());
+        for order_id in stale {
+            if self.cancel_order_unthrottled(order_id, CancelReason::StalePurge).await? {
+                cancelled.push(order_id);
+            }
+        }
+        self.matching_engine.compact(symbol);
+
+        Ok(BookPurge {
+            symbol: symbol.to_string(),
+            cutoff,
+            cancelled,
+            usage: self.book_usage(symbol),
+        })
+    }
+}
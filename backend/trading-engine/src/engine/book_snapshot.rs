@@ -0,0 +1,61 @@
+//! Persisting the order books across restarts. A snapshot records each
+//! resting order with its time priority and the counters new entries and
+//! trades are numbered from, so a restored engine queues and matches
+//! exactly as the one that wrote it would have.
+
+use crate::{
+    engine::{fixed_point::TickScale, TradingEngine},
+    types::*,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestingEntry {
+    pub order: Order,
+    pub priority: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    /// Last priority handed out; the next resting order gets one more.
+    pub next_priority: u64,
+    /// Last trade sequence used, which trade ids are derived from.
+    pub next_trade_sequence: u64,
+    pub tick_scales: HashMap<String, TickScale>,
+    /// Resting orders, lowest priority (earliest) first.
+    pub entries: Vec<RestingEntry>,
+}
+
+impl TradingEngine {
+    pub fn book_snapshot(&self) -> BookSnapshot {
+        self.matching_engine.snapshot()
+    }
+
+    pub async fn save_book_snapshot(&self) -> crate::types::Result<()> {
+        self.state_store.save_book_snapshot(&self.book_snapshot()).await
+    }
+
+    /// Restores the books from the state store, if a snapshot was saved.
+    /// Returns the number of resting orders restored.
+    pub async fn restore_book(&self) -> crate::types::Result<usize> {
+        match self.state_store.load_book_snapshot().await? {
+            Some(snapshot) => self.restore_book_snapshot(snapshot),
+            None => Ok(0),
+        }
+    }
+
+    pub fn restore_book_snapshot(&self, snapshot: BookSnapshot) -> crate::types::Result<usize> {
+        let orders: Vec<Order> = snapshot.entries.iter().map(|entry| entry.order.clone()).collect();
+        self.matching_engine.restore(snapshot)?;
+        for order in &orders {
+            if let Some(expiry) = order.expires_at() {
+                self.expiry_wheel.schedule(order.id, expiry);
+            }
+            self.orders.insert(order.id, order.clone());
+        }
+        info!("Restored {} resting orders with their time priority", orders.len());
+        Ok(orders.len())
+    }
+}
@@ -0,0 +1,116 @@
+//! The trading calendar as the engine serves it: holidays per market and
+//! settlement dates computed on the same calendar trades settle and accrue
+//! on.
+
+use crate::{
+    engine::TradingEngine,
+    types::*,
+    utils::calendar::{parse_cycle, CalendarYear, Holiday},
+};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettlementQuery {
+    /// Today on the engine clock when unset.
+    pub trade_date: Option<NaiveDate>,
+    /// `T+N` or `N`; the configured cycle when unset.
+    pub cycle: Option<String>,
+    /// The settlement market when unset.
+    pub market: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementDate {
+    pub market: String,
+    pub trade_date: NaiveDate,
+    pub cycle_days: u32,
+    pub settlement_date: NaiveDate,
+    /// Holidays of the market between trade and settlement that pushed
+    /// settlement out.
+    pub holidays_skipped: Vec<Holiday>,
+}
+
+impl TradingEngine {
+    /// The market's holidays in `year`, or the current year.
+    pub fn calendar_year(&self, market: &str, year: Option<i32>) -> Result<CalendarYear> {
+        let year = year.unwrap_or_else(|| self.time_provider.today().year());
+        self.time_provider
+            .calendar()
+            .year(market, year)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("No calendar for year {}", year)))
+    }
+
+    pub fn set_holidays(&self, market: &str, holidays: Vec<Holiday>) {
+        self.time_provider.calendar().set_holidays(market, holidays);
+    }
+
+    pub fn settlement_date_for(&self, query: &SettlementQuery) -> Result<SettlementDate> {
+        let calendar = self.time_provider.calendar();
+        let cycle_days = match &query.cycle {
+            Some(cycle) => parse_cycle(cycle)
+                .filter(|days| *days <= 30)
+                .ok_or_else(|| TradingError::InvalidRequest(format!("Invalid settlement cycle {}", cycle)))?,
+            None => self.config.settlement_cycle_days,
+        };
+        let market = query
+            .market
+            .as_deref()
+            .map(str::to_uppercase)
+            .unwrap_or_else(|| calendar.settlement_market());
+        let trade_date = query.trade_date.unwrap_or_else(|| self.time_provider.today());
+        let settlement_date = calendar.settlement_date(&market, trade_date, cycle_days);
+
+        let mut holidays_skipped = Vec::new();
+        for year in trade_date.year()..=settlement_date.year() {
+            if let Some(calendar_year) = calendar.year(&market, year) {
+                holidays_skipped.extend(
+                    calendar_year
+                        .holidays
+                        .into_iter()
+                        .filter(|holiday| holiday.date > trade_date && holiday.date < settlement_date),
+                );
+            }
+        }
+        Ok(SettlementDate {
+            market,
+            trade_date,
+            cycle_days,
+            settlement_date,
+            holidays_skipped,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_engine_settles_on_the_configured_calendar() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let diwali = NaiveDate::from_ymd_opt(2024, 11, 1).unwrap();
+        engine.set_holidays(
+            "IN",
+            vec![Holiday {
+                date: diwali,
+                name: "Diwali".to_string(),
+            }],
+        );
+
+        let thursday = NaiveDate::from_ymd_opt(2024, 10, 31).unwrap();
+        let settlement = engine
+            .settlement_date_for(&SettlementQuery {
+                trade_date: Some(thursday),
+                cycle: Some("T+1".to_string()),
+                market: None,
+            })
+            .unwrap();
+        assert_eq!(settlement.settlement_date, NaiveDate::from_ymd_opt(2024, 11, 4).unwrap());
+        assert_eq!(settlement.holidays_skipped.len(), 1);
+        // Settlement and accrual inside the engine use the same calendar
+        assert_eq!(engine.time_provider.settlement_date(thursday, 1), settlement.settlement_date);
+    }
+}
@@ -0,0 +1,325 @@
+//! Cash balances per account and currency, with daily interest accrual.
+//! Credit interest accrues on positive balances and debit interest on
+//! overdrawn ones, at rates set per currency and account type.
+
+use crate::{
+    engine::{
+        fees::{round_currency, BASIS_POINT},
+        TradingEngine,
+    },
+    types::*,
+    utils::time::TimeProvider,
+};
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+const DAYS_PER_YEAR: Decimal = Decimal::from_parts(365, 0, 0, false, 0);
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CashAccountType {
+    /// Balances may not go below zero.
+    #[default]
+    Cash,
+    /// Balances may be overdrawn, paying debit interest.
+    Margin,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CashEntryKind {
+    Deposit,
+    Withdrawal,
+    /// Interest accrued by the end-of-day job; negative for debit interest.
+    InterestAccrual,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CashEntry {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub currency: String,
+    pub kind: CashEntryKind,
+    pub amount: Decimal,
+    pub posted_on: NaiveDate,
+    pub note: Option<String>,
+}
+
+/// Annual rates in basis points, actual/365.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterestRate {
+    pub currency: String,
+    pub account_type: CashAccountType,
+    /// Paid on positive balances.
+    pub credit_rate_bps: Decimal,
+    /// Charged on overdrawn balances.
+    pub debit_rate_bps: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CashBalance {
+    pub account_id: Uuid,
+    pub currency: String,
+    pub account_type: CashAccountType,
+    /// Cash excluding interest accrued but not yet paid.
+    pub balance: Decimal,
+    pub accrued_interest: Decimal,
+    pub accrued_through: NaiveDate,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CashMovement {
+    pub currency: String,
+    /// Positive to deposit, negative to withdraw.
+    pub amount: Decimal,
+    pub note: Option<String>,
+}
+
+pub struct CashLedger {
+    balances: DashMap<(Uuid, String), CashBalance>,
+    account_types: DashMap<Uuid, CashAccountType>,
+    rates: DashMap<(String, CashAccountType), InterestRate>,
+    entries: RwLock<Vec<CashEntry>>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl CashLedger {
+    pub fn new(time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            balances: DashMap::new(),
+            account_types: DashMap::new(),
+            rates: DashMap::new(),
+            entries: RwLock::new(Vec::new()),
+            time_provider,
+        }
+    }
+
+    pub fn account_type(&self, account_id: Uuid) -> CashAccountType {
+        self.account_types.get(&account_id).map(|kind| *kind).unwrap_or_default()
+    }
+
+    /// Moving an overdrawn account back to a cash account is refused.
+    pub fn set_account_type(&self, account_id: Uuid, account_type: CashAccountType) -> crate::types::Result<()> {
+        if account_type == CashAccountType::Cash
+            && self
+                .balances
+                .iter()
+                .any(|entry| entry.account_id == account_id && entry.balance < Decimal::ZERO)
+        {
+            return Err(TradingError::InvalidRequest(format!(
+                "Account {} is overdrawn and must stay a margin account",
+                account_id
+            )));
+        }
+        self.account_types.insert(account_id, account_type);
+        for mut balance in self.balances.iter_mut().filter(|entry| entry.account_id == account_id) {
+            balance.account_type = account_type;
+        }
+        Ok(())
+    }
+
+    pub fn set_rate(&self, rate: InterestRate) -> crate::types::Result<InterestRate> {
+        if rate.credit_rate_bps < Decimal::ZERO || rate.debit_rate_bps < Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Interest rates cannot be negative".to_string()));
+        }
+        let rate = InterestRate {
+            currency: rate.currency.to_uppercase(),
+            ..rate
+        };
+        self.rates.insert((rate.currency.clone(), rate.account_type), rate.clone());
+        Ok(rate)
+    }
+
+    pub fn rates(&self) -> Vec<InterestRate> {
+        let mut rates: Vec<InterestRate> = self.rates.iter().map(|entry| entry.value().clone()).collect();
+        rates.sort_by(|a, b| a.currency.cmp(&b.currency));
+        rates
+    }
+
+    /// Deposits or withdraws cash. Cash accounts cannot be overdrawn.
+    pub fn post_movement(&self, account_id: Uuid, movement: CashMovement) -> crate::types::Result<CashBalance> {
+        if movement.amount.is_zero() {
+            return Err(TradingError::InvalidRequest("Cash movements must be non-zero".to_string()));
+        }
+        let currency = movement.currency.to_uppercase();
+        let today = self.time_provider.today();
+        let account_type = self.account_type(account_id);
+        let key = (account_id, currency.clone());
+        let current = self.balances.get(&key).map_or(Decimal::ZERO, |balance| balance.balance);
+        if account_type == CashAccountType::Cash && current + movement.amount < Decimal::ZERO {
+            return Err(TradingError::InsufficientBalance {
+                required: -movement.amount,
+                available: current,
+            });
+        }
+        let mut balance = self
+            .balances
+            .entry(key)
+            .or_insert_with(|| CashBalance {
+                account_id,
+                currency: currency.clone(),
+                account_type,
+                balance: Decimal::ZERO,
+                accrued_interest: Decimal::ZERO,
+                accrued_through: today,
+            });
+        // Interest up to today runs on the balance before the movement
+        self.accrue_balance(&mut balance, today);
+        balance.balance += movement.amount;
+
+        let kind = if movement.amount > Decimal::ZERO {
+            CashEntryKind::Deposit
+        } else {
+            CashEntryKind::Withdrawal
+        };
+        self.record(&balance, kind, movement.amount, today, movement.note);
+        Ok(balance.clone())
+    }
+
+    pub fn balances(&self, account_id: Option<Uuid>) -> Vec<CashBalance> {
+        let mut balances: Vec<CashBalance> = self
+            .balances
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == id))
+            .map(|entry| entry.value().clone())
+            .collect();
+        balances.sort_by(|a, b| (a.account_id, &a.currency).cmp(&(b.account_id, &b.currency)));
+        balances
+    }
+
+    /// Oldest first.
+    pub fn entries(&self, account_id: Option<Uuid>) -> Vec<CashEntry> {
+        self.entries
+            .read()
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == id))
+            .cloned()
+            .collect()
+    }
+
+    /// End-of-day job: accrues interest on every balance through today and
+    /// returns the entries posted. Safe to call repeatedly; each day
+    /// accrues once.
+    pub fn accrue_interest(&self) -> Vec<CashEntry> {
+        let today = self.time_provider.today();
+        let mut posted = Vec::new();
+        for mut balance in self.balances.iter_mut() {
+            posted.extend(self.accrue_balance(&mut balance, today));
+        }
+        if !posted.is_empty() {
+            info!("Posted {} cash interest accruals through {}", posted.len(), today);
+        }
+        posted
+    }
+
+    fn accrue_balance(&self, balance: &mut CashBalance, through: NaiveDate) -> Option<CashEntry> {
+        let days = (through - balance.accrued_through).num_days();
+        if days <= 0 {
+            return None;
+        }
+        balance.accrued_through = through;
+        let rate = self.rates.get(&(balance.currency.clone(), balance.account_type))?;
+        let rate_bps = if balance.balance >= Decimal::ZERO {
+            rate.credit_rate_bps
+        } else {
+            rate.debit_rate_bps
+        };
+        let interest = round_currency(balance.balance * rate_bps * BASIS_POINT * Decimal::from(days) / DAYS_PER_YEAR);
+        if interest.is_zero() {
+            return None;
+        }
+        balance.accrued_interest += interest;
+        Some(self.record(
+            balance,
+            CashEntryKind::InterestAccrual,
+            interest,
+            through,
+            Some(format!("{} days at {} bps", days, rate_bps)),
+        ))
+    }
+
+    fn record(
+        &self,
+        balance: &CashBalance,
+        kind: CashEntryKind,
+        amount: Decimal,
+        posted_on: NaiveDate,
+        note: Option<String>,
+    ) -> CashEntry {
+        let entry = CashEntry {
+            id: Uuid::new_v4(),
+            account_id: balance.account_id,
+            currency: balance.currency.clone(),
+            kind,
+            amount,
+            posted_on,
+            note,
+        };
+        self.entries.write().push(entry.clone());
+        entry
+    }
+}
+
+impl TradingEngine {
+    pub fn cash(&self) -> &CashLedger {
+        &self.cash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::time::SimulatedClock;
+    use chrono::{Duration, TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    fn movement(amount: Decimal) -> CashMovement {
+        CashMovement {
+            currency: "inr".to_string(),
+            amount,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_interest_accrues_daily_at_credit_and_debit_rates() {
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 18, 0, 0).unwrap()));
+        let ledger = CashLedger::new(Arc::new(TimeProvider::with_clock(clock.clone())));
+        for account_type in [CashAccountType::Cash, CashAccountType::Margin] {
+            ledger
+                .set_rate(InterestRate {
+                    currency: "INR".to_string(),
+                    account_type,
+                    credit_rate_bps: dec!(365),
+                    debit_rate_bps: dec!(730),
+                })
+                .unwrap();
+        }
+
+        let saver = Uuid::new_v4();
+        let borrower = Uuid::new_v4();
+        ledger.post_movement(saver, movement(dec!(1000000))).unwrap();
+        assert!(matches!(
+            ledger.post_movement(borrower, movement(dec!(-500000))),
+            Err(TradingError::InsufficientBalance { .. })
+        ));
+        ledger.set_account_type(borrower, CashAccountType::Margin).unwrap();
+        ledger.post_movement(borrower, movement(dec!(-500000))).unwrap();
+
+        clock.advance(Duration::days(2));
+        let posted = ledger.accrue_interest();
+        assert_eq!(posted.len(), 2);
+        assert!(ledger.accrue_interest().is_empty());
+
+        let balances = ledger.balances(Some(saver));
+        assert_eq!((balances[0].balance, balances[0].accrued_interest), (dec!(1000000), dec!(200)));
+        assert_eq!(ledger.balances(Some(borrower))[0].accrued_interest, dec!(-200));
+        assert!(ledger.set_account_type(borrower, CashAccountType::Cash).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{config::Config, types::*, utils::time::TimeProvider};
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Arc};
+use uuid::Uuid;
+
+/// One side of a novated trade: the member faces the clearing account
+/// rather than the original counterparty.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClearingLeg {
+    pub trade_id: Uuid,
+    pub member_account_id: Uuid,
+    pub counterparty_account_id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub settlement_date: NaiveDate,
+}
+
+impl ClearingLeg {
+    fn signed_notional(&self) -> Decimal {
+        let notional = notional_value(self.quantity, self.price);
+        match self.side {
+            OrderSide::Buy => notional,
+            OrderSide::Sell => -notional,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberExposure {
+    pub member_account_id: Uuid,
+    /// Net unsettled notional per symbol; positive is a net purchase.
+    pub net_by_symbol: BTreeMap<String, Decimal>,
+    pub exposure: Decimal,
+    pub limit: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DefaultFundContribution {
+    pub member_account_id: Uuid,
+    pub exposure: Decimal,
+    pub contribution: Decimal,
+}
+
+/// Central counterparty: novates matched trades, tracks each member's
+/// unsettled exposure to the clearing account and sizes the default fund.
+pub struct ClearingHouse {
+    config: Arc<Config>,
+    legs: RwLock<Vec<ClearingLeg>>,
+    exposure_limits: DashMap<Uuid, Decimal>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl ClearingHouse {
+    pub fn new(config: Arc<Config>, time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            config,
+            legs: RwLock::new(Vec::new()),
+            exposure_limits: DashMap::new(),
+            time_provider,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.ccp_enabled
+    }
+
+    pub fn clearing_account_id(&self) -> Uuid {
+        self.config.clearing_account_id
+    }
+
+    /// Replaces the bilateral trade with a buy leg and a sell leg, each
+    /// against the clearing account.
+    pub fn novate(&self, trade: &Trade) -> [ClearingLeg; 2] {
+        let clearing = self.clearing_account_id();
+        let leg = |member_account_id, side| ClearingLeg {
+            trade_id: trade.id,
+            member_account_id,
+            counterparty_account_id: clearing,
+            symbol: trade.symbol.clone(),
+            side,
+            quantity: trade.quantity,
+            price: trade.price,
+            settlement_date: trade.settlement_date,
+        };
+        let legs = [
+            leg(trade.buyer_account_id, OrderSide::Buy),
+            leg(trade.seller_account_id, OrderSide::Sell),
+        ];
+        self.legs.write().extend(legs.iter().cloned());
+        legs
+    }
+
+    pub fn legs(&self, member_account_id: Option<Uuid>) -> Vec<ClearingLeg> {
+        self.legs
+            .read()
+            .iter()
+            .filter(|leg| member_account_id.is_none_or(|id| leg.member_account_id == id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn set_exposure_limit(&self, member_account_id: Uuid, limit: Decimal) {
+        self.exposure_limits.insert(member_account_id, limit);
+    }
+
+    pub fn exposure_limit(&self, member_account_id: Uuid) -> Decimal {
+        self.exposure_limits
+            .get(&member_account_id)
+            .map(|limit| *limit)
+            .unwrap_or(self.config.default_member_exposure_limit)
+    }
+
+    /// Exposure is the sum over symbols of the absolute net notional still
+    /// awaiting settlement.
+    pub fn member_exposure(&self, member_account_id: Uuid) -> MemberExposure {
+        let today = self.time_provider.today();
+        let mut net_by_symbol: BTreeMap<String, Decimal> = BTreeMap::new();
+        for leg in self.legs.read().iter() {
+            if leg.member_account_id == member_account_id && leg.settlement_date > today {
+                *net_by_symbol.entry(leg.symbol.clone()).or_default() += leg.signed_notional();
+            }
+        }
+
+        MemberExposure {
+            member_account_id,
+            exposure: net_by_symbol.values().map(|net| net.abs()).sum(),
+            net_by_symbol,
+            limit: self.exposure_limit(member_account_id),
+        }
+    }
+
+    pub fn exposures(&self) -> Vec<MemberExposure> {
+        let mut members: Vec<Uuid> = self.legs.read().iter().map(|leg| leg.member_account_id).collect();
+        members.sort();
+        members.dedup();
+        members
+            .into_iter()
+            .map(|member| self.member_exposure(member))
+            .collect()
+    }
+
+    /// Rejects an order whose full execution at `price` would take the
+    /// member's exposure over its limit. Orders that reduce exposure are
+    /// always allowed.
+    pub fn check_order(&self, order: &Order, price: Decimal) -> crate::types::Result<()> {
+        let current = self.member_exposure(order.account_id);
+        let net = current
+            .net_by_symbol
+            .get(&order.symbol)
+            .copied()
+            .unwrap_or_default();
+        let delta = match order.side {
+            OrderSide::Buy => notional_value(order.quantity, price),
+            OrderSide::Sell => -notional_value(order.quantity, price),
+        };
+        let projected = current.exposure - net.abs() + (net + delta).abs();
+
+        if projected > current.limit && projected > current.exposure {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::ClearingExposure,
+                message: format!(
+                    "Clearing exposure {} would exceed member limit {}",
+                    projected, current.limit
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Splits the configured default fund across members in proportion to
+    /// their exposure, subject to a minimum contribution.
+    pub fn default_fund(&self) -> Vec<DefaultFundContribution> {
+        let exposures = self.exposures();
+        let total: Decimal = exposures.iter().map(|member| member.exposure).sum();
+
+        exposures
+            .into_iter()
+            .map(|member| {
+                let share = if total.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    self.config.default_fund_size * member.exposure / total
+                };
+                DefaultFundContribution {
+                    member_account_id: member.member_account_id,
+                    exposure: member.exposure,
+                    contribution: share
+                        .max(self.config.min_default_fund_contribution)
+                        .round_dp(2),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    utils::{
+        clock_sync::{ClockAlert, ClockReport, ClockStatus, TimeSource},
+        time::TimeProvider,
+    },
+};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+impl TradingEngine {
+    /// The engine clock, for work done on the engine's behalf outside it.
+    pub fn time_provider(&self) -> &Arc<TimeProvider> {
+        &self.time_provider
+    }
+
+    pub fn clock_report(&self) -> ClockReport {
+        self.time_provider.clock_quality().report()
+    }
+
+    /// Measures the engine clock against `source`. A change of grade is
+    /// published for every subscriber, since it qualifies every timestamp
+    /// that follows.
+    pub async fn check_clock(&self, source: &dyn TimeSource) -> Option<ClockAlert> {
+        let name = source.name();
+        let quality = self.time_provider.clock_quality();
+        let alert = match source.sample().await {
+            Ok(sample) => quality.record(&name, sample, self.time_provider.now()),
+            Err(e) => quality.record_failure(&name, e.to_string(), self.time_provider.now()),
+        }?;
+
+        if alert.status == ClockStatus::Synchronized {
+            info!("Clock synchronized to {}: {}", alert.source, alert.reason);
+        } else {
+            warn!("Clock {:?} against {}: {}", alert.status, alert.source, alert.reason);
+        }
+        self.event_journal
+            .publish(EngineEvent::ClockQualityChanged(alert.clone()), Vec::new());
+        Some(alert)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+    utils::logging,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tracing::info;
+use uuid::Uuid;
+
+/// An offsetting transfer: the short account buys from the long one.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionTrade {
+    pub buyer_account_id: Uuid,
+    pub seller_account_id: Uuid,
+    pub quantity: Decimal,
+    /// Set once executed.
+    pub trade_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolCompression {
+    pub symbol: String,
+    /// Reference price the transfers are done at.
+    pub price: Option<Decimal>,
+    pub net_quantity: Decimal,
+    pub gross_before: Decimal,
+    pub gross_after: Decimal,
+    pub trades: Vec<CompressionTrade>,
+    /// Why nothing is proposed for a symbol that has offsetting positions.
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionReport {
+    pub firm_id: String,
+    pub generated_at: DateTime<Utc>,
+    pub accounts: usize,
+    pub symbols: Vec<SymbolCompression>,
+    pub executed: bool,
+}
+
+/// Pairs the largest long with the largest short until one side runs out.
+/// Each account's net moves toward zero and the total net is unchanged.
+fn offsetting_transfers(positions: &[(Uuid, Decimal)]) -> Vec<CompressionTrade> {
+    let mut longs: Vec<(Uuid, Decimal)> = positions.iter().copied().filter(|(_, qty)| *qty > Decimal::ZERO).collect();
+    let mut shorts: Vec<(Uuid, Decimal)> = positions
+        .iter()
+        .filter(|(_, qty)| *qty < Decimal::ZERO)
+        .map(|&(account, qty)| (account, -qty))
+        .collect();
+    // Ties broken by account so proposals are repeatable
+    longs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    shorts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut trades = Vec::new();
+    let (mut l, mut s) = (0, 0);
+    while l < longs.len() && s < shorts.len() {
+        let quantity = longs[l].1.min(shorts[s].1);
+        trades.push(CompressionTrade {
+            buyer_account_id: shorts[s].0,
+            seller_account_id: longs[l].0,
+            quantity,
+            trade_id: None,
+        });
+        longs[l].1 -= quantity;
+        shorts[s].1 -= quantity;
+        if longs[l].1.is_zero() {
+            l += 1;
+        }
+        if shorts[s].1.is_zero() {
+            s += 1;
+        }
+    }
+    trades
+}
+
+impl TradingEngine {
+    /// Proposes internal trades that offset the long and short positions a
+    /// firm's accounts hold in the same instrument, at the reference price.
+    /// With `execute` set the trades are booked, fee-free, as
+    /// `TradeType::Compression`.
+    pub async fn compress_positions(&self, firm_id: &str, execute: bool) -> crate::types::Result<CompressionReport> {
+        let accounts = self.firms.accounts_of(firm_id);
+        if accounts.is_empty() {
+            return Err(TradingError::InvalidRequest(format!("Firm {} has no accounts", firm_id)));
+        }
+
+        let mut by_symbol: BTreeMap<String, Vec<(Uuid, Decimal)>> = BTreeMap::new();
+        for &account_id in &accounts {
+            for position in self.position_manager.get_positions(Some(account_id)).await {
+                if !position.quantity.is_zero() {
+                    by_symbol
+                        .entry(position.symbol)
+                        .or_default()
+                        .push((account_id, position.quantity));
+                }
+            }
+        }
+
+        let mut report = CompressionReport {
+            firm_id: firm_id.to_string(),
+            generated_at: self.time_provider.now(),
+            accounts: accounts.len(),
+            symbols: Vec::new(),
+            executed: execute,
+        };
+        for (symbol, positions) in by_symbol {
+            let net_quantity: Decimal = positions.iter().map(|(_, qty)| *qty).sum();
+            let gross_before: Decimal = positions.iter().map(|(_, qty)| qty.abs()).sum();
+            let mut compression = SymbolCompression {
+                symbol: symbol.clone(),
+                price: None,
+                net_quantity,
+                gross_before,
+                gross_after: gross_before,
+                trades: Vec::new(),
+                skipped_reason: None,
+            };
+            let trades = offsetting_transfers(&positions);
+            if trades.is_empty() {
+                report.symbols.push(compression);
+                continue;
+            }
+            if let Err(e) = self.check_instrument_tradable(&symbol) {
+                compression.skipped_reason = Some(e.to_string());
+                report.symbols.push(compression);
+                continue;
+            }
+            let price = match self.reference_price(&symbol) {
+                Ok(reference) => reference.price,
+                Err(e) => {
+                    compression.skipped_reason = Some(e.to_string());
+                    report.symbols.push(compression);
+                    continue;
+                }
+            };
+
+            let compressed: Decimal = trades.iter().map(|trade| trade.quantity).sum();
+            compression.price = Some(price);
+            compression.gross_after = gross_before - compressed * Decimal::TWO;
+            compression.trades = trades;
+            if execute {
+                self.book_compression(&symbol, price, &mut compression.trades).await?;
+                info!(
+                    "Compressed {} for firm {}: gross {} -> {}",
+                    symbol, firm_id, compression.gross_before, compression.gross_after
+                );
+            }
+            report.symbols.push(compression);
+        }
+        Ok(report)
+    }
+
+    async fn book_compression(&self, symbol: &str, price: Decimal, transfers: &mut [CompressionTrade]) -> crate::types::Result<()> {
+        let now = self.time_provider.now();
+        let mut trades = Vec::with_capacity(transfers.len());
+        for transfer in transfers.iter_mut() {
+            let order = |side: OrderSide, account_id: Uuid| Order {
+                id: Uuid::new_v4(),
+                client_order_id: "COMPRESSION".to_string(),
+                symbol: symbol.to_string(),
+                side,
+                order_type: OrderType::Limit,
+                quantity: transfer.quantity,
+                price: Some(price),
+                filled_quantity: Decimal::ZERO,
+                remaining_quantity: transfer.quantity,
+                status: OrderStatus::Pending,
+                timestamp: now,
+                user_id: Uuid::nil(),
+                account_id,
+                time_in_force: TimeInForce::ImmediateOrCancel,
+                metadata: HashMap::new(),
+                strategy_id: None,
+                fills: OrderFills::default(),
+                cancel_reason: None,
+            };
+            let buy = order(OrderSide::Buy, transfer.buyer_account_id);
+            let sell = order(OrderSide::Sell, transfer.seller_account_id);
+            let mut trade = self.matching_engine.build_trade(&buy, &sell, transfer.quantity, price);
+            trade.trade_type = TradeType::Compression;
+            trade.internalized = true;
+            trade.buyer_fees = FeeBreakdown::default();
+            trade.seller_fees = FeeBreakdown::default();
+            let sequence = self.event_journal.publish(
+                EngineEvent::TradeExecuted(trade.clone()),
+                vec![trade.buyer_account_id, trade.seller_account_id],
+            );
+            logging::audit_trade(&self.config.engine_instance_id, &trade, sequence);
+            transfer.trade_id = Some(trade.id);
+            trades.push(trade);
+        }
+        self.record_trades(&trades).await
+    }
+}
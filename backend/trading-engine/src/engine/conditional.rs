@@ -0,0 +1,425 @@
+//! Conditional orders. The order is held off the book with a trigger on
+//! the symbol's last price, best bid or offer, or yield; the trigger task
+//! re-evaluates a symbol's held orders whenever an event may have moved
+//! those, and releases an order into matching once its condition fires.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerReference {
+    LastPrice,
+    Bid,
+    Ask,
+    Mid,
+    /// Yield to maturity at the last price, in percent.
+    Yield,
+}
+
+/// When a held order is released. "Through" is in the order's favour for
+/// an if-touched order and against it for a stop; on yield references the
+/// direction flips, since yield falls as price rises.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TriggerCondition {
+    /// Fires once the reference reaches `level` from above for a buy, or
+    /// from below for a sell.
+    IfTouched { reference: TriggerReference, level: Decimal },
+    /// Fires once the reference reaches `level` from below for a buy, or
+    /// from above for a sell.
+    Stop { reference: TriggerReference, level: Decimal },
+    /// A stop that trails the best reference value seen since entry by
+    /// `offset`.
+    TrailingStop { reference: TriggerReference, offset: Decimal },
+}
+
+impl TriggerCondition {
+    pub fn reference(&self) -> TriggerReference {
+        match *self {
+            TriggerCondition::IfTouched { reference, .. }
+            | TriggerCondition::Stop { reference, .. }
+            | TriggerCondition::TrailingStop { reference, .. } => reference,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConditionalStatus {
+    Waiting,
+    /// The condition fired and the order was released.
+    Triggered,
+    Cancelled,
+    /// The condition fired but the released order was rejected.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConditionalOrder {
+    pub id: Uuid,
+    /// Released as is when the condition fires.
+    pub order: Order,
+    pub condition: TriggerCondition,
+    pub status: ConditionalStatus,
+    pub created_at: DateTime<Utc>,
+    /// For a trailing stop, the best reference value seen so far.
+    pub watermark: Option<Decimal>,
+    pub triggered_at: Option<DateTime<Utc>>,
+    /// The reference value the condition fired on.
+    pub trigger_value: Option<Decimal>,
+    pub error: Option<String>,
+}
+
+impl ConditionalOrder {
+    /// Feeds the current reference value to the condition, moving a
+    /// trailing stop's watermark. Returns whether the condition fired.
+    fn observe(&mut self, value: Decimal) -> bool {
+        // Compare price-like values: higher is always better for a seller
+        let invert = self.condition.reference() == TriggerReference::Yield;
+        let signed = |value: Decimal| if invert { -value } else { value };
+        let buy = self.order.side == OrderSide::Buy;
+        let current = signed(value);
+
+        match self.condition {
+            TriggerCondition::IfTouched { level, .. } => {
+                if buy {
+                    current <= signed(level)
+                } else {
+                    current >= signed(level)
+                }
+            }
+            TriggerCondition::Stop { level, .. } => {
+                if buy {
+                    current >= signed(level)
+                } else {
+                    current <= signed(level)
+                }
+            }
+            TriggerCondition::TrailingStop { offset, .. } => {
+                let best = match self.watermark.map(signed) {
+                    Some(best) if buy => best.min(current),
+                    Some(best) => best.max(current),
+                    None => current,
+                };
+                self.watermark = Some(signed(best));
+                if buy {
+                    current >= best + offset
+                } else {
+                    current <= best - offset
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ConditionalBook {
+    orders: DashMap<Uuid, ConditionalOrder>,
+}
+
+impl ConditionalBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<ConditionalOrder> {
+        self.orders.get(&id).map(|order| order.clone())
+    }
+
+    /// Newest first, optionally for one account.
+    pub fn list(&self, account_id: Option<Uuid>) -> Vec<ConditionalOrder> {
+        let mut orders: Vec<ConditionalOrder> = self
+            .orders
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.order.account_id == id))
+            .map(|entry| entry.clone())
+            .collect();
+        orders.sort_by_key(|order| std::cmp::Reverse(order.created_at));
+        orders
+    }
+
+    fn waiting_in(&self, symbol: &str) -> Vec<Uuid> {
+        self.orders
+            .iter()
+            .filter(|entry| entry.status == ConditionalStatus::Waiting && entry.order.symbol == symbol)
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    fn waiting_symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self
+            .orders
+            .iter()
+            .filter(|entry| entry.status == ConditionalStatus::Waiting)
+            .map(|entry| entry.order.symbol.clone())
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+}
+
+impl TradingEngine {
+    pub fn conditional_orders(&self) -> &ConditionalBook {
+        &self.conditional_orders
+    }
+
+    /// Holds `order` until `condition` fires. The condition is checked
+    /// straight away, so an already-touched order is released at once.
+    pub async fn submit_conditional_order(
+        &self,
+        order: Order,
+        condition: TriggerCondition,
+    ) -> Result<ConditionalOrder> {
+        if !matches!(order.order_type, OrderType::Market | OrderType::Limit) {
+            return Err(TradingError::InvalidOrderField {
+                field: OrderField::OrderType,
+                message: "Conditional orders release a market or limit order".to_string(),
+            });
+        }
+        let threshold = match condition {
+            TriggerCondition::IfTouched { level, .. } | TriggerCondition::Stop { level, .. } => level,
+            TriggerCondition::TrailingStop { offset, .. } => offset,
+        };
+        if threshold <= Decimal::ZERO {
+            return Err(TradingError::InvalidRequest(
+                "Trigger levels and trailing offsets must be positive".to_string(),
+            ));
+        }
+        if condition.reference() == TriggerReference::Yield && self.instruments.get(&order.symbol).is_none() {
+            return Err(TradingError::InstrumentNotFound(order.symbol.clone()));
+        }
+        self.validate_order(&order).await?;
+
+        let held = ConditionalOrder {
+            id: Uuid::new_v4(),
+            order,
+            condition,
+            status: ConditionalStatus::Waiting,
+            created_at: self.time_provider.now(),
+            watermark: None,
+            triggered_at: None,
+            trigger_value: None,
+            error: None,
+        };
+        let (id, symbol) = (held.id, held.order.symbol.clone());
+        self.conditional_orders.orders.insert(id, held.clone());
+        self.publish_conditional(&held);
+
+        self.evaluate_conditional_orders(&symbol).await;
+        Ok(self.conditional_orders.get(id).unwrap_or(held))
+    }
+
+    pub fn cancel_conditional_order(&self, id: Uuid) -> Result<ConditionalOrder> {
+        let cancelled = {
+            let mut held = self
+                .conditional_orders
+                .orders
+                .get_mut(&id)
+                .ok_or_else(|| TradingError::OrderNotFound(id.to_string()))?;
+            if held.status != ConditionalStatus::Waiting {
+                return Err(TradingError::InvalidRequest(format!(
+                    "Conditional order {} is already {:?}",
+                    id, held.status
+                )));
+            }
+            held.status = ConditionalStatus::Cancelled;
+            held.clone()
+        };
+        self.publish_conditional(&cancelled);
+        Ok(cancelled)
+    }
+
+    fn trigger_reference_value(&self, symbol: &str, reference: TriggerReference) -> Option<Decimal> {
+        match reference {
+            TriggerReference::LastPrice => self.last_trade_price(symbol),
+            TriggerReference::Bid => self.matching_engine.get_best_bid(symbol),
+            TriggerReference::Ask => self.matching_engine.get_best_ask(symbol),
+            TriggerReference::Mid => {
+                let bid = self.matching_engine.get_best_bid(symbol)?;
+                let ask = self.matching_engine.get_best_ask(symbol)?;
+                Some((bid + ask) / Decimal::TWO)
+            }
+            TriggerReference::Yield => {
+                let price = self.last_trade_price(symbol)?;
+                self.bond_analytics(symbol, Some(price)).ok()?.yield_to_maturity
+            }
+        }
+    }
+
+    /// Checks every waiting order in `symbol` against the current market
+    /// and releases those whose condition fired. Returns how many fired.
+    pub async fn evaluate_conditional_orders(&self, symbol: &str) -> usize {
+        let mut references: HashMap<TriggerReference, Option<Decimal>> = HashMap::new();
+        let mut fired = Vec::new();
+        for id in self.conditional_orders.waiting_in(symbol) {
+            let Some(mut held) = self.conditional_orders.orders.get_mut(&id) else {
+                continue;
+            };
+            if held.status != ConditionalStatus::Waiting {
+                continue;
+            }
+            let reference = held.condition.reference();
+            let value = *references
+                .entry(reference)
+                .or_insert_with(|| self.trigger_reference_value(symbol, reference));
+            let Some(value) = value else {
+                continue;
+            };
+            if held.observe(value) {
+                held.status = ConditionalStatus::Triggered;
+                held.triggered_at = Some(self.time_provider.now());
+                held.trigger_value = Some(value);
+                fired.push(held.clone());
+            }
+        }
+
+        let count = fired.len();
+        for mut held in fired {
+            info!(
+                "Conditional order {} fired on {:?} at {}",
+                held.id,
+                held.condition.reference(),
+                held.trigger_value.unwrap_or_default()
+            );
+            if let Err(e) = self.submit_order(held.order.clone()).await {
+                warn!("Conditional order {} was released but rejected: {}", held.id, e);
+                held.status = ConditionalStatus::Failed;
+                held.error = Some(e.to_string());
+                self.conditional_orders.orders.insert(held.id, held.clone());
+            }
+            self.publish_conditional(&held);
+        }
+        count
+    }
+
+    fn publish_conditional(&self, held: &ConditionalOrder) {
+        self.event_journal.publish(
+            EngineEvent::ConditionalOrderUpdated(held.clone()),
+            vec![held.order.account_id],
+        );
+    }
+
+    /// The symbol whose last price or BBO an event may have moved.
+    fn moved_symbol(&self, event: &EngineEvent) -> Option<String> {
+        match event {
+            EngineEvent::TradeExecuted(trade) => Some(trade.symbol.clone()),
+            EngineEvent::OrderSubmitted(order) => Some(order.symbol.clone()),
+            EngineEvent::OrderCancelled { order_id, .. } | EngineEvent::OrderExpired { order_id, .. } => {
+                self.get_order(order_id).map(|order| order.symbol)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Trigger task: re-evaluates a symbol's waiting conditional orders after
+/// each event that may have moved its market. After falling behind the
+/// event stream it re-evaluates every symbol with waiting orders.
+pub async fn run_triggers(engine: Arc<TradingEngine>) {
+    let mut events = engine.subscribe_events();
+    loop {
+        let symbols = match events.recv().await {
+            Ok(event) => engine.moved_symbol(&event.event).into_iter().collect(),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Trigger task skipped {} events, re-evaluating all symbols", skipped);
+                engine.conditional_orders.waiting_symbols()
+            }
+            Err(RecvError::Closed) => return,
+        };
+        for symbol in symbols {
+            engine.evaluate_conditional_orders(&symbol).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::time::Duration;
+
+    fn order(side: OrderSide, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).price(price).account(account_id).build()
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_the_best_value() {
+        let mut held = ConditionalOrder {
+            id: Uuid::new_v4(),
+            order: order(OrderSide::Sell, dec!(98.00), Uuid::new_v4()),
+            condition: TriggerCondition::TrailingStop {
+                reference: TriggerReference::LastPrice,
+                offset: dec!(0.50),
+            },
+            status: ConditionalStatus::Waiting,
+            created_at: Utc::now(),
+            watermark: None,
+            triggered_at: None,
+            trigger_value: None,
+            error: None,
+        };
+        assert!(!held.observe(dec!(99.00)));
+        assert!(!held.observe(dec!(99.60)));
+        assert!(!held.observe(dec!(99.20)));
+        assert_eq!(held.watermark, Some(dec!(99.60)));
+        assert!(held.observe(dec!(99.10)));
+
+        // On yield a seller's stop trails the lowest yield upward
+        held.condition = TriggerCondition::TrailingStop {
+            reference: TriggerReference::Yield,
+            offset: dec!(0.10),
+        };
+        held.watermark = None;
+        assert!(!held.observe(dec!(7.20)));
+        assert!(!held.observe(dec!(7.05)));
+        assert!(held.observe(dec!(7.15)));
+    }
+
+    #[tokio::test]
+    async fn test_if_touched_order_is_released_by_the_trigger_task() {
+        let engine = Arc::new(TradingEngine::new(Arc::new(Config::default())).await.unwrap());
+        tokio::spawn(run_triggers(engine.clone()));
+        // Let the trigger task subscribe before anything is published
+        tokio::task::yield_now().await;
+        engine.submit_order(order(OrderSide::Sell, dec!(100.00), Uuid::new_v4())).await.unwrap();
+
+        let buyer = Uuid::new_v4();
+        let held = engine
+            .submit_conditional_order(
+                order(OrderSide::Buy, dec!(99.50), buyer),
+                TriggerCondition::IfTouched {
+                    reference: TriggerReference::Ask,
+                    level: dec!(99.50),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(held.status, ConditionalStatus::Waiting);
+        assert!(engine.get_order(&held.order.id).is_none());
+
+        // A lower offer touches the level and the released bid lifts it
+        engine.submit_order(order(OrderSide::Sell, dec!(99.40), Uuid::new_v4())).await.unwrap();
+        for _ in 0..100 {
+            if engine.get_order(&held.order.id).is_some_and(|order| order.status == OrderStatus::Filled) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let fired = engine.conditional_orders().get(held.id).unwrap();
+        assert_eq!((fired.status, fired.trigger_value), (ConditionalStatus::Triggered, Some(dec!(99.40))));
+        assert_eq!(engine.get_order(&held.order.id).unwrap().status, OrderStatus::Filled);
+    }
+}
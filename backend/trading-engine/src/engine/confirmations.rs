@@ -0,0 +1,418 @@
+//! Trade confirmation matching. Both parties to a block or dealer-to-client
+//! trade affirm its economics before it may settle. An affirmation that
+//! disagrees with the trade record raises an exception with the fields
+//! that differ, which operations resolve from a queue.
+
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Negotiated trades, agreed off the order book, need affirming.
+fn requires_confirmation(trade_type: &TradeType) -> bool {
+    matches!(trade_type, TradeType::Block | TradeType::DealerToClient)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConfirmationStatus {
+    /// Waiting on one or both parties.
+    Pending,
+    /// Both parties agree with the trade record; clear to settle.
+    Affirmed,
+    /// A party's affirmation disagreed; waiting on operations.
+    Exception,
+}
+
+/// The economics a party affirms, from its own side.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffirmRequest {
+    pub account_id: Uuid,
+    pub side: OrderSide,
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub settlement_date: NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Affirmation {
+    pub affirmed_at: DateTime<Utc>,
+    /// Set when operations confirmed the trade record for the party.
+    pub by_operations: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Confirmation {
+    pub trade_id: Uuid,
+    pub trade_number: String,
+    pub trade_type: TradeType,
+    pub buyer_account_id: Uuid,
+    pub seller_account_id: Uuid,
+    pub status: ConfirmationStatus,
+    pub buyer: Option<Affirmation>,
+    pub seller: Option<Affirmation>,
+    pub created_at: DateTime<Utc>,
+    pub affirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldMismatch {
+    pub field: &'static str,
+    /// As booked.
+    pub trade: serde_json::Value,
+    /// As the party affirmed it.
+    pub affirmed: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExceptionStatus {
+    Open,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionAction {
+    /// The trade record stands; the party is taken as having affirmed it.
+    ConfirmTradeRecord,
+    /// The party has to affirm again.
+    RequireReaffirmation,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExceptionResolution {
+    pub resolved_by: String,
+    pub action: ResolutionAction,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfirmationException {
+    pub id: Uuid,
+    pub trade_id: Uuid,
+    pub account_id: Uuid,
+    pub mismatches: Vec<FieldMismatch>,
+    pub raised_at: DateTime<Utc>,
+    pub status: ExceptionStatus,
+    pub resolved_by: Option<String>,
+    pub action: Option<ResolutionAction>,
+    pub note: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct ConfirmationBook {
+    confirmations: DashMap<Uuid, Confirmation>,
+    exceptions: DashMap<Uuid, ConfirmationException>,
+}
+
+impl ConfirmationBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, trade_id: Uuid) -> Option<Confirmation> {
+        self.confirmations.get(&trade_id).map(|confirmation| confirmation.clone())
+    }
+
+    /// Oldest first, optionally for one party.
+    pub fn list(&self, account_id: Option<Uuid>) -> Vec<Confirmation> {
+        let mut confirmations: Vec<Confirmation> = self
+            .confirmations
+            .iter()
+            .filter(|entry| {
+                account_id.is_none_or(|id| entry.buyer_account_id == id || entry.seller_account_id == id)
+            })
+            .map(|entry| entry.value().clone())
+            .collect();
+        confirmations.sort_by_key(|confirmation| confirmation.created_at);
+        confirmations
+    }
+
+    /// The operations queue: oldest first.
+    pub fn exceptions(&self, status: Option<ExceptionStatus>) -> Vec<ConfirmationException> {
+        let mut exceptions: Vec<ConfirmationException> = self
+            .exceptions
+            .iter()
+            .filter(|entry| status.is_none_or(|status| entry.status == status))
+            .map(|entry| entry.value().clone())
+            .collect();
+        exceptions.sort_by_key(|exception| exception.raised_at);
+        exceptions
+    }
+
+    /// Whether a trade may go on to settlement: either it needs no
+    /// affirming or both parties have affirmed it.
+    pub fn cleared_to_settle(&self, trade_id: Uuid) -> bool {
+        self.confirmations
+            .get(&trade_id)
+            .is_none_or(|confirmation| confirmation.status == ConfirmationStatus::Affirmed)
+    }
+
+    pub(crate) fn record_trade(&self, trade: &Trade, now: DateTime<Utc>) {
+        if !requires_confirmation(&trade.trade_type) {
+            return;
+        }
+        self.confirmations.insert(
+            trade.id,
+            Confirmation {
+                trade_id: trade.id,
+                trade_number: trade.trade_number.clone(),
+                trade_type: trade.trade_type.clone(),
+                buyer_account_id: trade.buyer_account_id,
+                seller_account_id: trade.seller_account_id,
+                status: ConfirmationStatus::Pending,
+                buyer: None,
+                seller: None,
+                created_at: now,
+                affirmed_at: None,
+            },
+        );
+    }
+}
+
+impl Confirmation {
+    fn affirm(&mut self, account_id: Uuid, affirmation: Affirmation) {
+        let at = affirmation.affirmed_at;
+        if account_id == self.buyer_account_id {
+            self.buyer = Some(affirmation.clone());
+        }
+        if account_id == self.seller_account_id {
+            self.seller = Some(affirmation);
+        }
+        self.status = if self.buyer.is_some() && self.seller.is_some() {
+            self.affirmed_at = Some(at);
+            ConfirmationStatus::Affirmed
+        } else {
+            ConfirmationStatus::Pending
+        };
+    }
+}
+
+fn mismatches(trade: &Trade, request: &AffirmRequest) -> Vec<FieldMismatch> {
+    let side = if request.account_id == trade.buyer_account_id {
+        OrderSide::Buy
+    } else {
+        OrderSide::Sell
+    };
+    let mut mismatches = Vec::new();
+    let mut compare = |field, booked: serde_json::Value, affirmed: serde_json::Value| {
+        if booked != affirmed {
+            mismatches.push(FieldMismatch {
+                field,
+                trade: booked,
+                affirmed,
+            });
+        }
+    };
+    compare("side", serde_json::json!(side), serde_json::json!(request.side));
+    compare("symbol", serde_json::json!(trade.symbol), serde_json::json!(request.symbol));
+    compare(
+        "quantity",
+        serde_json::json!(trade.quantity.normalize()),
+        serde_json::json!(request.quantity.normalize()),
+    );
+    compare(
+        "price",
+        serde_json::json!(trade.price.normalize()),
+        serde_json::json!(request.price.normalize()),
+    );
+    compare(
+        "settlement_date",
+        serde_json::json!(trade.settlement_date),
+        serde_json::json!(request.settlement_date),
+    );
+    mismatches
+}
+
+impl TradingEngine {
+    pub fn confirmations(&self) -> &ConfirmationBook {
+        &self.confirmations
+    }
+
+    /// A party's affirmation of a trade. Economics that differ from the
+    /// trade record raise an exception instead of counting.
+    pub fn affirm_trade(&self, trade_id: Uuid, request: AffirmRequest) -> Result<Confirmation> {
+        let trade = self
+            .get_trade(&trade_id.to_string())
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown trade {}", trade_id)))?;
+        let mut confirmation = self
+            .confirmations
+            .confirmations
+            .get_mut(&trade_id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Trade {} needs no affirmation", trade_id)))?;
+        if request.account_id != trade.buyer_account_id && request.account_id != trade.seller_account_id {
+            return Err(TradingError::InvalidRequest(format!(
+                "Account {} is not a party to trade {}",
+                request.account_id, trade_id
+            )));
+        }
+        if confirmation.status == ConfirmationStatus::Affirmed {
+            return Err(TradingError::InvalidRequest(format!("Trade {} is already affirmed", trade_id)));
+        }
+
+        let now = self.time_provider.now();
+        let mismatches = mismatches(&trade, &request);
+        if mismatches.is_empty() {
+            confirmation.affirm(
+                request.account_id,
+                Affirmation {
+                    affirmed_at: now,
+                    by_operations: None,
+                },
+            );
+            if confirmation.status == ConfirmationStatus::Affirmed {
+                info!("Trade {} affirmed by both parties", trade.trade_number);
+            }
+        } else {
+            warn!(
+                "Affirmation of trade {} by {} disagrees on {}",
+                trade.trade_number,
+                request.account_id,
+                mismatches.iter().map(|mismatch| mismatch.field).collect::<Vec<_>>().join(", ")
+            );
+            confirmation.status = ConfirmationStatus::Exception;
+            let exception = ConfirmationException {
+                id: Uuid::new_v4(),
+                trade_id,
+                account_id: request.account_id,
+                mismatches,
+                raised_at: now,
+                status: ExceptionStatus::Open,
+                resolved_by: None,
+                action: None,
+                note: None,
+                resolved_at: None,
+            };
+            self.confirmations.exceptions.insert(exception.id, exception);
+        }
+        Ok(confirmation.clone())
+    }
+
+    /// Closes an exception. The confirmation leaves exception status once
+    /// none of its exceptions is open.
+    pub fn resolve_confirmation_exception(
+        &self,
+        exception_id: Uuid,
+        resolution: ExceptionResolution,
+    ) -> Result<ConfirmationException> {
+        let now = self.time_provider.now();
+        let exception = {
+            let mut exception = self
+                .confirmations
+                .exceptions
+                .get_mut(&exception_id)
+                .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown exception {}", exception_id)))?;
+            if exception.status == ExceptionStatus::Resolved {
+                return Err(TradingError::InvalidRequest(format!(
+                    "Exception {} is already resolved",
+                    exception_id
+                )));
+            }
+            exception.status = ExceptionStatus::Resolved;
+            exception.resolved_by = Some(resolution.resolved_by.clone());
+            exception.action = Some(resolution.action);
+            exception.note = resolution.note;
+            exception.resolved_at = Some(now);
+            exception.clone()
+        };
+
+        let still_open = self
+            .confirmations
+            .exceptions
+            .iter()
+            .any(|entry| entry.trade_id == exception.trade_id && entry.status == ExceptionStatus::Open);
+        if let Some(mut confirmation) = self.confirmations.confirmations.get_mut(&exception.trade_id) {
+            if resolution.action == ResolutionAction::ConfirmTradeRecord {
+                confirmation.affirm(
+                    exception.account_id,
+                    Affirmation {
+                        affirmed_at: now,
+                        by_operations: Some(resolution.resolved_by),
+                    },
+                );
+            } else {
+                confirmation.status = ConfirmationStatus::Pending;
+            }
+            if still_open {
+                confirmation.status = ConfirmationStatus::Exception;
+            }
+        }
+        Ok(exception)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        engine::d2c::{DealerQuoteRequest, HitDecision, HitRequest, QuoteTier},
+    };
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_trade_settles_only_once_both_sides_affirm() {
+        let config = Config {
+            settlement_outbound: "memory".to_string(),
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let (dealer, client) = (Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .stream_dealer_quote(DealerQuoteRequest {
+                dealer_account_id: dealer,
+                symbol: "GSEC10Y".to_string(),
+                segment: "default".to_string(),
+                tiers: vec![QuoteTier { max_quantity: dec!(1000000), bid: dec!(99.10), ask: dec!(99.30) }],
+                valid_for_ms: 60_000,
+            })
+            .unwrap();
+        let hit = engine
+            .hit_dealer_quote(HitRequest {
+                client_order_id: "CONF-1".to_string(),
+                account_id: client,
+                user_id: Uuid::new_v4(),
+                dealer_account_id: dealer,
+                symbol: "GSEC10Y".to_string(),
+                side: OrderSide::Buy,
+                quantity: dec!(1000000),
+                price: dec!(99.30),
+            })
+            .await
+            .unwrap();
+        let decision = HitDecision { dealer_account_id: dealer, accept: true, reason: None };
+        let trade_id = engine.decide_hit(hit.id, decision).await.unwrap().trade_id.unwrap();
+        let trade = engine.get_trade(&trade_id.to_string()).unwrap();
+        assert!(engine.export_settlement_instructions().await.unwrap().is_empty());
+
+        let affirm = |account_id, side, price| AffirmRequest {
+            account_id,
+            side,
+            symbol: "GSEC10Y".to_string(),
+            quantity: dec!(1000000),
+            price,
+            settlement_date: trade.settlement_date,
+        };
+        engine.affirm_trade(trade_id, affirm(client, OrderSide::Buy, dec!(99.30))).unwrap();
+        let disputed = engine.affirm_trade(trade_id, affirm(dealer, OrderSide::Sell, dec!(99.25))).unwrap();
+        assert_eq!(disputed.status, ConfirmationStatus::Exception);
+        let exception = engine.confirmations().exceptions(Some(ExceptionStatus::Open)).remove(0);
+        let fields: Vec<&str> = exception.mismatches.iter().map(|mismatch| mismatch.field).collect();
+        assert_eq!(fields, vec!["price"]);
+
+        let resolution = ExceptionResolution {
+            resolved_by: "ops".to_string(),
+            action: ResolutionAction::ConfirmTradeRecord,
+            note: Some("Dealer confirmed 99.30 by phone".to_string()),
+        };
+        engine.resolve_confirmation_exception(exception.id, resolution).unwrap();
+        assert_eq!(engine.confirmations().get(trade_id).unwrap().status, ConfirmationStatus::Affirmed);
+        assert_eq!(engine.export_settlement_instructions().await.unwrap().len(), 2);
+    }
+}
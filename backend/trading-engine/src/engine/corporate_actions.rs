@@ -0,0 +1,326 @@
+use crate::{
+    engine::{instruments::InstrumentStatus, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CorporateActionKind {
+    RatingChange { rating: Option<String> },
+    /// A step-up or step-down of the coupon, in percent.
+    CouponRevision { coupon_rate: Decimal },
+    /// Redeems `fraction` of every holding at `price` per 100 face. A
+    /// fraction of one retires the bond and delists it.
+    EarlyRedemption { fraction: Decimal, price: Decimal },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CorporateActionStatus {
+    Scheduled,
+    Applied,
+    Failed,
+}
+
+/// Cash owed to, or by a short, an account for redeemed bonds.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedemptionPayment {
+    pub account_id: Uuid,
+    pub quantity: Decimal,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorporateAction {
+    pub id: Uuid,
+    pub symbol: String,
+    #[serde(flatten)]
+    pub kind: CorporateActionKind,
+    pub effective_date: NaiveDate,
+    pub status: CorporateActionStatus,
+    pub created_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub payments: Vec<RedemptionPayment>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorporateActionRequest {
+    #[serde(flatten)]
+    pub kind: CorporateActionKind,
+    pub effective_date: NaiveDate,
+}
+
+/// Every corporate action recorded, scheduled or done.
+#[derive(Default)]
+pub struct CorporateActionLog {
+    actions: DashMap<Uuid, CorporateAction>,
+}
+
+impl CorporateActionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The history of one instrument in effective-date order.
+    pub fn history(&self, symbol: &str) -> Vec<CorporateAction> {
+        let mut actions: Vec<CorporateAction> = self
+            .actions
+            .iter()
+            .filter(|entry| entry.symbol == symbol)
+            .map(|entry| entry.value().clone())
+            .collect();
+        actions.sort_by_key(|action| (action.effective_date, action.created_at));
+        actions
+    }
+
+    /// Actions that have been applied, in the order they took effect.
+    pub fn applied(&self) -> Vec<CorporateAction> {
+        let mut applied: Vec<CorporateAction> = self
+            .actions
+            .iter()
+            .filter(|entry| entry.status == CorporateActionStatus::Applied)
+            .map(|entry| entry.value().clone())
+            .collect();
+        applied.sort_by_key(|action| action.applied_at);
+        applied
+    }
+
+    fn due(&self, today: NaiveDate) -> Vec<CorporateAction> {
+        let mut due: Vec<CorporateAction> = self
+            .actions
+            .iter()
+            .filter(|entry| entry.status == CorporateActionStatus::Scheduled && entry.effective_date <= today)
+            .map(|entry| entry.value().clone())
+            .collect();
+        due.sort_by_key(|action| (action.effective_date, action.created_at));
+        due
+    }
+}
+
+fn validate(kind: &CorporateActionKind) -> crate::types::Result<()> {
+    match kind {
+        CorporateActionKind::CouponRevision { coupon_rate } if *coupon_rate < Decimal::ZERO => Err(
+            TradingError::InvalidRequest("Coupon rate cannot be negative".to_string()),
+        ),
+        CorporateActionKind::EarlyRedemption { fraction, price }
+            if *fraction <= Decimal::ZERO || *fraction > Decimal::ONE || *price <= Decimal::ZERO =>
+        {
+            Err(TradingError::InvalidRequest(
+                "Redemption needs a fraction in (0, 1] and a positive price".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl TradingEngine {
+    pub fn corporate_actions(&self) -> &CorporateActionLog {
+        &self.corporate_actions
+    }
+
+    /// Records an action for a listed instrument. One already effective is
+    /// applied straight away.
+    pub async fn schedule_corporate_action(
+        &self,
+        symbol: &str,
+        request: CorporateActionRequest,
+    ) -> crate::types::Result<CorporateAction> {
+        match self.instruments.status(symbol) {
+            None => return Err(TradingError::InstrumentNotFound(symbol.to_string())),
+            Some(InstrumentStatus::Delisted) => {
+                return Err(TradingError::InstrumentNotTradable(format!("{} is delisted", symbol)))
+            }
+            Some(_) => {}
+        }
+        validate(&request.kind)?;
+
+        let action = CorporateAction {
+            id: Uuid::new_v4(),
+            symbol: symbol.to_string(),
+            kind: request.kind,
+            effective_date: request.effective_date,
+            status: CorporateActionStatus::Scheduled,
+            created_at: self.time_provider.now(),
+            applied_at: None,
+            payments: Vec::new(),
+            error: None,
+        };
+        let id = action.id;
+        self.corporate_actions.actions.insert(id, action.clone());
+
+        self.apply_due_corporate_actions().await;
+        Ok(self
+            .corporate_actions
+            .actions
+            .get(&id)
+            .map(|action| action.clone())
+            .unwrap_or(action))
+    }
+
+    /// Applies every scheduled action whose effective date has arrived on
+    /// the engine clock, returning them as applied or failed.
+    pub async fn apply_due_corporate_actions(&self) -> Vec<CorporateAction> {
+        let now = self.time_provider.now();
+        let mut applied = Vec::new();
+        for mut action in self.corporate_actions.due(now.date_naive()) {
+            match self.apply_corporate_action(&action).await {
+                Ok(payments) => {
+                    action.status = CorporateActionStatus::Applied;
+                    action.payments = payments;
+                    info!("Applied {:?} to {}", action.kind, action.symbol);
+                }
+                Err(e) => {
+                    warn!("Corporate action {} on {} failed: {}", action.id, action.symbol, e);
+                    action.status = CorporateActionStatus::Failed;
+                    action.error = Some(e.to_string());
+                }
+            }
+            action.applied_at = Some(now);
+            self.corporate_actions.actions.insert(action.id, action.clone());
+            if action.status == CorporateActionStatus::Applied {
+                self.event_journal
+                    .publish(EngineEvent::CorporateActionApplied(action.clone()), Vec::new());
+            }
+            applied.push(action);
+        }
+        applied
+    }
+
+    async fn apply_corporate_action(&self, action: &CorporateAction) -> crate::types::Result<Vec<RedemptionPayment>> {
+        match &action.kind {
+            CorporateActionKind::RatingChange { rating } => {
+                self.instruments
+                    .update_bond(&action.symbol, |bond| bond.rating = rating.clone())?;
+                Ok(Vec::new())
+            }
+            CorporateActionKind::CouponRevision { coupon_rate } => {
+                self.instruments
+                    .update_bond(&action.symbol, |bond| bond.coupon_rate = *coupon_rate)?;
+                Ok(Vec::new())
+            }
+            CorporateActionKind::EarlyRedemption { fraction, price } => {
+                // Holders are paid the interest accrued to the redemption
+                let (_, accrued) = self.quote_basis(&action.symbol);
+                let mut payments = Vec::new();
+                for (position, quantity) in self.position_manager.redeem(&action.symbol, *fraction, *price).await {
+                    payments.push(RedemptionPayment {
+                        account_id: position.account_id,
+                        quantity,
+                        amount: notional_value(quantity, *price + accrued),
+                    });
+                    let account_id = position.account_id;
+                    self.event_journal
+                        .publish(EngineEvent::PositionUpdated(position), vec![account_id]);
+                }
+                if *fraction == Decimal::ONE {
+                    self.delist_instrument(&action.symbol, Some("Redeemed".to_string()))?;
+                }
+                Ok(payments)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::allocation::MatchingAlgorithm, test_support::OrderBuilder, utils::time::{SimulatedClock, TimeProvider}};
+    use chrono::{Duration, TimeZone};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_actions_apply_on_effective_date() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::with_clock(clock.clone())),
+        )
+        .await
+        .unwrap();
+        let bond = Bond {
+            isin: "INE062A08256".to_string(),
+            symbol: "SBI2031".to_string(),
+            issuer: "State Bank of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2031, 9, 1, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.50),
+            face_value: dec!(100),
+            bond_type: BondType::CorporateBond,
+            rating: Some("AA+".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 1,
+            day_count: DayCount::ActualActualIcma,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        engine.list_instrument(bond, MatchingAlgorithm::PriceTime).unwrap();
+
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        for (side, account) in [(OrderSide::Sell, seller), (OrderSide::Buy, buyer)] {
+            let order = OrderBuilder::new(side)
+                .symbol("SBI2031")
+                .quantity(dec!(1000000))
+                .price(dec!(99.00))
+                .account(account)
+                .timestamp(start)
+                .build();
+            engine.submit_order(order).await.unwrap();
+        }
+
+        let request = |kind, days| CorporateActionRequest {
+            kind,
+            effective_date: (start + Duration::days(days)).date_naive(),
+        };
+        let upgrade = engine
+            .schedule_corporate_action("SBI2031", request(CorporateActionKind::RatingChange { rating: Some("AAA".to_string()) }, 0))
+            .await
+            .unwrap();
+        assert_eq!(upgrade.status, CorporateActionStatus::Applied);
+        assert_eq!(engine.instruments().get("SBI2031").unwrap().rating.as_deref(), Some("AAA"));
+
+        let partial_call = engine
+            .schedule_corporate_action(
+                "SBI2031",
+                request(CorporateActionKind::EarlyRedemption { fraction: dec!(0.25), price: dec!(101) }, 7),
+            )
+            .await
+            .unwrap();
+        assert_eq!(partial_call.status, CorporateActionStatus::Scheduled);
+        assert!(engine
+            .schedule_corporate_action(
+                "SBI2031",
+                request(CorporateActionKind::EarlyRedemption { fraction: dec!(1.5), price: dec!(101) }, 7),
+            )
+            .await
+            .is_err());
+
+        clock.advance(Duration::days(7));
+        let applied = engine.apply_due_corporate_actions().await;
+        assert_eq!(applied.len(), 1);
+        let payment = applied[0].payments.iter().find(|payment| payment.account_id == buyer).unwrap();
+        assert_eq!(payment.quantity, dec!(250000));
+        assert!(payment.amount > dec!(252500));
+        let short_pnl = engine.get_position(seller, "SBI2031").await.unwrap().realized_pnl;
+        let position = engine.get_position(buyer, "SBI2031").await.unwrap();
+        assert_eq!(position.quantity, dec!(750000));
+        // 250,000 redeemed at 101 against a 99 cost, net of the buyer's fees
+        assert!(position.realized_pnl > dec!(4900) && position.realized_pnl < dec!(5000));
+        // The short pays out on its redeemed quantity
+        assert!(short_pnl < dec!(-5000));
+
+        let history = engine.corporate_actions().history("SBI2031");
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|action| action.status == CorporateActionStatus::Applied));
+    }
+}
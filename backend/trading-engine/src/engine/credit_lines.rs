@@ -0,0 +1,233 @@
+//! Bilateral credit lines for trades that are not centrally cleared. An
+//! account that has set up any lines trades only with counterparties it
+//! has a line for, and only while their unsettled trades together stay
+//! within it. Accounts without lines are not restricted.
+
+use crate::{engine::TradingEngine, types::*, utils::time::TimeProvider};
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreditLineRequest {
+    pub counterparty_account_id: Uuid,
+    /// Largest unsettled notional the grantor will carry against the
+    /// counterparty. Zero blocks trading with it.
+    pub limit: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditLine {
+    pub grantor_account_id: Uuid,
+    pub counterparty_account_id: Uuid,
+    pub limit: Decimal,
+    pub exposure: Decimal,
+    pub headroom: Decimal,
+}
+
+pub struct CreditLineBook {
+    /// Limits by grantor, then counterparty.
+    lines: DashMap<Uuid, HashMap<Uuid, Decimal>>,
+    /// Unsettled notional per settlement date, by account pair in id order.
+    exposures: DashMap<(Uuid, Uuid), Vec<(NaiveDate, Decimal)>>,
+    time_provider: Arc<TimeProvider>,
+}
+
+fn pair(a: Uuid, b: Uuid) -> (Uuid, Uuid) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl CreditLineBook {
+    pub fn new(time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            lines: DashMap::new(),
+            exposures: DashMap::new(),
+            time_provider,
+        }
+    }
+
+    pub fn set_line(&self, grantor_account_id: Uuid, request: CreditLineRequest) -> Result<CreditLine> {
+        if request.limit < Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Credit line limits cannot be negative".to_string()));
+        }
+        if grantor_account_id == request.counterparty_account_id {
+            return Err(TradingError::InvalidRequest("An account needs no line with itself".to_string()));
+        }
+        self.lines
+            .entry(grantor_account_id)
+            .or_default()
+            .insert(request.counterparty_account_id, request.limit);
+        Ok(self.line(grantor_account_id, request.counterparty_account_id, request.limit))
+    }
+
+    /// Removing an account's last line lifts its restrictions altogether.
+    pub fn remove_line(&self, grantor_account_id: Uuid, counterparty_account_id: Uuid) -> Result<()> {
+        let removed = self
+            .lines
+            .get_mut(&grantor_account_id)
+            .and_then(|mut lines| lines.remove(&counterparty_account_id));
+        if removed.is_none() {
+            return Err(TradingError::InvalidRequest(format!(
+                "No credit line from {} to {}",
+                grantor_account_id, counterparty_account_id
+            )));
+        }
+        self.lines.remove_if(&grantor_account_id, |_, lines| lines.is_empty());
+        Ok(())
+    }
+
+    /// The lines an account has granted, largest exposure first.
+    pub fn lines(&self, grantor_account_id: Uuid) -> Vec<CreditLine> {
+        let mut lines: Vec<CreditLine> = self
+            .lines
+            .get(&grantor_account_id)
+            .map(|lines| {
+                lines
+                    .iter()
+                    .map(|(counterparty, limit)| self.line(grantor_account_id, *counterparty, *limit))
+                    .collect()
+            })
+            .unwrap_or_default();
+        lines.sort_by_key(|line| std::cmp::Reverse(line.exposure));
+        lines
+    }
+
+    fn line(&self, grantor_account_id: Uuid, counterparty_account_id: Uuid, limit: Decimal) -> CreditLine {
+        let exposure = self.exposure(grantor_account_id, counterparty_account_id);
+        CreditLine {
+            grantor_account_id,
+            counterparty_account_id,
+            limit,
+            exposure,
+            headroom: limit - exposure,
+        }
+    }
+
+    /// Gross notional of the pair's trades still to settle.
+    pub fn exposure(&self, a: Uuid, b: Uuid) -> Decimal {
+        let today = self.time_provider.today();
+        self.exposures
+            .get(&pair(a, b))
+            .map(|trades| {
+                trades
+                    .iter()
+                    .filter(|(settlement_date, _)| *settlement_date >= today)
+                    .map(|(_, notional)| *notional)
+                    .sum()
+            })
+            .unwrap_or_default()
+    }
+
+    /// How much more the two accounts may trade with each other, or `None`
+    /// if neither has lines.
+    pub fn headroom(&self, a: Uuid, b: Uuid) -> Option<Decimal> {
+        if a == b {
+            return None;
+        }
+        let limits: Vec<Decimal> = [(a, b), (b, a)]
+            .into_iter()
+            .filter_map(|(grantor, counterparty)| {
+                let lines = self.lines.get(&grantor)?;
+                Some(lines.get(&counterparty).copied().unwrap_or(Decimal::ZERO))
+            })
+            .collect();
+        let limit = limits.into_iter().min()?;
+        Some(limit - self.exposure(a, b))
+    }
+
+    /// Sets aside `notional` of the pair's headroom for one match, on top
+    /// of what `reserved` already holds against `b`. Nothing is set aside
+    /// if the line cannot take it.
+    pub fn reserve(&self, a: Uuid, b: Uuid, notional: Decimal, reserved: &mut HashMap<Uuid, Decimal>) -> bool {
+        let Some(headroom) = self.headroom(a, b) else {
+            return true;
+        };
+        let used = reserved.entry(b).or_default();
+        if *used + notional > headroom {
+            return false;
+        }
+        *used += notional;
+        true
+    }
+
+    /// Rejects a trade of `notional` between the two accounts that the
+    /// tighter of their lines cannot take.
+    pub fn check(&self, a: Uuid, b: Uuid, notional: Decimal) -> Result<()> {
+        match self.headroom(a, b) {
+            Some(headroom) if notional > headroom => Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::CreditLine,
+                message: format!(
+                    "Trade of {} between {} and {} exceeds credit line headroom {}",
+                    notional,
+                    a,
+                    b,
+                    headroom.max(Decimal::ZERO)
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        if trade.buyer_account_id == trade.seller_account_id {
+            return;
+        }
+        let today = self.time_provider.today();
+        let mut trades = self
+            .exposures
+            .entry(pair(trade.buyer_account_id, trade.seller_account_id))
+            .or_default();
+        trades.retain(|(settlement_date, _)| *settlement_date >= today);
+        trades.push((trade.settlement_date, notional_value(trade.quantity, trade.price)));
+    }
+}
+
+impl TradingEngine {
+    pub fn credit_lines(&self) -> &CreditLineBook {
+        &self.credit_lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+
+    fn order(side: OrderSide, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_matching_skips_counterparties_without_headroom() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (buyer, unknown, known) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .credit_lines()
+            .set_line(buyer, CreditLineRequest { counterparty_account_id: known, limit: dec!(150000) })
+            .unwrap();
+
+        // The better offer is from an account the buyer has no line with
+        engine.submit_order(order(OrderSide::Sell, dec!(99.00), unknown)).await.unwrap();
+        engine.submit_order(order(OrderSide::Sell, dec!(99.50), known)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(100.00), buyer)).await.unwrap();
+
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].seller_account_id, known);
+        assert_eq!(engine.credit_lines().lines(buyer)[0].headroom, dec!(50500));
+
+        // What is left of the line cannot take another round lot
+        engine.submit_order(order(OrderSide::Sell, dec!(99.50), known)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(100.00), buyer)).await.unwrap();
+        assert_eq!(engine.get_trades().len(), 1);
+    }
+}
@@ -0,0 +1,625 @@
+//! Dealer-to-client trading. Dealers stream tiered quotes per client
+//! segment; a client hits a quote and the dealer has a last-look window to
+//! accept or reject. A hit the dealer leaves undecided is accepted when the
+//! window closes. Accepted hits are booked as trades like any other.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Segment for clients not assigned one, and the quotes they see.
+pub const DEFAULT_SEGMENT: &str = "default";
+
+/// Decisions a dealer needs before its rejection rate is judged.
+const MIN_DECISIONS_FOR_ALERT: u64 = 20;
+
+/// Prices for trades up to `max_quantity` face value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteTier {
+    pub max_quantity: Decimal,
+    pub bid: Decimal,
+    pub ask: Decimal,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DealerQuoteRequest {
+    pub dealer_account_id: Uuid,
+    pub symbol: String,
+    #[serde(default = "default_segment")]
+    pub segment: String,
+    pub tiers: Vec<QuoteTier>,
+    pub valid_for_ms: u64,
+}
+
+fn default_segment() -> String {
+    DEFAULT_SEGMENT.to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DealerQuote {
+    pub id: Uuid,
+    pub dealer_account_id: Uuid,
+    pub symbol: String,
+    pub segment: String,
+    /// Smallest tier first.
+    pub tiers: Vec<QuoteTier>,
+    pub quoted_at: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+}
+
+impl DealerQuote {
+    /// The tier a trade of `quantity` is priced from.
+    pub fn tier_for(&self, quantity: Decimal) -> Option<&QuoteTier> {
+        self.tiers.iter().find(|tier| quantity <= tier.max_quantity)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HitRequest {
+    pub client_order_id: String,
+    pub account_id: Uuid,
+    pub user_id: Uuid,
+    pub dealer_account_id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    /// The price the client saw; the hit fails if the quote has moved.
+    pub price: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HitStatus {
+    /// In the dealer's last-look window.
+    Pending,
+    Accepted,
+    /// The window closed without a decision.
+    AutoAccepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DealerHit {
+    pub id: Uuid,
+    pub quote_id: Uuid,
+    pub dealer_account_id: Uuid,
+    pub client_account_id: Uuid,
+    /// The client's order, which stays pending through the last look.
+    pub order_id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub status: HitStatus,
+    pub received_at: DateTime<Utc>,
+    pub last_look_until: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub reject_reason: Option<String>,
+    pub trade_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HitDecision {
+    pub dealer_account_id: Uuid,
+    pub accept: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DealerStats {
+    pub dealer_account_id: Uuid,
+    pub hits: u64,
+    pub accepted: u64,
+    pub auto_accepted: u64,
+    pub rejected: u64,
+    /// Rejections over decided hits, in percent.
+    pub reject_rate_pct: Decimal,
+    /// Set once the rate is over the configured alert level.
+    pub flagged: bool,
+}
+
+impl DealerStats {
+    fn record(&mut self, status: HitStatus, alert_pct: Decimal) {
+        match status {
+            HitStatus::Pending => self.hits += 1,
+            HitStatus::Accepted => self.accepted += 1,
+            HitStatus::AutoAccepted => self.auto_accepted += 1,
+            HitStatus::Rejected => self.rejected += 1,
+        }
+        let decided = self.accepted + self.auto_accepted + self.rejected;
+        if decided > 0 {
+            let rate = Decimal::from(self.rejected) * Decimal::ONE_HUNDRED / Decimal::from(decided);
+            self.reject_rate_pct = rate.round_dp(2);
+        }
+        self.flagged = decided >= MIN_DECISIONS_FOR_ALERT && self.reject_rate_pct > alert_pct;
+    }
+}
+
+#[derive(Default)]
+pub struct DealerDesk {
+    segments: DashMap<Uuid, String>,
+    last_look_ms: DashMap<Uuid, u64>,
+    /// Keyed by dealer, symbol and segment.
+    quotes: DashMap<(Uuid, String, String), DealerQuote>,
+    hits: DashMap<Uuid, DealerHit>,
+    stats: DashMap<Uuid, DealerStats>,
+}
+
+impl DealerDesk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn segment_of(&self, account_id: Uuid) -> String {
+        self.segments
+            .get(&account_id)
+            .map(|segment| segment.clone())
+            .unwrap_or_else(default_segment)
+    }
+
+    pub fn set_segment(&self, account_id: Uuid, segment: String) {
+        self.segments.insert(account_id, segment);
+    }
+
+    /// The quote a client in `segment` is shown, falling back to the
+    /// dealer's default-segment quote.
+    fn quote_for(&self, dealer_account_id: Uuid, symbol: &str, segment: &str) -> Option<DealerQuote> {
+        [segment, DEFAULT_SEGMENT].into_iter().find_map(|segment| {
+            self.quotes
+                .get(&(dealer_account_id, symbol.to_string(), segment.to_string()))
+                .map(|quote| quote.clone())
+        })
+    }
+
+    /// Live quotes in `symbol` as a client in `segment` sees them, one per
+    /// dealer.
+    pub fn quotes_for(&self, symbol: &str, segment: &str, now: DateTime<Utc>) -> Vec<DealerQuote> {
+        let mut dealers: Vec<Uuid> = self
+            .quotes
+            .iter()
+            .filter(|quote| quote.symbol == symbol)
+            .map(|quote| quote.dealer_account_id)
+            .collect();
+        dealers.sort();
+        dealers.dedup();
+        dealers
+            .into_iter()
+            .filter_map(|dealer| self.quote_for(dealer, symbol, segment))
+            .filter(|quote| quote.valid_until > now)
+            .collect()
+    }
+
+    /// Hits where the account is the client or the dealer, newest first.
+    pub fn hits(&self, account_id: Option<Uuid>) -> Vec<DealerHit> {
+        let mut hits: Vec<DealerHit> = self
+            .hits
+            .iter()
+            .filter(|hit| {
+                account_id.is_none_or(|id| hit.client_account_id == id || hit.dealer_account_id == id)
+            })
+            .map(|hit| hit.clone())
+            .collect();
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.received_at));
+        hits
+    }
+
+    pub fn stats(&self) -> Vec<DealerStats> {
+        let mut stats: Vec<DealerStats> = self.stats.iter().map(|stats| stats.clone()).collect();
+        stats.sort_by_key(|stats| stats.dealer_account_id);
+        stats
+    }
+
+    /// Moves a pending hit to `status`. `None` if it was already decided.
+    fn decide(&self, hit_id: Uuid, status: HitStatus, reason: Option<String>, now: DateTime<Utc>) -> Option<DealerHit> {
+        let mut hit = self.hits.get_mut(&hit_id)?;
+        if hit.status != HitStatus::Pending {
+            return None;
+        }
+        hit.status = status;
+        hit.decided_at = Some(now);
+        hit.reject_reason = reason;
+        Some(hit.clone())
+    }
+}
+
+fn invalid(field: OrderField, message: String) -> TradingError {
+    TradingError::InvalidOrderField { field, message }
+}
+
+impl TradingEngine {
+    pub fn d2c(&self) -> &DealerDesk {
+        &self.dealer_desk
+    }
+
+    pub fn stream_dealer_quote(&self, request: DealerQuoteRequest) -> Result<DealerQuote> {
+        let mut tiers = request.tiers;
+        tiers.sort_by_key(|tier| tier.max_quantity);
+        if tiers.is_empty() || request.valid_for_ms == 0 {
+            return Err(TradingError::InvalidRequest(
+                "A quote needs at least one tier and a validity".to_string(),
+            ));
+        }
+        if request.valid_for_ms > self.config.d2c_max_quote_validity_ms {
+            return Err(TradingError::InvalidRequest(format!(
+                "A quote may be valid for at most {}ms",
+                self.config.d2c_max_quote_validity_ms
+            )));
+        }
+        if let Some(tier) = tiers
+            .iter()
+            .find(|tier| tier.max_quantity <= Decimal::ZERO || tier.bid <= Decimal::ZERO || tier.bid >= tier.ask)
+        {
+            return Err(TradingError::InvalidRequest(format!(
+                "Tier up to {} must have a positive size and a bid below the ask",
+                tier.max_quantity
+            )));
+        }
+        self.check_instrument_tradable(&request.symbol)?;
+
+        let now = self.time_provider.now();
+        let valid_until = i64::try_from(request.valid_for_ms)
+            .ok()
+            .and_then(Duration::try_milliseconds)
+            .and_then(|validity| now.checked_add_signed(validity))
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Validity {}ms is out of range", request.valid_for_ms)))?;
+        let quote = DealerQuote {
+            id: Uuid::new_v4(),
+            dealer_account_id: request.dealer_account_id,
+            symbol: request.symbol,
+            segment: request.segment,
+            tiers,
+            quoted_at: now,
+            valid_until,
+        };
+        let key = (quote.dealer_account_id, quote.symbol.clone(), quote.segment.clone());
+        self.dealer_desk.quotes.insert(key, quote.clone());
+        Ok(quote)
+    }
+
+    pub fn set_dealer_last_look(&self, dealer_account_id: Uuid, last_look_ms: u64) -> Result<()> {
+        if last_look_ms > self.config.d2c_max_last_look_ms {
+            return Err(TradingError::InvalidRequest(format!(
+                "Last look may be at most {}ms",
+                self.config.d2c_max_last_look_ms
+            )));
+        }
+        self.dealer_desk.last_look_ms.insert(dealer_account_id, last_look_ms);
+        Ok(())
+    }
+
+    /// Live quotes in the client's segment, leaving out dealers the client
+    /// has no credit line headroom with.
+    pub fn client_quotes(&self, account_id: Uuid, symbol: &str) -> Vec<DealerQuote> {
+        let segment = self.dealer_desk.segment_of(account_id);
+        let mut quotes = self.dealer_desk.quotes_for(symbol, &segment, self.time_provider.now());
+        if !self.clearing.enabled() {
+            quotes.retain(|quote| {
+                self.credit_lines
+                    .headroom(account_id, quote.dealer_account_id)
+                    .is_none_or(|headroom| headroom > Decimal::ZERO)
+            });
+        }
+        quotes
+    }
+
+    /// Hits a dealer's quote at the price the client saw. The client's
+    /// order goes through the usual checks, then waits on the dealer's
+    /// last look.
+    pub async fn hit_dealer_quote(&self, mut request: HitRequest) -> Result<DealerHit> {
+        self.await_event_consumers().await?;
+        request.symbol = self.instruments.canonical(&request.symbol);
+        let now = self.time_provider.now();
+        let segment = self.dealer_desk.segment_of(request.account_id);
+        let quote = self
+            .dealer_desk
+            .quote_for(request.dealer_account_id, &request.symbol, &segment)
+            .filter(|quote| quote.valid_until > now)
+            .ok_or_else(|| TradingError::InvalidRequest("The dealer has no live quote for this client".to_string()))?;
+        let tier = quote.tier_for(request.quantity).ok_or_else(|| {
+            invalid(OrderField::Quantity, format!("{} is beyond the dealer's largest tier", request.quantity))
+        })?;
+        let price = match request.side {
+            OrderSide::Buy => tier.ask,
+            OrderSide::Sell => tier.bid,
+        };
+        if price != request.price {
+            return Err(invalid(OrderField::Price, format!("The quote has moved to {}", price)));
+        }
+        if !self.clearing.enabled() {
+            self.credit_lines.check(
+                request.account_id,
+                quote.dealer_account_id,
+                notional_value(request.quantity, price),
+            )?;
+        }
+
+        let order = Order {
+            id: Uuid::new_v4(),
+            client_order_id: request.client_order_id,
+            symbol: request.symbol,
+            side: request.side,
+            order_type: OrderType::Limit,
+            quantity: request.quantity,
+            price: Some(price),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: request.quantity,
+            status: OrderStatus::Pending,
+            timestamp: now,
+            user_id: request.user_id,
+            account_id: request.account_id,
+            time_in_force: TimeInForce::FillOrKill,
+            metadata: HashMap::new(),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        };
+        if let Err(e) = self.check_new_order(&order).await {
+            self.reject_new_order(order, &e, now);
+            return Err(e);
+        }
+        self.orders.insert(order.id, order.clone());
+        self.event_journal
+            .publish(EngineEvent::OrderSubmitted(order.clone()), vec![order.account_id]);
+
+        let last_look_ms = self
+            .dealer_desk
+            .last_look_ms
+            .get(&quote.dealer_account_id)
+            .map(|ms| *ms)
+            .unwrap_or(self.config.d2c_last_look_ms);
+        let hit = DealerHit {
+            id: Uuid::new_v4(),
+            quote_id: quote.id,
+            dealer_account_id: quote.dealer_account_id,
+            client_account_id: order.account_id,
+            order_id: order.id,
+            symbol: order.symbol.clone(),
+            side: order.side.clone(),
+            quantity: order.quantity,
+            price,
+            status: HitStatus::Pending,
+            received_at: now,
+            last_look_until: now + Duration::milliseconds(last_look_ms as i64),
+            decided_at: None,
+            reject_reason: None,
+            trade_id: None,
+        };
+        self.dealer_desk.hits.insert(hit.id, hit.clone());
+        self.record_hit(&hit);
+        Ok(hit)
+    }
+
+    /// The dealer's last-look decision on a pending hit.
+    pub async fn decide_hit(&self, hit_id: Uuid, decision: HitDecision) -> Result<DealerHit> {
+        let hit = self
+            .dealer_desk
+            .hits
+            .get(&hit_id)
+            .map(|hit| hit.clone())
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown hit {}", hit_id)))?;
+        if hit.dealer_account_id != decision.dealer_account_id {
+            return Err(TradingError::PermissionDenied("Only the quoting dealer may decide a hit".to_string()));
+        }
+        let now = self.time_provider.now();
+        if now > hit.last_look_until {
+            return Err(TradingError::InvalidRequest("The last-look window has closed".to_string()));
+        }
+        let (status, reason) = match decision.accept {
+            true => (HitStatus::Accepted, None),
+            false => {
+                let reason = decision.reason.unwrap_or_else(|| "Rejected on last look".to_string());
+                (HitStatus::Rejected, Some(reason))
+            }
+        };
+        let hit = self
+            .dealer_desk
+            .decide(hit_id, status, reason, now)
+            .ok_or_else(|| TradingError::InvalidRequest("The hit has already been decided".to_string()))?;
+        self.settle_hit(hit).await
+    }
+
+    /// Accepts every hit whose last-look window has closed. Returns how
+    /// many were accepted.
+    pub async fn sweep_last_look(&self) -> usize {
+        let now = self.time_provider.now();
+        let due: Vec<Uuid> = self
+            .dealer_desk
+            .hits
+            .iter()
+            .filter(|hit| hit.status == HitStatus::Pending && hit.last_look_until <= now)
+            .map(|hit| hit.id)
+            .collect();
+        let mut accepted = 0;
+        for hit_id in due {
+            let Some(hit) = self.dealer_desk.decide(hit_id, HitStatus::AutoAccepted, None, now) else {
+                continue;
+            };
+            match self.settle_hit(hit).await {
+                Ok(_) => accepted += 1,
+                Err(e) => warn!("Failed to book auto-accepted hit {}: {}", hit_id, e),
+            }
+        }
+        accepted
+    }
+
+    /// Books an accepted hit or releases a rejected one's order.
+    async fn settle_hit(&self, mut hit: DealerHit) -> Result<DealerHit> {
+        let client = self
+            .get_order(&hit.order_id)
+            .ok_or_else(|| TradingError::OrderNotFound(hit.order_id.to_string()))?;
+
+        if hit.status == HitStatus::Rejected {
+            let reject_reason = RejectReason::LastLook;
+            self.metrics.increment_order_rejects(&reject_reason);
+            if let Some(mut order) = self.orders.get_mut(&client.id) {
+                order.status = OrderStatus::Rejected(reject_reason);
+                order.remaining_quantity = Decimal::ZERO;
+            }
+            self.event_journal.publish(
+                EngineEvent::OrderRejected {
+                    order_id: client.id,
+                    client_order_id: client.client_order_id.clone(),
+                    account_id: client.account_id,
+                    symbol: client.symbol.clone(),
+                    reason: hit.reject_reason.clone().unwrap_or_default(),
+                    reject_reason,
+                },
+                vec![client.account_id],
+            );
+        } else {
+            let dealer = Order {
+                id: Uuid::new_v4(),
+                client_order_id: format!("D2C-{}", hit.id),
+                side: match client.side {
+                    OrderSide::Buy => OrderSide::Sell,
+                    OrderSide::Sell => OrderSide::Buy,
+                },
+                user_id: Uuid::nil(),
+                account_id: hit.dealer_account_id,
+                ..client.clone()
+            };
+            self.orders.insert(dealer.id, dealer.clone());
+            self.event_journal
+                .publish(EngineEvent::OrderSubmitted(dealer.clone()), vec![dealer.account_id]);
+
+            let mut trade = self.matching_engine.build_trade(&client, &dealer, hit.quantity, hit.price);
+            trade.trade_type = TradeType::DealerToClient;
+            self.matching_engine.publish_trade(&trade, &client, &dealer);
+            self.record_trades(std::slice::from_ref(&trade)).await?;
+            hit.trade_id = Some(trade.id);
+            if let Some(mut stored) = self.dealer_desk.hits.get_mut(&hit.id) {
+                stored.trade_id = hit.trade_id;
+            }
+            info!("Booked {:?} hit {} as trade {}", hit.status, hit.id, trade.trade_number);
+        }
+        self.record_hit(&hit);
+        Ok(hit)
+    }
+
+    fn record_hit(&self, hit: &DealerHit) {
+        self.dealer_desk
+            .stats
+            .entry(hit.dealer_account_id)
+            .or_insert_with(|| DealerStats {
+                dealer_account_id: hit.dealer_account_id,
+                ..Default::default()
+            })
+            .record(hit.status, self.config.d2c_reject_rate_alert_pct);
+        self.event_journal.publish(
+            EngineEvent::DealerHitUpdated(hit.clone()),
+            vec![hit.client_account_id, hit.dealer_account_id],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        utils::time::{SimulatedClock, TimeProvider},
+    };
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn hit(account_id: Uuid, dealer_account_id: Uuid, quantity: Decimal, price: Decimal) -> HitRequest {
+        HitRequest {
+            client_order_id: format!("HIT-{}", quantity),
+            account_id,
+            user_id: Uuid::new_v4(),
+            dealer_account_id,
+            symbol: "GSEC10Y".to_string(),
+            side: OrderSide::Buy,
+            quantity,
+            price,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dealer_hits_are_priced_by_segment_and_decided_on_last_look() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let config = Arc::new(Config::default());
+        let engine = TradingEngine::with_time_provider(config, Arc::new(TimeProvider::with_clock(clock.clone())))
+            .await
+            .unwrap();
+        let (dealer, client) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.d2c().set_segment(client, "tier1".to_string());
+        for (segment, ask) in [("tier1", dec!(99.30)), (DEFAULT_SEGMENT, dec!(99.60))] {
+            engine
+                .stream_dealer_quote(DealerQuoteRequest {
+                    dealer_account_id: dealer,
+                    symbol: "GSEC10Y".to_string(),
+                    segment: segment.to_string(),
+                    tiers: vec![
+                        QuoteTier { max_quantity: dec!(5000000), bid: dec!(99.00), ask: ask + dec!(0.10) },
+                        QuoteTier { max_quantity: dec!(1000000), bid: dec!(99.10), ask },
+                    ],
+                    valid_for_ms: 60_000,
+                })
+                .unwrap();
+        }
+        let shown = engine.client_quotes(client, "GSEC10Y");
+        assert_eq!((shown.len(), shown[0].tiers[0].ask), (1, dec!(99.30)));
+
+        let stale = engine.hit_dealer_quote(hit(client, dealer, dec!(2000000), dec!(99.30))).await;
+        assert!(matches!(stale, Err(TradingError::InvalidOrderField { field: OrderField::Price, .. })));
+
+        let rejected = engine.hit_dealer_quote(hit(client, dealer, dec!(2000000), dec!(99.40))).await.unwrap();
+        let decision = HitDecision {
+            dealer_account_id: dealer,
+            accept: false,
+            reason: Some("Price moved".to_string()),
+        };
+        let rejected = engine.decide_hit(rejected.id, decision).await.unwrap();
+        assert_eq!(rejected.status, HitStatus::Rejected);
+        let order = engine.get_order(&rejected.order_id).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected(RejectReason::LastLook));
+
+        let pending = engine.hit_dealer_quote(hit(client, dealer, dec!(1000000), dec!(99.30))).await.unwrap();
+        assert_eq!(engine.sweep_last_look().await, 0);
+        clock.advance(Duration::milliseconds(250));
+        assert_eq!(engine.sweep_last_look().await, 1);
+
+        let booked = engine.d2c().hits(Some(client)).into_iter().find(|hit| hit.id == pending.id).unwrap();
+        assert_eq!(booked.status, HitStatus::AutoAccepted);
+        let trade = engine.get_trades().into_iter().find(|trade| Some(trade.id) == booked.trade_id).unwrap();
+        assert_eq!(trade.trade_type, TradeType::DealerToClient);
+        assert_eq!((trade.buyer_account_id, trade.seller_account_id), (client, dealer));
+        let position = engine.get_position(dealer, "GSEC10Y").await.unwrap();
+        assert_eq!(position.quantity, dec!(-1000000));
+
+        let stats = engine.d2c().stats();
+        assert_eq!((stats[0].hits, stats[0].rejected, stats[0].auto_accepted), (2, 1, 1));
+        assert_eq!(stats[0].reject_rate_pct, dec!(50));
+        assert!(!stats[0].flagged);
+    }
+
+    #[tokio::test]
+    async fn test_quote_validity_is_capped() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let quote = |valid_for_ms| DealerQuoteRequest {
+            dealer_account_id: Uuid::new_v4(),
+            symbol: "GSEC10Y".to_string(),
+            segment: DEFAULT_SEGMENT.to_string(),
+            tiers: vec![QuoteTier { max_quantity: dec!(1000000), bid: dec!(99.10), ask: dec!(99.30) }],
+            valid_for_ms,
+        };
+        for valid_for_ms in [u64::MAX, i64::MAX as u64 + 1, 300_001] {
+            assert!(matches!(
+                engine.stream_dealer_quote(quote(valid_for_ms)),
+                Err(TradingError::InvalidRequest(_))
+            ));
+        }
+        let quote = engine.stream_dealer_quote(quote(300_000)).unwrap();
+        assert_eq!(quote.valid_until - quote.quoted_at, Duration::minutes(5));
+    }
+}
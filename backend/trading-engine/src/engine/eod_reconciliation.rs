@@ -0,0 +1,247 @@
+//! End-of-day position reconciliation. Each account's holdings are rebuilt
+//! from the whole trade history, archived trades included, less what early
+//! redemptions retired, and compared with the position manager. Any
+//! difference is a break, reported with the trades behind it.
+
+use crate::{
+    engine::{retention::ArchiveQuery, trade_store::TradeQuery, TradingEngine},
+    persistence::archive::DataClass,
+    types::*,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// One side of a trade as it moved an account's position.
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionTrade {
+    pub trade_id: Uuid,
+    pub trade_number: String,
+    pub timestamp: DateTime<Utc>,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionBreak {
+    pub account_id: Uuid,
+    pub symbol: String,
+    /// What the position manager holds.
+    pub position_quantity: Decimal,
+    /// What the trades and redemptions add up to.
+    pub expected_quantity: Decimal,
+    pub difference: Decimal,
+    /// Face value early redemptions retired.
+    pub redeemed_quantity: Decimal,
+    pub trades: Vec<PositionTrade>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EodReconciliation {
+    pub date: NaiveDate,
+    pub run_at: DateTime<Utc>,
+    pub trades_checked: usize,
+    pub positions_checked: usize,
+    pub breaks: Vec<PositionBreak>,
+}
+
+#[derive(Default)]
+struct Expected {
+    quantity: Decimal,
+    redeemed: Decimal,
+    trades: Vec<PositionTrade>,
+}
+
+#[derive(Default)]
+pub struct EodReconciliationLog {
+    runs: DashMap<NaiveDate, EodReconciliation>,
+    /// The last day closed, so each is reconciled once.
+    last_closed: Mutex<Option<NaiveDate>>,
+}
+
+impl EodReconciliationLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, date: NaiveDate) -> Option<EodReconciliation> {
+        self.runs.get(&date).map(|run| run.clone())
+    }
+}
+
+impl TradingEngine {
+    /// The reconciliation of `date`: a fresh one for today, otherwise the
+    /// one run at its close.
+    pub async fn eod_reconciliation(&self, date: NaiveDate) -> Result<EodReconciliation> {
+        if date == self.time_provider.today() {
+            return self.reconcile_positions(date).await;
+        }
+        self.eod_reconciliations
+            .get(date)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("No position reconciliation for {}", date)))
+    }
+
+    /// Reconciles the day just ended once the engine date moves on. The
+    /// first call only notes the date.
+    pub async fn run_due_eod_reconciliation(&self) {
+        let today = self.time_provider.today();
+        let closed = {
+            let mut last_closed = self.eod_reconciliations.last_closed.lock();
+            match last_closed.replace(today) {
+                Some(previous) if previous < today => today - Duration::days(1),
+                _ => return,
+            }
+        };
+        if let Err(e) = self.reconcile_positions(closed).await {
+            error!("Position reconciliation for {} failed: {}", closed, e);
+        }
+    }
+
+    /// Rebuilds every position from trades and redemptions and records the
+    /// breaks against the position manager under `date`.
+    pub async fn reconcile_positions(&self, date: NaiveDate) -> Result<EodReconciliation> {
+        let run_at = self.time_provider.now();
+        let trades = self.trade_history(run_at).await?;
+
+        let mut expected: BTreeMap<(Uuid, String), Expected> = BTreeMap::new();
+        for trade in &trades {
+            for (account_id, side, quantity) in [
+                (trade.buyer_account_id, OrderSide::Buy, trade.quantity),
+                (trade.seller_account_id, OrderSide::Sell, -trade.quantity),
+            ] {
+                let entry = expected.entry((account_id, trade.symbol.clone())).or_default();
+                entry.quantity += quantity;
+                entry.trades.push(PositionTrade {
+                    trade_id: trade.id,
+                    trade_number: trade.trade_number.clone(),
+                    timestamp: trade.timestamp,
+                    side,
+                    quantity: trade.quantity,
+                    price: trade.price,
+                });
+            }
+        }
+        for action in self.corporate_actions.applied() {
+            for payment in &action.payments {
+                let entry = expected.entry((payment.account_id, action.symbol.clone())).or_default();
+                entry.quantity -= payment.quantity;
+                entry.redeemed += payment.quantity;
+            }
+        }
+
+        let positions = self.position_manager.get_positions(None).await;
+        let positions_checked = positions.len();
+        for position in positions {
+            expected.entry((position.account_id, position.symbol.clone())).or_default();
+        }
+
+        let mut breaks = Vec::new();
+        for ((account_id, symbol), expected) in expected {
+            let position_quantity = self
+                .position_manager
+                .get_position(account_id, &symbol)
+                .await
+                .map_or(Decimal::ZERO, |position| position.quantity);
+            if position_quantity == expected.quantity {
+                continue;
+            }
+            breaks.push(PositionBreak {
+                account_id,
+                symbol,
+                position_quantity,
+                expected_quantity: expected.quantity,
+                difference: position_quantity - expected.quantity,
+                redeemed_quantity: expected.redeemed,
+                trades: expected.trades,
+            });
+        }
+
+        let run = EodReconciliation {
+            date,
+            run_at,
+            trades_checked: trades.len(),
+            positions_checked,
+            breaks,
+        };
+        if run.breaks.is_empty() {
+            info!("Positions for {} reconcile with {} trades", date, run.trades_checked);
+        } else {
+            warn!("Position reconciliation for {} found {} breaks", date, run.breaks.len());
+        }
+        self.eod_reconciliations.runs.insert(date, run.clone());
+        Ok(run)
+    }
+
+    /// Every trade up to `to`, live and archived, oldest first.
+    async fn trade_history(&self, to: DateTime<Utc>) -> Result<Vec<Trade>> {
+        let mut trades = self
+            .query_trades(&TradeQuery {
+                from: None,
+                to: Some(to),
+                symbol: None,
+                account_id: None,
+                limit: None,
+            })
+            .await?;
+        if self.archiver.has_sink() {
+            let held: HashSet<Uuid> = trades.iter().map(|trade| trade.id).collect();
+            let query = ArchiveQuery {
+                from: None,
+                to: Some(to),
+                symbol: None,
+                account_id: None,
+                limit: Some(usize::MAX),
+            };
+            for record in self.query_archive(DataClass::Trades, &query).await? {
+                let trade: Trade = serde_json::from_value(record.record)
+                    .map_err(|e| TradingError::InternalError(format!("Unreadable archived trade: {}", e)))?;
+                if !held.contains(&trade.id) {
+                    trades.push(trade);
+                }
+            }
+            trades.sort_by_key(|trade| trade.timestamp);
+        }
+        Ok(trades)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_drift_from_trades_is_reported_with_the_trades() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+
+        let today = engine.time_provider.today();
+        assert!(engine.reconcile_positions(today).await.unwrap().breaks.is_empty());
+
+        // A position changed outside any trade
+        let trade = engine.get_trades().pop().unwrap();
+        engine.position_manager.update_position(&trade).await.unwrap();
+        let run = engine.reconcile_positions(today).await.unwrap();
+        assert_eq!(run.breaks.len(), 2);
+        let buyer_break = run.breaks.iter().find(|b| b.account_id == buyer).unwrap();
+        assert_eq!(buyer_break.expected_quantity, dec!(100000));
+        assert_eq!(buyer_break.difference, dec!(100000));
+        assert_eq!(buyer_break.trades[0].trade_id, trade.id);
+        assert_eq!(engine.eod_reconciliation(today).await.unwrap().breaks.len(), 2);
+    }
+}
@@ -0,0 +1,396 @@
+use crate::{
+    engine::EngineEvent,
+    utils::{clock_sync::ClockStamp, time::TimeProvider},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Notify,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SequencedEvent {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Quality of the clock `timestamp` was read from.
+    pub clock: ClockStamp,
+    pub account_ids: Vec<Uuid>,
+    pub event: EngineEvent,
+}
+
+impl SequencedEvent {
+    pub fn concerns(&self, account_id: Uuid) -> bool {
+        self.account_ids.contains(&account_id)
+    }
+
+    /// Events not tied to any account (instrument lifecycle, market-wide
+    /// notices) are visible to every subscriber.
+    pub fn is_public(&self) -> bool {
+        self.account_ids.is_empty()
+    }
+
+    pub fn visible_to(&self, account_id: Uuid) -> bool {
+        self.is_public() || self.concerns(account_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventReplay {
+    pub events: Vec<SequencedEvent>,
+    pub next_seq: u64,
+    pub oldest_available_seq: u64,
+    pub truncated: bool,
+}
+
+/// How a subscriber receives live events. Lossy subscribers share a
+/// broadcast channel and skip events when they fall a channel's length
+/// behind, which suits UI streams that can replay from the journal.
+/// Lossless subscribers each get their own queue that never drops an
+/// event; when one is full, new orders wait until it drains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Lossy,
+    Lossless,
+}
+
+/// A lossless subscriber's queue. Publishing happens under the journal
+/// lock and never blocks, so the bound is where backpressure starts rather
+/// than a hard limit; events already in flight still go in.
+struct LosslessQueue {
+    events: Mutex<VecDeque<SequencedEvent>>,
+    bound: usize,
+    readable: Notify,
+    writable: Notify,
+    closed: AtomicBool,
+}
+
+impl LosslessQueue {
+    fn push(&self, event: SequencedEvent) {
+        self.events.lock().push_back(event);
+        self.readable.notify_one();
+    }
+
+    fn has_room(&self) -> bool {
+        self.events.lock().len() < self.bound
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.readable.notify_one();
+    }
+}
+
+pub struct LosslessReceiver {
+    queue: Arc<LosslessQueue>,
+}
+
+impl LosslessReceiver {
+    pub async fn recv(&mut self) -> Option<SequencedEvent> {
+        loop {
+            let readable = self.queue.readable.notified();
+            if let Some(event) = self.queue.events.lock().pop_front() {
+                self.queue.writable.notify_waiters();
+                return Some(event);
+            }
+            if self.queue.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            readable.await;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.events.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for LosslessReceiver {
+    fn drop(&mut self) {
+        // Releases any order entry waiting on this queue
+        self.queue.closed.store(true, Ordering::Release);
+        self.queue.writable.notify_waiters();
+    }
+}
+
+/// Either kind of subscription, read the same way. A lossless one never
+/// reports `Lagged`.
+pub enum EventReceiver {
+    Lossy(broadcast::Receiver<SequencedEvent>),
+    Lossless(LosslessReceiver),
+}
+
+impl EventReceiver {
+    pub async fn recv(&mut self) -> Result<SequencedEvent, RecvError> {
+        match self {
+            EventReceiver::Lossy(receiver) => receiver.recv().await,
+            EventReceiver::Lossless(receiver) => receiver.recv().await.ok_or(RecvError::Closed),
+        }
+    }
+}
+
+struct JournalState {
+    next_seq: u64,
+    entries: VecDeque<SequencedEvent>,
+    lossless: Vec<Arc<LosslessQueue>>,
+}
+
+/// Sequences every engine event, keeps the most recent ones for replay and
+/// fans them out to live subscribers.
+pub struct EventJournal {
+    state: RwLock<JournalState>,
+    capacity: usize,
+    channel_size: usize,
+    sender: broadcast::Sender<SequencedEvent>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl EventJournal {
+    pub fn new(capacity: usize, channel_size: usize, time_provider: Arc<TimeProvider>) -> Self {
+        let (sender, _) = broadcast::channel(channel_size);
+
+        Self {
+            state: RwLock::new(JournalState {
+                next_seq: 1,
+                entries: VecDeque::with_capacity(capacity.min(100000)),
+                lossless: Vec::new(),
+            }),
+            capacity,
+            channel_size,
+            sender,
+            time_provider,
+        }
+    }
+
+    pub fn publish(&self, event: EngineEvent, account_ids: Vec<Uuid>) -> u64 {
+        // Sequence assignment, journaling and fan-out happen under one lock so
+        // live subscribers observe events in sequence order.
+        let mut state = self.state.write();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+
+        let (timestamp, clock) = self.time_provider.stamped_now();
+        let entry = SequencedEvent {
+            seq,
+            timestamp,
+            clock,
+            account_ids,
+            event,
+        };
+
+        state.entries.push_back(entry.clone());
+        if state.entries.len() > self.capacity {
+            state.entries.pop_front();
+        }
+
+        state.lossless.retain(|queue| !queue.closed.load(Ordering::Acquire));
+        for queue in &state.lossless {
+            queue.push(entry.clone());
+        }
+        let _ = self.sender.send(entry);
+
+        seq
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SequencedEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribes with the given delivery. Lossless queues hold up to the
+    /// channel size before order entry is held back.
+    pub fn subscribe_with(&self, delivery: Delivery) -> EventReceiver {
+        match delivery {
+            Delivery::Lossy => EventReceiver::Lossy(self.subscribe()),
+            Delivery::Lossless => {
+                let queue = Arc::new(LosslessQueue {
+                    events: Mutex::new(VecDeque::new()),
+                    bound: self.channel_size,
+                    readable: Notify::new(),
+                    writable: Notify::new(),
+                    closed: AtomicBool::new(false),
+                });
+                self.state.write().lossless.push(queue.clone());
+                EventReceiver::Lossless(LosslessReceiver { queue })
+            }
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count() + self.state.read().lossless.len()
+    }
+
+    /// Lossless subscribers whose queues are full.
+    pub fn stalled_consumers(&self) -> usize {
+        self.state
+            .read()
+            .lossless
+            .iter()
+            .filter(|queue| !queue.closed.load(Ordering::Acquire) && !queue.has_room())
+            .count()
+    }
+
+    /// Waits until every lossless subscriber has room for more events.
+    /// Callers bound the wait; see `TradingEngine::await_event_consumers`.
+    pub async fn backpressure(&self) {
+        loop {
+            let full = self
+                .state
+                .read()
+                .lossless
+                .iter()
+                .find(|queue| !queue.closed.load(Ordering::Acquire) && !queue.has_room())
+                .cloned();
+            let Some(queue) = full else {
+                return;
+            };
+            let writable = queue.writable.notified();
+            if queue.has_room() || queue.closed.load(Ordering::Acquire) {
+                continue;
+            }
+            writable.await;
+        }
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.state.read().next_seq - 1
+    }
+
+    /// Drops retained events stamped before `cutoff`. Returns how many were
+    /// dropped.
+    pub fn prune_before(&self, cutoff: DateTime<Utc>) -> usize {
+        let mut state = self.state.write();
+        let before = state.entries.len();
+        while state.entries.front().is_some_and(|entry| entry.timestamp < cutoff) {
+            state.entries.pop_front();
+        }
+        before - state.entries.len()
+    }
+
+    /// Returns retained events with `seq >= from_seq`, optionally restricted
+    /// to those concerning a single account. `truncated` is set when events
+    /// the caller asked for have already been evicted from the journal.
+    pub fn replay(&self, from_seq: u64, account_id: Option<Uuid>, limit: usize) -> EventReplay {
+        let state = self.state.read();
+        let oldest_available_seq = state
+            .entries
+            .front()
+            .map(|entry| entry.seq)
+            .unwrap_or(state.next_seq);
+
+        let mut events = Vec::new();
+        let mut next_seq = from_seq.max(oldest_available_seq);
+
+        for entry in state.entries.iter().filter(|entry| entry.seq >= from_seq) {
+            if events.len() >= limit {
+                break;
+            }
+            next_seq = entry.seq + 1;
+            if account_id.is_none_or(|id| entry.visible_to(id)) {
+                events.push(entry.clone());
+            }
+        }
+
+        if events.len() < limit {
+            next_seq = next_seq.max(state.next_seq);
+        }
+
+        EventReplay {
+            events,
+            next_seq,
+            oldest_available_seq,
+            truncated: from_seq < oldest_available_seq,
+        }
+    }
+}
+
+impl Drop for EventJournal {
+    fn drop(&mut self) {
+        for queue in &self.state.get_mut().lossless {
+            queue.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CancelReason;
+
+    fn cancelled() -> EngineEvent {
+        EngineEvent::OrderCancelled {
+            order_id: Uuid::new_v4(),
+            cancel_reason: CancelReason::ClientRequest,
+        }
+    }
+
+    #[test]
+    fn test_replay_filters_by_account_and_sequence() {
+        let journal = EventJournal::new(10, 16, Arc::new(TimeProvider::new()));
+        let account_a = Uuid::new_v4();
+        let account_b = Uuid::new_v4();
+
+        journal.publish(cancelled(), vec![account_a]);
+        journal.publish(cancelled(), vec![account_b]);
+        journal.publish(cancelled(), vec![account_a]);
+
+        let replay = journal.replay(2, Some(account_a), 100);
+        assert_eq!(replay.events.len(), 1);
+        assert_eq!(replay.events[0].seq, 3);
+        assert_eq!(replay.next_seq, 4);
+        assert!(!replay.truncated);
+    }
+
+    #[test]
+    fn test_replay_reports_evicted_events() {
+        let journal = EventJournal::new(2, 16, Arc::new(TimeProvider::new()));
+        for _ in 0..5 {
+            journal.publish(cancelled(), vec![]);
+        }
+
+        let replay = journal.replay(1, None, 100);
+        assert!(replay.truncated);
+        assert_eq!(replay.oldest_available_seq, 4);
+        assert_eq!(replay.events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_lossless_subscriber_gets_every_event_and_holds_back_entry() {
+        let journal = Arc::new(EventJournal::new(10, 2, Arc::new(TimeProvider::new())));
+        let mut lossy = journal.subscribe_with(Delivery::Lossy);
+        let EventReceiver::Lossless(mut lossless) = journal.subscribe_with(Delivery::Lossless) else {
+            panic!("expected a lossless subscription");
+        };
+        for _ in 0..5 {
+            journal.publish(cancelled(), vec![]);
+        }
+        assert!(matches!(lossy.recv().await, Err(RecvError::Lagged(3))));
+        assert_eq!(lossless.len(), 5);
+
+        let waiting = tokio::spawn({
+            let journal = journal.clone();
+            async move { journal.backpressure().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        let mut received = Vec::new();
+        for _ in 0..4 {
+            received.push(lossless.recv().await.unwrap().seq);
+        }
+        waiting.await.unwrap();
+        received.push(lossless.recv().await.unwrap().seq);
+        assert_eq!(received, vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,341 @@
+//! Execution reports in the manner of FIX: one record per acknowledgement,
+//! fill, cancel, expiry or reject of an order, carrying the order's
+//! cumulative and leaves quantity and average fill price at that point.
+//! The report task builds them from engine events and writes them to the
+//! state store, which `/executions` reads back.
+
+use crate::{
+    engine::{
+        event_journal::{Delivery, SequencedEvent},
+        EngineEvent, TradingEngine,
+    },
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// What happened to the order, as FIX `ExecType`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecType {
+    New,
+    Trade,
+    /// The order's quantity was reduced.
+    Replaced,
+    Cancelled,
+    Expired,
+    Rejected,
+}
+
+/// The order's state after the execution, as FIX `OrdStatus`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecOrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Expired,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub exec_id: Uuid,
+    /// The engine event the report was built from.
+    pub event_seq: u64,
+    pub order_id: Uuid,
+    pub client_order_id: String,
+    pub account_id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub exec_type: ExecType,
+    pub ord_status: ExecOrderStatus,
+    pub order_quantity: Decimal,
+    /// Quantity and clean price of this fill; zero and unset otherwise.
+    pub last_quantity: Decimal,
+    pub last_price: Option<Decimal>,
+    pub cum_quantity: Decimal,
+    pub leaves_quantity: Decimal,
+    /// Average clean price of the fills so far; unset before the first.
+    pub avg_price: Option<Decimal>,
+    /// Notional value of the fills so far, on the per-100 price basis.
+    pub cumulative_notional: Decimal,
+    pub trade_id: Option<Uuid>,
+    /// Why the order was rejected.
+    pub text: Option<String>,
+    /// Why the order was cancelled or expired.
+    #[serde(default)]
+    pub cancel_reason: Option<CancelReason>,
+    pub transact_time: DateTime<Utc>,
+}
+
+/// Filters for execution reports. Unset bounds are open.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExecutionQuery {
+    pub account_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ExecutionQuery {
+    pub fn matches(&self, report: &ExecutionReport) -> bool {
+        self.account_id.is_none_or(|id| report.account_id == id)
+            && self.from.is_none_or(|from| report.transact_time >= from)
+            && self.to.is_none_or(|to| report.transact_time <= to)
+    }
+}
+
+/// Fills of an order reported so far.
+#[derive(Debug, Clone, Default)]
+struct Cumulative {
+    quantity: Decimal,
+    fills: OrderFills,
+}
+
+/// Cumulative fills of orders still working, and how far through the
+/// event journal reports have been built.
+#[derive(Default)]
+pub struct ExecutionTracker {
+    working: DashMap<Uuid, Cumulative>,
+    last_seq: AtomicU64,
+}
+
+impl ExecutionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::Relaxed)
+    }
+}
+
+impl TradingEngine {
+    /// Builds the reports for one engine event. An order's acknowledgement
+    /// is reported before its first fill even when the aggressor's fills
+    /// are published ahead of its acceptance.
+    pub fn execution_reports(&self, event: &SequencedEvent) -> Vec<ExecutionReport> {
+        let tracker = &self.execution_tracker;
+        tracker.last_seq.fetch_max(event.seq, Ordering::Relaxed);
+
+        let (order_id, exec_type) = match &event.event {
+            EngineEvent::OrderSubmitted(order) => (order.id, ExecType::New),
+            EngineEvent::OrderFilled { order_id, .. } => (*order_id, ExecType::Trade),
+            EngineEvent::OrderReduced { order_id, .. } => (*order_id, ExecType::Replaced),
+            EngineEvent::OrderCancelled { order_id, .. } => (*order_id, ExecType::Cancelled),
+            EngineEvent::OrderExpired { order_id, .. } => (*order_id, ExecType::Expired),
+            EngineEvent::OrderRejected { order_id, .. } => (*order_id, ExecType::Rejected),
+            _ => return Vec::new(),
+        };
+        let Some(order) = self.get_order(&order_id) else {
+            warn!("No order {} for execution report at event {}", order_id, event.seq);
+            return Vec::new();
+        };
+
+        let mut reports = Vec::new();
+        let seen = tracker.working.contains_key(&order_id);
+        let report = |exec_type, ord_status, cumulative: &Cumulative, fill: Option<&Trade>| ExecutionReport {
+            exec_id: Uuid::new_v4(),
+            event_seq: event.seq,
+            order_id,
+            client_order_id: order.client_order_id.clone(),
+            account_id: order.account_id,
+            symbol: order.symbol.clone(),
+            side: order.side.clone(),
+            exec_type,
+            ord_status,
+            order_quantity: order.quantity,
+            last_quantity: fill.map(|trade| trade.quantity).unwrap_or_default(),
+            last_price: fill.map(|trade| trade.price),
+            cum_quantity: cumulative.quantity,
+            leaves_quantity: match ord_status {
+                ExecOrderStatus::New | ExecOrderStatus::PartiallyFilled => order.quantity - cumulative.quantity,
+                _ => Decimal::ZERO,
+            },
+            avg_price: cumulative.fills.avg_fill_price,
+            cumulative_notional: cumulative.fills.cumulative_notional,
+            trade_id: fill.map(|trade| trade.id),
+            text: match &event.event {
+                EngineEvent::OrderRejected { reason, .. } => Some(reason.clone()),
+                _ => None,
+            },
+            cancel_reason: match &event.event {
+                EngineEvent::OrderCancelled { cancel_reason, .. } => Some(*cancel_reason),
+                EngineEvent::OrderExpired { .. } => Some(CancelReason::Expired),
+                _ => None,
+            },
+            transact_time: event.timestamp,
+        };
+
+        match &event.event {
+            EngineEvent::OrderSubmitted(_) => {
+                // Already acknowledged ahead of its fills
+                if !seen {
+                    tracker.working.insert(order_id, Cumulative::default());
+                    reports.push(report(ExecType::New, ExecOrderStatus::New, &Cumulative::default(), None));
+                }
+            }
+            EngineEvent::OrderFilled { trade, .. } => {
+                if !seen {
+                    reports.push(report(ExecType::New, ExecOrderStatus::New, &Cumulative::default(), None));
+                }
+                let mut cumulative = tracker
+                    .working
+                    .remove(&order_id)
+                    .map(|(_, cumulative)| cumulative)
+                    .unwrap_or_default();
+                cumulative.quantity += trade.quantity;
+                cumulative.fills.record(trade.quantity, trade.price, cumulative.quantity);
+                let ord_status = if cumulative.quantity >= order.quantity {
+                    ExecOrderStatus::Filled
+                } else {
+                    ExecOrderStatus::PartiallyFilled
+                };
+                reports.push(report(exec_type, ord_status, &cumulative, Some(trade)));
+                if ord_status == ExecOrderStatus::PartiallyFilled {
+                    tracker.working.insert(order_id, cumulative);
+                }
+            }
+            EngineEvent::OrderReduced { .. } => {
+                let cumulative = tracker.working.get(&order_id).map(|c| c.clone()).unwrap_or_default();
+                let ord_status = if cumulative.quantity > Decimal::ZERO {
+                    ExecOrderStatus::PartiallyFilled
+                } else {
+                    ExecOrderStatus::New
+                };
+                reports.push(report(exec_type, ord_status, &cumulative, None));
+            }
+            _ => {
+                let cumulative = tracker
+                    .working
+                    .remove(&order_id)
+                    .map(|(_, cumulative)| cumulative)
+                    .unwrap_or_default();
+                let ord_status = match exec_type {
+                    ExecType::Cancelled => ExecOrderStatus::Cancelled,
+                    ExecType::Expired => ExecOrderStatus::Expired,
+                    _ => ExecOrderStatus::Rejected,
+                };
+                reports.push(report(exec_type, ord_status, &cumulative, None));
+            }
+        }
+        reports
+    }
+
+    /// Stored execution reports matching `query`, oldest first.
+    pub async fn query_executions(&self, query: &ExecutionQuery) -> Result<Vec<ExecutionReport>> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from > to {
+                return Err(TradingError::InvalidRequest("`from` must not be after `to`".to_string()));
+            }
+        }
+        self.state_store.load_executions(query).await
+    }
+
+    async fn save_execution_reports(&self, event: &SequencedEvent) {
+        let reports = self.execution_reports(event);
+        if reports.is_empty() {
+            return;
+        }
+        if let Err(e) = self.state_store.save_executions(&reports).await {
+            error!("Failed to persist {} execution reports: {}", reports.len(), e);
+        }
+    }
+}
+
+/// Builds and stores execution reports from engine events, received
+/// losslessly. Should any be reported skipped it catches up from the
+/// journal.
+pub async fn run_reports(engine: Arc<TradingEngine>) {
+    let mut events = engine.subscribe_events_with(Delivery::Lossless);
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if event.seq > engine.execution_tracker.last_seq() {
+                    engine.save_execution_reports(&event).await;
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Execution report task skipped {} events, replaying from the journal", skipped);
+                let replay = engine.replay_events(engine.execution_tracker.last_seq() + 1, None, usize::MAX);
+                if replay.truncated {
+                    error!(
+                        "Execution reports missing for events before {}, which have left the journal",
+                        replay.oldest_available_seq
+                    );
+                }
+                for event in &replay.events {
+                    engine.save_execution_reports(event).await;
+                }
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_reports_carry_cumulative_fills_for_each_order() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, dec!(100000), dec!(99.00), seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Sell, dec!(100000), dec!(99.50), seller)).await.unwrap();
+        let bid = engine.submit_order(order(OrderSide::Buy, dec!(300000), dec!(99.50), buyer)).await.unwrap();
+        engine.cancel_order(bid).await.unwrap();
+
+        for event in engine.replay_events(1, None, usize::MAX).events {
+            engine.save_execution_reports(&event).await;
+        }
+        let reports = engine
+            .query_executions(&ExecutionQuery {
+                account_id: Some(buyer),
+                ..ExecutionQuery::default()
+            })
+            .await
+            .unwrap();
+        let summary: Vec<(ExecType, ExecOrderStatus, Decimal, Decimal)> = reports
+            .iter()
+            .map(|report| (report.exec_type, report.ord_status, report.cum_quantity, report.leaves_quantity))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (ExecType::New, ExecOrderStatus::New, dec!(0), dec!(300000)),
+                (ExecType::Trade, ExecOrderStatus::PartiallyFilled, dec!(100000), dec!(200000)),
+                (ExecType::Trade, ExecOrderStatus::PartiallyFilled, dec!(200000), dec!(100000)),
+                (ExecType::Cancelled, ExecOrderStatus::Cancelled, dec!(200000), dec!(0)),
+            ]
+        );
+        assert_eq!(reports[2].last_price, Some(dec!(99.50)));
+        assert_eq!(reports[3].avg_price, Some(dec!(99.25)));
+        assert_eq!(reports[3].cancel_reason, Some(CancelReason::ClientRequest));
+
+        let stored = engine.get_order(&bid).unwrap();
+        assert_eq!(stored.fills.avg_fill_price, reports[3].avg_price);
+        assert_eq!(stored.fills.cumulative_notional, dec!(198500));
+        assert_eq!(
+            (stored.fills.last_fill_quantity, stored.fills.last_fill_price),
+            (dec!(100000), Some(dec!(99.50)))
+        );
+    }
+}
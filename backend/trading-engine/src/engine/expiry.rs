@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+const SLOTS: usize = 4096;
+
+/// Hashed timer wheel for order expiry. Deadlines are rounded up to the next
+/// tick, so an order is never reported before its expiry and at most one
+/// tick after the sweep that follows it. Entries are not removed when an
+/// order fills or is cancelled; the caller re-checks each id it is handed.
+pub struct ExpiryWheel {
+    tick_ms: i64,
+    state: Mutex<WheelState>,
+}
+
+struct WheelState {
+    slots: Vec<Vec<(Uuid, i64)>>,
+    /// Last tick swept, once the wheel has been advanced.
+    current: Option<i64>,
+}
+
+impl ExpiryWheel {
+    pub fn new(tick_ms: u64) -> Self {
+        Self {
+            tick_ms: tick_ms.max(1) as i64,
+            state: Mutex::new(WheelState {
+                slots: vec![Vec::new(); SLOTS],
+                current: None,
+            }),
+        }
+    }
+
+    pub fn schedule(&self, order_id: Uuid, expiry: DateTime<Utc>) {
+        let mut state = self.state.lock();
+        let mut deadline = (expiry.timestamp_millis() + self.tick_ms - 1).div_euclid(self.tick_ms);
+        // Already due: pick it up on the next sweep
+        if let Some(current) = state.current {
+            deadline = deadline.max(current + 1);
+        }
+        state.slots[slot(deadline)].push((order_id, deadline));
+    }
+
+    /// Returns every scheduled id whose deadline is at or before `now`. A
+    /// clock that moves backwards releases nothing.
+    pub fn advance(&self, now: DateTime<Utc>) -> Vec<Uuid> {
+        let mut state = self.state.lock();
+        let target = now.timestamp_millis().div_euclid(self.tick_ms);
+        let from = match state.current {
+            Some(current) if current >= target => return Vec::new(),
+            Some(current) if target - current < SLOTS as i64 => current + 1,
+            // First sweep, or the clock jumped a full rotation: check everything
+            _ => target - SLOTS as i64 + 1,
+        };
+        state.current = Some(target);
+
+        let mut due = Vec::new();
+        for tick in from..=target {
+            state.slots[slot(tick)].retain(|&(order_id, deadline)| {
+                if deadline <= target {
+                    due.push(order_id);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        due
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().slots.iter().map(Vec::len).sum()
+    }
+}
+
+fn slot(tick: i64) -> usize {
+    tick.rem_euclid(SLOTS as i64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_wheel_never_releases_early_and_survives_clock_jumps() {
+        let wheel = ExpiryWheel::new(50);
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let (soon, later, overdue) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        wheel.schedule(soon, start + Duration::milliseconds(120));
+        wheel.schedule(later, start + Duration::hours(3));
+        assert!(wheel.advance(start).is_empty());
+        assert!(wheel.advance(start + Duration::milliseconds(119)).is_empty());
+        assert_eq!(wheel.advance(start + Duration::milliseconds(150)), vec![soon]);
+
+        // Scheduled after its expiry has passed: released by the next sweep
+        wheel.schedule(overdue, start - Duration::seconds(1));
+        assert_eq!(wheel.advance(start + Duration::milliseconds(200)), vec![overdue]);
+
+        // Many rotations later in one step
+        assert!(wheel.advance(start + Duration::hours(2)).is_empty());
+        assert_eq!(wheel.advance(start + Duration::hours(3)), vec![later]);
+        assert_eq!(wheel.len(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Runtime switches for matching and risk behaviours being rolled out.
+//! Each flag is on everywhere unless configured otherwise; an admin
+//! override replaces the configured rollout until it is cleared.
+
+use crate::{engine::TradingEngine, types::Order};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureFlag {
+    /// Trading incoming outrights against implied spread quotes.
+    ImpliedMatching,
+    /// Crossing against the same firm's interest before the book.
+    Internalization,
+    /// Pro-rata allocation for instruments configured for it; price-time
+    /// otherwise.
+    ProRataAllocation,
+    GroupRiskLimits,
+    BookCapacityLimits,
+}
+
+impl FeatureFlag {
+    pub const ALL: [FeatureFlag; 5] = [
+        FeatureFlag::ImpliedMatching,
+        FeatureFlag::Internalization,
+        FeatureFlag::ProRataAllocation,
+        FeatureFlag::GroupRiskLimits,
+        FeatureFlag::BookCapacityLimits,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "implied_matching" => Some(FeatureFlag::ImpliedMatching),
+            "internalization" => Some(FeatureFlag::Internalization),
+            "pro_rata_allocation" => Some(FeatureFlag::ProRataAllocation),
+            "group_risk_limits" => Some(FeatureFlag::GroupRiskLimits),
+            "book_capacity_limits" => Some(FeatureFlag::BookCapacityLimits),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Who a flag is on for. Listed symbols and accounts are always on; of the
+/// rest, `percent` of accounts are, chosen by account id so an account
+/// stays in or out as the percentage grows.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FlagRollout {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub symbols: HashSet<String>,
+    #[serde(default)]
+    pub accounts: HashSet<Uuid>,
+    #[serde(default)]
+    pub percent: u8,
+}
+
+impl FlagRollout {
+    pub fn on() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    pub fn is_on_for(&self, symbol: &str, account_id: Uuid) -> bool {
+        self.enabled
+            || self.symbols.contains(symbol)
+            || self.accounts.contains(&account_id)
+            || (account_id.as_u128() % 100) < u128::from(self.percent)
+    }
+}
+
+#[derive(Default)]
+struct FlagCounters {
+    evaluations: AtomicU64,
+    enabled: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagState {
+    pub flag: FeatureFlag,
+    pub rollout: FlagRollout,
+    pub overridden: bool,
+    pub evaluations: u64,
+    /// Evaluations that came out on.
+    pub enabled: u64,
+}
+
+pub struct FeatureFlags {
+    configured: HashMap<FeatureFlag, FlagRollout>,
+    overrides: DashMap<FeatureFlag, FlagRollout>,
+    counters: [FlagCounters; FeatureFlag::ALL.len()],
+}
+
+impl FeatureFlags {
+    pub fn new(configured: HashMap<FeatureFlag, FlagRollout>) -> Self {
+        Self {
+            configured,
+            overrides: DashMap::new(),
+            counters: Default::default(),
+        }
+    }
+
+    /// Whether `flag` is on for an order from `account_id` in `symbol`.
+    pub fn is_enabled(&self, flag: FeatureFlag, symbol: &str, account_id: Uuid) -> bool {
+        let enabled = match self.overrides.get(&flag) {
+            Some(rollout) => rollout.is_on_for(symbol, account_id),
+            None => self.configured.get(&flag).is_none_or(|rollout| rollout.is_on_for(symbol, account_id)),
+        };
+        let counters = &self.counters[flag.index()];
+        counters.evaluations.fetch_add(1, Ordering::Relaxed);
+        if enabled {
+            counters.enabled.fetch_add(1, Ordering::Relaxed);
+        }
+        enabled
+    }
+
+    pub fn set_override(&self, flag: FeatureFlag, rollout: FlagRollout) {
+        self.overrides.insert(flag, rollout);
+    }
+
+    /// Returns the flag to its configured rollout.
+    pub fn clear_override(&self, flag: FeatureFlag) -> bool {
+        self.overrides.remove(&flag).is_some()
+    }
+
+    pub fn state(&self, flag: FeatureFlag) -> FlagState {
+        let overridden = self.overrides.get(&flag).map(|rollout| rollout.clone());
+        let counters = &self.counters[flag.index()];
+        FlagState {
+            flag,
+            overridden: overridden.is_some(),
+            rollout: overridden
+                .or_else(|| self.configured.get(&flag).cloned())
+                .unwrap_or_else(FlagRollout::on),
+            evaluations: counters.evaluations.load(Ordering::Relaxed),
+            enabled: counters.enabled.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn states(&self) -> Vec<FlagState> {
+        FeatureFlag::ALL.iter().map(|flag| self.state(*flag)).collect()
+    }
+}
+
+impl TradingEngine {
+    pub fn feature_flags(&self) -> &FeatureFlags {
+        &self.feature_flags
+    }
+
+    pub(crate) fn feature_enabled(&self, flag: FeatureFlag, order: &Order) -> bool {
+        self.feature_flags.is_enabled(flag, &order.symbol, order.account_id)
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{engine::instruments::InstrumentRegistry, types::*};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub(crate) const BASIS_POINT: Decimal = Decimal::from_parts(1, 0, 0, false, 4);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum AccountTier {
+    Retail,
+    #[default]
+    Standard,
+    Institutional,
+    MarketMaker,
+}
+
+/// A fee rule applies to trades matching its tier and instrument type; a
+/// `None` criterion matches anything. The most specific matching rule wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeRule {
+    pub tier: Option<AccountTier>,
+    pub instrument_type: Option<BondType>,
+    pub maker_rate_bps: Decimal,
+    pub taker_rate_bps: Decimal,
+    pub brokerage_rate_bps: Decimal,
+    pub min_brokerage: Decimal,
+}
+
+impl FeeRule {
+    fn matches(&self, tier: AccountTier, instrument_type: Option<&BondType>) -> bool {
+        self.tier.is_none_or(|t| t == tier)
+            && self
+                .instrument_type
+                .as_ref()
+                .is_none_or(|t| Some(t) == instrument_type)
+    }
+
+    fn specificity(&self) -> u8 {
+        self.tier.is_some() as u8 + self.instrument_type.is_some() as u8
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub rules: Vec<FeeRule>,
+    /// GST charged on exchange fees and brokerage.
+    pub tax_rate: Decimal,
+    /// Stamp duty charged to the buyer on trade notional.
+    pub stamp_duty_rate_bps: Decimal,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                FeeRule {
+                    tier: None,
+                    instrument_type: None,
+                    maker_rate_bps: Decimal::new(5, 2),
+                    taker_rate_bps: Decimal::new(10, 2),
+                    brokerage_rate_bps: Decimal::new(25, 2),
+                    min_brokerage: Decimal::ZERO,
+                },
+                FeeRule {
+                    tier: Some(AccountTier::Retail),
+                    instrument_type: None,
+                    maker_rate_bps: Decimal::new(5, 2),
+                    taker_rate_bps: Decimal::new(10, 2),
+                    brokerage_rate_bps: Decimal::ONE,
+                    min_brokerage: Decimal::from(20),
+                },
+                FeeRule {
+                    tier: Some(AccountTier::MarketMaker),
+                    instrument_type: None,
+                    maker_rate_bps: Decimal::ZERO,
+                    taker_rate_bps: Decimal::new(10, 2),
+                    brokerage_rate_bps: Decimal::ZERO,
+                    min_brokerage: Decimal::ZERO,
+                },
+            ],
+            tax_rate: Decimal::new(18, 2),
+            stamp_duty_rate_bps: Decimal::new(1, 2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccountFeeSummary {
+    pub account_id: Uuid,
+    pub trade_count: u64,
+    pub notional: Decimal,
+    pub exchange_fees: Decimal,
+    pub brokerage: Decimal,
+    pub tax: Decimal,
+    pub stamp_duty: Decimal,
+    pub total: Decimal,
+}
+
+pub struct FeeEngine {
+    schedule: RwLock<FeeSchedule>,
+    account_tiers: DashMap<Uuid, AccountTier>,
+    summaries: DashMap<Uuid, AccountFeeSummary>,
+    instruments: Arc<InstrumentRegistry>,
+}
+
+impl FeeEngine {
+    pub fn new(instruments: Arc<InstrumentRegistry>) -> Self {
+        Self {
+            schedule: RwLock::new(FeeSchedule::default()),
+            account_tiers: DashMap::new(),
+            summaries: DashMap::new(),
+            instruments,
+        }
+    }
+
+    pub fn schedule(&self) -> FeeSchedule {
+        self.schedule.read().clone()
+    }
+
+    pub fn set_schedule(&self, schedule: FeeSchedule) {
+        *self.schedule.write() = schedule;
+    }
+
+    pub fn account_tier(&self, account_id: Uuid) -> AccountTier {
+        self.account_tiers
+            .get(&account_id)
+            .map(|tier| *tier)
+            .unwrap_or_default()
+    }
+
+    pub fn set_account_tier(&self, account_id: Uuid, tier: AccountTier) {
+        self.account_tiers.insert(account_id, tier);
+    }
+
+    /// Computes fees for both sides of a trade according to which side was
+    /// the aggressor, attaches them to the trade and accumulates them into
+    /// the per-account totals.
+    pub fn apply(&self, trade: &mut Trade) {
+        let instrument_type = self.instruments.instrument_type(&trade.symbol);
+        let notional = notional_value(trade.quantity, trade.price);
+
+        trade.buyer_fees = self.calculate(
+            notional,
+            trade.buyer_account_id,
+            instrument_type.as_ref(),
+            trade.liquidity_role(&OrderSide::Buy),
+            OrderSide::Buy,
+        );
+        trade.seller_fees = self.calculate(
+            notional,
+            trade.seller_account_id,
+            instrument_type.as_ref(),
+            trade.liquidity_role(&OrderSide::Sell),
+            OrderSide::Sell,
+        );
+
+        self.record(trade.buyer_account_id, notional, &trade.buyer_fees);
+        self.record(trade.seller_account_id, notional, &trade.seller_fees);
+    }
+
+    pub fn calculate(
+        &self,
+        notional: Decimal,
+        account_id: Uuid,
+        instrument_type: Option<&BondType>,
+        role: LiquidityRole,
+        side: OrderSide,
+    ) -> FeeBreakdown {
+        let schedule = self.schedule.read();
+        let tier = self.account_tier(account_id);
+
+        let Some(rule) = schedule
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(tier, instrument_type))
+            .max_by_key(|rule| rule.specificity())
+        else {
+            return FeeBreakdown {
+                role: Some(role),
+                ..Default::default()
+            };
+        };
+
+        let exchange_rate = match role {
+            LiquidityRole::Maker => rule.maker_rate_bps,
+            LiquidityRole::Taker => rule.taker_rate_bps,
+        };
+        let exchange_fee = round_currency(notional * exchange_rate * BASIS_POINT);
+        let brokerage = round_currency((notional * rule.brokerage_rate_bps * BASIS_POINT).max(rule.min_brokerage));
+        let tax = round_currency((exchange_fee + brokerage) * schedule.tax_rate);
+        let stamp_duty = match side {
+            OrderSide::Buy => round_currency(notional * schedule.stamp_duty_rate_bps * BASIS_POINT),
+            OrderSide::Sell => Decimal::ZERO,
+        };
+
+        FeeBreakdown {
+            role: Some(role),
+            exchange_fee,
+            brokerage,
+            tax,
+            stamp_duty,
+            total: exchange_fee + brokerage + tax + stamp_duty,
+        }
+    }
+
+    fn record(&self, account_id: Uuid, notional: Decimal, fees: &FeeBreakdown) {
+        let mut summary = self.summaries.entry(account_id).or_insert_with(|| AccountFeeSummary {
+            account_id,
+            ..Default::default()
+        });
+        summary.trade_count += 1;
+        summary.notional += notional;
+        summary.exchange_fees += fees.exchange_fee;
+        summary.brokerage += fees.brokerage;
+        summary.tax += fees.tax;
+        summary.stamp_duty += fees.stamp_duty;
+        summary.total += fees.total;
+    }
+
+    pub fn account_summary(&self, account_id: Uuid) -> AccountFeeSummary {
+        self.summaries
+            .get(&account_id)
+            .map(|summary| summary.clone())
+            .unwrap_or(AccountFeeSummary {
+                account_id,
+                ..Default::default()
+            })
+    }
+}
+
+pub(crate) fn round_currency(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::time::TimeProvider;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_most_specific_rule_wins() {
+        let engine = FeeEngine::new(Arc::new(InstrumentRegistry::new(Arc::new(TimeProvider::new()))));
+        let retail = Uuid::new_v4();
+        engine.set_account_tier(retail, AccountTier::Retail);
+
+        // 1,000,000 face at 98.50 => 985,000 notional
+        let notional = notional_value(dec!(1000000), dec!(98.50));
+        let fees = engine.calculate(notional, retail, None, LiquidityRole::Taker, OrderSide::Buy);
+
+        assert_eq!(fees.exchange_fee, dec!(9.85));
+        assert_eq!(fees.brokerage, dec!(98.50));
+        assert_eq!(fees.tax, dec!(19.50));
+        assert_eq!(fees.stamp_duty, dec!(0.99));
+        assert_eq!(fees.total, dec!(128.84));
+    }
+
+    #[test]
+    fn test_minimum_brokerage_and_seller_stamp_duty() {
+        let engine = FeeEngine::new(Arc::new(InstrumentRegistry::new(Arc::new(TimeProvider::new()))));
+        let retail = Uuid::new_v4();
+        engine.set_account_tier(retail, AccountTier::Retail);
+
+        let fees = engine.calculate(dec!(10000), retail, None, LiquidityRole::Maker, OrderSide::Sell);
+
+        assert_eq!(fees.brokerage, dec!(20));
+        assert_eq!(fees.stamp_duty, Decimal::ZERO);
+    }
+}
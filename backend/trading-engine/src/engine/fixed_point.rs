@@ -0,0 +1,186 @@
+//! Scaled integer prices and quantities for the matching engine. Values
+//! are converted on the way into the book and back when trades and depth
+//! are reported, so matching itself never touches `Decimal`.
+
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+
+/// Decimal places an instrument's prices and quantities are held to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TickScale {
+    pub price_decimals: u32,
+    pub quantity_decimals: u32,
+}
+
+impl Default for TickScale {
+    fn default() -> Self {
+        Self {
+            price_decimals: 6,
+            quantity_decimals: 4,
+        }
+    }
+}
+
+/// Above this many decimal places even small values overflow a `u64`.
+pub const MAX_DECIMALS: u32 = 12;
+
+impl TickScale {
+    pub fn is_valid(&self) -> bool {
+        self.price_decimals <= MAX_DECIMALS && self.quantity_decimals <= MAX_DECIMALS
+    }
+
+    /// `None` unless the price is non-negative, fits, and has no more
+    /// decimal places than the scale.
+    pub fn price_ticks(&self, price: Decimal) -> Option<u64> {
+        to_ticks(price, self.price_decimals)
+    }
+
+    pub fn quantity_ticks(&self, quantity: Decimal) -> Option<u64> {
+        to_ticks(quantity, self.quantity_decimals)
+    }
+
+    pub fn price(&self, ticks: u64) -> Decimal {
+        from_ticks(ticks, self.price_decimals)
+    }
+
+    pub fn quantity(&self, ticks: u64) -> Decimal {
+        from_ticks(ticks, self.quantity_decimals)
+    }
+
+    /// Quantity ticks in one whole unit.
+    pub fn lot(&self) -> u64 {
+        10u64.pow(self.quantity_decimals)
+    }
+
+    /// Rounds a derived price, such as a clean price computed from a
+    /// dirty one, to the scale.
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        price.round_dp(self.price_decimals)
+    }
+
+    /// Volume-weighted price of `cost` (price ticks times quantity ticks)
+    /// over `quantity` ticks. The cost is reduced to whole price ticks
+    /// before it becomes a `Decimal`, which could not hold it; only the
+    /// remainder is divided, rounded to `Decimal`'s precision. `None` for
+    /// no quantity, or a cost no price in ticks could add up to.
+    pub fn average_price(&self, cost: u128, quantity: u64) -> Option<Decimal> {
+        let divisor = u128::from(quantity);
+        let whole = u64::try_from(cost.checked_div(divisor)?).ok()?;
+        // Below `quantity`, so it fits a u64 too
+        let remainder = Decimal::from((cost % divisor) as u64) / Decimal::from(quantity);
+        (Decimal::from(whole) + remainder).checked_mul(from_ticks(1, self.price_decimals))
+    }
+}
+
+fn to_ticks(value: Decimal, decimals: u32) -> Option<u64> {
+    if value.is_sign_negative() && !value.is_zero() {
+        return None;
+    }
+    if value.normalize().scale() > decimals {
+        return None;
+    }
+    let scaled = value.checked_mul(Decimal::from(10u64.checked_pow(decimals)?))?;
+    scaled.trunc().to_u64()
+}
+
+fn from_ticks(ticks: u64, decimals: u32) -> Decimal {
+    Decimal::from_i128_with_scale(i128::from(ticks), decimals).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_ticks_round_trip_at_every_scale() {
+        let values = [
+            dec!(0),
+            dec!(0.000001),
+            dec!(1),
+            dec!(99.25),
+            dec!(99.2525),
+            dec!(100.000000),
+            dec!(101.123456),
+            dec!(5000000),
+            dec!(1000000000),
+        ];
+        for decimals in 0..=MAX_DECIMALS {
+            let scale = TickScale {
+                price_decimals: decimals,
+                quantity_decimals: decimals,
+            };
+            for value in values {
+                match scale.price_ticks(value) {
+                    Some(ticks) => {
+                        assert_eq!(scale.price(ticks), value, "{} at {} decimals", value, decimals);
+                        assert_eq!(scale.quantity_ticks(value), Some(ticks));
+                    }
+                    // Only values finer than the scale or too large for it
+                    None => assert!(
+                        value.normalize().scale() > decimals
+                            || value * Decimal::from(10u64.pow(decimals)) > Decimal::from(u64::MAX)
+                    ),
+                }
+            }
+        }
+        for ticks in [0, 1, 9, 10, 99_250_000, u64::MAX / 10, u64::MAX] {
+            let scale = TickScale::default();
+            assert_eq!(scale.price_ticks(scale.price(ticks)), Some(ticks));
+            assert_eq!(scale.quantity_ticks(scale.quantity(ticks)), Some(ticks));
+        }
+    }
+
+    #[test]
+    fn test_ticks_reject_what_the_scale_cannot_hold() {
+        let scale = TickScale::default();
+        assert_eq!(scale.price_ticks(dec!(99.1234567)), None);
+        assert_eq!(scale.price_ticks(dec!(-1)), None);
+        assert_eq!(scale.quantity_ticks(dec!(1e16)), None);
+        assert_eq!(scale.price_ticks(dec!(99.1234560)), Some(99_123_456));
+        assert_eq!(scale.average_price(99_000_000 * 10_000 + 100_000_000 * 30_000, 40_000), Some(dec!(99.75)));
+    }
+
+    #[test]
+    fn test_average_price_holds_at_the_scale_limits() {
+        let finest = TickScale {
+            price_decimals: MAX_DECIMALS,
+            quantity_decimals: MAX_DECIMALS,
+        };
+        // Every tick at the top price: the cost needs all 128 bits
+        let top = u128::from(u64::MAX);
+        assert_eq!(finest.average_price(top * top, u64::MAX), Some(finest.price(u64::MAX)));
+        // A shortfall finer than `Decimal` can carry rounds away
+        assert_eq!(finest.average_price(top * top - 1, u64::MAX), Some(finest.price(u64::MAX)));
+        assert_eq!(finest.average_price(top * top - top, u64::MAX), Some(finest.price(u64::MAX - 1)));
+
+        let coarsest = TickScale {
+            price_decimals: 0,
+            quantity_decimals: 0,
+        };
+        assert_eq!(coarsest.average_price(top * top, u64::MAX), Some(Decimal::from(u64::MAX)));
+        assert_eq!(coarsest.average_price(7, 1), Some(dec!(7)));
+    }
+
+    #[test]
+    fn test_average_price_rounds_the_remainder() {
+        let scale = TickScale::default();
+        // One unit at 100 and two at 101
+        let cost = 100_000_000 * 10_000 + 101_000_000 * 20_000;
+        let average = scale.average_price(cost, 30_000).unwrap();
+        assert_eq!(average, dec!(100.66666666666666666666666667));
+        assert_eq!(scale.round_price(average), dec!(100.666667));
+        // An exact average keeps no spurious digits
+        assert_eq!(scale.average_price(99_250_000 * 3, 3), Some(dec!(99.25)));
+    }
+
+    #[test]
+    fn test_average_price_refuses_what_cannot_be_a_price() {
+        let scale = TickScale::default();
+        assert_eq!(scale.average_price(100, 0), None);
+        assert_eq!(scale.average_price(u128::MAX, 1), None);
+        let top = u128::from(u64::MAX);
+        assert_eq!(scale.average_price((top + 1) * 2, 2), None);
+        assert_eq!(scale.average_price(top * 2, 2), Some(scale.price(u64::MAX)));
+    }
+}
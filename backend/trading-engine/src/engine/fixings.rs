@@ -0,0 +1,164 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A published reference rate, in percent.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Fixing {
+    pub date: NaiveDate,
+    pub rate: Decimal,
+}
+
+/// The rate that applies on a date: the fixing for that date or, when
+/// none was published, the latest one before it. Rates for dates after the
+/// last fixing are projected flat from it.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct AppliedRate {
+    pub fixing: Fixing,
+    pub projected: bool,
+}
+
+/// Reference-rate fixings by index, fed by the admin API or a rates feed.
+#[derive(Default)]
+pub struct FixingStore {
+    indices: DashMap<String, BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl FixingStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a fixing, replacing any earlier one for the same date.
+    pub fn record(&self, index: &str, fixing: Fixing) {
+        self.indices
+            .entry(index.to_string())
+            .or_default()
+            .insert(fixing.date, fixing.rate);
+    }
+
+    pub fn history(&self, index: &str) -> Vec<Fixing> {
+        self.indices
+            .get(index)
+            .map(|fixings| {
+                fixings
+                    .iter()
+                    .map(|(&date, &rate)| Fixing { date, rate })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn indices(&self) -> Vec<String> {
+        let mut indices: Vec<String> = self.indices.iter().map(|entry| entry.key().clone()).collect();
+        indices.sort();
+        indices
+    }
+
+    pub fn rate_on(&self, index: &str, date: NaiveDate) -> Option<AppliedRate> {
+        let fixings = self.indices.get(index)?;
+        let (&fixed_on, &rate) = fixings.range(..=date).next_back()?;
+        let last = fixings.keys().next_back().copied();
+        Some(AppliedRate {
+            fixing: Fixing { date: fixed_on, rate },
+            projected: fixed_on < date && last == Some(fixed_on),
+        })
+    }
+
+    /// Coupon rate in percent for the period starting `period_start`: the
+    /// fixed coupon, or for a floating-rate bond the applicable index rate
+    /// plus the spread.
+    pub fn coupon_rate(&self, bond: &Bond, period_start: NaiveDate) -> Decimal {
+        let Some(terms) = &bond.floating_rate else {
+            return bond.coupon_rate;
+        };
+        let fixing_date = period_start - chrono::Duration::days(terms.fixing_lag_days as i64);
+        match self.rate_on(&terms.index, fixing_date) {
+            Some(applied) => applied.fixing.rate + terms.spread_bps / Decimal::ONE_HUNDRED,
+            None => bond.coupon_rate,
+        }
+    }
+}
+
+impl TradingEngine {
+    pub fn fixings(&self) -> &FixingStore {
+        &self.fixings
+    }
+
+    pub fn record_fixing(&self, index: &str, fixing: Fixing) -> crate::types::Result<()> {
+        if index.trim().is_empty() {
+            return Err(TradingError::InvalidRequest("Index name is required".to_string()));
+        }
+        if fixing.rate < -Decimal::ONE_HUNDRED || fixing.rate > Decimal::ONE_HUNDRED {
+            return Err(TradingError::InvalidRequest(format!("Implausible fixing rate {}", fixing.rate)));
+        }
+        self.fixings.record(index, fixing);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{accrued, analytics};
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_floating_coupons_use_fixings_then_projection() {
+        let frn = Bond {
+            isin: "IN0020200294".to_string(),
+            symbol: "GOIFRB2028".to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2028, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(6.00),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: Some(FloatingRateTerms {
+                index: "TBILL182".to_string(),
+                spread_bps: dec!(125),
+                fixing_lag_days: 1,
+            }),
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        let fixings = FixingStore::new();
+        let settlement = date(2024, 3, 1);
+
+        // No fixings yet: the stated coupon applies
+        assert_eq!(fixings.coupon_rate(&frn, date(2023, 12, 15)), dec!(6.00));
+
+        fixings.record("TBILL182", Fixing { date: date(2023, 6, 14), rate: dec!(6.80) });
+        fixings.record("TBILL182", Fixing { date: date(2023, 12, 14), rate: dec!(7.10) });
+        assert_eq!(fixings.coupon_rate(&frn, date(2023, 12, 15)), dec!(8.35));
+        assert_eq!(fixings.coupon_rate(&frn, date(2023, 6, 15)), dec!(8.05));
+        // The period starting in June has no fixing yet and is projected
+        let applied = fixings.rate_on("TBILL182", date(2024, 6, 14)).unwrap();
+        assert!(applied.projected);
+        assert_eq!(applied.fixing.rate, dec!(7.10));
+        assert!(!fixings.rate_on("TBILL182", date(2023, 12, 14)).unwrap().projected);
+
+        // 76 days at 8.35%
+        assert_eq!(
+            accrued::accrued_interest(&frn, settlement, &fixings),
+            dec!(8.35) * (dec!(76) / dec!(360))
+        );
+
+        // Priced at par on a reset date, the bond yields index plus spread
+        let ytm = analytics::yield_to_maturity(&frn, dec!(100), date(2023, 12, 15), &fixings).unwrap();
+        assert_eq!(ytm, dec!(8.35));
+    }
+}
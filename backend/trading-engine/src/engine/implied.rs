@@ -0,0 +1,236 @@
+use crate::{
+    engine::{
+        spreads::{SpreadLeg, SpreadOrder},
+        TradingEngine,
+    },
+    types::*,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Outright liquidity implied by a resting spread order together with the
+/// best outright prices on its other legs (implied-out).
+#[derive(Debug, Clone, Serialize)]
+pub struct ImpliedQuote {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub spread_order_id: Uuid,
+}
+
+/// Spread liquidity implied by the best outright prices on every leg
+/// (implied-in). Quantity is in spread units.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImpliedLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+impl TradingEngine {
+    /// Implied-out quotes for `symbol`, one per resting spread order that
+    /// can currently be completed against the other legs' books.
+    pub fn implied_quotes(&self, symbol: &str) -> Vec<ImpliedQuote> {
+        self.spread_book
+            .resting(|legs| legs.iter().any(|leg| leg.symbol == symbol))
+            .iter()
+            .filter_map(|spread| self.implied_quote(spread, symbol))
+            .collect()
+    }
+
+    fn implied_quote(&self, spread: &SpreadOrder, symbol: &str) -> Option<ImpliedQuote> {
+        let target = spread.leg(symbol)?;
+        let mut others = Decimal::ZERO;
+        let mut spread_quantity = spread.remaining_quantity;
+        for leg in spread.legs.iter().filter(|leg| leg.symbol != symbol) {
+            let (price, available) = self.leg_touch(leg, &spread.side)?;
+            others += Decimal::from(leg.ratio) * price;
+            spread_quantity = spread_quantity.min(available / Decimal::from(leg.ratio.unsigned_abs()));
+        }
+
+        let price = (spread.price - others) / Decimal::from(target.ratio);
+        (price > Decimal::ZERO && spread_quantity > Decimal::ZERO).then(|| ImpliedQuote {
+            symbol: symbol.to_string(),
+            side: target.side(&spread.side),
+            price,
+            quantity: target.quantity(spread_quantity),
+            spread_order_id: spread.id,
+        })
+    }
+
+    /// Best contra price and size for one leg of a spread traded on
+    /// `spread_side`. A leg that buys lifts the offer; one that sells hits
+    /// the bid.
+    fn leg_touch(&self, leg: &SpreadLeg, spread_side: &OrderSide) -> Option<(Decimal, Decimal)> {
+        let book_side = match leg.side(spread_side) {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        self.matching_engine.best_level(&leg.symbol, &book_side)
+    }
+
+    /// Net price and size at which a spread order on `spread_side` could
+    /// trade now against the top of every outright book.
+    pub(crate) fn implied_spread_level(&self, legs: &[SpreadLeg], spread_side: &OrderSide) -> Option<ImpliedLevel> {
+        let mut price = Decimal::ZERO;
+        let mut quantity: Option<Decimal> = None;
+        for leg in legs {
+            let (leg_price, available) = self.leg_touch(leg, spread_side)?;
+            price += Decimal::from(leg.ratio) * leg_price;
+            let units = available / Decimal::from(leg.ratio.unsigned_abs());
+            quantity = Some(quantity.map_or(units, |quantity| quantity.min(units)));
+        }
+        Some(ImpliedLevel {
+            price,
+            quantity: quantity?,
+        })
+    }
+
+    /// Direct price levels for `symbol` merged with implied-out levels.
+    /// Direct liquidity is listed ahead of implied at the same price.
+    pub(crate) fn book_with_implied(&self, symbol: &str) -> Option<(Vec<PriceLevel>, Vec<PriceLevel>)> {
+        let (mut bids, mut asks) = self.matching_engine.depth(symbol)?;
+        for quote in self.implied_quotes(symbol) {
+            let levels = match quote.side {
+                OrderSide::Buy => &mut bids,
+                OrderSide::Sell => &mut asks,
+            };
+            match levels
+                .iter_mut()
+                .find(|level| level.implied && level.price == quote.price)
+            {
+                Some(level) => {
+                    level.quantity += quote.quantity;
+                    level.order_count += 1;
+                }
+                None => levels.push(PriceLevel {
+                    price: quote.price,
+                    quantity: quote.quantity,
+                    order_count: 1,
+                    implied: true,
+                }),
+            }
+        }
+
+        bids.sort_by(|a, b| b.price.cmp(&a.price).then(a.implied.cmp(&b.implied)));
+        asks.sort_by(|a, b| a.price.cmp(&b.price).then(a.implied.cmp(&b.implied)));
+        Some((bids, asks))
+    }
+
+    /// Trades an incoming outright order against implied quotes that beat
+    /// the direct touch. Each fill executes the spread's leg against the
+    /// order and its other legs against the outright books, all or nothing.
+    pub(crate) fn match_implied(&self, order: &mut Order) -> Vec<Trade> {
+        let contra = match order.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        // Whether `price` is better than `than` from the order's point of view
+        let side = order.side.clone();
+        let improves = |price: Decimal, than: Decimal| match side {
+            OrderSide::Buy => price < than,
+            OrderSide::Sell => price > than,
+        };
+
+        let mut trades = Vec::new();
+        while order.remaining_quantity > Decimal::ZERO {
+            let best = self
+                .implied_quotes(&order.symbol)
+                .into_iter()
+                .filter(|quote| quote.side == contra)
+                .min_by(|a, b| match contra {
+                    OrderSide::Sell => a.price.cmp(&b.price),
+                    OrderSide::Buy => b.price.cmp(&a.price),
+                });
+            let Some(quote) = best else {
+                break;
+            };
+
+            let crosses = order.price.is_none_or(|limit| !improves(limit, quote.price));
+            let beats_direct = self
+                .matching_engine
+                .best_level(&order.symbol, &contra)
+                .is_none_or(|(touch, _)| improves(quote.price, touch));
+            if !crosses || !beats_direct {
+                break;
+            }
+
+            let Some(filled) = self.execute_implied_quote(order, &quote) else {
+                break;
+            };
+            trades.extend(filled);
+        }
+        trades
+    }
+
+    fn execute_implied_quote(&self, order: &mut Order, quote: &ImpliedQuote) -> Option<Vec<Trade>> {
+        let target = self.spread_book.get(&quote.spread_order_id)?.leg(&order.symbol)?.clone();
+        let spread_quantity = order.remaining_quantity.min(quote.quantity) / Decimal::from(target.ratio.unsigned_abs());
+        let now = self.time_provider.now();
+        let (limit, side) = (order.price, order.side.clone());
+
+        let (spread, (children, leg_trades, price)) =
+            self.spread_book
+                .fill_with(quote.spread_order_id, spread_quantity, |spread| {
+                    let others: Vec<&SpreadLeg> = spread
+                        .legs
+                        .iter()
+                        .filter(|leg| leg.symbol != target.symbol)
+                        .collect();
+                    let mut children: Vec<Order> = others
+                        .iter()
+                        .map(|leg| spread.child_order(leg, spread_quantity, now))
+                        .collect();
+
+                    // The spread trades at exactly its own price; the leg
+                    // hit by the incoming order absorbs the difference.
+                    let mut target_price = None;
+                    let executed = self.matching_engine.execute_legs(&mut children, |vwaps| {
+                        let others: Decimal = others
+                            .iter()
+                            .zip(vwaps)
+                            .map(|(leg, &price)| Decimal::from(leg.ratio) * price)
+                            .sum();
+                        let price = (spread.price - others) / Decimal::from(target.ratio);
+                        target_price = Some(price);
+                        price > Decimal::ZERO
+                            && limit.is_none_or(|limit| match side {
+                                OrderSide::Buy => price <= limit,
+                                OrderSide::Sell => price >= limit,
+                            })
+                    });
+                    let trades = match executed {
+                        Ok(trades) => trades?,
+                        Err(e) => {
+                            warn!("Implied quote on spread {} cannot trade: {}", spread.id, e);
+                            return None;
+                        }
+                    };
+                    Some((children, trades, target_price?))
+                })?;
+
+        let quantity = target.quantity(spread_quantity);
+        let resting = spread.leg_order(&target, price);
+        let mut trade = self.matching_engine.build_trade(order, &resting, quantity, price);
+        trade.trade_type = TradeType::SpreadLeg;
+        self.matching_engine.publish_trade(&trade, order, &resting);
+
+        order.remaining_quantity -= quantity;
+        order.filled_quantity += quantity;
+        order.fills.record(quantity, price, order.filled_quantity);
+        order.status = if order.remaining_quantity <= Decimal::ZERO {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+
+        self.store_child_orders(children);
+        self.publish_spread_order(&spread);
+
+        let mut trades = vec![trade];
+        trades.extend(leg_trades);
+        Some(trades)
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MAX_INDEX_RATIO_DAYS: i64 = 366;
+
+/// A published monthly level of a price index. `month` may be any day of
+/// the month it is for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct IndexLevel {
+    pub month: NaiveDate,
+    pub value: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct IndexRatio {
+    pub date: NaiveDate,
+    pub reference_index: Decimal,
+    pub index_ratio: Decimal,
+}
+
+fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("every month has a first day")
+}
+
+fn days_in_month(date: NaiveDate) -> i64 {
+    let start = month_start(date);
+    (start + Months::new(1) - start).num_days()
+}
+
+/// Monthly price index levels, e.g. CPI prints, by index name.
+#[derive(Default)]
+pub struct InflationIndexStore {
+    series: DashMap<String, BTreeMap<NaiveDate, Decimal>>,
+}
+
+impl InflationIndexStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a level, replacing a revised one for the same month.
+    pub fn record(&self, index: &str, level: IndexLevel) {
+        self.series
+            .entry(index.to_string())
+            .or_default()
+            .insert(month_start(level.month), level.value);
+    }
+
+    pub fn history(&self, index: &str) -> Vec<IndexLevel> {
+        self.series
+            .get(index)
+            .map(|series| {
+                series
+                    .iter()
+                    .map(|(&month, &value)| IndexLevel { month, value })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Reference index for `date`: the level `lag_months` before its month,
+    /// moved linearly toward the following month's level by the day of the
+    /// month. Holds the earlier level flat until the later one is published.
+    pub fn reference_index(&self, index: &str, date: NaiveDate, lag_months: u32) -> Option<Decimal> {
+        let series = self.series.get(index)?;
+        let base_month = month_start(date).checked_sub_months(Months::new(lag_months))?;
+        let base = *series.get(&base_month)?;
+        let Some(&next) = series.get(&(base_month + Months::new(1))) else {
+            return Some(base);
+        };
+        let elapsed = Decimal::from(date.day() as i64 - 1) / Decimal::from(days_in_month(date));
+        Some(base + (next - base) * elapsed)
+    }
+
+    /// Index ratio of an inflation-linked bond on `date`. None for other
+    /// bonds and while the index has no level for the date.
+    pub fn index_ratio(&self, bond: &Bond, date: NaiveDate) -> Option<Decimal> {
+        let terms = bond.inflation_linked.as_ref()?;
+        if terms.base_index <= Decimal::ZERO {
+            return None;
+        }
+        let reference = self.reference_index(&terms.index, date, terms.lag_months)?;
+        Some(reference / terms.base_index)
+    }
+}
+
+impl TradingEngine {
+    pub fn inflation_indices(&self) -> &InflationIndexStore {
+        &self.inflation_indices
+    }
+
+    pub fn record_index_level(&self, index: &str, level: IndexLevel) -> crate::types::Result<()> {
+        if index.trim().is_empty() {
+            return Err(TradingError::InvalidRequest("Index name is required".to_string()));
+        }
+        if level.value <= Decimal::ZERO {
+            return Err(TradingError::InvalidRequest(format!("Invalid index level {}", level.value)));
+        }
+        self.inflation_indices.record(index, level);
+        Ok(())
+    }
+
+    /// Daily index ratios of an inflation-linked instrument from `from` to
+    /// `to`, skipping days the index does not yet cover.
+    pub fn index_ratios(&self, symbol: &str, from: NaiveDate, to: NaiveDate) -> crate::types::Result<Vec<IndexRatio>> {
+        let bond = self
+            .instruments
+            .get(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        let Some(terms) = &bond.inflation_linked else {
+            return Err(TradingError::InvalidRequest(format!("{} is not inflation-linked", symbol)));
+        };
+        if to < from || (to - from).num_days() > MAX_INDEX_RATIO_DAYS {
+            return Err(TradingError::InvalidRequest(format!(
+                "Date range must be ordered and at most {} days",
+                MAX_INDEX_RATIO_DAYS
+            )));
+        }
+
+        Ok((0..=(to - from).num_days())
+            .map(|offset| from + Duration::days(offset))
+            .filter_map(|date| {
+                let reference_index = self
+                    .inflation_indices
+                    .reference_index(&terms.index, date, terms.lag_months)?;
+                Some(IndexRatio {
+                    date,
+                    reference_index,
+                    index_ratio: self.inflation_indices.index_ratio(&bond, date)?,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_index_ratio_interpolates_lagged_levels() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let linker = Bond {
+            isin: "IN0020130103".to_string(),
+            symbol: "IIB2028".to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2028, 12, 25, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(1.50),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: Some(InflationTerms {
+                index: "CPI-C".to_string(),
+                base_index: dec!(150),
+                lag_months: 3,
+            }),
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        engine
+            .list_instrument(linker, crate::engine::allocation::MatchingAlgorithm::PriceTime)
+            .unwrap();
+        engine
+            .record_index_level("CPI-C", IndexLevel { month: date(2023, 12, 1), value: dec!(186) })
+            .unwrap();
+        engine
+            .record_index_level("CPI-C", IndexLevel { month: date(2024, 1, 20), value: dec!(189.10) })
+            .unwrap();
+        assert!(engine
+            .record_index_level("CPI-C", IndexLevel { month: date(2024, 2, 1), value: dec!(0) })
+            .is_err());
+
+        let ratios = engine.index_ratios("IIB2028", date(2024, 2, 28), date(2024, 4, 16)).unwrap();
+        // February needs November; March interpolates December to January
+        assert_eq!(ratios.first().unwrap().date, date(2024, 3, 1));
+        assert_eq!(ratios[0].reference_index, dec!(186));
+        assert_eq!(ratios[0].index_ratio, dec!(1.24));
+        // Halfway through March: 186 + 3.10 * 15/31
+        assert_eq!(ratios[15].reference_index, dec!(186) + dec!(3.10) * (dec!(15) / dec!(31)));
+        // April holds January flat until February is published
+        let last = ratios.last().unwrap();
+        assert_eq!((last.date, last.reference_index), (date(2024, 4, 16), dec!(189.10)));
+        assert!(engine.index_ratios("IIB2028", date(2024, 3, 1), date(2026, 3, 1)).is_err());
+    }
+}
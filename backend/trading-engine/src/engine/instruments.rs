@@ -0,0 +1,320 @@
+use crate::{
+    engine::{
+        allocation::{FirmPreference, MatchingAlgorithm},
+        fixed_point::TickScale,
+    },
+    types::*,
+    utils::time::TimeProvider};
+use chrono::{DateTime, Utc};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Identifier schemes clients may use in place of the internal symbol.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AliasKind {
+    Isin,
+    Cusip,
+}
+
+impl AliasKind {
+    fn length(&self) -> usize {
+        match self {
+            AliasKind::Isin => 12,
+            AliasKind::Cusip => 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AliasRequest {
+    pub alias: String,
+    pub kind: AliasKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolAlias {
+    pub alias: String,
+    pub kind: AliasKind,
+    pub symbol: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum InstrumentStatus {
+    Active,
+    Suspended,
+    Auction,
+    Delisted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Instrument {
+    pub bond: Bond,
+    pub status: InstrumentStatus,
+    pub listed_at: DateTime<Utc>,
+    pub status_changed_at: DateTime<Utc>,
+    pub status_reason: Option<String>,
+    pub matching_algorithm: MatchingAlgorithm,
+    pub firm_preference: FirmPreference,
+    pub tick_scale: TickScale,
+}
+
+/// Reference data and lifecycle state for tradable instruments, keyed by
+/// symbol.
+pub struct InstrumentRegistry {
+    instruments: DashMap<String, Instrument>,
+    /// Upper-cased alias to the symbol it names.
+    aliases: DashMap<String, SymbolAlias>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl InstrumentRegistry {
+    pub fn new(time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            instruments: DashMap::new(),
+            aliases: DashMap::new(),
+            time_provider,
+        }
+    }
+
+    /// Lists an instrument as active. Relisting is allowed only once the
+    /// previous listing under the same symbol has been delisted. The bond's
+    /// ISIN becomes an alias for the symbol unless it names another one.
+    pub fn register(
+        &self,
+        bond: Bond,
+        matching_algorithm: MatchingAlgorithm,
+    ) -> crate::types::Result<Instrument> {
+        if let Some(existing) = self.instruments.get(&bond.symbol) {
+            if existing.status != InstrumentStatus::Delisted {
+                return Err(TradingError::InvalidRequest(format!(
+                    "Instrument {} is already listed",
+                    bond.symbol
+                )));
+            }
+        }
+
+        let now = self.time_provider.now();
+        let instrument = Instrument {
+            bond: Bond {
+                is_active: true,
+                ..bond
+            },
+            status: InstrumentStatus::Active,
+            listed_at: now,
+            status_changed_at: now,
+            status_reason: None,
+            matching_algorithm,
+            firm_preference: FirmPreference::default(),
+            tick_scale: TickScale::default(),
+        };
+        self.instruments
+            .insert(instrument.bond.symbol.clone(), instrument.clone());
+        let isin = AliasRequest {
+            alias: instrument.bond.isin.clone(),
+            kind: AliasKind::Isin,
+        };
+        if !isin.alias.is_empty() {
+            let _ = self.add_alias(&instrument.bond.symbol, isin);
+        }
+
+        Ok(instrument)
+    }
+
+    pub fn set_status(
+        &self,
+        symbol: &str,
+        status: InstrumentStatus,
+        reason: Option<String>,
+    ) -> crate::types::Result<Instrument> {
+        let mut instrument = self
+            .instruments
+            .get_mut(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+
+        let allowed = matches!(
+            (instrument.status, status),
+            (InstrumentStatus::Active, InstrumentStatus::Suspended)
+                | (InstrumentStatus::Suspended, InstrumentStatus::Active)
+                | (InstrumentStatus::Active, InstrumentStatus::Auction)
+                | (InstrumentStatus::Suspended, InstrumentStatus::Auction)
+                | (InstrumentStatus::Auction, InstrumentStatus::Active)
+                | (InstrumentStatus::Auction, InstrumentStatus::Suspended)
+                | (InstrumentStatus::Active, InstrumentStatus::Delisted)
+                | (InstrumentStatus::Suspended, InstrumentStatus::Delisted)
+                | (InstrumentStatus::Auction, InstrumentStatus::Delisted)
+        );
+        if !allowed {
+            return Err(TradingError::InvalidRequest(format!(
+                "Cannot move {} from {:?} to {:?}",
+                symbol, instrument.status, status
+            )));
+        }
+
+        instrument.status = status;
+        instrument.status_changed_at = self.time_provider.now();
+        instrument.status_reason = reason;
+        instrument.bond.is_active = status == InstrumentStatus::Active;
+
+        Ok(instrument.clone())
+    }
+
+    pub fn set_matching_algorithm(
+        &self,
+        symbol: &str,
+        matching_algorithm: MatchingAlgorithm,
+    ) -> crate::types::Result<Instrument> {
+        let mut instrument = self
+            .instruments
+            .get_mut(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        instrument.matching_algorithm = matching_algorithm;
+        Ok(instrument.clone())
+    }
+
+    pub fn set_firm_preference(
+        &self,
+        symbol: &str,
+        firm_preference: FirmPreference,
+    ) -> crate::types::Result<Instrument> {
+        let mut instrument = self
+            .instruments
+            .get_mut(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        instrument.firm_preference = firm_preference;
+        Ok(instrument.clone())
+    }
+
+    pub fn set_tick_scale(&self, symbol: &str, tick_scale: TickScale) -> crate::types::Result<Instrument> {
+        let mut instrument = self
+            .instruments
+            .get_mut(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        instrument.tick_scale = tick_scale;
+        Ok(instrument.clone())
+    }
+
+    /// Amends the reference data of a listed instrument.
+    pub fn update_bond(&self, symbol: &str, update: impl FnOnce(&mut Bond)) -> crate::types::Result<Instrument> {
+        let mut instrument = self
+            .instruments
+            .get_mut(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        update(&mut instrument.bond);
+        Ok(instrument.clone())
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<Bond> {
+        self.instruments.get(symbol).map(|instrument| instrument.bond.clone())
+    }
+
+    pub fn instrument(&self, symbol: &str) -> Option<Instrument> {
+        self.instruments.get(symbol).map(|instrument| instrument.clone())
+    }
+
+    pub fn status(&self, symbol: &str) -> Option<InstrumentStatus> {
+        self.instruments.get(symbol).map(|instrument| instrument.status)
+    }
+
+    pub fn matching_algorithm(&self, symbol: &str) -> Option<MatchingAlgorithm> {
+        self.instruments
+            .get(symbol)
+            .map(|instrument| instrument.matching_algorithm)
+    }
+
+    pub fn firm_preference(&self, symbol: &str) -> FirmPreference {
+        self.instruments
+            .get(symbol)
+            .map(|instrument| instrument.firm_preference)
+            .unwrap_or_default()
+    }
+
+    pub fn instrument_type(&self, symbol: &str) -> Option<BondType> {
+        self.instruments
+            .get(symbol)
+            .map(|instrument| instrument.bond.bond_type.clone())
+    }
+
+    pub fn list(&self) -> Vec<Instrument> {
+        self.instruments.iter().map(|entry| entry.value().clone()).collect()
+    }
+
+    /// The internal symbol for a symbol or alias, or the key itself if it
+    /// is neither, so lookups by it fail as they did before.
+    pub fn canonical(&self, key: &str) -> String {
+        if self.instruments.contains_key(key) {
+            return key.to_string();
+        }
+        self.aliases
+            .get(&key.to_uppercase())
+            .map_or_else(|| key.to_string(), |alias| alias.symbol.clone())
+    }
+
+    pub fn add_alias(&self, symbol: &str, request: AliasRequest) -> crate::types::Result<SymbolAlias> {
+        if !self.instruments.contains_key(symbol) {
+            return Err(TradingError::InstrumentNotFound(symbol.to_string()));
+        }
+        let alias = request.alias.trim().to_uppercase();
+        if alias.len() != request.kind.length() || !alias.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(TradingError::InvalidRequest(format!(
+                "{} is not a valid {:?}: expected {} letters or digits",
+                alias,
+                request.kind,
+                request.kind.length()
+            )));
+        }
+        if self.instruments.contains_key(&alias) {
+            return Err(TradingError::InvalidRequest(format!("{} is already a symbol", alias)));
+        }
+        let entry = SymbolAlias {
+            alias: alias.clone(),
+            kind: request.kind,
+            symbol: symbol.to_string(),
+        };
+        match self.aliases.entry(alias) {
+            Entry::Occupied(existing) if existing.get().symbol != symbol => Err(TradingError::InvalidRequest(format!(
+                "{} is already an alias of {}",
+                existing.key(),
+                existing.get().symbol
+            ))),
+            Entry::Occupied(mut existing) => {
+                existing.insert(entry.clone());
+                Ok(entry)
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(entry.clone());
+                Ok(entry)
+            }
+        }
+    }
+
+    pub fn remove_alias(&self, alias: &str) -> crate::types::Result<SymbolAlias> {
+        self.aliases
+            .remove(&alias.to_uppercase())
+            .map(|(_, alias)| alias)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("No alias {}", alias)))
+    }
+
+    pub fn aliases(&self, symbol: &str) -> Vec<SymbolAlias> {
+        let mut aliases: Vec<SymbolAlias> = self
+            .aliases
+            .iter()
+            .filter(|entry| entry.symbol == symbol)
+            .map(|entry| entry.value().clone())
+            .collect();
+        aliases.sort_by(|a, b| a.alias.cmp(&b.alias));
+        aliases
+    }
+
+    /// Active or suspended instruments whose maturity has passed.
+    pub fn matured(&self) -> Vec<String> {
+        let now = self.time_provider.now();
+        self.instruments
+            .iter()
+            .filter(|entry| {
+                entry.status != InstrumentStatus::Delisted && entry.bond.maturity_date <= now
+            })
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+}
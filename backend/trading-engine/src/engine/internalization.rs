@@ -0,0 +1,54 @@
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Which broker firm each account trades through. Only accounts with a firm
+/// take part in internal crossing.
+pub struct FirmRegistry {
+    firms: DashMap<Uuid, String>,
+}
+
+impl FirmRegistry {
+    pub fn new() -> Self {
+        Self {
+            firms: DashMap::new(),
+        }
+    }
+
+    pub fn set_firm(&self, account_id: Uuid, firm_id: Option<String>) {
+        match firm_id {
+            Some(firm_id) => {
+                self.firms.insert(account_id, firm_id);
+            }
+            None => {
+                self.firms.remove(&account_id);
+            }
+        }
+    }
+
+    pub fn firm_of(&self, account_id: Uuid) -> Option<String> {
+        self.firms.get(&account_id).map(|firm| firm.clone())
+    }
+
+    pub fn accounts_of(&self, firm_id: &str) -> Vec<Uuid> {
+        self.firms
+            .iter()
+            .filter(|entry| entry.value() == firm_id)
+            .map(|entry| *entry.key())
+            .collect()
+    }
+
+    /// Different accounts of the same firm. An account never crosses with
+    /// itself.
+    pub fn same_firm(&self, a: Uuid, b: Uuid) -> bool {
+        a != b
+            && self
+                .firm_of(a)
+                .is_some_and(|firm| self.firm_of(b).is_some_and(|other| other == firm))
+    }
+}
+
+impl Default for FirmRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
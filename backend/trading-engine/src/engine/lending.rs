@@ -0,0 +1,356 @@
+use crate::{
+    engine::{
+        fees::{round_currency, BASIS_POINT},
+        TradingEngine,
+    },
+    types::*,
+    utils::time::TimeProvider,
+};
+use chrono::NaiveDate;
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+const DAYS_PER_YEAR: Decimal = Decimal::from_parts(365, 0, 0, false, 0);
+/// Longest loan term accepted, in days.
+pub const MAX_TERM_DAYS: u32 = 3650;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LoanStatus {
+    Open,
+    Returned,
+}
+
+/// A securities loan. Fees accrue daily on the face value lent at the
+/// annual `rate_bps`, actual/365.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanContract {
+    pub id: Uuid,
+    pub symbol: String,
+    pub lender_account_id: Uuid,
+    pub borrower_account_id: Uuid,
+    pub quantity: Decimal,
+    pub rate_bps: Decimal,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub status: LoanStatus,
+    pub accrued_fees: Decimal,
+    pub accrued_through: NaiveDate,
+    pub returned_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoanRequest {
+    pub symbol: String,
+    pub lender_account_id: Uuid,
+    pub borrower_account_id: Uuid,
+    pub quantity: Decimal,
+    pub rate_bps: Decimal,
+    pub term_days: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LendableInventory {
+    pub account_id: Uuid,
+    pub symbol: String,
+    pub lendable: Decimal,
+    pub on_loan: Decimal,
+}
+
+impl LendableInventory {
+    pub fn available(&self) -> Decimal {
+        self.lendable - self.on_loan
+    }
+}
+
+/// Lendable inventory and the loans drawn against it.
+pub struct LendingDesk {
+    inventory: DashMap<(Uuid, String), LendableInventory>,
+    contracts: DashMap<Uuid, LoanContract>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl LendingDesk {
+    pub fn new(time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            inventory: DashMap::new(),
+            contracts: DashMap::new(),
+            time_provider,
+        }
+    }
+
+    /// Sets how much of a holding the account makes available to lend.
+    /// It cannot drop below what is already out on loan.
+    pub fn set_lendable(
+        &self,
+        account_id: Uuid,
+        symbol: &str,
+        lendable: Decimal,
+    ) -> crate::types::Result<LendableInventory> {
+        if lendable < Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Lendable quantity cannot be negative".to_string()));
+        }
+
+        let mut entry = self
+            .inventory
+            .entry((account_id, symbol.to_string()))
+            .or_insert_with(|| LendableInventory {
+                account_id,
+                symbol: symbol.to_string(),
+                lendable: Decimal::ZERO,
+                on_loan: Decimal::ZERO,
+            });
+        if lendable < entry.on_loan {
+            return Err(TradingError::InvalidRequest(format!(
+                "{} already on loan exceeds requested lendable {}",
+                entry.on_loan, lendable
+            )));
+        }
+        entry.lendable = lendable;
+        Ok(entry.clone())
+    }
+
+    pub fn inventory(&self, account_id: Option<Uuid>) -> Vec<LendableInventory> {
+        self.inventory
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == id))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    pub fn create_loan(&self, request: LoanRequest) -> crate::types::Result<LoanContract> {
+        if request.quantity <= Decimal::ZERO || request.term_days == 0 {
+            return Err(TradingError::InvalidRequest(
+                "Loan quantity and term must be positive".to_string(),
+            ));
+        }
+        if request.rate_bps < Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Loan rate cannot be negative".to_string()));
+        }
+        if request.term_days > MAX_TERM_DAYS {
+            return Err(TradingError::InvalidRequest(format!(
+                "Loan term may be at most {} days",
+                MAX_TERM_DAYS
+            )));
+        }
+        if request.lender_account_id == request.borrower_account_id {
+            return Err(TradingError::InvalidRequest("An account cannot borrow from itself".to_string()));
+        }
+        let today = self.time_provider.today();
+        let end_date = today
+            .checked_add_signed(chrono::Duration::days(request.term_days.into()))
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Loan term of {} days is out of range", request.term_days)))?;
+
+        let key = (request.lender_account_id, request.symbol.clone());
+        let mut inventory = self.inventory.get_mut(&key).ok_or(TradingError::InsufficientBalance {
+            required: request.quantity,
+            available: Decimal::ZERO,
+        })?;
+        if inventory.available() < request.quantity {
+            return Err(TradingError::InsufficientBalance {
+                required: request.quantity,
+                available: inventory.available(),
+            });
+        }
+        inventory.on_loan += request.quantity;
+
+        let contract = LoanContract {
+            id: Uuid::new_v4(),
+            symbol: request.symbol,
+            lender_account_id: request.lender_account_id,
+            borrower_account_id: request.borrower_account_id,
+            quantity: request.quantity,
+            rate_bps: request.rate_bps,
+            start_date: today,
+            end_date,
+            status: LoanStatus::Open,
+            accrued_fees: Decimal::ZERO,
+            accrued_through: today,
+            returned_on: None,
+        };
+        self.contracts.insert(contract.id, contract.clone());
+
+        info!(
+            "Loan {} opened: {} {} from {} to {}",
+            contract.id, contract.quantity, contract.symbol, contract.lender_account_id, contract.borrower_account_id
+        );
+        Ok(contract)
+    }
+
+    /// Closes a loan early, accruing fees up to today first.
+    pub fn return_loan(&self, contract_id: Uuid) -> crate::types::Result<LoanContract> {
+        let today = self.time_provider.today();
+        let mut contract = self
+            .contracts
+            .get_mut(&contract_id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Loan {} not found", contract_id)))?;
+        if contract.status != LoanStatus::Open {
+            return Err(TradingError::InvalidRequest(format!("Loan {} is already returned", contract_id)));
+        }
+
+        accrue_contract(&mut contract, today);
+        self.close(&mut contract, today);
+        Ok(contract.clone())
+    }
+
+    /// Accrues every open loan through today and returns loans that have
+    /// reached their end date. Safe to call repeatedly; each day accrues
+    /// once.
+    pub fn accrue_fees(&self) -> Vec<Uuid> {
+        let today = self.time_provider.today();
+        let mut matured = Vec::new();
+        for mut contract in self.contracts.iter_mut() {
+            if contract.status != LoanStatus::Open {
+                continue;
+            }
+            let through = today.min(contract.end_date);
+            accrue_contract(&mut contract, through);
+            let end_date = contract.end_date;
+            if today >= end_date {
+                self.close(&mut contract, end_date);
+                matured.push(contract.id);
+            }
+        }
+        matured
+    }
+
+    pub fn contracts(&self, account_id: Option<Uuid>) -> Vec<LoanContract> {
+        let mut contracts: Vec<LoanContract> = self
+            .contracts
+            .iter()
+            .filter(|entry| {
+                account_id.is_none_or(|id| entry.lender_account_id == id || entry.borrower_account_id == id)
+            })
+            .map(|entry| entry.value().clone())
+            .collect();
+        contracts.sort_by_key(|contract| (contract.start_date, contract.id));
+        contracts
+    }
+
+    /// Net quantity an account has received (positive) or given out
+    /// (negative) through open loans in a symbol.
+    pub fn net_borrowed(&self, account_id: Uuid, symbol: &str) -> Decimal {
+        self.contracts
+            .iter()
+            .filter(|entry| entry.status == LoanStatus::Open && entry.symbol == symbol)
+            .map(|entry| {
+                if entry.borrower_account_id == account_id {
+                    entry.quantity
+                } else if entry.lender_account_id == account_id {
+                    -entry.quantity
+                } else {
+                    Decimal::ZERO
+                }
+            })
+            .sum()
+    }
+
+    fn close(&self, contract: &mut LoanContract, on: NaiveDate) {
+        contract.status = LoanStatus::Returned;
+        contract.returned_on = Some(on);
+        if let Some(mut inventory) = self
+            .inventory
+            .get_mut(&(contract.lender_account_id, contract.symbol.clone()))
+        {
+            inventory.on_loan -= contract.quantity;
+        }
+        info!("Loan {} returned", contract.id);
+    }
+}
+
+fn accrue_contract(contract: &mut LoanContract, through: NaiveDate) {
+    let days = (through - contract.accrued_through).num_days();
+    if days <= 0 {
+        return;
+    }
+    let fee = contract.quantity * contract.rate_bps * BASIS_POINT * Decimal::from(days) / DAYS_PER_YEAR;
+    contract.accrued_fees = round_currency(contract.accrued_fees + fee);
+    contract.accrued_through = through;
+}
+
+impl TradingEngine {
+    /// Quantity an account can deliver into settlement: its traded position
+    /// plus securities borrowed, less securities lent out.
+    pub async fn deliverable_quantity(&self, account_id: Uuid, symbol: &str) -> Decimal {
+        let position = self
+            .get_position(account_id, symbol)
+            .await
+            .map(|position| position.quantity)
+            .unwrap_or_default();
+        position + self.lending.net_borrowed(account_id, symbol)
+    }
+
+    pub fn lending(&self) -> &LendingDesk {
+        &self.lending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::time::SimulatedClock;
+    use chrono::{Duration, TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_loan_draws_inventory_and_accrues_daily() {
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()));
+        let desk = LendingDesk::new(Arc::new(TimeProvider::with_clock(clock.clone())));
+        let lender = Uuid::new_v4();
+        let borrower = Uuid::new_v4();
+        desk.set_lendable(lender, "GSEC10Y", dec!(1000000)).unwrap();
+
+        let loan = desk
+            .create_loan(LoanRequest {
+                symbol: "GSEC10Y".to_string(),
+                lender_account_id: lender,
+                borrower_account_id: borrower,
+                quantity: dec!(730000),
+                rate_bps: dec!(50),
+                term_days: 3,
+            })
+            .unwrap();
+        assert_eq!(desk.inventory(Some(lender))[0].available(), dec!(270000));
+        assert_eq!(desk.net_borrowed(borrower, "GSEC10Y"), dec!(730000));
+        assert_eq!(desk.net_borrowed(lender, "GSEC10Y"), dec!(-730000));
+
+        // 730,000 at 50bp/year => 10 per day
+        clock.advance(Duration::days(1));
+        desk.accrue_fees();
+        desk.accrue_fees();
+        assert_eq!(desk.contracts(None)[0].accrued_fees, dec!(10));
+
+        clock.advance(Duration::days(5));
+        assert_eq!(desk.accrue_fees(), vec![loan.id]);
+        let returned = &desk.contracts(Some(borrower))[0];
+        assert_eq!(returned.status, LoanStatus::Returned);
+        assert_eq!(returned.accrued_fees, dec!(30));
+        assert_eq!(desk.inventory(Some(lender))[0].available(), dec!(1000000));
+    }
+
+    #[test]
+    fn test_loan_term_is_bounded_before_inventory_is_drawn() {
+        let desk = LendingDesk::new(Arc::new(TimeProvider::new()));
+        let lender = Uuid::new_v4();
+        desk.set_lendable(lender, "GSEC10Y", dec!(1000000)).unwrap();
+        let request = |term_days| LoanRequest {
+            symbol: "GSEC10Y".to_string(),
+            lender_account_id: lender,
+            borrower_account_id: Uuid::new_v4(),
+            quantity: dec!(500000),
+            rate_bps: dec!(50),
+            term_days,
+        };
+
+        for term_days in [MAX_TERM_DAYS + 1, u32::MAX] {
+            assert!(matches!(desk.create_loan(request(term_days)), Err(TradingError::InvalidRequest(_))));
+        }
+        assert_eq!(desk.inventory(Some(lender))[0].available(), dec!(1000000));
+
+        let loan = desk.create_loan(request(MAX_TERM_DAYS)).unwrap();
+        assert_eq!((loan.end_date - loan.start_date).num_days(), i64::from(MAX_TERM_DAYS));
+    }
+}
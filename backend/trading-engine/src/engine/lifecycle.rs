@@ -0,0 +1,251 @@
+use crate::{
+    engine::{
+        allocation::{FirmPreference, MatchingAlgorithm},
+        fixed_point::TickScale,
+        instruments::{Instrument, InstrumentStatus},
+        EngineEvent, TradingEngine,
+    },
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentArchive {
+    pub symbol: String,
+    pub delisted_at: DateTime<Utc>,
+    pub reason: Option<String>,
+    pub cancelled_orders: Vec<Uuid>,
+    pub orders: Vec<Order>,
+    pub trade_count: usize,
+    pub traded_volume: Decimal,
+    pub last_price: Option<Decimal>,
+}
+
+impl TradingEngine {
+    pub fn list_instrument(
+        &self,
+        bond: Bond,
+        matching_algorithm: MatchingAlgorithm,
+    ) -> crate::types::Result<Instrument> {
+        if self.synthetic_indices.contains(&bond.symbol) {
+            return Err(TradingError::InvalidRequest(format!("{} is a synthetic index", bond.symbol)));
+        }
+        // A relisted symbol starts over at the default scale
+        self.matching_engine.set_tick_scale(&bond.symbol, TickScale::default())?;
+        let instrument = self.instruments.register(bond, matching_algorithm)?;
+        let symbol = instrument.bond.symbol.clone();
+
+        self.matching_engine.open_book(&symbol);
+        self.order_book_manager
+            .update_orderbook(symbol.clone(), Vec::new(), Vec::new());
+        self.publish_status_change(&instrument);
+
+        info!("Instrument {} listed", symbol);
+        Ok(instrument)
+    }
+
+    /// Switches how resting quantity is allocated at each price level. Takes
+    /// effect from the next incoming order; resting priority is unchanged.
+    pub fn set_matching_algorithm(
+        &self,
+        symbol: &str,
+        matching_algorithm: MatchingAlgorithm,
+    ) -> crate::types::Result<Instrument> {
+        let instrument = self
+            .instruments
+            .set_matching_algorithm(symbol, matching_algorithm)?;
+        info!("Instrument {} now matches with {:?}", symbol, matching_algorithm);
+        Ok(instrument)
+    }
+
+    /// Sets how resting orders from the aggressor's own firm are allocated
+    /// in a symbol.
+    pub fn set_firm_preference(
+        &self,
+        symbol: &str,
+        firm_preference: FirmPreference,
+    ) -> crate::types::Result<Instrument> {
+        let instrument = self.instruments.set_firm_preference(symbol, firm_preference)?;
+        info!("Instrument {} now applies {:?} to same-firm interest", symbol, firm_preference);
+        Ok(instrument)
+    }
+
+    /// Sets the decimal places an instrument's prices and quantities are
+    /// matched in. Only allowed while nothing rests on its book.
+    pub fn set_tick_scale(&self, symbol: &str, tick_scale: TickScale) -> crate::types::Result<Instrument> {
+        if self.instruments.status(symbol).is_none() {
+            return Err(TradingError::InstrumentNotFound(symbol.to_string()));
+        }
+        self.matching_engine.set_tick_scale(symbol, tick_scale)?;
+        let instrument = self.instruments.set_tick_scale(symbol, tick_scale)?;
+        info!(
+            "Instrument {} now matches in {} price and {} quantity decimals",
+            symbol, tick_scale.price_decimals, tick_scale.quantity_decimals
+        );
+        Ok(instrument)
+    }
+
+    /// Halts trading in a symbol. New orders are rejected while suspended;
+    /// resting orders stay on the book unless `cancel_resting` is set.
+    pub async fn suspend_instrument(
+        &self,
+        symbol: &str,
+        reason: Option<String>,
+        cancel_resting: bool,
+    ) -> crate::types::Result<(Instrument, Vec<Uuid>)> {
+        let instrument = self
+            .instruments
+            .set_status(symbol, InstrumentStatus::Suspended, reason)?;
+        self.publish_status_change(&instrument);
+
+        let mut cancelled = Vec::new();
+        if cancel_resting {
+            for order_id in self.open_order_ids(symbol) {
+                if self.cancel_order_unthrottled(order_id, CancelReason::InstrumentSuspended).await? {
+                    cancelled.push(order_id);
+                }
+            }
+        }
+
+        warn!("Instrument {} suspended, {} orders cancelled", symbol, cancelled.len());
+        Ok((instrument, cancelled))
+    }
+
+    pub fn resume_instrument(&self, symbol: &str) -> crate::types::Result<Instrument> {
+        let instrument = self
+            .instruments
+            .set_status(symbol, InstrumentStatus::Active, None)?;
+        self.publish_status_change(&instrument);
+
+        info!("Instrument {} resumed", symbol);
+        Ok(instrument)
+    }
+
+    /// Closes a symbol's book, cancels whatever was resting on it and moves
+    /// its orders out of the live order map into an archive record.
+    pub fn delist_instrument(
+        &self,
+        symbol: &str,
+        reason: Option<String>,
+    ) -> crate::types::Result<InstrumentArchive> {
+        let instrument = self
+            .instruments
+            .set_status(symbol, InstrumentStatus::Delisted, reason.clone())?;
+
+        let mut cancelled_orders = self.matching_engine.close_book(symbol);
+        cancelled_orders.extend(self.auction_book.take(symbol).iter().map(|order| order.id));
+        for order_id in &cancelled_orders {
+            if let Some(mut order) = self.orders.get_mut(order_id) {
+                order.status = OrderStatus::Cancelled;
+                order.cancel_reason = Some(CancelReason::InstrumentDelisted);
+                self.event_journal.publish(
+                    EngineEvent::OrderCancelled {
+                        order_id: *order_id,
+                        cancel_reason: CancelReason::InstrumentDelisted,
+                    },
+                    vec![order.account_id],
+                );
+                self.metrics.increment_orders_cancelled();
+            }
+        }
+        self.order_book_manager.remove_orderbook(symbol);
+
+        let order_ids: Vec<Uuid> = self
+            .orders
+            .iter()
+            .filter(|entry| entry.symbol == symbol)
+            .map(|entry| *entry.key())
+            .collect();
+        let orders = order_ids
+            .iter()
+            .filter_map(|order_id| self.orders.remove(order_id).map(|(_, order)| order))
+            .collect();
+
+        let (trade_count, traded_volume, last_price) = {
+            let trades = self.trades.read();
+            let symbol_trades: Vec<&Trade> = trades.iter().filter(|t| t.symbol == symbol).collect();
+            (
+                symbol_trades.len(),
+                symbol_trades.iter().map(|t| t.quantity).sum(),
+                symbol_trades.last().map(|t| t.price),
+            )
+        };
+
+        let archive = InstrumentArchive {
+            symbol: symbol.to_string(),
+            delisted_at: instrument.status_changed_at,
+            reason,
+            cancelled_orders,
+            orders,
+            trade_count,
+            traded_volume,
+            last_price,
+        };
+        self.instrument_archives.insert(symbol.to_string(), archive.clone());
+        self.publish_status_change(&instrument);
+
+        info!("Instrument {} delisted and archived", symbol);
+        Ok(archive)
+    }
+
+    /// Delists every instrument whose maturity date has passed.
+    pub fn delist_matured_instruments(&self) -> Vec<String> {
+        let mut delisted = Vec::new();
+        for symbol in self.instruments.matured() {
+            match self.delist_instrument(&symbol, Some("Matured".to_string())) {
+                Ok(_) => delisted.push(symbol),
+                Err(e) => warn!("Failed to delist matured instrument {}: {}", symbol, e),
+            }
+        }
+        delisted
+    }
+
+    pub fn get_instrument_archive(&self, symbol: &str) -> Option<InstrumentArchive> {
+        self.instrument_archives
+            .get(symbol)
+            .map(|archive| archive.clone())
+    }
+
+    pub(crate) fn check_instrument_tradable(&self, symbol: &str) -> crate::types::Result<()> {
+        if self.synthetic_indices.contains(symbol) {
+            return Err(TradingError::InstrumentNotTradable(format!(
+                "{} is a synthetic index",
+                symbol
+            )));
+        }
+        match self.instruments.status(symbol) {
+            Some(InstrumentStatus::Active | InstrumentStatus::Auction) => Ok(()),
+            Some(status) => Err(TradingError::InstrumentNotTradable(format!(
+                "{} is {:?}",
+                symbol, status
+            ))),
+            None if self.config.require_listed_instruments => {
+                Err(TradingError::InstrumentNotFound(symbol.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn open_order_ids(&self, symbol: &str) -> Vec<Uuid> {
+        self.orders
+            .iter()
+            .filter(|entry| entry.symbol == symbol && entry.is_open())
+            .map(|entry| *entry.key())
+            .collect()
+    }
+
+    pub(crate) fn publish_status_change(&self, instrument: &Instrument) {
+        self.event_journal.publish(
+            EngineEvent::InstrumentStatusChanged {
+                symbol: instrument.bond.symbol.clone(),
+                status: instrument.status,
+                reason: instrument.status_reason.clone(),
+            },
+            Vec::new(),
+        );
+    }
+}
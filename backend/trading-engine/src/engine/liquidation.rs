@@ -0,0 +1,434 @@
+//! Stop-out. An account with a liquidation policy is checked periodically;
+//! once its margin utilization reaches the policy's threshold the engine
+//! cancels the account's open orders and enters market orders that cut its
+//! positions until utilization is back at the target. Each step goes to
+//! the liquidation log and out on the event stream.
+
+use crate::{
+    engine::{valuation::PositionValuation, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Set on every order a liquidation enters, to the liquidation's id.
+pub const LIQUIDATION_KEY: &str = "liquidation";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidationMethod {
+    /// Closes the positions with the worst unrealized P&L first.
+    #[default]
+    LargestLossFirst,
+    /// Cuts every position by the same fraction.
+    ProRata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidationPolicy {
+    /// Share of a position's gross market value held as margin.
+    pub margin_rate: Decimal,
+    /// Utilization at which liquidation starts.
+    pub threshold: Decimal,
+    /// Utilization liquidation cuts positions back to.
+    pub target: Decimal,
+    #[serde(default)]
+    pub method: LiquidationMethod,
+}
+
+/// Margin of one account. Collateral is its cash plus realized and
+/// unrealized P&L; the requirement is its gross market value at the
+/// margin rate.
+#[derive(Debug, Clone, Serialize)]
+pub struct MarginStatus {
+    pub account_id: Uuid,
+    pub collateral: Decimal,
+    pub requirement: Decimal,
+    /// Requirement over collateral; `None` when there is no collateral.
+    pub utilization: Option<Decimal>,
+    pub breached: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum LiquidationAction {
+    Triggered {
+        collateral: Decimal,
+        requirement: Decimal,
+        utilization: Option<Decimal>,
+    },
+    OrderCancelled {
+        order_id: Uuid,
+    },
+    OrderEntered {
+        order_id: Uuid,
+        symbol: String,
+        side: OrderSide,
+        quantity: Decimal,
+    },
+    OrderFailed {
+        symbol: String,
+        side: OrderSide,
+        quantity: Decimal,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidationEvent {
+    /// Shared by every step of one liquidation.
+    pub liquidation_id: Uuid,
+    pub account_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub action: LiquidationAction,
+}
+
+#[derive(Default)]
+pub struct LiquidationDesk {
+    policies: DashMap<Uuid, LiquidationPolicy>,
+    log: RwLock<Vec<LiquidationEvent>>,
+}
+
+impl LiquidationDesk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(&self, account_id: Uuid) -> Option<LiquidationPolicy> {
+        self.policies.get(&account_id).map(|policy| policy.clone())
+    }
+
+    pub fn set_policy(&self, account_id: Uuid, policy: LiquidationPolicy) -> crate::types::Result<LiquidationPolicy> {
+        if policy.margin_rate <= Decimal::ZERO || policy.margin_rate > Decimal::ONE {
+            return Err(TradingError::InvalidRequest("The margin rate must be above 0 and at most 1".to_string()));
+        }
+        if policy.target <= Decimal::ZERO || policy.target >= policy.threshold {
+            return Err(TradingError::InvalidRequest(
+                "The target utilization must be above 0 and below the threshold".to_string(),
+            ));
+        }
+        self.policies.insert(account_id, policy.clone());
+        Ok(policy)
+    }
+
+    pub fn remove_policy(&self, account_id: Uuid) -> Option<LiquidationPolicy> {
+        self.policies.remove(&account_id).map(|(_, policy)| policy)
+    }
+
+    /// Oldest first, optionally for one account.
+    pub fn log(&self, account_id: Option<Uuid>) -> Vec<LiquidationEvent> {
+        self.log
+            .read()
+            .iter()
+            .filter(|event| account_id.is_none_or(|id| event.account_id == id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Quantity to take off each position, by symbol, for the requirement to
+/// fall to `target` times the collateral. Positions without market value
+/// carry no margin and are left alone.
+fn reductions(
+    positions: &[PositionValuation],
+    policy: &LiquidationPolicy,
+    collateral: Decimal,
+    requirement: Decimal,
+) -> Vec<(String, Decimal)> {
+    let mut excess = requirement - policy.target * collateral.max(Decimal::ZERO);
+    if excess <= Decimal::ZERO {
+        return Vec::new();
+    }
+    let mut margined: Vec<&PositionValuation> = positions
+        .iter()
+        .filter(|position| !position.quantity.is_zero() && !position.market_value.is_zero())
+        .collect();
+    let margin = |position: &PositionValuation| position.market_value.abs() * policy.margin_rate;
+
+    match policy.method {
+        LiquidationMethod::ProRata => {
+            let fraction = (excess / requirement).min(Decimal::ONE);
+            margined
+                .into_iter()
+                .map(|position| (position.symbol.clone(), position.quantity.abs() * fraction))
+                .collect()
+        }
+        LiquidationMethod::LargestLossFirst => {
+            margined.sort_by(|a, b| {
+                a.unrealized_pnl
+                    .cmp(&b.unrealized_pnl)
+                    .then_with(|| margin(b).cmp(&margin(a)))
+            });
+            let mut cuts = Vec::new();
+            for position in margined {
+                if excess <= Decimal::ZERO {
+                    break;
+                }
+                let released = margin(position).min(excess);
+                excess -= released;
+                cuts.push((position.symbol.clone(), position.quantity.abs() * released / margin(position)));
+            }
+            cuts
+        }
+    }
+}
+
+impl TradingEngine {
+    pub fn liquidations(&self) -> &LiquidationDesk {
+        &self.liquidations
+    }
+
+    /// Margin of an account with a liquidation policy, at current marks.
+    pub async fn margin_status(&self, account_id: Uuid) -> Option<MarginStatus> {
+        let policy = self.liquidations.policy(account_id)?;
+        Some(self.measure_margin(account_id, &policy).await.0)
+    }
+
+    async fn measure_margin(
+        &self,
+        account_id: Uuid,
+        policy: &LiquidationPolicy,
+    ) -> (MarginStatus, Vec<PositionValuation>) {
+        let valuation = self.value_portfolio(account_id).await;
+        let cash: Decimal = self
+            .cash
+            .balances(Some(account_id))
+            .iter()
+            .map(|balance| balance.balance + balance.accrued_interest)
+            .sum();
+        let collateral = cash + valuation.realized_pnl + valuation.unrealized_pnl;
+        let requirement: Decimal = valuation
+            .positions
+            .iter()
+            .map(|position| position.market_value.abs() * policy.margin_rate)
+            .sum();
+        let utilization = (collateral > Decimal::ZERO).then(|| requirement / collateral);
+        let breached = requirement > Decimal::ZERO && utilization.is_none_or(|utilization| utilization >= policy.threshold);
+        let status = MarginStatus {
+            account_id,
+            collateral,
+            requirement,
+            utilization,
+            breached,
+        };
+        (status, valuation.positions)
+    }
+
+    /// Liquidates every account with a policy whose utilization has reached
+    /// its threshold. Returns the accounts liquidated.
+    pub async fn check_liquidations(&self) -> Vec<Uuid> {
+        let accounts: Vec<(Uuid, LiquidationPolicy)> = self
+            .liquidations
+            .policies
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect();
+        let mut liquidated = Vec::new();
+        for (account_id, policy) in accounts {
+            let (status, positions) = self.measure_margin(account_id, &policy).await;
+            if status.breached {
+                self.liquidate(&policy, status, &positions).await;
+                liquidated.push(account_id);
+            }
+        }
+        liquidated
+    }
+
+    async fn liquidate(&self, policy: &LiquidationPolicy, status: MarginStatus, positions: &[PositionValuation]) {
+        let account_id = status.account_id;
+        let liquidation_id = Uuid::new_v4();
+        error!(
+            "Liquidating account {}: requirement {} against collateral {}",
+            account_id, status.requirement, status.collateral
+        );
+        self.log_liquidation(
+            liquidation_id,
+            account_id,
+            LiquidationAction::Triggered {
+                collateral: status.collateral,
+                requirement: status.requirement,
+                utilization: status.utilization,
+            },
+        );
+
+        let open: Vec<Uuid> = self
+            .orders
+            .iter()
+            .filter(|order| order.account_id == account_id && order.is_open())
+            .map(|order| order.id)
+            .collect();
+        for order_id in open {
+            match self.cancel_order_unthrottled(order_id, CancelReason::Liquidation).await {
+                Ok(true) => self.log_liquidation(liquidation_id, account_id, LiquidationAction::OrderCancelled { order_id }),
+                Ok(false) => {}
+                Err(e) => warn!("Liquidation {} could not cancel order {}: {}", liquidation_id, order_id, e),
+            }
+        }
+
+        let held: HashMap<&str, Decimal> = positions
+            .iter()
+            .map(|position| (position.symbol.as_str(), position.quantity))
+            .collect();
+        for (symbol, cut) in reductions(positions, policy, status.collateral, status.requirement) {
+            let position_quantity = held[symbol.as_str()];
+            let decimals = self.matching_engine.tick_scale(&symbol).quantity_decimals;
+            let quantity = cut
+                .round_dp_with_strategy(decimals, RoundingStrategy::AwayFromZero)
+                .min(position_quantity.abs());
+            let side = if position_quantity > Decimal::ZERO {
+                OrderSide::Sell
+            } else {
+                OrderSide::Buy
+            };
+            let order = Order {
+                id: Uuid::new_v4(),
+                client_order_id: format!("LIQ-{}", liquidation_id.simple()),
+                symbol: symbol.clone(),
+                side: side.clone(),
+                order_type: OrderType::Market,
+                quantity,
+                price: None,
+                filled_quantity: Decimal::ZERO,
+                remaining_quantity: quantity,
+                status: OrderStatus::Pending,
+                timestamp: self.time_provider.now(),
+                user_id: Uuid::nil(),
+                account_id,
+                time_in_force: TimeInForce::ImmediateOrCancel,
+                metadata: HashMap::from([(LIQUIDATION_KEY.to_string(), liquidation_id.to_string())]),
+                strategy_id: None,
+                fills: OrderFills::default(),
+                cancel_reason: None,
+            };
+            let action = match self.submit_order(order).await {
+                Ok(order_id) => LiquidationAction::OrderEntered {
+                    order_id,
+                    symbol,
+                    side,
+                    quantity,
+                },
+                Err(e) => LiquidationAction::OrderFailed {
+                    symbol,
+                    side,
+                    quantity,
+                    reason: e.to_string(),
+                },
+            };
+            self.log_liquidation(liquidation_id, account_id, action);
+        }
+    }
+
+    fn log_liquidation(&self, liquidation_id: Uuid, account_id: Uuid, action: LiquidationAction) {
+        let event = LiquidationEvent {
+            liquidation_id,
+            account_id,
+            timestamp: self.time_provider.now(),
+            action,
+        };
+        self.liquidations.log.write().push(event.clone());
+        self.event_journal.publish(EngineEvent::Liquidation(event), vec![account_id]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::cash::CashMovement, test_support::OrderBuilder};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn valued(symbol: &str, quantity: Decimal, market_value: Decimal, unrealized_pnl: Decimal) -> PositionValuation {
+        PositionValuation {
+            symbol: symbol.to_string(),
+            quantity,
+            mark_price: dec!(100),
+            mark_source: None,
+            matrix_confidence: None,
+            market_value,
+            accrued_interest: dec!(0),
+            unrealized_pnl,
+        }
+    }
+
+    fn policy(method: LiquidationMethod) -> LiquidationPolicy {
+        LiquidationPolicy {
+            margin_rate: dec!(0.1),
+            threshold: dec!(1),
+            target: dec!(0.5),
+            method,
+        }
+    }
+
+    #[test]
+    fn test_reductions_follow_the_policy_method() {
+        let positions = [
+            valued("GSEC5Y", dec!(1000000), dec!(1000000), dec!(-5000)),
+            valued("GSEC10Y", dec!(-2000000), dec!(-2000000), dec!(-20000)),
+        ];
+        // Requirement 300000 against collateral 200000: 200000 must go
+        let largest_loss = reductions(&positions, &policy(LiquidationMethod::LargestLossFirst), dec!(200000), dec!(300000));
+        assert_eq!(
+            largest_loss,
+            vec![("GSEC10Y".to_string(), dec!(2000000))]
+        );
+
+        let pro_rata = reductions(&positions, &policy(LiquidationMethod::ProRata), dec!(200000), dec!(300000));
+        let cut = |symbol: &str| pro_rata.iter().find(|(s, _)| s == symbol).unwrap().1.round_dp(0);
+        assert_eq!((cut("GSEC5Y"), cut("GSEC10Y")), (dec!(666667), dec!(1333333)));
+    }
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_breached_account_is_stopped_out() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (fund, dealer, bidder) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, dec!(1000000), dec!(100), dealer)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(1000000), dec!(100), fund)).await.unwrap();
+        let resting = engine.submit_order(order(OrderSide::Buy, dec!(500000), dec!(98), fund)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(1000000), dec!(100), bidder)).await.unwrap();
+
+        engine
+            .cash()
+            .post_movement(
+                fund,
+                CashMovement {
+                    currency: "INR".to_string(),
+                    amount: dec!(50000),
+                    note: None,
+                },
+            )
+            .unwrap();
+        engine.liquidations().set_policy(fund, policy(LiquidationMethod::LargestLossFirst)).unwrap();
+        let status = engine.margin_status(fund).await.unwrap();
+        assert!(status.breached);
+
+        assert_eq!(engine.check_liquidations().await, vec![fund]);
+        assert!(!engine.get_order(&resting).unwrap().is_open());
+        let log = engine.liquidations().log(Some(fund));
+        assert!(matches!(log[0].action, LiquidationAction::Triggered { .. }));
+        assert!(matches!(log[1].action, LiquidationAction::OrderCancelled { order_id } if order_id == resting));
+        let LiquidationAction::OrderEntered { side, quantity, .. } = &log[2].action else {
+            panic!("expected a liquidation order, got {:?}", log[2].action);
+        };
+        // Enough of the position to bring the requirement to half the
+        // collateral, which fees have taken a little off
+        let excess = status.requirement - dec!(0.5) * status.collateral;
+        let expected = (dec!(1000000) * excess / status.requirement).round_dp_with_strategy(4, RoundingStrategy::AwayFromZero);
+        assert_eq!((side, *quantity), (&OrderSide::Sell, expected));
+        assert!(expected > dec!(750000));
+
+        // Back at the target, so the next check leaves the account alone
+        assert!(!engine.margin_status(fund).await.unwrap().breached);
+        assert!(engine.check_liquidations().await.is_empty());
+    }
+}
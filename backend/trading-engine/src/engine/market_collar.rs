@@ -0,0 +1,124 @@
+//! Protective collar on market orders. A market order trades no further
+//! from the symbol's reference price than its collar allows: on the way to
+//! the book it becomes a limit at that bound, taking the venue's collar
+//! unless the order names its own. What the collar leaves unfilled rests at
+//! the bound, or is cancelled for an immediate time in force.
+
+use crate::{
+    engine::{matching::Unfilled, TradingEngine},
+    types::*,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Metadata key for an order's own collar, in percent of the reference
+/// price.
+pub const COLLAR_PCT_KEY: &str = "collar_pct";
+/// Metadata key recording the limit a collared market order was given.
+pub const COLLAR_PRICE_KEY: &str = "collar_price";
+
+/// The collar an order names, `Err` when it is not a number.
+pub fn order_collar_pct(order: &Order) -> Option<std::result::Result<Decimal, rust_decimal::Error>> {
+    order.metadata.get(COLLAR_PCT_KEY).map(|pct| pct.parse())
+}
+
+impl TradingEngine {
+    /// Turns a market order into a limit at its collar, here and where the
+    /// order is stored, and says what becomes of the quantity the collar
+    /// leaves unfilled. Without a collar, or with no reference price and
+    /// nothing on the other side to take one from, the order is left as it
+    /// is.
+    pub(crate) fn apply_market_collar(&self, order: &mut Order) -> Unfilled {
+        if order.order_type != OrderType::Market {
+            return Unfilled::Rest;
+        }
+        let pct = match order_collar_pct(order) {
+            Some(Ok(pct)) => pct,
+            _ => self.config.market_order_collar_pct,
+        };
+        if pct <= Decimal::ZERO {
+            return Unfilled::Rest;
+        }
+        let reference = self
+            .reference_price(&order.symbol)
+            .map(|reference| reference.price)
+            .ok()
+            .or_else(|| match order.side {
+                OrderSide::Buy => self.matching_engine.get_best_ask(&order.symbol),
+                OrderSide::Sell => self.matching_engine.get_best_bid(&order.symbol),
+            });
+        let Some(reference) = reference else {
+            return Unfilled::Rest;
+        };
+
+        // Rounded inside the collar onto the instrument's price scale
+        let decimals = self.matching_engine.tick_scale(&order.symbol).price_decimals;
+        let offset = reference * pct / Decimal::ONE_HUNDRED;
+        let collar = match order.side {
+            OrderSide::Buy => (reference + offset).round_dp_with_strategy(decimals, RoundingStrategy::ToNegativeInfinity),
+            OrderSide::Sell => (reference - offset).round_dp_with_strategy(decimals, RoundingStrategy::ToPositiveInfinity),
+        };
+        order.order_type = OrderType::Limit;
+        order.price = Some(collar);
+        order.metadata.insert(COLLAR_PRICE_KEY.to_string(), collar.to_string());
+        if let Some(mut stored) = self.orders.get_mut(&order.id) {
+            stored.order_type = OrderType::Limit;
+            stored.price = Some(collar);
+            stored.metadata.insert(COLLAR_PRICE_KEY.to_string(), collar.to_string());
+        }
+
+        match order.time_in_force {
+            TimeInForce::ImmediateOrCancel => Unfilled::Cancel,
+            TimeInForce::FillOrKill => Unfilled::Kill,
+            _ => Unfilled::Rest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Option<Decimal>, time_in_force: TimeInForce) -> Order {
+        let order = OrderBuilder::new(side).quantity(quantity).time_in_force(time_in_force);
+        match price {
+            Some(price) => order.price(price).build(),
+            None => order.market().build(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_market_orders_stop_at_their_collar() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        for price in [dec!(100.00), dec!(104.00), dec!(110.00)] {
+            let ask = order(OrderSide::Sell, dec!(100000), Some(price), TimeInForce::GoodTillCancel);
+            engine.submit_order(ask).await.unwrap();
+        }
+
+        // Priced off the best ask with no trade yet: 5% above is 105
+        let sweep = order(OrderSide::Buy, dec!(300000), None, TimeInForce::ImmediateOrCancel);
+        let sweep = engine.get_order(&engine.submit_order(sweep).await.unwrap()).unwrap();
+        assert_eq!((sweep.order_type, sweep.price), (OrderType::Limit, Some(dec!(105))));
+        assert_eq!((sweep.filled_quantity, sweep.status), (dec!(200000), OrderStatus::Cancelled));
+        assert_eq!(sweep.cancel_reason, Some(CancelReason::MarketCollar));
+
+        // Its own 10% collar reaches 110 but cannot fill the whole order
+        let kill = OrderBuilder::new(OrderSide::Buy)
+            .quantity(dec!(200000))
+            .market()
+            .time_in_force(TimeInForce::FillOrKill)
+            .metadata(COLLAR_PCT_KEY, "10")
+            .build();
+        let kill = engine.get_order(&engine.submit_order(kill).await.unwrap()).unwrap();
+        assert_eq!((kill.filled_quantity, kill.status), (dec!(0), OrderStatus::Cancelled));
+
+        // A day order rests what is left at its collar
+        let rest = order(OrderSide::Buy, dec!(200000), None, TimeInForce::GoodForDay);
+        let rest = engine.get_order(&engine.submit_order(rest).await.unwrap()).unwrap();
+        assert_eq!(rest.filled_quantity, dec!(0));
+        assert_eq!(engine.matching_engine.get_best_bid("GSEC10Y"), Some(dec!(109.2)));
+    }
+}
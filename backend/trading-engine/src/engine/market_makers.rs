@@ -0,0 +1,220 @@
+use crate::{
+    engine::{instruments::InstrumentStatus, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// What a registered market maker must show in each of its symbols.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotingObligation {
+    pub symbols: Vec<String>,
+    /// Widest compliant spread between the maker's best bid and offer, in
+    /// price points.
+    pub max_spread: Decimal,
+    /// Face value needed at the maker's best price on each side.
+    pub min_size: Decimal,
+    /// Share of the session, in percent, the maker must be compliant for.
+    pub min_presence_pct: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketMaker {
+    #[serde(default)]
+    pub account_id: Uuid,
+    pub name: String,
+    pub obligation: QuotingObligation,
+}
+
+/// Quote samples for one maker, symbol and trading day.
+#[derive(Debug, Clone, Default)]
+struct DailyQuoteStats {
+    samples: u64,
+    two_sided: u64,
+    compliant: u64,
+    spread_sum: Decimal,
+    bid_size_sum: Decimal,
+    ask_size_sum: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolCompliance {
+    pub symbol: String,
+    pub samples: u64,
+    /// Share of samples with a compliant two-sided quote, in percent.
+    pub presence_pct: Decimal,
+    pub two_sided_pct: Decimal,
+    /// Over two-sided samples only.
+    pub average_spread: Option<Decimal>,
+    pub average_bid_size: Option<Decimal>,
+    pub average_ask_size: Option<Decimal>,
+    pub meets_obligation: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceReport {
+    pub account_id: Uuid,
+    pub date: NaiveDate,
+    pub symbols: Vec<SymbolCompliance>,
+}
+
+/// A maker's best resting bid or offer and the size at that price.
+#[derive(Clone, Copy)]
+struct Touch {
+    price: Decimal,
+    size: Decimal,
+}
+
+fn improve(touch: &mut Option<Touch>, price: Decimal, size: Decimal, better: impl Fn(Decimal, Decimal) -> bool) {
+    match touch {
+        Some(current) if current.price == price => current.size += size,
+        Some(current) if !better(price, current.price) => {}
+        _ => *touch = Some(Touch { price, size }),
+    }
+}
+
+fn percent(part: u64, whole: u64) -> Decimal {
+    if whole == 0 {
+        return Decimal::ZERO;
+    }
+    (Decimal::from(part) * Decimal::ONE_HUNDRED / Decimal::from(whole)).round_dp(2)
+}
+
+#[derive(Default)]
+pub struct MarketMakerMonitor {
+    makers: DashMap<Uuid, MarketMaker>,
+    stats: DashMap<(Uuid, String, NaiveDate), DailyQuoteStats>,
+}
+
+impl MarketMakerMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, maker: MarketMaker) {
+        self.makers.insert(maker.account_id, maker);
+    }
+
+    pub fn deregister(&self, account_id: Uuid) -> Option<MarketMaker> {
+        self.makers.remove(&account_id).map(|(_, maker)| maker)
+    }
+
+    pub fn get(&self, account_id: Uuid) -> Option<MarketMaker> {
+        self.makers.get(&account_id).map(|maker| maker.clone())
+    }
+
+    pub fn all(&self) -> Vec<MarketMaker> {
+        self.makers.iter().map(|maker| maker.clone()).collect()
+    }
+
+    fn record(&self, maker: &MarketMaker, symbol: &str, date: NaiveDate, bid: Option<Touch>, ask: Option<Touch>) {
+        let mut stats = self
+            .stats
+            .entry((maker.account_id, symbol.to_string(), date))
+            .or_default();
+        stats.samples += 1;
+        let (Some(bid), Some(ask)) = (bid, ask) else {
+            return;
+        };
+        let spread = ask.price - bid.price;
+        stats.two_sided += 1;
+        stats.spread_sum += spread;
+        stats.bid_size_sum += bid.size;
+        stats.ask_size_sum += ask.size;
+        let obligation = &maker.obligation;
+        if spread <= obligation.max_spread && bid.size >= obligation.min_size && ask.size >= obligation.min_size {
+            stats.compliant += 1;
+        }
+    }
+
+    pub fn compliance(&self, account_id: Uuid, date: NaiveDate) -> Option<ComplianceReport> {
+        let maker = self.get(account_id)?;
+        let symbols = maker
+            .obligation
+            .symbols
+            .iter()
+            .map(|symbol| {
+                let stats = self
+                    .stats
+                    .get(&(account_id, symbol.clone(), date))
+                    .map(|stats| stats.clone())
+                    .unwrap_or_default();
+                let average = |sum: Decimal| {
+                    (stats.two_sided > 0).then(|| (sum / Decimal::from(stats.two_sided)).round_dp(4))
+                };
+                let presence_pct = percent(stats.compliant, stats.samples);
+                SymbolCompliance {
+                    symbol: symbol.clone(),
+                    samples: stats.samples,
+                    presence_pct,
+                    two_sided_pct: percent(stats.two_sided, stats.samples),
+                    average_spread: average(stats.spread_sum),
+                    average_bid_size: average(stats.bid_size_sum),
+                    average_ask_size: average(stats.ask_size_sum),
+                    meets_obligation: stats.samples > 0 && presence_pct >= maker.obligation.min_presence_pct,
+                }
+            })
+            .collect();
+        Some(ComplianceReport {
+            account_id,
+            date,
+            symbols,
+        })
+    }
+}
+
+impl TradingEngine {
+    pub fn market_makers(&self) -> &MarketMakerMonitor {
+        &self.market_makers
+    }
+
+    /// Takes one sample of every registered maker's quotes in each of its
+    /// symbols that is open for continuous trading.
+    pub fn sample_market_maker_quotes(&self) {
+        let makers = self.market_makers.all();
+        if makers.is_empty() {
+            return;
+        }
+        let now: DateTime<Utc> = self.time_provider.now();
+
+        let mut touches: HashMap<(Uuid, String), (Option<Touch>, Option<Touch>)> = HashMap::new();
+        for entry in self.orders.iter() {
+            let order = entry.value();
+            let Some(price) = order.price.filter(|_| order.is_open()) else {
+                continue;
+            };
+            if !self.market_makers.makers.contains_key(&order.account_id) {
+                continue;
+            }
+            let (bid, ask) = touches.entry((order.account_id, order.symbol.clone())).or_default();
+            match order.side {
+                OrderSide::Buy => improve(bid, price, order.remaining_quantity, |a, b| a > b),
+                OrderSide::Sell => improve(ask, price, order.remaining_quantity, |a, b| a < b),
+            }
+        }
+
+        for maker in &makers {
+            for symbol in &maker.obligation.symbols {
+                if self.instruments.status(symbol) != Some(InstrumentStatus::Active) {
+                    continue;
+                }
+                let (bid, ask) = touches
+                    .get(&(maker.account_id, symbol.clone()))
+                    .copied()
+                    .unwrap_or_default();
+                self.market_makers.record(maker, symbol, now.date_naive(), bid, ask);
+            }
+        }
+    }
+
+    pub fn market_maker_compliance(&self, account_id: Uuid, date: Option<NaiveDate>) -> crate::types::Result<ComplianceReport> {
+        let date = date.unwrap_or_else(|| self.time_provider.now().date_naive());
+        self.market_makers
+            .compliance(account_id, date)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("{} is not a registered market maker", account_id)))
+    }
+}
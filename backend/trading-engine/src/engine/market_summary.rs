@@ -0,0 +1,291 @@
+//! The market summary page: every listed symbol's top of book, last trade,
+//! day range and change on the previous close in one response. Day
+//! statistics are kept up as trades are recorded; tops of book and the
+//! yields on them are cached under the book generation, so the books are
+//! only read again for a summary once they have changed.
+
+use crate::{
+    engine::{analytics::yield_to_maturity, instruments::InstrumentStatus, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolSummary {
+    pub symbol: String,
+    pub status: InstrumentStatus,
+    pub bid_price: Option<Decimal>,
+    pub ask_price: Option<Decimal>,
+    pub bid_yield: Option<Decimal>,
+    pub ask_yield: Option<Decimal>,
+    pub last_price: Option<Decimal>,
+    pub last_quantity: Option<Decimal>,
+    pub last_trade_at: Option<DateTime<Utc>>,
+    pub last_yield: Option<Decimal>,
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    /// The latest trade today.
+    pub close: Option<Decimal>,
+    pub volume: Decimal,
+    pub trades: u64,
+    pub previous_close: Option<Decimal>,
+    pub change: Option<Decimal>,
+    pub change_percent: Option<Decimal>,
+}
+
+/// Prices are in each instrument's quoting convention; yields are to
+/// maturity, in percent.
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketSummary {
+    pub generated_at: DateTime<Utc>,
+    pub symbols: Vec<SymbolSummary>,
+}
+
+/// One symbol's trading on `date`, in clean prices.
+#[derive(Debug, Clone, Default)]
+struct DayStats {
+    date: NaiveDate,
+    open: Option<Decimal>,
+    high: Option<Decimal>,
+    low: Option<Decimal>,
+    close: Option<Decimal>,
+    volume: Decimal,
+    trades: u64,
+    last_price: Option<Decimal>,
+    last_quantity: Option<Decimal>,
+    last_trade_at: Option<DateTime<Utc>>,
+    /// The close of the last earlier day the symbol traded.
+    previous_close: Option<Decimal>,
+}
+
+impl DayStats {
+    /// The stats as of `today`, rolling over if the symbol has not traded
+    /// yet today.
+    fn as_of(&self, today: NaiveDate) -> DayStats {
+        if self.date >= today {
+            return self.clone();
+        }
+        DayStats {
+            date: today,
+            previous_close: self.close.or(self.previous_close),
+            last_price: self.last_price,
+            last_quantity: self.last_quantity,
+            last_trade_at: self.last_trade_at,
+            ..DayStats::default()
+        }
+    }
+}
+
+/// Clean top of book and its yields, as of a book generation and
+/// settlement date.
+#[derive(Debug, Clone, Copy)]
+struct CachedTop {
+    generation: u64,
+    settlement: NaiveDate,
+    bid: Option<Decimal>,
+    ask: Option<Decimal>,
+    bid_yield: Option<Decimal>,
+    ask_yield: Option<Decimal>,
+}
+
+#[derive(Default)]
+pub struct MarketSummaryTracker {
+    days: DashMap<String, DayStats>,
+    tops: DashMap<String, CachedTop>,
+    /// Yield of the last trade price, with the price and settlement date it
+    /// was solved for.
+    last_yields: DashMap<String, (Decimal, NaiveDate, Option<Decimal>)>,
+}
+
+impl MarketSummaryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        let date = trade.timestamp.date_naive();
+        let mut day = self.days.entry(trade.symbol.clone()).or_default();
+        if day.date < date {
+            *day = day.as_of(date);
+        }
+        if day.date > date {
+            // Late-reported trades from an earlier day only move the last trade
+            return;
+        }
+        day.open.get_or_insert(trade.price);
+        day.high = Some(day.high.map_or(trade.price, |high| high.max(trade.price)));
+        day.low = Some(day.low.map_or(trade.price, |low| low.min(trade.price)));
+        day.close = Some(trade.price);
+        day.volume += trade.quantity;
+        day.trades += 1;
+        day.last_price = Some(trade.price);
+        day.last_quantity = Some(trade.quantity);
+        day.last_trade_at = Some(trade.timestamp);
+    }
+}
+
+impl TradingEngine {
+    pub fn market_summary(&self) -> MarketSummary {
+        let now = self.time_provider.now();
+        let today = now.date_naive();
+        let settlement = self.time_provider.settlement_date(today, self.config.settlement_cycle_days);
+        let generation = self.matching_engine.generation();
+
+        let mut symbols: Vec<SymbolSummary> = self
+            .instruments
+            .list()
+            .into_iter()
+            .filter(|instrument| instrument.status != InstrumentStatus::Delisted)
+            .map(|instrument| {
+                let symbol = instrument.bond.symbol.clone();
+                let top = self.cached_top(&instrument.bond, generation, settlement);
+                let day = self
+                    .market_summary
+                    .days
+                    .get(&symbol)
+                    .map(|day| day.as_of(today))
+                    .unwrap_or_default();
+                let last_yield = day
+                    .last_price
+                    .and_then(|price| self.last_trade_yield(&instrument.bond, price, settlement));
+                let previous_close = self.reference_prices.previous_close(&symbol).or(day.previous_close);
+                let change = day.close.zip(previous_close).map(|(close, previous)| close - previous);
+                let change_percent = change
+                    .zip(previous_close)
+                    .filter(|(_, previous)| !previous.is_zero())
+                    .map(|(change, previous)| (change / previous * Decimal::ONE_HUNDRED).round_dp(4));
+
+                let quoted = |price: Option<Decimal>| price.map(|price| self.to_quoted_price(&symbol, price));
+                SymbolSummary {
+                    status: instrument.status,
+                    bid_price: quoted(top.bid),
+                    ask_price: quoted(top.ask),
+                    bid_yield: top.bid_yield,
+                    ask_yield: top.ask_yield,
+                    last_price: quoted(day.last_price),
+                    last_quantity: day.last_quantity,
+                    last_trade_at: day.last_trade_at,
+                    last_yield,
+                    open: quoted(day.open),
+                    high: quoted(day.high),
+                    low: quoted(day.low),
+                    close: quoted(day.close),
+                    volume: day.volume,
+                    trades: day.trades,
+                    previous_close: quoted(previous_close),
+                    change,
+                    change_percent,
+                    symbol,
+                }
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        MarketSummary {
+            generated_at: now,
+            symbols,
+        }
+    }
+
+    fn cached_top(&self, bond: &Bond, generation: u64, settlement: NaiveDate) -> CachedTop {
+        let symbol = &bond.symbol;
+        if let Some(top) = self.market_summary.tops.get(symbol) {
+            if top.generation == generation && top.settlement == settlement {
+                return *top;
+            }
+        }
+        let bid = self.matching_engine.get_best_bid(symbol);
+        let ask = self.matching_engine.get_best_ask(symbol);
+        let solve =
+            |price: Option<Decimal>| price.and_then(|price| yield_to_maturity(bond, price, settlement, &self.fixings));
+        let top = CachedTop {
+            generation,
+            settlement,
+            bid,
+            ask,
+            bid_yield: solve(bid),
+            ask_yield: solve(ask),
+        };
+        self.market_summary.tops.insert(symbol.clone(), top);
+        top
+    }
+
+    fn last_trade_yield(&self, bond: &Bond, price: Decimal, settlement: NaiveDate) -> Option<Decimal> {
+        if let Some(cached) = self.market_summary.last_yields.get(&bond.symbol) {
+            if cached.0 == price && cached.1 == settlement {
+                return cached.2;
+            }
+        }
+        let solved = yield_to_maturity(bond, price, settlement, &self.fixings);
+        self.market_summary
+            .last_yields
+            .insert(bond.symbol.clone(), (price, settlement, solved));
+        solved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::allocation::MatchingAlgorithm, test_support::OrderBuilder, utils::daycount::DayCount};
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn bond() -> Bond {
+        Bond {
+            isin: "IN0020230085".to_string(),
+            symbol: "GSEC10Y".to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2033, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.18),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        }
+    }
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).build()
+    }
+
+    #[tokio::test]
+    async fn test_summary_tracks_the_day_and_the_book() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine.list_instrument(bond(), MatchingAlgorithm::PriceTime).unwrap();
+        engine.reference_prices().set_previous_close("GSEC10Y", dec!(100), Utc::now());
+        let prints = [(dec!(99.50), dec!(100000)), (dec!(100.25), dec!(50000)), (dec!(99.75), dec!(25000))];
+        for (price, quantity) in prints {
+            engine.submit_order(order(OrderSide::Sell, quantity, price)).await.unwrap();
+            engine.submit_order(order(OrderSide::Buy, quantity, price)).await.unwrap();
+        }
+        engine.submit_order(order(OrderSide::Buy, dec!(100000), dec!(99.25))).await.unwrap();
+
+        let summary = engine.market_summary();
+        let gsec = summary.symbols.iter().find(|s| s.symbol == "GSEC10Y").unwrap();
+        assert_eq!((gsec.open, gsec.high, gsec.low), (Some(dec!(99.50)), Some(dec!(100.25)), Some(dec!(99.50))));
+        assert_eq!(gsec.close, Some(dec!(99.75)));
+        assert_eq!(gsec.volume, dec!(175000));
+        assert_eq!(gsec.trades, 3);
+        assert_eq!(gsec.change, Some(dec!(-0.25)));
+        assert_eq!(gsec.bid_price, Some(dec!(99.25)));
+        assert_eq!(gsec.ask_price, None);
+        assert!(gsec.bid_yield.is_some() && gsec.last_yield.is_some());
+
+        engine.submit_order(order(OrderSide::Sell, dec!(100000), dec!(99.90))).await.unwrap();
+        let gsec = engine.market_summary().symbols.into_iter().find(|s| s.symbol == "GSEC10Y").unwrap();
+        assert_eq!(gsec.ask_price, Some(dec!(99.90)));
+    }
+}
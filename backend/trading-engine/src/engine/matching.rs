@@ -1,19 +1,37 @@
 use crate::{
-    engine::EngineEvent,
+    engine::{
+        accrued,
+        book_snapshot::{BookSnapshot, RestingEntry},
+        allocation::{FirmPreference, MatchAllocator, MatchingAlgorithm, RestingInterest},
+        book_limits::BookFootprint,
+        symbol_stats::OpenInterest,
+        credit_lines::CreditLineBook,
+        risk_manager::OrderActivity,
+        event_journal::EventJournal,
+        feature_flags::{FeatureFlag, FeatureFlags},
+        fees::FeeEngine,
+        fixed_point::TickScale,
+        fixings::FixingStore,
+        inflation::InflationIndexStore,
+        instruments::InstrumentRegistry,
+        internalization::FirmRegistry,
+        EngineEvent,
+    },
     types::*,
     config::Config,
-    utils::metrics::Metrics,
+    utils::{logging, metrics::Metrics, time::TimeProvider, versioned::VersionedWriteGuard},
 };
-use anyhow::Result;
-use chrono::Utc;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use rust_decimal::Decimal;
+use serde::Serialize;
 use std::{
-    collections::{BTreeMap, VecDeque},
-    sync::Arc,
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
-use tokio::sync::broadcast;
 use tracing::{debug, info};
 use uuid::Uuid;
 
@@ -21,355 +39,1236 @@ use uuid::Uuid;
 struct OrderBookEntry {
     order: Order,
     priority: u64,
+    /// The order's open quantity in ticks; matching reads this, never the
+    /// order's `Decimal` fields.
+    remaining: u64,
 }
 
 impl OrderBookEntry {
-    fn new(order: Order, priority: u64) -> Self {
-        Self { order, priority }
+    fn new(order: Order, priority: u64, remaining: u64) -> Self {
+        Self {
+            order,
+            priority,
+            remaining,
+        }
+    }
+
+    /// The entry itself plus the heap its strings hold.
+    fn estimated_bytes(&self) -> usize {
+        let order = &self.order;
+        let metadata: usize = order
+            .metadata
+            .iter()
+            .map(|(key, value)| std::mem::size_of::<(String, String)>() + key.capacity() + value.capacity())
+            .sum();
+        std::mem::size_of::<Self>()
+            + order.client_order_id.capacity()
+            + order.symbol.capacity()
+            + order.strategy_id.as_ref().map_or(0, String::capacity)
+            + metadata
     }
 }
 
+/// Price levels keyed by price in ticks of the symbol's scale.
+type PriceLevels = BTreeMap<u64, VecDeque<OrderBookEntry>>;
+
+/// Both sides of one symbol's book. They share a lock, so an incoming
+/// order matches and rests in one step and no opposite order can rest
+/// against it in between.
+#[derive(Default)]
+struct SymbolBook {
+    bids: PriceLevels,
+    asks: PriceLevels,
+    /// Set once `close_book` has drained the book; anyone who was
+    /// waiting on it looks the symbol up again.
+    closed: bool,
+}
+
+impl SymbolBook {
+    /// The side orders on `side` rest on.
+    fn side(&self, side: &OrderSide) -> &PriceLevels {
+        match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, side: &OrderSide) -> &mut PriceLevels {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    /// The side orders on `side` trade against.
+    fn contra_mut(&mut self, side: &OrderSide) -> &mut PriceLevels {
+        match side {
+            OrderSide::Buy => &mut self.asks,
+            OrderSide::Sell => &mut self.bids,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+/// An incoming order with its limit and open quantity in ticks. The
+/// order's `Decimal` fields follow along for reporting.
+struct Taker<'a> {
+    order: &'a mut Order,
+    scale: TickScale,
+    limit: Option<u64>,
+    remaining: u64,
+}
+
+impl<'a> Taker<'a> {
+    fn new(order: &'a mut Order, scale: TickScale) -> crate::types::Result<Self> {
+        let limit = match order.price {
+            Some(price) => Some(scale.price_ticks(price).ok_or_else(|| {
+                TradingError::InvalidOrderField {
+                    field: OrderField::Price,
+                    message: format!("Price {} is finer than {} decimal places", price, scale.price_decimals),
+                }
+            })?),
+            None => None,
+        };
+        let remaining = scale.quantity_ticks(order.remaining_quantity).ok_or_else(|| {
+            TradingError::InvalidOrderField {
+                field: OrderField::Quantity,
+                message: format!(
+                    "Quantity {} is finer than {} decimal places",
+                    order.remaining_quantity, scale.quantity_decimals
+                ),
+            }
+        })?;
+        Ok(Self {
+            order,
+            scale,
+            limit,
+            remaining,
+        })
+    }
+
+    /// Whether a resting level at `price` is good enough to trade with.
+    /// Market orders match at any price.
+    fn crosses(&self, price: u64) -> bool {
+        self.limit.is_none_or(|limit| match self.order.side {
+            OrderSide::Buy => price <= limit,
+            OrderSide::Sell => price >= limit,
+        })
+    }
+}
+
+/// What becomes of the part of an incoming order that does not trade at
+/// once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unfilled {
+    /// Rests on the book at the order's limit.
+    Rest,
+    /// Is left for the caller to cancel.
+    Cancel,
+    /// Nothing trades unless all of it can.
+    Kill,
+}
+
+/// One resting order as the market-by-order feed shows it, without the
+/// account behind it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BookOrder {
+    pub order_id: Uuid,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    /// Time priority within the price; lower trades first.
+    pub priority: u64,
+}
+
 pub struct MatchingEngine {
     config: Arc<Config>,
-    buy_orders: Arc<RwLock<BTreeMap<String, BTreeMap<Decimal, VecDeque<OrderBookEntry>>>>>,
-    sell_orders: Arc<RwLock<BTreeMap<String, BTreeMap<Decimal, VecDeque<OrderBookEntry>>>>>,
-    order_index: Arc<DashMap<Uuid, (String, Decimal, OrderSide)>>,
-    event_sender: broadcast::Sender<EngineEvent>,
+    books: DashMap<String, Arc<RwLock<SymbolBook>>>,
+    order_index: Arc<DashMap<Uuid, (String, u64, OrderSide, Uuid)>>,
+    tick_scales: DashMap<String, TickScale>,
+    activity: Arc<OrderActivity>,
+    event_journal: Arc<EventJournal>,
     metrics: Arc<Metrics>,
+    time_provider: Arc<TimeProvider>,
+    fee_engine: Arc<FeeEngine>,
+    instruments: Arc<InstrumentRegistry>,
+    firms: Arc<FirmRegistry>,
+    credit_lines: Arc<CreditLineBook>,
+    fixings: Arc<FixingStore>,
+    inflation_indices: Arc<InflationIndexStore>,
+    feature_flags: Arc<FeatureFlags>,
     next_priority: Arc<parking_lot::Mutex<u64>>,
+    next_trade_sequence: Arc<parking_lot::Mutex<u64>>,
+    /// Advances on every change to any book.
+    generation: AtomicU64,
+}
+
+/// Namespace for name-based trade ids.
+const TRADE_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2a4e_93d7_5b08_a2e1_7c4f_0d39_b615);
+
+/// Trade id and human-readable trade number for the `sequence`th trade of
+/// an engine instance. Both depend only on their inputs, so replaying the
+/// same order flow on the same instance reproduces them.
+pub fn trade_identity(instance: &str, sequence: u64, buyer_order_id: Uuid, seller_order_id: Uuid) -> (Uuid, String) {
+    let name = format!("{}:{}:{}:{}", instance, sequence, buyer_order_id, seller_order_id);
+    (
+        Uuid::new_v5(&TRADE_ID_NAMESPACE, name.as_bytes()),
+        format!("{}-{:012}", instance, sequence),
+    )
 }
 
 impl MatchingEngine {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Arc<Config>,
-        event_sender: broadcast::Sender<EngineEvent>,
+        event_journal: Arc<EventJournal>,
         metrics: Arc<Metrics>,
+        time_provider: Arc<TimeProvider>,
+        fee_engine: Arc<FeeEngine>,
+        instruments: Arc<InstrumentRegistry>,
+        firms: Arc<FirmRegistry>,
+        credit_lines: Arc<CreditLineBook>,
+        activity: Arc<OrderActivity>,
+        fixings: Arc<FixingStore>,
+        inflation_indices: Arc<InflationIndexStore>,
+        feature_flags: Arc<FeatureFlags>,
     ) -> Self {
         Self {
             config,
-            buy_orders: Arc::new(RwLock::new(BTreeMap::new())),
-            sell_orders: Arc::new(RwLock::new(BTreeMap::new())),
+            books: DashMap::new(),
             order_index: Arc::new(DashMap::new()),
-            event_sender,
+            tick_scales: DashMap::new(),
+            activity,
+            event_journal,
             metrics,
+            time_provider,
+            fee_engine,
+            instruments,
+            firms,
+            credit_lines,
+            fixings,
+            inflation_indices,
+            feature_flags,
             next_priority: Arc::new(parking_lot::Mutex::new(0)),
+            next_trade_sequence: Arc::new(parking_lot::Mutex::new(0)),
+            generation: AtomicU64::new(0),
         }
     }
 
-    pub async fn process_order(&self, order: Order) -> crate::types::Result<Vec<Trade>> {
-        let mut trades = Vec::new();
-        let mut remaining_order = order.clone();
+    pub async fn process_order_with(&self, order: Order, unfilled: Unfilled) -> crate::types::Result<Vec<Trade>> {
+        // Cross against the same firm's resting interest at the touch first;
+        // an all-or-none order is checked against the book as one instead
+        let internalize = unfilled != Unfilled::Kill
+            && self.config.internalization_enabled
+            && self.instruments.firm_preference(&order.symbol) != FirmPreference::AntiInternalization
+            && self
+                .feature_flags
+                .is_enabled(FeatureFlag::Internalization, &order.symbol, order.account_id);
 
-        // Try to match against existing orders
-        let matched_trades = self.match_order(&mut remaining_order).await?;
-        trades.extend(matched_trades);
+        // Matching and resting the remainder happen under the one lock on
+        // the symbol's book, so the book is never left crossed
+        self.with_book(&order.symbol, |book| {
+            let mut trades = Vec::new();
+            let mut remaining_order = order.clone();
+            let mut taker = Taker::new(&mut remaining_order, self.tick_scale(&order.symbol))?;
 
-        // If there's remaining quantity, add to order book
-        if remaining_order.remaining_quantity > Decimal::ZERO {
-            self.add_to_order_book(remaining_order).await?;
-        }
+            if internalize {
+                trades.extend(self.internalize(&mut taker, book.contra_mut(&order.side)));
+            }
+
+            // Try to match against existing orders
+            let matched_trades = self.match_order(&mut taker, book.contra_mut(&order.side), unfilled == Unfilled::Kill);
+            trades.extend(matched_trades);
+
+            // If there's remaining quantity, add to order book
+            let (limit, remaining) = (taker.limit, taker.remaining);
+            if remaining > 0 && unfilled == Unfilled::Rest {
+                self.add_to_order_book(book.side_mut(&order.side), remaining_order, limit, remaining)?;
+            }
 
-        Ok(trades)
+            Ok(trades)
+        })
     }
 
-    async fn match_order(&self, order: &mut Order) -> crate::types::Result<Vec<Trade>> {
-        let mut trades = Vec::new();
+    /// Matches the incoming order against the contra side of its book.
+    fn match_order(&self, taker: &mut Taker<'_>, levels: &mut PriceLevels, all_or_none: bool) -> Vec<Trade> {
+        if !all_or_none {
+            return self.match_against(taker, levels);
+        }
+        match sweep(levels, &taker.order.side, taker.remaining) {
+            Some((worst, _)) if taker.crosses(worst) => self.match_against(taker, levels),
+            _ => Vec::new(),
+        }
+    }
+
+    fn match_against(&self, taker: &mut Taker<'_>, levels: &mut PriceLevels) -> Vec<Trade> {
+        let order = &*taker.order;
+        let algorithm = match self.instruments.matching_algorithm(&order.symbol).unwrap_or_default() {
+            MatchingAlgorithm::ProRata
+                if !self
+                    .feature_flags
+                    .is_enabled(FeatureFlag::ProRataAllocation, &order.symbol, order.account_id) =>
+            {
+                MatchingAlgorithm::PriceTime
+            }
+            algorithm => algorithm,
+        };
+        let allocator = algorithm.allocator();
+        let preference = self.instruments.firm_preference(&order.symbol);
 
-        match order.side {
-            OrderSide::Buy => {
-                trades.extend(self.match_buy_order(order).await?);
+        // Best price first: lowest ask for a buy, highest bid for a sell.
+        let prices: Vec<u64> = match order.side {
+            OrderSide::Buy => levels.keys().copied().collect(),
+            OrderSide::Sell => levels.keys().rev().copied().collect(),
+        };
+
+        let mut trades = Vec::new();
+        let mut credit_used = HashMap::new();
+        for price in prices {
+            if taker.remaining == 0 || !taker.crosses(price) {
+                break;
             }
-            OrderSide::Sell => {
-                trades.extend(self.match_sell_order(order).await?);
+
+            let Some(level) = levels.get_mut(&price) else {
+                continue;
+            };
+            trades.extend(self.match_level(taker, price, level, allocator, preference, &mut credit_used));
+            if level.is_empty() {
+                levels.remove(&price);
             }
         }
 
-        Ok(trades)
+        trades
     }
 
-    async fn match_buy_order(&self, buy_order: &mut Order) -> crate::types::Result<Vec<Trade>> {
-        let mut trades = Vec::new();
-        let mut sell_orders = self.sell_orders.write();
-        
-        if let Some(symbol_orders) = sell_orders.get_mut(&buy_order.symbol) {
-            let mut prices_to_remove = Vec::new();
-            
-            for (&price, price_level) in symbol_orders.iter_mut() {
-                // For buy orders, match against sell orders at or below the buy price
-                if let Some(buy_price) = buy_order.price {
-                    if price > buy_price {
-                        break; // Sell price too high
-                    }
-                } // Market orders match at any price
+    /// Fills every leg in full against the outright books, or none of them.
+    /// The legs' books stay locked from pricing to execution, and each leg
+    /// is sized against the resting orders it may trade with before any of
+    /// them trades, so `accept` sees the volume-weighted price each leg
+    /// will actually get. A leg that cannot be matched at all is an error.
+    pub(crate) fn execute_legs(
+        &self,
+        legs: &mut [Order],
+        accept: impl FnOnce(&[Decimal]) -> bool,
+    ) -> crate::types::Result<Option<Vec<Trade>>> {
+        let mut symbols: Vec<String> = legs.iter().map(|leg| leg.symbol.clone()).collect();
+        symbols.sort();
+        symbols.dedup();
+        let Some(shared) = symbols
+            .iter()
+            .map(|symbol| self.books.get(symbol).map(|book| book.clone()))
+            .collect::<Option<Vec<Arc<RwLock<SymbolBook>>>>>()
+        else {
+            return Ok(None);
+        };
+        // Locked in symbol order, so spreads sharing a leg cannot deadlock
+        let mut books: Vec<VersionedWriteGuard<'_, SymbolBook>> = shared
+            .iter()
+            .map(|book| VersionedWriteGuard::new(book, &self.generation))
+            .collect();
+        if books.iter().any(|book| book.closed) {
+            return Ok(None);
+        }
+        let book_of = |symbol: &String| symbols.partition_point(|locked| locked < symbol);
 
-                while let Some(mut sell_entry) = price_level.pop_front() {
-                    if buy_order.remaining_quantity <= Decimal::ZERO {
-                        price_level.push_front(sell_entry);
-                        break;
-                    }
+        let mut takers = Vec::with_capacity(legs.len());
+        let mut vwaps = Vec::with_capacity(legs.len());
+        for leg in legs.iter_mut() {
+            let mut taker = Taker::new(leg, self.tick_scale(&leg.symbol))?;
+            let book = &books[book_of(&taker.order.symbol)];
+            let levels = match taker.order.side {
+                OrderSide::Buy => &book.asks,
+                OrderSide::Sell => &book.bids,
+            };
+            let Some((worst, cost)) = self.sweep_within_credit(&mut taker, levels) else {
+                return Ok(None);
+            };
+            let Some(vwap) = taker.scale.average_price(cost, taker.remaining) else {
+                return Ok(None);
+            };
+            vwaps.push(vwap);
+            takers.push((taker, worst));
+        }
+        if !accept(&vwaps) {
+            return Ok(None);
+        }
 
-                    let trade_quantity = buy_order.remaining_quantity.min(sell_entry.order.remaining_quantity);
-                    let trade_price = price; // Price improvement for buy order
-
-                    // Create trade
-                    let trade = Trade {
-                        id: Uuid::new_v4(),
-                        symbol: buy_order.symbol.clone(),
-                        buyer_order_id: buy_order.id,
-                        seller_order_id: sell_entry.order.id,
-                        quantity: trade_quantity,
-                        price: trade_price,
-                        timestamp: Utc::now(),
-                        trade_type: TradeType::Regular,
-                    };
-
-                    // Update order quantities
-                    buy_order.remaining_quantity -= trade_quantity;
-                    buy_order.filled_quantity += trade_quantity;
-                    sell_entry.order.remaining_quantity -= trade_quantity;
-                    sell_entry.order.filled_quantity += trade_quantity;
-
-                    // Update order statuses
-                    if buy_order.remaining_quantity <= Decimal::ZERO {
-                        buy_order.status = OrderStatus::Filled;
-                    } else {
-                        buy_order.status = OrderStatus::PartiallyFilled;
-                    }
+        let mut trades = Vec::new();
+        for (mut taker, worst) in takers {
+            taker.limit = Some(worst);
+            taker.order.price = Some(taker.scale.price(worst));
+            let levels = books[book_of(&taker.order.symbol)].contra_mut(&taker.order.side);
+            trades.extend(self.match_against(&mut taker, levels));
+        }
+        Ok(Some(trades))
+    }
 
-                    if sell_entry.order.remaining_quantity <= Decimal::ZERO {
-                        sell_entry.order.status = OrderStatus::Filled;
-                        // Remove from index
-                        self.order_index.remove(&sell_entry.order.id);
-                    } else {
-                        sell_entry.order.status = OrderStatus::PartiallyFilled;
-                        price_level.push_front(sell_entry);
-                    }
+    /// Like `sweep`, but passes over the resting orders `match_level` would
+    /// leave out for the taker's credit lines.
+    fn sweep_within_credit(&self, taker: &mut Taker<'_>, levels: &PriceLevels) -> Option<(u64, u128)> {
+        let quantity = taker.remaining;
+        let ordered: Box<dyn Iterator<Item = (&u64, &VecDeque<OrderBookEntry>)>> = match taker.order.side {
+            OrderSide::Buy => Box::new(levels.iter()),
+            OrderSide::Sell => Box::new(levels.iter().rev()),
+        };
 
-                    trades.push(trade.clone());
-                    
-                    // Send events
-                    let _ = self.event_sender.send(EngineEvent::TradeExecuted(trade.clone()));
-                    let _ = self.event_sender.send(EngineEvent::OrderFilled {
-                        order_id: buy_order.id,
-                        trade: trade.clone(),
-                    });
-                    let _ = self.event_sender.send(EngineEvent::OrderFilled {
-                        order_id: sell_entry.order.id,
-                        trade: trade.clone(),
-                    });
-
-                    self.metrics.increment_trades_executed();
-                    
-                    debug!("Trade executed: {} {} @ {} between orders {} and {}", 
-                           trade_quantity, buy_order.symbol, trade_price, 
-                           buy_order.id, sell_entry.order.id);
-                }
+        let mut credit_used = HashMap::new();
+        let mut cost = 0u128;
+        let mut swept = None;
+        for (&price, level) in ordered {
+            let mut reserved = credit_used.clone();
+            let eligible: Vec<&OrderBookEntry> = level
+                .iter()
+                .filter(|entry| self.within_credit_line(taker, entry, price, &mut reserved))
+                .collect();
+            for entry in eligible {
+                let take = taker.remaining.min(entry.remaining);
+                taker.remaining -= take;
+                cost += u128::from(take) * u128::from(price);
+                *credit_used.entry(entry.order.account_id).or_default() +=
+                    notional_value(taker.scale.quantity(take), taker.scale.price(price));
+            }
+            if taker.remaining == 0 {
+                swept = Some((price, cost));
+                break;
+            }
+        }
+        taker.remaining = quantity;
+        swept
+    }
 
-                if price_level.is_empty() {
-                    prices_to_remove.push(price);
-                }
+    /// Fills a marketable order against resting orders from other accounts
+    /// of its own firm at the best contra price, ahead of time priority.
+    /// Crosses only happen at the touch, so the client never trades worse
+    /// than the public book would have given them.
+    fn internalize(&self, taker: &mut Taker<'_>, levels: &mut PriceLevels) -> Vec<Trade> {
+        if self.firms.firm_of(taker.order.account_id).is_none() {
+            return Vec::new();
+        }
 
-                if buy_order.remaining_quantity <= Decimal::ZERO {
-                    break;
-                }
-            }
+        let touch = match taker.order.side {
+            OrderSide::Buy => levels.keys().next().copied(),
+            OrderSide::Sell => levels.keys().next_back().copied(),
+        };
+        let Some(price) = touch.filter(|price| taker.crosses(*price)) else {
+            return Vec::new();
+        };
 
-            // Clean up empty price levels
-            for price in prices_to_remove {
-                symbol_orders.remove(&price);
+        let Some(level) = levels.get_mut(&price) else {
+            return Vec::new();
+        };
+        let mut trades = Vec::new();
+        for entry in level.iter_mut() {
+            if taker.remaining == 0 {
+                break;
+            }
+            if !self.firms.same_firm(taker.order.account_id, entry.order.account_id) {
+                continue;
             }
+            let quantity = taker.remaining.min(entry.remaining);
+            trades.push(self.execute_fill(taker, entry, quantity, price, true));
         }
 
-        Ok(trades)
+        self.remove_filled(level);
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+        trades
     }
 
-    async fn match_sell_order(&self, sell_order: &mut Order) -> crate::types::Result<Vec<Trade>> {
-        let mut trades = Vec::new();
-        let mut buy_orders = self.buy_orders.write();
-        
-        if let Some(symbol_orders) = buy_orders.get_mut(&sell_order.symbol) {
-            let mut prices_to_remove = Vec::new();
-            
-            // Iterate through buy orders from highest to lowest price
-            for (&price, price_level) in symbol_orders.iter_mut().rev() {
-                // For sell orders, match against buy orders at or above the sell price
-                if let Some(sell_price) = sell_order.price {
-                    if price < sell_price {
-                        break; // Buy price too low
-                    }
-                } // Market orders match at any price
+    /// Fills the incoming order against one price level, sharing quantity
+    /// among the resting orders as the instrument's allocator and firm
+    /// preference decide. `credit_used` carries the notional this order
+    /// has already traded per counterparty, for the credit line checks.
+    fn match_level(
+        &self,
+        taker: &mut Taker<'_>,
+        price: u64,
+        level: &mut VecDeque<OrderBookEntry>,
+        allocator: &dyn MatchAllocator,
+        preference: FirmPreference,
+        credit_used: &mut HashMap<Uuid, Decimal>,
+    ) -> Vec<Trade> {
+        let account_id = taker.order.account_id;
+        let mut reserved = credit_used.clone();
+        let interest: Vec<RestingInterest> = level
+            .iter()
+            .map(|entry| RestingInterest {
+                remaining: entry.remaining,
+                priority: entry.priority,
+                same_firm: preference != FirmPreference::None
+                    && self.firms.same_firm(account_id, entry.order.account_id),
+                eligible: self.within_credit_line(taker, entry, price, &mut reserved),
+            })
+            .collect();
 
-                while let Some(mut buy_entry) = price_level.pop_front() {
-                    if sell_order.remaining_quantity <= Decimal::ZERO {
-                        price_level.push_front(buy_entry);
-                        break;
-                    }
+        let trades: Vec<Trade> = allocator
+            .allocate_with_preference(&interest, taker.remaining, taker.scale.lot(), preference)
+            .into_iter()
+            .map(|allocation| {
+                let resting = &mut level[allocation.index];
+                self.execute_fill(taker, resting, allocation.quantity, price, false)
+            })
+            .collect();
+        for trade in &trades {
+            let counterparty = match taker.order.side {
+                OrderSide::Buy => trade.seller_account_id,
+                OrderSide::Sell => trade.buyer_account_id,
+            };
+            *credit_used.entry(counterparty).or_default() += notional_value(trade.quantity, trade.price);
+        }
 
-                    let trade_quantity = sell_order.remaining_quantity.min(buy_entry.order.remaining_quantity);
-                    let trade_price = price; // Price improvement for sell order
-
-                    // Create trade
-                    let trade = Trade {
-                        id: Uuid::new_v4(),
-                        symbol: sell_order.symbol.clone(),
-                        buyer_order_id: buy_entry.order.id,
-                        seller_order_id: sell_order.id,
-                        quantity: trade_quantity,
-                        price: trade_price,
-                        timestamp: Utc::now(),
-                        trade_type: TradeType::Regular,
-                    };
-
-                    // Update order quantities
-                    sell_order.remaining_quantity -= trade_quantity;
-                    sell_order.filled_quantity += trade_quantity;
-                    buy_entry.order.remaining_quantity -= trade_quantity;
-                    buy_entry.order.filled_quantity += trade_quantity;
-
-                    // Update order statuses
-                    if sell_order.remaining_quantity <= Decimal::ZERO {
-                        sell_order.status = OrderStatus::Filled;
-                    } else {
-                        sell_order.status = OrderStatus::PartiallyFilled;
-                    }
+        self.remove_filled(level);
+        trades
+    }
 
-                    if buy_entry.order.remaining_quantity <= Decimal::ZERO {
-                        buy_entry.order.status = OrderStatus::Filled;
-                        // Remove from index
-                        self.order_index.remove(&buy_entry.order.id);
-                    } else {
-                        buy_entry.order.status = OrderStatus::PartiallyFilled;
-                        price_level.push_front(buy_entry);
-                    }
+    /// Whether a full fill against `entry` fits in the credit line between
+    /// the two accounts, after what the order has traded or may trade with
+    /// the same counterparty. Cleared trades face the CCP instead and are
+    /// not checked.
+    fn within_credit_line(
+        &self,
+        taker: &Taker<'_>,
+        entry: &OrderBookEntry,
+        price: u64,
+        reserved: &mut HashMap<Uuid, Decimal>,
+    ) -> bool {
+        if self.config.ccp_enabled {
+            return true;
+        }
+        let quantity = taker.scale.quantity(taker.remaining.min(entry.remaining));
+        let notional = notional_value(quantity, taker.scale.price(price));
+        self.credit_lines
+            .reserve(taker.order.account_id, entry.order.account_id, notional, reserved)
+    }
 
-                    trades.push(trade.clone());
-                    
-                    // Send events
-                    let _ = self.event_sender.send(EngineEvent::TradeExecuted(trade.clone()));
-                    let _ = self.event_sender.send(EngineEvent::OrderFilled {
-                        order_id: sell_order.id,
-                        trade: trade.clone(),
-                    });
-                    let _ = self.event_sender.send(EngineEvent::OrderFilled {
-                        order_id: buy_entry.order.id,
-                        trade: trade.clone(),
-                    });
-
-                    self.metrics.increment_trades_executed();
-                    
-                    debug!("Trade executed: {} {} @ {} between orders {} and {}", 
-                           trade_quantity, sell_order.symbol, trade_price, 
-                           buy_entry.order.id, sell_order.id);
-                }
+    fn execute_fill(
+        &self,
+        taker: &mut Taker<'_>,
+        resting: &mut OrderBookEntry,
+        quantity: u64,
+        price: u64,
+        internalized: bool,
+    ) -> Trade {
+        taker.remaining -= quantity;
+        resting.remaining -= quantity;
 
-                if price_level.is_empty() {
-                    prices_to_remove.push(price);
-                }
+        // Back to decimals for the trade and the orders' reported state
+        let scale = taker.scale;
+        let (order, quantity) = (&mut *taker.order, scale.quantity(quantity));
+        let mut trade = self.build_trade(order, &resting.order, quantity, scale.price(price));
+        trade.internalized = internalized;
 
-                if sell_order.remaining_quantity <= Decimal::ZERO {
-                    break;
-                }
+        fill(order, quantity, trade.price, taker.remaining);
+        fill(&mut resting.order, quantity, trade.price, resting.remaining);
+        self.activity.resting_filled(&resting.order, quantity);
+
+        self.publish_trade(&trade, order, &resting.order);
+        debug!(
+            "Trade executed: {} {} @ {} between orders {} and {}",
+            trade.quantity, trade.symbol, trade.price, trade.buyer_order_id, trade.seller_order_id
+        );
+        trade
+    }
+
+    fn remove_filled(&self, level: &mut VecDeque<OrderBookEntry>) {
+        level.retain(|entry| {
+            let open = entry.remaining > 0;
+            if !open {
+                self.order_index.remove(&entry.order.id);
+                self.activity.order_left_book(&entry.order);
             }
+            open
+        });
+    }
+
+    pub(crate) fn build_trade(&self, aggressor: &Order, resting: &Order, quantity: Decimal, price: Decimal) -> Trade {
+        let (buyer, seller) = match aggressor.side {
+            OrderSide::Buy => (aggressor, resting),
+            OrderSide::Sell => (resting, aggressor),
+        };
 
-            // Clean up empty price levels
-            for price in prices_to_remove {
-                symbol_orders.remove(&price);
+        let sequence = {
+            let mut next = self.next_trade_sequence.lock();
+            *next += 1;
+            *next
+        };
+        let (id, trade_number) = trade_identity(&self.config.engine_instance_id, sequence, buyer.id, seller.id);
+
+        let now = self.time_provider.now();
+        let settlement_date = self
+            .time_provider
+            .settlement_date(now.date_naive(), self.config.settlement_cycle_days);
+        let (accrued_interest, quoted_price, index_ratio) = match self.instruments.get(&aggressor.symbol) {
+            Some(bond) => {
+                let accrued = accrued::accrued_interest(&bond, settlement_date, &self.fixings);
+                (
+                    accrued,
+                    self.tick_scale(&aggressor.symbol)
+                        .round_price(accrued::to_quoted(bond.quote_convention, price, accrued)),
+                    self.inflation_indices.index_ratio(&bond, settlement_date),
+                )
             }
+            None => (Decimal::ZERO, price, None),
+        };
+        let mut trade = Trade {
+            id,
+            trade_number,
+            symbol: aggressor.symbol.clone(),
+            buyer_order_id: buyer.id,
+            seller_order_id: seller.id,
+            buyer_account_id: buyer.account_id,
+            seller_account_id: seller.account_id,
+            quantity,
+            price,
+            accrued_interest,
+            quoted_price,
+            settlement_amount: notional_value(quantity, price + accrued_interest) * index_ratio.unwrap_or(Decimal::ONE),
+            index_ratio,
+            timestamp: now,
+            settlement_date,
+            trade_type: TradeType::Regular,
+            aggressor_side: aggressor.side.clone(),
+            maker_order_id: resting.id,
+            buyer_fees: FeeBreakdown::default(),
+            seller_fees: FeeBreakdown::default(),
+            buyer_strategy_id: buyer.strategy_id.clone(),
+            seller_strategy_id: seller.strategy_id.clone(),
+            internalized: false,
+        };
+        self.fee_engine.apply(&mut trade);
+        trade
+    }
+
+    pub(crate) fn publish_trade(&self, trade: &Trade, aggressor: &Order, resting: &Order) {
+        let accounts = vec![aggressor.account_id, resting.account_id];
+        let sequence = self.event_journal.publish(EngineEvent::TradeExecuted(trade.clone()), accounts);
+        logging::audit_trade(&self.config.engine_instance_id, trade, sequence);
+        for order in [aggressor, resting] {
+            self.event_journal.publish(
+                EngineEvent::OrderFilled {
+                    order_id: order.id,
+                    trade: trade.clone(),
+                },
+                vec![order.account_id],
+            );
         }
 
-        Ok(trades)
+        self.metrics.increment_trades_executed();
     }
 
-    async fn add_to_order_book(&self, order: Order) -> crate::types::Result<()> {
+    fn add_to_order_book(
+        &self,
+        levels: &mut PriceLevels,
+        order: Order,
+        limit: Option<u64>,
+        remaining: u64,
+    ) -> crate::types::Result<()> {
+        let price = limit.ok_or_else(|| {
+            TradingError::InvalidOrder("Cannot add market order to book".to_string())
+        })?;
+
         let priority = {
             let mut next_priority = self.next_priority.lock();
             *next_priority += 1;
             *next_priority
         };
 
-        let entry = OrderBookEntry::new(order.clone(), priority);
-        
-        let price = order.price.ok_or_else(|| {
-            TradingError::InvalidOrder("Cannot add market order to book".to_string())
-        })?;
-
-        match order.side {
-            OrderSide::Buy => {
-                let mut buy_orders = self.buy_orders.write();
-                buy_orders
-                    .entry(order.symbol.clone())
-                    .or_insert_with(BTreeMap::new)
-                    .entry(price)
-                    .or_insert_with(VecDeque::new)
-                    .push_back(entry);
-            }
-            OrderSide::Sell => {
-                let mut sell_orders = self.sell_orders.write();
-                sell_orders
-                    .entry(order.symbol.clone())
-                    .or_insert_with(BTreeMap::new)
-                    .entry(price)
-                    .or_insert_with(VecDeque::new)
-                    .push_back(entry);
-            }
-        }
+        let entry = OrderBookEntry::new(order.clone(), priority, remaining);
+        levels.entry(price).or_default().push_back(entry);
 
         // Update index
-        self.order_index.insert(order.id, (order.symbol.clone(), price, order.side));
+        self.activity.order_rested(&order);
+        self.order_index
+            .insert(order.id, (order.symbol.clone(), price, order.side, order.account_id));
         
-        info!("Order {} added to book: {} {} @ {}", 
-              order.id, order.remaining_quantity, order.symbol, price);
+        info!(
+            order_id = %order.id,
+            account_id = %order.account_id,
+            symbol = %order.symbol,
+            remaining = %order.remaining_quantity,
+            price = %order.price.unwrap_or_default(),
+            "Order added to book"
+        );
 
         Ok(())
     }
 
     pub async fn cancel_order(&self, order_id: Uuid) -> crate::types::Result<bool> {
-        if let Some((_, (symbol, price, side))) = self.order_index.remove(&order_id) {
-            match side {
-                OrderSide::Buy => {
-                    let mut buy_orders = self.buy_orders.write();
-                    if let Some(symbol_orders) = buy_orders.get_mut(&symbol) {
-                        if let Some(price_level) = symbol_orders.get_mut(&price) {
-                            price_level.retain(|entry| entry.order.id != order_id);
-                            if price_level.is_empty() {
-                                symbol_orders.remove(&price);
-                            }
+        if let Some((_, (symbol, price, side, _))) = self.order_index.remove(&order_id) {
+            self.with_existing_book(&symbol, |book| {
+                let symbol_orders = book.side_mut(&side);
+                if let Some(price_level) = symbol_orders.get_mut(&price) {
+                    if let Some(position) = price_level.iter().position(|entry| entry.order.id == order_id) {
+                        if let Some(entry) = price_level.remove(position) {
+                            self.activity.order_left_book(&entry.order);
                         }
                     }
-                }
-                OrderSide::Sell => {
-                    let mut sell_orders = self.sell_orders.write();
-                    if let Some(symbol_orders) = sell_orders.get_mut(&symbol) {
-                        if let Some(price_level) = symbol_orders.get_mut(&price) {
-                            price_level.retain(|entry| entry.order.id != order_id);
-                            if price_level.is_empty() {
-                                symbol_orders.remove(&price);
-                            }
-                        }
+                    if price_level.is_empty() {
+                        symbol_orders.remove(&price);
                     }
                 }
-            }
+            });
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Cuts a resting order's total quantity to `quantity` in its book
+    /// entry, keeping its place in the queue. Returns the quantity taken
+    /// off, or None if the order is not resting.
+    pub fn reduce_order(&self, order_id: Uuid, quantity: Decimal) -> crate::types::Result<Option<Decimal>> {
+        let Some((symbol, price, side, _)) = self.order_index.get(&order_id).map(|entry| entry.clone()) else {
+            return Ok(None);
+        };
+        let scale = self.tick_scale(&symbol);
+        self.with_existing_book(&symbol, |book| {
+            let Some(entry) = book
+                .side_mut(&side)
+                .get_mut(&price)
+                .and_then(|level| level.iter_mut().find(|entry| entry.order.id == order_id))
+            else {
+                return Ok(None);
+            };
+            self.reduce_entry(entry, scale, quantity)
+        })
+        .unwrap_or(Ok(None))
+    }
+
+    fn reduce_entry(
+        &self,
+        entry: &mut OrderBookEntry,
+        scale: TickScale,
+        quantity: Decimal,
+    ) -> crate::types::Result<Option<Decimal>> {
+        let invalid = |message: String| TradingError::InvalidOrderField {
+            field: OrderField::Quantity,
+            message,
+        };
+        // Checked again here as fills may have landed since the caller looked
+        let filled = entry.order.filled_quantity;
+        if quantity <= filled {
+            return Err(invalid(format!("Quantity {} leaves nothing open after {} filled", quantity, filled)));
+        }
+        if quantity >= entry.order.quantity {
+            return Err(invalid(format!("Quantity {} is not below the order's {}", quantity, entry.order.quantity)));
+        }
+        let remaining = scale.quantity_ticks(quantity - filled).ok_or_else(|| {
+            invalid(format!("Quantity {} is finer than {} decimal places", quantity, scale.quantity_decimals))
+        })?;
+
+        let reduced_by = entry.order.quantity - quantity;
+        entry.remaining = remaining;
+        entry.order.quantity = quantity;
+        entry.order.remaining_quantity -= reduced_by;
+        self.activity.resting_reduced(&entry.order, reduced_by);
+        Ok(Some(reduced_by))
+    }
+
+    /// The book's copy of a resting order, None if it is not resting.
+    pub fn resting_order(&self, order_id: Uuid) -> Option<Order> {
+        let (symbol, price, side, _) = self.order_index.get(&order_id).map(|entry| entry.clone())?;
+        self.read_book(&symbol, |book| {
+            let level = book.side(&side).get(&price)?;
+            level
+                .iter()
+                .find(|entry| entry.order.id == order_id)
+                .map(|entry| entry.order.clone())
+        })
+        .flatten()
+    }
+
+    /// Runs `f` with the symbol's book write-locked, opening the book if
+    /// the symbol has none.
+    fn with_book<R>(&self, symbol: &str, f: impl FnOnce(&mut SymbolBook) -> R) -> R {
+        let shared = self.books.entry(symbol.to_string()).or_default().clone();
+        let mut book = VersionedWriteGuard::new(&shared, &self.generation);
+        if book.closed {
+            drop(book);
+            return self.with_book(symbol, f);
+        }
+        f(&mut book)
+    }
+
+    /// Runs `f` with the symbol's book write-locked, None if it has none.
+    fn with_existing_book<R>(&self, symbol: &str, f: impl FnOnce(&mut SymbolBook) -> R) -> Option<R> {
+        let shared = self.books.get(symbol)?.clone();
+        let mut book = VersionedWriteGuard::new(&shared, &self.generation);
+        if book.closed {
+            return None;
+        }
+        Some(f(&mut book))
+    }
+
+    /// Runs `f` with the symbol's book read-locked, None if it has none.
+    fn read_book<R>(&self, symbol: &str, f: impl FnOnce(&SymbolBook) -> R) -> Option<R> {
+        let shared = self.books.get(symbol)?.clone();
+        let book = shared.read();
+        Some(f(&book))
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// The scale a symbol's book is kept in.
+    pub fn tick_scale(&self, symbol: &str) -> TickScale {
+        self.tick_scales.get(symbol).map(|scale| *scale).unwrap_or_default()
+    }
+
+    /// Changes the scale of a symbol's book, which must be empty unless
+    /// the scale stays the same.
+    pub fn set_tick_scale(&self, symbol: &str, scale: TickScale) -> crate::types::Result<()> {
+        if !scale.is_valid() {
+            return Err(TradingError::InvalidRequest(format!(
+                "Tick scales are limited to {} decimal places",
+                crate::engine::fixed_point::MAX_DECIMALS
+            )));
+        }
+        // The book stays locked so no order can rest at the old scale
+        self.with_book(symbol, |book| {
+            if !book.is_empty() && self.tick_scale(symbol) != scale {
+                return Err(TradingError::InvalidRequest(format!(
+                    "Cannot change the tick scale of {} while orders rest on its book",
+                    symbol
+                )));
+            }
+            self.tick_scales.insert(symbol.to_string(), scale);
+            Ok(())
+        })
+    }
+
+    /// The resting orders with their time priority, and the counters the
+    /// next entry and trade would be numbered from.
+    pub fn snapshot(&self) -> BookSnapshot {
+        // Every book and the counters are read under the same locks, so
+        // the snapshot is a single point in the order flow. Books are
+        // locked in symbol order, as `execute_legs` does
+        let mut shared: Vec<(String, Arc<RwLock<SymbolBook>>)> = self
+            .books
+            .iter()
+            .map(|book| (book.key().clone(), book.value().clone()))
+            .collect();
+        shared.sort_by(|a, b| a.0.cmp(&b.0));
+        let books: Vec<_> = shared.iter().map(|(_, book)| book.read()).collect();
+        let mut entries: Vec<RestingEntry> = books
+            .iter()
+            .flat_map(|book| book.bids.values().chain(book.asks.values()))
+            .flatten()
+            .map(|entry| RestingEntry {
+                order: entry.order.clone(),
+                priority: entry.priority,
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.priority);
+        BookSnapshot {
+            next_priority: *self.next_priority.lock(),
+            next_trade_sequence: *self.next_trade_sequence.lock(),
+            tick_scales: self
+                .tick_scales
+                .iter()
+                .map(|scale| (scale.key().clone(), *scale.value()))
+                .collect(),
+            entries,
+        }
+    }
+
+    /// Rebuilds the books from a snapshot, each entry keeping its
+    /// priority. Only allowed before anything rests on the books.
+    pub fn restore(&self, snapshot: BookSnapshot) -> crate::types::Result<()> {
+        if !self.order_index.is_empty() {
+            return Err(TradingError::InvalidRequest(
+                "Books can only be restored into an empty engine".to_string(),
+            ));
+        }
+
+        for (symbol, scale) in snapshot.tick_scales {
+            self.tick_scales.insert(symbol, scale);
+        }
+        let mut entries = snapshot.entries;
+        entries.sort_by_key(|entry| entry.priority);
+        let mut last_priority = 0;
+        for RestingEntry { order, priority } in entries {
+            let scale = self.tick_scale(&order.symbol);
+            let ticks = order.price.and_then(|price| scale.price_ticks(price));
+            let (Some(price), Some(remaining)) = (ticks, scale.quantity_ticks(order.remaining_quantity)) else {
+                return Err(TradingError::InvalidRequest(format!(
+                    "Order {} does not fit the tick scale of {}",
+                    order.id, order.symbol
+                )));
+            };
+            self.order_index
+                .insert(order.id, (order.symbol.clone(), price, order.side.clone(), order.account_id));
+            self.activity.order_rested(&order);
+            last_priority = last_priority.max(priority);
+            self.with_book(&order.symbol.clone(), |book| {
+                book.side_mut(&order.side)
+                    .entry(price)
+                    .or_default()
+                    .push_back(OrderBookEntry::new(order, priority, remaining));
+            });
+        }
+
+        *self.next_priority.lock() = snapshot.next_priority.max(last_priority);
+        let mut next_trade_sequence = self.next_trade_sequence.lock();
+        *next_trade_sequence = (*next_trade_sequence).max(snapshot.next_trade_sequence);
+        Ok(())
+    }
+
+    /// Creates an empty book for a newly listed symbol.
+    pub fn open_book(&self, symbol: &str) {
+        self.with_book(symbol, |_| ());
+    }
+
+    /// Removes a symbol's book entirely and returns the ids of the orders
+    /// that were resting on it.
+    pub fn close_book(&self, symbol: &str) -> Vec<Uuid> {
+        let Some((_, shared)) = self.books.remove(symbol) else {
+            return Vec::new();
+        };
+        let mut book = VersionedWriteGuard::new(&shared, &self.generation);
+        book.closed = true;
+        let (bids, asks) = (std::mem::take(&mut book.bids), std::mem::take(&mut book.asks));
+        let mut removed = Vec::new();
+        for entry in bids.values().chain(asks.values()).flatten() {
+            if self.order_index.remove(&entry.order.id).is_some() {
+                self.activity.order_left_book(&entry.order);
+            }
+            removed.push(entry.order.id);
+        }
+        removed
+    }
+
     pub fn get_best_bid(&self, symbol: &str) -> Option<Decimal> {
-        let buy_orders = self.buy_orders.read();
-        buy_orders
-            .get(symbol)?
-            .keys()
-            .next_back()
-            .copied()
+        let best = self.read_book(symbol, |book| book.bids.keys().next_back().copied())??;
+        Some(self.tick_scale(symbol).price(best))
     }
 
     pub fn get_best_ask(&self, symbol: &str) -> Option<Decimal> {
-        let sell_orders = self.sell_orders.read();
-        sell_orders
-            .get(symbol)?
-            .keys()
-            .next()
-            .copied()
+        let best = self.read_book(symbol, |book| book.asks.keys().next().copied())??;
+        Some(self.tick_scale(symbol).price(best))
+    }
+
+    /// Best price and the total quantity resting at it on one side of the
+    /// book: bids for `Buy`, asks for `Sell`.
+    pub fn best_level(&self, symbol: &str, side: &OrderSide) -> Option<(Decimal, Decimal)> {
+        let scale = self.tick_scale(symbol);
+        self.read_book(symbol, |book| {
+            let levels = book.side(side);
+            let (price, level) = match side {
+                OrderSide::Buy => levels.iter().next_back()?,
+                OrderSide::Sell => levels.iter().next()?,
+            };
+            Some((scale.price(*price), scale.quantity(level.iter().map(|entry| entry.remaining).sum())))
+        })?
+    }
+
+    /// Symbols with a book.
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.books.iter().map(|book| book.key().clone()).collect();
+        symbols.sort();
+        symbols
+    }
+
+    pub fn open_interest(&self, symbol: &str) -> OpenInterest {
+        let scale = self.tick_scale(symbol);
+        let side = |levels: &PriceLevels| {
+            let entries = levels.values().flatten();
+            (scale.quantity(entries.clone().map(|entry| entry.remaining).sum()), entries.count())
+        };
+        let ((bid_quantity, bid_orders), (ask_quantity, ask_orders)) = self
+            .read_book(symbol, |book| (side(&book.bids), side(&book.asks)))
+            .unwrap_or(((Decimal::ZERO, 0), (Decimal::ZERO, 0)));
+        OpenInterest {
+            bid_quantity,
+            ask_quantity,
+            bid_orders,
+            ask_orders,
+        }
+    }
+
+    pub fn book_footprint(&self, symbol: &str) -> BookFootprint {
+        self.read_book(symbol, |book| {
+            let mut footprint = BookFootprint {
+                bid_levels: book.bids.len(),
+                ask_levels: book.asks.len(),
+                ..BookFootprint::default()
+            };
+            for level in book.bids.values().chain(book.asks.values()) {
+                footprint.resting_orders += level.len();
+                footprint.estimated_bytes += std::mem::size_of::<(u64, VecDeque<OrderBookEntry>)>()
+                    + (level.capacity() - level.len()) * std::mem::size_of::<OrderBookEntry>()
+                    + level.iter().map(OrderBookEntry::estimated_bytes).sum::<usize>();
+            }
+            footprint
+        })
+        .unwrap_or_default()
+    }
+
+    /// Gives back the spare capacity of a symbol's price levels.
+    pub fn compact(&self, symbol: &str) {
+        self.with_existing_book(symbol, |book| {
+            for levels in [&mut book.bids, &mut book.asks] {
+                levels.values_mut().for_each(VecDeque::shrink_to_fit);
+            }
+        });
+    }
+
+    /// Resting orders at the best `levels` prices of each side, bids
+    /// highest first and asks lowest first, in time priority within a
+    /// price. Prices are clean.
+    pub fn resting_orders(&self, symbol: &str, levels: usize) -> (Vec<BookOrder>, Vec<BookOrder>) {
+        let scale = self.tick_scale(symbol);
+        let orders = |prices: &mut dyn Iterator<Item = (&u64, &VecDeque<OrderBookEntry>)>| -> Vec<BookOrder> {
+            prices
+                .take(levels)
+                .flat_map(|(price, level)| {
+                    level.iter().map(|entry| BookOrder {
+                        order_id: entry.order.id,
+                        price: scale.price(*price),
+                        quantity: scale.quantity(entry.remaining),
+                        priority: entry.priority,
+                    })
+                })
+                .collect()
+        };
+
+        self.read_book(symbol, |book| (orders(&mut book.bids.iter().rev()), orders(&mut book.asks.iter())))
+            .unwrap_or_default()
+    }
+
+    /// Aggregated price levels for a symbol, bids highest first and asks
+    /// lowest first.
+    pub fn depth(&self, symbol: &str) -> Option<(Vec<PriceLevel>, Vec<PriceLevel>)> {
+        let scale = self.tick_scale(symbol);
+        let aggregate = |(price, level): (&u64, &VecDeque<OrderBookEntry>)| PriceLevel {
+            price: scale.price(*price),
+            quantity: scale.quantity(level.iter().map(|entry| entry.remaining).sum()),
+            order_count: level.len() as u32,
+            implied: false,
+        };
+
+        self.read_book(symbol, |book| {
+            let bids = book.bids.iter().rev().map(aggregate).collect();
+            let asks = book.asks.iter().map(aggregate).collect();
+            (bids, asks)
+        })
+    }
+}
+
+/// Applies a fill of `quantity` at `price` to an order's reported state,
+/// `remaining` being its open quantity in ticks afterwards.
+fn fill(order: &mut Order, quantity: Decimal, price: Decimal, remaining: u64) {
+    order.remaining_quantity -= quantity;
+    order.filled_quantity += quantity;
+    order.fills.record(quantity, price, order.filled_quantity);
+    order.status = if remaining == 0 {
+        OrderStatus::Filled
+    } else {
+        OrderStatus::PartiallyFilled
+    };
+}
+
+/// Worst price reached and total cost (price ticks times quantity ticks)
+/// for taking `quantity` from `levels`, or `None` if the book is too thin.
+fn sweep(levels: &PriceLevels, side: &OrderSide, quantity: u64) -> Option<(u64, u128)> {
+    let ordered: Box<dyn Iterator<Item = (&u64, &VecDeque<OrderBookEntry>)>> = match side {
+        OrderSide::Buy => Box::new(levels.iter()),
+        OrderSide::Sell => Box::new(levels.iter().rev()),
+    };
+
+    let mut left = quantity;
+    let mut cost = 0u128;
+    for (&price, level) in ordered {
+        let available: u64 = level.iter().map(|entry| entry.remaining).sum();
+        let take = available.min(left);
+        cost += u128::from(take) * u128::from(price);
+        left -= take;
+        if left == 0 {
+            return Some((price, cost));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::credit_lines::CreditLineRequest, test_support::OrderBuilder};
+    use rust_decimal_macros::dec;
+
+    fn matching_engine(config: Config) -> (MatchingEngine, Arc<CreditLineBook>) {
+        let time_provider = Arc::new(TimeProvider::new());
+        let instruments = Arc::new(InstrumentRegistry::new(time_provider.clone()));
+        let credit_lines = Arc::new(CreditLineBook::new(time_provider.clone()));
+        let engine = MatchingEngine::new(
+            Arc::new(config.clone()),
+            Arc::new(EventJournal::new(100, 100, time_provider.clone())),
+            Arc::new(Metrics::new()),
+            time_provider,
+            Arc::new(FeeEngine::new(instruments.clone())),
+            instruments,
+            Arc::new(FirmRegistry::new()),
+            credit_lines.clone(),
+            Arc::new(OrderActivity::new()),
+            Arc::new(FixingStore::new()),
+            Arc::new(InflationIndexStore::new()),
+            Arc::new(FeatureFlags::new(config.feature_flags)),
+        );
+        (engine, credit_lines)
+    }
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_better_prices_then_earlier_orders_fill_first() {
+        let (engine, _) = matching_engine(Config::default());
+        let first = order(OrderSide::Sell, dec!(100000), dec!(99.50), Uuid::new_v4());
+        let second = order(OrderSide::Sell, dec!(100000), dec!(99.50), Uuid::new_v4());
+        let best = order(OrderSide::Sell, dec!(100000), dec!(99.25), Uuid::new_v4());
+        for resting in [&first, &second, &best] {
+            engine.process_order_with(resting.clone(), Unfilled::Rest).await.unwrap();
+        }
+
+        let buy = order(OrderSide::Buy, dec!(250000), dec!(99.50), Uuid::new_v4());
+        let trades = engine.process_order_with(buy, Unfilled::Rest).await.unwrap();
+        let fills: Vec<(Uuid, Decimal, Decimal)> = trades
+            .iter()
+            .map(|trade| (trade.maker_order_id, trade.price, trade.quantity))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (best.id, dec!(99.25), dec!(100000)),
+                (first.id, dec!(99.50), dec!(100000)),
+                (second.id, dec!(99.50), dec!(50000)),
+            ]
+        );
+        let left = engine.resting_order(second.id).unwrap();
+        assert_eq!(left.remaining_quantity, dec!(50000));
+        assert_eq!(left.status, OrderStatus::PartiallyFilled);
+    }
+
+    #[tokio::test]
+    async fn test_partial_fills_rest_cancel_or_kill_the_remainder() {
+        let (engine, _) = matching_engine(Config::default());
+        let seller = Uuid::new_v4();
+        engine
+            .process_order_with(order(OrderSide::Sell, dec!(100000), dec!(99.50), seller), Unfilled::Rest)
+            .await
+            .unwrap();
+
+        // Too little on offer to fill it all: nothing trades
+        let kill = order(OrderSide::Buy, dec!(150000), dec!(99.50), Uuid::new_v4());
+        assert!(engine.process_order_with(kill, Unfilled::Kill).await.unwrap().is_empty());
+        assert_eq!(engine.best_level("GSEC10Y", &OrderSide::Sell), Some((dec!(99.50), dec!(100000))));
+
+        let rest = order(OrderSide::Buy, dec!(150000), dec!(99.50), Uuid::new_v4());
+        let trades = engine.process_order_with(rest.clone(), Unfilled::Rest).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, dec!(100000));
+        let resting = engine.resting_order(rest.id).unwrap();
+        assert_eq!(resting.remaining_quantity, dec!(50000));
+        assert_eq!(resting.filled_quantity, dec!(100000));
+        assert_eq!(resting.status, OrderStatus::PartiallyFilled);
+        assert_eq!(engine.get_best_ask("GSEC10Y"), None);
+
+        engine
+            .process_order_with(order(OrderSide::Buy, dec!(50000), dec!(99.25), seller), Unfilled::Rest)
+            .await
+            .unwrap();
+        let cancel = order(OrderSide::Sell, dec!(200000), dec!(99.25), Uuid::new_v4());
+        let trades = engine.process_order_with(cancel.clone(), Unfilled::Cancel).await.unwrap();
+        assert_eq!(trades.iter().map(|trade| trade.quantity).sum::<Decimal>(), dec!(100000));
+        assert!(engine.resting_order(cancel.id).is_none());
+        assert_eq!(engine.get_best_bid("GSEC10Y"), None);
+    }
+
+    #[tokio::test]
+    async fn test_prices_are_held_to_the_tick_scale() {
+        let (engine, _) = matching_engine(Config::default());
+        let scale = TickScale {
+            price_decimals: 2,
+            quantity_decimals: 0,
+        };
+        engine.set_tick_scale("GSEC10Y", scale).unwrap();
+
+        let finer = order(OrderSide::Buy, dec!(100000), dec!(99.125), Uuid::new_v4());
+        assert!(matches!(
+            engine.process_order_with(finer, Unfilled::Rest).await,
+            Err(TradingError::InvalidOrderField { field: OrderField::Price, .. })
+        ));
+        let fractional = order(OrderSide::Buy, dec!(100000.5), dec!(99.25), Uuid::new_v4());
+        assert!(matches!(
+            engine.process_order_with(fractional, Unfilled::Rest).await,
+            Err(TradingError::InvalidOrderField { field: OrderField::Quantity, .. })
+        ));
+
+        // Trailing zeros are within the scale
+        engine
+            .process_order_with(order(OrderSide::Sell, dec!(1), dec!(100.000), Uuid::new_v4()), Unfilled::Rest)
+            .await
+            .unwrap();
+        engine
+            .process_order_with(order(OrderSide::Sell, dec!(2), dec!(101), Uuid::new_v4()), Unfilled::Rest)
+            .await
+            .unwrap();
+        assert_eq!(engine.get_best_ask("GSEC10Y"), Some(dec!(100)));
+
+        // A sweep's average price is carried in full for the caller to round
+        let mut legs = [order(OrderSide::Buy, dec!(3), dec!(101), Uuid::new_v4())];
+        let mut vwap = None;
+        let trades = engine
+            .execute_legs(&mut legs, |vwaps| {
+                vwap = Some(vwaps[0]);
+                true
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(vwap, Some(dec!(100.66666666666666666666666667)));
+        assert_eq!(scale.round_price(vwap.unwrap()), dec!(100.67));
+        assert_eq!(trades.iter().map(|trade| trade.quantity).sum::<Decimal>(), dec!(3));
+    }
+
+    #[tokio::test]
+    async fn test_orders_beyond_credit_lines_are_skipped_and_keep_their_place() {
+        let (engine, credit_lines) = matching_engine(Config::default());
+        let (buyer, unknown, known) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        credit_lines
+            .set_line(buyer, CreditLineRequest { counterparty_account_id: known, limit: dec!(150000) })
+            .unwrap();
+
+        let skipped = order(OrderSide::Sell, dec!(100000), dec!(99.00), unknown);
+        let first = order(OrderSide::Sell, dec!(100000), dec!(99.50), known);
+        let second = order(OrderSide::Sell, dec!(100000), dec!(99.50), known);
+        for resting in [&skipped, &first, &second] {
+            engine.process_order_with(resting.clone(), Unfilled::Rest).await.unwrap();
+        }
+
+        // The line takes the first order at 99.50 but not a second on top
+        let buy = order(OrderSide::Buy, dec!(300000), dec!(100.00), buyer);
+        let trades = engine.process_order_with(buy, Unfilled::Cancel).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].maker_order_id, first.id);
+        assert_eq!(engine.resting_order(skipped.id).unwrap().remaining_quantity, dec!(100000));
+        assert_eq!(engine.resting_order(second.id).unwrap().remaining_quantity, dec!(100000));
+        assert_eq!(engine.get_best_ask("GSEC10Y"), Some(dec!(99.00)));
+
+        // With the trades cleared, no bilateral line applies
+        let (cleared, credit_lines) = matching_engine(Config {
+            ccp_enabled: true,
+            ..Config::default()
+        });
+        credit_lines
+            .set_line(buyer, CreditLineRequest { counterparty_account_id: known, limit: dec!(0) })
+            .unwrap();
+        cleared.process_order_with(skipped.clone(), Unfilled::Rest).await.unwrap();
+        let buy = order(OrderSide::Buy, dec!(100000), dec!(99.00), buyer);
+        assert_eq!(cleared.process_order_with(buy, Unfilled::Cancel).await.unwrap().len(), 1);
     }
 }
@@ -0,0 +1,248 @@
+//! Fair value for bonds without a usable market price: a benchmark yield
+//! at the bond's tenor plus a credit spread, taken from comparable bonds
+//! that do have prices or, failing those, from the rating and sector grid.
+
+use crate::{
+    engine::{accrued, analytics, instruments::InstrumentStatus, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::RwLock;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Comparables further apart in tenor than this are not used.
+const MAX_COMPARABLE_TENOR_GAP_YEARS: Decimal = Decimal::from_parts(3, 0, 0, false, 0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub tenor_years: Decimal,
+    /// Percent.
+    pub yield_pct: Decimal,
+}
+
+/// Credit spreads over the benchmark curve, in basis points. A bond's
+/// grid spread is its rating's plus its sector's.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpreadGrid {
+    #[serde(default)]
+    pub ratings: HashMap<String, Decimal>,
+    #[serde(default)]
+    pub sectors: HashMap<BondType, Decimal>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatrixMethod {
+    Comparables,
+    SpreadGrid,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceConfidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatrixPrice {
+    pub symbol: String,
+    /// Clean.
+    pub price: Decimal,
+    pub yield_pct: Decimal,
+    pub benchmark_yield_pct: Decimal,
+    pub spread_bps: Decimal,
+    pub method: MatrixMethod,
+    pub comparables: Vec<String>,
+    pub confidence: PriceConfidence,
+    pub as_of: DateTime<Utc>,
+}
+
+/// The benchmark curve and spread grid. Without an explicit curve, one is
+/// built from priced government securities.
+#[derive(Default)]
+pub struct PricingMatrix {
+    curve: RwLock<Vec<CurvePoint>>,
+    spreads: RwLock<SpreadGrid>,
+}
+
+impl PricingMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_curve(&self, mut points: Vec<CurvePoint>) {
+        points.sort_by_key(|point| point.tenor_years);
+        *self.curve.write() = points;
+    }
+
+    pub fn curve(&self) -> Vec<CurvePoint> {
+        self.curve.read().clone()
+    }
+
+    pub fn set_spreads(&self, spreads: SpreadGrid) {
+        *self.spreads.write() = spreads;
+    }
+
+    pub fn spreads(&self) -> SpreadGrid {
+        self.spreads.read().clone()
+    }
+
+    fn grid_spread(&self, bond: &Bond) -> Decimal {
+        let spreads = self.spreads.read();
+        let rating = bond
+            .rating
+            .as_ref()
+            .and_then(|rating| spreads.ratings.get(rating))
+            .copied()
+            .unwrap_or_default();
+        rating + spreads.sectors.get(&bond.bond_type).copied().unwrap_or_default()
+    }
+}
+
+/// Linear between points, flat beyond the ends. `points` are sorted by
+/// tenor.
+fn interpolate(points: &[CurvePoint], tenor: Decimal) -> Option<Decimal> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if tenor <= first.tenor_years {
+        return Some(first.yield_pct);
+    }
+    if tenor >= last.tenor_years {
+        return Some(last.yield_pct);
+    }
+    let upper = points.iter().position(|point| point.tenor_years >= tenor)?;
+    let (a, b) = (&points[upper - 1], &points[upper]);
+    let weight = (tenor - a.tenor_years) / (b.tenor_years - a.tenor_years);
+    Some(a.yield_pct + (b.yield_pct - a.yield_pct) * weight)
+}
+
+fn tenor_years(bond: &Bond, settlement: NaiveDate) -> Decimal {
+    Decimal::from((bond.maturity_date.date_naive() - settlement).num_days()) / Decimal::from(365)
+}
+
+impl TradingEngine {
+    pub fn pricing_matrix(&self) -> &PricingMatrix {
+        &self.pricing_matrix
+    }
+
+    pub(crate) fn settlement_today(&self) -> NaiveDate {
+        let now = self.time_provider.now();
+        self.time_provider.settlement_date(now.date_naive(), self.config.settlement_cycle_days)
+    }
+
+    /// The explicit curve, or one through the yields of government
+    /// securities with a reference price.
+    pub fn benchmark_curve(&self) -> Vec<CurvePoint> {
+        let explicit = self.pricing_matrix.curve();
+        if !explicit.is_empty() {
+            return explicit;
+        }
+        let settlement = self.settlement_today();
+        let mut points: Vec<CurvePoint> = self
+            .instruments
+            .list()
+            .into_iter()
+            .filter(|instrument| {
+                instrument.bond.bond_type == BondType::GovernmentSecurity
+                    && instrument.status != InstrumentStatus::Delisted
+            })
+            .filter_map(|instrument| {
+                let price = self.reference_price(&instrument.bond.symbol).ok()?.price;
+                Some(CurvePoint {
+                    tenor_years: tenor_years(&instrument.bond, settlement),
+                    yield_pct: analytics::yield_to_maturity(&instrument.bond, price, settlement, &self.fixings)?,
+                })
+            })
+            .collect();
+        points.sort_by_key(|point| point.tenor_years);
+        points
+    }
+
+    /// The benchmark curve's yield at the bond's tenor.
+    pub(crate) fn curve_yield(&self, bond: &Bond, settlement: NaiveDate) -> Option<Decimal> {
+        interpolate(&self.benchmark_curve(), tenor_years(bond, settlement))
+    }
+
+    /// Clean price of the bond at a yield in percent.
+    pub(crate) fn clean_price_at_yield(&self, bond: &Bond, yield_pct: Decimal, settlement: NaiveDate) -> Option<Decimal> {
+        (yield_pct / Decimal::ONE_HUNDRED)
+            .to_f64()
+            .and_then(|rate| analytics::dirty_price_at_yield(bond, rate, settlement, &self.fixings))
+            .and_then(Decimal::from_f64_retain)
+            .map(|dirty| (dirty - accrued::accrued_interest(bond, settlement, &self.fixings)).round_dp(6))
+    }
+
+    /// Estimates a clean price for `symbol` from the benchmark curve and
+    /// a credit spread. Comparables are listed bonds of the same rating
+    /// and sector with a reference price, weighted towards closer tenors.
+    pub fn matrix_price(&self, symbol: &str) -> crate::types::Result<MatrixPrice> {
+        let bond = self
+            .instruments
+            .get(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(symbol.to_string()))?;
+        let settlement = self.settlement_today();
+        let curve = self.benchmark_curve();
+        let tenor = tenor_years(&bond, settlement);
+        let benchmark = interpolate(&curve, tenor)
+            .ok_or_else(|| TradingError::InvalidRequest("No benchmark curve to price from".to_string()))?;
+
+        let mut comparables = Vec::new();
+        let (mut weighted_spread, mut total_weight) = (Decimal::ZERO, Decimal::ZERO);
+        for instrument in self.instruments.list() {
+            let other = &instrument.bond;
+            if other.symbol == symbol
+                || other.bond_type != bond.bond_type
+                || other.rating != bond.rating
+                || instrument.status == InstrumentStatus::Delisted
+            {
+                continue;
+            }
+            let other_tenor = tenor_years(other, settlement);
+            let gap = (other_tenor - tenor).abs();
+            if gap > MAX_COMPARABLE_TENOR_GAP_YEARS {
+                continue;
+            }
+            let Ok(reference) = self.reference_price(&other.symbol) else {
+                continue;
+            };
+            let (Some(ytm), Some(other_benchmark)) = (
+                analytics::yield_to_maturity(other, reference.price, settlement, &self.fixings),
+                interpolate(&curve, other_tenor),
+            ) else {
+                continue;
+            };
+            let weight = Decimal::ONE / (Decimal::ONE + gap);
+            weighted_spread += (ytm - other_benchmark) * Decimal::ONE_HUNDRED * weight;
+            total_weight += weight;
+            comparables.push(other.symbol.clone());
+        }
+
+        let (method, spread_bps, confidence) = if comparables.is_empty() {
+            (MatrixMethod::SpreadGrid, self.pricing_matrix.grid_spread(&bond), PriceConfidence::Low)
+        } else {
+            let confidence = if comparables.len() >= 2 { PriceConfidence::High } else { PriceConfidence::Medium };
+            (MatrixMethod::Comparables, (weighted_spread / total_weight).round_dp(2), confidence)
+        };
+
+        let yield_pct = benchmark + spread_bps / Decimal::ONE_HUNDRED;
+        let price = self
+            .clean_price_at_yield(&bond, yield_pct, settlement)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Cannot price {} at {}%", symbol, yield_pct)))?;
+
+        Ok(MatrixPrice {
+            symbol: symbol.to_string(),
+            price,
+            yield_pct: yield_pct.round_dp(6),
+            benchmark_yield_pct: benchmark.round_dp(6),
+            spread_bps,
+            method,
+            comparables,
+            confidence,
+            as_of: self.time_provider.now(),
+        })
+    }
+}
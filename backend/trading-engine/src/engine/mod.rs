@@ -1,137 +1,703 @@
 use crate::{
     config::Config,
+    feeds::FeedMonitor,
+    persistence::{self, StateStore},
     types::*,
-    utils::{metrics::Metrics, time::TimeProvider},
+    utils::{
+        clock_sync::{ClockAlert, ClockTolerance},
+        logging,
+        metrics::Metrics,
+        time::TimeProvider,
+    },
 };
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use parking_lot::RwLock;
 use rust_decimal::Decimal;
-use std::{collections::VecDeque, sync::Arc};
-use tokio::sync::{broadcast, mpsc};
-use tracing::{error, info, warn};
+use serde::Serialize;
+use std::{ops::Deref, sync::Arc};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+pub mod account_groups;
+pub mod accrued;
+pub mod account_stats;
+pub mod analytics;
+pub mod allocation;
+pub mod approvals;
+pub mod auction;
+pub mod baskets;
+pub mod benchmark_orders;
+pub mod book_limits;
+pub mod book_snapshot;
+pub mod calendar;
+pub mod cash;
+pub mod clearing;
+pub mod clock;
+pub mod compression;
+pub mod credit_lines;
+pub mod conditional;
+pub mod confirmations;
+pub mod corporate_actions;
+pub mod d2c;
+pub mod eod_reconciliation;
+pub mod event_journal;
+pub mod executions;
+pub mod expiry;
+pub mod feature_flags;
+pub mod fees;
+pub mod fixed_point;
+pub mod fixings;
+pub mod inflation;
+pub mod implied;
+pub mod instruments;
+pub mod internalization;
+pub mod lending;
+pub mod lifecycle;
+pub mod liquidation;
+pub mod market_collar;
+pub mod market_makers;
+pub mod market_summary;
+pub mod matrix_pricing;
 pub mod matching;
+pub mod money_market;
+pub mod notifications;
+pub mod oms_sync;
+pub mod order_import;
+pub mod order_preview;
+pub mod permissions;
+pub mod overview;
 pub mod order_book;
+pub mod pnl;
+pub mod pnl_timeseries;
+pub mod pre_open;
+pub mod price_improvement;
+pub mod price_time_book;
+pub mod position_close;
 pub mod position_manager;
+pub mod reference_import;
+pub mod reference_mm;
+pub mod reference_price;
+pub mod reconciliation;
+pub mod retention;
+pub mod settlement_export;
+pub mod settlement_fails;
 pub mod risk_manager;
+pub mod sharding;
+pub mod signing;
+pub mod speed_bump;
+pub mod spreads;
+pub mod strategies;
+pub mod symbol_stats;
+pub mod synthetic_indices;
+pub mod tenor_limits;
+pub mod trade_aggregates;
+pub mod trade_store;
+pub mod validation;
+pub mod valuation;
+pub mod warmup;
+pub mod webhooks;
+pub mod what_if;
 
-use matching::MatchingEngine;
+use account_stats::{AccountStatsTracker, AccountTradingStats};
+use approvals::{ApprovalQueue, StagedOrder};
+use auction::{AuctionBook, IndicativePrice};
+use baskets::BasketBook;
+use benchmark_orders::{BenchmarkBook, BenchmarkOrder};
+use reference_mm::ReferenceMarketMaker;
+use cash::CashLedger;
+use credit_lines::CreditLineBook;
+use clearing::ClearingHouse;
+use confirmations::ConfirmationBook;
+use event_journal::{Delivery, EventJournal, EventReceiver, EventReplay, SequencedEvent};
+use expiry::ExpiryWheel;
+use feature_flags::{FeatureFlag, FeatureFlags};
+use fees::FeeEngine;
+use account_groups::AccountGroupRegistry;
+use corporate_actions::{CorporateAction, CorporateActionLog};
+use conditional::{ConditionalBook, ConditionalOrder};
+use d2c::{DealerDesk, DealerHit};
+use eod_reconciliation::EodReconciliationLog;
+use executions::ExecutionTracker;
+use fixings::FixingStore;
+use inflation::InflationIndexStore;
+use instruments::{InstrumentRegistry, InstrumentStatus};
+use internalization::FirmRegistry;
+use lending::LendingDesk;
+use lifecycle::InstrumentArchive;
+use liquidation::{LiquidationDesk, LiquidationEvent};
+use book_limits::BookLimitRegistry;
+use market_makers::MarketMakerMonitor;
+use market_summary::MarketSummaryTracker;
+use symbol_stats::SymbolStatsTracker;
+use settlement_export::SettlementExporter;
+use settlement_fails::SettlementFailBook;
+use trade_store::TradeStore;
+use validation::{OrderValidator, ValidationPipeline};
+use sharding::MatchingShards;
+use signing::{OrderSignature, SigningKeyRegistry};
+use speed_bump::SpeedBumpRegistry;
+use pre_open::PreOpenQueue;
+use warmup::Warmup;
+use synthetic_indices::{IndexTicker, SyntheticIndexRegistry};
+use price_improvement::{ImprovementAuction, PriceImprovementDesk};
+use reference_import::InstrumentImportLog;
+use matrix_pricing::PricingMatrix;
+use matching::{MatchingEngine, Unfilled};
+use notifications::NotificationCenter;
 use order_book::OrderBookManager;
+use permissions::PermissionsMatrix;
+use pnl::PnlAttribution;
+use pnl_timeseries::PnlTimeseries;
 use position_manager::PositionManager;
-use risk_manager::RiskManager;
+use reference_price::ReferencePriceService;
+use retention::Archiver;
+use risk_manager::{AccountActivity, OrderActivity, OverrideRequest, RiskCheck, RiskManager, RiskOverride};
+use spreads::{SpreadBook, SpreadOrder};
+use strategies::StrategyRegistry;
+use webhooks::WebhookRegistry;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum EngineEvent {
     OrderSubmitted(Order),
-    OrderCancelled(Uuid),
+    OrderCancelled { order_id: Uuid, cancel_reason: CancelReason },
+    OrderExpired { order_id: Uuid, expiry: DateTime<Utc> },
+    /// A resting order's size was cut in place, keeping its priority.
+    OrderReduced {
+        order_id: Uuid,
+        previous_quantity: Decimal,
+        quantity: Decimal,
+        remaining_quantity: Decimal,
+    },
     OrderFilled { order_id: Uuid, trade: Trade },
     TradeExecuted(Trade),
     PositionUpdated(Position),
     RiskViolation { account_id: Uuid, violation: String },
+    /// An order passed its soft limits on an override.
+    RiskWarning {
+        order_id: Uuid,
+        account_id: Uuid,
+        warnings: Vec<String>,
+        override_token: Uuid,
+    },
+    RiskOverrideGranted(RiskOverride),
+    RiskOverrideRevoked(RiskOverride),
+    OrderRejected {
+        order_id: Uuid,
+        client_order_id: String,
+        account_id: Uuid,
+        symbol: String,
+        reason: String,
+        reject_reason: RejectReason,
+    },
+    InstrumentStatusChanged { symbol: String, status: InstrumentStatus, reason: Option<String> },
+    AuctionIndicative(IndicativePrice),
+    SpreadOrderUpdated(SpreadOrder),
+    /// A benchmark order was entered, filled, re-marked or cancelled.
+    BenchmarkOrderUpdated(BenchmarkOrder),
+    CorporateActionApplied(CorporateAction),
+    /// The engine clock's grade against UTC changed.
+    ClockQualityChanged(ClockAlert),
+    /// A client signature checked out; journaled ahead of the order it
+    /// covers.
+    OrderSignatureVerified {
+        order_id: Uuid,
+        account_id: Uuid,
+        signature: OrderSignature,
+    },
+    /// A dealer-to-client hit was received or decided.
+    DealerHitUpdated(DealerHit),
+    /// A price-improvement auction opened or closed; sent to the market
+    /// makers it was shown to.
+    ImprovementAuctionUpdated(ImprovementAuction),
+    /// A synthetic index was defined or its value moved.
+    IndexValueUpdated(IndexTicker),
+    /// An order was staged for approval, or its approval was decided.
+    OrderApprovalUpdated(StagedOrder),
+    /// A conditional order was entered, fired or cancelled.
+    ConditionalOrderUpdated(ConditionalOrder),
+    /// A step of an account's stop-out.
+    Liquidation(LiquidationEvent),
 }
 
 pub struct TradingEngine {
     config: Arc<Config>,
     matching_engine: Arc<MatchingEngine>,
+    matching_shards: Arc<MatchingShards>,
     order_book_manager: Arc<OrderBookManager>,
     position_manager: Arc<PositionManager>,
     risk_manager: Arc<RiskManager>,
+    instruments: Arc<InstrumentRegistry>,
+    fee_engine: Arc<FeeEngine>,
+    firms: Arc<FirmRegistry>,
+    credit_lines: Arc<CreditLineBook>,
+    activity: Arc<OrderActivity>,
+    account_stats: Arc<AccountStatsTracker>,
+    instrument_archives: Arc<DashMap<String, InstrumentArchive>>,
+    auction_book: Arc<AuctionBook>,
+    spread_book: Arc<SpreadBook>,
+    benchmark_book: BenchmarkBook,
+    reference_mm: ReferenceMarketMaker,
+    lending: Arc<LendingDesk>,
+    cash: Arc<CashLedger>,
+    clearing: Arc<ClearingHouse>,
+    strategies: Arc<StrategyRegistry>,
+    pnl: Arc<PnlAttribution>,
+    pnl_timeseries: Arc<PnlTimeseries>,
+    reference_prices: Arc<ReferencePriceService>,
+    expiry_wheel: Arc<ExpiryWheel>,
+    notifications: Arc<NotificationCenter>,
+    webhooks: Arc<WebhookRegistry>,
+    signing_keys: Arc<SigningKeyRegistry>,
+    dealer_desk: Arc<DealerDesk>,
+    approvals: Arc<ApprovalQueue>,
+    conditional_orders: Arc<ConditionalBook>,
+    liquidations: Arc<LiquidationDesk>,
+    execution_tracker: Arc<ExecutionTracker>,
+    baskets: Arc<BasketBook>,
+    fixings: Arc<FixingStore>,
+    inflation_indices: Arc<InflationIndexStore>,
+    corporate_actions: Arc<CorporateActionLog>,
+    permissions: Arc<PermissionsMatrix>,
+    account_groups: Arc<AccountGroupRegistry>,
+    market_makers: Arc<MarketMakerMonitor>,
+    pricing_matrix: Arc<PricingMatrix>,
+    book_limits: Arc<BookLimitRegistry>,
+    speed_bumps: Arc<SpeedBumpRegistry>,
+    price_improvement: Arc<PriceImprovementDesk>,
+    synthetic_indices: Arc<SyntheticIndexRegistry>,
+    pre_open: Arc<PreOpenQueue>,
+    warmup: Arc<Warmup>,
+    eod_reconciliations: Arc<EodReconciliationLog>,
+    market_summary: Arc<MarketSummaryTracker>,
+    symbol_stats: Arc<SymbolStatsTracker>,
+    instrument_imports: Arc<InstrumentImportLog>,
+    feature_flags: Arc<FeatureFlags>,
+    feed_monitor: Arc<FeedMonitor>,
+    validation: Arc<ValidationPipeline>,
     orders: Arc<DashMap<Uuid, Order>>,
-    trades: Arc<RwLock<VecDeque<Trade>>>,
-    event_sender: broadcast::Sender<EngineEvent>,
+    trades: Arc<TradeStore>,
+    event_journal: Arc<EventJournal>,
     metrics: Arc<Metrics>,
     time_provider: Arc<TimeProvider>,
+    state_store: Arc<dyn StateStore>,
+    archiver: Arc<Archiver>,
+    settlement_exporter: Arc<SettlementExporter>,
+    settlement_fails: Arc<SettlementFailBook>,
+    confirmations: Arc<ConfirmationBook>,
 }
 
 impl TradingEngine {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
+        Self::with_time_provider(config, Arc::new(TimeProvider::new())).await
+    }
+
+    pub async fn with_time_provider(config: Arc<Config>, time_provider: Arc<TimeProvider>) -> Result<Self> {
+        Self::with_validators(config, time_provider, Vec::new()).await
+    }
+
+    /// An engine that also checks every order against `validators`, after
+    /// the built-in ones of the same phase.
+    pub async fn with_validators(
+        config: Arc<Config>,
+        time_provider: Arc<TimeProvider>,
+        validators: Vec<Box<dyn OrderValidator>>,
+    ) -> Result<Self> {
         let metrics = Arc::new(Metrics::new());
-        let time_provider = Arc::new(TimeProvider::new());
-        let (event_sender, _) = broadcast::channel(10000);
+        time_provider.clock_quality().set_tolerance(ClockTolerance {
+            max_error_us: config.clock_max_error_us,
+            max_drift_ppm: config.clock_max_drift_ppm,
+        });
+        time_provider.calendar().set_settlement_market(&config.settlement_calendar);
+        let event_journal = Arc::new(EventJournal::new(
+            config.event_journal_capacity,
+            config.event_channel_size,
+            time_provider.clone(),
+        ));
+
+        let instruments = Arc::new(InstrumentRegistry::new(time_provider.clone()));
+        let fee_engine = Arc::new(FeeEngine::new(instruments.clone()));
+        let firms = Arc::new(FirmRegistry::new());
+        let credit_lines = Arc::new(CreditLineBook::new(time_provider.clone()));
+        let activity = Arc::new(OrderActivity::new());
+        let fixings = Arc::new(FixingStore::new());
+        let inflation_indices = Arc::new(InflationIndexStore::new());
+        let feature_flags = Arc::new(FeatureFlags::new(config.feature_flags.clone()));
 
         let matching_engine = Arc::new(MatchingEngine::new(
             config.clone(),
-            event_sender.clone(),
+            event_journal.clone(),
             metrics.clone(),
+            time_provider.clone(),
+            fee_engine.clone(),
+            instruments.clone(),
+            firms.clone(),
+            credit_lines.clone(),
+            activity.clone(),
+            fixings.clone(),
+            inflation_indices.clone(),
+            feature_flags.clone(),
         ));
 
-        let order_book_manager = Arc::new(OrderBookManager::new(config.clone()));
-        let position_manager = Arc::new(PositionManager::new(config.clone()).await?);
-        let risk_manager = Arc::new(RiskManager::new(config.clone()).await?);
+        let matching_shards = Arc::new(MatchingShards::start(
+            config.matching_shards,
+            &config.matching_shard_cores,
+        )?);
+        let order_book_manager = Arc::new(OrderBookManager::new(config.clone(), time_provider.clone()));
+        let position_manager = Arc::new(PositionManager::new(time_provider.clone()).await?);
+        let risk_manager = Arc::new(RiskManager::new(config.clone(), activity.clone(), time_provider.clone()).await?);
+        let state_store = persistence::connect(&config)?;
+        let archiver = Arc::new(Archiver::new(persistence::archive::connect(&config)));
+        let settlement_exporter = Arc::new(SettlementExporter::new(&config));
+        let clearing = Arc::new(ClearingHouse::new(config.clone(), time_provider.clone()));
+        let pnl_timeseries = Arc::new(PnlTimeseries::new(config.pnl_snapshot_capacity));
+        let reference_prices = Arc::new(ReferencePriceService::new(config.clone()));
+        let expiry_wheel = Arc::new(ExpiryWheel::new(config.expiry_tolerance_ms / 2));
+        let notifications = Arc::new(NotificationCenter::new(config.notification_delivery_history));
+        let feed_monitor = Arc::new(FeedMonitor::new(config.feed_stale_after_secs));
 
         let orders = Arc::new(DashMap::new());
-        let trades = Arc::new(RwLock::new(VecDeque::with_capacity(100000)));
+        let trades = Arc::new(TradeStore::new(config.trade_memory_capacity));
 
         Ok(Self {
             config,
             matching_engine,
+            matching_shards,
             order_book_manager,
             position_manager,
             risk_manager,
+            instruments,
+            fee_engine,
+            firms,
+            credit_lines,
+            activity,
+            account_stats: Arc::new(AccountStatsTracker::new()),
+            instrument_archives: Arc::new(DashMap::new()),
+            auction_book: Arc::new(AuctionBook::new()),
+            spread_book: Arc::new(SpreadBook::new()),
+            benchmark_book: BenchmarkBook::new(),
+            reference_mm: ReferenceMarketMaker::new(),
+            lending: Arc::new(LendingDesk::new(time_provider.clone())),
+            cash: Arc::new(CashLedger::new(time_provider.clone())),
+            clearing,
+            strategies: Arc::new(StrategyRegistry::new(time_provider.clone())),
+            pnl: Arc::new(PnlAttribution::new()),
+            pnl_timeseries,
+            reference_prices,
+            expiry_wheel,
+            notifications,
+            webhooks: Arc::new(WebhookRegistry::new()),
+            signing_keys: Arc::new(SigningKeyRegistry::new()),
+            dealer_desk: Arc::new(DealerDesk::new()),
+            approvals: Arc::new(ApprovalQueue::new()),
+            conditional_orders: Arc::new(ConditionalBook::new()),
+            liquidations: Arc::new(LiquidationDesk::new()),
+            execution_tracker: Arc::new(ExecutionTracker::new()),
+            baskets: Arc::new(BasketBook::new()),
+            fixings,
+            inflation_indices,
+            corporate_actions: Arc::new(CorporateActionLog::new()),
+            permissions: Arc::new(PermissionsMatrix::new()),
+            account_groups: Arc::new(AccountGroupRegistry::new()),
+            market_makers: Arc::new(MarketMakerMonitor::new()),
+            pricing_matrix: Arc::new(PricingMatrix::new()),
+            book_limits: Arc::new(BookLimitRegistry::new()),
+            speed_bumps: Arc::new(SpeedBumpRegistry::new()),
+            price_improvement: Arc::new(PriceImprovementDesk::new()),
+            synthetic_indices: Arc::new(SyntheticIndexRegistry::new()),
+            pre_open: Arc::new(PreOpenQueue::new()),
+            warmup: Arc::new(Warmup::new()),
+            eod_reconciliations: Arc::new(EodReconciliationLog::new()),
+            market_summary: Arc::new(MarketSummaryTracker::new()),
+            symbol_stats: Arc::new(SymbolStatsTracker::new()),
+            instrument_imports: Arc::new(InstrumentImportLog::new()),
+            feature_flags,
+            feed_monitor,
+            validation: Arc::new(ValidationPipeline::new(validators)),
             orders,
             trades,
-            event_sender,
+            event_journal,
             metrics,
             time_provider,
+            state_store,
+            archiver,
+            settlement_exporter,
+            settlement_fails: Arc::new(SettlementFailBook::new()),
+            confirmations: Arc::new(ConfirmationBook::new()),
         })
     }
 
-    pub async fn submit_order(&self, mut order: Order) -> crate::types::Result<Uuid> {
-        info!("Submitting order: {}", order.id);
-        
-        // Validate order
-        self.validate_order(&order).await?;
-        
-        // Risk checks
-        self.risk_manager.check_order(&order).await?;
-        
-        // Set timestamp
-        order.timestamp = self.time_provider.now();
+    pub async fn submit_order(&self, order: Order) -> crate::types::Result<Uuid> {
+        let timestamp = self.time_provider.now();
+        self.submit_order_at(order, timestamp).await
+    }
+
+    /// Accepts an order stamped with `timestamp` rather than the current
+    /// time, as when backloading orders that were entered elsewhere.
+    /// Orders of accounts under four-eyes approval are staged instead.
+    pub(crate) async fn submit_order_at(
+        &self,
+        mut order: Order,
+        timestamp: DateTime<Utc>,
+    ) -> crate::types::Result<Uuid> {
+        self.await_event_consumers().await?;
+        // Clients may name the instrument by ISIN or CUSIP
+        order.symbol = self.instruments.canonical(&order.symbol);
+        if self.approvals.requires_approval(order.account_id) {
+            return self.stage_order(order, timestamp).await;
+        }
+        self.enter_order_at(order, timestamp).await
+    }
+
+    /// Holds order entry while a lossless event consumer is a full queue
+    /// behind, and turns the order away if it has not caught up within
+    /// `event_backpressure_timeout_ms`.
+    pub(crate) async fn await_event_consumers(&self) -> crate::types::Result<()> {
+        let waited_ms = self.config.event_backpressure_timeout_ms;
+        let limit = std::time::Duration::from_millis(waited_ms);
+        if tokio::time::timeout(limit, self.event_journal.backpressure()).await.is_ok() {
+            return Ok(());
+        }
+        self.metrics.increment_consumer_stalls();
+        warn!(
+            stalled = self.event_journal.stalled_consumers(),
+            "Order entry held back {}ms by a stalled event consumer, rejecting", waited_ms
+        );
+        Err(TradingError::ConsumerStalled { waited_ms })
+    }
+
+    async fn enter_order_at(&self, order: Order, timestamp: DateTime<Utc>) -> crate::types::Result<Uuid> {
+        info!(order_id = %order.id, account_id = %order.account_id, symbol = %order.symbol, "Submitting order");
+
+        if let Err(e) = self.check_new_order(&order).await {
+            self.reject_new_order(order, &e, timestamp);
+            return Err(e);
+        }
+        self.accept_order_at(order, timestamp).await
+    }
+
+    /// Takes in an order that has passed its checks: queues it for the
+    /// open, or sends it to the book.
+    async fn accept_order_at(&self, mut order: Order, timestamp: DateTime<Utc>) -> crate::types::Result<Uuid> {
+        self.normalize_order_price(&mut order);
+        order.timestamp = timestamp;
         order.remaining_quantity = order.quantity;
+
+        if self.queue_for_open(&order) {
+            // Waits for the open; released in arrival order
+            self.orders.insert(order.id, order.clone());
+            if let Some(expiry) = order.expires_at() {
+                self.expiry_wheel.schedule(order.id, expiry);
+            }
+            let sequence =
+                self.event_journal.publish(EngineEvent::OrderSubmitted(order.clone()), vec![order.account_id]);
+            logging::audit_order(&self.config.engine_instance_id, "order_accepted", &order, sequence, self.time_provider.now());
+            self.metrics.increment_orders_submitted();
+            return Ok(order.id);
+        }
+
+        // Held after the checks so a rejected order is turned away at once
+        self.apply_speed_bump(&order).await;
         
         // Store order
         self.orders.insert(order.id, order.clone());
+        if let Some(expiry) = order.expires_at() {
+            self.expiry_wheel.schedule(order.id, expiry);
+        }
+        let order_id = order.id;
+        self.route_order(order, true).await?;
         
-        // Send to matching engine
-        let trades = self.matching_engine.process_order(order.clone()).await?;
+        self.metrics.increment_orders_submitted();
         
-        // Update positions
-        for trade in &trades {
-            self.position_manager.update_position(trade).await?;
+        Ok(order_id)
+    }
+
+    /// Sends a stored order to its symbol's auction or to the book.
+    /// `announce` publishes the order's acceptance, which orders released
+    /// from the pre-open queue have already had.
+    pub(crate) async fn route_order(&self, mut order: Order, announce: bool) -> crate::types::Result<()> {
+        if self.in_auction(&order.symbol) {
+            // Auction orders wait for the uncross instead of matching now
+            if announce {
+                let sequence =
+                    self.event_journal.publish(EngineEvent::OrderSubmitted(order.clone()), vec![order.account_id]);
+                logging::audit_order(&self.config.engine_instance_id, "order_accepted", &order, sequence, self.time_provider.now());
+            }
+            self.enter_auction(&order);
+        } else {
+            // Providers' improvements, then implied liquidity that beats
+            // the book, trade first, none of it beyond a market order's
+            // collar
+            let unfilled = self.apply_market_collar(&mut order);
+            let mut trades = self.run_price_improvement(&mut order).await;
+            if order.remaining_quantity > Decimal::ZERO && self.feature_enabled(FeatureFlag::ImpliedMatching, &order) {
+                trades.extend(self.match_implied(&mut order));
+            }
+            if order.remaining_quantity > Decimal::ZERO {
+                trades.extend(self.process_order(order.clone(), unfilled).await?);
+            }
+            self.record_trades(&trades).await?;
+
+            // Send event
+            if announce {
+                let sequence =
+                    self.event_journal.publish(EngineEvent::OrderSubmitted(order.clone()), vec![order.account_id]);
+                logging::audit_order(&self.config.engine_instance_id, "order_accepted", &order, sequence, self.time_provider.now());
+            }
+            if unfilled != Unfilled::Rest && self.get_order(&order.id).is_some_and(|order| order.is_open()) {
+                self.cancel_order_unthrottled(order.id, CancelReason::MarketCollar).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a new order as rejected for `error`. The order is kept so
+    /// it can be looked up with why it was turned away.
+    fn reject_new_order(&self, mut order: Order, error: &TradingError, timestamp: DateTime<Utc>) {
+        let reject_reason = error.reject_reason();
+        self.metrics.increment_order_rejects(&reject_reason);
+        order.timestamp = timestamp;
+        order.status = OrderStatus::Rejected(reject_reason);
+        order.remaining_quantity = Decimal::ZERO;
+        self.orders.entry(order.id).or_insert_with(|| order.clone());
+        let sequence = self.event_journal.publish(
+            EngineEvent::OrderRejected {
+                order_id: order.id,
+                client_order_id: order.client_order_id.clone(),
+                account_id: order.account_id,
+                symbol: order.symbol.clone(),
+                reason: error.to_string(),
+                reject_reason,
+            },
+            vec![order.account_id],
+        );
+        logging::audit_order(&self.config.engine_instance_id, "order_rejected", &order, sequence, self.time_provider.now());
+    }
+
+    /// Validation, risk and clearing checks an order must pass to be
+    /// accepted.
+    async fn check_new_order(&self, order: &Order) -> crate::types::Result<()> {
+        self.check_new_orders(std::slice::from_ref(order)).await
+    }
+
+    /// The same checks for an order that trades as several outright
+    /// stand-ins, such as the legs of a spread. It counts as one message.
+    pub(crate) async fn check_new_orders(&self, orders: &[Order]) -> crate::types::Result<()> {
+        let Some(first) = orders.first() else {
+            return Ok(());
+        };
+        // Validate order
+        for order in orders {
+            self.validate_order(order).await?;
         }
         
-        // Store trades
-        {
-            let mut trades_lock = self.trades.write();
-            for trade in trades {
-                trades_lock.push_back(trade.clone());
-                if trades_lock.len() > 100000 {
-                    trades_lock.pop_front();
+        // Risk checks
+        self.activity.record_message(first.account_id);
+        for order in orders {
+            self.check_new_order_risk(order).await?;
+        }
+        Ok(())
+    }
+
+    /// Risk and clearing checks for one order, publishing any warning or
+    /// violation.
+    async fn check_new_order_risk(&self, order: &Order) -> crate::types::Result<()> {
+        match self.check_risk(order).await {
+            Ok(check) => {
+                if let Some(override_token) = check.override_token {
+                    warn!(
+                        "Order {} passed soft limits on override {}: {}",
+                        order.id,
+                        override_token,
+                        check.warnings.join("; ")
+                    );
+                    self.event_journal.publish(
+                        EngineEvent::RiskWarning {
+                            order_id: order.id,
+                            account_id: order.account_id,
+                            warnings: check.warnings,
+                            override_token,
+                        },
+                        vec![order.account_id],
+                    );
                 }
             }
+            Err(e) => {
+                if let TradingError::RiskLimitExceeded { message: violation, .. } = &e {
+                    self.metrics
+                        .increment_risk_violations(self.time_provider.now().date_naive());
+                    self.event_journal.publish(
+                        EngineEvent::RiskViolation {
+                            account_id: order.account_id,
+                            violation: violation.clone(),
+                        },
+                        vec![order.account_id],
+                    );
+                }
+                return Err(e);
+            }
         }
-        
-        // Send event
-        let _ = self.event_sender.send(EngineEvent::OrderSubmitted(order.clone()));
-        
-        self.metrics.increment_orders_submitted();
-        
-        Ok(order.id)
+        if self.clearing.enabled() {
+            self.check_clearing_exposure(order)?;
+        }
+        Ok(())
     }
 
+    /// Account, group and tenor limits, without recording anything.
+    async fn check_risk(&self, order: &Order) -> crate::types::Result<RiskCheck> {
+        let check = self.risk_manager.check_order(order).await?;
+        if self.feature_enabled(FeatureFlag::GroupRiskLimits, order) {
+            self.check_group_limits(order).await?;
+        }
+        self.check_tenor_limits(order).await?;
+        Ok(check)
+    }
+
+    /// Cancels at the owner's request, subject to the account's minimum
+    /// quote life and update rate.
     pub async fn cancel_order(&self, order_id: Uuid) -> crate::types::Result<bool> {
-        info!("Cancelling order: {}", order_id);
+        if let Some(order) = self.get_order(&order_id).filter(|order| order.is_open()) {
+            self.risk_manager.admit_quote_update(&order).await?;
+        }
+        self.cancel_order_unthrottled(order_id, CancelReason::ClientRequest).await
+    }
+
+    /// Cancels without the quote throttle, for the venue's own cancels and
+    /// the cancel leg of an amend that already passed it.
+    pub(crate) async fn cancel_order_unthrottled(
+        &self,
+        order_id: Uuid,
+        cancel_reason: CancelReason,
+    ) -> crate::types::Result<bool> {
+        info!(order_id = %order_id, cancel_reason = ?cancel_reason, "Cancelling order");
         
         if let Some((_, mut order)) = self.orders.remove(&order_id) {
+            if order.status == OrderStatus::PendingApproval {
+                self.orders.insert(order_id, order);
+                return Ok(self.withdraw_staged_order(order_id, cancel_reason));
+            }
+            if !order.is_open() {
+                self.orders.insert(order_id, order);
+                return Ok(false);
+            }
             order.status = OrderStatus::Cancelled;
+            order.cancel_reason = Some(cancel_reason);
             self.orders.insert(order_id, order.clone());
+            // The cancel leg of an amend was counted with the amend
+            if cancel_reason != CancelReason::Replaced {
+                self.activity.record_message(order.account_id);
+            }
             
-            self.matching_engine.cancel_order(order_id).await?;
+            if !self.matching_engine.cancel_order(order_id).await? {
+                self.leave_auction(&order.symbol, order_id);
+            }
             
-            let _ = self.event_sender.send(EngineEvent::OrderCancelled(order_id));
+            let sequence = self.event_journal.publish(
+                EngineEvent::OrderCancelled { order_id, cancel_reason },
+                vec![order.account_id],
+            );
+            logging::audit_order(&self.config.engine_instance_id, "order_cancelled", &order, sequence, self.time_provider.now());
             
             self.metrics.increment_orders_cancelled();
             
@@ -141,6 +707,167 @@ impl TradingEngine {
         }
     }
 
+    /// Cuts an open order's total quantity to `quantity` where it rests,
+    /// without losing its place in the queue. Reducing to what has already
+    /// filled cancels the rest.
+    pub async fn reduce_order(&self, order_id: Uuid, quantity: Decimal) -> crate::types::Result<Order> {
+        let order = self
+            .get_order(&order_id)
+            .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+        if !order.is_open() {
+            return Err(TradingError::InvalidOrder("Order is no longer open".to_string()));
+        }
+        if quantity < order.filled_quantity || quantity >= order.quantity {
+            return Err(TradingError::InvalidOrderField {
+                field: OrderField::Quantity,
+                message: format!(
+                    "Quantity must be at least the {} filled and below the order's {}",
+                    order.filled_quantity, order.quantity
+                ),
+            });
+        }
+        self.risk_manager.admit_quote_update(&order).await?;
+        if quantity == order.filled_quantity {
+            self.cancel_order_unthrottled(order_id, CancelReason::ClientRequest).await?;
+            return self
+                .get_order(&order_id)
+                .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()));
+        }
+
+        let reduced_by = self.matching_engine.reduce_order(order_id, quantity)?.ok_or_else(|| {
+            TradingError::InvalidOrder("Only orders resting on the book can be reduced".to_string())
+        })?;
+        let order = {
+            let mut order = self
+                .orders
+                .get_mut(&order_id)
+                .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+            order.quantity -= reduced_by;
+            order.remaining_quantity -= reduced_by;
+            order.clone()
+        };
+        self.activity.record_message(order.account_id);
+        let sequence = self.event_journal.publish(
+            EngineEvent::OrderReduced {
+                order_id,
+                previous_quantity: order.quantity + reduced_by,
+                quantity: order.quantity,
+                remaining_quantity: order.remaining_quantity,
+            },
+            vec![order.account_id],
+        );
+        logging::audit_order(&self.config.engine_instance_id, "order_reduced", &order, sequence, self.time_provider.now());
+        info!(order_id = %order_id, quantity = %order.quantity, reduced_by = %reduced_by, "Order reduced");
+        Ok(order)
+    }
+
+    /// Cancel/replace. The open remainder of the order is cancelled and a
+    /// replacement with the amended quantity and price is submitted at the
+    /// back of the queue. Returns the replacement's id.
+    pub async fn modify_order(
+        &self,
+        order_id: Uuid,
+        quantity: Option<Decimal>,
+        price: Option<Decimal>,
+    ) -> crate::types::Result<Uuid> {
+        let original = self
+            .get_order(&order_id)
+            .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+        if !original.is_open() {
+            return Err(TradingError::InvalidOrder("Order is no longer open".to_string()));
+        }
+
+        let quantity = quantity.unwrap_or(original.remaining_quantity);
+        let mut replacement = Order {
+            id: Uuid::new_v4(),
+            quantity,
+            // Re-entered in the instrument's convention; resting prices are clean
+            price: price.or_else(|| {
+                original
+                    .metadata
+                    .get("quoted_price")
+                    .and_then(|quoted| quoted.parse().ok())
+                    .or(original.price)
+            }),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: quantity,
+            status: OrderStatus::Pending,
+            fills: OrderFills::default(),
+            ..original.clone()
+        };
+        replacement
+            .metadata
+            .insert("replaces".to_string(), order_id.to_string());
+
+        // The amendment is checked once, as one message, before the
+        // original is touched, so a bad request leaves it working. Risk
+        // sees the replacement in the original's place, so the original's
+        // resting exposure is taken out meanwhile.
+        self.await_event_consumers().await?;
+        let resting = self.matching_engine.resting_order(order_id);
+        if let Some(resting) = &resting {
+            self.activity.order_left_book(resting);
+        }
+        let check = self.check_new_order(&replacement).await;
+        if let Some(resting) = &resting {
+            self.activity.order_rested(resting);
+        }
+        check?;
+        self.risk_manager.admit_quote_update(&original).await?;
+        // Filled or cancelled since it was checked: nothing to replace
+        if !self.cancel_order_unthrottled(order_id, CancelReason::Replaced).await? {
+            return Err(TradingError::InvalidOrder("Order is no longer open".to_string()));
+        }
+
+        let timestamp = self.time_provider.now();
+        if self.approvals.requires_approval(replacement.account_id) {
+            return self.stage_order(replacement, timestamp).await;
+        }
+        self.accept_order_at(replacement, timestamp).await
+    }
+
+    /// Removes resting orders whose expiry has passed according to the
+    /// engine clock and returns their ids.
+    pub async fn expire_orders(&self) -> crate::types::Result<Vec<Uuid>> {
+        let now = self.time_provider.now();
+        let due = self.expiry_wheel.advance(now);
+
+        let mut expired = Vec::with_capacity(due.len());
+        for order_id in due {
+            // Filled or cancelled since it was scheduled
+            let (symbol, expiry) = match self.orders.get(&order_id) {
+                Some(order) if order.is_open() => match order.expires_at() {
+                    Some(expiry) => (order.symbol.clone(), expiry),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if !self.matching_engine.cancel_order(order_id).await? && !self.leave_auction(&symbol, order_id) {
+                continue;
+            }
+
+            if let Some(mut order) = self.orders.get_mut(&order_id) {
+                order.status = OrderStatus::Expired;
+                order.cancel_reason = Some(CancelReason::Expired);
+                let sequence = self
+                    .event_journal
+                    .publish(EngineEvent::OrderExpired { order_id, expiry }, vec![order.account_id]);
+                logging::audit_order(&self.config.engine_instance_id, "order_expired", &order, sequence, now);
+            }
+            expired.push(order_id);
+        }
+
+        if !expired.is_empty() {
+            info!(
+                "Expired {} orders, {} expiries scheduled",
+                expired.len(),
+                self.expiry_wheel.len()
+            );
+        }
+
+        Ok(expired)
+    }
+
     pub fn get_order(&self, order_id: &Uuid) -> Option<Order> {
         self.orders.get(order_id).map(|o| o.clone())
     }
@@ -149,91 +876,2160 @@ impl TradingEngine {
         self.orders.iter().map(|entry| entry.value().clone()).collect()
     }
 
-    pub fn get_trades(&self) -> Vec<Trade> {
-        self.trades.read().iter().cloned().collect()
+    /// Orders without copying them. Each stays locked while it is held.
+    pub fn iter_orders(&self) -> impl Iterator<Item = impl Deref<Target = Order> + '_> + '_ {
+        self.orders.iter()
+    }
+
+    /// Looks a retained trade up by its id or its trade number.
+    pub fn get_trade(&self, key: &str) -> Option<Trade> {
+        let id = Uuid::parse_str(key).ok();
+        self.trades
+            .read()
+            .iter()
+            .rev()
+            .find(|trade| id.map_or(trade.trade_number == key, |id| trade.id == id))
+            .cloned()
     }
 
+    /// Refreshes the symbol's snapshot from the live book, including
+    /// implied levels, and returns it.
     pub fn get_orderbook(&self, symbol: &str) -> Option<OrderBook> {
+        if let Some((bids, asks)) = self.book_with_implied(symbol) {
+            self.order_book_manager
+                .update_orderbook(symbol.to_string(), bids, asks);
+        }
         self.order_book_manager.get_orderbook(symbol)
     }
 
     pub async fn get_positions(&self, account_id: Option<Uuid>) -> Vec<Position> {
-        self.position_manager.get_positions(account_id).await
+        self.position_manager
+            .get_positions(account_id)
+            .await
+            .into_iter()
+            .map(|position| self.with_accrued_interest(position))
+            .collect()
     }
 
-    pub fn subscribe_events(&self) -> broadcast::Receiver<EngineEvent> {
-        self.event_sender.subscribe()
+    pub async fn get_position(&self, account_id: Uuid, symbol: &str) -> Option<Position> {
+        self.position_manager
+            .get_position(account_id, symbol)
+            .await
+            .map(|position| self.with_accrued_interest(position))
     }
 
-    async fn validate_order(&self, order: &Order) -> crate::types::Result<()> {
-        if order.quantity <= Decimal::ZERO {
-            return Err(TradingError::InvalidOrder("Quantity must be positive".to_string()));
-        }
+    fn with_accrued_interest(&self, mut position: Position) -> Position {
+        let (_, accrued) = self.quote_basis(&position.symbol);
+        let index_ratio = self.settlement_index_ratio(&position.symbol).unwrap_or(Decimal::ONE);
+        position.accrued_interest = notional_value(position.quantity, accrued) * index_ratio;
+        position
+    }
 
-        if let Some(price) = order.price {
-            if price <= Decimal::ZERO {
-                return Err(TradingError::InvalidOrder("Price must be positive".to_string()));
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SequencedEvent> {
+        self.event_journal.subscribe()
+    }
+
+    /// Subscribes with a chosen delivery; consumers that persist or settle
+    /// from events take them losslessly.
+    pub fn subscribe_events_with(&self, delivery: Delivery) -> EventReceiver {
+        self.event_journal.subscribe_with(delivery)
+    }
+
+    pub fn last_event_seq(&self) -> u64 {
+        self.event_journal.last_seq()
+    }
+
+    pub fn replay_events(&self, from_seq: u64, account_id: Option<Uuid>, limit: usize) -> EventReplay {
+        self.event_journal.replay(from_seq, account_id, limit)
+    }
+
+    /// Applies executed trades to positions, account stats, the stored
+    /// state of both orders and the trade history.
+    async fn record_trades(&self, trades: &[Trade]) -> crate::types::Result<()> {
+        for trade in trades {
+            if self.clearing.enabled() {
+                self.clearing.novate(trade);
+            } else {
+                self.credit_lines.record_trade(trade);
             }
+            self.confirmations.record_trade(trade, self.time_provider.now());
+            self.position_manager.update_position(trade).await?;
+            self.account_stats.record_trade(trade);
+            self.activity.record_trade(trade);
+            self.pnl.record_trade(trade);
+            self.reference_prices.record_trade(trade);
+            self.market_summary.record_trade(trade);
+            self.apply_fill(trade.buyer_order_id, trade);
+            self.apply_fill(trade.seller_order_id, trade);
         }
 
-        if order.symbol.is_empty() {
-            return Err(TradingError::InvalidOrder("Symbol cannot be empty".to_string()));
+        self.retain_trades(trades).await;
+        Ok(())
+    }
+
+    /// Prices the order at its limit, or at the contra touch for market
+    /// orders, and checks the member's clearing exposure.
+    fn check_clearing_exposure(&self, order: &Order) -> crate::types::Result<()> {
+        let price = order.price.or_else(|| match order.side {
+            OrderSide::Buy => self.matching_engine.get_best_ask(&order.symbol),
+            OrderSide::Sell => self.matching_engine.get_best_bid(&order.symbol),
+        });
+        match price {
+            Some(price) => self.clearing.check_order(order, price),
+            None => Ok(()),
         }
+    }
 
-        // Additional validation logic
-        match &order.order_type {
-            OrderType::Limit => {
-                if order.price.is_none() {
-                    return Err(TradingError::InvalidOrder("Limit orders must have a price".to_string()));
-                }
-            }
-            OrderType::Market => {
-                if order.price.is_some() {
-                    return Err(TradingError::InvalidOrder("Market orders cannot have a price".to_string()));
-                }
-            }
-            _ => {}
+    fn apply_fill(&self, order_id: Uuid, trade: &Trade) {
+        if let Some(mut order) = self.orders.get_mut(&order_id) {
+            order.filled_quantity += trade.quantity;
+            order.remaining_quantity -= trade.quantity;
+            let filled_quantity = order.filled_quantity;
+            order.fills.record(trade.quantity, trade.price, filled_quantity);
+            order.status = if order.remaining_quantity <= Decimal::ZERO {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
         }
+    }
 
-        Ok(())
+    pub fn instruments(&self) -> &InstrumentRegistry {
+        &self.instruments
+    }
+
+    pub fn fee_engine(&self) -> &FeeEngine {
+        &self.fee_engine
+    }
+
+    pub fn strategies(&self) -> &StrategyRegistry {
+        &self.strategies
+    }
+
+    pub fn pnl(&self) -> &PnlAttribution {
+        &self.pnl
+    }
+
+    pub fn clearing(&self) -> &ClearingHouse {
+        &self.clearing
+    }
+
+    pub fn firms(&self) -> &FirmRegistry {
+        &self.firms
+    }
+
+    pub fn get_account_stats(&self, account_id: Uuid) -> AccountTradingStats {
+        self.account_stats.get(account_id)
+    }
+
+    pub fn get_account_activity(&self, account_id: Uuid) -> AccountActivity {
+        self.activity.get(account_id)
+    }
+
+    pub fn risk_manager(&self) -> &RiskManager {
+        &self.risk_manager
+    }
+
+    /// Grants a soft-limit override; the grant goes on the event journal
+    /// as the audit record.
+    pub fn grant_risk_override(&self, request: OverrideRequest) -> crate::types::Result<RiskOverride> {
+        let grant = self.risk_manager.grant_override(request)?;
+        info!(
+            token = %grant.token,
+            account_id = %grant.account_id,
+            approved_by = %grant.approved_by,
+            expires_at = %grant.expires_at,
+            "Risk override granted"
+        );
+        self.event_journal
+            .publish(EngineEvent::RiskOverrideGranted(grant.clone()), vec![grant.account_id]);
+        Ok(grant)
+    }
+
+    pub fn revoke_risk_override(&self, token: Uuid) -> crate::types::Result<RiskOverride> {
+        let grant = self
+            .risk_manager
+            .revoke_override(token)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown override {}", token)))?;
+        self.event_journal
+            .publish(EngineEvent::RiskOverrideRevoked(grant.clone()), vec![grant.account_id]);
+        Ok(grant)
     }
 
     pub fn get_metrics(&self) -> &Metrics {
         &self.metrics
     }
+
+    pub fn feed_monitor(&self) -> &FeedMonitor {
+        &self.feed_monitor
+    }
+
+    pub fn state_store(&self) -> &dyn StateStore {
+        self.state_store.as_ref()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::{config::Config, test_support::OrderBuilder, utils::time::SimulatedClock};
+    use allocation::MatchingAlgorithm;
+    use chrono::{Duration, TimeZone, Utc};
     use rust_decimal_macros::dec;
+    use std::collections::HashMap;
 
     #[tokio::test]
     async fn test_submit_order() {
         let config = Arc::new(Config::default());
         let engine = TradingEngine::new(config).await.unwrap();
 
-        let order = Order {
-            id: Uuid::new_v4(),
-            client_order_id: "TEST001".to_string(),
-            symbol: "GSEC10Y".to_string(),
-            side: OrderSide::Buy,
-            order_type: OrderType::Limit,
-            quantity: dec!(1000000),
-            price: Some(dec!(98.50)),
-            filled_quantity: Decimal::ZERO,
-            remaining_quantity: Decimal::ZERO,
-            status: OrderStatus::Pending,
-            timestamp: Utc::now(),
-            user_id: Uuid::new_v4(),
-            account_id: Uuid::new_v4(),
-            time_in_force: TimeInForce::GoodTillCancel,
-            metadata: HashMap::new(),
-        };
+        let order = OrderBuilder::new(OrderSide::Buy)
+            .quantity(dec!(1000000))
+            .price(dec!(98.50))
+            .build();
 
         let result = engine.submit_order(order.clone()).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), order.id);
     }
+
+    #[tokio::test]
+    async fn test_good_till_date_order_expires_on_simulated_clock() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let config = Arc::new(Config::default());
+        let engine = TradingEngine::with_time_provider(config, Arc::new(TimeProvider::with_clock(clock.clone())))
+            .await
+            .unwrap();
+
+        let order = OrderBuilder::new(OrderSide::Sell)
+            .quantity(dec!(500000))
+            .price(dec!(99.10))
+            .time_in_force(TimeInForce::GoodTillDate(start + Duration::hours(2)))
+            .timestamp(start)
+            .build();
+        engine.submit_order(order.clone()).await.unwrap();
+
+        clock.advance(Duration::hours(1));
+        assert!(engine.expire_orders().await.unwrap().is_empty());
+
+        clock.advance(Duration::hours(1));
+        assert_eq!(engine.expire_orders().await.unwrap(), vec![order.id]);
+        assert_eq!(engine.get_order(&order.id).unwrap().status, OrderStatus::Expired);
+    }
+
+    fn limit_order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_trade_records_aggressor_and_maker() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let maker = Uuid::new_v4();
+        let taker = Uuid::new_v4();
+
+        let resting = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), maker);
+        engine.submit_order(resting.clone()).await.unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(40000), dec!(99.50), taker))
+            .await
+            .unwrap();
+
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].aggressor_side, OrderSide::Buy);
+        assert_eq!(trades[0].maker_order_id, resting.id);
+        assert_eq!(trades[0].price, dec!(99.25));
+
+        let maker_stats = engine.get_account_stats(maker);
+        assert_eq!(maker_stats.maker_volume, dec!(40000));
+        assert_eq!(maker_stats.taker_trades, 0);
+        assert_eq!(engine.get_account_stats(taker).taker_volume, dec!(40000));
+    }
+
+    fn test_bond(symbol: &str) -> Bond {
+        Bond {
+            isin: "IN0020230085".to_string(),
+            symbol: symbol.to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(2033, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.18),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suspended_instrument_rejects_orders_and_cancels_resting() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+
+        let resting = limit_order(OrderSide::Buy, dec!(100000), dec!(98.75), Uuid::new_v4());
+        engine.submit_order(resting.clone()).await.unwrap();
+
+        let (_, cancelled) = engine
+            .suspend_instrument("GSEC10Y", Some("Issuer event".to_string()), true)
+            .await
+            .unwrap();
+        assert_eq!(cancelled, vec![resting.id]);
+        assert_eq!(engine.get_order(&resting.id).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(
+            engine.get_order(&resting.id).unwrap().cancel_reason,
+            Some(CancelReason::InstrumentSuspended)
+        );
+
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.75), Uuid::new_v4()))
+            .await;
+        assert!(matches!(rejected, Err(TradingError::InstrumentNotTradable(_))));
+
+        engine.resume_instrument("GSEC10Y").unwrap();
+        let archive = engine.delist_instrument("GSEC10Y", None).unwrap();
+        assert!(archive.orders.iter().any(|order| order.id == resting.id));
+        assert!(engine.get_order(&resting.id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pro_rata_instrument_shares_level_by_size() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::ProRata)
+            .unwrap();
+
+        let top = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), Uuid::new_v4());
+        let large = limit_order(OrderSide::Sell, dec!(300000), dec!(99.25), Uuid::new_v4());
+        let small = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), Uuid::new_v4());
+        for order in [&top, &large, &small] {
+            engine.submit_order(order.clone()).await.unwrap();
+        }
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(300000), dec!(99.25), Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        let filled = |order: &Order| engine.get_order(&order.id).unwrap().filled_quantity;
+        assert_eq!(filled(&top), dec!(100000));
+        assert_eq!(filled(&large), dec!(150000));
+        assert_eq!(filled(&small), dec!(50000));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_reports_and_repairs_store_drift() {
+        use reconciliation::{DiscrepancyKind, ReconciledEntity};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let buyer = Uuid::new_v4();
+        let resting = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), Uuid::new_v4());
+        engine.submit_order(resting.clone()).await.unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.25), buyer))
+            .await
+            .unwrap();
+
+        let mut stale = engine.get_order(&resting.id).unwrap();
+        stale.status = OrderStatus::Pending;
+        stale.filled_quantity = Decimal::ZERO;
+        engine.state_store().save_order(&stale).await.unwrap();
+        let orphan = limit_order(OrderSide::Buy, dec!(5000), dec!(97.00), Uuid::new_v4());
+        engine.state_store().save_order(&orphan).await.unwrap();
+
+        let report = engine.reconcile(false).await.unwrap();
+        let mismatch = report
+            .discrepancies
+            .iter()
+            .find(|d| d.key == resting.id.to_string())
+            .unwrap();
+        assert_eq!(mismatch.kind, DiscrepancyKind::Mismatch);
+        assert!(mismatch.fields.iter().any(|f| f.field == "status"));
+        assert!(report
+            .discrepancies
+            .iter()
+            .any(|d| d.key == orphan.id.to_string() && d.kind == DiscrepancyKind::MissingInEngine));
+        assert!(report.discrepancies.iter().any(|d| {
+            d.entity == ReconciledEntity::Position && d.kind == DiscrepancyKind::MissingInStore
+        }));
+        assert!(report.discrepancies.iter().all(|d| !d.repaired));
+
+        let applied = engine.reconcile(true).await.unwrap();
+        assert!(applied.discrepancies.iter().all(|d| d.repaired));
+        assert!(engine.reconcile(false).await.unwrap().discrepancies.is_empty());
+        assert!(engine.state_store().load_orders().await.unwrap().iter().all(|o| o.id != orphan.id));
+    }
+
+    #[tokio::test]
+    async fn test_auction_publishes_indicative_and_uncrosses_at_equilibrium() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let resting = limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), Uuid::new_v4());
+        engine.submit_order(resting.clone()).await.unwrap();
+
+        engine.start_auction("GSEC10Y").unwrap();
+        let mut events = engine.subscribe_events();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(300000), dec!(99.50), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.40), Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert!(engine.get_trades().is_empty());
+
+        let mut last_indicative = None;
+        while let Ok(event) = events.try_recv() {
+            if let EngineEvent::AuctionIndicative(indicative) = event.event {
+                last_indicative = Some(indicative);
+            }
+        }
+        let indicative = last_indicative.unwrap();
+        assert_eq!(indicative.price, Some(dec!(99.40)));
+        assert_eq!(indicative.matched_volume, dec!(200000));
+        assert_eq!(indicative.imbalance_side, Some(OrderSide::Buy));
+        assert_eq!(indicative.imbalance_quantity, dec!(100000));
+
+        let trades = engine.uncross_auction("GSEC10Y").await.unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades
+            .iter()
+            .all(|trade| trade.price == dec!(99.40) && trade.trade_type == TradeType::Auction));
+        assert_eq!(engine.get_order(&resting.id).unwrap().status, OrderStatus::Filled);
+        assert_eq!(engine.matching_engine.get_best_bid("GSEC10Y"), Some(dec!(99.50)));
+        assert_eq!(engine.instruments().status("GSEC10Y"), Some(InstrumentStatus::Active));
+    }
+
+    #[tokio::test]
+    async fn test_internalization_crosses_same_firm_ahead_of_queue() {
+        let config = Config {
+            internalization_enabled: true,
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let client = Uuid::new_v4();
+        let desk = Uuid::new_v4();
+        engine.firms().set_firm(client, Some("BRK1".to_string()));
+        engine.firms().set_firm(desk, Some("BRK1".to_string()));
+
+        let outside = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), Uuid::new_v4());
+        let internal = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), desk);
+        engine.submit_order(outside.clone()).await.unwrap();
+        engine.submit_order(internal.clone()).await.unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(150000), dec!(99.50), client))
+            .await
+            .unwrap();
+
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].seller_order_id, internal.id);
+        assert!(trades[0].internalized);
+        assert_eq!(trades[0].price, dec!(99.25));
+        assert_eq!(trades[1].seller_order_id, outside.id);
+        assert!(!trades[1].internalized);
+    }
+
+    #[tokio::test]
+    async fn test_ccp_novates_trades_and_enforces_member_exposure() {
+        let config = Config {
+            ccp_enabled: true,
+            clearing_account_id: Uuid::new_v4(),
+            ..Config::default()
+        };
+        let clearing_account = config.clearing_account_id;
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let buyer = Uuid::new_v4();
+        let seller = Uuid::new_v4();
+
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(1000000), dec!(99.00), seller))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(1000000), dec!(99.00), buyer))
+            .await
+            .unwrap();
+
+        let legs = engine.clearing().legs(None);
+        assert_eq!(legs.len(), 2);
+        assert!(legs.iter().all(|leg| leg.counterparty_account_id == clearing_account));
+        assert_eq!(engine.clearing().member_exposure(buyer).exposure, dec!(990000));
+
+        engine.clearing().set_exposure_limit(buyer, dec!(1000000));
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), buyer))
+            .await;
+        assert!(matches!(rejected, Err(TradingError::RiskLimitExceeded { .. })));
+        // Selling back reduces exposure and is always allowed
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), buyer))
+            .await
+            .unwrap();
+
+        let fund = engine.clearing().default_fund();
+        assert_eq!(fund.len(), 2);
+        assert!(fund.iter().all(|member| member.contribution >= dec!(1000000)));
+    }
+
+    #[tokio::test]
+    async fn test_strategy_tags_attribute_pnl() {
+        use pnl::PnlGrouping;
+        use strategies::StrategyRequest;
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let trader = Uuid::new_v4();
+        let street = Uuid::new_v4();
+        engine
+            .strategies()
+            .register(StrategyRequest {
+                id: "CURVE".to_string(),
+                name: "Curve steepener".to_string(),
+                account_ids: vec![trader],
+            })
+            .unwrap();
+
+        let mut untracked = limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), street);
+        untracked.strategy_id = Some("MISSING".to_string());
+        assert!(matches!(
+            engine.submit_order(untracked).await,
+            Err(TradingError::InvalidOrderField { field: OrderField::Strategy, .. })
+        ));
+
+        let tagged = |side, price| {
+            let mut order = limit_order(side, dec!(100000), price, trader);
+            order.strategy_id = Some("CURVE".to_string());
+            order
+        };
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), street))
+            .await
+            .unwrap();
+        engine.submit_order(tagged(OrderSide::Buy, dec!(99.00))).await.unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.50), street))
+            .await
+            .unwrap();
+        engine.submit_order(tagged(OrderSide::Sell, dec!(99.50))).await.unwrap();
+
+        let trades = engine.get_trades();
+        assert_eq!(trades[0].buyer_strategy_id.as_deref(), Some("CURVE"));
+        assert_eq!(trades[1].seller_strategy_id.as_deref(), Some("CURVE"));
+
+        let by_strategy = engine.pnl().summarize(PnlGrouping::Strategy, Some(trader));
+        assert_eq!(by_strategy.len(), 1);
+        assert_eq!(by_strategy[0].group.as_deref(), Some("CURVE"));
+        assert_eq!(by_strategy[0].realized_pnl, dec!(500));
+    }
+
+    #[tokio::test]
+    async fn test_spread_orders_fill_implied_and_against_spreads() {
+        use spreads::{SpreadLeg, SpreadOrderRequest};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let street = Uuid::new_v4();
+        let outright = |symbol: &str, side, price| {
+            let mut order = limit_order(side, dec!(100000), price, street);
+            order.symbol = symbol.to_string();
+            order
+        };
+        let steepener = |side, price, account_id, legs: &[(&str, i32)]| SpreadOrderRequest {
+            client_order_id: format!("SPREAD-{}", Uuid::new_v4()),
+            legs: legs
+                .iter()
+                .map(|(symbol, ratio)| SpreadLeg { symbol: symbol.to_string(), ratio: *ratio })
+                .collect(),
+            side,
+            quantity: dec!(100000),
+            price,
+            user_id: Uuid::new_v4(),
+            account_id,
+            reference_index: None,
+        };
+
+        engine.submit_order(outright("GSEC10Y", OrderSide::Sell, dec!(99.00))).await.unwrap();
+        engine.submit_order(outright("GSEC2Y", OrderSide::Buy, dec!(97.00))).await.unwrap();
+
+        // Buying 10s and selling 2s nets 2.00, inside the 2.50 limit
+        let trader = Uuid::new_v4();
+        let implied = engine
+            .submit_spread_order(steepener(OrderSide::Buy, dec!(2.50), trader, &[("GSEC10Y", 1), ("GSEC2Y", -1)]))
+            .await
+            .unwrap();
+        assert_eq!(implied.order.status, OrderStatus::Filled);
+        assert_eq!(implied.trades.len(), 2);
+        let children: Vec<Order> = engine
+            .get_orders()
+            .into_iter()
+            .filter(|order| order.metadata.contains_key("spread_order_id"))
+            .collect();
+        assert_eq!(children.len(), 2);
+        assert!(children.iter().all(|order| order.status == OrderStatus::Filled));
+
+        // No outright liquidity left, so the offer rests on the spread book
+        let seller = Uuid::new_v4();
+        let offer = engine
+            .submit_spread_order(steepener(OrderSide::Sell, dec!(1.75), seller, &[("GSEC10Y", 1), ("GSEC2Y", -1)]))
+            .await
+            .unwrap();
+        assert_eq!(offer.order.status, OrderStatus::Pending);
+
+        // Same spread with the legs listed the other way round
+        let buyer = Uuid::new_v4();
+        let crossed = engine
+            .submit_spread_order(steepener(OrderSide::Buy, dec!(2.00), buyer, &[("GSEC2Y", -1), ("GSEC10Y", 1)]))
+            .await
+            .unwrap();
+        assert_eq!(crossed.order.status, OrderStatus::Filled);
+
+        let ten_year = crossed.trades.iter().find(|trade| trade.symbol == "GSEC10Y").unwrap();
+        let two_year = crossed.trades.iter().find(|trade| trade.symbol == "GSEC2Y").unwrap();
+        assert_eq!(ten_year.trade_type, TradeType::SpreadLeg);
+        assert_eq!((ten_year.buyer_account_id, two_year.seller_account_id), (buyer, buyer));
+        assert_eq!(ten_year.price - two_year.price, dec!(1.75));
+        assert_eq!(
+            engine.get_spread_orders(Some(seller))[0].status,
+            OrderStatus::Filled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_spread_order_records_why() {
+        use spreads::{SpreadLeg, SpreadOrderRequest};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine.reference_prices().set_external("GSEC2Y", dec!(97.00), Utc::now());
+        let account_id = Uuid::new_v4();
+        let mut events = engine.subscribe_events();
+        let offer = engine
+            .submit_spread_order(SpreadOrderRequest {
+                client_order_id: "SPREAD-CANCEL".to_string(),
+                legs: vec![
+                    SpreadLeg { symbol: "GSEC10Y".to_string(), ratio: 1 },
+                    SpreadLeg { symbol: "GSEC2Y".to_string(), ratio: -1 },
+                ],
+                side: OrderSide::Sell,
+                quantity: dec!(100000),
+                price: dec!(1.75),
+                user_id: Uuid::new_v4(),
+                account_id,
+                reference_index: None,
+            })
+            .await
+            .unwrap()
+            .order;
+        assert_eq!(offer.cancel_reason, None);
+
+        assert!(engine.cancel_spread_order(offer.id).unwrap());
+        let cancelled = &engine.get_spread_orders(Some(account_id))[0];
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        assert_eq!(cancelled.cancel_reason, Some(CancelReason::ClientRequest));
+        assert_eq!(engine.spread_books()[0].best_ask, None);
+
+        let mut published = None;
+        while let Ok(event) = events.try_recv() {
+            if let EngineEvent::SpreadOrderUpdated(order) = event.event {
+                published = Some(order);
+            }
+        }
+        assert_eq!(published.unwrap().cancel_reason, Some(CancelReason::ClientRequest));
+        assert!(!engine.cancel_spread_order(offer.id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_spread_order_rests_whole_when_a_leg_book_is_too_thin() {
+        use spreads::{SpreadLeg, SpreadOrderRequest};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let street = Uuid::new_v4();
+        let mut ten_year_offer = limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), street);
+        ten_year_offer.symbol = "GSEC10Y".to_string();
+        engine.submit_order(ten_year_offer).await.unwrap();
+        // Half what the spread needs on its other leg
+        let mut two_year_bid = limit_order(OrderSide::Buy, dec!(50000), dec!(97.00), street);
+        two_year_bid.symbol = "GSEC2Y".to_string();
+        engine.submit_order(two_year_bid).await.unwrap();
+
+        let spread = engine
+            .submit_spread_order(SpreadOrderRequest {
+                client_order_id: "STEEPENER-THIN".to_string(),
+                legs: vec![
+                    SpreadLeg { symbol: "GSEC10Y".to_string(), ratio: 1 },
+                    SpreadLeg { symbol: "GSEC2Y".to_string(), ratio: -1 },
+                ],
+                side: OrderSide::Buy,
+                quantity: dec!(100000),
+                price: dec!(2.50),
+                user_id: Uuid::new_v4(),
+                account_id: Uuid::new_v4(),
+                reference_index: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(spread.trades.is_empty());
+        assert_eq!(spread.order.status, OrderStatus::Pending);
+        assert_eq!(spread.order.remaining_quantity, dec!(100000));
+        assert!(engine.get_trades().is_empty());
+        assert_eq!(engine.get_orderbook("GSEC10Y").unwrap().asks[0].quantity, dec!(100000));
+        assert_eq!(engine.spread_books()[0].bid_quantity, dec!(100000));
+    }
+
+    #[tokio::test]
+    async fn test_spread_legs_are_sized_within_credit_lines() {
+        use credit_lines::CreditLineRequest;
+        use spreads::{SpreadLeg, SpreadOrderRequest};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (ten_year_dealer, two_year_dealer, trader) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut ten_year_offer = limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), ten_year_dealer);
+        ten_year_offer.symbol = "GSEC10Y".to_string();
+        engine.submit_order(ten_year_offer).await.unwrap();
+        let mut two_year_bid = limit_order(OrderSide::Buy, dec!(100000), dec!(97.00), two_year_dealer);
+        two_year_bid.symbol = "GSEC2Y".to_string();
+        engine.submit_order(two_year_bid).await.unwrap();
+        // The only 2s bid is from a dealer the trader will not face
+        engine
+            .credit_lines()
+            .set_line(
+                trader,
+                CreditLineRequest {
+                    counterparty_account_id: ten_year_dealer,
+                    limit: dec!(1000000),
+                },
+            )
+            .unwrap();
+
+        let spread = engine
+            .submit_spread_order(SpreadOrderRequest {
+                client_order_id: "STEEPENER-CREDIT".to_string(),
+                legs: vec![
+                    SpreadLeg { symbol: "GSEC10Y".to_string(), ratio: 1 },
+                    SpreadLeg { symbol: "GSEC2Y".to_string(), ratio: -1 },
+                ],
+                side: OrderSide::Buy,
+                quantity: dec!(100000),
+                price: dec!(2.50),
+                user_id: Uuid::new_v4(),
+                account_id: trader,
+                reference_index: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(spread.trades.is_empty());
+        assert_eq!(spread.order.status, OrderStatus::Pending);
+        assert!(engine.get_trades().is_empty());
+        assert_eq!(engine.get_orderbook("GSEC10Y").unwrap().asks[0].quantity, dec!(100000));
+    }
+
+    #[tokio::test]
+    async fn test_outright_order_hits_implied_spread_quote() {
+        use spreads::{SpreadLeg, SpreadOrderRequest};
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        for symbol in ["GSEC10Y", "GSEC2Y"] {
+            engine
+                .list_instrument(test_bond(symbol), MatchingAlgorithm::PriceTime)
+                .unwrap();
+        }
+        let street = Uuid::new_v4();
+        let mut two_year_bid = limit_order(OrderSide::Buy, dec!(100000), dec!(97.00), street);
+        two_year_bid.symbol = "GSEC2Y".to_string();
+        engine.submit_order(two_year_bid).await.unwrap();
+
+        let spread_buyer = Uuid::new_v4();
+        let spread = engine
+            .submit_spread_order(SpreadOrderRequest {
+                client_order_id: "STEEPENER-1".to_string(),
+                legs: vec![
+                    SpreadLeg { symbol: "GSEC10Y".to_string(), ratio: 1 },
+                    SpreadLeg { symbol: "GSEC2Y".to_string(), ratio: -1 },
+                ],
+                side: OrderSide::Buy,
+                quantity: dec!(100000),
+                price: dec!(2.50),
+                user_id: Uuid::new_v4(),
+                account_id: spread_buyer,
+                reference_index: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(spread.order.status, OrderStatus::Pending);
+
+        // Selling 2s at 97.00 lets the spread pay up to 99.50 for 10s
+        let book = engine.get_orderbook("GSEC10Y").unwrap();
+        assert_eq!(book.bids.len(), 1);
+        assert!(book.bids[0].implied);
+        assert_eq!(book.bids[0].price, dec!(99.50));
+
+        let seller = Uuid::new_v4();
+        let order = limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), seller);
+        let order_id = engine.submit_order(order).await.unwrap();
+        assert_eq!(engine.get_order(&order_id).unwrap().status, OrderStatus::Filled);
+
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].symbol.as_str(), trades[0].price), ("GSEC10Y", dec!(99.50)));
+        assert_eq!(trades[0].buyer_account_id, spread_buyer);
+        assert_eq!((trades[1].symbol.as_str(), trades[1].price), ("GSEC2Y", dec!(97.00)));
+        assert_eq!(trades[1].seller_account_id, spread_buyer);
+        assert_eq!(
+            engine.get_spread_orders(Some(spread_buyer))[0].status,
+            OrderStatus::Filled
+        );
+        assert!(engine.get_orderbook("GSEC10Y").unwrap().bids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_order_limit_rejects_and_publishes_violation() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_open_orders_per_symbol = 2;
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let first = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.00), account))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.10), account))
+            .await
+            .unwrap();
+
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.20), account))
+            .await;
+        assert!(matches!(rejected, Err(TradingError::RiskLimitExceeded { .. })));
+        assert_eq!(engine.get_metrics().snapshot().risk_violations, 1);
+        let replay = engine.replay_events(1, Some(account), usize::MAX);
+        assert!(replay
+            .events
+            .iter()
+            .any(|event| matches!(event.event, EngineEvent::RiskViolation { .. })));
+
+        // Cancelling frees a slot
+        engine.cancel_order(first).await.unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.20), account))
+            .await
+            .unwrap();
+
+        let activity = engine.get_account_activity(account);
+        assert_eq!((activity.open_orders, activity.messages), (2, 5));
+    }
+
+    #[tokio::test]
+    async fn test_amend_rejected_by_risk_leaves_the_original_open() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_position_size = dec!(200000);
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let original = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(150000), dec!(98.00), account))
+            .await
+            .unwrap();
+        let rejected = engine.modify_order(original, Some(dec!(250000)), None).await;
+        assert!(matches!(rejected, Err(TradingError::RiskLimitExceeded { .. })));
+        assert!(engine.get_order(&original).unwrap().is_open());
+        assert_eq!(engine.matching_engine.get_best_bid("GSEC10Y"), Some(dec!(98.00)));
+        assert_eq!(engine.get_account_activity(account).open_orders, 1);
+
+        // The replacement is checked in the original's place, not beside it
+        let replacement = engine.modify_order(original, None, Some(dec!(98.10))).await.unwrap();
+        assert_eq!(engine.get_order(&original).unwrap().cancel_reason, Some(CancelReason::Replaced));
+        assert_eq!(engine.get_order(&replacement).unwrap().quantity, dec!(150000));
+        // The order and each amend, rejected or not, count once
+        assert_eq!(engine.get_account_activity(account).messages, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_opposite_orders_never_leave_the_book_crossed() {
+        let engine = Arc::new(TradingEngine::new(Arc::new(Config::default())).await.unwrap());
+        let tasks: Vec<_> = (0..200)
+            .map(|i| {
+                let engine = engine.clone();
+                let side = if i % 2 == 0 { OrderSide::Buy } else { OrderSide::Sell };
+                tokio::spawn(async move { engine.submit_order(OrderBuilder::new(side).build()).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        // Every order at one price, so whatever rests must be on one side
+        let bid = engine.matching_engine.get_best_bid("GSEC10Y");
+        let ask = engine.matching_engine.get_best_ask("GSEC10Y");
+        assert!(bid.is_none() || ask.is_none(), "book left crossed: {:?} / {:?}", bid, ask);
+    }
+
+    #[tokio::test]
+    async fn test_resting_orders_count_toward_position_and_value_limits() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_position_size = dec!(250000);
+        limits.max_order_value = dec!(400000);
+        limits.concentration_limit = Decimal::ONE;
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let first = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.00), account))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.00), account))
+            .await
+            .unwrap();
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.00), account))
+            .await;
+        assert!(matches!(
+            rejected,
+            Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::PositionSize,
+                ..
+            })
+        ));
+        assert_eq!(engine.get_account_activity(account).open_order_value, dec!(196000));
+
+        // A partial fill and a cancel both release resting exposure
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(60000), dec!(98.00), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine.cancel_order(first).await.unwrap();
+        assert_eq!(engine.get_account_activity(account).open_order_value, dec!(98000));
+
+        // Value counts both sides: 98000 resting plus 306000 is over 400000
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(300000), dec!(102.00), account))
+            .await;
+        assert!(matches!(
+            rejected,
+            Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::OrderValue,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_order_flow_reproduces_trade_identifiers() {
+        let resting = limit_order(OrderSide::Sell, dec!(200000), dec!(99.00), Uuid::new_v4());
+        let first = limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), Uuid::new_v4());
+        let second = limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), Uuid::new_v4());
+
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+            for order in [&resting, &first, &second] {
+                engine.submit_order(order.clone()).await.unwrap();
+            }
+            runs.push(engine);
+        }
+
+        let trades = runs[0].get_trades();
+        let replayed = runs[1].get_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].id, replayed[0].id);
+        assert_eq!(trades[1].id, replayed[1].id);
+        assert_ne!(trades[0].id, trades[1].id);
+        assert_eq!(trades[1].trade_number, "VV01-000000000002");
+
+        let by_number = runs[0].get_trade("VV01-000000000002").unwrap();
+        assert_eq!(by_number.id, trades[1].id);
+        assert_eq!(runs[0].get_trade(&trades[0].id.to_string()).unwrap().trade_number, trades[0].trade_number);
+        assert!(runs[0].get_trade("VV01-000000000003").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_overview_summarizes_books_rates_and_accounts() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(300000), dec!(99.00), maker))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), taker))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.50), taker))
+            .await
+            .unwrap();
+
+        let overview = engine.overview().await;
+        assert_eq!(overview.resting_orders, 2);
+        let symbol = &overview.symbols[0];
+        assert_eq!(
+            (symbol.resting_bid_quantity, symbol.resting_ask_quantity, symbol.open_interest),
+            (dec!(100000), dec!(200000), dec!(100000))
+        );
+        assert_eq!((overview.rates[0].orders, overview.rates[0].trades), (3, 1));
+        assert_eq!(overview.top_accounts.len(), 2);
+        assert_eq!(overview.risk_violations_today, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dirty_quoted_instrument_matches_on_clean_price() {
+        // Trades on Thursday settle on 1 March, 76 days into the coupon period
+        let start = Utc.with_ymd_and_hms(2024, 2, 29, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::with_clock(clock)),
+        )
+        .await
+        .unwrap();
+        engine
+            .list_instrument(
+                Bond {
+                    quote_convention: QuoteConvention::Dirty,
+                    ..test_bond("GSEC10Y")
+                },
+                MatchingAlgorithm::PriceTime,
+            )
+            .unwrap();
+        let accrued = dec!(7.18) * (dec!(76) / dec!(360));
+        assert_eq!(engine.quote_basis("GSEC10Y"), (QuoteConvention::Dirty, accrued));
+
+        let sell = limit_order(OrderSide::Sell, dec!(100000), dec!(101.00), Uuid::new_v4());
+        let sell_id = engine.submit_order(sell).await.unwrap();
+        // The clean price is held to the book's six decimal places
+        let resting = engine.get_order(&sell_id).unwrap();
+        assert_eq!(resting.price, Some((dec!(101.00) - accrued).round_dp(6)));
+        let book = engine.quoted_orderbook("GSEC10Y").unwrap();
+        assert_eq!(book.asks[0].price, dec!(101.00));
+
+        let buy = limit_order(OrderSide::Buy, dec!(100000), dec!(101.00), Uuid::new_v4());
+        engine.submit_order(buy).await.unwrap();
+        let trade = engine.get_trades().pop().unwrap();
+        assert_eq!(trade.price, (dec!(101.00) - accrued).round_dp(6));
+        assert_eq!(trade.accrued_interest, accrued);
+        assert_eq!(trade.quoted_price, dec!(101.00));
+        assert_eq!(trade.settlement_amount.round_dp(2), dec!(101000));
+    }
+
+    #[tokio::test]
+    async fn test_compression_reduces_gross_and_keeps_net() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (desk_a, desk_b, desk_c, outside) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for account in [desk_a, desk_b, desk_c] {
+            engine.firms().set_firm(account, Some("DEALER1".to_string()));
+        }
+
+        // Desk A buys 3mm then sells 5mm to desk C: A is short 2mm, B long 1mm, C long 5mm
+        for (buyer, seller, quantity) in [
+            (desk_a, outside, dec!(3000000)),
+            (desk_b, outside, dec!(1000000)),
+            (desk_c, desk_a, dec!(5000000)),
+        ] {
+            engine
+                .submit_order(limit_order(OrderSide::Sell, quantity, dec!(99.50), seller))
+                .await
+                .unwrap();
+            engine
+                .submit_order(limit_order(OrderSide::Buy, quantity, dec!(99.50), buyer))
+                .await
+                .unwrap();
+        }
+
+        let proposal = engine.compress_positions("DEALER1", false).await.unwrap();
+        let gsec = &proposal.symbols[0];
+        assert_eq!(
+            (gsec.net_quantity, gsec.gross_before, gsec.gross_after),
+            (dec!(4000000), dec!(8000000), dec!(4000000))
+        );
+        assert_eq!(gsec.trades.len(), 1);
+        assert_eq!((gsec.trades[0].buyer_account_id, gsec.trades[0].seller_account_id), (desk_a, desk_c));
+        // A dry run leaves positions alone
+        assert_eq!(engine.get_position(desk_a, "GSEC10Y").await.unwrap().quantity, dec!(-2000000));
+
+        let trades_before = engine.get_trades().len();
+        let executed = engine.compress_positions("DEALER1", true).await.unwrap();
+        assert!(executed.symbols[0].trades[0].trade_id.is_some());
+        let mut quantities = Vec::new();
+        for account in [desk_a, desk_b, desk_c] {
+            quantities.push(engine.get_position(account, "GSEC10Y").await.unwrap().quantity);
+        }
+        assert_eq!(quantities, vec![dec!(0), dec!(1000000), dec!(3000000)]);
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), trades_before + 1);
+        let compression = trades.iter().find(|trade| trade.trade_type == TradeType::Compression).unwrap();
+        assert!(compression.buyer_fees.total.is_zero() && compression.price == dec!(99.50));
+
+        assert!(engine.compress_positions("NOBODY", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_what_if_projects_shock_and_orders_without_executing() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let (account, counterparty) = (Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(10000000), dec!(99.50), counterparty))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(10000000), dec!(99.50), account))
+            .await
+            .unwrap();
+        let held = engine.get_position(account, "GSEC10Y").await.unwrap();
+
+        let shocked: what_if::WhatIfRequest = serde_json::from_value(serde_json::json!({
+            "account_id": account,
+            "shock": { "parallel_bps": "50" }
+        }))
+        .unwrap();
+        let report = engine.what_if(&shocked).await.unwrap();
+        let gsec = &report.positions[0];
+        assert!(gsec.shocked_price < gsec.mark_price);
+        // Half a point of yield costs roughly duration * 0.5% of value
+        let estimate = -gsec.market_value_before * gsec.modified_duration.unwrap() * dec!(0.005);
+        assert!((report.pnl_impact - estimate).abs() < estimate.abs() * dec!(0.05));
+        assert!(report.var_before > Decimal::ZERO);
+        // The long is worth less after the shock
+        assert!(report.var_after < report.var_before);
+
+        let flatten: what_if::WhatIfRequest = serde_json::from_value(serde_json::json!({
+            "account_id": account,
+            "orders": [{ "symbol": "GSEC10Y", "side": "Sell", "quantity": "10000000", "price": "99.75" }]
+        }))
+        .unwrap();
+        let report = engine.what_if(&flatten).await.unwrap();
+        assert_eq!(report.positions[0].quantity_after, Decimal::ZERO);
+        assert!(report.order_checks[0].rejection.is_none());
+        assert_eq!(report.var_after, Decimal::ZERO);
+        // 10mm sold a quarter point above cost, before fees
+        assert_eq!(report.pnl_after - held.realized_pnl, dec!(25000));
+
+        let position = engine.get_position(account, "GSEC10Y").await.unwrap();
+        assert_eq!((position.quantity, position.realized_pnl), (held.quantity, held.realized_pnl));
+        assert_eq!(engine.get_trades().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_soft_limit_needs_second_person_override() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.soft_max_order_value = Some(dec!(1000000));
+        limits.max_order_value = dec!(5000000);
+        limits.concentration_limit = Decimal::ONE;
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let large = limit_order(OrderSide::Buy, dec!(2000000), dec!(99.50), account);
+        let rejected = engine.submit_order(large.clone()).await;
+        assert!(matches!(
+            rejected,
+            Err(TradingError::RiskLimitExceeded { message, .. }) if message.contains("override")
+        ));
+
+        // The trader cannot approve their own override
+        let self_approved = engine
+            .grant_risk_override(OverrideRequest {
+                account_id: account,
+                approved_by: large.user_id,
+                reason: "client rebalancing".to_string(),
+                duration_minutes: 30,
+            })
+            .unwrap();
+        let mut order = large.clone();
+        order.id = Uuid::new_v4();
+        order.metadata.insert(risk_manager::OVERRIDE_TOKEN_KEY.to_string(), self_approved.token.to_string());
+        assert!(engine.submit_order(order.clone()).await.is_err());
+
+        let officer = Uuid::new_v4();
+        let grant = engine
+            .grant_risk_override(OverrideRequest {
+                account_id: account,
+                approved_by: officer,
+                reason: "client rebalancing".to_string(),
+                duration_minutes: 30,
+            })
+            .unwrap();
+        order.id = Uuid::new_v4();
+        order.metadata.insert(risk_manager::OVERRIDE_TOKEN_KEY.to_string(), grant.token.to_string());
+        engine.submit_order(order.clone()).await.unwrap();
+        let replay = engine.replay_events(0, Some(account), 100);
+        assert!(replay.events.iter().any(|event| matches!(
+            &event.event,
+            EngineEvent::RiskWarning { override_token, .. } if *override_token == grant.token
+        )));
+        assert!(replay
+            .events
+            .iter()
+            .any(|event| matches!(&event.event, EngineEvent::RiskOverrideGranted(granted) if granted.approved_by == officer)));
+
+        // Hard limits hold even with an override
+        let mut oversized = order.clone();
+        oversized.id = Uuid::new_v4();
+        oversized.quantity = dec!(6000000);
+        oversized.remaining_quantity = dec!(6000000);
+        assert!(engine.submit_order(oversized).await.is_err());
+
+        engine.revoke_risk_override(grant.token).unwrap();
+        order.id = Uuid::new_v4();
+        assert!(engine.submit_order(order).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_permissions_restrict_types_tenor_and_shorting() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let mut corporate = test_bond("NHAI2030");
+        corporate.bond_type = BondType::CorporateBond;
+        corporate.maturity_date = Utc.with_ymd_and_hms(2030, 6, 15, 0, 0, 0).unwrap();
+        engine.list_instrument(corporate, MatchingAlgorithm::PriceTime).unwrap();
+
+        let (fund, dealer) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.permissions().set(
+            fund,
+            permissions::TradingPermissions {
+                instrument_types: Some(vec![BondType::GovernmentSecurity]),
+                long_only: true,
+                max_tenor_years: None,
+            },
+        );
+
+        let mut corporate_buy = limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), fund);
+        corporate_buy.symbol = "NHAI2030".to_string();
+        assert!(matches!(
+            engine.submit_order(corporate_buy).await,
+            Err(TradingError::PermissionDenied(_))
+        ));
+        // No position yet, so a sell would go short
+        assert!(matches!(
+            engine
+                .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.50), fund))
+                .await,
+            Err(TradingError::PermissionDenied(_))
+        ));
+
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(300000), dec!(99.25), dealer))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(300000), dec!(99.25), fund))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(200000), dec!(99.50), fund))
+            .await
+            .unwrap();
+        // 200,000 of the 300,000 is already offered
+        assert!(engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(200000), dec!(99.60), fund))
+            .await
+            .is_err());
+
+        // Tightening the tenor takes effect on the next order
+        let mut permissions = engine.permissions().get(fund).unwrap();
+        permissions.max_tenor_years = Some(dec!(5));
+        engine.permissions().set(fund, permissions);
+        assert!(matches!(
+            engine
+                .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), fund))
+                .await,
+            Err(TradingError::PermissionDenied(message)) if message.contains("GSEC10Y")
+        ));
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), dealer))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_group_limits_sum_across_member_accounts() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (desk_a, desk_b, outside) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine
+            .account_groups()
+            .upsert(account_groups::AccountGroup {
+                id: "ENTITY1".to_string(),
+                name: "Entity One".to_string(),
+                accounts: vec![desk_a, desk_b],
+                limits: account_groups::GroupLimits {
+                    max_net_position: Some(dec!(1000000)),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(600000), dec!(99.50), outside))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(600000), dec!(99.50), desk_a))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(300000), dec!(99.00), desk_b))
+            .await
+            .unwrap();
+
+        // 600,000 held by A plus 300,000 bid by B leaves room for 100,000
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(200000), dec!(99.00), desk_b))
+            .await;
+        assert!(matches!(
+            rejected,
+            Err(TradingError::RiskLimitExceeded { message, .. }) if message.contains("ENTITY1")
+        ));
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), desk_a))
+            .await
+            .unwrap();
+        // Selling reduces the group's net and is allowed
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(500000), dec!(99.75), desk_b))
+            .await
+            .unwrap();
+
+        let exposure = engine.group_exposure("ENTITY1").await.unwrap();
+        let gsec = &exposure.symbols[0];
+        assert_eq!(
+            (gsec.net_position, gsec.open_buy_quantity, gsec.open_sell_quantity),
+            (dec!(600000), dec!(400000), dec!(500000))
+        );
+        assert_eq!(exposure.gross_exposure, dec!(1500000));
+
+        assert!(engine
+            .account_groups()
+            .upsert(account_groups::AccountGroup {
+                id: "ENTITY2".to_string(),
+                name: "Entity Two".to_string(),
+                accounts: vec![desk_a],
+                limits: Default::default(),
+            })
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quote_life_and_update_rate_throttle_cancels() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::with_clock(clock.clone())),
+        )
+        .await
+        .unwrap();
+        let maker = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(maker).await.unwrap();
+        limits.min_quote_life_ms = 250;
+        limits.max_quote_updates_per_second = 2;
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let mut quotes = Vec::new();
+        for price in [dec!(99.00), dec!(98.95), dec!(98.90)] {
+            quotes.push(
+                engine
+                    .submit_order(limit_order(OrderSide::Buy, dec!(100000), price, maker))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        clock.advance(chrono::Duration::milliseconds(100));
+        assert!(matches!(
+            engine.cancel_order(quotes[0]).await,
+            Err(TradingError::MinimumQuoteLife { remaining_ms: 150 })
+        ));
+        assert!(matches!(
+            engine.modify_order(quotes[0], None, Some(dec!(99.05))).await,
+            Err(TradingError::MinimumQuoteLife { .. })
+        ));
+
+        clock.advance(chrono::Duration::milliseconds(200));
+        assert!(engine.cancel_order(quotes[0]).await.unwrap());
+        engine.modify_order(quotes[1], None, Some(dec!(98.97))).await.unwrap();
+        assert!(matches!(
+            engine.cancel_order(quotes[2]).await,
+            Err(TradingError::QuoteRateExceeded(_))
+        ));
+        assert!(engine.get_order(&quotes[2]).unwrap().is_open());
+
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(engine.cancel_order(quotes[2]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_reduced_order_keeps_its_priority() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let first = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), Uuid::new_v4()))
+            .await
+            .unwrap();
+        let second = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(30000), dec!(99.00), Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            engine.reduce_order(first, dec!(20000)).await,
+            Err(TradingError::InvalidOrderField { field: OrderField::Quantity, .. })
+        ));
+        let reduced = engine.reduce_order(first, dec!(50000)).await.unwrap();
+        assert_eq!((reduced.quantity, reduced.remaining_quantity), (dec!(50000), dec!(20000)));
+        assert_eq!(engine.matching_engine.best_level("GSEC10Y", &OrderSide::Buy), Some((dec!(99.00), dec!(120000))));
+
+        // Still ahead of the order that arrived after it
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(40000), dec!(99.00), Uuid::new_v4()))
+            .await
+            .unwrap();
+        assert_eq!(engine.get_order(&first).unwrap().status, OrderStatus::Filled);
+        assert_eq!(engine.get_order(&second).unwrap().remaining_quantity, dec!(80000));
+    }
+
+    #[tokio::test]
+    async fn test_market_maker_presence_is_sampled_per_symbol() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let maker = Uuid::new_v4();
+        engine.market_makers().register(market_makers::MarketMaker {
+            account_id: maker,
+            name: "Primary Dealer One".to_string(),
+            obligation: market_makers::QuotingObligation {
+                symbols: vec!["GSEC10Y".to_string()],
+                max_spread: dec!(0.10),
+                min_size: dec!(500000),
+                min_presence_pct: dec!(70),
+            },
+        });
+
+        // One-sided
+        let bid = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(500000), dec!(99.40), maker))
+            .await
+            .unwrap();
+        engine.sample_market_maker_quotes();
+        // Two-sided but too wide
+        let wide = engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(500000), dec!(99.60), maker))
+            .await
+            .unwrap();
+        engine.sample_market_maker_quotes();
+        // Tightened with two orders at the touch
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(300000), dec!(99.48), maker))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(200000), dec!(99.48), maker))
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            engine.sample_market_maker_quotes();
+        }
+        engine.cancel_order(bid).await.unwrap();
+        engine.cancel_order(wide).await.unwrap();
+
+        let report = engine.market_maker_compliance(maker, None).unwrap();
+        let gsec = &report.symbols[0];
+        assert_eq!((gsec.samples, gsec.two_sided_pct, gsec.presence_pct), (4, dec!(75), dec!(50)));
+        // (0.20 + 0.08 + 0.08) / 3
+        assert_eq!(gsec.average_spread, Some(dec!(0.12)));
+        assert!(!gsec.meets_obligation);
+        assert!(engine.market_maker_compliance(Uuid::new_v4(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_portfolio_valuation_marks_at_last_trade() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let account = Uuid::new_v4();
+        for (side, price, owner) in [
+            (OrderSide::Sell, dec!(99.50), Uuid::new_v4()),
+            (OrderSide::Buy, dec!(99.50), account),
+            (OrderSide::Sell, dec!(100.00), Uuid::new_v4()),
+            (OrderSide::Buy, dec!(100.00), Uuid::new_v4()),
+        ] {
+            engine.submit_order(limit_order(side, dec!(1000000), price, owner)).await.unwrap();
+        }
+
+        let valuation = engine.value_portfolio(account).await;
+        let gsec = &valuation.positions[0];
+        assert_eq!(gsec.mark_price, dec!(100.00));
+        assert_eq!(gsec.market_value, dec!(1000000));
+        assert_eq!(valuation.unrealized_pnl, dec!(5000));
+        assert!(valuation.accrued_interest > Decimal::ZERO);
+        assert_eq!(valuation.total_value, valuation.market_value + valuation.accrued_interest);
+        assert!(valuation.same_values(&engine.value_portfolio(account).await));
+        assert!(engine.value_portfolio(Uuid::new_v4()).await.positions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_feed_quotes_drive_reference_price_and_revaluation() {
+        let config = Config {
+            reference_price_sources: vec![reference_price::ReferenceSource::External],
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let account = Uuid::new_v4();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(1000000), dec!(99.50), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(1000000), dec!(99.50), account))
+            .await
+            .unwrap();
+
+        let quote = |symbol: &str| MarketData {
+            symbol: symbol.to_string(),
+            bid_price: Some(dec!(99.90)),
+            ask_price: Some(dec!(100.10)),
+            last_price: None,
+            volume: Decimal::ZERO,
+            timestamp: Utc::now(),
+            yield_to_maturity: None,
+            yield_to_worst: None,
+            duration: None,
+            accrued_interest: None,
+        };
+        crate::feeds::ingest(&engine, "vendor", vec![quote("GSEC10Y"), quote("UNLISTED")]).await;
+
+        let reference = engine.reference_price("GSEC10Y").unwrap();
+        assert_eq!(reference.price, dec!(100.00));
+        let position = engine.get_position(account, "GSEC10Y").await.unwrap();
+        assert_eq!(position.unrealized_pnl, dec!(5000));
+        // The book is empty, so the ticker shows the vendor's quote
+        assert_eq!(engine.market_data("GSEC10Y").unwrap().bid_price, Some(dec!(99.90)));
+
+        let health = engine.feed_monitor().health(Utc::now());
+        assert_eq!((health[0].updates, health[0].rejected, health[0].stale), (1, 1, false));
+    }
+
+    #[tokio::test]
+    async fn test_matrix_price_from_grid_then_comparables_marks_unpriced_position() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(now));
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::with_clock(clock.clone())),
+        )
+        .await
+        .unwrap();
+        let corporate = |symbol: &str, year: i32| Bond {
+            maturity_date: Utc.with_ymd_and_hms(year, 6, 15, 0, 0, 0).unwrap(),
+            bond_type: BondType::CorporateBond,
+            rating: Some("AA".to_string()),
+            ..test_bond(symbol)
+        };
+        for bond in [test_bond("GSEC10Y"), corporate("CORP5Y", 2029), corporate("CORP7Y", 2031)] {
+            engine.list_instrument(bond, MatchingAlgorithm::PriceTime).unwrap();
+        }
+        engine.reference_prices().set_previous_close("GSEC10Y", dec!(100), now);
+        let mut grid = matrix_pricing::SpreadGrid::default();
+        grid.ratings.insert("AA".to_string(), dec!(150));
+        engine.pricing_matrix().set_spreads(grid);
+
+        let from_grid = engine.matrix_price("CORP5Y").unwrap();
+        assert_eq!(from_grid.method, matrix_pricing::MatrixMethod::SpreadGrid);
+        assert_eq!(from_grid.confidence, matrix_pricing::PriceConfidence::Low);
+        assert_eq!(from_grid.yield_pct - from_grid.benchmark_yield_pct, dec!(1.5));
+        assert!(from_grid.price < dec!(100));
+
+        // A comparable trading tighter than the grid pulls the estimate up
+        engine.reference_prices().set_previous_close("CORP7Y", dec!(98), now);
+        let from_comparables = engine.matrix_price("CORP5Y").unwrap();
+        assert_eq!(from_comparables.comparables, vec!["CORP7Y".to_string()]);
+        assert_eq!(from_comparables.confidence, matrix_pricing::PriceConfidence::Medium);
+        assert!(from_comparables.spread_bps < dec!(150) && from_comparables.price > from_grid.price);
+
+        let account = Uuid::new_v4();
+        for (side, owner) in [(OrderSide::Sell, Uuid::new_v4()), (OrderSide::Buy, account)] {
+            let order = Order {
+                symbol: "CORP5Y".to_string(),
+                ..limit_order(side, dec!(1000000), dec!(99), owner)
+            };
+            engine.submit_order(order).await.unwrap();
+        }
+        // Once the last trade is too old to be a reference price
+        clock.advance(chrono::Duration::hours(1));
+        let valuation = engine.value_portfolio(account).await;
+        let position = &valuation.positions[0];
+        assert_eq!(position.matrix_confidence, Some(matrix_pricing::PriceConfidence::Medium));
+        assert_eq!(position.mark_price, engine.matrix_price("CORP5Y").unwrap().price);
+    }
+
+    #[tokio::test]
+    async fn test_full_book_rejects_passive_orders_until_stale_ones_are_purged() {
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()));
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::with_clock(clock.clone())),
+        )
+        .await
+        .unwrap();
+        engine.book_limits().set(
+            "GSEC10Y",
+            book_limits::BookLimits {
+                max_resting_orders: 2,
+                max_bytes: 1024 * 1024,
+            },
+        );
+        let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+        for price in [dec!(99.00), dec!(99.25)] {
+            engine.submit_order(limit_order(OrderSide::Buy, dec!(100000), price, maker)).await.unwrap();
+        }
+        let usage = engine.book_usage("GSEC10Y");
+        assert_eq!((usage.footprint.resting_orders, usage.footprint.bid_levels), (2, 2));
+        assert!(usage.footprint.estimated_bytes > 0 && usage.utilization == Decimal::ONE);
+
+        let passive = engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.50), taker))
+            .await;
+        assert!(matches!(passive, Err(TradingError::BookCapacityExceeded(_))));
+        // Taking liquidity is still allowed on a full book
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.25), taker))
+            .await
+            .unwrap();
+
+        clock.advance(chrono::Duration::hours(2));
+        let fresh = engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(100000), dec!(99.50), taker))
+            .await
+            .unwrap();
+        let purge = engine
+            .purge_stale_orders("GSEC10Y", chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(purge.cancelled.len(), 1);
+        assert_eq!(purge.usage.footprint.resting_orders, 1);
+        assert!(engine.get_order(&fresh).unwrap().is_open());
+    }
+
+    #[tokio::test]
+    async fn test_trades_beyond_memory_window_are_queried_from_store() {
+        let config = Config {
+            trade_memory_capacity: 2,
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        for price in [dec!(99.00), dec!(99.25), dec!(99.50)] {
+            engine
+                .submit_order(limit_order(OrderSide::Sell, dec!(100000), price, seller))
+                .await
+                .unwrap();
+            engine
+                .submit_order(limit_order(OrderSide::Buy, dec!(100000), price, buyer))
+                .await
+                .unwrap();
+        }
+        let recent = engine.get_trades();
+        assert_eq!(recent.len(), 2);
+
+        let window = engine.query_trades(&trade_store::TradeQuery::default()).await.unwrap();
+        assert_eq!(window.len(), 2);
+        let history = trade_store::TradeQuery {
+            from: Some(recent[0].timestamp - chrono::Duration::hours(1)),
+            account_id: Some(buyer),
+            ..trade_store::TradeQuery::default()
+        };
+        let trades = engine.query_trades(&history).await.unwrap();
+        let prices: Vec<Decimal> = trades.iter().map(|trade| trade.price).collect();
+        assert_eq!(prices, vec![dec!(99.00), dec!(99.25), dec!(99.50)]);
+
+        let latest = engine
+            .query_trades(&trade_store::TradeQuery {
+                limit: Some(1),
+                ..history
+            })
+            .await
+            .unwrap();
+        assert_eq!(latest[0].price, dec!(99.50));
+    }
+
+    #[test]
+    fn test_config_validation_lists_every_problem_and_redacts_credentials() {
+        assert!(Config::default().validate().is_empty());
+        assert!(Config::defaults_for("production").validate().is_empty());
+
+        let config = Config {
+            event_channel_size: 0,
+            feed_poll_interval_ms: 0,
+            persistence_backend: "memory".to_string(),
+            environment: "production".to_string(),
+            database_url: "postgresql://trader:secret@db:5432/vedha".to_string(),
+            ..Config::default()
+        };
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|e| e.contains("FEED_POLL_INTERVAL_MS")));
+
+        let redacted = config.redacted();
+        assert_eq!(redacted.database_url, "postgresql://trader:***@db:5432/vedha");
+    }
+
+    #[tokio::test]
+    async fn test_feature_flag_rolls_book_limits_out_to_listed_accounts() {
+        let pilot = Uuid::new_v4();
+        let config = Config {
+            max_resting_orders_per_symbol: 1,
+            feature_flags: HashMap::from([(
+                FeatureFlag::BookCapacityLimits,
+                feature_flags::FlagRollout {
+                    accounts: [pilot].into(),
+                    ..feature_flags::FlagRollout::off()
+                },
+            )]),
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let other = Uuid::nil();
+        for price in [dec!(99.00), dec!(99.25)] {
+            engine.submit_order(limit_order(OrderSide::Buy, dec!(100000), price, other)).await.unwrap();
+        }
+        let rejected = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.75), pilot))
+            .await;
+        assert!(matches!(rejected, Err(TradingError::BookCapacityExceeded(_))));
+
+        engine
+            .feature_flags()
+            .set_override(FeatureFlag::BookCapacityLimits, feature_flags::FlagRollout::off());
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.75), pilot))
+            .await
+            .unwrap();
+        assert!(engine.feature_flags().clear_override(FeatureFlag::BookCapacityLimits));
+
+        let state = engine.feature_flags().state(FeatureFlag::BookCapacityLimits);
+        assert!(!state.overridden && state.rollout.accounts.contains(&pilot));
+        assert_eq!((state.evaluations, state.enabled), (4, 1));
+    }
+
+    #[tokio::test]
+    async fn test_sharded_matching_spreads_symbols_and_migrates() {
+        let config = Config {
+            matching_shards: 2,
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        for symbol in ["GSEC10Y", "GSEC5Y"] {
+            let order = |side, account| Order {
+                symbol: symbol.to_string(),
+                ..limit_order(side, dec!(100000), dec!(99.50), account)
+            };
+            engine.submit_order(order(OrderSide::Sell, seller)).await.unwrap();
+            engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+        }
+        assert_eq!(engine.get_trades().len(), 2);
+
+        let topology = engine.matching_shards().topology();
+        assert_eq!(topology[0].symbols, vec!["GSEC10Y".to_string()]);
+        assert_eq!(topology[1].symbols, vec!["GSEC5Y".to_string()]);
+
+        engine.matching_shards().migrate("GSEC5Y", 0).unwrap();
+        assert_eq!(engine.matching_shards().topology()[0].symbols.len(), 2);
+        assert!(engine.matching_shards().migrate("GSEC5Y", 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_matching_runs_in_the_instruments_tick_scale() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let scale = fixed_point::TickScale {
+            price_decimals: 2,
+            quantity_decimals: 0,
+        };
+        engine.set_tick_scale("GSEC10Y", scale).unwrap();
+
+        let too_fine = engine.submit_order(limit_order(OrderSide::Sell, dec!(100), dec!(99.125), Uuid::new_v4()));
+        assert!(matches!(too_fine.await, Err(TradingError::InvalidOrderField { field: OrderField::Price, .. })));
+        let fractional = engine.submit_order(limit_order(OrderSide::Sell, dec!(100.5), dec!(99.12), Uuid::new_v4()));
+        assert!(matches!(fractional.await, Err(TradingError::InvalidOrderField { field: OrderField::Quantity, .. })));
+
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(300), dec!(99.12), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Sell, dec!(200), dec!(99.13), Uuid::new_v4()))
+            .await
+            .unwrap();
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(400), dec!(99.20), Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        let trades = engine.get_trades();
+        let fills: Vec<(Decimal, Decimal)> = trades.iter().map(|trade| (trade.price, trade.quantity)).collect();
+        assert_eq!(fills, vec![(dec!(99.12), dec!(300)), (dec!(99.13), dec!(100))]);
+        assert_eq!(engine.matching_engine.best_level("GSEC10Y", &OrderSide::Sell), Some((dec!(99.13), dec!(100))));
+
+        // The scale is fixed while anything rests on the book
+        assert!(engine.set_tick_scale("GSEC10Y", fixed_point::TickScale::default()).is_err());
+        assert_eq!(engine.instruments().instrument("GSEC10Y").unwrap().tick_scale, scale);
+    }
+
+    #[tokio::test]
+    async fn test_restored_book_matches_in_the_original_sequence() {
+        let original = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let mut resting = Vec::new();
+        for (quantity, price) in [
+            (dec!(100), dec!(99.50)),
+            (dec!(200), dec!(99.25)),
+            (dec!(300), dec!(99.50)),
+            (dec!(400), dec!(99.25)),
+            (dec!(500), dec!(99.50)),
+        ] {
+            let order = limit_order(OrderSide::Sell, quantity, price, Uuid::new_v4());
+            resting.push(original.submit_order(order).await.unwrap());
+        }
+        // A gap in the priorities and a partly filled head of the queue
+        original.cancel_order(resting[2]).await.unwrap();
+        let partial = limit_order(OrderSide::Buy, dec!(50), dec!(99.25), Uuid::new_v4());
+        original.submit_order(partial).await.unwrap();
+
+        original.save_book_snapshot().await.unwrap();
+        let stored = original.state_store().load_book_snapshot().await.unwrap().unwrap();
+        let json = serde_json::to_string(&stored).unwrap();
+        let restored = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let count = restored
+            .restore_book_snapshot(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(
+            serde_json::to_value(restored.book_snapshot()).unwrap(),
+            serde_json::to_value(original.book_snapshot()).unwrap()
+        );
+        assert!(restored.restore_book_snapshot(stored).is_err());
+
+        // A late order at the best price queues behind everything restored
+        for engine in [&original, &restored] {
+            let late = Order {
+                id: Uuid::from_u128(1),
+                ..limit_order(OrderSide::Sell, dec!(600), dec!(99.25), Uuid::from_u128(2))
+            };
+            engine.submit_order(late).await.unwrap();
+        }
+        for engine in [&original, &restored] {
+            let aggressor = Order {
+                id: Uuid::from_u128(3),
+                ..limit_order(OrderSide::Buy, dec!(1200), dec!(99.50), Uuid::from_u128(4))
+            };
+            engine.submit_order(aggressor).await.unwrap();
+        }
+
+        let fills = |engine: &TradingEngine| -> Vec<(Uuid, Uuid, Decimal, Decimal)> {
+            let trades = engine.get_trades();
+            trades
+                .iter()
+                .filter(|trade| trade.buyer_order_id == Uuid::from_u128(3))
+                .map(|trade| (trade.id, trade.maker_order_id, trade.price, trade.quantity))
+                .collect()
+        };
+        let expected: Vec<(Uuid, Decimal)> = vec![
+            (resting[1], dec!(150)),
+            (resting[3], dec!(400)),
+            (Uuid::from_u128(1), dec!(600)),
+            (resting[0], dec!(50)),
+        ];
+        let original_fills = fills(&original);
+        assert_eq!(
+            original_fills.iter().map(|fill| (fill.1, fill.3)).collect::<Vec<_>>(),
+            expected
+        );
+        // Same makers, prices and trade ids, in the same order
+        assert_eq!(fills(&restored), original_fills);
+    }
+
+    #[tokio::test]
+    async fn test_events_carry_the_clock_grade_they_were_stamped_under() {
+        use crate::utils::clock_sync::{ClockSample, ClockStatus, TimeSource};
+
+        struct Reference(Option<i64>);
+
+        #[async_trait::async_trait]
+        impl TimeSource for Reference {
+            fn name(&self) -> String {
+                "test".to_string()
+            }
+
+            async fn sample(&self) -> anyhow::Result<ClockSample> {
+                let offset_us = self.0.ok_or_else(|| anyhow::anyhow!("unreachable"))?;
+                Ok(ClockSample {
+                    offset_us,
+                    uncertainty_us: 50,
+                    synchronized: true,
+                })
+            }
+        }
+
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let bid = || limit_order(OrderSide::Buy, dec!(100), dec!(99.00), Uuid::new_v4());
+        engine.submit_order(bid()).await.unwrap();
+
+        let alert = engine.check_clock(&Reference(Some(200))).await.unwrap();
+        assert_eq!(alert.status, ClockStatus::Synchronized);
+        assert!(engine.check_clock(&Reference(Some(250))).await.is_none());
+        engine.submit_order(bid()).await.unwrap();
+        // Over the default 1000us bound
+        let alert = engine.check_clock(&Reference(Some(5000))).await.unwrap();
+        assert_eq!(alert.status, ClockStatus::Degraded);
+        engine.check_clock(&Reference(None)).await.unwrap();
+        engine.submit_order(bid()).await.unwrap();
+
+        let stamps: Vec<(bool, ClockStatus, Option<u64>)> = engine
+            .replay_events(1, None, 100)
+            .events
+            .iter()
+            .map(|event| {
+                let alert = matches!(event.event, EngineEvent::ClockQualityChanged(_));
+                (alert, event.clock.status, event.clock.max_error_us)
+            })
+            .collect();
+        assert_eq!(
+            stamps,
+            vec![
+                (false, ClockStatus::Unknown, None),
+                (true, ClockStatus::Synchronized, Some(250)),
+                (false, ClockStatus::Synchronized, Some(300)),
+                (true, ClockStatus::Degraded, Some(5050)),
+                (true, ClockStatus::Unsynchronized, None),
+                (false, ClockStatus::Unsynchronized, None),
+            ]
+        );
+        assert_eq!(engine.clock_report().failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retention_archives_aged_data_and_reads_it_back() {
+        use crate::persistence::archive::DataClass;
+        use retention::ArchiveQuery;
+
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let config = Config {
+            archive_sink: "memory".to_string(),
+            trade_memory_capacity: 1,
+            retention_days: DataClass::ALL.into_iter().map(|class| (class, 30)).collect(),
+            ..Config::default()
+        };
+        let engine = TradingEngine::with_time_provider(
+            Arc::new(config),
+            Arc::new(TimeProvider::with_clock(clock.clone())),
+        )
+        .await
+        .unwrap();
+        let (buyer, seller, resting) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut filled = Vec::new();
+        for _ in 0..2 {
+            for (side, account) in [(OrderSide::Sell, seller), (OrderSide::Buy, buyer)] {
+                let order = limit_order(side, dec!(100), dec!(99.00), account);
+                filled.push(engine.submit_order(order).await.unwrap());
+            }
+        }
+        let open = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100), dec!(98.00), resting))
+            .await
+            .unwrap();
+
+        clock.advance(chrono::Duration::days(31));
+        let recent = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100), dec!(97.00), resting))
+            .await
+            .unwrap();
+        let journaled = engine.replay_events(1, None, 1000).events;
+        let aged = journaled.iter().filter(|event| event.timestamp == start).count();
+        let run = engine.run_retention().await.unwrap();
+        let counts: Vec<(DataClass, usize, usize)> = run
+            .classes
+            .iter()
+            .map(|class| (class.class, class.archived, class.removed))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (DataClass::Orders, 4, 4),
+                // The second trade is still in the in-memory window
+                (DataClass::Trades, 1, 1),
+                (DataClass::Audit, journaled.len(), aged),
+                (DataClass::BookSnapshots, 1, 0),
+            ]
+        );
+        assert!(filled.iter().all(|id| engine.get_order(id).is_none()));
+        assert!(engine.get_order(&open).is_some() && engine.get_order(&recent).is_some());
+        assert_eq!(run.audit_events_missed, 0);
+
+        let trades = engine
+            .query_archive(DataClass::Trades, &ArchiveQuery { account_id: Some(buyer), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].at, start);
+        let orders = engine
+            .query_archive(
+                DataClass::Orders,
+                &ArchiveQuery {
+                    symbol: Some("GSEC10Y".to_string()),
+                    to: Some(start + chrono::Duration::days(1)),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(orders.len(), 4);
+        let audit = engine
+            .query_archive(DataClass::Audit, &ArchiveQuery { account_id: Some(resting), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(audit.len(), 2);
+        let later = ArchiveQuery {
+            from: Some(start + chrono::Duration::days(40)),
+            ..Default::default()
+        };
+        assert!(engine.query_archive(DataClass::Audit, &later).await.unwrap().is_empty());
+        assert_eq!(engine.replay_events(1, None, 1000).events.len(), journaled.len() - aged);
+
+        // Nothing new is due, and only events since the last run are archived
+        let again = engine.run_retention().await.unwrap();
+        assert!(again.classes[..3].iter().all(|class| class.archived == 0));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_orders_carry_a_classified_reason() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_order_value = dec!(1000000);
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let oversized = limit_order(OrderSide::Buy, dec!(5000000), dec!(99.00), account);
+        let too_fine = limit_order(OrderSide::Buy, dec!(100), dec!(99.1234567), account);
+        let mut unknown = limit_order(OrderSide::Buy, dec!(100), dec!(99.00), account);
+        unknown.symbol = String::new();
+        let expected = [
+            RejectReason::RiskLimit {
+                limit: RiskLimitKind::OrderValue,
+            },
+            RejectReason::Validation {
+                field: Some(OrderField::Price),
+            },
+            RejectReason::Validation {
+                field: Some(OrderField::Symbol),
+            },
+        ];
+        for (order, reason) in [oversized, too_fine, unknown].into_iter().zip(expected) {
+            let order_id = order.id;
+            let error = engine.submit_order(order).await.unwrap_err();
+            assert_eq!(error.reject_reason(), reason);
+            assert_eq!(engine.get_order(&order_id).unwrap().status, OrderStatus::Rejected(reason));
+        }
+
+        let events: Vec<RejectReason> = engine
+            .replay_events(1, Some(account), 100)
+            .events
+            .into_iter()
+            .filter_map(|event| match event.event {
+                EngineEvent::OrderRejected { reject_reason, .. } => Some(reject_reason),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(events, expected);
+        let rejects = engine.get_metrics().snapshot().order_rejects;
+        assert_eq!(rejects.get("risk_limit.order_value"), Some(&1));
+        assert_eq!(rejects.get("validation.price"), Some(&1));
+        assert!(engine.get_orders().iter().all(|order| !order.is_open()));
+    }
+
+    #[tokio::test]
+    async fn test_orders_may_name_the_instrument_by_isin_or_cusip() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine
+            .list_instrument(test_bond("GSEC10Y"), MatchingAlgorithm::PriceTime)
+            .unwrap();
+        let cusip = instruments::AliasRequest {
+            alias: "y2000abc1".to_string(),
+            kind: instruments::AliasKind::Cusip,
+        };
+        engine.instruments().add_alias("GSEC10Y", cusip).unwrap();
+        let clash = instruments::AliasRequest {
+            alias: "IN0020230085".to_string(),
+            kind: instruments::AliasKind::Isin,
+        };
+        assert!(engine.instruments().add_alias("GSEC5Y", clash).is_err());
+
+        let mut sell = limit_order(OrderSide::Sell, dec!(100000), dec!(99.00), Uuid::new_v4());
+        sell.symbol = "IN0020230085".to_string();
+        let mut buy = limit_order(OrderSide::Buy, dec!(100000), dec!(99.00), Uuid::new_v4());
+        buy.symbol = "Y2000ABC1".to_string();
+        let sell_id = engine.submit_order(sell).await.unwrap();
+        engine.submit_order(buy).await.unwrap();
+
+        assert_eq!(engine.get_order(&sell_id).unwrap().symbol, "GSEC10Y");
+        assert_eq!(engine.get_trades()[0].symbol, "GSEC10Y");
+        engine.instruments().remove_alias("y2000abc1").unwrap();
+        assert_eq!(engine.instruments().aliases("GSEC10Y").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_lossless_consumer_turns_orders_away() {
+        let config = Config {
+            event_channel_size: 2,
+            event_backpressure_timeout_ms: 50,
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let mut consumer = engine.subscribe_events_with(Delivery::Lossless);
+        let account_id = Uuid::new_v4();
+        for price in [dec!(99.00), dec!(98.75)] {
+            engine.submit_order(limit_order(OrderSide::Buy, dec!(100000), price, account_id)).await.unwrap();
+        }
+
+        let stalled = engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.50), account_id))
+            .await;
+        assert!(matches!(stalled, Err(TradingError::ConsumerStalled { waited_ms: 50 })));
+        assert_eq!(engine.get_metrics().snapshot().consumer_stalls, 1);
+
+        while engine.event_journal.stalled_consumers() > 0 {
+            consumer.recv().await.unwrap();
+        }
+        engine
+            .submit_order(limit_order(OrderSide::Buy, dec!(100000), dec!(98.50), account_id))
+            .await
+            .unwrap();
+    }
 }
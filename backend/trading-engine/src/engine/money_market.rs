@@ -0,0 +1,91 @@
+//! Discount-basis pricing for T-bills and other money-market instruments.
+//! They pay no coupons and accrue nothing; the return is the discount to
+//! face value, stated as a simple yield over the days to maturity.
+
+use crate::types::*;
+use chrono::NaiveDate;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+/// Days in the year used to annualize: 360 on ACT/360, 365 otherwise.
+pub fn year_basis(day_count: DayCount) -> Decimal {
+    match day_count {
+        DayCount::Actual360 => Decimal::from(360),
+        _ => Decimal::from(365),
+    }
+}
+
+pub fn days_to_maturity(bond: &Bond, settlement: NaiveDate) -> i64 {
+    (bond.maturity_date.date_naive() - settlement).num_days()
+}
+
+/// Price per 100 face at a money-market yield in percent.
+pub fn price_at_yield(yield_pct: Decimal, days: i64, basis: Decimal) -> Option<Decimal> {
+    if days <= 0 {
+        return None;
+    }
+    let growth = Decimal::ONE + yield_pct / Decimal::ONE_HUNDRED * Decimal::from(days) / basis;
+    (growth > Decimal::ZERO).then(|| Decimal::ONE_HUNDRED / growth)
+}
+
+/// Return on the price paid, simple-annualized: `(100 - P) / P * basis / days`.
+/// The yield quoted for Indian T-bills.
+pub fn money_market_yield(price: Decimal, days: i64, basis: Decimal) -> Option<Decimal> {
+    if days <= 0 || price <= Decimal::ZERO {
+        return None;
+    }
+    Some((Decimal::ONE_HUNDRED - price) / price * basis / Decimal::from(days) * Decimal::ONE_HUNDRED)
+}
+
+/// Discount to face, simple-annualized: `(100 - P) / 100 * basis / days`.
+pub fn discount_yield(price: Decimal, days: i64, basis: Decimal) -> Option<Decimal> {
+    if days <= 0 {
+        return None;
+    }
+    Some((Decimal::ONE_HUNDRED - price) * basis / Decimal::from(days))
+}
+
+/// Yield comparable with a semi-annual coupon bond. Up to half a year it is
+/// the money-market yield on 365 days; beyond, the bill is treated as
+/// compounding once at the half-year.
+pub fn bond_equivalent_yield(price: Decimal, days: i64) -> Option<Decimal> {
+    if days <= 0 || price <= Decimal::ZERO {
+        return None;
+    }
+    if days <= 182 {
+        return money_market_yield(price, days, Decimal::from(365));
+    }
+    let t = days as f64 / 365.0;
+    let p = price.to_f64()?;
+    let a = t / 2.0 - 0.25;
+    let discriminant = t * t - 4.0 * a * (1.0 - 100.0 / p);
+    let rate = (-t + discriminant.sqrt()) / (2.0 * a);
+    Decimal::from_f64_retain(rate * 100.0).map(|rate| rate.round_dp(6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bill_yields_round_trip_price() {
+        let basis = year_basis(DayCount::Actual365Fixed);
+
+        // A 91-day bill at 98.30
+        let mmy = money_market_yield(dec!(98.30), 91, basis).unwrap();
+        assert_eq!(mmy.round_dp(4), dec!(6.9366));
+        assert_eq!(price_at_yield(mmy, 91, basis).unwrap().round_dp(10), dec!(98.30));
+        assert_eq!(discount_yield(dec!(98.30), 91, basis).unwrap().round_dp(4), dec!(6.8187));
+        assert_eq!(bond_equivalent_yield(dec!(98.30), 91), Some(mmy));
+
+        // A 364-day bill: compounding at the half-year lowers the equivalent yield
+        let mmy = money_market_yield(dec!(93.50), 364, basis).unwrap();
+        let bey = bond_equivalent_yield(dec!(93.50), 364).unwrap();
+        assert!(bey < mmy);
+        let half = bey / dec!(200);
+        let grown = dec!(93.50) * (Decimal::ONE + half) * (Decimal::ONE + half * (dec!(364) / dec!(365) - dec!(0.5)) * dec!(2));
+        assert!((grown - dec!(100)).abs() < dec!(0.0001));
+
+        assert!(money_market_yield(dec!(99), 0, basis).is_none());
+    }
+}
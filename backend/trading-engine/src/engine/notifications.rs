@@ -0,0 +1,370 @@
+use crate::{
+    engine::{event_journal::SequencedEvent, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tracing::info;
+use uuid::Uuid;
+
+/// Where a user's notifications go. Email has no transport yet; those
+/// deliveries are logged and marked skipped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "channel", rename_all = "snake_case")]
+pub enum DeliveryTarget {
+    Webhook { url: String },
+    Email { address: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationFilters {
+    /// Fills on the account at or above this quantity. Unset turns fill
+    /// notifications off.
+    #[serde(default)]
+    pub fills_min_quantity: Option<Decimal>,
+    #[serde(default)]
+    pub risk_violations: bool,
+    /// Instrument status changes: suspensions, auctions, delistings.
+    #[serde(default)]
+    pub session_events: bool,
+    /// Rating changes, coupon revisions and redemptions on any instrument.
+    #[serde(default)]
+    pub corporate_actions: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSubscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub target: DeliveryTarget,
+    pub filters: NotificationFilters,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscriptionRequest {
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub target: DeliveryTarget,
+    #[serde(default)]
+    pub filters: NotificationFilters,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Fill,
+    RiskViolation,
+    SessionEvent,
+    CorporateAction,
+}
+
+/// Body sent to a delivery target.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub delivery_id: Uuid,
+    pub subscription_id: Uuid,
+    pub kind: NotificationKind,
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event: EngineEvent,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryRecord {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub seq: u64,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliveryFilter {
+    pub user_id: Option<Uuid>,
+    pub subscription_id: Option<Uuid>,
+    pub status: Option<DeliveryStatus>,
+}
+
+/// Notification subscriptions and the status of recent deliveries. Only
+/// the last `history` deliveries are kept.
+pub struct NotificationCenter {
+    subscriptions: DashMap<Uuid, NotificationSubscription>,
+    deliveries: DashMap<Uuid, DeliveryRecord>,
+    delivery_order: Mutex<VecDeque<Uuid>>,
+    history: usize,
+}
+
+impl NotificationCenter {
+    pub fn new(history: usize) -> Self {
+        Self {
+            subscriptions: DashMap::new(),
+            deliveries: DashMap::new(),
+            delivery_order: Mutex::new(VecDeque::new()),
+            history,
+        }
+    }
+
+    pub fn subscriptions(&self, user_id: Option<Uuid>) -> Vec<NotificationSubscription> {
+        let mut subscriptions: Vec<NotificationSubscription> = self
+            .subscriptions
+            .iter()
+            .filter(|entry| user_id.is_none_or(|id| entry.user_id == id))
+            .map(|entry| entry.value().clone())
+            .collect();
+        subscriptions.sort_by_key(|subscription| subscription.created_at);
+        subscriptions
+    }
+
+    /// Subscriptions that want `event`, with the kind of notification each
+    /// should receive.
+    pub fn matching(&self, event: &SequencedEvent) -> Vec<(NotificationSubscription, NotificationKind)> {
+        let wants = |subscription: &NotificationSubscription| -> Option<NotificationKind> {
+            let filters = &subscription.filters;
+            match &event.event {
+                EngineEvent::OrderFilled { trade, .. } => (event.concerns(subscription.account_id)
+                    && filters.fills_min_quantity.is_some_and(|min| trade.quantity >= min))
+                .then_some(NotificationKind::Fill),
+                EngineEvent::RiskViolation { account_id, .. } | EngineEvent::RiskWarning { account_id, .. } => {
+                    (filters.risk_violations && *account_id == subscription.account_id)
+                        .then_some(NotificationKind::RiskViolation)
+                }
+                EngineEvent::InstrumentStatusChanged { .. } => {
+                    filters.session_events.then_some(NotificationKind::SessionEvent)
+                }
+                EngineEvent::CorporateActionApplied(_) => {
+                    filters.corporate_actions.then_some(NotificationKind::CorporateAction)
+                }
+                _ => None,
+            }
+        };
+
+        self.subscriptions
+            .iter()
+            .filter_map(|entry| wants(entry.value()).map(|kind| (entry.value().clone(), kind)))
+            .collect()
+    }
+
+    pub fn record_pending(
+        &self,
+        subscription: &NotificationSubscription,
+        kind: NotificationKind,
+        seq: u64,
+        now: DateTime<Utc>,
+    ) -> DeliveryRecord {
+        let record = DeliveryRecord {
+            id: Uuid::new_v4(),
+            subscription_id: subscription.id,
+            user_id: subscription.user_id,
+            kind,
+            seq,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.deliveries.insert(record.id, record.clone());
+
+        let mut order = self.delivery_order.lock();
+        order.push_back(record.id);
+        while order.len() > self.history {
+            if let Some(evicted) = order.pop_front() {
+                self.deliveries.remove(&evicted);
+            }
+        }
+        record
+    }
+
+    /// Records the outcome of one delivery attempt.
+    pub fn record_attempt(
+        &self,
+        delivery_id: Uuid,
+        status: DeliveryStatus,
+        error: Option<String>,
+        now: DateTime<Utc>,
+    ) {
+        if let Some(mut record) = self.deliveries.get_mut(&delivery_id) {
+            record.attempts += 1;
+            record.status = status;
+            record.last_error = error.or(record.last_error.take());
+            record.updated_at = now;
+        }
+    }
+
+    pub fn delivery(&self, delivery_id: Uuid) -> Option<DeliveryRecord> {
+        self.deliveries.get(&delivery_id).map(|record| record.clone())
+    }
+
+    pub fn deliveries(&self, filter: &DeliveryFilter) -> Vec<DeliveryRecord> {
+        let mut deliveries: Vec<DeliveryRecord> = self
+            .deliveries
+            .iter()
+            .filter(|entry| {
+                let record = entry.value();
+                filter.user_id.is_none_or(|id| record.user_id == id)
+                    && filter.subscription_id.is_none_or(|id| record.subscription_id == id)
+                    && filter.status.is_none_or(|status| record.status == status)
+            })
+            .map(|entry| entry.value().clone())
+            .collect();
+        deliveries.sort_by_key(|record| std::cmp::Reverse(record.created_at));
+        deliveries
+    }
+}
+
+fn validate_target(target: &DeliveryTarget) -> crate::types::Result<()> {
+    match target {
+        DeliveryTarget::Webhook { url } if !(url.starts_with("https://") || url.starts_with("http://")) => Err(
+            TradingError::InvalidRequest("Webhook URL must be http or https".to_string()),
+        ),
+        DeliveryTarget::Email { address } if !address.contains('@') => {
+            Err(TradingError::InvalidRequest("Invalid email address".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl TradingEngine {
+    pub fn notifications(&self) -> &NotificationCenter {
+        &self.notifications
+    }
+
+    pub async fn subscribe_notifications(
+        &self,
+        request: SubscriptionRequest,
+    ) -> crate::types::Result<NotificationSubscription> {
+        validate_target(&request.target)?;
+        let subscription = NotificationSubscription {
+            id: Uuid::new_v4(),
+            user_id: request.user_id,
+            account_id: request.account_id,
+            target: request.target,
+            filters: request.filters,
+            created_at: self.time_provider.now(),
+        };
+        self.state_store
+            .save_notification_subscription(&subscription)
+            .await?;
+        self.notifications
+            .subscriptions
+            .insert(subscription.id, subscription.clone());
+        Ok(subscription)
+    }
+
+    pub async fn unsubscribe_notifications(&self, subscription_id: Uuid) -> crate::types::Result<bool> {
+        self.state_store
+            .delete_notification_subscription(subscription_id)
+            .await?;
+        Ok(self.notifications.subscriptions.remove(&subscription_id).is_some())
+    }
+
+    /// Restores stored subscriptions, typically at startup.
+    pub async fn load_notification_subscriptions(&self) -> crate::types::Result<usize> {
+        let subscriptions = self.state_store.load_notification_subscriptions().await?;
+        let count = subscriptions.len();
+        for subscription in subscriptions {
+            self.notifications
+                .subscriptions
+                .insert(subscription.id, subscription);
+        }
+        info!("Loaded {} notification subscriptions", count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_subscriptions_persist_and_filter_fills_by_size() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (user, account) = (Uuid::new_v4(), Uuid::new_v4());
+        let subscription = engine
+            .subscribe_notifications(SubscriptionRequest {
+                user_id: user,
+                account_id: account,
+                target: DeliveryTarget::Webhook {
+                    url: "https://oms.example.com/fills".to_string(),
+                },
+                filters: NotificationFilters {
+                    fills_min_quantity: Some(dec!(500000)),
+                    risk_violations: true,
+                    session_events: false,
+                    corporate_actions: false,
+                },
+            })
+            .await
+            .unwrap();
+        let bad_target = SubscriptionRequest {
+            user_id: user,
+            account_id: account,
+            target: DeliveryTarget::Email {
+                address: "desk".to_string(),
+            },
+            filters: NotificationFilters::default(),
+        };
+        assert!(engine.subscribe_notifications(bad_target).await.is_err());
+
+        let stored = engine.state_store().load_notification_subscriptions().await.unwrap();
+        assert_eq!(stored.len(), 1);
+
+        let order = OrderBuilder::new(OrderSide::Buy)
+            .quantity(dec!(1000000))
+            .price(dec!(99.00))
+            .account(account)
+            .user(user)
+            .build();
+        let fill = |quantity: Decimal| {
+            let seller = Order {
+                id: Uuid::new_v4(),
+                account_id: Uuid::new_v4(),
+                side: OrderSide::Sell,
+                ..order.clone()
+            };
+            let trade = engine.matching_engine.build_trade(&order, &seller, quantity, dec!(99.00));
+            SequencedEvent {
+                seq: 1,
+                timestamp: Utc::now(),
+                clock: Default::default(),
+                account_ids: vec![account],
+                event: EngineEvent::OrderFilled {
+                    order_id: order.id,
+                    trade,
+                },
+            }
+        };
+
+        assert!(engine.notifications().matching(&fill(dec!(100000))).is_empty());
+        let matched = engine.notifications().matching(&fill(dec!(500000)));
+        assert_eq!(matched.len(), 1);
+        assert_eq!((matched[0].0.id, matched[0].1), (subscription.id, NotificationKind::Fill));
+
+        assert!(engine.unsubscribe_notifications(subscription.id).await.unwrap());
+        assert!(engine.state_store().load_notification_subscriptions().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! Pull-based sync for order management systems that cannot hold a stream
+//! open. A client polls with the cursor it was last given and gets the
+//! orders, trades and positions changed since, read off the event journal.
+//! Orders and positions come as their current state, so a page carries each
+//! at most once; trades come as they happened.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum SyncRecord {
+    Order(Order),
+    Trade(Trade),
+    Position(Position),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncChange {
+    /// The journal event behind the change.
+    pub seq: u64,
+    #[serde(flatten)]
+    pub record: SyncRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncChanges {
+    pub changes: Vec<SyncChange>,
+    /// Pass back as `since_seq` on the next poll.
+    pub next_cursor: u64,
+    /// Changes past `next_cursor` are already waiting.
+    pub has_more: bool,
+    /// Events after `since_seq` have left the journal; the client must
+    /// reload orders and positions in full before carrying on from
+    /// `next_cursor`.
+    pub resync_required: bool,
+}
+
+impl TradingEngine {
+    /// Changes after `since_seq`, reading at most `limit` journal events.
+    /// With an account, only that account's orders, trades and positions.
+    pub async fn sync_changes(&self, since_seq: u64, account_id: Option<Uuid>, limit: usize) -> SyncChanges {
+        let replay = self.event_journal.replay(since_seq + 1, account_id, limit);
+        let next_cursor = replay.next_seq - 1;
+
+        let mut changes = Vec::new();
+        for event in &replay.events {
+            let seq = event.seq;
+            match &event.event {
+                EngineEvent::OrderSubmitted(order) => {
+                    let order = self.get_order(&order.id).unwrap_or_else(|| order.clone());
+                    changes.push(SyncChange {
+                        seq,
+                        record: SyncRecord::Order(order),
+                    });
+                }
+                EngineEvent::OrderApprovalUpdated(staged) => {
+                    let order = self.get_order(&staged.order.id).unwrap_or_else(|| staged.order.clone());
+                    changes.push(SyncChange {
+                        seq,
+                        record: SyncRecord::Order(order),
+                    });
+                }
+                EngineEvent::OrderCancelled { order_id, .. }
+                | EngineEvent::OrderExpired { order_id, .. }
+                | EngineEvent::OrderFilled { order_id, .. }
+                | EngineEvent::OrderRejected { order_id, .. }
+                | EngineEvent::OrderReduced { order_id, .. } => {
+                    // Orders of a delisted symbol have moved to its archive
+                    if let Some(order) = self.get_order(order_id) {
+                        changes.push(SyncChange {
+                            seq,
+                            record: SyncRecord::Order(order),
+                        });
+                    }
+                }
+                EngineEvent::TradeExecuted(trade) => {
+                    changes.push(SyncChange {
+                        seq,
+                        record: SyncRecord::Trade(trade.clone()),
+                    });
+                    for account in [trade.buyer_account_id, trade.seller_account_id] {
+                        if account_id.is_some_and(|id| id != account) {
+                            continue;
+                        }
+                        if let Some(position) = self.position_manager.get_position(account, &trade.symbol).await {
+                            changes.push(SyncChange {
+                                seq,
+                                record: SyncRecord::Position(position),
+                            });
+                        }
+                    }
+                }
+                EngineEvent::PositionUpdated(position) => {
+                    let position = self
+                        .position_manager
+                        .get_position(position.account_id, &position.symbol)
+                        .await
+                        .unwrap_or_else(|| position.clone());
+                    changes.push(SyncChange {
+                        seq,
+                        record: SyncRecord::Position(position),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        SyncChanges {
+            changes: latest_only(changes),
+            next_cursor,
+            has_more: next_cursor < self.event_journal.last_seq(),
+            resync_required: replay.truncated,
+        }
+    }
+}
+
+/// Keeps the last change of each order and position, which already holds
+/// its state as of the poll, and every trade.
+fn latest_only(changes: Vec<SyncChange>) -> Vec<SyncChange> {
+    let mut orders = HashSet::new();
+    let mut positions = HashSet::new();
+    let mut kept: Vec<SyncChange> = changes
+        .into_iter()
+        .rev()
+        .filter(|change| match &change.record {
+            SyncRecord::Order(order) => orders.insert(order.id),
+            SyncRecord::Position(position) => positions.insert((position.account_id, position.symbol.clone())),
+            SyncRecord::Trade(_) => true,
+        })
+        .collect();
+    kept.reverse();
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, quantity: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_polls_pick_up_where_the_cursor_left_off() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        let ask = engine.submit_order(order(OrderSide::Sell, dec!(200000), seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(100000), buyer)).await.unwrap();
+
+        let first = engine.sync_changes(0, Some(seller), 1000).await;
+        assert!(!first.has_more && !first.resync_required);
+        let kinds: Vec<&str> = first
+            .changes
+            .iter()
+            .map(|change| match &change.record {
+                SyncRecord::Order(_) => "order",
+                SyncRecord::Trade(_) => "trade",
+                SyncRecord::Position(_) => "position",
+            })
+            .collect();
+        // The ask's fill and acceptance collapse into its latest state
+        assert_eq!(kinds, vec!["trade", "position", "order"]);
+        let SyncRecord::Order(latest) = &first.changes[2].record else { unreachable!() };
+        assert_eq!(latest.remaining_quantity, dec!(100000));
+
+        engine.cancel_order(ask).await.unwrap();
+        let second = engine.sync_changes(first.next_cursor, Some(seller), 1000).await;
+        assert_eq!(second.changes.len(), 1);
+        let SyncRecord::Order(cancelled) = &second.changes[0].record else { unreachable!() };
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+
+        let paged = engine.sync_changes(0, None, 1).await;
+        assert!(paged.has_more);
+        assert_eq!(paged.next_cursor, 1);
+    }
+}
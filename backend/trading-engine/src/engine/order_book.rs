@@ -1,19 +1,18 @@
-use crate::types::*;
+use crate::{types::*, utils::time::TimeProvider};
 use dashmap::DashMap;
 use parking_lot::RwLock;
-use rust_decimal::Decimal;
-use std::collections::BTreeMap;
 use std::sync::Arc;
-use uuid::Uuid;
 
 pub struct OrderBookManager {
     order_books: Arc<DashMap<String, Arc<RwLock<OrderBook>>>>,
+    time_provider: Arc<TimeProvider>,
 }
 
 impl OrderBookManager {
-    pub fn new(config: Arc<crate::config::Config>) -> Self {
+    pub fn new(_config: Arc<crate::config::Config>, time_provider: Arc<TimeProvider>) -> Self {
         Self {
             order_books: Arc::new(DashMap::new()),
+            time_provider,
         }
     }
 
@@ -28,7 +27,7 @@ impl OrderBookManager {
             symbol: symbol.clone(),
             bids,
             asks,
-            last_update: chrono::Utc::now(),
+            last_update: self.time_provider.now(),
         };
 
         match self.order_books.get(&symbol) {
@@ -41,35 +40,7 @@ impl OrderBookManager {
         }
     }
 
-    pub fn get_best_bid(&self, symbol: &str) -> Option<Decimal> {
-        self.order_books
-            .get(symbol)?
-            .read()
-            .bids
-            .first()
-            .map(|level| level.price)
-    }
-
-    pub fn get_best_ask(&self, symbol: &str) -> Option<Decimal> {
-        self.order_books
-            .get(symbol)?
-            .read()
-            .asks
-            .first()
-            .map(|level| level.price)
-    }
-
-    pub fn get_spread(&self, symbol: &str) -> Option<Decimal> {
-        let book = self.order_books.get(symbol)?.read();
-        let best_bid = book.bids.first()?.price;
-        let best_ask = book.asks.first()?.price;
-        Some(best_ask - best_bid)
-    }
-
-    pub fn get_market_depth(&self, symbol: &str, levels: usize) -> Option<(Vec<PriceLevel>, Vec<PriceLevel>)> {
-        let book = self.order_books.get(symbol)?.read();
-        let bids = book.bids.iter().take(levels).cloned().collect();
-        let asks = book.asks.iter().take(levels).cloned().collect();
-        Some((bids, asks))
+    pub fn remove_orderbook(&self, symbol: &str) {
+        self.order_books.remove(symbol);
     }
 }
@@ -0,0 +1,285 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Json,
+}
+
+impl ImportFormat {
+    /// CSV for `text/csv` bodies, JSON otherwise.
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        match content_type {
+            Some(content_type) if content_type.starts_with("text/csv") => ImportFormat::Csv,
+            _ => ImportFormat::Json,
+        }
+    }
+}
+
+/// One order in an import file. Enumerations are plain names so the same
+/// shape works as a CSV header row and as JSON objects; `expiry` goes with
+/// a `GoodTillDate` order type or a `GoodTillDate` or `GoodTillTime` time
+/// in force.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRow {
+    pub client_order_id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: String,
+    pub quantity: Decimal,
+    pub price: Option<Decimal>,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub time_in_force: Option<String>,
+    pub expiry: Option<DateTime<Utc>>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub strategy_id: Option<String>,
+}
+
+impl ImportRow {
+    fn into_order(self, now: DateTime<Utc>) -> std::result::Result<Order, String> {
+        let order_type = match self.order_type.as_str() {
+            "Limit" => OrderType::Limit,
+            "Market" => OrderType::Market,
+            "PostOnly" => OrderType::PostOnly,
+            "GoodTillDate" => OrderType::GoodTillDate {
+                expiry: self.expiry.ok_or("GoodTillDate orders need an expiry")?,
+            },
+            other => return Err(format!("Unsupported order type {}", other)),
+        };
+        let time_in_force = match self.time_in_force.as_deref().unwrap_or("") {
+            "" | "GoodTillCancel" => TimeInForce::GoodTillCancel,
+            "GoodForDay" => TimeInForce::GoodForDay,
+            "ImmediateOrCancel" => TimeInForce::ImmediateOrCancel,
+            "FillOrKill" => TimeInForce::FillOrKill,
+            "GoodTillDate" => TimeInForce::GoodTillDate(
+                self.expiry.ok_or("GoodTillDate orders need an expiry")?,
+            ),
+            "GoodTillTime" => TimeInForce::GoodTillTime(
+                self.expiry.ok_or("GoodTillTime orders need an expiry")?,
+            ),
+            other => return Err(format!("Unsupported time in force {}", other)),
+        };
+
+        let order = Order {
+            id: Uuid::new_v4(),
+            client_order_id: self.client_order_id,
+            symbol: self.symbol,
+            side: self.side,
+            order_type,
+            quantity: self.quantity,
+            price: self.price,
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: self.quantity,
+            status: OrderStatus::Pending,
+            timestamp: self.timestamp.unwrap_or(now).min(now),
+            user_id: self.user_id,
+            account_id: self.account_id,
+            time_in_force,
+            metadata: HashMap::from([("imported".to_string(), "true".to_string())]),
+            strategy_id: self.strategy_id,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        };
+        if order.expires_at().is_some_and(|expiry| expiry <= now) {
+            return Err("Order has already expired".to_string());
+        }
+        Ok(order)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum ImportRowStatus {
+    Accepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRowResult {
+    /// 1-based position among the data rows of the file.
+    pub row: usize,
+    pub client_order_id: Option<String>,
+    pub status: ImportRowStatus,
+    pub order_id: Option<Uuid>,
+    pub order_status: Option<OrderStatus>,
+    pub error: Option<String>,
+}
+
+impl ImportRowResult {
+    fn rejected(row: usize, client_order_id: Option<String>, error: String) -> Self {
+        Self {
+            row,
+            client_order_id,
+            status: ImportRowStatus::Rejected,
+            order_id: None,
+            order_status: None,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub total: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub results: Vec<ImportRowResult>,
+}
+
+/// Splits an import body into rows. Rows that fail to parse are returned as
+/// errors so the rest of the file can still be loaded; only a body that is
+/// not a CSV table or JSON array fails as a whole.
+fn parse_rows(
+    format: ImportFormat,
+    body: &str,
+) -> crate::types::Result<Vec<std::result::Result<ImportRow, String>>> {
+    match format {
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes());
+            Ok(reader
+                .deserialize::<ImportRow>()
+                .map(|row| row.map_err(|e| e.to_string()))
+                .collect())
+        }
+        ImportFormat::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_str(body)
+                .map_err(|e| TradingError::InvalidRequest(format!("Expected a JSON array of orders: {}", e)))?;
+            Ok(values
+                .into_iter()
+                .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+                .collect())
+        }
+    }
+}
+
+impl TradingEngine {
+    /// Loads a batch of orders, typically resting GTC orders migrated from
+    /// another venue. Each row is validated and submitted on its own. Rows
+    /// are submitted in order of their original timestamps, which they
+    /// keep, so they queue in their original priority relative to each
+    /// other; they still rank behind orders already on the book.
+    pub async fn import_orders(&self, format: ImportFormat, body: &str) -> crate::types::Result<ImportReport> {
+        let rows = parse_rows(format, body)?;
+        if rows.len() > self.config.order_import_max_rows {
+            return Err(TradingError::InvalidRequest(format!(
+                "Import has {} rows, the limit is {}",
+                rows.len(),
+                self.config.order_import_max_rows
+            )));
+        }
+
+        let now = self.time_provider.now();
+        let mut results = Vec::with_capacity(rows.len());
+        let mut pending = Vec::new();
+        let mut client_order_ids = HashSet::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let row_number = index + 1;
+            let row = match row {
+                Ok(row) => row,
+                Err(error) => {
+                    results.push(ImportRowResult::rejected(row_number, None, error));
+                    continue;
+                }
+            };
+
+            let client_order_id = row.client_order_id.clone();
+            if !client_order_ids.insert(client_order_id.clone()) {
+                results.push(ImportRowResult::rejected(
+                    row_number,
+                    Some(client_order_id),
+                    "Duplicate client order id in import".to_string(),
+                ));
+                continue;
+            }
+            match row.into_order(now) {
+                Ok(order) => pending.push((row_number, order)),
+                Err(error) => results.push(ImportRowResult::rejected(row_number, Some(client_order_id), error)),
+            }
+        }
+
+        pending.sort_by_key(|(_, order)| order.timestamp);
+        for (row_number, order) in pending {
+            let client_order_id = Some(order.client_order_id.clone());
+            let timestamp = order.timestamp;
+            let result = match self.submit_order_at(order, timestamp).await {
+                Ok(order_id) => ImportRowResult {
+                    row: row_number,
+                    client_order_id,
+                    status: ImportRowStatus::Accepted,
+                    order_id: Some(order_id),
+                    order_status: self.get_order(&order_id).map(|order| order.status),
+                    error: None,
+                },
+                Err(e) => ImportRowResult::rejected(row_number, client_order_id, e.to_string()),
+            };
+            results.push(result);
+        }
+        results.sort_by_key(|result| result.row);
+
+        let accepted = results
+            .iter()
+            .filter(|result| result.status == ImportRowStatus::Accepted)
+            .count();
+        info!("Imported {} of {} orders", accepted, results.len());
+        Ok(ImportReport {
+            total: results.len(),
+            accepted,
+            rejected: results.len() - accepted,
+            results,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_csv_import_keeps_original_priority_and_reports_bad_rows() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (early, late) = (Uuid::new_v4(), Uuid::new_v4());
+        let user = Uuid::new_v4();
+        let csv = format!(
+            "client_order_id,symbol,side,order_type,quantity,price,user_id,account_id,time_in_force,expiry,timestamp,strategy_id\n\
+             MIG-2,GSEC10Y,Buy,Limit,100000,99.00,{user},{late},,,2024-03-01T10:05:00Z,\n\
+             MIG-1,GSEC10Y,Buy,Limit,100000,99.00,{user},{early},GoodTillCancel,,2024-03-01T10:00:00Z,\n\
+             MIG-3,GSEC10Y,Buy,Limit,100000,,{user},{early},,,,\n\
+             MIG-4,GSEC10Y,Hold,Limit,100000,99.00,{user},{early},,,,\n"
+        );
+
+        let report = engine.import_orders(ImportFormat::Csv, &csv).await.unwrap();
+        assert_eq!((report.total, report.accepted, report.rejected), (4, 2, 2));
+        assert_eq!(report.results[2].status, ImportRowStatus::Rejected);
+        assert_eq!(report.results[3].client_order_id, None);
+
+        let seller = Uuid::new_v4();
+        engine
+            .submit_order(
+                OrderBuilder::new(OrderSide::Sell)
+                    .price(dec!(99.00))
+                    .account(seller)
+                    .user(user)
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        // MIG-1 was entered first on the old venue, so it fills first
+        let trades = engine.get_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buyer_account_id, early);
+    }
+}
@@ -0,0 +1,195 @@
+//! Dry run of order entry: the validators, risk limits and clearing checks
+//! an order would meet, with the time in force and expiry it would carry
+//! and its projected effect on margin and limits. Nothing is recorded,
+//! counted or published.
+
+use crate::{
+    engine::{
+        what_if::{HypotheticalOrder, LimitUtilization, WhatIfRequest},
+        TradingEngine,
+    },
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarginImpact {
+    pub collateral: Decimal,
+    pub requirement_before: Decimal,
+    pub requirement_after: Decimal,
+    pub utilization_before: Option<Decimal>,
+    pub utilization_after: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderPreview {
+    pub accepted: bool,
+    /// The validator, or `risk` or `clearing`, that would turn the order
+    /// away.
+    pub rejected_by: Option<&'static str>,
+    pub rejection: Option<String>,
+    /// The instrument's symbol, where the order named it by ISIN or CUSIP.
+    pub symbol: String,
+    /// The clean price the order would rest at.
+    pub price: Option<Decimal>,
+    pub time_in_force: TimeInForce,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The order would wait for a supervisor's approval before trading.
+    pub requires_approval: bool,
+    /// Soft limits the order passes only on its override.
+    pub risk_warnings: Vec<String>,
+    /// Set for accounts under a liquidation policy.
+    pub margin: Option<MarginImpact>,
+    /// Limit utilization before and after a full fill, where the order can
+    /// be priced.
+    pub limits: Vec<LimitUtilization>,
+}
+
+/// The time in force an order trades under once its type is taken into
+/// account: order types that carry their own lifetime override the field,
+/// and market orders never rest.
+pub fn normalized_time_in_force(order: &Order) -> TimeInForce {
+    match &order.order_type {
+        OrderType::GoodTillDate { expiry } => TimeInForce::GoodTillDate(*expiry),
+        OrderType::Market | OrderType::ImmediateOrCancel => TimeInForce::ImmediateOrCancel,
+        OrderType::FillOrKill => TimeInForce::FillOrKill,
+        _ => order.time_in_force.clone(),
+    }
+}
+
+impl TradingEngine {
+    /// What submitting `order` now would do.
+    pub async fn preview_order(&self, mut order: Order) -> Result<OrderPreview> {
+        order.symbol = self.instruments.canonical(&order.symbol);
+        order.timestamp = self.time_provider.now();
+        order.remaining_quantity = order.quantity;
+
+        let mut risk_warnings = Vec::new();
+        let failure = match self.first_validation_failure(&order).await {
+            Some(failure) => Some(failure),
+            None => match self.check_risk(&order).await {
+                Err(e) => Some(("risk", e)),
+                Ok(check) => {
+                    risk_warnings = check.warnings;
+                    if self.clearing.enabled() {
+                        self.check_clearing_exposure(&order).err().map(|e| ("clearing", e))
+                    } else {
+                        None
+                    }
+                }
+            },
+        };
+
+        self.normalize_order_price(&mut order);
+        order.time_in_force = normalized_time_in_force(&order);
+        let (margin, limits) = match failure {
+            None => self.projected_impact(&order).await,
+            Some(_) => (None, Vec::new()),
+        };
+        Ok(OrderPreview {
+            accepted: failure.is_none(),
+            rejected_by: failure.as_ref().map(|(name, _)| *name),
+            rejection: failure.map(|(_, e)| e.to_string()),
+            requires_approval: self.approvals.requires_approval(order.account_id),
+            expires_at: order.expires_at(),
+            symbol: order.symbol,
+            price: order.price,
+            time_in_force: order.time_in_force,
+            risk_warnings,
+            margin,
+            limits,
+        })
+    }
+
+    /// Margin and limit utilization were the order filled in full at its
+    /// price, or at the reference price for market orders.
+    async fn projected_impact(&self, order: &Order) -> (Option<MarginImpact>, Vec<LimitUtilization>) {
+        let request = WhatIfRequest {
+            account_id: order.account_id,
+            orders: vec![HypotheticalOrder {
+                symbol: order.symbol.clone(),
+                side: order.side.clone(),
+                quantity: order.quantity,
+                price: order.price,
+            }],
+            shock: Default::default(),
+        };
+        let Ok(report) = self.what_if(&request).await else {
+            return (None, Vec::new());
+        };
+
+        let margin = match (self.liquidations.policy(order.account_id), self.margin_status(order.account_id).await) {
+            (Some(policy), Some(status)) => {
+                let change: Decimal = report
+                    .positions
+                    .iter()
+                    .map(|position| position.market_value_after.abs() - position.market_value_before.abs())
+                    .sum();
+                let requirement_after = (status.requirement + change * policy.margin_rate).max(Decimal::ZERO);
+                let utilization = |requirement: Decimal| {
+                    (status.collateral > Decimal::ZERO).then(|| (requirement / status.collateral).round_dp(6))
+                };
+                Some(MarginImpact {
+                    collateral: status.collateral,
+                    requirement_before: status.requirement,
+                    requirement_after,
+                    utilization_before: utilization(status.requirement),
+                    utilization_after: utilization(requirement_after),
+                })
+            }
+            _ => None,
+        };
+        (margin, report.limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn order(quantity: Decimal, order_type: OrderType, account_id: Uuid) -> Order {
+        OrderBuilder::new(OrderSide::Buy)
+            .order_type(order_type)
+            .quantity(quantity)
+            .account(account_id)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_preview_reports_outcome_without_submitting() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account_id = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account_id).await.unwrap();
+        limits.max_position_size = dec!(2000000);
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        let expiry = Utc::now() + chrono::Duration::hours(4);
+        let preview = engine
+            .preview_order(order(dec!(500000), OrderType::GoodTillDate { expiry }, account_id))
+            .await
+            .unwrap();
+        assert!(preview.accepted);
+        assert_eq!(preview.time_in_force, TimeInForce::GoodTillDate(expiry));
+        assert_eq!(preview.expires_at, Some(expiry));
+        let position = preview.limits.iter().find(|limit| limit.limit == "max_position_size").unwrap();
+        assert_eq!((position.before, position.after), (dec!(0), dec!(500000)));
+        assert_eq!(position.utilization_after, Some(dec!(0.25)));
+
+        let preview = engine
+            .preview_order(order(dec!(5000000), OrderType::Limit, account_id))
+            .await
+            .unwrap();
+        assert!(!preview.accepted);
+        assert_eq!(preview.rejected_by, Some("risk"));
+
+        assert!(engine.get_orders().is_empty());
+        assert_eq!(engine.risk_manager().activity(account_id).messages, 0);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    engine::{account_stats::AccountTradingStats, instruments::InstrumentStatus, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const RATE_WINDOWS_MINUTES: [i64; 3] = [1, 5, 15];
+const TOP_ACCOUNTS: usize = 10;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SymbolOverview {
+    pub symbol: String,
+    pub resting_orders: usize,
+    pub resting_bid_quantity: Decimal,
+    pub resting_ask_quantity: Decimal,
+    /// Sum of long positions, which equals the sum of short positions.
+    pub open_interest: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityRate {
+    pub window_minutes: i64,
+    pub orders: usize,
+    pub trades: usize,
+    pub orders_per_second: Decimal,
+    pub trades_per_second: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemHealth {
+    pub persistence_backend: String,
+    pub last_event_seq: u64,
+    pub event_subscribers: usize,
+    pub scheduled_expiries: usize,
+    pub clearing_enabled: bool,
+    pub instruments_by_status: BTreeMap<String, usize>,
+}
+
+/// Engine-wide state for the ops dashboard in one response.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineOverview {
+    pub generated_at: DateTime<Utc>,
+    /// Symbols with resting orders or open positions.
+    pub symbols: Vec<SymbolOverview>,
+    pub resting_orders: usize,
+    pub rates: Vec<ActivityRate>,
+    pub top_accounts: Vec<AccountTradingStats>,
+    pub risk_violations_today: u64,
+    pub health: SubsystemHealth,
+}
+
+impl TradingEngine {
+    pub async fn overview(&self) -> EngineOverview {
+        let now = self.time_provider.now();
+
+        let mut symbols: BTreeMap<String, SymbolOverview> = BTreeMap::new();
+        let mut order_times = Vec::new();
+        for entry in self.orders.iter() {
+            let order = entry.value();
+            order_times.push(order.timestamp);
+            if !order.is_open() {
+                continue;
+            }
+            let summary = symbols.entry(order.symbol.clone()).or_default();
+            summary.resting_orders += 1;
+            match order.side {
+                OrderSide::Buy => summary.resting_bid_quantity += order.remaining_quantity,
+                OrderSide::Sell => summary.resting_ask_quantity += order.remaining_quantity,
+            }
+        }
+        for position in self.position_manager.get_positions(None).await {
+            if position.quantity > Decimal::ZERO {
+                symbols.entry(position.symbol.clone()).or_default().open_interest += position.quantity;
+            }
+        }
+        let symbols: Vec<SymbolOverview> = symbols
+            .into_iter()
+            .map(|(symbol, summary)| SymbolOverview { symbol, ..summary })
+            .collect();
+
+        let trade_times: Vec<DateTime<Utc>> = self.trades.read().iter().map(|trade| trade.timestamp).collect();
+        let rates = RATE_WINDOWS_MINUTES
+            .iter()
+            .map(|&window_minutes| {
+                let since = now - Duration::minutes(window_minutes);
+                let orders = order_times.iter().filter(|&&time| time > since).count();
+                let trades = trade_times.iter().filter(|&&time| time > since).count();
+                let seconds = Decimal::from(window_minutes * 60);
+                ActivityRate {
+                    window_minutes,
+                    orders,
+                    trades,
+                    orders_per_second: Decimal::from(orders) / seconds,
+                    trades_per_second: Decimal::from(trades) / seconds,
+                }
+            })
+            .collect();
+
+        let mut instruments_by_status = BTreeMap::new();
+        for instrument in self.instruments.list() {
+            let status = match instrument.status {
+                InstrumentStatus::Active => "active",
+                InstrumentStatus::Suspended => "suspended",
+                InstrumentStatus::Auction => "auction",
+                InstrumentStatus::Delisted => "delisted",
+            };
+            *instruments_by_status.entry(status.to_string()).or_insert(0) += 1;
+        }
+
+        EngineOverview {
+            generated_at: now,
+            resting_orders: symbols.iter().map(|summary| summary.resting_orders).sum(),
+            symbols,
+            rates,
+            top_accounts: self.account_stats.top_by_volume(TOP_ACCOUNTS),
+            risk_violations_today: self.metrics.risk_violations_on(now.date_naive()),
+            health: SubsystemHealth {
+                persistence_backend: self.config.persistence_backend.clone(),
+                last_event_seq: self.event_journal.last_seq(),
+                event_subscribers: self.event_journal.subscriber_count(),
+                scheduled_expiries: self.expiry_wheel.len(),
+                clearing_enabled: self.clearing.enabled(),
+                instruments_by_status,
+            },
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{engine::TradingEngine, types::*};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What an account may trade. Accounts without an entry trade anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TradingPermissions {
+    /// Unset allows every type.
+    #[serde(default)]
+    pub instrument_types: Option<Vec<BondType>>,
+    /// Sells may not take the position below zero, counting resting sells.
+    #[serde(default)]
+    pub long_only: bool,
+    /// Longest remaining maturity the account may trade.
+    #[serde(default)]
+    pub max_tenor_years: Option<Decimal>,
+}
+
+impl TradingPermissions {
+    fn restricts_instruments(&self) -> bool {
+        self.instrument_types.is_some() || self.max_tenor_years.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountPermissions {
+    pub account_id: Uuid,
+    #[serde(flatten)]
+    pub permissions: TradingPermissions,
+}
+
+#[derive(Default)]
+pub struct PermissionsMatrix {
+    accounts: DashMap<Uuid, TradingPermissions>,
+}
+
+impl PermissionsMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, account_id: Uuid, permissions: TradingPermissions) {
+        self.accounts.insert(account_id, permissions);
+    }
+
+    pub fn remove(&self, account_id: Uuid) -> bool {
+        self.accounts.remove(&account_id).is_some()
+    }
+
+    pub fn get(&self, account_id: Uuid) -> Option<TradingPermissions> {
+        self.accounts.get(&account_id).map(|permissions| permissions.clone())
+    }
+
+    pub fn all(&self) -> Vec<AccountPermissions> {
+        self.accounts
+            .iter()
+            .map(|entry| AccountPermissions {
+                account_id: *entry.key(),
+                permissions: entry.value().clone(),
+            })
+            .collect()
+    }
+}
+
+impl TradingEngine {
+    pub fn permissions(&self) -> &PermissionsMatrix {
+        &self.permissions
+    }
+
+    /// Checks an order against its account's entitlements. Unlisted
+    /// instruments fail any type or tenor restriction, as neither can be
+    /// known.
+    pub(crate) async fn check_permissions(&self, order: &Order) -> crate::types::Result<()> {
+        let Some(permissions) = self.permissions.get(order.account_id) else {
+            return Ok(());
+        };
+
+        if permissions.restricts_instruments() {
+            let bond = self.instruments.get(&order.symbol).ok_or_else(|| {
+                TradingError::PermissionDenied(format!("{} is not a listed instrument", order.symbol))
+            })?;
+            if let Some(types) = &permissions.instrument_types {
+                if !types.contains(&bond.bond_type) {
+                    return Err(TradingError::PermissionDenied(format!(
+                        "Account is not permissioned for {:?}",
+                        bond.bond_type
+                    )));
+                }
+            }
+            if let Some(max_tenor) = permissions.max_tenor_years {
+                let days = (bond.maturity_date - self.time_provider.now()).num_days();
+                let tenor = Decimal::from(days) / Decimal::from(365);
+                if tenor > max_tenor {
+                    return Err(TradingError::PermissionDenied(format!(
+                        "{} matures in {} years, beyond the permitted {}",
+                        order.symbol,
+                        tenor.round_dp(2),
+                        max_tenor
+                    )));
+                }
+            }
+        }
+
+        if permissions.long_only && order.side == OrderSide::Sell {
+            let held = self
+                .position_manager
+                .get_position(order.account_id, &order.symbol)
+                .await
+                .map(|position| position.quantity)
+                .unwrap_or_default();
+            let resting_sells: Decimal = self
+                .orders
+                .iter()
+                .filter(|entry| {
+                    let resting = entry.value();
+                    resting.account_id == order.account_id
+                        && resting.symbol == order.symbol
+                        && resting.side == OrderSide::Sell
+                        && resting.is_open()
+                })
+                .map(|entry| entry.remaining_quantity)
+                .sum();
+            if order.quantity + resting_sells > held {
+                return Err(TradingError::PermissionDenied(format!(
+                    "Long-only account holds {} of {} with {} already offered",
+                    held, order.symbol, resting_sells
+                )));
+            }
+        }
+        Ok(())
+    }
+}
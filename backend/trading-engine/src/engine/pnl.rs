@@ -0,0 +1,158 @@
+use crate::types::*;
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PnlGrouping {
+    #[default]
+    Strategy,
+    Account,
+    Symbol,
+}
+
+/// Holding of one account in one symbol under one strategy tag, carried at
+/// average cost.
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyLot {
+    pub account_id: Uuid,
+    pub symbol: String,
+    pub strategy_id: Option<String>,
+    pub quantity: Decimal,
+    pub average_price: Decimal,
+    pub realized_pnl: Decimal,
+    pub fees: Decimal,
+}
+
+impl StrategyLot {
+    fn apply(&mut self, quantity_change: Decimal, price: Decimal, fees: Decimal) {
+        let old_quantity = self.quantity;
+        let new_quantity = old_quantity + quantity_change;
+        let increasing =
+            old_quantity.is_zero() || old_quantity.is_sign_positive() == quantity_change.is_sign_positive();
+
+        if increasing {
+            let total_cost = old_quantity.abs() * self.average_price + quantity_change.abs() * price;
+            self.average_price = total_cost / new_quantity.abs();
+        } else {
+            let closed_quantity = quantity_change.abs().min(old_quantity.abs());
+            let direction = if old_quantity.is_sign_positive() { Decimal::ONE } else { -Decimal::ONE };
+            self.realized_pnl += notional_value(closed_quantity * direction, price - self.average_price);
+            if !new_quantity.is_zero() && new_quantity.is_sign_positive() != old_quantity.is_sign_positive() {
+                self.average_price = price;
+            }
+        }
+
+        self.quantity = new_quantity;
+        self.fees += fees;
+    }
+
+    fn unrealized_pnl(&self, mark: Option<Decimal>) -> Decimal {
+        mark.map(|mark| notional_value(self.quantity, mark - self.average_price))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PnlSummary {
+    /// Strategy id, account id or symbol depending on the grouping; `None`
+    /// collects untagged flow when grouping by strategy.
+    pub group: Option<String>,
+    pub realized_pnl: Decimal,
+    pub unrealized_pnl: Decimal,
+    pub fees: Decimal,
+    pub net_pnl: Decimal,
+    pub lots: usize,
+}
+
+type LotKey = (Uuid, String, Option<String>);
+
+/// Attributes trading P&L to the strategy tags carried on trades.
+pub struct PnlAttribution {
+    lots: DashMap<LotKey, StrategyLot>,
+    marks: DashMap<String, Decimal>,
+}
+
+impl PnlAttribution {
+    pub fn new() -> Self {
+        Self {
+            lots: DashMap::new(),
+            marks: DashMap::new(),
+        }
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        self.marks.insert(trade.symbol.clone(), trade.price);
+
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let (account_id, strategy_id, quantity_change) = match side {
+                OrderSide::Buy => (trade.buyer_account_id, trade.buyer_strategy_id.clone(), trade.quantity),
+                OrderSide::Sell => (trade.seller_account_id, trade.seller_strategy_id.clone(), -trade.quantity),
+            };
+            let key = (account_id, trade.symbol.clone(), strategy_id.clone());
+            self.lots
+                .entry(key)
+                .or_insert_with(|| StrategyLot {
+                    account_id,
+                    symbol: trade.symbol.clone(),
+                    strategy_id,
+                    quantity: Decimal::ZERO,
+                    average_price: Decimal::ZERO,
+                    realized_pnl: Decimal::ZERO,
+                    fees: Decimal::ZERO,
+                })
+                .apply(quantity_change, trade.price, trade.fees_for(&side).total);
+        }
+    }
+
+    /// Last traded price seen for a symbol.
+    pub fn mark(&self, symbol: &str) -> Option<Decimal> {
+        self.marks.get(symbol).map(|mark| *mark)
+    }
+
+    pub fn lots(&self, account_id: Option<Uuid>) -> Vec<StrategyLot> {
+        self.lots
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == id))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Realized, unrealized (marked at the last trade price) and fee totals
+    /// per group.
+    pub fn summarize(&self, grouping: PnlGrouping, account_id: Option<Uuid>) -> Vec<PnlSummary> {
+        let mut groups: BTreeMap<Option<String>, PnlSummary> = BTreeMap::new();
+        for lot in self.lots(account_id) {
+            let group = match grouping {
+                PnlGrouping::Strategy => lot.strategy_id.clone(),
+                PnlGrouping::Account => Some(lot.account_id.to_string()),
+                PnlGrouping::Symbol => Some(lot.symbol.clone()),
+            };
+            let unrealized = lot.unrealized_pnl(self.marks.get(&lot.symbol).map(|mark| *mark));
+
+            let summary = groups.entry(group.clone()).or_insert_with(|| PnlSummary {
+                group,
+                realized_pnl: Decimal::ZERO,
+                unrealized_pnl: Decimal::ZERO,
+                fees: Decimal::ZERO,
+                net_pnl: Decimal::ZERO,
+                lots: 0,
+            });
+            summary.realized_pnl += lot.realized_pnl;
+            summary.unrealized_pnl += unrealized;
+            summary.fees += lot.fees;
+            summary.net_pnl += lot.realized_pnl + unrealized - lot.fees;
+            summary.lots += 1;
+        }
+        groups.into_values().collect()
+    }
+}
+
+impl Default for PnlAttribution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
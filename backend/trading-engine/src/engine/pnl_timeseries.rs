@@ -0,0 +1,175 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PnlSnapshot {
+    pub account_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub realized_pnl: Decimal,
+    pub unrealized_pnl: Decimal,
+    pub total_pnl: Decimal,
+    pub market_value: Decimal,
+}
+
+/// Recent P&L snapshots per account, newest last, capped at `capacity`
+/// entries per account.
+pub struct PnlTimeseries {
+    capacity: usize,
+    series: DashMap<Uuid, RwLock<VecDeque<PnlSnapshot>>>,
+}
+
+impl PnlTimeseries {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            series: DashMap::new(),
+        }
+    }
+
+    pub fn record(&self, snapshot: PnlSnapshot) {
+        let entry = self.series.entry(snapshot.account_id).or_default();
+        let mut series = entry.write();
+        series.push_back(snapshot);
+        while series.len() > self.capacity {
+            series.pop_front();
+        }
+    }
+
+    /// Snapshots in `[from, to]`, plus whether the buffer still covers
+    /// `from`.
+    pub fn range(&self, account_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> (Vec<PnlSnapshot>, bool) {
+        let Some(entry) = self.series.get(&account_id) else {
+            return (Vec::new(), false);
+        };
+        let series = entry.read();
+        let covered = series.front().is_some_and(|oldest| oldest.timestamp <= from);
+        let snapshots = series
+            .iter()
+            .filter(|snapshot| snapshot.timestamp >= from && snapshot.timestamp <= to)
+            .cloned()
+            .collect();
+        (snapshots, covered)
+    }
+}
+
+/// Keeps the last snapshot in each `interval`-wide bucket.
+pub fn downsample(snapshots: Vec<PnlSnapshot>, interval: Duration) -> Vec<PnlSnapshot> {
+    let width = interval.num_milliseconds();
+    if width <= 0 {
+        return snapshots;
+    }
+
+    let mut buckets: BTreeMap<i64, PnlSnapshot> = BTreeMap::new();
+    for snapshot in snapshots {
+        let bucket = snapshot.timestamp.timestamp_millis().div_euclid(width);
+        buckets.insert(bucket, snapshot);
+    }
+    buckets.into_values().collect()
+}
+
+impl TradingEngine {
+    /// Samples every account's P&L, marking open positions at the last
+    /// traded price, into the ring buffer and the state store.
+    pub async fn snapshot_pnl(&self) -> Vec<PnlSnapshot> {
+        let timestamp = self.time_provider.now();
+        let mut totals: BTreeMap<Uuid, PnlSnapshot> = BTreeMap::new();
+
+        for position in self.get_positions(None).await {
+            let mark = self.pnl.mark(&position.symbol).unwrap_or(position.average_price);
+            let snapshot = totals.entry(position.account_id).or_insert_with(|| PnlSnapshot {
+                account_id: position.account_id,
+                timestamp,
+                realized_pnl: Decimal::ZERO,
+                unrealized_pnl: Decimal::ZERO,
+                total_pnl: Decimal::ZERO,
+                market_value: Decimal::ZERO,
+            });
+            let unrealized = notional_value(position.quantity, mark - position.average_price);
+            snapshot.realized_pnl += position.realized_pnl;
+            snapshot.unrealized_pnl += unrealized;
+            snapshot.total_pnl += position.realized_pnl + unrealized;
+            snapshot.market_value += notional_value(position.quantity, mark);
+        }
+
+        let snapshots: Vec<PnlSnapshot> = totals.into_values().collect();
+        for snapshot in &snapshots {
+            self.pnl_timeseries.record(snapshot.clone());
+        }
+        if let Err(e) = self.state_store.save_pnl_snapshots(&snapshots).await {
+            warn!("Failed to persist P&L snapshots: {}", e);
+        }
+        snapshots
+    }
+
+    /// Equity curve for an account. Served from memory when the ring
+    /// buffer reaches back far enough, otherwise from the state store.
+    pub async fn pnl_timeseries(
+        &self,
+        account_id: Uuid,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        interval: Option<Duration>,
+    ) -> crate::types::Result<Vec<PnlSnapshot>> {
+        let to = to.unwrap_or_else(|| self.time_provider.now());
+        let from = from.unwrap_or_else(|| to - Duration::days(1));
+        if from > to {
+            return Err(TradingError::InvalidRequest("`from` must not be after `to`".to_string()));
+        }
+
+        let (mut snapshots, covered) = self.pnl_timeseries.range(account_id, from, to);
+        if !covered {
+            let stored = self.state_store.load_pnl_snapshots(account_id, from, to).await?;
+            if stored.len() > snapshots.len() {
+                snapshots = stored;
+            }
+        }
+
+        Ok(match interval {
+            Some(interval) => downsample(snapshots, interval),
+            None => snapshots,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snapshot(account_id: Uuid, minute: u32, total: i64) -> PnlSnapshot {
+        PnlSnapshot {
+            account_id,
+            timestamp: Utc.with_ymd_and_hms(2024, 3, 1, 10, minute, 0).unwrap(),
+            realized_pnl: Decimal::ZERO,
+            unrealized_pnl: Decimal::from(total),
+            total_pnl: Decimal::from(total),
+            market_value: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_caps_and_downsamples() {
+        let account = Uuid::new_v4();
+        let series = PnlTimeseries::new(10);
+        for minute in 0..15 {
+            series.record(snapshot(account, minute, minute as i64));
+        }
+
+        let from = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let (snapshots, covered) = series.range(account, from, to);
+        assert_eq!(snapshots.len(), 10);
+        assert!(!covered);
+
+        let five_minute = downsample(snapshots, Duration::minutes(5));
+        let totals: Vec<Decimal> = five_minute.iter().map(|s| s.total_pnl).collect();
+        assert_eq!(totals, vec![Decimal::from(9), Decimal::from(14)]);
+    }
+}
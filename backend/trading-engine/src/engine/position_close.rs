@@ -0,0 +1,225 @@
+//! Flattening a position in one call. The engine works out the offsetting
+//! side and size from the account's position and enters the orders, at
+//! market or at the mid, optionally in slices.
+
+use crate::{
+    engine::{fees::BASIS_POINT, TradingEngine},
+    types::*,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Set on every order a close generates.
+pub const CLOSE_POSITION_KEY: &str = "close_position";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseStyle {
+    /// Takes the book. With a slippage cap, a limit that far through the
+    /// mid instead, so nothing fills beyond it.
+    #[default]
+    Market,
+    /// Rests at the mid, moved towards the market by at most the cap.
+    LimitAtMid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClosePositionRequest {
+    pub user_id: Uuid,
+    #[serde(default)]
+    pub style: CloseStyle,
+    pub max_slippage_bps: Option<Decimal>,
+    /// Largest order to enter; the position is split into slices of this
+    /// size, the last taking the remainder.
+    pub slice_quantity: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClosePositionResult {
+    pub account_id: Uuid,
+    pub symbol: String,
+    pub position_quantity: Decimal,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub price: Option<Decimal>,
+    pub order_ids: Vec<Uuid>,
+    /// Why slices after the last entered ones were not entered.
+    pub incomplete_reason: Option<String>,
+}
+
+fn slices(quantity: Decimal, slice_quantity: Option<Decimal>) -> Vec<Decimal> {
+    let Some(slice) = slice_quantity.filter(|slice| *slice < quantity) else {
+        return vec![quantity];
+    };
+    let mut slices = Vec::new();
+    let mut remaining = quantity;
+    while remaining > Decimal::ZERO {
+        slices.push(remaining.min(slice));
+        remaining -= slice;
+    }
+    slices
+}
+
+impl TradingEngine {
+    /// Enters the orders that take the account's position in `symbol` to
+    /// zero. Slices entered before one is rejected stay in the market.
+    pub async fn close_position(
+        &self,
+        account_id: Uuid,
+        symbol: &str,
+        request: ClosePositionRequest,
+    ) -> Result<ClosePositionResult> {
+        let symbol = self.instruments.canonical(symbol);
+        let position_quantity = self
+            .get_position(account_id, &symbol)
+            .await
+            .map(|position| position.quantity)
+            .unwrap_or_default();
+        if position_quantity.is_zero() {
+            return Err(TradingError::InvalidRequest(format!(
+                "Account {} has no position in {}",
+                account_id, symbol
+            )));
+        }
+        if request.max_slippage_bps.is_some_and(|bps| bps < Decimal::ZERO) {
+            return Err(TradingError::InvalidRequest("The slippage cap cannot be negative".to_string()));
+        }
+        if request.slice_quantity.is_some_and(|slice| slice <= Decimal::ZERO) {
+            return Err(TradingError::InvalidRequest("Slices must be larger than zero".to_string()));
+        }
+
+        let side = if position_quantity > Decimal::ZERO {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        let price = match (request.style, request.max_slippage_bps) {
+            (CloseStyle::Market, None) => None,
+            (style, cap) => Some(self.close_price(&symbol, &side, style, cap.unwrap_or_default())?),
+        };
+        let order_type = if price.is_some() { OrderType::Limit } else { OrderType::Market };
+
+        let mut result = ClosePositionResult {
+            account_id,
+            symbol: symbol.clone(),
+            position_quantity,
+            side: side.clone(),
+            order_type: order_type.clone(),
+            price,
+            order_ids: Vec::new(),
+            incomplete_reason: None,
+        };
+        let batch = Uuid::new_v4();
+        for (index, quantity) in slices(position_quantity.abs(), request.slice_quantity).into_iter().enumerate() {
+            let order = Order {
+                id: Uuid::new_v4(),
+                client_order_id: format!("CLOSE-{}-{}", batch.simple(), index + 1),
+                symbol: symbol.clone(),
+                side: side.clone(),
+                order_type: order_type.clone(),
+                quantity,
+                price,
+                filled_quantity: Decimal::ZERO,
+                remaining_quantity: quantity,
+                status: OrderStatus::Pending,
+                timestamp: self.time_provider.now(),
+                user_id: request.user_id,
+                account_id,
+                time_in_force: TimeInForce::GoodTillCancel,
+                metadata: HashMap::from([(CLOSE_POSITION_KEY.to_string(), batch.to_string())]),
+                strategy_id: None,
+                fills: OrderFills::default(),
+                cancel_reason: None,
+            };
+            match self.submit_order(order).await {
+                Ok(order_id) => result.order_ids.push(order_id),
+                Err(e) if result.order_ids.is_empty() => return Err(e),
+                Err(e) => {
+                    warn!("Closing {} for {} stopped after {} slices: {}", symbol, account_id, index, e);
+                    result.incomplete_reason = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        info!(
+            "Entered {} orders to close {} {} for {}",
+            result.order_ids.len(),
+            position_quantity,
+            symbol,
+            account_id
+        );
+        Ok(result)
+    }
+
+    /// The mid, moved `cap_bps` towards the side being hit and rounded
+    /// away from it onto the instrument's price scale.
+    fn close_price(&self, symbol: &str, side: &OrderSide, style: CloseStyle, cap_bps: Decimal) -> Result<Decimal> {
+        let (bid, ask) = (
+            self.matching_engine.get_best_bid(symbol),
+            self.matching_engine.get_best_ask(symbol),
+        );
+        let mid = match (bid, ask) {
+            (Some(bid), Some(ask)) => (bid + ask) / Decimal::TWO,
+            _ => {
+                return Err(TradingError::InvalidRequest(format!(
+                    "{} needs a two-sided book to price a {:?} close",
+                    symbol, style
+                )))
+            }
+        };
+        let offset = mid * cap_bps * BASIS_POINT;
+        let decimals = self.matching_engine.tick_scale(symbol).price_decimals;
+        Ok(match side {
+            OrderSide::Sell => (mid - offset).round_dp_with_strategy(decimals, RoundingStrategy::AwayFromZero),
+            OrderSide::Buy => (mid + offset).round_dp_with_strategy(decimals, RoundingStrategy::ToZero),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[test]
+    fn test_slices_leave_the_remainder_last() {
+        assert_eq!(slices(dec!(250000), Some(dec!(100000))), vec![dec!(100000), dec!(100000), dec!(50000)]);
+        assert_eq!(slices(dec!(250000), None), vec![dec!(250000)]);
+    }
+
+    #[tokio::test]
+    async fn test_close_sells_a_long_position_in_slices_within_the_cap() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (fund, dealer, bidder) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, dec!(300000), dec!(99.50), dealer)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(300000), dec!(99.50), fund)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(200000), dec!(99.40), bidder)).await.unwrap();
+        engine.submit_order(order(OrderSide::Sell, dec!(100000), dec!(99.60), dealer)).await.unwrap();
+
+        let request = ClosePositionRequest {
+            user_id: Uuid::new_v4(),
+            style: CloseStyle::Market,
+            max_slippage_bps: Some(dec!(20)),
+            slice_quantity: Some(dec!(100000)),
+        };
+        let result = engine.close_position(fund, "GSEC10Y", request).await.unwrap();
+        // Mid 99.50 less 20bp, so the 99.40 bid is within reach
+        assert_eq!((result.side, result.price), (OrderSide::Sell, Some(dec!(99.301))));
+        assert_eq!(result.order_ids.len(), 3);
+
+        let position = engine.get_position(fund, "GSEC10Y").await.unwrap();
+        assert_eq!(position.quantity, dec!(300000) - dec!(200000));
+        let resting = engine.get_order(&result.order_ids[2]).unwrap();
+        assert_eq!(resting.remaining_quantity, dec!(100000));
+    }
+}
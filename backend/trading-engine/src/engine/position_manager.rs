@@ -1,6 +1,5 @@
-use crate::types::*;
+use crate::{types::*, utils::time::TimeProvider};
 use anyhow::Result;
-use chrono::Utc;
 use dashmap::DashMap;
 use rust_decimal::Decimal;
 use std::sync::Arc;
@@ -8,20 +7,23 @@ use uuid::Uuid;
 
 pub struct PositionManager {
     positions: Arc<DashMap<(Uuid, String), Position>>,
-    config: Arc<crate::config::Config>,
+    /// Latest external marks, used for revaluation in place of the trade price.
+    marks: DashMap<String, Decimal>,
+    time_provider: Arc<TimeProvider>,
 }
 
 impl PositionManager {
-    pub async fn new(config: Arc<crate::config::Config>) -> Result<Self> {
+    pub async fn new(time_provider: Arc<TimeProvider>) -> Result<Self> {
         Ok(Self {
             positions: Arc::new(DashMap::new()),
-            config,
+            marks: DashMap::new(),
+            time_provider,
         })
     }
 
     pub async fn update_position(&self, trade: &Trade) -> crate::types::Result<()> {
-        let buyer_key = (trade.buyer_order_id, trade.symbol.clone());
-        let seller_key = (trade.seller_order_id, trade.symbol.clone());
+        let buyer_key = (trade.buyer_account_id, trade.symbol.clone());
+        let seller_key = (trade.seller_account_id, trade.symbol.clone());
 
         // Update buyer position
         self.update_position_for_trade(buyer_key, trade, OrderSide::Buy).await?;
@@ -43,26 +45,44 @@ impl PositionManager {
             OrderSide::Buy => trade.quantity,
             OrderSide::Sell => -trade.quantity,
         };
+        // Fees are a realized cost as soon as the trade happens
+        let fees = trade.fees_for(&side).total;
+        // Inflation-linked principal is worth its indexed amount
+        let index_ratio = trade.index_ratio.unwrap_or(Decimal::ONE);
 
         match self.positions.get_mut(&(account_id, symbol.clone())) {
             Some(mut position) => {
                 let old_quantity = position.quantity;
                 let new_quantity = old_quantity + quantity_change;
-                
-                // Calculate new average price
-                if new_quantity != Decimal::ZERO {
-                    let total_cost = (old_quantity * position.average_price) + 
-                                   (quantity_change.abs() * trade.price);
+                let increasing = old_quantity.is_zero()
+                    || old_quantity.is_sign_positive() == quantity_change.is_sign_positive();
+
+                if increasing {
+                    // Calculate new average price
+                    let total_cost = (old_quantity.abs() * position.average_price)
+                        + (quantity_change.abs() * trade.price);
                     position.average_price = total_cost / new_quantity.abs();
+                } else {
+                    // Realize P&L on the closed portion against the average price
+                    let closed_quantity = quantity_change.abs().min(old_quantity.abs());
+                    let direction = if old_quantity.is_sign_positive() { Decimal::ONE } else { -Decimal::ONE };
+                    let realized = notional_value(closed_quantity * direction, trade.price - position.average_price);
+                    position.realized_pnl += realized;
+
+                    // A reversal opens the remainder at the trade price
+                    if !new_quantity.is_zero() && new_quantity.is_sign_positive() != old_quantity.is_sign_positive() {
+                        position.average_price = trade.price;
+                    }
                 }
 
+                position.realized_pnl -= fees;
                 position.quantity = new_quantity;
-                position.last_updated = Utc::now();
+                position.last_updated = self.time_provider.now();
                 
                 // Update market value and P&L
                 let current_price = self.get_current_price(&symbol).await.unwrap_or(trade.price);
-                position.market_value = new_quantity * current_price;
-                position.unrealized_pnl = (current_price - position.average_price) * new_quantity;
+                position.market_value = notional_value(new_quantity, current_price) * index_ratio;
+                position.unrealized_pnl = notional_value(new_quantity, current_price - position.average_price);
             }
             None => {
                 // Create new position
@@ -71,10 +91,11 @@ impl PositionManager {
                     account_id,
                     quantity: quantity_change,
                     average_price: trade.price,
-                    market_value: quantity_change * trade.price,
+                    market_value: notional_value(quantity_change, trade.price) * index_ratio,
                     unrealized_pnl: Decimal::ZERO,
-                    realized_pnl: Decimal::ZERO,
-                    last_updated: Utc::now(),
+                    realized_pnl: -fees,
+                    accrued_interest: Decimal::ZERO,
+                    last_updated: self.time_provider.now(),
                 };
                 self.positions.insert((account_id, symbol), position);
             }
@@ -108,38 +129,42 @@ impl PositionManager {
             .map(|pos| pos.clone())
     }
 
-    pub async fn calculate_portfolio_value(&self, account_id: Uuid) -> Decimal {
-        self.positions
-            .iter()
-            .filter_map(|entry| {
-                let ((acc_id, _), position) = entry.pair();
-                if *acc_id == account_id {
-                    Some(position.market_value)
-                } else {
-                    None
-                }
-            })
-            .sum()
+    /// Redeems `fraction` of every open position in `symbol` at `price`,
+    /// realizing P&L against the average price. Returns the changed
+    /// positions with the quantity each redeemed.
+    pub async fn redeem(&self, symbol: &str, fraction: Decimal, price: Decimal) -> Vec<(Position, Decimal)> {
+        let mut redeemed = Vec::new();
+        for mut entry in self.positions.iter_mut() {
+            let position = entry.value_mut();
+            if position.symbol != symbol || position.quantity.is_zero() {
+                continue;
+            }
+            let quantity = position.quantity * fraction;
+            position.realized_pnl += notional_value(quantity, price - position.average_price);
+            position.quantity -= quantity;
+            position.market_value = notional_value(position.quantity, price);
+            position.unrealized_pnl = notional_value(position.quantity, price - position.average_price);
+            position.last_updated = self.time_provider.now();
+            redeemed.push((position.clone(), quantity));
+        }
+        redeemed
     }
 
-    pub async fn calculate_total_pnl(&self, account_id: Uuid) -> (Decimal, Decimal) {
-        let mut unrealized = Decimal::ZERO;
-        let mut realized = Decimal::ZERO;
-
-        for entry in self.positions.iter() {
-            let ((acc_id, _), position) = entry.pair();
-            if *acc_id == account_id {
-                unrealized += position.unrealized_pnl;
-                realized += position.realized_pnl;
+    /// Marks every position in `symbol` to `price`, keeping the mark for
+    /// positions the symbol's later trades touch.
+    pub async fn revalue(&self, symbol: &str, price: Decimal, index_ratio: Decimal) {
+        self.marks.insert(symbol.to_string(), price);
+        for mut entry in self.positions.iter_mut() {
+            let position = entry.value_mut();
+            if position.symbol != symbol {
+                continue;
             }
+            position.market_value = notional_value(position.quantity, price) * index_ratio;
+            position.unrealized_pnl = notional_value(position.quantity, price - position.average_price);
         }
-
-        (unrealized, realized)
     }
 
-    async fn get_current_price(&self, _symbol: &str) -> Option<Decimal> {
-        // This would typically fetch from market data service
-        // For now, returning None to use trade price
-        None
+    async fn get_current_price(&self, symbol: &str) -> Option<Decimal> {
+        self.marks.get(symbol).map(|mark| *mark)
     }
 }
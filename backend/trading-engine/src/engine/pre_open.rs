@@ -0,0 +1,183 @@
+//! Orders accepted while the market is closed. They pass the usual checks
+//! on entry, then wait in arrival order and are released into the book, or
+//! into a symbol's opening auction, once the market opens. Orders arriving
+//! while the queue drains join its back, so none overtakes a queued order.
+
+use crate::{engine::TradingEngine, types::*};
+use chrono::{Duration, NaiveTime};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Daily opening hours in the market's local time, on business days of
+/// the settlement calendar.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TradingHours {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl TradingHours {
+    /// Reads `HH:MM-HH:MM`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (open, close) = value.split_once('-')?;
+        let open = NaiveTime::parse_from_str(open.trim(), "%H:%M").ok()?;
+        let close = NaiveTime::parse_from_str(close.trim(), "%H:%M").ok()?;
+        (open < close).then_some(Self { open, close })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStatus {
+    pub open: bool,
+    pub trading_hours: Option<TradingHours>,
+    pub queued_orders: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    orders: VecDeque<Uuid>,
+    releasing: bool,
+}
+
+#[derive(Default)]
+pub struct PreOpenQueue {
+    state: Mutex<QueueState>,
+}
+
+impl PreOpenQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queues the order if the market is closed or earlier orders are
+    /// still waiting or being released. Returns whether it was queued.
+    fn enqueue_unless_open(&self, order_id: Uuid, market_open: bool) -> bool {
+        let mut state = self.state.lock();
+        if market_open && state.orders.is_empty() && !state.releasing {
+            return false;
+        }
+        state.orders.push_back(order_id);
+        true
+    }
+
+    /// Claims the queue for release. `false` if another release holds it.
+    fn start_release(&self) -> bool {
+        let mut state = self.state.lock();
+        !std::mem::replace(&mut state.releasing, true)
+    }
+
+    /// The next order to release, ending the release once none are left.
+    fn next(&self) -> Option<Uuid> {
+        let mut state = self.state.lock();
+        let next = state.orders.pop_front();
+        if next.is_none() {
+            state.releasing = false;
+        }
+        next
+    }
+}
+
+impl TradingEngine {
+    /// Whether the engine clock is within trading hours. Without
+    /// configured hours the market never closes.
+    pub fn market_open(&self) -> bool {
+        let Some(hours) = self.config.trading_hours else {
+            return true;
+        };
+        let local = self.time_provider.now().naive_utc()
+            + Duration::minutes(self.config.trading_hours_utc_offset_minutes as i64);
+        let calendar = self.time_provider.calendar();
+        calendar.is_business_day(&calendar.settlement_market(), local.date())
+            && (hours.open..hours.close).contains(&local.time())
+    }
+
+    pub fn session_status(&self) -> SessionStatus {
+        SessionStatus {
+            open: self.market_open(),
+            trading_hours: self.config.trading_hours,
+            queued_orders: self.pre_open.len(),
+        }
+    }
+
+    /// Queues an accepted order to wait for the open, where it must.
+    pub(crate) fn queue_for_open(&self, order: &Order) -> bool {
+        self.pre_open.enqueue_unless_open(order.id, self.market_open())
+    }
+
+    /// Releases queued orders in arrival order once the market is open.
+    /// Orders cancelled or expired while waiting are skipped. Returns how
+    /// many were released.
+    pub async fn release_pre_open_orders(&self) -> usize {
+        if self.pre_open.is_empty() || !self.market_open() || !self.pre_open.start_release() {
+            return 0;
+        }
+        let mut released = 0;
+        while let Some(order_id) = self.pre_open.next() {
+            let Some(order) = self.get_order(&order_id).filter(|order| order.is_open()) else {
+                continue;
+            };
+            if let Err(e) = self.route_order(order, false).await {
+                error!("Failed to release pre-open order {}: {}", order_id, e);
+                continue;
+            }
+            released += 1;
+        }
+        if released > 0 {
+            info!("Released {} pre-open orders", released);
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::OrderBuilder,
+        utils::time::{SimulatedClock, TimeProvider},
+    };
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_queued_orders_keep_arrival_priority_at_the_open() {
+        // 07:30 IST on a Monday
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 1, 8, 2, 0, 0).unwrap()));
+        let config = Config {
+            trading_hours: TradingHours::parse("09:00-17:00"),
+            ..Config::default()
+        };
+        let time_provider = Arc::new(TimeProvider::with_clock(clock.clone()));
+        let engine = TradingEngine::with_time_provider(Arc::new(config), time_provider).await.unwrap();
+        assert!(!engine.market_open());
+
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let first_bid = engine.submit_order(order(OrderSide::Buy, first)).await.unwrap();
+        let second_bid = engine.submit_order(order(OrderSide::Buy, second)).await.unwrap();
+        assert_eq!(engine.session_status().queued_orders, 2);
+        assert_eq!(engine.release_pre_open_orders().await, 0);
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(engine.release_pre_open_orders().await, 2);
+        engine.submit_order(order(OrderSide::Sell, Uuid::new_v4())).await.unwrap();
+        assert_eq!(engine.get_order(&first_bid).unwrap().status, OrderStatus::Filled);
+        assert_eq!(engine.get_order(&second_bid).unwrap().status, OrderStatus::Pending);
+        assert!(engine.pre_open.is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+//! Price-improvement auctions. A marketable order from a retail-tier
+//! account is shown to the registered market makers for a few
+//! milliseconds before it reaches the book; responses priced through the
+//! contra touch fill it first, best price then earliest, and whatever is
+//! left trades on the book as usual. Improvement is tallied per provider.
+
+use crate::{
+    engine::{fees::AccountTier, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImprovementAuctionStatus {
+    Open,
+    Closed,
+}
+
+/// What providers are shown of the order. The account behind it is not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImprovementAuction {
+    pub id: Uuid,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    /// The contra touch when the auction opened; responses must beat it.
+    pub reference_price: Decimal,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub status: ImprovementAuctionStatus,
+    /// Filled by providers, once closed.
+    pub improved_quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImprovementResponse {
+    pub provider_account_id: Uuid,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImprovementStats {
+    pub provider_account_id: Uuid,
+    pub responses: u64,
+    /// Auctions the provider filled some of the order in.
+    pub auctions_won: u64,
+    pub quantity_filled: Decimal,
+    /// Money saved by the orders filled, against the contra touch.
+    pub improvement_value: Decimal,
+    /// Price points of improvement, weighted by quantity filled.
+    pub average_improvement: Option<Decimal>,
+}
+
+struct OpenAuction {
+    auction: ImprovementAuction,
+    account_id: Uuid,
+    responses: Vec<ImprovementResponse>,
+}
+
+#[derive(Default)]
+pub struct PriceImprovementDesk {
+    open: DashMap<Uuid, OpenAuction>,
+    stats: DashMap<Uuid, ImprovementStats>,
+}
+
+impl PriceImprovementDesk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open_auctions(&self) -> Vec<ImprovementAuction> {
+        let mut auctions: Vec<ImprovementAuction> = self.open.iter().map(|open| open.auction.clone()).collect();
+        auctions.sort_by_key(|auction| auction.started_at);
+        auctions
+    }
+
+    pub fn stats(&self) -> Vec<ImprovementStats> {
+        let mut stats: Vec<ImprovementStats> = self.stats.iter().map(|stats| stats.clone()).collect();
+        stats.sort_by_key(|stats| stats.provider_account_id);
+        stats
+    }
+
+    fn provider_stats(&self, provider_account_id: Uuid) -> dashmap::mapref::one::RefMut<'_, Uuid, ImprovementStats> {
+        self.stats.entry(provider_account_id).or_insert_with(|| ImprovementStats {
+            provider_account_id,
+            ..Default::default()
+        })
+    }
+}
+
+/// Whether `price` is better than `than` for an order on `side`.
+fn improves(side: &OrderSide, price: Decimal, than: Decimal) -> bool {
+    match side {
+        OrderSide::Buy => price < than,
+        OrderSide::Sell => price > than,
+    }
+}
+
+impl TradingEngine {
+    pub fn price_improvement(&self) -> &PriceImprovementDesk {
+        &self.price_improvement
+    }
+
+    /// A provider's offer to fill an open auction. A later response from
+    /// the same provider replaces its earlier one.
+    pub fn respond_to_improvement_auction(&self, auction_id: Uuid, response: ImprovementResponse) -> Result<()> {
+        if self.market_makers.get(response.provider_account_id).is_none() {
+            return Err(TradingError::PermissionDenied(
+                "Only registered market makers may respond to improvement auctions".to_string(),
+            ));
+        }
+        let mut open = self
+            .price_improvement
+            .open
+            .get_mut(&auction_id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("No open improvement auction {}", auction_id)))?;
+        let auction = &open.auction;
+        if response.quantity <= Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Quantity must be positive".to_string()));
+        }
+        if !improves(&auction.side, response.price, auction.reference_price) {
+            return Err(TradingError::InvalidRequest(format!(
+                "Responses must improve on {}",
+                auction.reference_price
+            )));
+        }
+        if self.matching_engine.tick_scale(&auction.symbol).price_ticks(response.price).is_none() {
+            return Err(TradingError::InvalidRequest("Price does not fit the instrument's tick".to_string()));
+        }
+        if response.provider_account_id == open.account_id {
+            return Err(TradingError::InvalidRequest("A provider cannot improve its own order".to_string()));
+        }
+
+        self.price_improvement.provider_stats(response.provider_account_id).responses += 1;
+        open.responses.retain(|existing| existing.provider_account_id != response.provider_account_id);
+        open.responses.push(response);
+        Ok(())
+    }
+
+    /// Runs an improvement auction for `order` where it qualifies, filling
+    /// what providers improve on and leaving the rest for the book.
+    pub(crate) async fn run_price_improvement(&self, order: &mut Order) -> Vec<Trade> {
+        let window_ms = self.config.price_improvement_window_ms;
+        if window_ms == 0
+            || self.fee_engine.account_tier(order.account_id) != AccountTier::Retail
+            || !self.is_marketable(order)
+        {
+            return Vec::new();
+        }
+        let providers: Vec<Uuid> = self
+            .market_makers
+            .all()
+            .into_iter()
+            .map(|maker| maker.account_id)
+            .filter(|account_id| *account_id != order.account_id)
+            .collect();
+        let reference_price = match order.side {
+            OrderSide::Buy => self.matching_engine.get_best_ask(&order.symbol),
+            OrderSide::Sell => self.matching_engine.get_best_bid(&order.symbol),
+        };
+        let Some(reference_price) = reference_price.filter(|_| !providers.is_empty()) else {
+            return Vec::new();
+        };
+
+        let now = self.time_provider.now();
+        let mut auction = ImprovementAuction {
+            id: Uuid::new_v4(),
+            symbol: order.symbol.clone(),
+            side: order.side.clone(),
+            quantity: order.remaining_quantity,
+            reference_price,
+            started_at: now,
+            ends_at: now + Duration::milliseconds(window_ms as i64),
+            status: ImprovementAuctionStatus::Open,
+            improved_quantity: Decimal::ZERO,
+        };
+        self.price_improvement.open.insert(
+            auction.id,
+            OpenAuction {
+                auction: auction.clone(),
+                account_id: order.account_id,
+                responses: Vec::new(),
+            },
+        );
+        self.event_journal
+            .publish(EngineEvent::ImprovementAuctionUpdated(auction.clone()), providers.clone());
+        debug!("Holding order {} for improvement auction {}", order.id, auction.id);
+        tokio::time::sleep(std::time::Duration::from_millis(window_ms)).await;
+
+        let Some((_, OpenAuction { mut responses, .. })) = self.price_improvement.open.remove(&auction.id) else {
+            return Vec::new();
+        };
+        // Stable, so equal prices keep the order they arrived in
+        responses.sort_by(|a, b| match order.side {
+            OrderSide::Buy => a.price.cmp(&b.price),
+            OrderSide::Sell => b.price.cmp(&a.price),
+        });
+        let mut trades = Vec::new();
+        for response in responses {
+            if order.remaining_quantity <= Decimal::ZERO {
+                break;
+            }
+            let quantity = order.remaining_quantity.min(response.quantity);
+            let provider_account_id = response.provider_account_id;
+            if !self.clearing.enabled() {
+                let notional = notional_value(quantity, response.price);
+                if let Err(e) = self.credit_lines.check(order.account_id, provider_account_id, notional) {
+                    debug!("Skipping improvement from {}: {}", provider_account_id, e);
+                    continue;
+                }
+            }
+            trades.push(self.fill_improvement(order, &auction, &response, quantity));
+        }
+
+        auction.status = ImprovementAuctionStatus::Closed;
+        auction.improved_quantity = trades.iter().map(|trade| trade.quantity).sum();
+        if auction.improved_quantity > Decimal::ZERO {
+            info!(
+                "Improvement auction {} filled {} of order {}",
+                auction.id, auction.improved_quantity, order.id
+            );
+        }
+        self.event_journal.publish(EngineEvent::ImprovementAuctionUpdated(auction), providers);
+        trades
+    }
+
+    fn fill_improvement(
+        &self,
+        order: &mut Order,
+        auction: &ImprovementAuction,
+        response: &ImprovementResponse,
+        quantity: Decimal,
+    ) -> Trade {
+        let provider = Order {
+            id: Uuid::new_v4(),
+            client_order_id: format!("PI-{}", auction.id),
+            side: match order.side {
+                OrderSide::Buy => OrderSide::Sell,
+                OrderSide::Sell => OrderSide::Buy,
+            },
+            quantity,
+            price: Some(response.price),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: quantity,
+            status: OrderStatus::Pending,
+            time_in_force: TimeInForce::ImmediateOrCancel,
+            user_id: Uuid::nil(),
+            account_id: response.provider_account_id,
+            metadata: Default::default(),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            ..order.clone()
+        };
+        self.orders.insert(provider.id, provider.clone());
+        self.event_journal
+            .publish(EngineEvent::OrderSubmitted(provider.clone()), vec![provider.account_id]);
+
+        let mut trade = self.matching_engine.build_trade(order, &provider, quantity, response.price);
+        trade.trade_type = TradeType::PriceImprovement;
+        self.matching_engine.publish_trade(&trade, order, &provider);
+
+        order.remaining_quantity -= quantity;
+        order.filled_quantity += quantity;
+        order.fills.record(quantity, response.price, order.filled_quantity);
+        order.status = if order.remaining_quantity <= Decimal::ZERO {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+
+        let improvement = (auction.reference_price - response.price).abs();
+        let mut stats = self.price_improvement.provider_stats(response.provider_account_id);
+        let weighted = stats.average_improvement.unwrap_or_default() * stats.quantity_filled + improvement * quantity;
+        stats.auctions_won += 1;
+        stats.quantity_filled += quantity;
+        stats.improvement_value += notional_value(quantity, improvement);
+        stats.average_improvement = Some((weighted / stats.quantity_filled).round_dp(6));
+        trade
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        engine::market_makers::{MarketMaker, QuotingObligation},
+        test_support::OrderBuilder,
+    };
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_retail_order_is_improved_before_reaching_the_book() {
+        let config = Config {
+            price_improvement_window_ms: 20,
+            ..Config::default()
+        };
+        let engine = Arc::new(TradingEngine::new(Arc::new(config)).await.unwrap());
+        let (retail, seller, provider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        engine.fee_engine().set_account_tier(retail, AccountTier::Retail);
+        engine.market_makers().register(MarketMaker {
+            account_id: provider,
+            name: "Provider".to_string(),
+            obligation: QuotingObligation {
+                symbols: vec!["GSEC10Y".to_string()],
+                max_spread: dec!(0.25),
+                min_size: dec!(100000),
+                min_presence_pct: dec!(90),
+            },
+        });
+        engine.submit_order(order(OrderSide::Sell, dec!(100000), dec!(99.50), seller)).await.unwrap();
+
+        let submitting = tokio::spawn({
+            let engine = engine.clone();
+            async move { engine.submit_order(order(OrderSide::Buy, dec!(100000), dec!(99.50), retail)).await }
+        });
+        let auction = loop {
+            if let Some(auction) = engine.price_improvement().open_auctions().pop() {
+                break auction;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(auction.reference_price, dec!(99.50));
+        let response = |price| ImprovementResponse {
+            provider_account_id: provider,
+            price,
+            quantity: dec!(40000),
+        };
+        assert!(engine.respond_to_improvement_auction(auction.id, response(dec!(99.50))).is_err());
+        engine.respond_to_improvement_auction(auction.id, response(dec!(99.40))).unwrap();
+
+        let order_id = submitting.await.unwrap().unwrap();
+        let filled = engine.get_order(&order_id).unwrap();
+        assert_eq!(filled.status, OrderStatus::Filled);
+        assert_eq!(filled.fills.avg_fill_price, Some(dec!(99.46)));
+        let stats = &engine.price_improvement().stats()[0];
+        assert_eq!((stats.auctions_won, stats.quantity_filled), (1, dec!(40000)));
+        assert_eq!(stats.improvement_value, dec!(40));
+        assert_eq!(stats.average_improvement, Some(dec!(0.10)));
+    }
+}
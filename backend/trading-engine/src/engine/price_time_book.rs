@@ -0,0 +1,330 @@
+//! A price-time book for orders that trade outside the outright books,
+//! such as spread and benchmark orders. Each order type says how it ranks
+//! and what prices its fills; matching, resting and cancelling are the
+//! same for all of them.
+
+use crate::{types::*, utils::versioned::VersionedWriteGuard};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use uuid::Uuid;
+
+pub trait RestingOrder: Clone {
+    /// What a fill against a resting order is priced by.
+    type Pricing: Clone;
+    /// What a book is, shared by every order in it.
+    type Book: Clone;
+
+    fn id(&self) -> Uuid;
+    /// The book the order trades in.
+    fn key(&self) -> String;
+    fn book(&self) -> Self::Book;
+    fn side(&self) -> &OrderSide;
+    /// Where the order ranks on its side; higher bids and lower asks are
+    /// better.
+    fn level(&self) -> Decimal;
+    fn remaining(&self) -> Decimal;
+    fn is_open(&self) -> bool;
+    fn fill(&mut self, quantity: Decimal);
+    /// Notes what a resting order was last filled at.
+    fn priced(&mut self, _pricing: &Self::Pricing) {}
+    fn cancel(&mut self, reason: CancelReason);
+}
+
+pub type Levels<O> = BTreeMap<Decimal, VecDeque<O>>;
+
+pub struct Book<O: RestingOrder> {
+    pub description: O::Book,
+    pub bids: Levels<O>,
+    pub asks: Levels<O>,
+}
+
+impl<O: RestingOrder> Book<O> {
+    fn new(description: O::Book) -> Self {
+        Self {
+            description,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    /// The side orders on `side` rest on.
+    fn side_mut(&mut self, side: &OrderSide) -> &mut Levels<O> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+}
+
+/// A match against a resting order, and what it was priced by.
+#[derive(Debug, Clone)]
+pub struct Fill<O: RestingOrder> {
+    pub resting: O,
+    pub quantity: Decimal,
+    pub pricing: O::Pricing,
+}
+
+pub struct PriceTimeBook<O: RestingOrder> {
+    books: RwLock<HashMap<String, Book<O>>>,
+    orders: DashMap<Uuid, O>,
+    /// Advances on every change to any book.
+    generation: AtomicU64,
+}
+
+impl<O: RestingOrder> Default for PriceTimeBook<O> {
+    fn default() -> Self {
+        Self {
+            books: RwLock::new(HashMap::new()),
+            orders: DashMap::new(),
+            generation: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<O: RestingOrder> PriceTimeBook<O> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Matches `order`, hands its fills to `then`, which may also work
+    /// what is left elsewhere, and rests any open remainder. It all happens
+    /// under one lock, so no opposite order can rest against it in between.
+    pub fn submit<T>(
+        &self,
+        order: &mut O,
+        price: impl Fn(&O) -> Option<O::Pricing>,
+        eligible: impl FnMut(&O, Decimal, &O::Pricing) -> bool,
+        then: impl FnOnce(&mut O, &[Fill<O>]) -> T,
+    ) -> T {
+        let mut books = VersionedWriteGuard::new(&self.books, &self.generation);
+        let fills = self.match_order(&mut books, order, price, eligible);
+        let worked = then(order, &fills);
+        if order.is_open() && order.remaining() > Decimal::ZERO {
+            self.rest(&mut books, order.clone());
+        } else {
+            self.orders.insert(order.id(), order.clone());
+        }
+        worked
+    }
+
+    /// Matches `order` against the opposite side of its book, best level
+    /// first. `price` prices a level from the order at its front; levels
+    /// it cannot price are skipped. Resting orders `eligible` turns down
+    /// keep their place.
+    fn match_order(
+        &self,
+        books: &mut HashMap<String, Book<O>>,
+        order: &mut O,
+        price: impl Fn(&O) -> Option<O::Pricing>,
+        mut eligible: impl FnMut(&O, Decimal, &O::Pricing) -> bool,
+    ) -> Vec<Fill<O>> {
+        let Some(book) = books.get_mut(&order.key()) else {
+            return Vec::new();
+        };
+        let levels = match order.side() {
+            OrderSide::Buy => &mut book.asks,
+            OrderSide::Sell => &mut book.bids,
+        };
+        let keys: Vec<Decimal> = match order.side() {
+            OrderSide::Buy => levels.keys().copied().collect(),
+            OrderSide::Sell => levels.keys().rev().copied().collect(),
+        };
+
+        let mut fills = Vec::new();
+        for key in keys {
+            if order.remaining() <= Decimal::ZERO {
+                break;
+            }
+            let crosses = match order.side() {
+                OrderSide::Buy => key <= order.level(),
+                OrderSide::Sell => key >= order.level(),
+            };
+            if !crosses {
+                break;
+            }
+            let Some(level) = levels.get_mut(&key) else {
+                continue;
+            };
+            let Some(pricing) = level.front().and_then(&price) else {
+                continue;
+            };
+
+            let mut index = 0;
+            while order.remaining() > Decimal::ZERO && index < level.len() {
+                let resting = &mut level[index];
+                let quantity = order.remaining().min(resting.remaining());
+                if !eligible(resting, quantity, &pricing) {
+                    index += 1;
+                    continue;
+                }
+                order.fill(quantity);
+                resting.fill(quantity);
+                resting.priced(&pricing);
+                self.orders.insert(resting.id(), resting.clone());
+                fills.push(Fill {
+                    resting: resting.clone(),
+                    quantity,
+                    pricing: pricing.clone(),
+                });
+                if resting.remaining() <= Decimal::ZERO {
+                    level.remove(index);
+                } else {
+                    index += 1;
+                }
+            }
+            if level.is_empty() {
+                levels.remove(&key);
+            }
+        }
+        fills
+    }
+
+    /// Places the open remainder of `order` on its book.
+    fn rest(&self, books: &mut HashMap<String, Book<O>>, order: O) {
+        self.orders.insert(order.id(), order.clone());
+        let book = books.entry(order.key()).or_insert_with(|| Book::new(order.book()));
+        book.side_mut(order.side())
+            .entry(order.level())
+            .or_default()
+            .push_back(order);
+    }
+
+    /// Keeps a record of an order that never rested.
+    pub fn record(&self, order: O) {
+        self.orders.insert(order.id(), order);
+    }
+
+    pub fn cancel(&self, order_id: Uuid, reason: CancelReason) -> Option<O> {
+        let mut books = VersionedWriteGuard::new(&self.books, &self.generation);
+        let mut order = self.orders.get(&order_id).map(|order| order.clone())?;
+        if !order.is_open() {
+            return None;
+        }
+
+        if let Some(book) = books.get_mut(&order.key()) {
+            let levels = book.side_mut(order.side());
+            if let Some(level) = levels.get_mut(&order.level()) {
+                level.retain(|resting| resting.id() != order_id);
+                if level.is_empty() {
+                    levels.remove(&order.level());
+                }
+            }
+        }
+
+        order.cancel(reason);
+        self.orders.insert(order_id, order.clone());
+        Some(order)
+    }
+
+    /// Fills `quantity` of a resting order, provided `execute` succeeds
+    /// first. The book stays locked throughout, so the order cannot be
+    /// filled or cancelled by anyone else in between.
+    pub fn fill_with<T>(
+        &self,
+        order_id: Uuid,
+        quantity: Decimal,
+        execute: impl FnOnce(&O) -> Option<T>,
+    ) -> Option<(O, T)> {
+        let mut books = VersionedWriteGuard::new(&self.books, &self.generation);
+        let (level, index) = Self::find(&mut books, &self.orders, order_id)?;
+        if level[index].remaining() < quantity {
+            return None;
+        }
+
+        let result = execute(&level[index])?;
+        level[index].fill(quantity);
+        let filled = level[index].clone();
+        if filled.remaining() <= Decimal::ZERO {
+            level.remove(index);
+        }
+        self.prune(&mut books, &filled);
+        self.orders.insert(order_id, filled.clone());
+        Some((filled, result))
+    }
+
+    /// Changes a resting order in place, keeping its priority. `change`
+    /// returns whether it changed anything; the changed order is returned.
+    pub fn update(&self, order_id: Uuid, change: impl FnOnce(&mut O) -> bool) -> Option<O> {
+        let mut books = VersionedWriteGuard::new(&self.books, &self.generation);
+        let (level, index) = Self::find(&mut books, &self.orders, order_id)?;
+        let resting = &mut level[index];
+        if !change(resting) {
+            return None;
+        }
+        let resting = resting.clone();
+        self.orders.insert(order_id, resting.clone());
+        Some(resting)
+    }
+
+    /// The level a resting order is queued in and its place in it.
+    fn find<'a>(
+        books: &'a mut HashMap<String, Book<O>>,
+        orders: &DashMap<Uuid, O>,
+        order_id: Uuid,
+    ) -> Option<(&'a mut VecDeque<O>, usize)> {
+        let (key, side, level) = {
+            let order = orders.get(&order_id)?;
+            let order = order.value();
+            (order.key(), order.side().clone(), order.level())
+        };
+        let level = books.get_mut(&key)?.side_mut(&side).get_mut(&level)?;
+        let index = level.iter().position(|resting| resting.id() == order_id)?;
+        Some((level, index))
+    }
+
+    /// Drops the level `order` rested at if nothing is left in it.
+    fn prune(&self, books: &mut HashMap<String, Book<O>>, order: &O) {
+        if let Some(book) = books.get_mut(&order.key()) {
+            let levels = book.side_mut(order.side());
+            if levels.get(&order.level()).is_some_and(VecDeque::is_empty) {
+                levels.remove(&order.level());
+            }
+        }
+    }
+
+    /// Open resting orders in the books `include` picks.
+    pub fn resting(&self, include: impl Fn(&O::Book) -> bool) -> Vec<O> {
+        self.books
+            .read()
+            .values()
+            .filter(|book| include(&book.description))
+            .flat_map(|book| book.bids.values().chain(book.asks.values()).flatten())
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, order_id: &Uuid) -> Option<O> {
+        self.orders.get(order_id).map(|order| order.clone())
+    }
+
+    /// Every order, open or not, for which `include` holds.
+    pub fn orders(&self, include: impl Fn(&O) -> bool) -> Vec<O> {
+        self.orders
+            .iter()
+            .filter(|order| include(order))
+            .map(|order| order.clone())
+            .collect()
+    }
+
+    /// One summary per book, sorted by book key.
+    pub fn summaries<S>(&self, summarize: impl Fn(&str, &Book<O>) -> S) -> Vec<S> {
+        let books = self.books.read();
+        let mut keys: Vec<&String> = books.keys().collect();
+        keys.sort();
+        keys.into_iter().map(|key| summarize(key, &books[key])).collect()
+    }
+}
+
+/// Total open quantity across `levels`.
+pub fn depth<O: RestingOrder>(levels: &Levels<O>) -> Decimal {
+    levels.values().flatten().map(RestingOrder::remaining).sum()
+}
@@ -0,0 +1,206 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum ReconciledEntity {
+    Order,
+    Position,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum DiscrepancyKind {
+    MissingInStore,
+    MissingInEngine,
+    Mismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub engine: Value,
+    pub stored: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Discrepancy {
+    pub entity: ReconciledEntity,
+    pub key: String,
+    pub kind: DiscrepancyKind,
+    pub fields: Vec<FieldDiff>,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciliationReport {
+    pub generated_at: DateTime<Utc>,
+    pub orders_checked: usize,
+    pub positions_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    pub applied: bool,
+}
+
+/// Engine-side record paired with whatever the store holds for the same key.
+struct Pair<T> {
+    engine: Option<T>,
+    stored: Option<T>,
+}
+
+impl TradingEngine {
+    /// Compares live orders and positions with the persisted copy. The
+    /// engine is treated as the source of truth: with `apply` set, drifted
+    /// store records are overwritten and orphans deleted.
+    pub async fn reconcile(&self, apply: bool) -> crate::types::Result<ReconciliationReport> {
+        let orders = pair_by_key(
+            self.get_orders(),
+            self.state_store.load_orders().await?,
+            |order| order.id,
+        );
+        let positions = pair_by_key(
+            self.get_positions(None).await,
+            self.state_store.load_positions().await?,
+            |position| (position.account_id, position.symbol.clone()),
+        );
+
+        let mut report = ReconciliationReport {
+            generated_at: self.time_provider.now(),
+            orders_checked: orders.len(),
+            positions_checked: positions.len(),
+            discrepancies: Vec::new(),
+            applied: apply,
+        };
+
+        for (order_id, pair) in &orders {
+            if let Some(mut discrepancy) = diff(ReconciledEntity::Order, order_id.to_string(), pair) {
+                if apply {
+                    discrepancy.repaired = self.repair_order(*order_id, pair).await;
+                }
+                report.discrepancies.push(discrepancy);
+            }
+        }
+
+        for ((account_id, symbol), pair) in &positions {
+            let key = format!("{}/{}", account_id, symbol);
+            if let Some(mut discrepancy) = diff(ReconciledEntity::Position, key, pair) {
+                if apply {
+                    discrepancy.repaired = self.repair_position(*account_id, symbol, pair).await;
+                }
+                report.discrepancies.push(discrepancy);
+            }
+        }
+
+        if !report.discrepancies.is_empty() {
+            warn!(
+                "Reconciliation found {} discrepancies (apply: {})",
+                report.discrepancies.len(),
+                apply
+            );
+        } else {
+            info!("Reconciliation found engine and store in sync");
+        }
+
+        Ok(report)
+    }
+
+    async fn repair_order(&self, order_id: Uuid, pair: &Pair<Order>) -> bool {
+        let result = match &pair.engine {
+            Some(order) => self.state_store.save_order(order).await,
+            None => self.state_store.delete_order(order_id).await,
+        };
+        log_repair(result, &order_id.to_string())
+    }
+
+    async fn repair_position(&self, account_id: Uuid, symbol: &str, pair: &Pair<Position>) -> bool {
+        let result = match &pair.engine {
+            Some(position) => self.state_store.save_position(position).await,
+            None => self.state_store.delete_position(account_id, symbol).await,
+        };
+        log_repair(result, &format!("{}/{}", account_id, symbol))
+    }
+}
+
+fn log_repair(result: crate::types::Result<()>, key: &str) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to repair {}: {}", key, e);
+            false
+        }
+    }
+}
+
+fn pair_by_key<T, K, F>(engine: Vec<T>, stored: Vec<T>, key: F) -> HashMap<K, Pair<T>>
+where
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let mut pairs: HashMap<K, Pair<T>> = HashMap::new();
+    for record in engine {
+        let key = key(&record);
+        pairs
+            .entry(key)
+            .or_insert(Pair { engine: None, stored: None })
+            .engine = Some(record);
+    }
+    for record in stored {
+        let key = key(&record);
+        pairs
+            .entry(key)
+            .or_insert(Pair { engine: None, stored: None })
+            .stored = Some(record);
+    }
+    pairs
+}
+
+fn diff<T: Serialize>(entity: ReconciledEntity, key: String, pair: &Pair<T>) -> Option<Discrepancy> {
+    let (kind, fields) = match (&pair.engine, &pair.stored) {
+        (Some(_), None) => (DiscrepancyKind::MissingInStore, Vec::new()),
+        (None, Some(_)) => (DiscrepancyKind::MissingInEngine, Vec::new()),
+        (Some(engine), Some(stored)) => {
+            let fields = field_diffs(to_value(engine), to_value(stored));
+            if fields.is_empty() {
+                return None;
+            }
+            (DiscrepancyKind::Mismatch, fields)
+        }
+        (None, None) => return None,
+    };
+
+    Some(Discrepancy {
+        entity,
+        key,
+        kind,
+        fields,
+        repaired: false,
+    })
+}
+
+fn to_value<T: Serialize>(record: &T) -> Value {
+    serde_json::to_value(record).unwrap_or(Value::Null)
+}
+
+/// Top-level field comparison of two serialized records.
+fn field_diffs(engine: Value, stored: Value) -> Vec<FieldDiff> {
+    let (Value::Object(engine), Value::Object(stored)) = (engine, stored) else {
+        return Vec::new();
+    };
+
+    let fields: BTreeSet<&String> = engine.keys().chain(stored.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let engine_value = engine.get(field).cloned().unwrap_or(Value::Null);
+            let stored_value = stored.get(field).cloned().unwrap_or(Value::Null);
+            (engine_value != stored_value).then(|| FieldDiff {
+                field: field.clone(),
+                engine: engine_value,
+                stored: stored_value,
+            })
+        })
+        .collect()
+}
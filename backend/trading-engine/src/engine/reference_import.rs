@@ -0,0 +1,465 @@
+//! Bulk reference data loads from ISIN master files. Each CSV row describes
+//! one bond; rows for symbols that are not listed create the instrument,
+//! rows for listed symbols amend its master fields. A dry run reports the
+//! same diff without applying it.
+
+use crate::{
+    engine::{allocation::MatchingAlgorithm, instruments::InstrumentStatus, TradingEngine},
+    types::*,
+    utils::daycount::DayCount,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+/// One bond in a master file. `symbol` defaults to the ISIN.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MasterRow {
+    pub isin: String,
+    pub symbol: Option<String>,
+    pub issuer: String,
+    pub maturity_date: NaiveDate,
+    /// Annual coupon in percent.
+    pub coupon_rate: Decimal,
+    pub face_value: Option<Decimal>,
+    pub bond_type: BondType,
+    pub day_count: Option<DayCount>,
+    pub coupon_frequency: Option<u32>,
+    pub rating: Option<String>,
+}
+
+impl MasterRow {
+    fn symbol(&self) -> String {
+        self.symbol
+            .clone()
+            .filter(|symbol| !symbol.is_empty())
+            .unwrap_or_else(|| self.isin.clone())
+    }
+
+    fn validate(&self, today: NaiveDate) -> std::result::Result<(), String> {
+        if !valid_isin(&self.isin) {
+            return Err(format!("{} is not a valid ISIN", self.isin));
+        }
+        if self.issuer.trim().is_empty() {
+            return Err("Issuer must not be empty".to_string());
+        }
+        if self.maturity_date <= today {
+            return Err(format!("Maturity {} is not in the future", self.maturity_date));
+        }
+        if self.coupon_rate < Decimal::ZERO || self.coupon_rate > Decimal::ONE_HUNDRED {
+            return Err(format!("Coupon rate {} is outside 0-100%", self.coupon_rate));
+        }
+        if self.face_value.is_some_and(|face_value| face_value <= Decimal::ZERO) {
+            return Err("Face value must be positive".to_string());
+        }
+        if self.coupon_frequency.is_some_and(|frequency| ![0, 1, 2, 4, 12].contains(&frequency)) {
+            return Err("Coupon frequency must be 0, 1, 2, 4 or 12".to_string());
+        }
+        Ok(())
+    }
+
+    /// Writes the row's fields over `bond`, leaving fields the master file
+    /// does not carry as they are.
+    fn apply_to(&self, bond: &mut Bond) {
+        bond.isin = self.isin.clone();
+        bond.issuer = self.issuer.clone();
+        bond.maturity_date = self.maturity_date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        bond.coupon_rate = self.coupon_rate;
+        bond.bond_type = self.bond_type.clone();
+        bond.rating = self.rating.clone().filter(|rating| !rating.is_empty());
+        if let Some(face_value) = self.face_value {
+            bond.face_value = face_value;
+        }
+        if let Some(day_count) = self.day_count {
+            bond.day_count = day_count;
+        }
+        if let Some(coupon_frequency) = self.coupon_frequency {
+            bond.coupon_frequency = coupon_frequency;
+        }
+    }
+
+    fn new_bond(&self) -> Bond {
+        let mut bond = Bond {
+            isin: String::new(),
+            symbol: self.symbol(),
+            issuer: String::new(),
+            maturity_date: DateTime::<Utc>::default(),
+            coupon_rate: Decimal::ZERO,
+            face_value: Decimal::ONE_HUNDRED,
+            bond_type: self.bond_type.clone(),
+            rating: None,
+            is_active: true,
+            quote_convention: QuoteConvention::default(),
+            coupon_frequency: if self.bond_type.is_money_market() { 0 } else { 2 },
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        };
+        self.apply_to(&mut bond);
+        bond
+    }
+}
+
+/// ISO 6166: two letters, nine alphanumerics and a Luhn check digit over
+/// the letters expanded to numbers (A = 10 ... Z = 35).
+fn valid_isin(isin: &str) -> bool {
+    let bytes = isin.as_bytes();
+    if bytes.len() != 12
+        || !bytes[..2].iter().all(u8::is_ascii_uppercase)
+        || !bytes[2..11].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        || !bytes[11].is_ascii_digit()
+    {
+        return false;
+    }
+
+    let digits: Vec<u32> = isin
+        .chars()
+        .filter_map(|c| c.to_digit(36))
+        .flat_map(|value| {
+            if value >= 10 {
+                vec![value / 10, value % 10]
+            } else {
+                vec![value]
+            }
+        })
+        .collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum InstrumentImportAction {
+    Create,
+    Update,
+    Unchanged,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentImportRow {
+    /// 1-based position among the data rows of the file.
+    pub row: usize,
+    pub isin: Option<String>,
+    pub symbol: Option<String>,
+    pub action: InstrumentImportAction,
+    pub changes: Vec<FieldChange>,
+    pub error: Option<String>,
+}
+
+impl InstrumentImportRow {
+    fn rejected(row: usize, master: Option<&MasterRow>, error: String) -> Self {
+        Self {
+            row,
+            isin: master.map(|master| master.isin.clone()),
+            symbol: master.map(MasterRow::symbol),
+            action: InstrumentImportAction::Rejected,
+            changes: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub rejected: usize,
+    pub results: Vec<InstrumentImportRow>,
+}
+
+/// An applied import, as kept in the history log.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentImportRecord {
+    pub id: Uuid,
+    pub imported_at: DateTime<Utc>,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub rejected: usize,
+    /// Symbols created or updated.
+    pub symbols: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct InstrumentImportLog {
+    records: RwLock<Vec<InstrumentImportRecord>>,
+}
+
+impl InstrumentImportLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most recent first.
+    pub fn history(&self) -> Vec<InstrumentImportRecord> {
+        self.records.read().iter().rev().cloned().collect()
+    }
+}
+
+/// The master fields of `current` that `updated` changes.
+fn diff(current: &Bond, updated: &Bond) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &'static str, from: serde_json::Value, to: serde_json::Value| {
+        if from != to {
+            changes.push(FieldChange { field, from, to });
+        }
+    };
+    compare("isin", json(&current.isin), json(&updated.isin));
+    compare("issuer", json(&current.issuer), json(&updated.issuer));
+    compare("maturity_date", json(&current.maturity_date), json(&updated.maturity_date));
+    compare("coupon_rate", json(&current.coupon_rate), json(&updated.coupon_rate));
+    compare("face_value", json(&current.face_value), json(&updated.face_value));
+    compare("bond_type", json(&current.bond_type), json(&updated.bond_type));
+    compare("day_count", json(&current.day_count), json(&updated.day_count));
+    compare("coupon_frequency", json(&current.coupon_frequency), json(&updated.coupon_frequency));
+    compare("rating", json(&current.rating), json(&updated.rating));
+    changes
+}
+
+fn json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+/// What applying a valid row would do.
+struct StagedRow {
+    symbol: String,
+    action: InstrumentImportAction,
+    changes: Vec<FieldChange>,
+    bond: Bond,
+}
+
+impl TradingEngine {
+    pub fn instrument_imports(&self) -> &InstrumentImportLog {
+        &self.instrument_imports
+    }
+
+    /// Creates or amends instruments from an ISIN master CSV. Every row is
+    /// validated and diffed against the listed instrument; bad rows are
+    /// reported without stopping the rest. With `dry_run` the diff is only
+    /// returned, otherwise it is applied and the import logged.
+    pub fn import_instruments(&self, body: &str, dry_run: bool) -> Result<InstrumentImportReport> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body.as_bytes());
+        let rows: Vec<std::result::Result<MasterRow, String>> = reader
+            .deserialize::<MasterRow>()
+            .map(|row| row.map_err(|e| e.to_string()))
+            .collect();
+        if rows.len() > self.config.instrument_import_max_rows {
+            return Err(TradingError::InvalidRequest(format!(
+                "Import has {} rows, the limit is {}",
+                rows.len(),
+                self.config.instrument_import_max_rows
+            )));
+        }
+
+        let now = self.time_provider.now();
+        let listed_isins: HashMap<String, String> = self
+            .instruments
+            .list()
+            .into_iter()
+            .filter(|instrument| instrument.status != InstrumentStatus::Delisted)
+            .map(|instrument| (instrument.bond.isin, instrument.bond.symbol))
+            .collect();
+        let (mut seen_symbols, mut seen_isins) = (HashSet::new(), HashSet::new());
+        let mut results = Vec::with_capacity(rows.len());
+        for (index, row) in rows.into_iter().enumerate() {
+            let row_number = index + 1;
+            let master = match row {
+                Ok(master) => master,
+                Err(error) => {
+                    results.push(InstrumentImportRow::rejected(row_number, None, error));
+                    continue;
+                }
+            };
+            if !seen_symbols.insert(master.symbol()) || !seen_isins.insert(master.isin.clone()) {
+                let error = "Symbol or ISIN appears more than once in the import".to_string();
+                results.push(InstrumentImportRow::rejected(row_number, Some(&master), error));
+                continue;
+            }
+
+            let outcome = self
+                .stage_master_row(&master, now.date_naive(), &listed_isins)
+                .and_then(|staged| if dry_run { Ok(staged) } else { self.apply_master_row(staged) });
+            results.push(match outcome {
+                Ok(staged) => InstrumentImportRow {
+                    row: row_number,
+                    isin: Some(master.isin),
+                    symbol: Some(staged.symbol),
+                    action: staged.action,
+                    changes: staged.changes,
+                    error: None,
+                },
+                Err(error) => InstrumentImportRow::rejected(row_number, Some(&master), error),
+            });
+        }
+
+        let count = |action| results.iter().filter(|result| result.action == action).count();
+        let report = InstrumentImportReport {
+            dry_run,
+            total: results.len(),
+            created: count(InstrumentImportAction::Create),
+            updated: count(InstrumentImportAction::Update),
+            unchanged: count(InstrumentImportAction::Unchanged),
+            rejected: count(InstrumentImportAction::Rejected),
+            results,
+        };
+        if !dry_run {
+            self.instrument_imports.records.write().push(InstrumentImportRecord {
+                id: Uuid::new_v4(),
+                imported_at: now,
+                total: report.total,
+                created: report.created,
+                updated: report.updated,
+                unchanged: report.unchanged,
+                rejected: report.rejected,
+                symbols: report
+                    .results
+                    .iter()
+                    .filter(|result| {
+                        matches!(result.action, InstrumentImportAction::Create | InstrumentImportAction::Update)
+                    })
+                    .filter_map(|result| result.symbol.clone())
+                    .collect(),
+            });
+            info!(
+                "Instrument import: {} created, {} updated, {} rejected",
+                report.created, report.updated, report.rejected
+            );
+        }
+        Ok(report)
+    }
+
+    fn stage_master_row(
+        &self,
+        master: &MasterRow,
+        today: NaiveDate,
+        listed_isins: &HashMap<String, String>,
+    ) -> std::result::Result<StagedRow, String> {
+        master.validate(today)?;
+        let symbol = master.symbol();
+        if let Some(other) = listed_isins.get(&master.isin).filter(|other| **other != symbol) {
+            return Err(format!("ISIN {} is already listed as {}", master.isin, other));
+        }
+
+        match self.instruments.instrument(&symbol) {
+            Some(instrument) if instrument.status != InstrumentStatus::Delisted => {
+                if instrument.bond.isin != master.isin {
+                    return Err(format!("{} is listed under ISIN {}", symbol, instrument.bond.isin));
+                }
+                let mut bond = instrument.bond.clone();
+                master.apply_to(&mut bond);
+                let changes = diff(&instrument.bond, &bond);
+                let action = if changes.is_empty() {
+                    InstrumentImportAction::Unchanged
+                } else {
+                    InstrumentImportAction::Update
+                };
+                Ok(StagedRow {
+                    symbol,
+                    action,
+                    changes,
+                    bond,
+                })
+            }
+            _ => Ok(StagedRow {
+                symbol,
+                action: InstrumentImportAction::Create,
+                changes: Vec::new(),
+                bond: master.new_bond(),
+            }),
+        }
+    }
+
+    fn apply_master_row(&self, staged: StagedRow) -> std::result::Result<StagedRow, String> {
+        let applied = match staged.action {
+            InstrumentImportAction::Create => self
+                .list_instrument(staged.bond.clone(), MatchingAlgorithm::default())
+                .map(|_| ()),
+            InstrumentImportAction::Update => {
+                let bond = staged.bond.clone();
+                self.instruments.update_bond(&staged.symbol, |current| *current = bond).map(|_| ())
+            }
+            InstrumentImportAction::Unchanged | InstrumentImportAction::Rejected => Ok(()),
+        };
+        applied.map(|_| staged).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    const HEADER: &str =
+        "isin,symbol,issuer,maturity_date,coupon_rate,face_value,bond_type,day_count,coupon_frequency,rating";
+
+    #[test]
+    fn test_isin_check_digit() {
+        assert!(valid_isin("IN0020230085"));
+        assert!(valid_isin("US0378331005"));
+        assert!(!valid_isin("US0378331006"));
+        assert!(!valid_isin("in0020230085"));
+    }
+
+    #[tokio::test]
+    async fn test_master_file_import_previews_then_applies() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let first = format!(
+            "{HEADER}\n\
+             IN0020230085,GS2033,Government of India,2033-06-17,7.18,100,GovernmentSecurity,30E/360,2,\n\
+             US0378331006,BAD,Apple,2040-01-01,3.00,,CorporateBond,,,\n"
+        );
+        let report = engine.import_instruments(&first, false).unwrap();
+        assert_eq!((report.created, report.rejected), (1, 1));
+        assert!(report.results[1].error.as_deref().unwrap().contains("ISIN"));
+
+        let amended = format!(
+            "{HEADER}\n\
+             IN0020230085,GS2033,Government of India,2033-06-17,7.26,100,GovernmentSecurity,30E/360,2,SOV\n"
+        );
+        let preview = engine.import_instruments(&amended, true).unwrap();
+        assert_eq!(preview.updated, 1);
+        let fields: Vec<&str> = preview.results[0].changes.iter().map(|change| change.field).collect();
+        assert_eq!(fields, vec!["coupon_rate", "rating"]);
+        assert_eq!(engine.instruments.get("GS2033").unwrap().coupon_rate, dec!(7.18));
+
+        engine.import_instruments(&amended, false).unwrap();
+        let bond = engine.instruments.get("GS2033").unwrap();
+        assert_eq!((bond.coupon_rate, bond.rating.as_deref()), (dec!(7.26), Some("SOV")));
+        let history = engine.instrument_imports().history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].symbols, vec!["GS2033".to_string()]);
+    }
+}
@@ -0,0 +1,192 @@
+//! Built-in reference market maker for demo environments and integration
+//! tests that need two-sided liquidity. It keeps one bid and one ask around
+//! the reference price of each configured symbol, entered and cancelled
+//! through the same order API as any client, so its quotes pass validation
+//! and risk checks and trade like anyone else's. It is off unless symbols
+//! are configured, and never runs in production.
+
+use crate::{engine::TradingEngine, types::*};
+use dashmap::DashMap;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Metadata key marking the reference market maker's orders.
+pub const REFERENCE_MM_KEY: &str = "reference_mm";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceQuote {
+    pub symbol: String,
+    pub bid: Option<Order>,
+    pub ask: Option<Order>,
+}
+
+/// The order currently quoting each symbol and side.
+#[derive(Default)]
+pub struct ReferenceMarketMaker {
+    quotes: DashMap<(String, bool), Uuid>,
+    next_quote: AtomicU64,
+}
+
+impl ReferenceMarketMaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn quote(&self, symbol: &str, side: &OrderSide) -> Option<Uuid> {
+        self.quotes
+            .get(&(symbol.to_string(), *side == OrderSide::Buy))
+            .map(|order_id| *order_id)
+    }
+}
+
+impl TradingEngine {
+    /// Re-centres the quotes on each symbol's reference price. A quote
+    /// still open at the right price is left alone; one that moved is
+    /// cancelled and entered again, and one that filled is replaced.
+    /// Returns how many quotes were entered.
+    pub async fn refresh_reference_quotes(&self) -> usize {
+        let half_spread = self.config.reference_mm_spread_bps / Decimal::from(20_000);
+        let mut entered = 0;
+        for symbol in &self.config.reference_mm_symbols {
+            let Ok(reference) = self.reference_price(symbol) else {
+                continue;
+            };
+            let decimals = self.matching_engine.tick_scale(symbol).price_decimals;
+            let offset = reference.price * half_spread;
+            let bid = (reference.price - offset).round_dp_with_strategy(decimals, RoundingStrategy::ToNegativeInfinity);
+            let ask = (reference.price + offset).round_dp_with_strategy(decimals, RoundingStrategy::ToPositiveInfinity);
+            for (side, price) in [(OrderSide::Buy, bid), (OrderSide::Sell, ask)] {
+                if self.requote(symbol, side, price).await {
+                    entered += 1;
+                }
+            }
+        }
+        entered
+    }
+
+    async fn requote(&self, symbol: &str, side: OrderSide, price: Decimal) -> bool {
+        let current = self
+            .reference_mm
+            .quote(symbol, &side)
+            .and_then(|order_id| self.get_order(&order_id))
+            .filter(|order| order.is_open());
+        if let Some(order) = current {
+            if order.price == Some(price) {
+                return false;
+            }
+            if let Err(e) = self.cancel_order(order.id).await {
+                warn!("Reference market maker could not pull its quote {}: {}", order.id, e);
+                return false;
+            }
+        }
+
+        let account_id = self.config.reference_mm_account_id;
+        let quantity = self.config.reference_mm_quote_size;
+        let sequence = self.reference_mm.next_quote.fetch_add(1, Ordering::Relaxed) + 1;
+        let order = Order {
+            id: Uuid::new_v4(),
+            client_order_id: format!("REFMM-{}-{}", symbol, sequence),
+            symbol: symbol.to_string(),
+            side: side.clone(),
+            order_type: OrderType::Limit,
+            quantity,
+            price: Some(price),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: quantity,
+            status: OrderStatus::Pending,
+            time_in_force: TimeInForce::GoodTillCancel,
+            timestamp: self.time_provider.now(),
+            user_id: account_id,
+            account_id,
+            metadata: HashMap::from([(REFERENCE_MM_KEY.to_string(), "true".to_string())]),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        };
+        let key = (symbol.to_string(), side == OrderSide::Buy);
+        match self.submit_order(order).await {
+            Ok(order_id) => {
+                self.reference_mm.quotes.insert(key, order_id);
+                true
+            }
+            Err(e) => {
+                warn!("Reference market maker could not quote {} {:?}: {}", symbol, side, e);
+                self.reference_mm.quotes.remove(&key);
+                false
+            }
+        }
+    }
+
+    /// The reference market maker's current quotes, per configured symbol.
+    pub fn reference_quotes(&self) -> Vec<ReferenceQuote> {
+        let open = |symbol: &str, side: OrderSide| {
+            self.reference_mm
+                .quote(symbol, &side)
+                .and_then(|order_id| self.get_order(&order_id))
+                .filter(|order| order.is_open())
+        };
+        self.config
+            .reference_mm_symbols
+            .iter()
+            .map(|symbol| ReferenceQuote {
+                symbol: symbol.clone(),
+                bid: open(symbol, OrderSide::Buy),
+                ask: open(symbol, OrderSide::Sell),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_quotes_follow_the_reference_price() {
+        let config = Config {
+            reference_mm_symbols: vec!["GSEC10Y".to_string()],
+            reference_mm_account_id: Uuid::new_v4(),
+            reference_mm_spread_bps: dec!(20),
+            reference_mm_quote_size: dec!(100000),
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        assert_eq!(engine.refresh_reference_quotes().await, 0);
+
+        engine.reference_prices().set_external("GSEC10Y", dec!(100.00), Utc::now());
+        assert_eq!(engine.refresh_reference_quotes().await, 2);
+        assert_eq!(engine.matching_engine.get_best_bid("GSEC10Y"), Some(dec!(99.90)));
+        assert_eq!(engine.matching_engine.get_best_ask("GSEC10Y"), Some(dec!(100.10)));
+        // Centred on its own mid, nothing moves
+        assert_eq!(engine.refresh_reference_quotes().await, 0);
+
+        // A client lifts the offer; both sides follow the last trade
+        let lift = OrderBuilder::new(OrderSide::Buy)
+            .price(dec!(100.10))
+            .time_in_force(TimeInForce::ImmediateOrCancel)
+            .build();
+        engine.submit_order(lift).await.unwrap();
+        assert_eq!(engine.refresh_reference_quotes().await, 2);
+        let quotes = &engine.reference_quotes()[0];
+        assert_eq!(quotes.bid.as_ref().and_then(|bid| bid.price), Some(dec!(99.9999)));
+        assert_eq!(quotes.ask.as_ref().and_then(|ask| ask.price), Some(dec!(100.2001)));
+        let entered: Vec<Order> = engine
+            .get_orders()
+            .into_iter()
+            .filter(|order| order.metadata.contains_key(REFERENCE_MM_KEY))
+            .collect();
+        assert_eq!(entered.len(), 4);
+        assert_eq!(entered.iter().filter(|order| order.status == OrderStatus::Cancelled).count(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{config::Config, engine::TradingEngine, types::*};
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceSource {
+    LastTrade,
+    Mid,
+    PreviousClose,
+    External,
+}
+
+impl ReferenceSource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "last_trade" => Some(ReferenceSource::LastTrade),
+            "mid" => Some(ReferenceSource::Mid),
+            "previous_close" => Some(ReferenceSource::PreviousClose),
+            "external" => Some(ReferenceSource::External),
+            _ => None,
+        }
+    }
+}
+
+/// What one source offered when a reference price was resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceCandidate {
+    pub source: ReferenceSource,
+    pub price: Option<Decimal>,
+    pub as_of: Option<DateTime<Utc>>,
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferencePrice {
+    pub symbol: String,
+    pub price: Decimal,
+    pub source: ReferenceSource,
+    pub as_of: DateTime<Utc>,
+    /// Every configured source in fallback order, including the one used.
+    pub candidates: Vec<ReferenceCandidate>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Observation {
+    price: Decimal,
+    as_of: DateTime<Utc>,
+}
+
+/// Holds the timestamped inputs for reference prices and picks the first
+/// fresh one in the configured source order. The book mid is supplied by
+/// the caller at resolution time since it is always current.
+pub struct ReferencePriceService {
+    config: Arc<Config>,
+    last_trades: DashMap<String, Observation>,
+    previous_closes: DashMap<String, Observation>,
+    external: DashMap<String, Observation>,
+    /// Latest full quote from an external feed, clean prices.
+    external_quotes: DashMap<String, MarketData>,
+}
+
+impl ReferencePriceService {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            last_trades: DashMap::new(),
+            previous_closes: DashMap::new(),
+            external: DashMap::new(),
+            external_quotes: DashMap::new(),
+        }
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        self.last_trades.insert(
+            trade.symbol.clone(),
+            Observation {
+                price: trade.price,
+                as_of: trade.timestamp,
+            },
+        );
+    }
+
+    pub fn set_previous_close(&self, symbol: &str, price: Decimal, as_of: DateTime<Utc>) {
+        self.previous_closes
+            .insert(symbol.to_string(), Observation { price, as_of });
+    }
+
+    pub fn previous_close(&self, symbol: &str) -> Option<Decimal> {
+        self.previous_closes.get(symbol).map(|close| close.price)
+    }
+
+    pub fn set_external(&self, symbol: &str, price: Decimal, as_of: DateTime<Utc>) {
+        self.external
+            .insert(symbol.to_string(), Observation { price, as_of });
+    }
+
+    /// Takes an external quote's last price, or its mid, as the external
+    /// reference price. Returns the price taken.
+    pub fn set_external_quote(&self, quote: MarketData) -> Option<Decimal> {
+        let mid = quote.bid_price.zip(quote.ask_price).map(|(bid, ask)| (bid + ask) / Decimal::TWO);
+        let price = quote.last_price.or(mid)?;
+        self.set_external(&quote.symbol, price, quote.timestamp);
+        self.external_quotes.insert(quote.symbol.clone(), quote);
+        Some(price)
+    }
+
+    /// The latest external quote, unless older than the external price's
+    /// maximum age.
+    pub fn external_quote(&self, symbol: &str, now: DateTime<Utc>) -> Option<MarketData> {
+        let quote = self.external_quotes.get(symbol)?;
+        let max_age = self.max_age(ReferenceSource::External)?;
+        (now - quote.timestamp <= max_age).then(|| quote.clone())
+    }
+
+    /// How long a source's price stays usable. The mid and the previous
+    /// close never go stale; the close is replaced at the end of each day.
+    fn max_age(&self, source: ReferenceSource) -> Option<Duration> {
+        match source {
+            ReferenceSource::LastTrade => Some(Duration::seconds(self.config.reference_last_trade_max_age_secs)),
+            ReferenceSource::External => Some(Duration::seconds(self.config.reference_external_max_age_secs)),
+            ReferenceSource::Mid | ReferenceSource::PreviousClose => None,
+        }
+    }
+
+    pub fn resolve(&self, symbol: &str, mid: Option<Decimal>, now: DateTime<Utc>) -> Option<ReferencePrice> {
+        let candidates: Vec<ReferenceCandidate> = self
+            .config
+            .reference_price_sources
+            .iter()
+            .map(|&source| {
+                let observation = match source {
+                    ReferenceSource::LastTrade => self.last_trades.get(symbol).map(|entry| *entry),
+                    ReferenceSource::PreviousClose => self.previous_closes.get(symbol).map(|entry| *entry),
+                    ReferenceSource::External => self.external.get(symbol).map(|entry| *entry),
+                    ReferenceSource::Mid => mid.map(|price| Observation { price, as_of: now }),
+                };
+                let stale = observation
+                    .zip(self.max_age(source))
+                    .is_some_and(|(observation, max_age)| now - observation.as_of > max_age);
+                ReferenceCandidate {
+                    source,
+                    price: observation.map(|observation| observation.price),
+                    as_of: observation.map(|observation| observation.as_of),
+                    stale,
+                }
+            })
+            .collect();
+
+        let chosen = candidates.iter().find(|candidate| candidate.price.is_some() && !candidate.stale)?;
+        Some(ReferencePrice {
+            symbol: symbol.to_string(),
+            price: chosen.price?,
+            source: chosen.source,
+            as_of: chosen.as_of?,
+            candidates: candidates.clone(),
+        })
+    }
+}
+
+impl TradingEngine {
+    pub fn reference_price(&self, symbol: &str) -> crate::types::Result<ReferencePrice> {
+        let mid = match (
+            self.matching_engine.get_best_bid(symbol),
+            self.matching_engine.get_best_ask(symbol),
+        ) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / Decimal::TWO),
+            _ => None,
+        };
+        self.reference_prices
+            .resolve(symbol, mid, self.time_provider.now())
+            .ok_or_else(|| TradingError::InstrumentNotFound(format!("No reference price for {}", symbol)))
+    }
+
+    pub fn reference_prices(&self) -> &ReferencePriceService {
+        &self.reference_prices
+    }
+
+    /// Takes a normalized quote from an external feed into the reference
+    /// prices and remarks open positions in the symbol to the resulting
+    /// reference price.
+    pub async fn ingest_market_data(&self, quote: MarketData) -> crate::types::Result<()> {
+        let symbol = quote.symbol.clone();
+        if self.instruments.get(&symbol).is_none() {
+            return Err(TradingError::InstrumentNotFound(symbol));
+        }
+        let price = self
+            .reference_prices
+            .set_external_quote(quote)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Quote for {} has no price", symbol)))?;
+        let mark = self.reference_price(&symbol).map(|reference| reference.price).unwrap_or(price);
+        let index_ratio = self.settlement_index_ratio(&symbol).unwrap_or(Decimal::ONE);
+        self.position_manager.revalue(&symbol, mark, index_ratio).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_stale_sources_fall_back_in_configured_order() {
+        let config = Config {
+            reference_price_sources: vec![
+                ReferenceSource::External,
+                ReferenceSource::Mid,
+                ReferenceSource::PreviousClose,
+            ],
+            reference_external_max_age_secs: 60,
+            ..Config::default()
+        };
+        let service = ReferencePriceService::new(Arc::new(config));
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+
+        service.set_previous_close("GSEC10Y", dec!(99.10), now - Duration::hours(18));
+        service.set_external("GSEC10Y", dec!(99.30), now - Duration::seconds(30));
+        let fresh = service.resolve("GSEC10Y", Some(dec!(99.20)), now).unwrap();
+        assert_eq!((fresh.source, fresh.price), (ReferenceSource::External, dec!(99.30)));
+
+        // Two minutes on the feed has gone quiet and the book is one-sided
+        let later = now + Duration::minutes(2);
+        let fallback = service.resolve("GSEC10Y", None, later).unwrap();
+        assert_eq!((fallback.source, fallback.price), (ReferenceSource::PreviousClose, dec!(99.10)));
+        assert!(fallback.candidates[0].stale);
+
+        assert!(service.resolve("GSEC2Y", None, later).is_none());
+    }
+}
@@ -0,0 +1,341 @@
+//! Retention windows for the engine's data. Each run moves data that has
+//! outlived its class's window into the archive sink and removes it from
+//! the live stores; archives are read back on demand for investigations.
+//!
+//! The event journal is bounded by count rather than age, so audit events
+//! are archived as they arrive instead of when they age out, and only
+//! pruned from the journal once past the window.
+
+use crate::{
+    engine::{trade_store::TradeQuery, TradingEngine},
+    persistence::archive::{
+        batch_key, batch_range, decode_batch, encode_batch, ArchiveSink, ArchivedRecord, DataClass,
+    },
+    types::*,
+};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// What one run did for one class of data.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassRun {
+    pub class: DataClass,
+    /// Records older than this were due.
+    pub cutoff: DateTime<Utc>,
+    pub archived: usize,
+    /// Records removed from the live stores.
+    pub removed: usize,
+    /// Archive object written, if any.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveRun {
+    pub started_at: DateTime<Utc>,
+    pub classes: Vec<ClassRun>,
+    /// Audit events evicted from the journal before they could be archived.
+    pub audit_events_missed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionStatus {
+    pub sink_configured: bool,
+    pub windows: Vec<(DataClass, u32)>,
+    pub last_run: Option<ArchiveRun>,
+}
+
+/// Filters for reading an archive back. Unset bounds are open.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ArchiveQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub symbol: Option<String>,
+    /// Matches any account field in the record.
+    pub account_id: Option<Uuid>,
+    /// Keeps the oldest matches. Defaults to 1000.
+    pub limit: Option<usize>,
+}
+
+const DEFAULT_QUERY_LIMIT: usize = 1000;
+
+impl ArchiveQuery {
+    fn matches(&self, record: &ArchivedRecord) -> bool {
+        self.from.is_none_or(|from| record.at >= from)
+            && self.to.is_none_or(|to| record.at <= to)
+            && self
+                .symbol
+                .as_ref()
+                .is_none_or(|symbol| mentions(&record.record, &|key, value| {
+                    key == "symbol" && value.as_str() == Some(symbol)
+                }))
+            && self.account_id.is_none_or(|id| {
+                let id = id.to_string();
+                mentions(&record.record, &|key, value| match key {
+                    "account_id" | "buyer_account_id" | "seller_account_id" => value.as_str() == Some(&id),
+                    "account_ids" => value
+                        .as_array()
+                        .is_some_and(|ids| ids.iter().any(|account| account.as_str() == Some(&id))),
+                    _ => false,
+                })
+            })
+    }
+
+    /// Whether a batch covering `range` can hold a match.
+    fn overlaps(&self, (first, last): (DateTime<Utc>, DateTime<Utc>)) -> bool {
+        self.from.is_none_or(|from| last >= from) && self.to.is_none_or(|to| first <= to)
+    }
+}
+
+/// Whether any field anywhere in `value` satisfies `field`. Records of
+/// every class are searched the same way, since events and snapshots nest
+/// the orders and trades they carry.
+fn mentions(value: &serde_json::Value, field: &dyn Fn(&str, &serde_json::Value) -> bool) -> bool {
+    match value {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .any(|(key, value)| field(key, value) || mentions(value, field)),
+        serde_json::Value::Array(items) => items.iter().any(|item| mentions(item, field)),
+        _ => false,
+    }
+}
+
+pub struct Archiver {
+    sink: Option<Arc<dyn ArchiveSink>>,
+    /// Last journal sequence archived.
+    audit_cursor: AtomicU64,
+    last_run: RwLock<Option<ArchiveRun>>,
+}
+
+impl Archiver {
+    pub fn new(sink: Option<Arc<dyn ArchiveSink>>) -> Self {
+        Self {
+            sink,
+            audit_cursor: AtomicU64::new(0),
+            last_run: RwLock::new(None),
+        }
+    }
+
+    pub fn has_sink(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    fn sink(&self) -> Result<&Arc<dyn ArchiveSink>> {
+        self.sink
+            .as_ref()
+            .ok_or_else(|| TradingError::InvalidRequest("No archive sink is configured".to_string()))
+    }
+
+    /// Writes `records` as one batch. Returns the key, or `None` when there
+    /// was nothing to write.
+    async fn put_batch(&self, class: DataClass, records: &[ArchivedRecord]) -> Result<Option<String>> {
+        let (Some(first), Some(last)) = (
+            records.iter().map(|record| record.at).min(),
+            records.iter().map(|record| record.at).max(),
+        ) else {
+            return Ok(None);
+        };
+        let key = batch_key(class, first, last);
+        self.sink()?.put(&key, encode_batch(records)?).await?;
+        Ok(Some(key))
+    }
+}
+
+fn to_record<T: Serialize>(at: DateTime<Utc>, record: &T) -> Result<ArchivedRecord> {
+    Ok(ArchivedRecord {
+        at,
+        record: serde_json::to_value(record).map_err(|e| TradingError::InternalError(e.to_string()))?,
+    })
+}
+
+impl TradingEngine {
+    pub fn retention_status(&self) -> RetentionStatus {
+        RetentionStatus {
+            sink_configured: self.archiver.sink.is_some(),
+            windows: DataClass::ALL
+                .into_iter()
+                .filter_map(|class| Some((class, *self.config.retention_days.get(&class)?)))
+                .collect(),
+            last_run: self.archiver.last_run.read().clone(),
+        }
+    }
+
+    fn retention_cutoff(&self, class: DataClass, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = *self.config.retention_days.get(&class)?;
+        Some(now - Duration::days(i64::from(days)))
+    }
+
+    /// Archives and removes everything past its retention window. Data is
+    /// only removed once the batch holding it has been written, so a failed
+    /// run leaves it live for the next one.
+    pub async fn run_retention(&self) -> Result<ArchiveRun> {
+        self.archiver.sink()?;
+        let now = self.time_provider.now();
+        let mut run = ArchiveRun {
+            started_at: now,
+            classes: Vec::new(),
+            audit_events_missed: 0,
+        };
+
+        for class in DataClass::ALL {
+            let Some(cutoff) = self.retention_cutoff(class, now) else {
+                continue;
+            };
+            let class_run = match class {
+                DataClass::Orders => self.archive_orders(cutoff).await?,
+                DataClass::Trades => self.archive_trades(cutoff).await?,
+                DataClass::Audit => {
+                    let (class_run, missed) = self.archive_audit(cutoff).await?;
+                    run.audit_events_missed = missed;
+                    class_run
+                }
+                DataClass::BookSnapshots => self.archive_book_snapshot(now, cutoff).await?,
+            };
+            run.classes.push(class_run);
+        }
+
+        info!(
+            "Retention run archived {} records",
+            run.classes.iter().map(|class| class.archived).sum::<usize>()
+        );
+        *self.archiver.last_run.write() = Some(run.clone());
+        Ok(run)
+    }
+
+    /// Orders no longer on the book and entered before `cutoff`.
+    async fn archive_orders(&self, cutoff: DateTime<Utc>) -> Result<ClassRun> {
+        let aged: Vec<Order> = self
+            .orders
+            .iter()
+            .filter(|order| !order.is_open() && order.timestamp < cutoff)
+            .map(|order| order.clone())
+            .collect();
+        let records = aged
+            .iter()
+            .map(|order| to_record(order.timestamp, order))
+            .collect::<Result<Vec<_>>>()?;
+        let key = self.archiver.put_batch(DataClass::Orders, &records).await?;
+
+        for order in &aged {
+            self.orders.remove(&order.id);
+            self.state_store.delete_order(order.id).await?;
+        }
+        Ok(ClassRun {
+            class: DataClass::Orders,
+            cutoff,
+            archived: records.len(),
+            removed: aged.len(),
+            key,
+        })
+    }
+
+    /// Trades spilled to the state store and executed before `cutoff`.
+    /// The in-memory window only ever holds recent trades.
+    async fn archive_trades(&self, cutoff: DateTime<Utc>) -> Result<ClassRun> {
+        let query = TradeQuery {
+            to: Some(cutoff),
+            ..Default::default()
+        };
+        let aged: Vec<Trade> = self
+            .state_store
+            .load_trades(&query)
+            .await?
+            .into_iter()
+            .filter(|trade| trade.timestamp < cutoff)
+            .collect();
+        let records = aged
+            .iter()
+            .map(|trade| to_record(trade.timestamp, trade))
+            .collect::<Result<Vec<_>>>()?;
+        let key = self.archiver.put_batch(DataClass::Trades, &records).await?;
+
+        let ids: Vec<Uuid> = aged.iter().map(|trade| trade.id).collect();
+        self.state_store.delete_trades(&ids).await?;
+        Ok(ClassRun {
+            class: DataClass::Trades,
+            cutoff,
+            archived: records.len(),
+            removed: ids.len(),
+            key,
+        })
+    }
+
+    /// Every journal event since the last run, then prunes the journal to
+    /// the window. Returns the run and the events that were evicted unseen.
+    async fn archive_audit(&self, cutoff: DateTime<Utc>) -> Result<(ClassRun, u64)> {
+        let cursor = self.archiver.audit_cursor.load(Ordering::SeqCst);
+        let replay = self.event_journal.replay(cursor + 1, None, usize::MAX);
+        let missed = replay.oldest_available_seq.saturating_sub(cursor + 1);
+        if missed > 0 {
+            warn!("{} audit events left the journal before they were archived", missed);
+        }
+        let records = replay
+            .events
+            .iter()
+            .map(|event| to_record(event.timestamp, event))
+            .collect::<Result<Vec<_>>>()?;
+        let key = self.archiver.put_batch(DataClass::Audit, &records).await?;
+
+        if let Some(last) = replay.events.last() {
+            self.archiver.audit_cursor.store(last.seq, Ordering::SeqCst);
+        }
+        let removed = self.event_journal.prune_before(cutoff);
+        let run = ClassRun {
+            class: DataClass::Audit,
+            cutoff,
+            archived: records.len(),
+            removed,
+            key,
+        };
+        Ok((run, missed))
+    }
+
+    /// Archives the current books and deletes archived snapshots older
+    /// than `cutoff`. Live books are never removed.
+    async fn archive_book_snapshot(&self, now: DateTime<Utc>, cutoff: DateTime<Utc>) -> Result<ClassRun> {
+        let sink = self.archiver.sink()?;
+        let mut removed = 0;
+        for key in sink.list(&format!("{}/", DataClass::BookSnapshots.name())).await? {
+            if batch_range(&key).is_some_and(|(_, last)| last < cutoff) {
+                sink.delete(&key).await?;
+                removed += 1;
+            }
+        }
+
+        let records = vec![to_record(now, &self.book_snapshot())?];
+        let key = self.archiver.put_batch(DataClass::BookSnapshots, &records).await?;
+        Ok(ClassRun {
+            class: DataClass::BookSnapshots,
+            cutoff,
+            archived: records.len(),
+            removed,
+            key,
+        })
+    }
+
+    /// Archived `class` records matching `query`, oldest first.
+    pub async fn query_archive(&self, class: DataClass, query: &ArchiveQuery) -> Result<Vec<ArchivedRecord>> {
+        let sink = self.archiver.sink()?;
+        let limit = query.limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+        let mut records = Vec::new();
+        for key in sink.list(&format!("{}/", class.name())).await? {
+            if !batch_range(&key).is_some_and(|range| query.overlaps(range)) {
+                continue;
+            }
+            records.extend(
+                decode_batch(&sink.get(&key).await?)?
+                    .into_iter()
+                    .filter(|record| query.matches(record)),
+            );
+        }
+        records.sort_by_key(|record| record.at);
+        records.truncate(limit);
+        Ok(records)
+    }
+}
@@ -1,28 +1,264 @@
-use crate::types::*;
+use crate::{types::*, utils::time::TimeProvider};
 use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
-use rust_decimal::Decimal;
-use std::sync::Arc;
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
 use uuid::Uuid;
 
+/// Order metadata key carrying a risk override token.
+pub const OVERRIDE_TOKEN_KEY: &str = "risk_override_token";
+const MAX_OVERRIDE_MINUTES: i64 = 24 * 60;
+
+/// Per-account order flow counters read by the risk checks: orders resting
+/// on the continuous book and what they could add if they all filled, order
+/// messages against trades for the order-to-trade ratio, and recent cancels
+/// and amends per symbol.
+#[derive(Default)]
+pub struct OrderActivity {
+    open_orders: DashMap<(Uuid, String), u32>,
+    open_exposure: DashMap<(Uuid, String), OpenExposure>,
+    messages: DashMap<Uuid, u64>,
+    trades: DashMap<Uuid, u64>,
+    quote_updates: DashMap<(Uuid, String), VecDeque<DateTime<Utc>>>,
+}
+
+/// Remaining face value and notional of an account's resting orders in
+/// one symbol, by side.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct OpenExposure {
+    pub buy_quantity: Decimal,
+    pub buy_value: Decimal,
+    pub sell_quantity: Decimal,
+    pub sell_value: Decimal,
+}
+
+impl OpenExposure {
+    pub fn quantity(&self, side: &OrderSide) -> Decimal {
+        match side {
+            OrderSide::Buy => self.buy_quantity,
+            OrderSide::Sell => self.sell_quantity,
+        }
+    }
+
+    pub fn value(&self) -> Decimal {
+        self.buy_value + self.sell_value
+    }
+
+    fn add(&mut self, other: &OpenExposure) {
+        self.buy_quantity += other.buy_quantity;
+        self.buy_value += other.buy_value;
+        self.sell_quantity += other.sell_quantity;
+        self.sell_value += other.sell_value;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountActivity {
+    pub account_id: Uuid,
+    pub open_orders: u32,
+    /// Notional of every resting order, both sides.
+    pub open_order_value: Decimal,
+    pub messages: u64,
+    pub trades: u64,
+    pub order_to_trade_ratio: Decimal,
+}
+
+impl OrderActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn order_rested(&self, order: &Order) {
+        *self
+            .open_orders
+            .entry((order.account_id, order.symbol.clone()))
+            .or_default() += 1;
+        self.adjust_exposure(order, order.remaining_quantity);
+    }
+
+    /// A resting order traded `quantity`, which no longer counts as open.
+    pub fn resting_filled(&self, order: &Order, quantity: Decimal) {
+        self.adjust_exposure(order, -quantity);
+    }
+
+    /// A resting order's size was cut by `quantity`.
+    pub fn resting_reduced(&self, order: &Order, quantity: Decimal) {
+        self.adjust_exposure(order, -quantity);
+    }
+
+    /// `order` left the book with its remaining quantity unfilled.
+    pub fn order_left_book(&self, order: &Order) {
+        if let Some(mut count) = self.open_orders.get_mut(&(order.account_id, order.symbol.clone())) {
+            *count = count.saturating_sub(1);
+        }
+        self.adjust_exposure(order, -order.remaining_quantity);
+    }
+
+    fn adjust_exposure(&self, order: &Order, quantity: Decimal) {
+        let value = notional_value(quantity, order.price.unwrap_or_default());
+        let mut exposure = self
+            .open_exposure
+            .entry((order.account_id, order.symbol.clone()))
+            .or_default();
+        match order.side {
+            OrderSide::Buy => {
+                exposure.buy_quantity += quantity;
+                exposure.buy_value += value;
+            }
+            OrderSide::Sell => {
+                exposure.sell_quantity += quantity;
+                exposure.sell_value += value;
+            }
+        }
+    }
+
+    /// What the account's resting orders could add, in one symbol or
+    /// summed across all of them.
+    pub fn open_exposure(&self, account_id: Uuid, symbol: Option<&str>) -> OpenExposure {
+        match symbol {
+            Some(symbol) => self
+                .open_exposure
+                .get(&(account_id, symbol.to_string()))
+                .map_or_else(OpenExposure::default, |exposure| *exposure),
+            None => self
+                .open_exposure
+                .iter()
+                .filter(|entry| entry.key().0 == account_id)
+                .fold(OpenExposure::default(), |mut total, entry| {
+                    total.add(entry.value());
+                    total
+                }),
+        }
+    }
+
+    pub fn record_message(&self, account_id: Uuid) {
+        *self.messages.entry(account_id).or_default() += 1;
+    }
+
+    pub fn record_trade(&self, trade: &Trade) {
+        for account_id in [trade.buyer_account_id, trade.seller_account_id] {
+            *self.trades.entry(account_id).or_default() += 1;
+        }
+    }
+
+    /// Records a cancel or amend unless `limit` were already made in the
+    /// second up to `now`. Returns whether it was recorded.
+    pub fn try_quote_update(&self, account_id: Uuid, symbol: &str, now: DateTime<Utc>, limit: u32) -> bool {
+        let mut updates = self.quote_updates.entry((account_id, symbol.to_string())).or_default();
+        let window_start = now - Duration::seconds(1);
+        while updates.front().is_some_and(|at| *at <= window_start) {
+            updates.pop_front();
+        }
+        if limit > 0 && updates.len() >= limit as usize {
+            return false;
+        }
+        updates.push_back(now);
+        true
+    }
+
+    /// Open orders for the account, in one symbol or across all of them.
+    pub fn open_orders(&self, account_id: Uuid, symbol: Option<&str>) -> u32 {
+        match symbol {
+            Some(symbol) => self
+                .open_orders
+                .get(&(account_id, symbol.to_string()))
+                .map_or(0, |count| *count),
+            None => self
+                .open_orders
+                .iter()
+                .filter(|entry| entry.key().0 == account_id)
+                .map(|entry| *entry.value())
+                .sum(),
+        }
+    }
+
+    pub fn get(&self, account_id: Uuid) -> AccountActivity {
+        let messages = self.messages.get(&account_id).map_or(0, |count| *count);
+        let trades = self.trades.get(&account_id).map_or(0, |count| *count);
+        AccountActivity {
+            account_id,
+            open_orders: self.open_orders(account_id, None),
+            open_order_value: self.open_exposure(account_id, None).value(),
+            messages,
+            trades,
+            order_to_trade_ratio: order_to_trade_ratio(messages, trades),
+        }
+    }
+}
+
+/// Messages per trade, counting an account with no trades as having one.
+fn order_to_trade_ratio(messages: u64, trades: u64) -> Decimal {
+    Decimal::from(messages) / Decimal::from(trades.max(1))
+}
+
+/// A time-boxed approval, granted by a risk officer, for an account's
+/// orders to pass its soft limits.
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskOverride {
+    pub token: Uuid,
+    pub account_id: Uuid,
+    pub approved_by: Uuid,
+    pub reason: String,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked: bool,
+}
+
+impl RiskOverride {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        !self.revoked && now < self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OverrideRequest {
+    pub account_id: Uuid,
+    pub approved_by: Uuid,
+    pub reason: String,
+    pub duration_minutes: i64,
+}
+
+/// Outcome of an order that passed the hard limits: the soft limits it
+/// breached and the override that let it through.
+#[derive(Debug, Clone, Default)]
+pub struct RiskCheck {
+    pub warnings: Vec<String>,
+    pub override_token: Option<Uuid>,
+}
+
 pub struct RiskManager {
     risk_limits: Arc<DashMap<Uuid, RiskLimits>>,
+    overrides: DashMap<Uuid, RiskOverride>,
+    activity: Arc<OrderActivity>,
     config: Arc<crate::config::Config>,
+    time_provider: Arc<TimeProvider>,
 }
 
 impl RiskManager {
-    pub async fn new(config: Arc<crate::config::Config>) -> Result<Self> {
+    pub async fn new(
+        config: Arc<crate::config::Config>,
+        activity: Arc<OrderActivity>,
+        time_provider: Arc<TimeProvider>,
+    ) -> Result<Self> {
         let risk_limits = Arc::new(DashMap::new());
         
         // Load default risk limits
         // This would typically come from database
         Ok(Self {
             risk_limits,
+            overrides: DashMap::new(),
+            activity,
             config,
+            time_provider,
         })
     }
 
-    pub async fn check_order(&self, order: &Order) -> crate::types::Result<()> {
+    pub async fn check_order(&self, order: &Order) -> crate::types::Result<RiskCheck> {
         // Get risk limits for account
         let limits = self.get_risk_limits(order.account_id).await?;
         
@@ -38,36 +274,213 @@ impl RiskManager {
         // Check daily loss limits
         self.check_daily_loss_limits(order, &limits).await?;
 
+        // Quote-stuffing controls
+        self.check_open_order_limits(order, &limits)?;
+        self.check_order_to_trade_ratio(order, &limits)?;
+
+        self.check_soft_limits(order, &limits)
+    }
+
+    /// Soft breaches pass only with an active override for the account
+    /// that someone other than the order's user approved.
+    fn check_soft_limits(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<RiskCheck> {
+        let mut warnings = Vec::new();
+        let order_value = notional_value(order.quantity, order.price.unwrap_or(Decimal::ZERO));
+        if let Some(soft) = limits.soft_max_order_value.filter(|soft| order_value > *soft) {
+            warnings.push(format!("Order value {} exceeds soft limit {}", order_value, soft));
+        }
+        if let Some(soft) = limits.soft_max_position_size.filter(|soft| order.quantity > *soft) {
+            warnings.push(format!("Order quantity {} exceeds soft position limit {}", order.quantity, soft));
+        }
+        if warnings.is_empty() {
+            return Ok(RiskCheck::default());
+        }
+
+        let now = self.time_provider.now();
+        let approved = order
+            .metadata
+            .get(OVERRIDE_TOKEN_KEY)
+            .and_then(|token| Uuid::parse_str(token).ok())
+            .and_then(|token| self.overrides.get(&token).map(|grant| grant.clone()))
+            .filter(|grant| {
+                grant.account_id == order.account_id && grant.approved_by != order.user_id && grant.is_active(now)
+            });
+        match approved {
+            Some(grant) => Ok(RiskCheck {
+                warnings,
+                override_token: Some(grant.token),
+            }),
+            None => Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::SoftLimit,
+                message: format!("{}; an approved override is required", warnings.join("; ")),
+            }),
+        }
+    }
+
+    pub fn grant_override(&self, request: OverrideRequest) -> crate::types::Result<RiskOverride> {
+        if request.duration_minutes <= 0 || request.duration_minutes > MAX_OVERRIDE_MINUTES {
+            return Err(TradingError::InvalidRequest(format!(
+                "Override duration must be 1 to {} minutes",
+                MAX_OVERRIDE_MINUTES
+            )));
+        }
+        if request.reason.trim().is_empty() {
+            return Err(TradingError::InvalidRequest("An override needs a reason".to_string()));
+        }
+        let now = self.time_provider.now();
+        let grant = RiskOverride {
+            token: Uuid::new_v4(),
+            account_id: request.account_id,
+            approved_by: request.approved_by,
+            reason: request.reason,
+            granted_at: now,
+            expires_at: now + Duration::minutes(request.duration_minutes),
+            revoked: false,
+        };
+        self.overrides.insert(grant.token, grant.clone());
+        Ok(grant)
+    }
+
+    pub fn revoke_override(&self, token: Uuid) -> Option<RiskOverride> {
+        let mut grant = self.overrides.get_mut(&token)?;
+        grant.revoked = true;
+        Some(grant.clone())
+    }
+
+    /// Overrides, newest first, optionally for one account.
+    pub fn overrides(&self, account_id: Option<Uuid>) -> Vec<RiskOverride> {
+        let mut overrides: Vec<RiskOverride> = self
+            .overrides
+            .iter()
+            .filter(|grant| account_id.is_none_or(|id| grant.account_id == id))
+            .map(|grant| grant.clone())
+            .collect();
+        overrides.sort_by_key(|grant| std::cmp::Reverse(grant.granted_at));
+        overrides
+    }
+
+    /// Gate for cancelling or amending a resting order: it must have rested
+    /// for the account's minimum quote life, and the account must be within
+    /// its per-symbol update rate. A passing update counts toward the rate.
+    pub async fn admit_quote_update(&self, order: &Order) -> crate::types::Result<()> {
+        let limits = self.get_risk_limits(order.account_id).await?;
+        let now = self.time_provider.now();
+        let rested = (now - order.timestamp).num_milliseconds();
+        let min_life = limits.min_quote_life_ms as i64;
+        if rested < min_life {
+            return Err(TradingError::MinimumQuoteLife {
+                remaining_ms: min_life - rested,
+            });
+        }
+        let limit = limits.max_quote_updates_per_second;
+        if !self.activity.try_quote_update(order.account_id, &order.symbol, now, limit) {
+            return Err(TradingError::QuoteRateExceeded(format!(
+                "{} updates per second in {}",
+                limit, order.symbol
+            )));
+        }
         Ok(())
     }
 
+    /// The order's value plus that of every order the account already has
+    /// resting, all of which could fill.
     fn check_order_size(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<()> {
-        let order_value = order.quantity * order.price.unwrap_or(Decimal::ZERO);
-        
-        if order_value > limits.max_order_value {
-            return Err(TradingError::RiskLimitExceeded(
-                format!("Order value {} exceeds limit {}", order_value, limits.max_order_value)
-            ));
+        let order_value = notional_value(order.quantity, order.price.unwrap_or(Decimal::ZERO));
+        let open_value = self.activity.open_exposure(order.account_id, None).value();
+
+        if order_value + open_value > limits.max_order_value {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::OrderValue,
+                message: format!(
+                    "Order value {} with {} resting exceeds limit {}",
+                    order_value, open_value, limits.max_order_value
+                ),
+            });
         }
-        
+
         Ok(())
     }
 
+    /// The order's quantity plus the account's resting orders on the same
+    /// side of the symbol.
     async fn check_position_limits(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<()> {
-        // This would check current position + new order against limits
-        // For now, simplified check
-        if order.quantity > limits.max_position_size {
-            return Err(TradingError::RiskLimitExceeded(
-                format!("Order quantity {} exceeds position limit {}", order.quantity, limits.max_position_size)
-            ));
+        let open = self
+            .activity
+            .open_exposure(order.account_id, Some(&order.symbol))
+            .quantity(&order.side);
+        if order.quantity + open > limits.max_position_size {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::PositionSize,
+                message: format!(
+                    "Order quantity {} with {} resting exceeds position limit {}",
+                    order.quantity, open, limits.max_position_size
+                ),
+            });
         }
-        
+
+        Ok(())
+    }
+
+    fn check_open_order_limits(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<()> {
+        let in_symbol = self.activity.open_orders(order.account_id, Some(&order.symbol));
+        if in_symbol >= limits.max_open_orders_per_symbol {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::OpenOrders,
+                message: format!(
+                    "{} open orders in {} reaches limit {}",
+                    in_symbol, order.symbol, limits.max_open_orders_per_symbol
+                ),
+            });
+        }
+
+        let total = self.activity.open_orders(order.account_id, None);
+        if total >= limits.max_open_orders {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::OpenOrders,
+                message: format!("{} open orders reaches limit {}", total, limits.max_open_orders),
+            });
+        }
+
         Ok(())
     }
 
-    async fn check_concentration_limits(&self, _order: &Order, _limits: &RiskLimits) -> crate::types::Result<()> {
-        // Check portfolio concentration by issuer, sector, rating, etc.
-        // Implementation would calculate current concentrations
+    fn check_order_to_trade_ratio(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<()> {
+        let activity = self.activity.get(order.account_id);
+        if activity.messages < self.config.order_to_trade_min_messages {
+            return Ok(());
+        }
+        if activity.order_to_trade_ratio > limits.max_order_to_trade_ratio {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::OrderToTradeRatio,
+                message: format!(
+                    "Order-to-trade ratio {} exceeds limit {}",
+                    activity.order_to_trade_ratio.round_dp(2),
+                    limits.max_order_to_trade_ratio
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// No one symbol may take more than the concentration limit's share of
+    /// the account's order value allowance, counting resting orders.
+    async fn check_concentration_limits(&self, order: &Order, limits: &RiskLimits) -> crate::types::Result<()> {
+        let order_value = notional_value(order.quantity, order.price.unwrap_or(Decimal::ZERO));
+        let symbol_value = order_value
+            + self
+                .activity
+                .open_exposure(order.account_id, Some(&order.symbol))
+                .value();
+        let allowance = limits.max_order_value * limits.concentration_limit;
+        if symbol_value > allowance {
+            return Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::Concentration,
+                message: format!(
+                    "Open value {} in {} exceeds {} of the order value limit",
+                    symbol_value, order.symbol, limits.concentration_limit
+                ),
+            });
+        }
         Ok(())
     }
 
@@ -89,17 +502,46 @@ impl RiskManager {
                     max_daily_loss: Decimal::from(1_000_000),     // 1M
                     concentration_limit: Decimal::from_f32(0.25).unwrap(), // 25%
                     var_limit: Decimal::from(5_000_000),          // 5M
+                    max_open_orders: self.config.default_max_open_orders,
+                    max_open_orders_per_symbol: self.config.default_max_open_orders_per_symbol,
+                    max_order_to_trade_ratio: self.config.default_max_order_to_trade_ratio,
+                    min_quote_life_ms: self.config.default_min_quote_life_ms,
+                    max_quote_updates_per_second: self.config.default_max_quote_updates_per_second,
+                    soft_max_order_value: None,
+                    soft_max_position_size: None,
+                    max_tenor_bucket_position: BTreeMap::new(),
                 })
             }
         }
     }
 
+    pub async fn risk_limits(&self, account_id: Uuid) -> crate::types::Result<RiskLimits> {
+        self.get_risk_limits(account_id).await
+    }
+
+    pub fn activity(&self, account_id: Uuid) -> AccountActivity {
+        self.activity.get(account_id)
+    }
+
     pub async fn set_risk_limits(&self, limits: RiskLimits) -> crate::types::Result<()> {
         self.risk_limits.insert(limits.account_id, limits);
         Ok(())
     }
 
-    pub async fn calculate_var(&self, account_id: Uuid) -> crate::types::Result<Decimal> {
+    /// One-day VaR of a portfolio under a single parallel yield factor:
+    /// the yield move at the configured confidence times the portfolio's
+    /// value sensitivity, `sum(market value * modified duration)`. Longs
+    /// and shorts offset.
+    pub fn duration_var(&self, exposures: &[(Decimal, Decimal)]) -> Decimal {
+        let sensitivity: Decimal = exposures
+            .iter()
+            .map(|(market_value, duration)| market_value * duration)
+            .sum();
+        let yield_move = self.config.var_yield_volatility_bps / Decimal::from(10_000) * self.config.var_confidence_multiplier;
+        (sensitivity * yield_move).abs()
+    }
+
+    pub async fn calculate_var(&self, _account_id: Uuid) -> crate::types::Result<Decimal> {
         // Simplified VaR calculation
         // In production, this would use proper risk models
         Ok(Decimal::from(1_000_000)) // Placeholder 1M VaR
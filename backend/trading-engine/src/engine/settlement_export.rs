@@ -0,0 +1,415 @@
+//! Settlement instructions for the custodian. Each trade still to settle
+//! gives one instruction per side, rendered as SWIFT MT540-543 text or
+//! ISO 20022 sese.023 XML and written to the outbound sink. Every
+//! instruction is tracked so a failed write is retried on the next run.
+
+use crate::{
+    config::Config,
+    engine::TradingEngine,
+    persistence::archive::{connect_to, ArchiveSink},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Settlement currency of every trade on the venue.
+const SETTLEMENT_CURRENCY: &str = "INR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettlementMessageFormat {
+    Mt54x,
+    Sese023,
+}
+
+impl SettlementMessageFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mt54x" => Some(SettlementMessageFormat::Mt54x),
+            "sese023" => Some(SettlementMessageFormat::Sese023),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            SettlementMessageFormat::Mt54x => "fin",
+            SettlementMessageFormat::Sese023 => "xml",
+        }
+    }
+}
+
+/// Receive or deliver, free of or against payment.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SettlementMessageType {
+    Mt540,
+    Mt541,
+    Mt542,
+    Mt543,
+}
+
+impl SettlementMessageType {
+    fn new(receive: bool, against_payment: bool) -> Self {
+        match (receive, against_payment) {
+            (true, false) => SettlementMessageType::Mt540,
+            (true, true) => SettlementMessageType::Mt541,
+            (false, false) => SettlementMessageType::Mt542,
+            (false, true) => SettlementMessageType::Mt543,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            SettlementMessageType::Mt540 => "540",
+            SettlementMessageType::Mt541 => "541",
+            SettlementMessageType::Mt542 => "542",
+            SettlementMessageType::Mt543 => "543",
+        }
+    }
+
+    fn receives(&self) -> bool {
+        matches!(self, SettlementMessageType::Mt540 | SettlementMessageType::Mt541)
+    }
+
+    fn against_payment(&self) -> bool {
+        matches!(self, SettlementMessageType::Mt541 | SettlementMessageType::Mt543)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InstructionStatus {
+    Pending,
+    Exported,
+    /// The write failed; retried on the next run.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementInstruction {
+    pub id: Uuid,
+    /// Sender's reference, 16 characters.
+    pub reference: String,
+    pub trade_id: Uuid,
+    pub account_id: Uuid,
+    pub counterparty_account_id: Uuid,
+    pub message_type: SettlementMessageType,
+    pub symbol: String,
+    pub isin: Option<String>,
+    pub trade_date: NaiveDate,
+    pub settlement_date: NaiveDate,
+    pub face_amount: Decimal,
+    pub price: Decimal,
+    pub settlement_amount: Decimal,
+    pub status: InstructionStatus,
+    /// Outbound key the message was written under.
+    pub key: Option<String>,
+    pub exported_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+pub struct SettlementExporter {
+    sink: Option<Arc<dyn ArchiveSink>>,
+    format: SettlementMessageFormat,
+    sender_bic: String,
+    place_bic: String,
+    /// By trade and account, so each side is instructed once.
+    instructions: DashMap<(Uuid, Uuid), SettlementInstruction>,
+}
+
+impl SettlementExporter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            sink: connect_to(&config.settlement_outbound),
+            format: SettlementMessageFormat::parse(&config.settlement_message_format)
+                .unwrap_or(SettlementMessageFormat::Mt54x),
+            sender_bic: config.settlement_sender_bic.clone(),
+            place_bic: config.settlement_place_bic.clone(),
+            instructions: DashMap::new(),
+        }
+    }
+
+    /// Oldest settlement first, optionally for one account or status.
+    pub fn list(&self, account_id: Option<Uuid>, status: Option<InstructionStatus>) -> Vec<SettlementInstruction> {
+        let mut instructions: Vec<SettlementInstruction> = self
+            .instructions
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == id))
+            .filter(|entry| status.is_none_or(|status| entry.status == status))
+            .map(|entry| entry.value().clone())
+            .collect();
+        instructions.sort_by_key(|instruction| (instruction.settlement_date, instruction.reference.clone()));
+        instructions
+    }
+
+    fn render(&self, instruction: &SettlementInstruction) -> String {
+        match self.format {
+            SettlementMessageFormat::Mt54x => self.render_mt(instruction),
+            SettlementMessageFormat::Sese023 => self.render_sese(instruction),
+        }
+    }
+
+    fn render_mt(&self, instruction: &SettlementInstruction) -> String {
+        let message_type = instruction.message_type;
+        let security = match &instruction.isin {
+            Some(isin) => format!("ISIN {}\r\n{}", isin, instruction.symbol),
+            None => instruction.symbol.clone(),
+        };
+        let agent = if message_type.receives() { "DEAG" } else { "REAG" };
+        let mut lines = vec![
+            ":16R:GENL".to_string(),
+            format!(":20C::SEME//{}", instruction.reference),
+            ":23G:NEWM".to_string(),
+            ":16S:GENL".to_string(),
+            ":16R:TRADDET".to_string(),
+            format!(":98A::SETT//{}", instruction.settlement_date.format("%Y%m%d")),
+            format!(":98A::TRAD//{}", instruction.trade_date.format("%Y%m%d")),
+            format!(":90A::DEAL//PRCT/{}", swift_decimal(instruction.price)),
+            format!(":35B:{}", security),
+            ":16S:TRADDET".to_string(),
+            ":16R:FIAC".to_string(),
+            format!(":36B::SETT//FAMT/{}", swift_decimal(instruction.face_amount)),
+            format!(":97A::SAFE//{}", instruction.account_id.simple()),
+            ":16S:FIAC".to_string(),
+            ":16R:SETDET".to_string(),
+            ":22F::SETR//TRAD".to_string(),
+            ":16R:SETPRTY".to_string(),
+            format!(":95R::{}/VVTR/{}", agent, instruction.counterparty_account_id.simple()),
+            ":16S:SETPRTY".to_string(),
+            ":16R:SETPRTY".to_string(),
+            format!(":95P::PSET//{}", self.place_bic),
+            ":16S:SETPRTY".to_string(),
+        ];
+        if message_type.against_payment() {
+            lines.push(":16R:AMT".to_string());
+            lines.push(format!(
+                ":19A::SETT//{}{}",
+                SETTLEMENT_CURRENCY,
+                swift_decimal(instruction.settlement_amount.round_dp(2))
+            ));
+            lines.push(":16S:AMT".to_string());
+        }
+        lines.push(":16S:SETDET".to_string());
+        format!(
+            "{{1:F01{}0000000000}}{{2:I{}{}N}}{{4:\r\n{}\r\n-}}",
+            bic12(&self.sender_bic),
+            message_type.code(),
+            bic12(&self.place_bic),
+            lines.join("\r\n")
+        )
+    }
+
+    fn render_sese(&self, instruction: &SettlementInstruction) -> String {
+        let message_type = instruction.message_type;
+        let security = match &instruction.isin {
+            Some(isin) => format!("<ISIN>{}</ISIN>", isin),
+            None => format!(
+                "<OthrId><Id>{}</Id><Tp><Prtry>SYMBOL</Prtry></Tp></OthrId>",
+                xml_escape(&instruction.symbol)
+            ),
+        };
+        let parties = if message_type.receives() { "DlvrgSttlmPties" } else { "RcvgSttlmPties" };
+        let amount = if message_type.against_payment() {
+            format!(
+                "<SttlmAmt><Amt Ccy=\"{}\">{}</Amt><CdtDbtInd>{}</CdtDbtInd></SttlmAmt>",
+                SETTLEMENT_CURRENCY,
+                instruction.settlement_amount.round_dp(2).normalize(),
+                if message_type.receives() { "DBIT" } else { "CRDT" }
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:sese.023.001.09\"><SctiesSttlmTxInstr>\
+             <TxId>{reference}</TxId>\
+             <SttlmTpAndAddtlParams><SctiesMvmntTp>{movement}</SctiesMvmntTp><Pmt>{payment}</Pmt>\
+             </SttlmTpAndAddtlParams>\
+             <TradDtls><TradDt><Dt><Dt>{trade_date}</Dt></Dt></TradDt>\
+             <SttlmDt><Dt><Dt>{settlement_date}</Dt></Dt></SttlmDt>\
+             <DealPric><Tp><Yldd>false</Yldd></Tp><Val><Rate>{price}</Rate></Val></DealPric></TradDtls>\
+             <FinInstrmId>{security}</FinInstrmId>\
+             <QtyAndAcctDtls><SttlmQty><Qty><FaceAmt>{face}</FaceAmt></Qty></SttlmQty>\
+             <SfkpgAcct><Id>{account}</Id></SfkpgAcct></QtyAndAcctDtls>\
+             <SttlmParams><SctiesTxTp><Cd>TRAD</Cd></SctiesTxTp></SttlmParams>\
+             <{parties}><Dpstry><Id><AnyBIC>{place}</AnyBIC></Id></Dpstry>\
+             <Pty1><Id><PrtryId><Id>{counterparty}</Id><Issr>VVTR</Issr></PrtryId></Id></Pty1></{parties}>\
+             {amount}</SctiesSttlmTxInstr></Document>\n",
+            reference = instruction.reference,
+            movement = if message_type.receives() { "RECE" } else { "DELI" },
+            payment = if message_type.against_payment() { "APMT" } else { "FREE" },
+            trade_date = instruction.trade_date,
+            settlement_date = instruction.settlement_date,
+            price = instruction.price.normalize(),
+            security = security,
+            face = instruction.face_amount.normalize(),
+            account = instruction.account_id.simple(),
+            parties = parties,
+            place = self.place_bic,
+            counterparty = instruction.counterparty_account_id.simple(),
+            amount = amount,
+        )
+    }
+}
+
+/// SWIFT decimal notation: comma as the decimal mark, always present.
+fn swift_decimal(value: Decimal) -> String {
+    let text = value.abs().normalize().to_string().replace('.', ",");
+    if text.contains(',') {
+        text
+    } else {
+        format!("{},", text)
+    }
+}
+
+/// A BIC padded to the 12-character logical terminal address.
+fn bic12(bic: &str) -> String {
+    let bic = if bic.len() == 8 { format!("{}XXX", bic) } else { bic.to_string() };
+    format!("{}A{}", &bic[..8], &bic[8..])
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl TradingEngine {
+    pub fn settlement_exporter(&self) -> &SettlementExporter {
+        &self.settlement_exporter
+    }
+
+    /// Instructs both sides of every trade settling today or later, once
+    /// any affirmation it needs is in, and writes each instruction not yet exported. Returns the instructions
+    /// this run wrote or failed to write.
+    pub async fn export_settlement_instructions(&self) -> Result<Vec<SettlementInstruction>> {
+        let exporter = &self.settlement_exporter;
+        let sink = exporter
+            .sink
+            .as_ref()
+            .ok_or_else(|| TradingError::InvalidRequest("No settlement outbound is configured".to_string()))?;
+        let today = self.time_provider.today();
+
+        for trade in self.get_trades() {
+            if trade.settlement_date < today || !self.confirmations.cleared_to_settle(trade.id) {
+                continue;
+            }
+            let isin = self.instruments.get(&trade.symbol).map(|bond| bond.isin);
+            let against_payment = trade.settlement_amount > Decimal::ZERO;
+            for (account_id, counterparty_account_id, receive) in [
+                (trade.buyer_account_id, trade.seller_account_id, true),
+                (trade.seller_account_id, trade.buyer_account_id, false),
+            ] {
+                exporter.instructions.entry((trade.id, account_id)).or_insert_with(|| {
+                    let id = Uuid::new_v4();
+                    SettlementInstruction {
+                        id,
+                        reference: id.simple().to_string()[..16].to_uppercase(),
+                        trade_id: trade.id,
+                        account_id,
+                        counterparty_account_id,
+                        message_type: SettlementMessageType::new(receive, against_payment),
+                        symbol: trade.symbol.clone(),
+                        isin: isin.clone(),
+                        trade_date: trade.timestamp.date_naive(),
+                        settlement_date: trade.settlement_date,
+                        face_amount: trade.quantity,
+                        price: trade.price,
+                        settlement_amount: trade.settlement_amount,
+                        status: InstructionStatus::Pending,
+                        key: None,
+                        exported_at: None,
+                        error: None,
+                    }
+                });
+            }
+        }
+
+        let mut written = Vec::new();
+        for mut instruction in exporter.list(None, None) {
+            if instruction.status == InstructionStatus::Exported {
+                continue;
+            }
+            let key = format!(
+                "settlement/{}/{}.{}",
+                instruction.settlement_date.format("%Y%m%d"),
+                instruction.reference,
+                exporter.format.extension()
+            );
+            match sink.put(&key, exporter.render(&instruction).into_bytes()).await {
+                Ok(()) => {
+                    instruction.status = InstructionStatus::Exported;
+                    instruction.key = Some(key);
+                    instruction.exported_at = Some(self.time_provider.now());
+                    instruction.error = None;
+                }
+                Err(e) => {
+                    warn!("Settlement instruction {} was not written: {}", instruction.reference, e);
+                    instruction.status = InstructionStatus::Failed;
+                    instruction.error = Some(e.to_string());
+                }
+            }
+            exporter
+                .instructions
+                .insert((instruction.trade_id, instruction.account_id), instruction.clone());
+            written.push(instruction);
+        }
+        if !written.is_empty() {
+            info!("Settlement export wrote {} instructions", written.len());
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).account(account_id).build()
+    }
+
+    #[test]
+    fn test_swift_notation() {
+        assert_eq!(swift_decimal(dec!(100000)), "100000,");
+        assert_eq!(swift_decimal(dec!(99.500)), "99,5");
+        assert_eq!(bic12("CCILINBB"), "CCILINBBAXXX");
+    }
+
+    #[tokio::test]
+    async fn test_trade_exports_one_instruction_per_side_once() {
+        let config = Config {
+            settlement_outbound: "memory".to_string(),
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+
+        let written = engine.export_settlement_instructions().await.unwrap();
+        assert_eq!(written.len(), 2);
+        assert!(written.iter().all(|instruction| instruction.status == InstructionStatus::Exported));
+        assert!(engine.export_settlement_instructions().await.unwrap().is_empty());
+
+        let receive = &engine.settlement_exporter().list(Some(buyer), None)[0];
+        assert_eq!(receive.message_type, SettlementMessageType::Mt541);
+        let sink = engine.settlement_exporter().sink.clone().unwrap();
+        let message = String::from_utf8(sink.get(receive.key.as_ref().unwrap()).await.unwrap()).unwrap();
+        assert!(message.starts_with("{1:F01VVTRINBBAXXX0000000000}{2:I541CCILINBBAXXXN}"));
+        assert!(message.contains(":36B::SETT//FAMT/100000,\r\n"));
+        assert!(message.contains(&format!(":95R::DEAG/VVTR/{}", seller.simple())));
+
+        let xml = SettlementExporter::new(&Config {
+            settlement_message_format: "sese023".to_string(),
+            ..Config::default()
+        })
+        .render(receive);
+        assert!(xml.contains("<SctiesMvmntTp>RECE</SctiesMvmntTp><Pmt>APMT</Pmt>"));
+    }
+}
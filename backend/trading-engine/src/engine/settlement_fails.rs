@@ -0,0 +1,462 @@
+//! Failed settlements. A side that does not deliver securities or cash on
+//! the intended settlement date is marked failed; the fail accrues a daily
+//! penalty on its unsettled value, may settle in parts, and is bought in
+//! once it has lasted too many business days.
+//!
+//! A business day's penalty accrues once the day has ended, so a fail that
+//! settles on a day pays nothing for that day.
+
+use crate::{
+    engine::{
+        fees::{round_currency, BASIS_POINT},
+        TradingEngine,
+    },
+    types::*,
+};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const HUNDRED: Decimal = Decimal::ONE_HUNDRED;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailedLeg {
+    /// The seller did not deliver the securities.
+    Securities,
+    /// The buyer did not pay.
+    Cash,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FailStatus {
+    Open,
+    /// Past the buy-in threshold; waiting for the buy-in (or, for a cash
+    /// fail, the sell-out) to execute.
+    BuyIn,
+    Settled,
+    BoughtIn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialSettlement {
+    pub settled_on: NaiveDate,
+    pub quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuyIn {
+    pub started_on: NaiveDate,
+    pub completed_on: Option<NaiveDate>,
+    /// Clean price the replacement trade executed at.
+    pub price: Option<Decimal>,
+    /// Price difference the failing party owes, never negative.
+    pub compensation: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementFail {
+    pub id: Uuid,
+    pub trade_id: Uuid,
+    pub symbol: String,
+    pub failing_account_id: Uuid,
+    pub counterparty_account_id: Uuid,
+    pub leg: FailedLeg,
+    pub reason: Option<String>,
+    pub intended_settlement_date: NaiveDate,
+    pub quantity: Decimal,
+    pub settled_quantity: Decimal,
+    /// Clean price of the trade.
+    pub price: Decimal,
+    pub settlement_amount: Decimal,
+    pub status: FailStatus,
+    /// Business days from the intended settlement date to today.
+    pub business_days_failed: u32,
+    pub penalty_accrued: Decimal,
+    pub accrued_through: NaiveDate,
+    pub partial_settlements: Vec<PartialSettlement>,
+    pub buy_in: Option<BuyIn>,
+}
+
+impl SettlementFail {
+    pub fn outstanding_quantity(&self) -> Decimal {
+        self.quantity - self.settled_quantity
+    }
+
+    /// The settlement amount of the quantity still to settle.
+    pub fn outstanding_value(&self) -> Decimal {
+        if self.quantity.is_zero() {
+            return Decimal::ZERO;
+        }
+        self.settlement_amount * self.outstanding_quantity() / self.quantity
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self.status, FailStatus::Open | FailStatus::BuyIn)
+    }
+
+    /// Accrues the penalty for every business day ended up to `through`.
+    fn accrue(&mut self, through: NaiveDate, penalty_bps: Decimal) {
+        let days = business_days(self.accrued_through, through);
+        if days == 0 {
+            return;
+        }
+        let penalty = self.outstanding_value() * penalty_bps * BASIS_POINT * Decimal::from(days);
+        self.penalty_accrued = round_currency(self.penalty_accrued + penalty);
+        self.accrued_through = through;
+    }
+}
+
+/// Business days after `from` up to and including `to`.
+fn business_days(from: NaiveDate, to: NaiveDate) -> u32 {
+    let mut days = 0;
+    let mut date = from;
+    while date < to {
+        date += Duration::days(1);
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            days += 1;
+        }
+    }
+    days
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FailRequest {
+    /// Trade id or trade number.
+    pub trade: String,
+    pub failing_account_id: Uuid,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgingBucket {
+    pub label: &'static str,
+    pub fails: usize,
+    pub outstanding_value: Decimal,
+}
+
+/// Open fails of one failing party by how long they have lasted.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailsAging {
+    pub account_id: Uuid,
+    pub open_fails: usize,
+    pub outstanding_value: Decimal,
+    pub penalty_accrued: Decimal,
+    pub buckets: Vec<AgingBucket>,
+}
+
+/// Upper bounds of the aging buckets in business days.
+const AGING_BUCKETS: [(&str, u32); 4] = [("0-3", 3), ("4-7", 7), ("8-15", 15), ("16+", u32::MAX)];
+
+#[derive(Default)]
+pub struct SettlementFailBook {
+    fails: DashMap<Uuid, SettlementFail>,
+}
+
+impl SettlementFailBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Oldest first, optionally for one failing party or counterparty.
+    pub fn list(&self, account_id: Option<Uuid>) -> Vec<SettlementFail> {
+        let mut fails: Vec<SettlementFail> = self
+            .fails
+            .iter()
+            .filter(|entry| {
+                account_id.is_none_or(|id| entry.failing_account_id == id || entry.counterparty_account_id == id)
+            })
+            .map(|entry| entry.value().clone())
+            .collect();
+        fails.sort_by_key(|fail| (fail.intended_settlement_date, fail.id));
+        fails
+    }
+
+    pub fn aging(&self) -> Vec<FailsAging> {
+        let mut accounts: BTreeMap<Uuid, FailsAging> = BTreeMap::new();
+        for fail in self.fails.iter().filter(|entry| entry.is_open()) {
+            let aging = accounts.entry(fail.failing_account_id).or_insert_with(|| FailsAging {
+                account_id: fail.failing_account_id,
+                open_fails: 0,
+                outstanding_value: Decimal::ZERO,
+                penalty_accrued: Decimal::ZERO,
+                buckets: AGING_BUCKETS
+                    .iter()
+                    .map(|(label, _)| AgingBucket {
+                        label,
+                        fails: 0,
+                        outstanding_value: Decimal::ZERO,
+                    })
+                    .collect(),
+            });
+            let value = fail.outstanding_value();
+            aging.open_fails += 1;
+            aging.outstanding_value += value;
+            aging.penalty_accrued += fail.penalty_accrued;
+            let bucket = AGING_BUCKETS
+                .iter()
+                .position(|(_, up_to)| fail.business_days_failed <= *up_to)
+                .unwrap_or(AGING_BUCKETS.len() - 1);
+            aging.buckets[bucket].fails += 1;
+            aging.buckets[bucket].outstanding_value += value;
+        }
+        let mut report: Vec<FailsAging> = accounts.into_values().collect();
+        report.sort_by_key(|aging| std::cmp::Reverse(aging.outstanding_value));
+        report
+    }
+
+    fn get_open(&self, id: Uuid) -> Result<dashmap::mapref::one::RefMut<'_, Uuid, SettlementFail>> {
+        let fail = self
+            .fails
+            .get_mut(&id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown settlement fail {}", id)))?;
+        if !fail.is_open() {
+            return Err(TradingError::InvalidRequest(format!(
+                "Settlement fail {} is already {:?}",
+                id, fail.status
+            )));
+        }
+        Ok(fail)
+    }
+}
+
+impl TradingEngine {
+    pub fn settlement_fails(&self) -> &SettlementFailBook {
+        &self.settlement_fails
+    }
+
+    /// Days whose penalty has accrued: every day before today.
+    fn penalty_cutoff(&self) -> NaiveDate {
+        self.time_provider.today() - Duration::days(1)
+    }
+
+    /// Marks one side of a trade as failed to settle. The trade must be
+    /// due, and the side not already failing.
+    pub fn mark_settlement_failed(&self, request: FailRequest) -> Result<SettlementFail> {
+        let trade = self
+            .get_trade(&request.trade)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown trade {}", request.trade)))?;
+        let (leg, counterparty_account_id) = if request.failing_account_id == trade.seller_account_id {
+            (FailedLeg::Securities, trade.buyer_account_id)
+        } else if request.failing_account_id == trade.buyer_account_id {
+            (FailedLeg::Cash, trade.seller_account_id)
+        } else {
+            return Err(TradingError::InvalidRequest(format!(
+                "Account {} is not a party to trade {}",
+                request.failing_account_id, trade.id
+            )));
+        };
+        let today = self.time_provider.today();
+        if trade.settlement_date > today {
+            return Err(TradingError::InvalidRequest(format!(
+                "Trade {} is not due to settle until {}",
+                trade.id, trade.settlement_date
+            )));
+        }
+        if self
+            .settlement_fails
+            .fails
+            .iter()
+            .any(|fail| fail.trade_id == trade.id && fail.failing_account_id == request.failing_account_id)
+        {
+            return Err(TradingError::InvalidRequest(format!(
+                "Trade {} is already marked failed for {}",
+                trade.id, request.failing_account_id
+            )));
+        }
+
+        let mut fail = SettlementFail {
+            id: Uuid::new_v4(),
+            trade_id: trade.id,
+            symbol: trade.symbol,
+            failing_account_id: request.failing_account_id,
+            counterparty_account_id,
+            leg,
+            reason: request.reason,
+            intended_settlement_date: trade.settlement_date,
+            quantity: trade.quantity,
+            settled_quantity: Decimal::ZERO,
+            price: trade.price,
+            settlement_amount: trade.settlement_amount,
+            status: FailStatus::Open,
+            business_days_failed: 0,
+            penalty_accrued: Decimal::ZERO,
+            accrued_through: trade.settlement_date - Duration::days(1),
+            partial_settlements: Vec::new(),
+            buy_in: None,
+        };
+        // Marked late, the fail owes for the days already missed
+        self.refresh_fail(&mut fail);
+        warn!(
+            "Settlement of trade {} failed on the {:?} leg for {}",
+            fail.trade_id, fail.leg, fail.failing_account_id
+        );
+        self.settlement_fails.fails.insert(fail.id, fail.clone());
+        Ok(fail)
+    }
+
+    /// Records that part or all of a failed side has now settled.
+    pub fn settle_failed_quantity(&self, id: Uuid, quantity: Decimal) -> Result<SettlementFail> {
+        let mut fail = self.settlement_fails.get_open(id)?;
+        if quantity <= Decimal::ZERO || quantity > fail.outstanding_quantity() {
+            return Err(TradingError::InvalidRequest(format!(
+                "Settled quantity must be positive and at most the outstanding {}",
+                fail.outstanding_quantity()
+            )));
+        }
+        self.refresh_fail(&mut fail);
+        let today = self.time_provider.today();
+        fail.settled_quantity += quantity;
+        fail.partial_settlements.push(PartialSettlement {
+            settled_on: today,
+            quantity,
+        });
+        if fail.outstanding_quantity().is_zero() {
+            fail.status = FailStatus::Settled;
+            info!("Failed settlement {} has settled in full", fail.id);
+        }
+        Ok(fail.clone())
+    }
+
+    /// Closes a fail in buy-in with the replacement trade's price. A
+    /// securities fail owes any rise over the trade price; a cash fail,
+    /// sold out instead, owes any fall.
+    pub fn complete_buy_in(&self, id: Uuid, price: Decimal) -> Result<SettlementFail> {
+        if price <= Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Buy-in price must be positive".to_string()));
+        }
+        let mut fail = self.settlement_fails.get_open(id)?;
+        self.refresh_fail(&mut fail);
+        let today = self.time_provider.today();
+        let difference = match fail.leg {
+            FailedLeg::Securities => price - fail.price,
+            FailedLeg::Cash => fail.price - price,
+        };
+        let compensation = round_currency((difference * fail.outstanding_quantity() / HUNDRED).max(Decimal::ZERO));
+        let buy_in = fail.buy_in.get_or_insert(BuyIn {
+            started_on: today,
+            completed_on: None,
+            price: None,
+            compensation: None,
+        });
+        buy_in.completed_on = Some(today);
+        buy_in.price = Some(price);
+        buy_in.compensation = Some(compensation);
+        fail.status = FailStatus::BoughtIn;
+        info!("Failed settlement {} bought in at {}, compensation {}", fail.id, price, compensation);
+        Ok(fail.clone())
+    }
+
+    /// Daily job: accrues penalties on open fails and starts the buy-in of
+    /// those past the threshold. Returns the fails that entered buy-in.
+    pub fn process_settlement_fails(&self) -> Vec<Uuid> {
+        let mut bought_in = Vec::new();
+        for mut fail in self.settlement_fails.fails.iter_mut() {
+            if !fail.is_open() {
+                continue;
+            }
+            let status = fail.status;
+            self.refresh_fail(&mut fail);
+            if status == FailStatus::Open && fail.status == FailStatus::BuyIn {
+                bought_in.push(fail.id);
+            }
+        }
+        bought_in
+    }
+
+    fn refresh_fail(&self, fail: &mut SettlementFail) {
+        let cutoff = self.penalty_cutoff();
+        fail.accrue(cutoff, self.config.settlement_fail_penalty_bps);
+        fail.business_days_failed = business_days(fail.intended_settlement_date - Duration::days(1), cutoff);
+        if fail.status == FailStatus::Open && fail.business_days_failed >= self.config.settlement_buy_in_after_days {
+            fail.status = FailStatus::BuyIn;
+            fail.buy_in = Some(BuyIn {
+                started_on: self.time_provider.today(),
+                completed_on: None,
+                price: None,
+                compensation: None,
+            });
+            warn!(
+                "Failed settlement {} of trade {} entered buy-in after {} business days",
+                fail.id, fail.trade_id, fail.business_days_failed
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::OrderBuilder,
+        utils::time::{SimulatedClock, TimeProvider},
+    };
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).price(dec!(100.00)).account(account_id).build()
+    }
+
+    #[test]
+    fn test_business_days_skip_weekends() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        // Friday the 1st to Monday the 4th
+        assert_eq!(business_days(date(1), date(4)), 1);
+        assert_eq!(business_days(date(4), date(4)), 0);
+        assert_eq!(business_days(date(1), date(11)), 6);
+    }
+
+    #[tokio::test]
+    async fn test_fail_accrues_penalties_settles_in_part_and_is_bought_in() {
+        // Monday, settling T+1 on Tuesday the 5th
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 3, 4, 10, 0, 0).unwrap()));
+        let config = Config {
+            settlement_fail_penalty_bps: dec!(1),
+            settlement_buy_in_after_days: 4,
+            ..Config::default()
+        };
+        let time_provider = Arc::new(TimeProvider::with_clock(clock.clone()));
+        let engine = TradingEngine::with_time_provider(Arc::new(config), time_provider).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+        let trade = engine.get_trades().pop().unwrap();
+        let request = |account| FailRequest {
+            trade: trade.id.to_string(),
+            failing_account_id: account,
+            reason: None,
+        };
+        assert!(engine.mark_settlement_failed(request(seller)).is_err());
+
+        clock.advance(Duration::days(1));
+        let fail = engine.mark_settlement_failed(request(seller)).unwrap();
+        assert_eq!((fail.leg, fail.counterparty_account_id), (FailedLeg::Securities, buyer));
+
+        // Wednesday: one day owed on 100000 at 1 bp
+        clock.advance(Duration::days(1));
+        engine.process_settlement_fails();
+        assert_eq!(engine.settlement_fails().list(None)[0].penalty_accrued, dec!(10));
+        engine.settle_failed_quantity(fail.id, dec!(40000)).unwrap();
+
+        // Monday: Wednesday to Friday owed on the 60000 left
+        clock.advance(Duration::days(5));
+        assert_eq!(engine.process_settlement_fails(), vec![fail.id]);
+        let fail = engine.settlement_fails().list(Some(seller)).remove(0);
+        assert_eq!((fail.status, fail.business_days_failed), (FailStatus::BuyIn, 4));
+        assert_eq!(fail.penalty_accrued, dec!(28));
+
+        let aging = engine.settlement_fails().aging();
+        assert_eq!((aging[0].account_id, aging[0].buckets[1].fails), (seller, 1));
+
+        let bought_in = engine.complete_buy_in(fail.id, dec!(100.50)).unwrap();
+        assert_eq!(bought_in.buy_in.unwrap().compensation, Some(dec!(300)));
+        assert!(engine.settlement_fails().aging().is_empty());
+    }
+}
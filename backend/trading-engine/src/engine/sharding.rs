@@ -0,0 +1,188 @@
+//! Optional dedicated matching threads. With shards configured, each symbol
+//! belongs to one shard and its orders are matched on that shard's OS
+//! thread, pinned to a core when one is configured for the shard. Without
+//! shards, orders are matched on the task that submitted them.
+
+use crate::{
+    engine::{matching::Unfilled, TradingEngine},
+    types::*,
+};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{future::Future, thread};
+use tokio::{runtime, sync::oneshot};
+use tracing::{info, warn};
+
+struct Shard {
+    handle: runtime::Handle,
+    thread: String,
+    core: Option<usize>,
+    pinned: bool,
+    /// Dropping this stops the shard's thread.
+    _shutdown: oneshot::Sender<()>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShardInfo {
+    pub index: usize,
+    pub thread: String,
+    pub core: Option<usize>,
+    /// Whether the thread is actually bound to `core`.
+    pub pinned: bool,
+    pub symbols: Vec<String>,
+}
+
+pub struct MatchingShards {
+    shards: Vec<Shard>,
+    assignments: DashMap<String, usize>,
+}
+
+impl MatchingShards {
+    /// Starts `count` matching threads. Shard `i` is pinned to `cores[i]`
+    /// if there is one.
+    pub fn start(count: usize, cores: &[usize]) -> anyhow::Result<Self> {
+        let shards = (0..count)
+            .map(|index| Self::start_shard(index, cores.get(index).copied()))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            shards,
+            assignments: DashMap::new(),
+        })
+    }
+
+    fn start_shard(index: usize, core: Option<usize>) -> anyhow::Result<Shard> {
+        let name = format!("matching-{}", index);
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            let pinned = core.is_some_and(pin_current_thread);
+            let runtime = match runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok((runtime.handle().clone(), pinned)));
+            let _ = runtime.block_on(shutdown_rx);
+        })?;
+        let (handle, pinned) = ready_rx.recv()??;
+        if core.is_some() && !pinned {
+            warn!("Matching shard {} could not be pinned to core {:?}", index, core);
+        }
+        Ok(Shard {
+            handle,
+            thread: name,
+            core,
+            pinned,
+            _shutdown: shutdown_tx,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.shards.is_empty()
+    }
+
+    /// The symbol's shard, assigning new symbols to the shard with the
+    /// fewest.
+    pub fn shard_for(&self, symbol: &str) -> Option<usize> {
+        if self.shards.is_empty() {
+            return None;
+        }
+        if let Some(shard) = self.assignments.get(symbol) {
+            return Some(*shard);
+        }
+        let mut counts = vec![0usize; self.shards.len()];
+        for assignment in self.assignments.iter() {
+            counts[*assignment.value()] += 1;
+        }
+        let least = (0..counts.len()).min_by_key(|index| counts[*index]).unwrap_or_default();
+        let shard = *self.assignments.entry(symbol.to_string()).or_insert(least);
+        info!("Symbol {} assigned to matching shard {}", symbol, shard);
+        Some(shard)
+    }
+
+    /// Moves a symbol's matching to another shard. Orders already handed
+    /// to the old shard finish there.
+    pub fn migrate(&self, symbol: &str, shard: usize) -> crate::types::Result<()> {
+        if shard >= self.shards.len() {
+            return Err(TradingError::InvalidRequest(format!(
+                "No matching shard {}; there are {}",
+                shard,
+                self.shards.len()
+            )));
+        }
+        let previous = self.assignments.insert(symbol.to_string(), shard);
+        info!("Symbol {} migrated from matching shard {:?} to {}", symbol, previous, shard);
+        Ok(())
+    }
+
+    /// Runs `task` on the symbol's shard, or in place without shards.
+    pub async fn run<T, F>(&self, symbol: &str, task: F) -> crate::types::Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = crate::types::Result<T>> + Send + 'static,
+    {
+        let Some(shard) = self.shard_for(symbol) else {
+            return task.await;
+        };
+        self.shards[shard]
+            .handle
+            .spawn(task)
+            .await
+            .map_err(|e| TradingError::InternalError(format!("Matching shard {} failed: {}", shard, e)))?
+    }
+
+    pub fn topology(&self) -> Vec<ShardInfo> {
+        let mut topology: Vec<ShardInfo> = self
+            .shards
+            .iter()
+            .enumerate()
+            .map(|(index, shard)| ShardInfo {
+                index,
+                thread: shard.thread.clone(),
+                core: shard.core,
+                pinned: shard.pinned,
+                symbols: Vec::new(),
+            })
+            .collect();
+        for assignment in self.assignments.iter() {
+            topology[*assignment.value()].symbols.push(assignment.key().clone());
+        }
+        for shard in &mut topology {
+            shard.symbols.sort();
+        }
+        topology
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> bool {
+    // SAFETY: the set is zeroed before use and only describes this thread
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_core: usize) -> bool {
+    false
+}
+
+impl TradingEngine {
+    pub fn matching_shards(&self) -> &MatchingShards {
+        &self.matching_shards
+    }
+
+    /// Matches an order on its symbol's shard.
+    pub(crate) async fn process_order(&self, order: Order, unfilled: Unfilled) -> crate::types::Result<Vec<Trade>> {
+        let matching_engine = self.matching_engine.clone();
+        let symbol = order.symbol.clone();
+        self.matching_shards
+            .run(&symbol, async move { matching_engine.process_order_with(order, unfilled).await })
+            .await
+    }
+}
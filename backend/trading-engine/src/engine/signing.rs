@@ -0,0 +1,312 @@
+//! Client-signed orders. An account registers Ed25519 public keys; a
+//! signed order carries the key id, a nonce, the time it was signed and a
+//! signature over its canonical payload. The engine checks the signature,
+//! refuses stale timestamps and reused nonces, and journals the signature
+//! so the order can be attributed to the key holder afterwards.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use dashmap::DashMap;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredKey {
+    pub key_id: String,
+    pub account_id: Uuid,
+    /// Hex Ed25519 public key.
+    pub public_key: String,
+    /// Unsigned orders for the account are refused while this key is
+    /// active.
+    pub required: bool,
+    pub registered_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl RegisteredKey {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SigningKeyRequest {
+    pub key_id: String,
+    pub account_id: Uuid,
+    pub public_key: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Signature block sent alongside an order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderSignature {
+    pub key_id: String,
+    /// Any string unique per key within the signing window.
+    pub nonce: String,
+    pub signed_at: DateTime<Utc>,
+    /// Hex Ed25519 signature of [`canonical_payload`].
+    pub signature: String,
+}
+
+/// The text a client signs: one `name=value` line per field, in this
+/// order. Decimals are written without trailing zeros, enums as their
+/// JSON, times as RFC 3339 with milliseconds, and absent values empty.
+pub fn canonical_payload(order: &Order, nonce: &str, signed_at: DateTime<Utc>) -> String {
+    [
+        ("account_id", order.account_id.to_string()),
+        ("user_id", order.user_id.to_string()),
+        ("client_order_id", order.client_order_id.clone()),
+        ("symbol", order.symbol.clone()),
+        ("side", json(&order.side)),
+        ("order_type", json(&order.order_type)),
+        ("quantity", order.quantity.normalize().to_string()),
+        ("price", order.price.map(|price| price.normalize().to_string()).unwrap_or_default()),
+        ("time_in_force", json(&order.time_in_force)),
+        ("strategy_id", order.strategy_id.clone().unwrap_or_default()),
+        ("nonce", nonce.to_string()),
+        ("signed_at", signed_at.to_rfc3339_opts(SecondsFormat::Millis, true)),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{}={}\n", name, value))
+    .collect()
+}
+
+fn json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// Nonces seen for one key, oldest first, kept for as long as a signature
+/// carrying them would still be accepted.
+#[derive(Default)]
+struct SeenNonces {
+    nonces: HashSet<String>,
+    order: VecDeque<(DateTime<Utc>, String)>,
+}
+
+impl SeenNonces {
+    /// Records `nonce`, returning false if it was already seen.
+    fn insert(&mut self, nonce: &str, signed_at: DateTime<Utc>, forget_before: DateTime<Utc>) -> bool {
+        while self.order.front().is_some_and(|(at, _)| *at < forget_before) {
+            if let Some((_, expired)) = self.order.pop_front() {
+                self.nonces.remove(&expired);
+            }
+        }
+        if !self.nonces.insert(nonce.to_string()) {
+            return false;
+        }
+        self.order.push_back((signed_at, nonce.to_string()));
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct SigningKeyRegistry {
+    keys: DashMap<String, RegisteredKey>,
+    nonces: DashMap<String, Mutex<SeenNonces>>,
+}
+
+impl SigningKeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn list(&self, account_id: Option<Uuid>) -> Vec<RegisteredKey> {
+        let mut keys: Vec<RegisteredKey> = self
+            .keys
+            .iter()
+            .filter(|key| account_id.is_none_or(|id| key.account_id == id))
+            .map(|key| key.clone())
+            .collect();
+        keys.sort_by_key(|key| key.registered_at);
+        keys
+    }
+
+    fn requires_signature(&self, account_id: Uuid) -> bool {
+        self.keys
+            .iter()
+            .any(|key| key.account_id == account_id && key.required && key.is_active())
+    }
+}
+
+fn decode_hex<const N: usize>(value: &str, what: &str) -> Result<[u8; N]> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| TradingError::InvalidRequest(format!("{} must be {} hex-encoded bytes", what, N)))
+}
+
+impl TradingEngine {
+    pub fn signing_keys(&self) -> &SigningKeyRegistry {
+        &self.signing_keys
+    }
+
+    pub fn register_signing_key(&self, request: SigningKeyRequest) -> Result<RegisteredKey> {
+        if request.key_id.trim().is_empty() {
+            return Err(TradingError::InvalidRequest("Key id must not be empty".to_string()));
+        }
+        let bytes = decode_hex::<32>(&request.public_key, "Public key")?;
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| TradingError::InvalidRequest("Public key is not a valid Ed25519 key".to_string()))?;
+        if self.signing_keys.keys.contains_key(&request.key_id) {
+            return Err(TradingError::InvalidRequest(format!("Key {} is already registered", request.key_id)));
+        }
+
+        let key = RegisteredKey {
+            key_id: request.key_id,
+            account_id: request.account_id,
+            public_key: request.public_key.to_lowercase(),
+            required: request.required,
+            registered_at: self.time_provider.now(),
+            revoked_at: None,
+        };
+        self.signing_keys.keys.insert(key.key_id.clone(), key.clone());
+        Ok(key)
+    }
+
+    /// Revoked keys are kept so past signatures can still be attributed.
+    pub fn revoke_signing_key(&self, key_id: &str) -> Option<RegisteredKey> {
+        let mut key = self.signing_keys.keys.get_mut(key_id)?;
+        key.revoked_at.get_or_insert(self.time_provider.now());
+        Some(key.clone())
+    }
+
+    /// Submits an order, first verifying its signature if it has one.
+    /// Accounts with a required key must sign.
+    pub async fn submit_signed_order(&self, order: Order, signature: Option<OrderSignature>) -> Result<Uuid> {
+        let timestamp = self.time_provider.now();
+        let verified = match &signature {
+            Some(signature) => self.verify_order_signature(&order, signature, timestamp),
+            None if self.signing_keys.requires_signature(order.account_id) => Err(TradingError::Unauthorized(
+                "Account only accepts signed orders".to_string(),
+            )),
+            None => Ok(()),
+        };
+        if let Err(e) = verified {
+            self.reject_new_order(order, &e, timestamp);
+            return Err(e);
+        }
+
+        if let Some(signature) = signature {
+            self.event_journal.publish(
+                EngineEvent::OrderSignatureVerified {
+                    order_id: order.id,
+                    account_id: order.account_id,
+                    signature,
+                },
+                vec![order.account_id],
+            );
+        }
+        self.submit_order_at(order, timestamp).await
+    }
+
+    fn verify_order_signature(&self, order: &Order, signature: &OrderSignature, now: DateTime<Utc>) -> Result<()> {
+        let unauthorized = |reason: &str| Err(TradingError::Unauthorized(reason.to_string()));
+        let Some(key) = self.signing_keys.keys.get(&signature.key_id).map(|key| key.clone()) else {
+            return unauthorized("Unknown signing key");
+        };
+        if key.account_id != order.account_id || !key.is_active() {
+            return unauthorized("Signing key is not active for this account");
+        }
+        let max_age = Duration::milliseconds(self.config.signed_order_max_age_ms as i64);
+        if (now - signature.signed_at).abs() > max_age {
+            return unauthorized("Signature timestamp is outside the accepted window");
+        }
+
+        let public_key = VerifyingKey::from_bytes(&decode_hex::<32>(&key.public_key, "Public key")?)
+            .map_err(|_| TradingError::InternalError(format!("Stored key {} is invalid", key.key_id)))?;
+        let bytes = decode_hex::<64>(&signature.signature, "Signature")?;
+        let payload = canonical_payload(order, &signature.nonce, signature.signed_at);
+        if public_key.verify(payload.as_bytes(), &Signature::from_bytes(&bytes)).is_err() {
+            return unauthorized("Signature does not match the order");
+        }
+
+        // Checked last so a forged order cannot burn a genuine nonce
+        let nonces = self.signing_keys.nonces.entry(key.key_id.clone()).or_default();
+        if !nonces.lock().insert(&signature.nonce, signature.signed_at, now - max_age) {
+            return unauthorized("Nonce has already been used");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use ed25519_dalek::Signer;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(account_id: Uuid) -> Order {
+        OrderBuilder::new(OrderSide::Buy).price(dec!(99.2500)).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_signed_orders_are_verified_once_and_journaled() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let account = Uuid::new_v4();
+        let secret = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        engine
+            .register_signing_key(SigningKeyRequest {
+                key_id: "desk-1".to_string(),
+                account_id: account,
+                public_key: hex::encode(secret.verifying_key().to_bytes()),
+                required: true,
+            })
+            .unwrap();
+
+        let sign = |order: &Order, nonce: &str| {
+            let signed_at = Utc::now();
+            let payload = canonical_payload(order, nonce, signed_at);
+            OrderSignature {
+                key_id: "desk-1".to_string(),
+                nonce: nonce.to_string(),
+                signed_at,
+                signature: hex::encode(secret.sign(payload.as_bytes()).to_bytes()),
+            }
+        };
+
+        let signed = order(account);
+        let signature = sign(&signed, "n-1");
+        let order_id = engine.submit_signed_order(signed.clone(), Some(signature.clone())).await.unwrap();
+
+        let replayed = Order {
+            id: Uuid::new_v4(),
+            ..signed.clone()
+        };
+        let replay = engine.submit_signed_order(replayed, Some(signature)).await;
+        assert!(matches!(replay, Err(TradingError::Unauthorized(reason)) if reason.contains("Nonce")));
+
+        let mut tampered = order(account);
+        let signature = sign(&tampered, "n-2");
+        tampered.quantity = dec!(900000);
+        let forged = engine.submit_signed_order(tampered, Some(signature)).await;
+        assert!(matches!(forged, Err(TradingError::Unauthorized(reason)) if reason.contains("match")));
+        assert!(engine.submit_signed_order(order(account), None).await.is_err());
+
+        let journaled: Vec<(Uuid, String)> = engine
+            .replay_events(1, Some(account), 100)
+            .events
+            .into_iter()
+            .filter_map(|event| match event.event {
+                EngineEvent::OrderSignatureVerified {
+                    order_id, signature, ..
+                } => Some((order_id, signature.nonce)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(journaled, vec![(order_id, "n-1".to_string())]);
+
+        engine.revoke_signing_key("desk-1").unwrap();
+        let unsigned = order(account);
+        assert!(engine.submit_signed_order(unsigned, None).await.is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+//! Speed bumps. A symbol can hold orders that would take liquidity for a
+//! fixed or random delay before they reach the book, giving resting orders
+//! time to reprice. Orders that would only rest pass straight through.
+
+use crate::{engine::TradingEngine, types::*};
+use dashmap::DashMap;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SpeedBump {
+    Fixed { delay_us: u64 },
+    /// Uniform over the range, drawn per order.
+    Random { min_delay_us: u64, max_delay_us: u64 },
+}
+
+impl SpeedBump {
+    fn max_delay_us(&self) -> u64 {
+        match *self {
+            SpeedBump::Fixed { delay_us } => delay_us,
+            SpeedBump::Random { max_delay_us, .. } => max_delay_us,
+        }
+    }
+
+    pub fn draw(&self) -> Duration {
+        let delay_us = match *self {
+            SpeedBump::Fixed { delay_us } => delay_us,
+            SpeedBump::Random {
+                min_delay_us,
+                max_delay_us,
+            } => rand::thread_rng().gen_range(min_delay_us..=max_delay_us),
+        };
+        Duration::from_micros(delay_us)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeedBumpStatus {
+    pub symbol: String,
+    pub bump: SpeedBump,
+    /// Orders held since the bump was set.
+    pub delayed_orders: u64,
+}
+
+struct SymbolBump {
+    bump: SpeedBump,
+    delayed_orders: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct SpeedBumpRegistry {
+    bumps: DashMap<String, SymbolBump>,
+}
+
+impl SpeedBumpRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&self, symbol: &str) -> bool {
+        self.bumps.remove(symbol).is_some()
+    }
+
+    pub fn list(&self) -> Vec<SpeedBumpStatus> {
+        let mut bumps: Vec<SpeedBumpStatus> = self
+            .bumps
+            .iter()
+            .map(|entry| SpeedBumpStatus {
+                symbol: entry.key().clone(),
+                bump: entry.bump,
+                delayed_orders: entry.delayed_orders.load(Ordering::Relaxed),
+            })
+            .collect();
+        bumps.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        bumps
+    }
+
+    /// Draws the delay for an order in `symbol` and counts it.
+    fn delay_for(&self, symbol: &str) -> Option<Duration> {
+        let entry = self.bumps.get(symbol)?;
+        entry.delayed_orders.fetch_add(1, Ordering::Relaxed);
+        Some(entry.bump.draw())
+    }
+}
+
+impl TradingEngine {
+    pub fn speed_bumps(&self) -> &SpeedBumpRegistry {
+        &self.speed_bumps
+    }
+
+    pub fn set_speed_bump(&self, symbol: &str, bump: SpeedBump) -> Result<()> {
+        if let SpeedBump::Random {
+            min_delay_us,
+            max_delay_us,
+        } = bump
+        {
+            if min_delay_us > max_delay_us {
+                return Err(TradingError::InvalidRequest(
+                    "A random speed bump's minimum must not exceed its maximum".to_string(),
+                ));
+            }
+        }
+        if bump.max_delay_us() > self.config.max_speed_bump_us {
+            return Err(TradingError::InvalidRequest(format!(
+                "Speed bumps may delay orders by at most {}us",
+                self.config.max_speed_bump_us
+            )));
+        }
+        self.speed_bumps.bumps.insert(
+            symbol.to_string(),
+            SymbolBump {
+                bump,
+                delayed_orders: AtomicU64::new(0),
+            },
+        );
+        Ok(())
+    }
+
+    /// Sleeps until the engine clock reaches `release`. A clock that runs
+    /// behind the timer, such as a simulated one, is waited on again for
+    /// whatever it still has to go.
+    async fn hold_until(&self, release: chrono::DateTime<chrono::Utc>) {
+        while let Ok(remaining) = (release - self.time_provider.now()).to_std() {
+            if remaining.is_zero() {
+                break;
+            }
+            tokio::time::sleep_until(Instant::now() + remaining).await;
+        }
+    }
+
+    /// Whether `order` would trade against the book as it stands.
+    pub(crate) fn is_marketable(&self, order: &Order) -> bool {
+        let opposite = match order.side {
+            OrderSide::Buy => self.matching_engine.get_best_ask(&order.symbol),
+            OrderSide::Sell => self.matching_engine.get_best_bid(&order.symbol),
+        };
+        match (opposite, order.price) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(best), Some(price)) => match order.side {
+                OrderSide::Buy => price >= best,
+                OrderSide::Sell => price <= best,
+            },
+        }
+    }
+
+    /// Holds a marketable order for its symbol's speed bump. Orders entering
+    /// an auction are not held, since nothing trades until the uncross.
+    pub(crate) async fn apply_speed_bump(&self, order: &Order) {
+        if !self.speed_bumps.bumps.contains_key(&order.symbol)
+            || self.in_auction(&order.symbol)
+            || !self.is_marketable(order)
+        {
+            return;
+        }
+        if let Some(delay) = self.speed_bumps.delay_for(&order.symbol) {
+            debug!("Holding order {} for a {:?} speed bump", order.id, delay);
+            let release = self.time_provider.now()
+                + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero());
+            self.hold_until(release).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, price: rust_decimal::Decimal) -> Order {
+        OrderBuilder::new(side).price(price).build()
+    }
+
+    #[tokio::test]
+    async fn test_speed_bump_holds_only_marketable_orders() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let too_slow = SpeedBump::Fixed { delay_us: 10_000_000 };
+        assert!(engine.set_speed_bump("GSEC10Y", too_slow).is_err());
+        engine.set_speed_bump("GSEC10Y", SpeedBump::Fixed { delay_us: 40_000 }).unwrap();
+        engine.submit_order(order(OrderSide::Sell, dec!(99.50))).await.unwrap();
+
+        let started = Instant::now();
+        engine.submit_order(order(OrderSide::Buy, dec!(99.25))).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(40));
+
+        let started = Instant::now();
+        let taker = engine.submit_order(order(OrderSide::Buy, dec!(99.50))).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(40));
+        assert_eq!(engine.get_order(&taker).unwrap().status, OrderStatus::Filled);
+        assert_eq!(engine.speed_bumps().list()[0].delayed_orders, 1);
+
+        let random = SpeedBump::Random {
+            min_delay_us: 200,
+            max_delay_us: 900,
+        };
+        assert!((0..100).map(|_| random.draw()).all(|delay| {
+            (Duration::from_micros(200)..=Duration::from_micros(900)).contains(&delay)
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_holds_follow_the_engine_clock() {
+        use crate::utils::time::{Clock, SimulatedClock, TimeProvider};
+        use chrono::{TimeZone, Utc};
+
+        let clock = Arc::new(SimulatedClock::new(Utc.with_ymd_and_hms(2024, 1, 8, 5, 0, 0).unwrap()));
+        let time_provider = Arc::new(TimeProvider::with_clock(clock.clone()));
+        let engine = TradingEngine::with_time_provider(Arc::new(Config::default()), time_provider)
+            .await
+            .unwrap();
+        let release = clock.now() + chrono::Duration::milliseconds(20);
+
+        // The timer runs out but the engine clock has not moved
+        let held = tokio::time::timeout(Duration::from_millis(60), engine.hold_until(release)).await;
+        assert!(held.is_err());
+
+        clock.advance(chrono::Duration::milliseconds(20));
+        let held = tokio::time::timeout(Duration::from_millis(60), engine.hold_until(release)).await;
+        assert!(held.is_ok());
+    }
+}
@@ -0,0 +1,528 @@
+use crate::{
+    engine::{
+        implied::ImpliedLevel,
+        price_time_book::{depth, Fill, PriceTimeBook, RestingOrder},
+        EngineEvent, TradingEngine,
+    },
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// One outright in a multi-leg order. Buying the spread buys `ratio` units
+/// of each positive leg and sells `|ratio|` units of each negative one, so
+/// a 2s10s steepener is `+1 GSEC10Y, -1 GSEC2Y` and a butterfly is
+/// `-1, +2, -1`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpreadLeg {
+    pub symbol: String,
+    pub ratio: i32,
+}
+
+impl SpreadLeg {
+    /// Side this leg trades on when the spread is bought or sold.
+    pub fn side(&self, spread_side: &OrderSide) -> OrderSide {
+        match (spread_side, self.ratio > 0) {
+            (OrderSide::Buy, true) | (OrderSide::Sell, false) => OrderSide::Buy,
+            _ => OrderSide::Sell,
+        }
+    }
+
+    pub fn quantity(&self, spread_quantity: Decimal) -> Decimal {
+        spread_quantity * Decimal::from(self.ratio.unsigned_abs())
+    }
+}
+
+/// A multi-leg order priced as a net: the ratio-weighted sum of its leg
+/// prices. The net may be negative.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpreadOrder {
+    pub id: Uuid,
+    pub client_order_id: String,
+    pub legs: Vec<SpreadLeg>,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub filled_quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    pub status: OrderStatus,
+    pub timestamp: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    /// Set for orders quoted against a synthetic index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_reference: Option<IndexReference>,
+    /// Why the order stopped working, once cancelled.
+    #[serde(default)]
+    pub cancel_reason: Option<CancelReason>,
+}
+
+/// The index value a spread order's offset was applied to, fixed when the
+/// order was accepted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexReference {
+    pub index: String,
+    pub value: Decimal,
+    pub offset: Decimal,
+}
+
+impl SpreadOrder {
+    /// Book the order trades in. Legs are kept sorted by symbol, so the
+    /// same combination always lands in the same book.
+    pub fn key(&self) -> String {
+        spread_key(&self.legs)
+    }
+
+    pub fn leg(&self, symbol: &str) -> Option<&SpreadLeg> {
+        self.legs.iter().find(|leg| leg.symbol == symbol)
+    }
+
+    /// Stand-in outright order for one leg, used to build leg trades.
+    pub(crate) fn leg_order(&self, leg: &SpreadLeg, price: Decimal) -> Order {
+        let quantity = leg.quantity(self.quantity);
+        Order {
+            id: self.id,
+            client_order_id: self.client_order_id.clone(),
+            symbol: leg.symbol.clone(),
+            side: leg.side(&self.side),
+            order_type: OrderType::Limit,
+            quantity,
+            price: Some(price),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: quantity,
+            status: OrderStatus::Pending,
+            timestamp: self.timestamp,
+            user_id: self.user_id,
+            account_id: self.account_id,
+            time_in_force: TimeInForce::ImmediateOrCancel,
+            metadata: HashMap::new(),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        }
+    }
+
+    /// Outright order that executes `spread_quantity` units of one leg
+    /// against the books on this order's behalf.
+    pub(crate) fn child_order(&self, leg: &SpreadLeg, spread_quantity: Decimal, timestamp: DateTime<Utc>) -> Order {
+        let quantity = leg.quantity(spread_quantity);
+        let mut child = self.leg_order(leg, Decimal::ZERO);
+        child.id = Uuid::new_v4();
+        child.client_order_id = format!("{}-{}", self.client_order_id, leg.symbol);
+        child.quantity = quantity;
+        child.remaining_quantity = quantity;
+        child.price = None;
+        child.timestamp = timestamp;
+        child
+            .metadata
+            .insert("spread_order_id".to_string(), self.id.to_string());
+        child
+    }
+}
+
+impl RestingOrder for SpreadOrder {
+    /// Leg prices, in leg order.
+    type Pricing = Vec<Decimal>;
+    type Book = Vec<SpreadLeg>;
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn key(&self) -> String {
+        spread_key(&self.legs)
+    }
+
+    fn book(&self) -> Vec<SpreadLeg> {
+        self.legs.clone()
+    }
+
+    fn side(&self) -> &OrderSide {
+        &self.side
+    }
+
+    fn level(&self) -> Decimal {
+        self.price
+    }
+
+    fn remaining(&self) -> Decimal {
+        self.remaining_quantity
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self.status, OrderStatus::Pending | OrderStatus::PartiallyFilled)
+    }
+
+    fn fill(&mut self, quantity: Decimal) {
+        self.filled_quantity += quantity;
+        self.remaining_quantity -= quantity;
+        self.status = if self.remaining_quantity <= Decimal::ZERO {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+    }
+
+    fn cancel(&mut self, reason: CancelReason) {
+        self.status = OrderStatus::Cancelled;
+        self.cancel_reason = Some(reason);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpreadOrderRequest {
+    pub client_order_id: String,
+    pub legs: Vec<SpreadLeg>,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    /// Quotes `price` as an offset from this synthetic index's value.
+    #[serde(default)]
+    pub reference_index: Option<String>,
+}
+
+/// A spread-versus-spread match, with the leg prices it executes at.
+pub type SpreadFill = Fill<SpreadOrder>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpreadExecution {
+    pub order: SpreadOrder,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpreadBookSummary {
+    pub key: String,
+    pub legs: Vec<SpreadLeg>,
+    pub best_bid: Option<Decimal>,
+    pub best_ask: Option<Decimal>,
+    pub bid_quantity: Decimal,
+    pub ask_quantity: Decimal,
+    pub implied_bid: Option<ImpliedLevel>,
+    pub implied_ask: Option<ImpliedLevel>,
+}
+
+/// Resting spread orders, one price-time book per leg combination.
+pub type SpreadBook = PriceTimeBook<SpreadOrder>;
+
+pub fn spread_key(legs: &[SpreadLeg]) -> String {
+    legs.iter()
+        .map(|leg| format!("{}:{:+}", leg.symbol, leg.ratio))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+impl TradingEngine {
+    /// Accepts a multi-leg order. Its legs pass the same checks as
+    /// outright orders at the spread's own price. It first trades against
+    /// opposing spread orders, then tries to fill what is left in one go
+    /// against the outright books, and rests any remainder on the spread
+    /// book, all without letting another spread order in between. Every leg
+    /// of a fill executes or none does.
+    pub async fn submit_spread_order(
+        &self,
+        request: SpreadOrderRequest,
+    ) -> crate::types::Result<SpreadExecution> {
+        self.await_event_consumers().await?;
+        let mut order = self.build_spread_order(request)?;
+        info!("Submitting spread order {} on {}", order.id, order.key());
+
+        if let Err(e) = self.check_spread_order(&order).await {
+            self.reject_spread_order(order, &e);
+            return Err(e);
+        }
+
+        let legs = order.legs.clone();
+        let trades = self.spread_book.submit(
+            &mut order,
+            |resting| self.leg_prices(&legs, resting.price),
+            |_, _, _| true,
+            |order, fills| {
+                let mut trades = Vec::new();
+                for fill in fills {
+                    trades.extend(self.spread_leg_trades(order, fill));
+                    self.publish_spread_order(&fill.resting);
+                }
+                if order.remaining_quantity > Decimal::ZERO {
+                    trades.extend(self.execute_implied(order));
+                }
+                trades
+            },
+        );
+        self.record_trades(&trades).await?;
+        self.publish_spread_order(&order);
+
+        Ok(SpreadExecution { order, trades })
+    }
+
+    /// Checks a spread order as its legs would trade at the spread's price.
+    /// The legs count as one message.
+    async fn check_spread_order(&self, order: &SpreadOrder) -> crate::types::Result<()> {
+        let prices = self.leg_prices(&order.legs, order.price).ok_or_else(|| {
+            TradingError::InvalidOrder("Spread price cannot be split into positive leg prices".to_string())
+        })?;
+        let legs: Vec<Order> = order
+            .legs
+            .iter()
+            .zip(prices)
+            .map(|(leg, price)| order.leg_order(leg, self.matching_engine.tick_scale(&leg.symbol).round_price(price)))
+            .collect();
+        self.check_new_orders(&legs).await
+    }
+
+    /// Keeps a spread order that failed its checks, with why.
+    fn reject_spread_order(&self, mut order: SpreadOrder, error: &TradingError) {
+        let reject_reason = error.reject_reason();
+        self.metrics.increment_order_rejects(&reject_reason);
+        order.status = OrderStatus::Rejected(reject_reason);
+        order.remaining_quantity = Decimal::ZERO;
+        self.spread_book.record(order.clone());
+        self.publish_spread_order(&order);
+    }
+
+    pub fn cancel_spread_order(&self, order_id: Uuid) -> crate::types::Result<bool> {
+        if self.spread_book.get(&order_id).is_none() {
+            return Err(TradingError::OrderNotFound(order_id.to_string()));
+        }
+        match self.spread_book.cancel(order_id, CancelReason::ClientRequest) {
+            Some(order) => {
+                self.publish_spread_order(&order);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn get_spread_orders(&self, account_id: Option<Uuid>) -> Vec<SpreadOrder> {
+        self.spread_book
+            .orders(|order| account_id.is_none_or(|id| order.account_id == id))
+    }
+
+    /// Spread book summaries with the implied-in levels the outright books
+    /// currently support.
+    pub fn spread_books(&self) -> Vec<SpreadBookSummary> {
+        self.spread_book.summaries(|key, book| SpreadBookSummary {
+            key: key.to_string(),
+            legs: book.description.clone(),
+            best_bid: book.bids.keys().next_back().copied(),
+            best_ask: book.asks.keys().next().copied(),
+            bid_quantity: depth(&book.bids),
+            ask_quantity: depth(&book.asks),
+            implied_bid: self.implied_spread_level(&book.description, &OrderSide::Sell),
+            implied_ask: self.implied_spread_level(&book.description, &OrderSide::Buy),
+        })
+    }
+
+    fn build_spread_order(&self, request: SpreadOrderRequest) -> crate::types::Result<SpreadOrder> {
+        if request.legs.len() < 2 {
+            return Err(TradingError::InvalidOrder(
+                "Spread orders need at least two legs".to_string(),
+            ));
+        }
+        if request.quantity <= Decimal::ZERO {
+            return Err(TradingError::InvalidOrder("Quantity must be positive".to_string()));
+        }
+
+        let mut symbols = HashSet::new();
+        for leg in &request.legs {
+            if leg.ratio == 0 {
+                return Err(TradingError::InvalidOrder(format!(
+                    "Leg {} has a zero ratio",
+                    leg.symbol
+                )));
+            }
+            if !symbols.insert(leg.symbol.as_str()) {
+                return Err(TradingError::InvalidOrder(format!(
+                    "{} appears in more than one leg",
+                    leg.symbol
+                )));
+            }
+            self.check_instrument_tradable(&leg.symbol)?;
+            if self.in_auction(&leg.symbol) {
+                return Err(TradingError::InstrumentNotTradable(format!(
+                    "{} is in auction",
+                    leg.symbol
+                )));
+            }
+        }
+
+        let index_reference = match request.reference_index {
+            Some(index) => Some(IndexReference {
+                value: self.index_value(&index)?,
+                index,
+                offset: request.price,
+            }),
+            None => None,
+        };
+        let price = index_reference
+            .as_ref()
+            .map_or(request.price, |reference| reference.value + reference.offset);
+
+        let mut legs = request.legs;
+        legs.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        if self.leg_prices(&legs, price).is_none() {
+            return Err(TradingError::InvalidOrder(
+                "Spread price cannot be split into positive leg prices".to_string(),
+            ));
+        }
+
+        Ok(SpreadOrder {
+            id: Uuid::new_v4(),
+            client_order_id: request.client_order_id,
+            legs,
+            side: request.side,
+            quantity: request.quantity,
+            price,
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: request.quantity,
+            status: OrderStatus::Pending,
+            timestamp: self.time_provider.now(),
+            user_id: request.user_id,
+            account_id: request.account_id,
+            index_reference,
+            cancel_reason: None,
+        })
+    }
+
+    /// Splits a net spread price into leg prices whose ratio-weighted sum
+    /// is exactly that net. Every leg but one is priced at its reference;
+    /// the remaining leg (the one without a reference, else the first)
+    /// absorbs the difference and must come out positive.
+    pub(crate) fn leg_prices(&self, legs: &[SpreadLeg], spread_price: Decimal) -> Option<Vec<Decimal>> {
+        let references: Vec<Option<Decimal>> = legs
+            .iter()
+            .map(|leg| self.leg_reference(&leg.symbol))
+            .collect();
+        let unpriced = references.iter().filter(|price| price.is_none()).count();
+        if unpriced > 1 {
+            return None;
+        }
+        let solved = references.iter().position(Option::is_none).unwrap_or(0);
+
+        let mut others = Decimal::ZERO;
+        for (index, (leg, reference)) in legs.iter().zip(&references).enumerate() {
+            if index != solved {
+                others += Decimal::from(leg.ratio) * (*reference)?;
+            }
+        }
+        let price = (spread_price - others) / Decimal::from(legs[solved].ratio);
+        if price <= Decimal::ZERO {
+            return None;
+        }
+
+        let mut prices: Vec<Decimal> = references.into_iter().map(Option::unwrap_or_default).collect();
+        prices[solved] = price;
+        Some(prices)
+    }
+
+    /// The symbol's reference price, else whichever side of its book is
+    /// quoted.
+    pub(crate) fn leg_reference(&self, symbol: &str) -> Option<Decimal> {
+        match self.reference_price(symbol) {
+            Ok(reference) => Some(reference.price),
+            Err(_) => self
+                .matching_engine
+                .get_best_bid(symbol)
+                .or_else(|| self.matching_engine.get_best_ask(symbol)),
+        }
+    }
+
+    /// One trade per leg for a spread-versus-spread fill. The incoming
+    /// order is the aggressor on every leg.
+    fn spread_leg_trades(&self, incoming: &SpreadOrder, fill: &SpreadFill) -> Vec<Trade> {
+        incoming
+            .legs
+            .iter()
+            .zip(&fill.pricing)
+            .map(|(leg, &price)| {
+                let aggressor = incoming.leg_order(leg, price);
+                let resting = fill.resting.leg_order(leg, price);
+                let mut trade =
+                    self.matching_engine
+                        .build_trade(&aggressor, &resting, leg.quantity(fill.quantity), price);
+                trade.trade_type = TradeType::SpreadLeg;
+                self.matching_engine.publish_trade(&trade, &aggressor, &resting);
+                trade
+            })
+            .collect()
+    }
+
+    /// Fills the whole remainder against the outright books if the legs can
+    /// be bought and sold at a combined net no worse than the order's
+    /// limit. Every leg is sized against its book before any of them
+    /// trades, and the spread is filled only by what every leg achieved.
+    /// Each leg becomes a child order that is recorded alongside regular
+    /// orders.
+    fn execute_implied(&self, order: &mut SpreadOrder) -> Vec<Trade> {
+        let now = self.time_provider.now();
+        let mut children: Vec<Order> = order
+            .legs
+            .iter()
+            .map(|leg| order.child_order(leg, order.remaining_quantity, now))
+            .collect();
+
+        let ratios: Vec<i32> = order.legs.iter().map(|leg| leg.ratio).collect();
+        let (side, limit) = (order.side.clone(), order.price);
+        let executed = self.matching_engine.execute_legs(&mut children, |vwaps| {
+            let net: Decimal = ratios
+                .iter()
+                .zip(vwaps)
+                .map(|(&ratio, &price)| Decimal::from(ratio) * price)
+                .sum();
+            match side {
+                OrderSide::Buy => net <= limit,
+                OrderSide::Sell => net >= limit,
+            }
+        });
+        let trades = match executed {
+            Ok(Some(trades)) => trades,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!("Spread order {} cannot trade against the outright books: {}", order.id, e);
+                return Vec::new();
+            }
+        };
+
+        let achieved = children
+            .iter()
+            .zip(&order.legs)
+            .map(|(child, leg)| child.filled_quantity / Decimal::from(leg.ratio.unsigned_abs()))
+            .min()
+            .unwrap_or_default();
+        self.store_child_orders(children);
+        if achieved > Decimal::ZERO {
+            order.fill(achieved);
+        }
+        trades
+    }
+
+    /// Records executed child orders as new. Their fills are applied to the
+    /// stored copies when the trades are recorded.
+    pub(crate) fn store_child_orders(&self, children: Vec<Order>) {
+        for child in children {
+            self.orders.insert(
+                child.id,
+                Order {
+                    filled_quantity: Decimal::ZERO,
+                    remaining_quantity: child.quantity,
+                    status: OrderStatus::Pending,
+                    fills: OrderFills::default(),
+                    ..child
+                },
+            );
+        }
+    }
+
+    pub(crate) fn publish_spread_order(&self, order: &SpreadOrder) {
+        self.event_journal.publish(
+            EngineEvent::SpreadOrderUpdated(order.clone()),
+            vec![order.account_id],
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{types::*, utils::time::TimeProvider};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Strategy {
+    pub id: String,
+    pub name: String,
+    /// Accounts allowed to tag orders with this strategy; empty means any.
+    pub account_ids: Vec<Uuid>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrategyRequest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub account_ids: Vec<Uuid>,
+}
+
+/// Strategies that orders may be tagged with.
+pub struct StrategyRegistry {
+    strategies: DashMap<String, Strategy>,
+    time_provider: Arc<TimeProvider>,
+}
+
+impl StrategyRegistry {
+    pub fn new(time_provider: Arc<TimeProvider>) -> Self {
+        Self {
+            strategies: DashMap::new(),
+            time_provider,
+        }
+    }
+
+    pub fn register(&self, request: StrategyRequest) -> crate::types::Result<Strategy> {
+        if request.id.is_empty() {
+            return Err(TradingError::InvalidRequest("Strategy id cannot be empty".to_string()));
+        }
+        if self.strategies.contains_key(&request.id) {
+            return Err(TradingError::InvalidRequest(format!(
+                "Strategy {} already exists",
+                request.id
+            )));
+        }
+
+        let strategy = Strategy {
+            id: request.id,
+            name: request.name,
+            account_ids: request.account_ids,
+            active: true,
+            created_at: self.time_provider.now(),
+        };
+        self.strategies.insert(strategy.id.clone(), strategy.clone());
+        Ok(strategy)
+    }
+
+    pub fn set_active(&self, id: &str, active: bool) -> crate::types::Result<Strategy> {
+        let mut strategy = self
+            .strategies
+            .get_mut(id)
+            .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown strategy {}", id)))?;
+        strategy.active = active;
+        Ok(strategy.clone())
+    }
+
+    pub fn list(&self) -> Vec<Strategy> {
+        let mut strategies: Vec<Strategy> = self.strategies.iter().map(|entry| entry.value().clone()).collect();
+        strategies.sort_by(|a, b| a.id.cmp(&b.id));
+        strategies
+    }
+
+    /// Checks an order's strategy tag, if it has one.
+    pub fn validate(&self, order: &Order) -> crate::types::Result<()> {
+        let Some(strategy_id) = &order.strategy_id else {
+            return Ok(());
+        };
+        let invalid = |message| TradingError::InvalidOrderField {
+            field: OrderField::Strategy,
+            message,
+        };
+        let strategy = self
+            .strategies
+            .get(strategy_id)
+            .ok_or_else(|| invalid(format!("Unknown strategy {}", strategy_id)))?;
+
+        if !strategy.active {
+            return Err(invalid(format!("Strategy {} is inactive", strategy_id)));
+        }
+        if !strategy.account_ids.is_empty() && !strategy.account_ids.contains(&order.account_id) {
+            return Err(invalid(format!(
+                "Account {} may not trade strategy {}",
+                order.account_id, strategy_id
+            )));
+        }
+        Ok(())
+    }
+}
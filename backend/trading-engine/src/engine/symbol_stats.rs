@@ -0,0 +1,202 @@
+//! Per-symbol session statistics kept from the event stream: matched
+//! volume, trade count and the largest trade of the day, plus the open
+//! interest resting on each side of the book, re-read whenever an order
+//! event touches the symbol.
+
+use crate::{
+    engine::{event_journal::SequencedEvent, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Quantity and order count resting on each side of a book.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct OpenInterest {
+    pub bid_quantity: Decimal,
+    pub ask_quantity: Decimal,
+    pub bid_orders: usize,
+    pub ask_orders: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LargestTrade {
+    pub trade_id: Uuid,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolStats {
+    pub symbol: String,
+    pub session_date: NaiveDate,
+    pub matched_volume: Decimal,
+    pub trade_count: u64,
+    pub largest_trade: Option<LargestTrade>,
+    #[serde(flatten)]
+    pub open_interest: OpenInterest,
+    /// The last event applied.
+    pub as_of_seq: u64,
+}
+
+impl SymbolStats {
+    fn new(symbol: &str, session_date: NaiveDate) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            session_date,
+            matched_volume: Decimal::ZERO,
+            trade_count: 0,
+            largest_trade: None,
+            open_interest: OpenInterest::default(),
+            as_of_seq: 0,
+        }
+    }
+
+    /// Starts a new session's counts; resting interest carries over.
+    fn roll_to(&mut self, session_date: NaiveDate) {
+        if self.session_date < session_date {
+            self.session_date = session_date;
+            self.matched_volume = Decimal::ZERO;
+            self.trade_count = 0;
+            self.largest_trade = None;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SymbolStatsTracker {
+    stats: DashMap<String, SymbolStats>,
+    last_seq: AtomicU64,
+}
+
+impl SymbolStatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TradingEngine {
+    /// The symbol's statistics for today's session.
+    pub fn symbol_stats(&self, symbol: &str) -> crate::types::Result<SymbolStats> {
+        let today = self.time_provider.today();
+        match self.symbol_stats.stats.get(symbol) {
+            Some(stats) => {
+                let mut stats = stats.clone();
+                stats.roll_to(today);
+                Ok(stats)
+            }
+            None if self.instruments.get(symbol).is_some() => Ok(SymbolStats::new(symbol, today)),
+            None => Err(TradingError::InstrumentNotFound(symbol.to_string())),
+        }
+    }
+
+    /// Applies one event, once; events already applied are skipped.
+    pub fn apply_symbol_stats(&self, event: &SequencedEvent) {
+        let tracker = &self.symbol_stats;
+        if tracker.last_seq.fetch_max(event.seq, Ordering::AcqRel) >= event.seq {
+            return;
+        }
+        let symbol = match &event.event {
+            EngineEvent::TradeExecuted(trade) => Some(trade.symbol.clone()),
+            EngineEvent::OrderSubmitted(order) => Some(order.symbol.clone()),
+            EngineEvent::OrderCancelled { order_id, .. }
+            | EngineEvent::OrderExpired { order_id, .. }
+            | EngineEvent::OrderReduced { order_id, .. } => self.get_order(order_id).map(|order| order.symbol),
+            EngineEvent::InstrumentStatusChanged { symbol, .. } => Some(symbol.clone()),
+            _ => None,
+        };
+        let Some(symbol) = symbol else {
+            return;
+        };
+
+        let session_date = event.timestamp.date_naive();
+        let mut stats = tracker
+            .stats
+            .entry(symbol.clone())
+            .or_insert_with(|| SymbolStats::new(&symbol, session_date));
+        stats.roll_to(session_date);
+        if let EngineEvent::TradeExecuted(trade) = &event.event {
+            stats.matched_volume += trade.quantity;
+            stats.trade_count += 1;
+            if stats.largest_trade.as_ref().is_none_or(|largest| trade.quantity > largest.quantity) {
+                stats.largest_trade = Some(LargestTrade {
+                    trade_id: trade.id,
+                    quantity: trade.quantity,
+                    price: trade.price,
+                    timestamp: trade.timestamp,
+                });
+            }
+        }
+        stats.open_interest = self.matching_engine.open_interest(&symbol);
+        stats.as_of_seq = event.seq;
+    }
+}
+
+/// Stats task: applies each event as it is published, replaying from the
+/// journal after falling behind so no trade goes uncounted.
+pub async fn run_symbol_stats(engine: Arc<TradingEngine>) {
+    let mut events = engine.subscribe_events();
+    loop {
+        match events.recv().await {
+            Ok(event) => engine.apply_symbol_stats(&event),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Symbol stats task skipped {} events, replaying from the journal", skipped);
+                let from = engine.symbol_stats.last_seq.load(Ordering::Acquire) + 1;
+                let replay = engine.replay_events(from, None, usize::MAX);
+                if replay.truncated {
+                    error!(
+                        "Symbol stats miss events before {}, which have left the journal",
+                        replay.oldest_available_seq
+                    );
+                }
+                for event in &replay.events {
+                    engine.apply_symbol_stats(event);
+                }
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).build()
+    }
+
+    #[tokio::test]
+    async fn test_stats_follow_the_event_stream() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        engine.submit_order(order(OrderSide::Sell, dec!(300000), dec!(99.50))).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(200000), dec!(99.50))).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(50000), dec!(99.50))).await.unwrap();
+        let bid = engine.submit_order(order(OrderSide::Buy, dec!(100000), dec!(99.00))).await.unwrap();
+        engine.cancel_order(bid).await.unwrap();
+
+        let replay = engine.replay_events(1, None, usize::MAX);
+        for event in replay.events.iter().chain(&replay.events) {
+            engine.apply_symbol_stats(event);
+        }
+        let stats = engine.symbol_stats("GSEC10Y").unwrap();
+        assert_eq!((stats.matched_volume, stats.trade_count), (dec!(250000), 2));
+        assert_eq!(stats.largest_trade.unwrap().quantity, dec!(200000));
+        assert_eq!((stats.open_interest.ask_quantity, stats.open_interest.ask_orders), (dec!(50000), 1));
+        assert_eq!(stats.open_interest.bid_quantity, dec!(0));
+        assert_eq!(stats.as_of_seq, replay.next_seq - 1);
+    }
+}
@@ -0,0 +1,323 @@
+//! Synthetic indices: weighted baskets of listed bonds, valued from the
+//! constituents' marks. They are tickers only and never trade, but a spread
+//! order may be quoted against one and positions can be bucketed by the
+//! indices their bonds belong to.
+
+use crate::{
+    engine::{EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexConstituent {
+    pub symbol: String,
+    pub weight: Decimal,
+}
+
+fn default_divisor() -> Decimal {
+    Decimal::ONE
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticIndex {
+    #[serde(default)]
+    pub symbol: String,
+    pub name: String,
+    pub constituents: Vec<IndexConstituent>,
+    /// The weighted sum of marks is divided by this to give the value.
+    #[serde(default = "default_divisor")]
+    pub divisor: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstituentMark {
+    pub symbol: String,
+    pub weight: Decimal,
+    pub mark: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexTicker {
+    pub symbol: String,
+    pub name: String,
+    /// Unset while any constituent has no mark.
+    pub value: Option<Decimal>,
+    pub constituents: Vec<ConstituentMark>,
+    pub as_of: DateTime<Utc>,
+}
+
+/// An account's positions in one index's constituents.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexExposure {
+    pub index: String,
+    /// Face value held across the constituents, net.
+    pub net_position: Decimal,
+    /// Face value weighted as the index weights its constituents.
+    pub weighted_position: Decimal,
+    pub market_value: Decimal,
+}
+
+#[derive(Default)]
+pub struct SyntheticIndexRegistry {
+    indices: DashMap<String, SyntheticIndex>,
+    tickers: DashMap<String, IndexTicker>,
+}
+
+impl SyntheticIndexRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<SyntheticIndex> {
+        self.indices.get(symbol).map(|index| index.clone())
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.indices.contains_key(symbol)
+    }
+
+    pub fn all(&self) -> Vec<SyntheticIndex> {
+        let mut indices: Vec<SyntheticIndex> = self.indices.iter().map(|index| index.clone()).collect();
+        indices.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        indices
+    }
+
+    /// The latest valuation of each index.
+    pub fn tickers(&self) -> Vec<IndexTicker> {
+        let mut tickers: Vec<IndexTicker> = self.tickers.iter().map(|ticker| ticker.clone()).collect();
+        tickers.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        tickers
+    }
+
+    pub fn ticker(&self, symbol: &str) -> Option<IndexTicker> {
+        self.tickers.get(symbol).map(|ticker| ticker.clone())
+    }
+}
+
+impl TradingEngine {
+    pub fn synthetic_indices(&self) -> &SyntheticIndexRegistry {
+        &self.synthetic_indices
+    }
+
+    /// Defines or redefines an index and values it at once.
+    pub fn define_index(&self, mut index: SyntheticIndex) -> Result<IndexTicker> {
+        index.symbol = index.symbol.trim().to_string();
+        if index.symbol.is_empty() {
+            return Err(TradingError::InvalidRequest("Index symbol cannot be empty".to_string()));
+        }
+        if self.instruments.get(&index.symbol).is_some() {
+            return Err(TradingError::InvalidRequest(format!(
+                "{} is a listed instrument",
+                index.symbol
+            )));
+        }
+        if index.constituents.is_empty() {
+            return Err(TradingError::InvalidRequest("An index needs at least one constituent".to_string()));
+        }
+        if index.divisor <= Decimal::ZERO {
+            return Err(TradingError::InvalidRequest("Divisor must be positive".to_string()));
+        }
+        let mut symbols = HashSet::new();
+        for constituent in &mut index.constituents {
+            constituent.symbol = self.instruments.canonical(&constituent.symbol);
+            if self.instruments.get(&constituent.symbol).is_none() {
+                return Err(TradingError::InstrumentNotFound(constituent.symbol.clone()));
+            }
+            if constituent.weight <= Decimal::ZERO {
+                return Err(TradingError::InvalidRequest(format!(
+                    "{} must have a positive weight",
+                    constituent.symbol
+                )));
+            }
+            if !symbols.insert(constituent.symbol.clone()) {
+                return Err(TradingError::InvalidRequest(format!(
+                    "{} appears more than once",
+                    constituent.symbol
+                )));
+            }
+        }
+
+        let ticker = self.value_index(&index);
+        self.synthetic_indices.indices.insert(index.symbol.clone(), index);
+        self.publish_index_ticker(ticker.clone());
+        Ok(ticker)
+    }
+
+    pub fn remove_index(&self, symbol: &str) -> bool {
+        self.synthetic_indices.tickers.remove(symbol);
+        self.synthetic_indices.indices.remove(symbol).is_some()
+    }
+
+    /// Revalues every index, publishing those whose value moved. Returns
+    /// how many did.
+    pub fn revalue_indices(&self) -> usize {
+        let mut changed = 0;
+        for index in self.synthetic_indices.all() {
+            let ticker = self.value_index(&index);
+            let previous = self.synthetic_indices.ticker(&index.symbol).and_then(|ticker| ticker.value);
+            if previous != ticker.value {
+                changed += 1;
+                self.publish_index_ticker(ticker);
+            }
+        }
+        changed
+    }
+
+    /// The index's value as last computed, as a reference price.
+    pub fn index_value(&self, symbol: &str) -> Result<Decimal> {
+        self.synthetic_indices
+            .ticker(symbol)
+            .ok_or_else(|| TradingError::InstrumentNotFound(format!("No synthetic index {}", symbol)))?
+            .value
+            .ok_or_else(|| TradingError::InvalidRequest(format!("{} has constituents without a mark", symbol)))
+    }
+
+    /// The account's positions bucketed by the indices their bonds are in.
+    /// A bond in several indices counts toward each.
+    pub async fn index_exposure(&self, account_id: Uuid) -> Vec<IndexExposure> {
+        let positions = self.position_manager.get_positions(Some(account_id)).await;
+        self.synthetic_indices
+            .all()
+            .into_iter()
+            .map(|index| {
+                let mut exposure = IndexExposure {
+                    index: index.symbol.clone(),
+                    net_position: Decimal::ZERO,
+                    weighted_position: Decimal::ZERO,
+                    market_value: Decimal::ZERO,
+                };
+                for constituent in &index.constituents {
+                    for position in positions.iter().filter(|position| position.symbol == constituent.symbol) {
+                        exposure.net_position += position.quantity;
+                        exposure.weighted_position += position.quantity * constituent.weight;
+                        exposure.market_value += position.market_value;
+                    }
+                }
+                exposure
+            })
+            .collect()
+    }
+
+    fn value_index(&self, index: &SyntheticIndex) -> IndexTicker {
+        let constituents: Vec<ConstituentMark> = index
+            .constituents
+            .iter()
+            .map(|constituent| ConstituentMark {
+                symbol: constituent.symbol.clone(),
+                weight: constituent.weight,
+                mark: self.leg_reference(&constituent.symbol),
+            })
+            .collect();
+        let value = constituents
+            .iter()
+            .map(|constituent| constituent.mark.map(|mark| mark * constituent.weight))
+            .sum::<Option<Decimal>>()
+            .map(|sum| (sum / index.divisor).round_dp(6));
+        IndexTicker {
+            symbol: index.symbol.clone(),
+            name: index.name.clone(),
+            value,
+            constituents,
+            as_of: self.time_provider.now(),
+        }
+    }
+
+    fn publish_index_ticker(&self, ticker: IndexTicker) {
+        self.synthetic_indices.tickers.insert(ticker.symbol.clone(), ticker.clone());
+        self.event_journal.publish(EngineEvent::IndexValueUpdated(ticker), Vec::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        engine::{
+            allocation::MatchingAlgorithm,
+            spreads::{SpreadLeg, SpreadOrderRequest},
+        },
+        utils::daycount::DayCount,
+    };
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn bond(symbol: &str, isin: &str, maturity_year: i32) -> Bond {
+        Bond {
+            isin: isin.to_string(),
+            symbol: symbol.to_string(),
+            issuer: "Government of India".to_string(),
+            maturity_date: Utc.with_ymd_and_hms(maturity_year, 6, 15, 0, 0, 0).unwrap(),
+            coupon_rate: dec!(7.10),
+            face_value: dec!(100),
+            bond_type: BondType::GovernmentSecurity,
+            rating: Some("SOV".to_string()),
+            is_active: true,
+            quote_convention: QuoteConvention::Clean,
+            coupon_frequency: 2,
+            day_count: DayCount::ThirtyE360,
+            floating_rate: None,
+            inflation_linked: None,
+            call_schedule: Vec::new(),
+            put_schedule: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_is_valued_from_marks_and_never_trades() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        for bond in [bond("GSEC5Y", "IN0020240019", 2029), bond("GSEC10Y", "IN0020240027", 2034)] {
+            engine.list_instrument(bond, MatchingAlgorithm::PriceTime).unwrap();
+        }
+        engine.reference_prices().set_external("GSEC5Y", dec!(100.00), Utc::now());
+        let index = SyntheticIndex {
+            symbol: "GSECIDX".to_string(),
+            name: "Benchmark G-Sec".to_string(),
+            constituents: vec![
+                IndexConstituent {
+                    symbol: "GSEC5Y".to_string(),
+                    weight: dec!(0.4),
+                },
+                IndexConstituent {
+                    symbol: "GSEC10Y".to_string(),
+                    weight: dec!(0.6),
+                },
+            ],
+            divisor: dec!(1),
+        };
+        assert_eq!(engine.define_index(index).unwrap().value, None);
+
+        engine.reference_prices().set_external("GSEC10Y", dec!(98.50), Utc::now());
+        assert_eq!(engine.revalue_indices(), 1);
+        assert_eq!(engine.index_value("GSECIDX").unwrap(), dec!(99.1));
+        assert_eq!(engine.revalue_indices(), 0);
+
+        let spread = engine
+            .submit_spread_order(SpreadOrderRequest {
+                client_order_id: "IDX-BASIS-1".to_string(),
+                legs: vec![
+                    SpreadLeg { symbol: "GSEC10Y".to_string(), ratio: 1 },
+                    SpreadLeg { symbol: "GSEC5Y".to_string(), ratio: -1 },
+                ],
+                side: OrderSide::Buy,
+                quantity: dec!(100000),
+                price: dec!(-100.60),
+                user_id: Uuid::new_v4(),
+                account_id: Uuid::new_v4(),
+                reference_index: Some("GSECIDX".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(spread.order.price, dec!(-1.5));
+        assert_eq!(spread.order.index_reference.unwrap().value, dec!(99.1));
+
+        assert!(engine.check_instrument_tradable("GSECIDX").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{engine::TradingEngine, types::*};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// An account's exposure in one tenor bucket, in face value.
+#[derive(Debug, Clone, Serialize)]
+pub struct TenorExposure {
+    pub bucket: TenorBucket,
+    pub net_position: Decimal,
+    pub open_buy_quantity: Decimal,
+    pub open_sell_quantity: Decimal,
+    /// The largest net position the bucket could reach if every resting
+    /// order on one side filled.
+    pub worst_case_net: Decimal,
+    pub limit: Option<Decimal>,
+}
+
+impl TenorExposure {
+    fn new(bucket: TenorBucket, limit: Option<Decimal>) -> Self {
+        Self {
+            bucket,
+            net_position: Decimal::ZERO,
+            open_buy_quantity: Decimal::ZERO,
+            open_sell_quantity: Decimal::ZERO,
+            worst_case_net: Decimal::ZERO,
+            limit,
+        }
+    }
+
+    fn update_worst_case(&mut self) {
+        self.worst_case_net = (self.net_position + self.open_buy_quantity)
+            .abs()
+            .max((self.net_position - self.open_sell_quantity).abs());
+    }
+}
+
+impl TradingEngine {
+    /// The bucket of a listed symbol by its remaining maturity today.
+    pub(crate) fn tenor_bucket(&self, symbol: &str) -> Option<TenorBucket> {
+        let bond = self.instruments.get(symbol)?;
+        Some(TenorBucket::classify(
+            bond.maturity_date.date_naive(),
+            self.time_provider.now().date_naive(),
+        ))
+    }
+
+    /// Positions and resting orders of an account by tenor bucket, against
+    /// its bucket limits. Unlisted symbols are left out.
+    pub async fn tenor_exposure(&self, account_id: Uuid) -> crate::types::Result<Vec<TenorExposure>> {
+        let limits = self.risk_manager.risk_limits(account_id).await?;
+        let mut buckets: BTreeMap<TenorBucket, TenorExposure> = TenorBucket::ALL
+            .into_iter()
+            .map(|bucket| {
+                let limit = limits.max_tenor_bucket_position.get(&bucket).copied();
+                (bucket, TenorExposure::new(bucket, limit))
+            })
+            .collect();
+
+        for position in self.position_manager.get_positions(Some(account_id)).await {
+            if let Some(exposure) = self.tenor_bucket(&position.symbol).and_then(|bucket| buckets.get_mut(&bucket)) {
+                exposure.net_position += position.quantity;
+            }
+        }
+        for entry in self.orders.iter() {
+            let order = entry.value();
+            if order.account_id != account_id || !order.is_open() {
+                continue;
+            }
+            if let Some(exposure) = self.tenor_bucket(&order.symbol).and_then(|bucket| buckets.get_mut(&bucket)) {
+                match order.side {
+                    OrderSide::Buy => exposure.open_buy_quantity += order.remaining_quantity,
+                    OrderSide::Sell => exposure.open_sell_quantity += order.remaining_quantity,
+                }
+            }
+        }
+
+        Ok(buckets
+            .into_values()
+            .map(|mut exposure| {
+                exposure.update_worst_case();
+                exposure
+            })
+            .collect())
+    }
+
+    /// Checks the bucket limit of the order's symbol as if the order were
+    /// resting alongside the account's other orders.
+    pub(crate) async fn check_tenor_limits(&self, order: &Order) -> crate::types::Result<()> {
+        let limits = self.risk_manager.risk_limits(order.account_id).await?;
+        let Some(bucket) = self
+            .tenor_bucket(&order.symbol)
+            .filter(|bucket| limits.max_tenor_bucket_position.contains_key(bucket))
+        else {
+            return Ok(());
+        };
+
+        let exposures = self.tenor_exposure(order.account_id).await?;
+        let Some(mut exposure) = exposures.into_iter().find(|exposure| exposure.bucket == bucket) else {
+            return Ok(());
+        };
+        match order.side {
+            OrderSide::Buy => exposure.open_buy_quantity += order.quantity,
+            OrderSide::Sell => exposure.open_sell_quantity += order.quantity,
+        }
+        exposure.update_worst_case();
+        match exposure.limit {
+            Some(limit) if exposure.worst_case_net > limit => Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::TenorBucket,
+                message: format!(
+                    "Net position in the {} bucket could reach {}, limit {}",
+                    bucket.label(),
+                    exposure.worst_case_net,
+                    limit
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use chrono::{Datelike, NaiveDate, Utc};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(symbol: &str, account_id: Uuid, side: OrderSide) -> Order {
+        OrderBuilder::new(side).symbol(symbol).price(dec!(99.00)).account(account_id).build()
+    }
+
+    #[test]
+    fn test_tenor_buckets_include_their_upper_bound() {
+        let as_of = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(TenorBucket::classify(date(2025, 3, 1), as_of), TenorBucket::UpToOneYear);
+        assert_eq!(TenorBucket::classify(date(2025, 3, 2), as_of), TenorBucket::OneToFiveYears);
+        assert_eq!(TenorBucket::classify(date(2034, 3, 1), as_of), TenorBucket::FiveToTenYears);
+        assert_eq!(TenorBucket::classify(date(2054, 1, 1), as_of), TenorBucket::OverTenYears);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_limit_counts_positions_and_open_orders() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let today = Utc::now().date_naive();
+        let maturity = |years: i32| today.with_year(today.year() + years).unwrap_or(today);
+        let master = format!(
+            "isin,symbol,issuer,maturity_date,coupon_rate,bond_type\n\
+             IN0020230085,GS3Y,Government of India,{},7.18,GovernmentSecurity\n\
+             US0378331005,GS30Y,Government of India,{},7.30,GovernmentSecurity\n",
+            maturity(3),
+            maturity(30)
+        );
+        assert_eq!(engine.import_instruments(&master, false).unwrap().created, 2);
+
+        let account = Uuid::new_v4();
+        let mut limits = engine.risk_manager().risk_limits(account).await.unwrap();
+        limits.max_tenor_bucket_position.insert(TenorBucket::OneToFiveYears, dec!(150000));
+        engine.risk_manager().set_risk_limits(limits).await.unwrap();
+
+        // The filled bid leaves the account long 100000 in the 1-5y bucket
+        engine.submit_order(order("GS3Y", Uuid::new_v4(), OrderSide::Sell)).await.unwrap();
+        engine.submit_order(order("GS3Y", account, OrderSide::Buy)).await.unwrap();
+        let rejected = engine.submit_order(order("GS3Y", account, OrderSide::Buy)).await;
+        assert!(matches!(
+            rejected,
+            Err(TradingError::RiskLimitExceeded {
+                limit: RiskLimitKind::TenorBucket,
+                ..
+            })
+        ));
+        assert!(engine.submit_order(order("GS30Y", account, OrderSide::Buy)).await.is_ok());
+
+        let exposure = engine.tenor_exposure(account).await.unwrap();
+        let one_to_five = &exposure[1];
+        assert_eq!((one_to_five.net_position, one_to_five.limit), (dec!(100000), Some(dec!(150000))));
+        assert_eq!(exposure[3].open_buy_quantity, dec!(100000));
+    }
+}
@@ -0,0 +1,177 @@
+//! Simple aggregations over trade history, so dashboards can ask for volume
+//! or VWAP by symbol, hour or account without a separate warehouse.
+
+use crate::{
+    engine::{trade_store::TradeQuery, TradingEngine},
+    types::*,
+};
+use chrono::{DurationRound, SecondsFormat};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeGrouping {
+    #[default]
+    Symbol,
+    /// The hour the trade executed in, UTC.
+    Hour,
+    /// Each trade counts once for the buyer and once for the seller.
+    Account,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeMetric {
+    #[default]
+    Volume,
+    /// Volume-weighted clean price.
+    Vwap,
+    Count,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeAggregate {
+    /// Symbol, start of the hour or account id depending on the grouping.
+    pub group: String,
+    pub value: Decimal,
+}
+
+#[derive(Default)]
+struct Totals {
+    volume: Decimal,
+    weighted_price: Decimal,
+    count: u64,
+}
+
+impl Totals {
+    fn add(&mut self, trade: &Trade) {
+        self.volume += trade.quantity;
+        self.weighted_price += trade.quantity * trade.price;
+        self.count += 1;
+    }
+
+    fn value(&self, metric: TradeMetric) -> Decimal {
+        match metric {
+            TradeMetric::Volume => self.volume,
+            TradeMetric::Vwap if self.volume.is_zero() => Decimal::ZERO,
+            TradeMetric::Vwap => self.weighted_price / self.volume,
+            TradeMetric::Count => Decimal::from(self.count),
+        }
+    }
+}
+
+/// Aggregates `trades` into one row per group, in group order. With
+/// `account_id` set, account grouping keeps only that account's side.
+fn aggregate(
+    trades: &mut dyn Iterator<Item = &Trade>,
+    group_by: TradeGrouping,
+    metric: TradeMetric,
+    account_id: Option<Uuid>,
+) -> Vec<TradeAggregate> {
+    let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+    for trade in trades {
+        match group_by {
+            TradeGrouping::Symbol => groups.entry(trade.symbol.clone()).or_default().add(trade),
+            TradeGrouping::Hour => {
+                let hour = trade
+                    .timestamp
+                    .duration_trunc(chrono::Duration::hours(1))
+                    .unwrap_or(trade.timestamp);
+                groups
+                    .entry(hour.to_rfc3339_opts(SecondsFormat::Secs, true))
+                    .or_default()
+                    .add(trade)
+            }
+            TradeGrouping::Account => {
+                for side in [OrderSide::Buy, OrderSide::Sell] {
+                    let account = trade.account_for(&side);
+                    if account_id.is_none_or(|id| id == account) {
+                        groups.entry(account.to_string()).or_default().add(trade);
+                    }
+                }
+            }
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(group, totals)| TradeAggregate {
+            group,
+            value: totals.value(metric),
+        })
+        .collect()
+}
+
+impl TradingEngine {
+    /// Aggregates the trades matching `query`, reading the state store only
+    /// when the in-memory window does not reach back far enough.
+    pub async fn aggregate_trades(
+        &self,
+        query: &TradeQuery,
+        group_by: TradeGrouping,
+        metric: TradeMetric,
+    ) -> Result<Vec<TradeAggregate>> {
+        if let Some(aggregates) =
+            self.with_recent_trades(query, |trades| aggregate(trades, group_by, metric, query.account_id))?
+        {
+            return Ok(aggregates);
+        }
+        let trades = self.query_trades(query).await?;
+        Ok(aggregate(&mut trades.iter(), group_by, metric, query.account_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, quantity: Decimal, price: Decimal, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).quantity(quantity).price(price).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_aggregates_include_trades_spilled_to_the_store() {
+        let config = Config {
+            trade_memory_capacity: 1,
+            ..Config::default()
+        };
+        let engine = TradingEngine::new(Arc::new(config)).await.unwrap();
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        for (quantity, price) in [(dec!(100000), dec!(99.00)), (dec!(300000), dec!(100.00))] {
+            engine.submit_order(order(OrderSide::Sell, quantity, price, seller)).await.unwrap();
+            engine.submit_order(order(OrderSide::Buy, quantity, price, buyer)).await.unwrap();
+        }
+        let history = TradeQuery {
+            from: Some(Utc::now() - chrono::Duration::hours(1)),
+            ..TradeQuery::default()
+        };
+
+        let vwap = engine
+            .aggregate_trades(&history, TradeGrouping::Symbol, TradeMetric::Vwap)
+            .await
+            .unwrap();
+        assert_eq!(vwap[0].group, "GSEC10Y");
+        assert_eq!(vwap[0].value, dec!(99.75));
+
+        let by_account = engine
+            .aggregate_trades(
+                &TradeQuery {
+                    account_id: Some(buyer),
+                    ..history
+                },
+                TradeGrouping::Account,
+                TradeMetric::Count,
+            )
+            .await
+            .unwrap();
+        assert_eq!(by_account.len(), 1);
+        assert_eq!((by_account[0].group.as_str(), by_account[0].value), (buyer.to_string().as_str(), dec!(2)));
+    }
+}
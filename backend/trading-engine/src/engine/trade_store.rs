@@ -0,0 +1,154 @@
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Utc};
+use parking_lot::{RwLock, RwLockReadGuard};
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Filters for trade history. Unset bounds are open.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TradeQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub symbol: Option<String>,
+    pub account_id: Option<Uuid>,
+    /// Keeps the most recent matches.
+    pub limit: Option<usize>,
+}
+
+impl TradeQuery {
+    pub fn matches(&self, trade: &Trade) -> bool {
+        self.from.is_none_or(|from| trade.timestamp >= from)
+            && self.to.is_none_or(|to| trade.timestamp <= to)
+            && self.symbol.as_ref().is_none_or(|symbol| trade.symbol == *symbol)
+            && self
+                .account_id
+                .is_none_or(|id| trade.buyer_account_id == id || trade.seller_account_id == id)
+    }
+}
+
+/// Recent trades in execution order, capped at `capacity`. Trades pushed
+/// out are handed back so they can be spilled to the state store.
+pub struct TradeStore {
+    capacity: usize,
+    recent: RwLock<VecDeque<Trade>>,
+}
+
+impl TradeStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            recent: RwLock::new(VecDeque::with_capacity(capacity.min(100000))),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, VecDeque<Trade>> {
+        self.recent.read()
+    }
+
+    /// Appends trades and returns the ones evicted to make room.
+    pub fn push(&self, trades: &[Trade]) -> Vec<Trade> {
+        let mut recent = self.recent.write();
+        let mut evicted = Vec::new();
+        for trade in trades {
+            recent.push_back(trade.clone());
+            while recent.len() > self.capacity {
+                evicted.extend(recent.pop_front());
+            }
+        }
+        evicted
+    }
+
+    /// Matching trades held in memory, plus whether memory reaches back to
+    /// `from`.
+    pub fn range(&self, query: &TradeQuery) -> (Vec<Trade>, bool) {
+        let recent = self.recent.read();
+        let covered = covers(&recent, query);
+        (recent.iter().filter(|trade| query.matches(trade)).cloned().collect(), covered)
+    }
+}
+
+/// Whether `recent` holds every trade `query` asks for. Without a `from`
+/// the window is all that is asked for.
+fn covers(recent: &VecDeque<Trade>, query: &TradeQuery) -> bool {
+    query
+        .from
+        .is_none_or(|from| recent.front().is_some_and(|oldest| oldest.timestamp <= from))
+}
+
+fn check_query(query: &TradeQuery) -> crate::types::Result<()> {
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(TradingError::InvalidRequest("`from` must not be after `to`".to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl TradingEngine {
+    /// Trades in the in-memory window.
+    pub fn get_trades(&self) -> Vec<Trade> {
+        self.trades.read().iter().cloned().collect()
+    }
+
+    /// Trade history, from memory when the window reaches back to
+    /// `query.from` and from the state store for anything older.
+    pub async fn query_trades(&self, query: &TradeQuery) -> crate::types::Result<Vec<Trade>> {
+        check_query(query)?;
+
+        let (recent, covered) = self.trades.range(query);
+        let mut trades = if covered {
+            recent
+        } else {
+            let held: HashSet<Uuid> = recent.iter().map(|trade| trade.id).collect();
+            let mut stored: Vec<Trade> = self
+                .state_store
+                .load_trades(&TradeQuery { limit: None, ..query.clone() })
+                .await?
+                .into_iter()
+                .filter(|trade| !held.contains(&trade.id))
+                .collect();
+            stored.extend(recent);
+            stored.sort_by_key(|trade| trade.timestamp);
+            stored
+        };
+
+        if let Some(limit) = query.limit {
+            let excess = trades.len().saturating_sub(limit);
+            trades.drain(..excess);
+        }
+        Ok(trades)
+    }
+
+    /// Hands the trades matching `query` to `f` without copying them, or
+    /// returns `None` if some are only in the state store. New trades wait
+    /// until `f` returns.
+    pub fn with_recent_trades<R>(
+        &self,
+        query: &TradeQuery,
+        f: impl FnOnce(&mut dyn Iterator<Item = &Trade>) -> R,
+    ) -> crate::types::Result<Option<R>> {
+        check_query(query)?;
+        let recent = self.trades.read();
+        if !covers(&recent, query) {
+            return Ok(None);
+        }
+        let matching = recent.iter().filter(|trade| query.matches(trade)).count();
+        let excess = query.limit.map_or(0, |limit| matching.saturating_sub(limit));
+        Ok(Some(f(&mut recent.iter().filter(|trade| query.matches(trade)).skip(excess))))
+    }
+
+    /// Adds executed trades to the in-memory window, moving the oldest to
+    /// the state store once the window is full.
+    pub(crate) async fn retain_trades(&self, trades: &[Trade]) {
+        let evicted = self.trades.push(trades);
+        if evicted.is_empty() {
+            return;
+        }
+        if let Err(e) = self.state_store.save_trades(&evicted).await {
+            warn!("Failed to spill {} trades to the state store: {}", evicted.len(), e);
+        }
+    }
+}
+
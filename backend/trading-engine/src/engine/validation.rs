@@ -0,0 +1,331 @@
+//! Order validation as a pipeline of validators, registered when the
+//! engine is built. Checks on the order alone run first, so a malformed
+//! order is turned away before anything looks up engine state; venue rules
+//! are added as further validators. Rejections are counted per validator.
+
+use crate::{
+    engine::{feature_flags::FeatureFlag, market_collar::order_collar_pct, TradingEngine},
+    types::*,
+};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationPhase {
+    /// Looks only at the order's own fields.
+    Static,
+    /// Consults instruments, permissions, limits or other engine state.
+    Stateful,
+}
+
+#[async_trait]
+pub trait OrderValidator: Send + Sync {
+    /// Names the validator in rejection metrics.
+    fn name(&self) -> &'static str;
+
+    fn phase(&self) -> ValidationPhase;
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()>;
+}
+
+fn invalid(field: OrderField, message: impl Into<String>) -> Result<()> {
+    Err(TradingError::InvalidOrderField {
+        field,
+        message: message.into(),
+    })
+}
+
+/// Positive quantity and price, a symbol, and a price only where the order
+/// type takes one.
+pub struct OrderFieldsValidator;
+
+#[async_trait]
+impl OrderValidator for OrderFieldsValidator {
+    fn name(&self) -> &'static str {
+        "order_fields"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Static
+    }
+
+    async fn validate(&self, _engine: &TradingEngine, order: &Order) -> Result<()> {
+        if order.quantity <= Decimal::ZERO {
+            return invalid(OrderField::Quantity, "Quantity must be positive");
+        }
+        if order.price.is_some_and(|price| price <= Decimal::ZERO) {
+            return invalid(OrderField::Price, "Price must be positive");
+        }
+        if order.symbol.is_empty() {
+            return invalid(OrderField::Symbol, "Symbol cannot be empty");
+        }
+        match order_collar_pct(order) {
+            Some(_) if order.order_type != OrderType::Market => {
+                return invalid(OrderField::OrderType, "Only market orders take a collar");
+            }
+            Some(Ok(pct)) if pct > Decimal::ZERO && pct < Decimal::ONE_HUNDRED => {}
+            Some(_) => return invalid(OrderField::Price, "Collar must be above 0 and below 100 percent"),
+            None => {}
+        }
+        match &order.order_type {
+            OrderType::Limit if order.price.is_none() => invalid(OrderField::Price, "Limit orders must have a price"),
+            OrderType::Market if order.price.is_some() => {
+                invalid(OrderField::Price, "Market orders cannot have a price")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Quantity and price fit the instrument's decimal places.
+pub struct PriceScaleValidator;
+
+#[async_trait]
+impl OrderValidator for PriceScaleValidator {
+    fn name(&self) -> &'static str {
+        "price_scale"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        let scale = engine.matching_engine.tick_scale(&order.symbol);
+        if scale.quantity_ticks(order.quantity).is_none() {
+            return invalid(
+                OrderField::Quantity,
+                format!("Quantity must fit {} decimal places", scale.quantity_decimals),
+            );
+        }
+        if order.price.is_some_and(|price| scale.price_ticks(price).is_none()) {
+            return invalid(
+                OrderField::Price,
+                format!("Price must fit {} decimal places", scale.price_decimals),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The instrument is listed, if listing is required, and not halted.
+pub struct InstrumentValidator;
+
+#[async_trait]
+impl OrderValidator for InstrumentValidator {
+    fn name(&self) -> &'static str {
+        "instrument"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        engine.check_instrument_tradable(&order.symbol)
+    }
+}
+
+pub struct PermissionsValidator;
+
+#[async_trait]
+impl OrderValidator for PermissionsValidator {
+    fn name(&self) -> &'static str {
+        "permissions"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        engine.check_permissions(order).await
+    }
+}
+
+/// Room on the symbol's book, where book capacity limits are on.
+pub struct BookCapacityValidator;
+
+#[async_trait]
+impl OrderValidator for BookCapacityValidator {
+    fn name(&self) -> &'static str {
+        "book_capacity"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        if engine.feature_enabled(FeatureFlag::BookCapacityLimits, order) {
+            engine.check_book_capacity(order)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct StrategyValidator;
+
+#[async_trait]
+impl OrderValidator for StrategyValidator {
+    fn name(&self) -> &'static str {
+        "strategy"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        engine.strategies.validate(order)
+    }
+}
+
+/// A good-till-time expiry still ahead on the engine clock.
+pub struct ExpiryValidator;
+
+#[async_trait]
+impl OrderValidator for ExpiryValidator {
+    fn name(&self) -> &'static str {
+        "expiry"
+    }
+
+    fn phase(&self) -> ValidationPhase {
+        ValidationPhase::Stateful
+    }
+
+    async fn validate(&self, engine: &TradingEngine, order: &Order) -> Result<()> {
+        match order.time_in_force {
+            TimeInForce::GoodTillTime(expiry) if expiry <= engine.time_provider.now() => {
+                invalid(OrderField::TimeInForce, "Expiry time has already passed")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidatorInfo {
+    pub name: &'static str,
+    pub phase: ValidationPhase,
+}
+
+/// The engine's validators in the order they run: by phase, then in the
+/// order registered.
+pub struct ValidationPipeline {
+    validators: Vec<Box<dyn OrderValidator>>,
+}
+
+impl ValidationPipeline {
+    /// The built-in validators followed by `extra`.
+    pub fn new(extra: Vec<Box<dyn OrderValidator>>) -> Self {
+        let mut validators: Vec<Box<dyn OrderValidator>> = vec![
+            Box::new(OrderFieldsValidator),
+            Box::new(PriceScaleValidator),
+            Box::new(InstrumentValidator),
+            Box::new(PermissionsValidator),
+            Box::new(BookCapacityValidator),
+            Box::new(StrategyValidator),
+            Box::new(ExpiryValidator),
+        ];
+        validators.extend(extra);
+        validators.sort_by_key(|validator| validator.phase());
+        Self { validators }
+    }
+
+    pub fn validators(&self) -> Vec<ValidatorInfo> {
+        self.validators
+            .iter()
+            .map(|validator| ValidatorInfo {
+                name: validator.name(),
+                phase: validator.phase(),
+            })
+            .collect()
+    }
+}
+
+impl TradingEngine {
+    pub fn validation_pipeline(&self) -> &ValidationPipeline {
+        &self.validation
+    }
+
+    /// Runs the order through every validator, stopping at the first that
+    /// rejects it.
+    pub(crate) async fn validate_order(&self, order: &Order) -> Result<()> {
+        match self.first_validation_failure(order).await {
+            Some((name, e)) => {
+                self.metrics.increment_validator_rejects(name);
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The first validator to reject the order and its error, without
+    /// counting the rejection.
+    pub(crate) async fn first_validation_failure(&self, order: &Order) -> Option<(&'static str, TradingError)> {
+        for validator in &self.validation.validators {
+            if let Err(e) = validator.validate(self, order).await {
+                return Some((validator.name(), e));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support::OrderBuilder, utils::time::TimeProvider};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    /// A venue rule: orders in whole crores of face value only.
+    struct RoundLotValidator;
+
+    #[async_trait]
+    impl OrderValidator for RoundLotValidator {
+        fn name(&self) -> &'static str {
+            "round_lot"
+        }
+
+        fn phase(&self) -> ValidationPhase {
+            ValidationPhase::Static
+        }
+
+        async fn validate(&self, _engine: &TradingEngine, order: &Order) -> Result<()> {
+            if (order.quantity % dec!(10000000)).is_zero() {
+                Ok(())
+            } else {
+                invalid(OrderField::Quantity, "Quantity must be a whole number of crores")
+            }
+        }
+    }
+
+    fn order(quantity: Decimal, price: Option<Decimal>) -> Order {
+        OrderBuilder::new(OrderSide::Buy).quantity(quantity).price(price).build()
+    }
+
+    #[tokio::test]
+    async fn test_registered_validator_runs_with_static_checks_and_is_counted() {
+        let engine = TradingEngine::with_validators(
+            Arc::new(Config::default()),
+            Arc::new(TimeProvider::new()),
+            vec![Box::new(RoundLotValidator)],
+        )
+        .await
+        .unwrap();
+        let names: Vec<&str> = engine.validation_pipeline().validators().iter().map(|v| v.name).collect();
+        assert_eq!(&names[..3], &["order_fields", "round_lot", "price_scale"]);
+
+        assert!(engine.submit_order(order(dec!(5000000), Some(dec!(99.50)))).await.is_err());
+        assert!(engine.submit_order(order(dec!(10000000), None)).await.is_err());
+        engine.submit_order(order(dec!(10000000), Some(dec!(99.50)))).await.unwrap();
+
+        let rejects = engine.get_metrics().snapshot().validator_rejects;
+        assert_eq!(rejects.get("round_lot"), Some(&1));
+        assert_eq!(rejects.get("order_fields"), Some(&1));
+    }
+}
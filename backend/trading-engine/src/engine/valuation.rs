@@ -0,0 +1,92 @@
+use crate::{
+    engine::{matrix_pricing::PriceConfidence, reference_price::ReferenceSource, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PositionValuation {
+    pub symbol: String,
+    pub quantity: Decimal,
+    /// Clean mark: the reference price, else a matrix price, else the
+    /// average price.
+    pub mark_price: Decimal,
+    pub mark_source: Option<ReferenceSource>,
+    /// Set when the mark is a matrix price.
+    pub matrix_confidence: Option<PriceConfidence>,
+    pub market_value: Decimal,
+    pub accrued_interest: Decimal,
+    pub unrealized_pnl: Decimal,
+}
+
+/// Indicative value of an account's portfolio at current marks.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioValuation {
+    pub account_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub positions: Vec<PositionValuation>,
+    pub market_value: Decimal,
+    pub accrued_interest: Decimal,
+    /// Market value plus accrued interest.
+    pub total_value: Decimal,
+    pub unrealized_pnl: Decimal,
+    pub realized_pnl: Decimal,
+}
+
+impl PortfolioValuation {
+    /// Same figures, ignoring when they were computed.
+    pub fn same_values(&self, other: &PortfolioValuation) -> bool {
+        self.positions == other.positions && self.realized_pnl == other.realized_pnl
+    }
+}
+
+impl TradingEngine {
+    /// Marks each position at its reference price, or its matrix price for
+    /// bonds without one, indexed for inflation-linked bonds, with accrued
+    /// interest to settlement.
+    pub async fn value_portfolio(&self, account_id: Uuid) -> PortfolioValuation {
+        let mut positions = self.get_positions(Some(account_id)).await;
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        let mut valuation = PortfolioValuation {
+            account_id,
+            timestamp: self.time_provider.now(),
+            positions: Vec::with_capacity(positions.len()),
+            market_value: Decimal::ZERO,
+            accrued_interest: Decimal::ZERO,
+            total_value: Decimal::ZERO,
+            unrealized_pnl: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+        };
+        for position in positions {
+            let (mark_price, mark_source, matrix_confidence) = match self.reference_price(&position.symbol) {
+                Ok(reference) => (reference.price, Some(reference.source), None),
+                Err(_) => match self.matrix_price(&position.symbol) {
+                    Ok(matrix) => (matrix.price, None, Some(matrix.confidence)),
+                    Err(_) => (position.average_price, None, None),
+                },
+            };
+            let index_ratio = self.settlement_index_ratio(&position.symbol).unwrap_or(Decimal::ONE);
+            let entry = PositionValuation {
+                market_value: notional_value(position.quantity, mark_price) * index_ratio,
+                unrealized_pnl: notional_value(position.quantity, mark_price - position.average_price),
+                accrued_interest: position.accrued_interest,
+                symbol: position.symbol,
+                quantity: position.quantity,
+                mark_price,
+                mark_source,
+                matrix_confidence,
+            };
+            valuation.market_value += entry.market_value;
+            valuation.accrued_interest += entry.accrued_interest;
+            valuation.unrealized_pnl += entry.unrealized_pnl;
+            valuation.realized_pnl += position.realized_pnl;
+            valuation.positions.push(entry);
+        }
+        valuation.total_value = valuation.market_value + valuation.accrued_interest;
+        valuation
+    }
+}
@@ -0,0 +1,255 @@
+//! Startup warm-up. Before the engine reports ready, resting orders from
+//! earlier sessions are put back on the books: the book snapshot restores
+//! them with their time priority, and open orders the store holds that the
+//! snapshot missed are re-entered in the order they arrived. The rebuilt
+//! books are then checked before `/readyz` lets traffic in.
+
+use crate::{engine::TradingEngine, types::*};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupPhase {
+    NotStarted,
+    RestoringSnapshot,
+    LoadingOrders,
+    VerifyingBooks,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupStatus {
+    pub phase: WarmupPhase,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Resting orders restored from the book snapshot.
+    pub orders_restored: usize,
+    /// Open orders from the store re-entered because the snapshot lacked them.
+    pub orders_reentered: usize,
+    /// Stored orders left off the books because they lapsed while down.
+    pub orders_lapsed: usize,
+    /// Broken book invariants, which keep the engine from becoming ready.
+    pub violations: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl WarmupStatus {
+    pub fn is_ready(&self) -> bool {
+        self.phase == WarmupPhase::Ready
+    }
+}
+
+pub struct Warmup {
+    status: RwLock<WarmupStatus>,
+}
+
+impl Warmup {
+    pub fn new() -> Self {
+        Self {
+            status: RwLock::new(WarmupStatus {
+                phase: WarmupPhase::NotStarted,
+                started_at: None,
+                finished_at: None,
+                orders_restored: 0,
+                orders_reentered: 0,
+                orders_lapsed: 0,
+                violations: Vec::new(),
+                error: None,
+            }),
+        }
+    }
+
+    pub fn status(&self) -> WarmupStatus {
+        self.status.read().clone()
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut WarmupStatus)) {
+        apply(&mut self.status.write());
+    }
+
+    fn enter(&self, phase: WarmupPhase) {
+        info!("Warm-up: {:?}", phase);
+        self.update(|status| status.phase = phase);
+    }
+}
+
+impl Default for Warmup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TradingEngine {
+    pub fn warmup_status(&self) -> WarmupStatus {
+        self.warmup.status()
+    }
+
+    /// Runs the warm-up within the configured timeout. The engine is ready
+    /// only if every step finished in time and the books verified clean.
+    pub async fn warm_up(&self) -> WarmupStatus {
+        let now = self.time_provider.now();
+        self.warmup.update(|status| status.started_at = Some(now));
+        let timeout = Duration::from_millis(self.config.warmup_timeout_ms);
+        let outcome = match tokio::time::timeout(timeout, self.rebuild_books()).await {
+            Ok(Ok(violations)) if violations.is_empty() => Ok(()),
+            Ok(Ok(violations)) => {
+                for violation in &violations {
+                    error!("Warm-up found a broken book: {}", violation);
+                }
+                self.warmup.update(|status| status.violations = violations);
+                Err("Book invariants do not hold".to_string())
+            }
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("Warm-up did not finish within {}ms", self.config.warmup_timeout_ms)),
+        };
+
+        let finished_at = self.time_provider.now();
+        self.warmup.update(|status| {
+            status.finished_at = Some(finished_at);
+            match outcome {
+                Ok(()) => status.phase = WarmupPhase::Ready,
+                Err(e) => {
+                    status.phase = WarmupPhase::Failed;
+                    status.error = Some(e);
+                }
+            }
+        });
+        let status = self.warmup.status();
+        match &status.error {
+            None => info!(
+                "Warm-up complete: {} orders restored, {} re-entered, {} lapsed",
+                status.orders_restored, status.orders_reentered, status.orders_lapsed
+            ),
+            Some(e) => error!("Warm-up failed, engine stays unready: {}", e),
+        }
+        status
+    }
+
+    /// Restores the snapshot, re-enters what it missed and returns the
+    /// invariants the rebuilt books break.
+    async fn rebuild_books(&self) -> crate::types::Result<Vec<String>> {
+        self.warmup.enter(WarmupPhase::RestoringSnapshot);
+        let restored = self.restore_book().await?;
+        self.warmup.update(|status| status.orders_restored = restored);
+
+        self.warmup.enter(WarmupPhase::LoadingOrders);
+        let now = self.time_provider.now();
+        let mut stored: Vec<Order> = self
+            .state_store
+            .load_orders()
+            .await?
+            .into_iter()
+            .filter(|order| order.is_open() && rests(order) && !self.orders.contains_key(&order.id))
+            .collect();
+        stored.sort_by_key(|order| order.timestamp);
+        info!("Warm-up: {} stored open orders missing from the books", stored.len());
+
+        let total = stored.len();
+        for (done, order) in stored.into_iter().enumerate() {
+            if order.expires_at().is_some_and(|expiry| expiry <= now) {
+                self.warmup.update(|status| status.orders_lapsed += 1);
+                continue;
+            }
+            self.orders.insert(order.id, order.clone());
+            if let Some(expiry) = order.expires_at() {
+                self.expiry_wheel.schedule(order.id, expiry);
+            }
+            if !self.queue_for_open(&order) {
+                self.route_order(order, false).await?;
+            }
+            self.warmup.update(|status| status.orders_reentered += 1);
+            if (done + 1) % 1000 == 0 {
+                info!("Warm-up: re-entered {} of {} orders", done + 1, total);
+            }
+        }
+
+        self.warmup.enter(WarmupPhase::VerifyingBooks);
+        Ok(self.book_violations())
+    }
+
+    /// Every resting order must be a live, open order with quantity left,
+    /// and no book may be crossed.
+    fn book_violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        for entry in self.book_snapshot().entries {
+            match self.orders.get(&entry.order.id) {
+                None => violations.push(format!("Resting order {} is unknown", entry.order.id)),
+                Some(order) if !order.is_open() || order.remaining_quantity <= Decimal::ZERO => {
+                    violations.push(format!("Resting order {} is not open ({:?})", order.id, order.status))
+                }
+                Some(_) => {}
+            }
+        }
+        for symbol in self.matching_engine.symbols() {
+            let bid = self.matching_engine.get_best_bid(&symbol);
+            let ask = self.matching_engine.get_best_ask(&symbol);
+            if let (Some(bid), Some(ask)) = (bid, ask) {
+                if bid >= ask {
+                    violations.push(format!("{} is crossed: bid {} at or above ask {}", symbol, bid, ask));
+                }
+            }
+        }
+        if !violations.is_empty() {
+            warn!("Warm-up found {} book violations", violations.len());
+        }
+        violations
+    }
+}
+
+/// Whether the order can rest on a book, rather than only trade on entry
+/// or wait for a trigger.
+fn rests(order: &Order) -> bool {
+    order.price.is_some()
+        && !matches!(
+            order.order_type,
+            OrderType::Market
+                | OrderType::Stop
+                | OrderType::StopLimit
+                | OrderType::FillOrKill
+                | OrderType::ImmediateOrCancel
+        )
+        && !matches!(order.time_in_force, TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::OrderBuilder;
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, price: Decimal, time_in_force: TimeInForce) -> Order {
+        OrderBuilder::new(side)
+            .price(price)
+            .time_in_force(time_in_force)
+            .timestamp(Utc::now() - chrono::Duration::days(2))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_puts_stored_gtc_orders_back_before_ready() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        assert!(!engine.warmup_status().is_ready());
+
+        let bid = order(OrderSide::Buy, dec!(99.25), TimeInForce::GoodTillCancel);
+        let ask = order(OrderSide::Sell, dec!(99.75), TimeInForce::GoodTillCancel);
+        let day_order = order(OrderSide::Buy, dec!(99.00), TimeInForce::GoodForDay);
+        for order in [&bid, &ask, &day_order] {
+            engine.state_store().save_order(order).await.unwrap();
+        }
+
+        let status = engine.warm_up().await;
+        assert!(status.is_ready(), "{:?}", status);
+        assert_eq!(status.orders_reentered, 2);
+        assert_eq!(status.orders_lapsed, 1);
+        assert_eq!(engine.matching_engine.get_best_bid("GSEC10Y"), Some(dec!(99.25)));
+        assert_eq!(engine.matching_engine.get_best_ask("GSEC10Y"), Some(dec!(99.75)));
+    }
+}
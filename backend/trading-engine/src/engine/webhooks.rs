@@ -0,0 +1,272 @@
+use crate::{
+    engine::{event_journal::SequencedEvent, EngineEvent, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "X-VV-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-VV-Timestamp";
+pub const WEBHOOK_ID_HEADER: &str = "X-VV-Webhook-Id";
+
+const MIN_SECRET_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+    TradeExecuted,
+    OrderRejected,
+}
+
+impl WebhookEventType {
+    pub fn of(event: &EngineEvent) -> Option<Self> {
+        match event {
+            EngineEvent::TradeExecuted(_) => Some(WebhookEventType::TradeExecuted),
+            EngineEvent::OrderRejected { .. } => Some(WebhookEventType::OrderRejected),
+            _ => None,
+        }
+    }
+}
+
+fn all_event_types() -> Vec<WebhookEventType> {
+    vec![WebhookEventType::TradeExecuted, WebhookEventType::OrderRejected]
+}
+
+/// An outbound endpoint. Without an account it receives the events of
+/// every account.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub account_id: Option<Uuid>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<WebhookEventType>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookRequest {
+    pub account_id: Option<Uuid>,
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature on every request.
+    pub secret: String,
+    #[serde(default = "all_event_types")]
+    pub events: Vec<WebhookEventType>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebhookStats {
+    pub delivered: u64,
+    pub retries: u64,
+    pub failed: u64,
+    pub consecutive_failures: u64,
+    pub last_error: Option<String>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookStatus {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub stats: WebhookStats,
+}
+
+/// Body of a webhook request.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: WebhookEventType,
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub data: EngineEvent,
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>` under the endpoint's secret.
+/// Receivers recompute it from the timestamp header and the raw body, and
+/// can reject stale timestamps to stop replays.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Default)]
+pub struct WebhookRegistry {
+    endpoints: DashMap<Uuid, WebhookEndpoint>,
+    stats: DashMap<Uuid, WebhookStats>,
+}
+
+impl WebhookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn list(&self, account_id: Option<Uuid>) -> Vec<WebhookStatus> {
+        let mut statuses: Vec<WebhookStatus> = self
+            .endpoints
+            .iter()
+            .filter(|entry| account_id.is_none_or(|id| entry.account_id == Some(id)))
+            .map(|entry| WebhookStatus {
+                endpoint: entry.value().clone(),
+                stats: self.stats.get(entry.key()).map(|stats| stats.clone()).unwrap_or_default(),
+            })
+            .collect();
+        statuses.sort_by_key(|status| status.endpoint.created_at);
+        statuses
+    }
+
+    /// Endpoints subscribed to `event`, with its type.
+    pub fn matching(&self, event: &SequencedEvent) -> Vec<(WebhookEndpoint, WebhookEventType)> {
+        let Some(event_type) = WebhookEventType::of(&event.event) else {
+            return Vec::new();
+        };
+        self.endpoints
+            .iter()
+            .filter(|entry| {
+                let endpoint = entry.value();
+                endpoint.events.contains(&event_type)
+                    && endpoint.account_id.is_none_or(|account_id| event.concerns(account_id))
+            })
+            .map(|entry| (entry.value().clone(), event_type))
+            .collect()
+    }
+
+    pub fn record_attempt(&self, webhook_id: Uuid, error: Option<String>, last: bool, now: DateTime<Utc>) {
+        let mut stats = self.stats.entry(webhook_id).or_default();
+        stats.last_attempt_at = Some(now);
+        match error {
+            None => {
+                stats.delivered += 1;
+                stats.consecutive_failures = 0;
+            }
+            Some(error) => {
+                if last {
+                    stats.failed += 1;
+                    stats.consecutive_failures += 1;
+                } else {
+                    stats.retries += 1;
+                }
+                stats.last_error = Some(error);
+            }
+        }
+    }
+}
+
+impl TradingEngine {
+    pub fn webhooks(&self) -> &WebhookRegistry {
+        &self.webhooks
+    }
+
+    pub fn register_webhook(&self, request: WebhookRequest) -> crate::types::Result<WebhookEndpoint> {
+        if !(request.url.starts_with("https://") || request.url.starts_with("http://")) {
+            return Err(TradingError::InvalidRequest("Webhook URL must be http or https".to_string()));
+        }
+        if request.secret.len() < MIN_SECRET_LENGTH {
+            return Err(TradingError::InvalidRequest(format!(
+                "Webhook secret must be at least {} characters",
+                MIN_SECRET_LENGTH
+            )));
+        }
+        if request.events.is_empty() {
+            return Err(TradingError::InvalidRequest("Webhook must subscribe to an event".to_string()));
+        }
+
+        let endpoint = WebhookEndpoint {
+            id: Uuid::new_v4(),
+            account_id: request.account_id,
+            url: request.url,
+            secret: request.secret,
+            events: request.events,
+            created_at: self.time_provider.now(),
+        };
+        self.webhooks.endpoints.insert(endpoint.id, endpoint.clone());
+        Ok(endpoint)
+    }
+
+    pub fn remove_webhook(&self, webhook_id: Uuid) -> bool {
+        self.webhooks.stats.remove(&webhook_id);
+        self.webhooks.endpoints.remove(&webhook_id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_account_and_global_webhooks_match_and_sign() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let (account, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let request = |account_id: Option<Uuid>, events: Vec<WebhookEventType>| WebhookRequest {
+            account_id,
+            url: "https://oms.example.com/hooks".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events,
+        };
+        let global = engine.register_webhook(request(None, all_event_types())).unwrap();
+        let rejections = engine
+            .register_webhook(request(Some(account), vec![WebhookEventType::OrderRejected]))
+            .unwrap();
+        assert!(engine
+            .register_webhook(WebhookRequest {
+                secret: "short".to_string(),
+                ..request(None, all_event_types())
+            })
+            .is_err());
+
+        let rejected = |account_id: Uuid| SequencedEvent {
+            seq: 7,
+            timestamp: Utc::now(),
+            clock: Default::default(),
+            account_ids: vec![account_id],
+            event: EngineEvent::OrderRejected {
+                order_id: Uuid::new_v4(),
+                client_order_id: "C-1".to_string(),
+                account_id,
+                symbol: "GSEC10Y".to_string(),
+                reason: "Risk limit exceeded".to_string(),
+                reject_reason: RejectReason::RiskLimit {
+                    limit: RiskLimitKind::OrderValue,
+                },
+            },
+        };
+        let mut ids: Vec<Uuid> = engine
+            .webhooks()
+            .matching(&rejected(account))
+            .into_iter()
+            .map(|(endpoint, _)| endpoint.id)
+            .collect();
+        ids.sort();
+        let mut expected = vec![global.id, rejections.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert_eq!(engine.webhooks().matching(&rejected(other)).len(), 1);
+
+        let signature = sign(&global.secret, 1_700_000_000, r#"{"seq":7}"#);
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign(&global.secret, 1_700_000_000, r#"{"seq":7}"#));
+        assert_ne!(signature, sign(&global.secret, 1_700_000_001, r#"{"seq":7}"#));
+        assert_ne!(signature, sign("fedcba9876543210", 1_700_000_000, r#"{"seq":7}"#));
+
+        engine.webhooks().record_attempt(global.id, Some("503".to_string()), false, Utc::now());
+        engine.webhooks().record_attempt(global.id, Some("503".to_string()), true, Utc::now());
+        let status = engine
+            .webhooks()
+            .list(None)
+            .into_iter()
+            .find(|status| status.endpoint.id == global.id)
+            .unwrap();
+        let stats = status.stats;
+        assert_eq!((stats.retries, stats.failed, stats.consecutive_failures), (1, 1, 1));
+    }
+}
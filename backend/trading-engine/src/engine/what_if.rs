@@ -0,0 +1,346 @@
+//! Read-only scenario evaluation: an account's positions as they would be
+//! after hypothetical fills and a yield-curve shock, with the resulting P&L,
+//! VaR and limit utilization. Nothing is executed or recorded.
+
+use crate::{
+    engine::{accrued, analytics, TradingEngine},
+    types::*,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HypotheticalOrder {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    /// Clean price the order is assumed to fill at in full. Defaults to the
+    /// reference price.
+    pub price: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenorShift {
+    /// Applies to bonds with at most this many years to maturity that no
+    /// shorter bucket covers.
+    pub up_to_years: Decimal,
+    pub bps: Decimal,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct YieldShock {
+    #[serde(default)]
+    pub parallel_bps: Decimal,
+    #[serde(default)]
+    pub tenors: Vec<TenorShift>,
+}
+
+impl YieldShock {
+    fn shift_bps(&self, years_to_maturity: Decimal) -> Decimal {
+        let mut tenors: Vec<&TenorShift> = self.tenors.iter().collect();
+        tenors.sort_by_key(|tenor| tenor.up_to_years);
+        let tenor = tenors
+            .into_iter()
+            .find(|tenor| years_to_maturity <= tenor.up_to_years)
+            .map(|tenor| tenor.bps)
+            .unwrap_or(Decimal::ZERO);
+        self.parallel_bps + tenor
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhatIfRequest {
+    pub account_id: Uuid,
+    #[serde(default)]
+    pub orders: Vec<HypotheticalOrder>,
+    #[serde(default)]
+    pub shock: YieldShock,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderCheck {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    /// The pre-trade risk rejection the order would meet, if any.
+    pub rejection: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectedPosition {
+    pub symbol: String,
+    pub quantity_before: Decimal,
+    pub quantity_after: Decimal,
+    pub average_price_after: Decimal,
+    pub mark_price: Decimal,
+    pub yield_shift_bps: Decimal,
+    /// The mark repriced at the shifted yield. Equal to the mark for
+    /// symbols without a yield.
+    pub shocked_price: Decimal,
+    pub modified_duration: Option<Decimal>,
+    pub market_value_before: Decimal,
+    pub market_value_after: Decimal,
+    pub pnl_before: Decimal,
+    pub pnl_after: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitUtilization {
+    pub limit: String,
+    pub limit_value: Decimal,
+    pub before: Decimal,
+    pub after: Decimal,
+    pub utilization_before: Option<Decimal>,
+    pub utilization_after: Option<Decimal>,
+}
+
+impl LimitUtilization {
+    fn new(limit: &str, limit_value: Decimal, before: Decimal, after: Decimal) -> Self {
+        let utilization = |value: Decimal| (limit_value > Decimal::ZERO).then(|| (value / limit_value).round_dp(6));
+        Self {
+            limit: limit.to_string(),
+            limit_value,
+            before,
+            after,
+            utilization_before: utilization(before),
+            utilization_after: utilization(after),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WhatIfReport {
+    pub account_id: Uuid,
+    pub generated_at: DateTime<Utc>,
+    pub order_checks: Vec<OrderCheck>,
+    pub positions: Vec<ProjectedPosition>,
+    pub pnl_before: Decimal,
+    pub pnl_after: Decimal,
+    pub pnl_impact: Decimal,
+    pub var_before: Decimal,
+    pub var_after: Decimal,
+    pub limits: Vec<LimitUtilization>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Holding {
+    quantity: Decimal,
+    average_price: Decimal,
+    realized_pnl: Decimal,
+}
+
+impl Holding {
+    /// Same averaging and realization as the position manager, before fees.
+    fn fill(&mut self, side: &OrderSide, quantity: Decimal, price: Decimal) {
+        let change = match side {
+            OrderSide::Buy => quantity,
+            OrderSide::Sell => -quantity,
+        };
+        let new_quantity = self.quantity + change;
+        if self.quantity.is_zero() || self.quantity.is_sign_positive() == change.is_sign_positive() {
+            self.average_price = (self.quantity.abs() * self.average_price + quantity * price) / new_quantity.abs();
+        } else {
+            let closed = quantity.min(self.quantity.abs());
+            let direction = if self.quantity.is_sign_positive() { Decimal::ONE } else { -Decimal::ONE };
+            self.realized_pnl += notional_value(closed * direction, price - self.average_price);
+            if !new_quantity.is_zero() && new_quantity.is_sign_positive() != self.quantity.is_sign_positive() {
+                self.average_price = price;
+            }
+        }
+        self.quantity = new_quantity;
+    }
+
+    fn pnl_at(&self, price: Decimal) -> Decimal {
+        self.realized_pnl + notional_value(self.quantity, price - self.average_price)
+    }
+}
+
+/// Mark, duration and shocked mark of one symbol.
+struct Pricing {
+    mark: Decimal,
+    shift_bps: Decimal,
+    shocked: Decimal,
+    duration: Option<Decimal>,
+}
+
+impl TradingEngine {
+    pub async fn what_if(&self, request: &WhatIfRequest) -> crate::types::Result<WhatIfReport> {
+        let limits = self.risk_manager.risk_limits(request.account_id).await?;
+        let now = self.time_provider.now();
+        let settlement = self.time_provider.settlement_date(now.date_naive(), self.config.settlement_cycle_days);
+
+        let mut holdings: BTreeMap<String, (Holding, Holding)> = BTreeMap::new();
+        for position in self.position_manager.get_positions(Some(request.account_id)).await {
+            let holding = Holding {
+                quantity: position.quantity,
+                average_price: position.average_price,
+                realized_pnl: position.realized_pnl,
+            };
+            holdings.insert(position.symbol, (holding, holding));
+        }
+
+        let mut order_checks = Vec::with_capacity(request.orders.len());
+        for hypothetical in &request.orders {
+            if hypothetical.quantity <= Decimal::ZERO {
+                return Err(TradingError::InvalidOrder("Quantity must be positive".to_string()));
+            }
+            let price = match hypothetical.price {
+                Some(price) => price,
+                None => self.reference_price(&hypothetical.symbol)?.price,
+            };
+            let order = Order {
+                id: Uuid::new_v4(),
+                client_order_id: "WHAT-IF".to_string(),
+                symbol: hypothetical.symbol.clone(),
+                side: hypothetical.side.clone(),
+                order_type: OrderType::Limit,
+                quantity: hypothetical.quantity,
+                price: Some(price),
+                filled_quantity: Decimal::ZERO,
+                remaining_quantity: hypothetical.quantity,
+                status: OrderStatus::Pending,
+                timestamp: now,
+                user_id: Uuid::nil(),
+                account_id: request.account_id,
+                time_in_force: TimeInForce::ImmediateOrCancel,
+                metadata: HashMap::new(),
+                strategy_id: None,
+                fills: OrderFills::default(),
+                cancel_reason: None,
+            };
+            let rejection = self.risk_manager.check_order(&order).await.err().map(|e| e.to_string());
+            holdings
+                .entry(hypothetical.symbol.clone())
+                .or_default()
+                .1
+                .fill(&hypothetical.side, hypothetical.quantity, price);
+            order_checks.push(OrderCheck {
+                symbol: hypothetical.symbol.clone(),
+                side: hypothetical.side.clone(),
+                quantity: hypothetical.quantity,
+                price,
+                rejection,
+            });
+        }
+
+        let mut positions = Vec::with_capacity(holdings.len());
+        for (symbol, (before, after)) in holdings {
+            let pricing = self.scenario_pricing(&symbol, before.average_price, &request.shock, settlement);
+            positions.push(ProjectedPosition {
+                quantity_before: before.quantity,
+                quantity_after: after.quantity,
+                average_price_after: after.average_price,
+                mark_price: pricing.mark,
+                yield_shift_bps: pricing.shift_bps,
+                shocked_price: pricing.shocked,
+                modified_duration: pricing.duration,
+                market_value_before: notional_value(before.quantity, pricing.mark),
+                market_value_after: notional_value(after.quantity, pricing.shocked),
+                pnl_before: before.pnl_at(pricing.mark),
+                pnl_after: after.pnl_at(pricing.shocked),
+                symbol,
+            });
+        }
+
+        let pnl_before: Decimal = positions.iter().map(|position| position.pnl_before).sum();
+        let pnl_after: Decimal = positions.iter().map(|position| position.pnl_after).sum();
+        let exposures = |after: bool| -> Vec<(Decimal, Decimal)> {
+            positions
+                .iter()
+                .filter_map(|position| {
+                    let value = if after { position.market_value_after } else { position.market_value_before };
+                    Some((value, position.modified_duration?))
+                })
+                .collect()
+        };
+        let var_before = self.risk_manager.duration_var(&exposures(false));
+        let var_after = self.risk_manager.duration_var(&exposures(true));
+
+        let largest = |quantity: fn(&ProjectedPosition) -> Decimal| {
+            positions.iter().map(|position| quantity(position).abs()).max().unwrap_or_default()
+        };
+        let concentration = |value: fn(&ProjectedPosition) -> Decimal| {
+            let gross: Decimal = positions.iter().map(|position| value(position).abs()).sum();
+            if gross.is_zero() {
+                return Decimal::ZERO;
+            }
+            let top = positions.iter().map(|position| value(position).abs()).max().unwrap_or_default();
+            (top / gross).round_dp(6)
+        };
+        let limit_utilization = vec![
+            LimitUtilization::new(
+                "max_position_size",
+                limits.max_position_size,
+                largest(|position| position.quantity_before),
+                largest(|position| position.quantity_after),
+            ),
+            LimitUtilization::new(
+                "concentration_limit",
+                limits.concentration_limit,
+                concentration(|position| position.market_value_before),
+                concentration(|position| position.market_value_after),
+            ),
+            LimitUtilization::new("var_limit", limits.var_limit, var_before, var_after),
+            LimitUtilization::new(
+                "max_daily_loss",
+                limits.max_daily_loss,
+                Decimal::ZERO,
+                (pnl_before - pnl_after).max(Decimal::ZERO),
+            ),
+        ];
+
+        Ok(WhatIfReport {
+            account_id: request.account_id,
+            generated_at: now,
+            order_checks,
+            positions,
+            pnl_before,
+            pnl_after,
+            pnl_impact: pnl_after - pnl_before,
+            var_before,
+            var_after,
+            limits: limit_utilization,
+        })
+    }
+
+    /// Marks at the reference price, falling back to `fallback`, and
+    /// reprices at the yield moved by the shock for the bond's tenor.
+    fn scenario_pricing(&self, symbol: &str, fallback: Decimal, shock: &YieldShock, settlement: NaiveDate) -> Pricing {
+        let mark = self.reference_price(symbol).map(|reference| reference.price).unwrap_or(fallback);
+        let unshocked = Pricing {
+            mark,
+            shift_bps: Decimal::ZERO,
+            shocked: mark,
+            duration: None,
+        };
+        let Some(bond) = self.instruments.get(symbol) else {
+            return unshocked;
+        };
+        let Some(ytm) = analytics::yield_to_maturity(&bond, mark, settlement, &self.fixings) else {
+            return unshocked;
+        };
+        let duration = analytics::modified_duration(&bond, ytm, settlement, &self.fixings);
+
+        let years = Decimal::from((bond.maturity_date.date_naive() - settlement).num_days()) / Decimal::from(365);
+        let shift_bps = shock.shift_bps(years);
+        let shocked = if shift_bps.is_zero() {
+            Some(mark)
+        } else {
+            let rate = (ytm + shift_bps / Decimal::ONE_HUNDRED) / Decimal::ONE_HUNDRED;
+            rate.to_f64()
+                .and_then(|rate| analytics::dirty_price_at_yield(&bond, rate, settlement, &self.fixings))
+                .and_then(Decimal::from_f64_retain)
+                .map(|dirty| (dirty - accrued::accrued_interest(&bond, settlement, &self.fixings)).round_dp(6))
+        };
+        Pricing {
+            mark,
+            shift_bps,
+            shocked: shocked.unwrap_or(mark),
+            duration,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{feeds::MarketDataFeed, types::MarketData, utils::time::TimeProvider};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// One instrument in a source's snapshot. Prices are clean, yields in
+/// percent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalQuote {
+    pub symbol: String,
+    #[serde(default, alias = "bid_price")]
+    pub bid: Option<Decimal>,
+    #[serde(default, alias = "ask_price")]
+    pub ask: Option<Decimal>,
+    #[serde(default, alias = "last_price")]
+    pub last: Option<Decimal>,
+    #[serde(default)]
+    pub volume: Decimal,
+    #[serde(default, alias = "yield_to_maturity")]
+    pub r#yield: Option<Decimal>,
+    /// When the source priced the instrument; receipt time if absent.
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl ExternalQuote {
+    /// Crossed or non-positive sides are dropped rather than passed on.
+    pub fn normalize(self, received_at: DateTime<Utc>) -> MarketData {
+        let positive = |price: Option<Decimal>| price.filter(|price| *price > Decimal::ZERO);
+        let (mut bid, mut ask) = (positive(self.bid), positive(self.ask));
+        if bid.zip(ask).is_some_and(|(bid, ask)| bid > ask) {
+            (bid, ask) = (None, None);
+        }
+        MarketData {
+            symbol: self.symbol.trim().to_uppercase(),
+            bid_price: bid,
+            ask_price: ask,
+            last_price: positive(self.last),
+            volume: self.volume,
+            timestamp: self.timestamp.unwrap_or(received_at),
+            yield_to_maturity: self.r#yield,
+            yield_to_worst: None,
+            duration: None,
+            accrued_interest: None,
+        }
+    }
+}
+
+/// Polls a REST endpoint returning a JSON array of `ExternalQuote`s.
+pub struct HttpPollingFeed {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+    interval: Interval,
+    /// Stamps quotes the source sent without a time.
+    time_provider: Arc<TimeProvider>,
+}
+
+impl HttpPollingFeed {
+    pub fn new(name: &str, url: &str, period: Duration, time_provider: Arc<TimeProvider>) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(period.max(Duration::from_secs(1))).build()?;
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Ok(Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            client,
+            interval,
+            time_provider,
+        })
+    }
+}
+
+#[async_trait]
+impl MarketDataFeed for HttpPollingFeed {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<MarketData>> {
+        self.interval.tick().await;
+        let body = self.client.get(&self.url).send().await?.error_for_status()?.bytes().await?;
+        let quotes: Vec<ExternalQuote> =
+            serde_json::from_slice(&body).with_context(|| format!("Unreadable snapshot from {}", self.url))?;
+        let received_at = self.time_provider.now();
+        Ok(quotes.into_iter().map(|quote| quote.normalize(received_at)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_normalize_drops_crossed_sides_and_defaults_timestamp() {
+        let received_at = Utc::now();
+        let quotes: Vec<ExternalQuote> = serde_json::from_str(
+            r#"[
+                {"symbol": " gsec10y ", "bid": "99.90", "ask": "100.10", "last_price": "100.00"},
+                {"symbol": "GSEC2Y", "bid": "101.00", "ask": "100.50", "last": "0"}
+            ]"#,
+        )
+        .unwrap();
+        let normalized: Vec<MarketData> = quotes.into_iter().map(|quote| quote.normalize(received_at)).collect();
+
+        assert_eq!(normalized[0].symbol, "GSEC10Y");
+        assert_eq!(normalized[0].last_price, Some(dec!(100.00)));
+        assert_eq!(normalized[0].timestamp, received_at);
+        assert_eq!((normalized[1].bid_price, normalized[1].ask_price, normalized[1].last_price), (None, None, None));
+    }
+}
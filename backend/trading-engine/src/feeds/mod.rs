@@ -0,0 +1,157 @@
+//! External market data. Each feed adapter turns its source's messages into
+//! `MarketData` with clean prices; `run` drives an adapter, hands its quotes
+//! to the engine and keeps the feed's health figures.
+
+use crate::{config::Config, engine::TradingEngine, types::MarketData};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+pub mod http_polling;
+
+use http_polling::HttpPollingFeed;
+
+#[async_trait]
+pub trait MarketDataFeed: Send {
+    fn name(&self) -> &str;
+
+    /// Waits for the source's next batch of quotes.
+    async fn next_batch(&mut self) -> anyhow::Result<Vec<MarketData>>;
+}
+
+#[derive(Debug, Clone, Default)]
+struct FeedStats {
+    updates: u64,
+    rejected: u64,
+    errors: u64,
+    last_update: Option<DateTime<Utc>>,
+    /// Receipt time less the newest quote's own timestamp.
+    last_latency_ms: Option<i64>,
+    last_error: Option<String>,
+    connected: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedHealth {
+    pub name: String,
+    pub connected: bool,
+    pub stale: bool,
+    pub updates: u64,
+    /// Quotes the engine refused, e.g. for unlisted symbols.
+    pub rejected: u64,
+    pub errors: u64,
+    pub last_update: Option<DateTime<Utc>>,
+    pub seconds_since_update: Option<i64>,
+    pub last_latency_ms: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+pub struct FeedMonitor {
+    feeds: DashMap<String, FeedStats>,
+    stale_after: Duration,
+}
+
+impl FeedMonitor {
+    pub fn new(stale_after_secs: i64) -> Self {
+        Self {
+            feeds: DashMap::new(),
+            stale_after: Duration::seconds(stale_after_secs),
+        }
+    }
+
+    pub fn register(&self, name: &str) {
+        self.feeds.entry(name.to_string()).or_default();
+    }
+
+    fn record_batch(&self, name: &str, accepted: u64, rejected: u64, newest: Option<DateTime<Utc>>, now: DateTime<Utc>) {
+        let mut stats = self.feeds.entry(name.to_string()).or_default();
+        stats.connected = true;
+        stats.updates += accepted;
+        stats.rejected += rejected;
+        if accepted > 0 {
+            stats.last_update = Some(now);
+            stats.last_latency_ms = newest.map(|timestamp| (now - timestamp).num_milliseconds());
+        }
+    }
+
+    fn record_error(&self, name: &str, error: String) {
+        let mut stats = self.feeds.entry(name.to_string()).or_default();
+        stats.connected = false;
+        stats.errors += 1;
+        stats.last_error = Some(error);
+    }
+
+    pub fn health(&self, now: DateTime<Utc>) -> Vec<FeedHealth> {
+        let mut health: Vec<FeedHealth> = self
+            .feeds
+            .iter()
+            .map(|entry| {
+                let stats = entry.value();
+                let since = stats.last_update.map(|last| now - last);
+                FeedHealth {
+                    name: entry.key().clone(),
+                    connected: stats.connected,
+                    stale: since.is_none_or(|since| since > self.stale_after),
+                    updates: stats.updates,
+                    rejected: stats.rejected,
+                    errors: stats.errors,
+                    last_update: stats.last_update,
+                    seconds_since_update: since.map(|since| since.num_seconds()),
+                    last_latency_ms: stats.last_latency_ms,
+                    last_error: stats.last_error.clone(),
+                }
+            })
+            .collect();
+        health.sort_by(|a, b| a.name.cmp(&b.name));
+        health
+    }
+}
+
+/// Hands one batch to the engine, counting what it accepts.
+pub async fn ingest(engine: &TradingEngine, name: &str, batch: Vec<MarketData>) {
+    let newest = batch.iter().map(|quote| quote.timestamp).max();
+    let (mut accepted, mut rejected) = (0, 0);
+    for quote in batch {
+        match engine.ingest_market_data(quote).await {
+            Ok(()) => accepted += 1,
+            Err(_) => rejected += 1,
+        }
+    }
+    engine
+        .feed_monitor()
+        .record_batch(name, accepted, rejected, newest, engine.time_provider().now());
+}
+
+/// Polls a feed for as long as the engine runs. Errors are counted and
+/// retried after a pause; adapters reconnect on their next call.
+pub async fn run(engine: Arc<TradingEngine>, mut feed: Box<dyn MarketDataFeed>, retry_delay: std::time::Duration) {
+    let name = feed.name().to_string();
+    engine.feed_monitor().register(&name);
+    info!("Market data feed {} started", name);
+    loop {
+        match feed.next_batch().await {
+            Ok(batch) => ingest(&engine, &name, batch).await,
+            Err(e) => {
+                warn!("Market data feed {} failed: {}", name, e);
+                engine.feed_monitor().record_error(&name, e.to_string());
+                tokio::time::sleep(retry_delay).await;
+            }
+        }
+    }
+}
+
+/// Starts every feed in the configuration.
+pub fn spawn_configured(engine: &Arc<TradingEngine>, config: &Config) {
+    let interval = std::time::Duration::from_millis(config.feed_poll_interval_ms);
+    for (name, url) in &config.market_data_feeds {
+        match HttpPollingFeed::new(name, url, interval, engine.time_provider().clone()) {
+            Ok(feed) => {
+                tokio::spawn(run(engine.clone(), Box::new(feed), interval));
+            }
+            Err(e) => warn!("Market data feed {} not started: {}", name, e),
+        }
+    }
+}
@@ -1,49 +1,87 @@
+
 use anyhow::Result;
-use axum::{extract::State, http::Method, routing::get, Router};
-use std::{net::SocketAddr, sync::Arc};
+use axum::{
+    http::Method,
+    routing::{delete, get, post, put},
+    Router,
+};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::signal;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
 mod config;
 mod engine;
+mod feeds;
 mod network;
+mod persistence;
+mod tenancy;
+#[cfg(test)]
+mod test_support;
 mod types;
 mod utils;
 
 use config::Config;
 use engine::TradingEngine;
-use network::handlers;
+use network::{
+    graphql::{self, GraphQLSchema},
+    handlers,
+    snapshots::BookSnapshotCache,
+    sse,
+};
+use tenancy::TenantRouter;
 
 #[derive(Clone)]
 pub struct AppState {
     pub engine: Arc<TradingEngine>,
     pub config: Arc<Config>,
+    pub book_snapshots: Arc<BookSnapshotCache>,
+    pub graphql: GraphQLSchema,
+}
+
+impl AppState {
+    pub fn new(engine: Arc<TradingEngine>, config: Arc<Config>) -> Self {
+        Self {
+            graphql: graphql::schema(engine.clone()),
+            engine,
+            config,
+            book_snapshots: Arc::new(BookSnapshotCache::new()),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
     let config = Arc::new(Config::from_env()?);
-    let engine = Arc::new(TradingEngine::new(config.clone()).await?);
-    
-    let state = AppState { engine, config };
+    utils::logging::init(&config)?;
+    let api = if config.tenant_api_keys.is_empty() {
+        let engine = start_engine(config.clone()).await?;
+        let state = AppState::new(engine, config.clone());
+        if config.binary_entry_port != 0 {
+            let addr = SocketAddr::from(([0, 0, 0, 0], config.binary_entry_port));
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("Binary order entry listening on {}", addr);
+            tokio::spawn(network::binary_entry::serve(listener, state.clone()));
+        }
+        routes().with_state(state)
+    } else {
+        let mut tenants = TenantRouter::new(config.tenant_api_keys.clone());
+        for tenant in config.tenants() {
+            let tenant_config = Arc::new(config.for_tenant(&tenant)?);
+            persistence::prepare_tenant(&tenant_config, &tenant).await?;
+            let engine = start_engine(tenant_config.clone()).await?;
+            info!("Tenant {} started as engine {}", tenant, tenant_config.engine_instance_id);
+            tenants.insert(&tenant, engine.clone(), routes().with_state(AppState::new(engine, tenant_config)));
+        }
+        tenancy::routes(tenants)
+    };
 
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_origin(tower_http::cors::Any)
         .allow_headers(tower_http::cors::Any);
 
-    let app = Router::new()
-        .route("/health", get(handlers::health_check))
-        .route("/orders", get(handlers::get_orders).post(handlers::submit_order))
-        .route("/orders/:id", get(handlers::get_order).delete(handlers::cancel_order))
-        .route("/trades", get(handlers::get_trades))
-        .route("/orderbook/:symbol", get(handlers::get_orderbook))
-        .route("/positions", get(handlers::get_positions))
-        .route("/ws", get(handlers::websocket_handler))
-        .with_state(state)
+    let app = api
         .layer(TraceLayer::new_for_http())
         .layer(cors);
 
@@ -59,6 +97,417 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Creates an engine and starts its background tasks.
+async fn start_engine(config: Arc<Config>) -> Result<Arc<TradingEngine>> {
+    let engine = Arc::new(TradingEngine::new(config.clone()).await?);
+
+    // Before any task that can enter, expire or trade orders starts, so
+    // restored orders keep their place; `/readyz` stays unready unless
+    // this succeeds
+    engine.warm_up().await;
+
+    // Wheel ticks and sweeps of half the tolerance each keep an order's
+    // removal within the tolerance of its expiry
+    let order_expiry_engine = engine.clone();
+    let order_expiry_interval = Duration::from_millis((config.expiry_tolerance_ms / 2).max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(order_expiry_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if let Err(e) = order_expiry_engine.expire_orders().await {
+                error!("Order expiry sweep failed: {}", e);
+            }
+        }
+    });
+
+    let expiry_engine = engine.clone();
+    let expiry_interval = Duration::from_millis(config.expiry_check_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(expiry_interval);
+        loop {
+            interval.tick().await;
+            expiry_engine.delist_matured_instruments();
+            expiry_engine.apply_due_corporate_actions().await;
+            expiry_engine.lending().accrue_fees();
+            expiry_engine.cash().accrue_interest();
+            expiry_engine.process_settlement_fails();
+            expiry_engine.expire_staged_orders();
+            expiry_engine.release_pre_open_orders().await;
+            expiry_engine.run_due_eod_reconciliation().await;
+        }
+    });
+
+    let last_look_engine = engine.clone();
+    let last_look_interval = Duration::from_millis(config.d2c_last_look_check_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(last_look_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            last_look_engine.sweep_last_look().await;
+        }
+    });
+
+    let liquidation_engine = engine.clone();
+    let liquidation_interval = Duration::from_millis(config.liquidation_check_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(liquidation_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            liquidation_engine.check_liquidations().await;
+        }
+    });
+
+    let pnl_engine = engine.clone();
+    let pnl_interval = Duration::from_millis(config.pnl_snapshot_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(pnl_interval);
+        loop {
+            interval.tick().await;
+            pnl_engine.snapshot_pnl().await;
+        }
+    });
+
+    if let Some(source) = utils::clock_sync::source_from_config(&config.clock_source) {
+        let clock_engine = engine.clone();
+        let clock_interval = Duration::from_millis(config.clock_check_interval_ms);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(clock_interval);
+            loop {
+                interval.tick().await;
+                clock_engine.check_clock(source.as_ref()).await;
+            }
+        });
+    }
+
+    if config.archive_sink != "none" {
+        let archive_engine = engine.clone();
+        let archive_interval = Duration::from_millis(config.archive_interval_ms);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(archive_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = archive_engine.run_retention().await {
+                    error!("Retention run failed: {}", e);
+                }
+            }
+        });
+    }
+
+    if config.settlement_outbound != "none" {
+        let settlement_engine = engine.clone();
+        let settlement_interval = Duration::from_millis(config.settlement_export_interval_ms);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(settlement_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = settlement_engine.export_settlement_instructions().await {
+                    error!("Settlement export failed: {}", e);
+                }
+            }
+        });
+    }
+
+    let quote_engine = engine.clone();
+    let quote_interval = Duration::from_millis(config.market_maker_sample_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(quote_interval);
+        loop {
+            interval.tick().await;
+            quote_engine.sample_market_maker_quotes();
+        }
+    });
+
+    let index_engine = engine.clone();
+    let index_interval = Duration::from_millis(config.index_valuation_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(index_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            index_engine.revalue_indices();
+        }
+    });
+
+    let benchmark_engine = engine.clone();
+    let benchmark_interval = Duration::from_millis(config.benchmark_reprice_interval_ms);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(benchmark_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            benchmark_engine.reprice_benchmark_orders();
+        }
+    });
+
+    if !config.reference_mm_symbols.is_empty() {
+        info!("Reference market maker quoting {}", config.reference_mm_symbols.join(", "));
+        let mm_engine = engine.clone();
+        let mm_interval = Duration::from_millis(config.reference_mm_refresh_interval_ms);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(mm_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                mm_engine.refresh_reference_quotes().await;
+            }
+        });
+    }
+
+    if let Err(e) = engine.load_notification_subscriptions().await {
+        warn!("Failed to load notification subscriptions: {}", e);
+    }
+    tokio::spawn(network::notifier::run(engine.clone(), config.clone()));
+    tokio::spawn(engine::conditional::run_triggers(engine.clone()));
+    tokio::spawn(engine::executions::run_reports(engine.clone()));
+    tokio::spawn(engine::symbol_stats::run_symbol_stats(engine.clone()));
+
+    feeds::spawn_configured(&engine, &config);
+
+    tokio::spawn(persistence::writer::run(
+        engine.clone(),
+        Duration::from_millis(config.state_flush_interval_ms),
+    ));
+
+    Ok(engine)
+}
+
+fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(handlers::health_check))
+        .route("/readyz", get(handlers::readiness_check))
+        .route("/orders", get(handlers::get_orders).post(handlers::submit_order))
+        .route("/orders/validate", post(handlers::validate_order))
+        .route(
+            "/orders/:id",
+            get(handlers::get_order).patch(handlers::reduce_order).delete(handlers::cancel_order),
+        )
+        .route("/trades", get(handlers::get_trades))
+        .route("/trades/aggregate", get(handlers::get_trade_aggregates))
+        .route("/trades/:key", get(handlers::get_trade))
+        .route("/executions", get(handlers::get_executions))
+        .route("/calendar/settle", get(handlers::get_settlement_date))
+        .route("/calendar/:market", get(handlers::get_calendar))
+        .route("/orderbook/:symbol", get(handlers::get_orderbook))
+        .route("/orderbook/:symbol/usage", get(handlers::get_book_usage))
+        .route("/admin/config", get(handlers::get_config))
+        .route("/admin/clock", get(handlers::get_clock))
+        .route("/admin/log-level", get(handlers::get_log_level).put(handlers::set_log_level))
+        .route("/session", get(handlers::get_session))
+        .route("/admin/session/release", post(handlers::release_pre_open_orders))
+        .route("/admin/calendar/:market", put(handlers::set_holidays))
+        .route("/admin/retention", get(handlers::get_retention))
+        .route("/admin/retention/run", post(handlers::run_retention))
+        .route("/admin/archive/:class", get(handlers::query_archive))
+        .route("/admin/feature-flags", get(handlers::list_feature_flags))
+        .route("/admin/validators", get(handlers::get_validators))
+        .route(
+            "/admin/feature-flags/:flag",
+            put(handlers::set_feature_flag).delete(handlers::clear_feature_flag),
+        )
+        .route("/admin/matching/topology", get(handlers::get_matching_topology))
+        .route("/admin/matching/topology/:symbol", put(handlers::migrate_symbol))
+        .route("/admin/books", get(handlers::list_book_usage))
+        .route(
+            "/admin/books/:symbol/limits",
+            put(handlers::set_book_limits).delete(handlers::clear_book_limits),
+        )
+        .route("/admin/books/:symbol/purge", post(handlers::purge_book))
+        .route("/admin/speed-bumps", get(handlers::list_speed_bumps))
+        .route("/admin/reference-mm", get(handlers::get_reference_quotes))
+        .route(
+            "/admin/speed-bumps/:symbol",
+            put(handlers::set_speed_bump).delete(handlers::clear_speed_bump),
+        )
+        .route("/admin/orders/import", post(handlers::import_orders))
+        .route("/spreads/orders", get(handlers::get_spread_orders).post(handlers::submit_spread_order))
+        .route("/spreads/orders/:id", delete(handlers::cancel_spread_order))
+        .route("/spreads/books", get(handlers::get_spread_books))
+        .route(
+            "/benchmark-orders",
+            get(handlers::get_benchmark_orders).post(handlers::submit_benchmark_order),
+        )
+        .route("/benchmark-orders/:id", delete(handlers::cancel_benchmark_order))
+        .route("/benchmark-books", get(handlers::get_benchmark_books))
+        .route("/indices", get(handlers::get_index_tickers))
+        .route("/indices/:symbol", get(handlers::get_index_ticker))
+        .route("/accounts/:id/index-exposure", get(handlers::get_index_exposure))
+        .route("/admin/indices/:symbol", put(handlers::define_index).delete(handlers::remove_index))
+        .route("/positions", get(handlers::get_positions))
+        .route("/positions/:account_id/:symbol/close", post(handlers::close_position))
+        .route("/events", get(handlers::get_events))
+        .route("/sync/changes", get(handlers::get_sync_changes))
+        .route("/accounts/:id/fees", get(handlers::get_account_fees))
+        .route("/accounts/:id/stats", get(handlers::get_account_stats))
+        .route("/admin/fees/schedule", get(handlers::get_fee_schedule).put(handlers::set_fee_schedule))
+        .route("/accounts/:id/activity", get(handlers::get_account_activity))
+        .route("/accounts/:id/risk-limits", get(handlers::get_risk_limits))
+        .route("/risk/what-if", post(handlers::evaluate_what_if))
+        .route("/risk/accounts/:id/tenor-exposure", get(handlers::get_tenor_exposure))
+        .route("/risk/accounts/:id/margin", get(handlers::get_margin_status))
+        .route("/risk/liquidations", get(handlers::get_liquidations))
+        .route("/marketmakers/:id/compliance", get(handlers::get_market_maker_compliance))
+        .route("/admin/marketmakers", get(handlers::get_market_makers))
+        .route(
+            "/admin/marketmakers/:id",
+            put(handlers::register_market_maker).delete(handlers::deregister_market_maker),
+        )
+        .route("/risk/groups/:id/exposure", get(handlers::get_group_exposure))
+        .route("/admin/risk/groups", get(handlers::get_account_groups))
+        .route(
+            "/admin/risk/groups/:id",
+            put(handlers::set_account_group).delete(handlers::delete_account_group),
+        )
+        .route("/admin/accounts/:id/risk-limits", put(handlers::set_risk_limits))
+        .route(
+            "/admin/accounts/:id/liquidation-policy",
+            get(handlers::get_liquidation_policy)
+                .put(handlers::set_liquidation_policy)
+                .delete(handlers::remove_liquidation_policy),
+        )
+        .route(
+            "/admin/risk/overrides",
+            get(handlers::get_risk_overrides).post(handlers::grant_risk_override),
+        )
+        .route("/admin/risk/overrides/:token", delete(handlers::revoke_risk_override))
+        .route("/admin/permissions", get(handlers::get_permissions))
+        .route(
+            "/admin/permissions/:account_id",
+            put(handlers::set_permissions).delete(handlers::delete_permissions),
+        )
+        .route("/admin/accounts/:id/tier", put(handlers::set_account_tier))
+        .route("/admin/accounts/:id/firm", put(handlers::set_account_firm))
+        .route(
+            "/admin/firms/:firm_id/compression",
+            get(handlers::get_compression_proposal).post(handlers::execute_compression),
+        )
+        .route("/instruments", get(handlers::get_instruments))
+        .route("/instruments/:symbol", get(handlers::get_instrument))
+        .route("/instruments/:symbol/aliases", get(handlers::get_symbol_aliases))
+        .route("/instruments/:symbol/analytics", get(handlers::get_bond_analytics))
+        .route("/market-data/:symbol", get(handlers::get_market_data))
+        .route("/market-summary", get(handlers::get_market_summary))
+        .route("/stats/:symbol", get(handlers::get_symbol_stats))
+        .route("/fixings/:index", get(handlers::get_fixings))
+        .route("/admin/fixings/:index", post(handlers::record_fixing))
+        .route("/inflation-indices/:index", get(handlers::get_index_levels))
+        .route("/admin/inflation-indices/:index", post(handlers::record_index_level))
+        .route("/instruments/:symbol/index-ratios", get(handlers::get_index_ratios))
+        .route("/instruments/:symbol/corporate-actions", get(handlers::get_corporate_actions))
+        .route("/admin/instruments", post(handlers::list_instrument))
+        .route("/admin/instruments/import", post(handlers::import_instruments))
+        .route("/admin/instruments/imports", get(handlers::get_instrument_imports))
+        .route("/admin/instruments/:symbol/aliases", post(handlers::add_symbol_alias))
+        .route("/admin/instruments/aliases/:alias", delete(handlers::remove_symbol_alias))
+        .route("/admin/instruments/:symbol/matching", put(handlers::set_matching_algorithm))
+        .route("/admin/instruments/:symbol/firm-preference", put(handlers::set_firm_preference))
+        .route("/admin/instruments/:symbol/tick-scale", put(handlers::set_tick_scale))
+        .route("/admin/instruments/:symbol/auction", post(handlers::start_auction))
+        .route("/admin/instruments/:symbol/uncross", post(handlers::uncross_auction))
+        .route("/admin/instruments/:symbol/suspend", post(handlers::suspend_instrument))
+        .route("/admin/instruments/:symbol/resume", post(handlers::resume_instrument))
+        .route("/admin/instruments/:symbol/delist", post(handlers::delist_instrument))
+        .route("/admin/instruments/:symbol/archive", get(handlers::get_instrument_archive))
+        .route("/admin/instruments/:symbol/corporate-actions", post(handlers::schedule_corporate_action))
+        .route("/admin/reconcile", get(handlers::reconcile_state))
+        .route("/admin/reconciliation/:date", get(handlers::get_eod_reconciliation))
+        .route("/admin/overview", get(handlers::get_overview))
+        .route("/reference-price/:symbol", get(handlers::get_reference_price))
+        .route("/matrix-price/:symbol", get(handlers::get_matrix_price))
+        .route("/pricing/curve", get(handlers::get_benchmark_curve))
+        .route("/pricing/spreads", get(handlers::get_spread_grid))
+        .route("/admin/pricing/curve", put(handlers::set_benchmark_curve))
+        .route("/admin/pricing/spreads", put(handlers::set_spread_grid))
+        .route("/reference-price/:symbol/external", post(handlers::set_external_reference_price))
+        .route("/feeds", get(handlers::get_feed_health))
+        .route("/admin/reference-price/:symbol/close", put(handlers::set_previous_close))
+        .route("/auction/:symbol/indicative", get(handlers::get_auction_indicative))
+        .route("/lending/inventory", get(handlers::get_lending_inventory).put(handlers::set_lendable_inventory))
+        .route("/lending/contracts", get(handlers::get_loan_contracts).post(handlers::create_loan))
+        .route("/lending/contracts/:id/return", post(handlers::return_loan))
+        .route("/settlement/instructions", get(handlers::get_settlement_instructions))
+        .route("/admin/settlement/export", post(handlers::export_settlement_instructions))
+        .route("/settlement/fails", get(handlers::get_settlement_fails).post(handlers::mark_settlement_failed))
+        .route("/settlement/fails/aging", get(handlers::get_fails_aging))
+        .route("/settlement/fails/:id/settle", post(handlers::settle_failed_quantity))
+        .route("/settlement/fails/:id/buy-in", post(handlers::complete_buy_in))
+        .route("/confirmations", get(handlers::get_confirmations))
+        .route("/confirmations/:trade_id", get(handlers::get_confirmation))
+        .route("/confirmations/:trade_id/affirm", post(handlers::affirm_trade))
+        .route("/admin/confirmations/exceptions", get(handlers::get_confirmation_exceptions))
+        .route(
+            "/admin/confirmations/exceptions/:id/resolve",
+            post(handlers::resolve_confirmation_exception),
+        )
+        .route("/accounts/:id/credit-lines", get(handlers::get_credit_lines).put(handlers::set_credit_line))
+        .route("/accounts/:id/credit-lines/:counterparty", delete(handlers::remove_credit_line))
+        .route("/accounts/:id/cash", get(handlers::get_cash_balances).post(handlers::post_cash_movement))
+        .route("/accounts/:id/cash/entries", get(handlers::get_cash_entries))
+        .route("/accounts/:id/cash/account-type", put(handlers::set_cash_account_type))
+        .route("/admin/cash/interest-rates", get(handlers::get_interest_rates).put(handlers::set_interest_rate))
+        .route("/accounts/:id/deliverable/:symbol", get(handlers::get_deliverable_quantity))
+        .route("/clearing/exposures", get(handlers::get_clearing_exposures))
+        .route("/clearing/members/:id/exposure", get(handlers::get_member_exposure))
+        .route("/clearing/legs", get(handlers::get_clearing_legs))
+        .route("/clearing/default-fund", get(handlers::get_default_fund))
+        .route("/admin/clearing/members/:id/limit", put(handlers::set_member_exposure_limit))
+        .route("/strategies", get(handlers::get_strategies))
+        .route("/admin/strategies", post(handlers::register_strategy))
+        .route("/admin/strategies/:id/status", put(handlers::set_strategy_status))
+        .route("/pnl", get(handlers::get_pnl))
+        .route(
+            "/notifications/subscriptions",
+            get(handlers::get_notification_subscriptions).post(handlers::create_notification_subscription),
+        )
+        .route("/notifications/subscriptions/:id", delete(handlers::delete_notification_subscription))
+        .route("/notifications/deliveries", get(handlers::get_notification_deliveries))
+        .route("/webhooks", get(handlers::get_webhooks).post(handlers::register_webhook))
+        .route("/webhooks/:id", delete(handlers::delete_webhook))
+        .route(
+            "/signing-keys",
+            get(handlers::get_signing_keys).post(handlers::register_signing_key),
+        )
+        .route("/signing-keys/:key_id", delete(handlers::revoke_signing_key))
+        .route(
+            "/conditional-orders",
+            get(handlers::get_conditional_orders).post(handlers::submit_conditional_order),
+        )
+        .route("/conditional-orders/:id", delete(handlers::cancel_conditional_order))
+        .route("/baskets", post(handlers::submit_basket))
+        .route("/baskets/:id", get(handlers::get_basket))
+        .route("/approvals", get(handlers::get_pending_approvals))
+        .route("/approvals/policies", get(handlers::get_approval_policies))
+        .route(
+            "/approvals/policies/:account_id",
+            put(handlers::set_approval_policy).delete(handlers::delete_approval_policy),
+        )
+        .route("/approvals/:order_id", get(handlers::get_staged_order).post(handlers::decide_staged_order))
+        .route("/d2c/clients/:account_id/segment", put(handlers::set_client_segment))
+        .route("/d2c/dealers/:account_id", put(handlers::set_dealer_settings))
+        .route("/d2c/dealers/stats", get(handlers::get_dealer_stats))
+        .route("/d2c/quotes", post(handlers::stream_dealer_quote))
+        .route("/d2c/quotes/:symbol", get(handlers::get_client_quotes))
+        .route("/d2c/hits", get(handlers::get_dealer_hits).post(handlers::hit_dealer_quote))
+        .route("/d2c/hits/:id/decision", post(handlers::decide_dealer_hit))
+        .route("/improvement-auctions", get(handlers::get_improvement_auctions))
+        .route("/improvement-auctions/stats", get(handlers::get_improvement_stats))
+        .route(
+            "/improvement-auctions/:id/responses",
+            post(handlers::respond_to_improvement_auction),
+        )
+        .route("/accounts/:id/pnl/timeseries", get(handlers::get_pnl_timeseries))
+        .route("/accounts/:id/valuation", get(handlers::get_portfolio_valuation))
+        .route("/graphql", get(graphql::graphiql).post(graphql::graphql_handler))
+        .route("/graphql/ws", get(graphql::graphql_ws_handler))
+        .route("/events/sse", get(sse::sse_handler))
+        .route("/ws", get(handlers::websocket_handler))
+        .route("/ws/auction", get(handlers::auction_websocket_handler))
+        .route("/ws/book", get(handlers::book_websocket_handler))
+        .route("/ws/valuation", get(handlers::valuation_websocket_handler))
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -0,0 +1,436 @@
+//! Binary order entry over TCP for participants who want less overhead
+//! than JSON over WebSocket. Frames decode to the same [`ClientMessage`]s
+//! and go through the same [`OrderEntrySession`], so validation and engine
+//! entry are shared with the WebSocket path.
+//!
+//! Every frame is a big-endian `u16` length followed by that many bytes: a
+//! one-byte message type, then its fields in order. Integers are
+//! big-endian; prices and quantities are `i64` counts of 10^-6; ids are 16
+//! raw bytes; strings are a `u8` length then UTF-8.
+//!
+//! Client to engine:
+//! - `L` logon: api key
+//! - `O` enter: request id `u32`, client order id, symbol, side `B`/`S`,
+//!   type `L`/`M`, time in force `G`/`D`/`I`/`F`, quantity, price (ignored
+//!   for market orders), user id
+//! - `U` replace: request id, order id, quantity, price (zero keeps either)
+//! - `X` cancel: request id, order id
+//!
+//! Engine to client:
+//! - `A` logon ack: account id, seq `u64`
+//! - `K` ack: request id, order id, status, seq
+//! - `J` reject: request id (zero when there is none), reject reason label,
+//!   message, seq
+//! - `F` fill: order id, trade id, quantity, price, leaves quantity, seq
+//!
+//! Statuses are `N` new, `P` partially filled, `F` filled, `C` cancelled,
+//! `R` rejected, `E` expired, `H` held for approval, `?` unknown. Fills are
+//! sent for the logged-on account's orders as they happen; a session that
+//! falls too far behind to send them all gets a reject and is closed.
+
+use crate::{
+    engine::EngineEvent,
+    network::{
+        handlers::SubmitOrderRequest,
+        order_entry::{reject, ClientMessage, OrderEntrySession, ServerMessage},
+    },
+    types::*,
+    AppState,
+};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{tcp::OwnedReadHalf, TcpListener, TcpStream},
+    sync::{broadcast::error::RecvError, mpsc},
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Decimal places carried by prices and quantities on the wire.
+const DECIMAL_SCALE: u32 = 6;
+
+/// Accepts connections until the listener fails.
+pub async fn serve(listener: TcpListener, state: AppState) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("Binary order entry connection from {}", peer);
+                let _ = stream.set_nodelay(true);
+                tokio::spawn(handle_connection(stream, state.clone()));
+            }
+            Err(e) => {
+                warn!("Binary order entry listener failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: AppState) {
+    let (reader, mut writer) = stream.into_split();
+    // Frames are read on their own task so a half-read frame is never
+    // dropped when an event is ready first
+    let (frames, mut inbound) = mpsc::channel(64);
+    tokio::spawn(read_frames(reader, frames));
+    let mut events = state.engine.subscribe_events();
+    let mut session = OrderEntrySession::default();
+
+    loop {
+        let reply = tokio::select! {
+            frame = inbound.recv() => match frame {
+                Some(frame) => {
+                    let reply = match decode(&frame, session.account_id()) {
+                        Ok(message) => session.handle_message(&state, message).await,
+                        Err(e) => reject(&state, None, e),
+                    };
+                    encode_reply(&reply)
+                }
+                None => break,
+            },
+            received = events.recv() => match received {
+                Ok(event) => match (&event.event, session.account_id()) {
+                    (EngineEvent::OrderFilled { order_id, trade }, Some(account_id))
+                        if event.concerns(account_id) =>
+                    {
+                        let leaves = state.engine.get_order(order_id).map(|order| order.remaining_quantity);
+                        encode_fill(*order_id, trade, leaves.unwrap_or_default(), event.seq)
+                    }
+                    _ => continue,
+                },
+                // Skipped fills cannot be sent late, so the session ends
+                // rather than carry on with a gap; the client reconciles
+                // its orders when it logs on again
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Binary order entry session lagged by {} events, disconnecting", skipped);
+                    let error = TradingError::InternalError(format!(
+                        "Session fell {} events behind and was closed; fills may be missing",
+                        skipped
+                    ));
+                    let _ = writer.write_all(&frame(&encode_reply(&reject(&state, None, error)))).await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if writer.write_all(&frame(&reply)).await.is_err() {
+            break;
+        }
+    }
+    info!("Binary order entry session closed");
+}
+
+async fn read_frames(mut reader: OwnedReadHalf, frames: mpsc::Sender<Vec<u8>>) {
+    loop {
+        let Ok(length) = reader.read_u16().await else {
+            return;
+        };
+        let mut frame = vec![0; usize::from(length)];
+        if reader.read_exact(&mut frame).await.is_err() || frames.send(frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn frame(body: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(body.len() + 2);
+    framed.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    framed.extend_from_slice(body);
+    framed
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(TradingError::InvalidRequest("Frame is truncated".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().expect("four bytes")))
+    }
+
+    fn decimal(&mut self) -> Result<Decimal> {
+        let units = i64::from_be_bytes(self.take(8)?.try_into().expect("eight bytes"));
+        Ok(Decimal::new(units, DECIMAL_SCALE).normalize())
+    }
+
+    fn uuid(&mut self) -> Result<Uuid> {
+        Ok(Uuid::from_bytes(self.take(16)?.try_into().expect("sixteen bytes")))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = usize::from(self.u8()?);
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| TradingError::InvalidRequest("String field is not UTF-8".to_string()))
+    }
+}
+
+fn invalid(what: &str, code: u8) -> TradingError {
+    TradingError::InvalidRequest(format!("Unknown {} {:?}", what, code as char))
+}
+
+/// Decodes one frame. Orders are entered for the session's account, so a
+/// frame before logon decodes against the nil account and is refused by the
+/// session.
+fn decode(frame: &[u8], account_id: Option<Uuid>) -> Result<ClientMessage> {
+    let mut reader = Reader { bytes: frame };
+    let message = match reader.u8()? {
+        b'L' => ClientMessage::Logon { api_key: reader.string()? },
+        b'O' => {
+            let request_id = reader.u32()?.to_string();
+            let client_order_id = reader.string()?;
+            let symbol = reader.string()?;
+            let side = match reader.u8()? {
+                b'B' => OrderSide::Buy,
+                b'S' => OrderSide::Sell,
+                code => return Err(invalid("side", code)),
+            };
+            let order_type = match reader.u8()? {
+                b'L' => OrderType::Limit,
+                b'M' => OrderType::Market,
+                code => return Err(invalid("order type", code)),
+            };
+            let time_in_force = match reader.u8()? {
+                b'G' => TimeInForce::GoodTillCancel,
+                b'D' => TimeInForce::GoodForDay,
+                b'I' => TimeInForce::ImmediateOrCancel,
+                b'F' => TimeInForce::FillOrKill,
+                code => return Err(invalid("time in force", code)),
+            };
+            let quantity = reader.decimal()?;
+            let price = reader.decimal()?;
+            let order = SubmitOrderRequest {
+                client_order_id,
+                symbol,
+                price: (!matches!(order_type, OrderType::Market)).then_some(price),
+                side,
+                order_type,
+                quantity,
+                user_id: reader.uuid()?,
+                account_id: account_id.unwrap_or_default(),
+                time_in_force: Some(time_in_force),
+                metadata: HashMap::new(),
+                strategy_id: None,
+                signature: None,
+            };
+            ClientMessage::Submit {
+                request_id,
+                order: Box::new(order),
+            }
+        }
+        b'U' => {
+            let request_id = reader.u32()?.to_string();
+            let order_id = reader.uuid()?;
+            let quantity = Some(reader.decimal()?).filter(|quantity| !quantity.is_zero());
+            let price = Some(reader.decimal()?).filter(|price| !price.is_zero());
+            ClientMessage::Modify {
+                request_id,
+                order_id,
+                quantity,
+                price,
+            }
+        }
+        b'X' => ClientMessage::Cancel {
+            request_id: reader.u32()?.to_string(),
+            order_id: reader.uuid()?,
+        },
+        code => return Err(invalid("message type", code)),
+    };
+    if !reader.bytes.is_empty() {
+        return Err(TradingError::InvalidRequest("Frame has trailing bytes".to_string()));
+    }
+    Ok(message)
+}
+
+fn put_decimal(out: &mut Vec<u8>, value: Decimal) {
+    let units = (value * Decimal::from(10u64.pow(DECIMAL_SCALE))).round().to_i64().unwrap_or(i64::MAX);
+    out.extend_from_slice(&units.to_be_bytes());
+}
+
+/// Writes at most 255 bytes of `value`, cut on a character boundary.
+fn put_string(out: &mut Vec<u8>, value: &str) {
+    let mut end = value.len().min(usize::from(u8::MAX));
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    out.push(end as u8);
+    out.extend_from_slice(&value.as_bytes()[..end]);
+}
+
+fn request_id(request_id: Option<&String>) -> [u8; 4] {
+    request_id
+        .and_then(|id| id.parse::<u32>().ok())
+        .unwrap_or_default()
+        .to_be_bytes()
+}
+
+fn status_code(status: Option<&OrderStatus>) -> u8 {
+    match status {
+        Some(OrderStatus::Pending) => b'N',
+        Some(OrderStatus::PartiallyFilled) => b'P',
+        Some(OrderStatus::Filled) => b'F',
+        Some(OrderStatus::Cancelled) => b'C',
+        Some(OrderStatus::Rejected(_)) => b'R',
+        Some(OrderStatus::Expired) => b'E',
+        Some(OrderStatus::PendingApproval) => b'H',
+        None => b'?',
+    }
+}
+
+fn encode_reply(reply: &ServerMessage) -> Vec<u8> {
+    let mut out = Vec::new();
+    match reply {
+        ServerMessage::LogonAck { account_id, seq } => {
+            out.push(b'A');
+            out.extend_from_slice(account_id.as_bytes());
+            out.extend_from_slice(&seq.to_be_bytes());
+        }
+        ServerMessage::Ack {
+            request_id: id,
+            order_id,
+            status,
+            seq,
+        } => {
+            out.push(b'K');
+            out.extend_from_slice(&request_id(Some(id)));
+            out.extend_from_slice(order_id.as_bytes());
+            out.push(status_code(status.as_ref()));
+            out.extend_from_slice(&seq.to_be_bytes());
+        }
+        ServerMessage::Reject {
+            request_id: id,
+            reason,
+            reject_reason,
+            seq,
+        } => {
+            out.push(b'J');
+            out.extend_from_slice(&request_id(id.as_ref()));
+            put_string(&mut out, &reject_reason.label());
+            put_string(&mut out, reason);
+            out.extend_from_slice(&seq.to_be_bytes());
+        }
+    }
+    out
+}
+
+fn encode_fill(order_id: Uuid, trade: &Trade, leaves: Decimal, seq: u64) -> Vec<u8> {
+    let mut out = vec![b'F'];
+    out.extend_from_slice(order_id.as_bytes());
+    out.extend_from_slice(trade.id.as_bytes());
+    put_decimal(&mut out, trade.quantity);
+    put_decimal(&mut out, trade.price);
+    put_decimal(&mut out, leaves);
+    out.extend_from_slice(&seq.to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::TradingEngine, test_support::OrderBuilder};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn enter(request_id: u32, side: u8, quantity: i64, price: i64) -> Vec<u8> {
+        let mut body = vec![b'O'];
+        body.extend_from_slice(&request_id.to_be_bytes());
+        put_string(&mut body, &format!("BIN-{}", request_id));
+        put_string(&mut body, "GSEC10Y");
+        body.extend_from_slice(&[side, b'L', b'G']);
+        body.extend_from_slice(&quantity.to_be_bytes());
+        body.extend_from_slice(&price.to_be_bytes());
+        body.extend_from_slice(Uuid::new_v4().as_bytes());
+        frame(&body)
+    }
+
+    async fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+        let length = stream.read_u16().await.unwrap();
+        let mut body = vec![0; usize::from(length)];
+        stream.read_exact(&mut body).await.unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn test_binary_session_enters_orders_and_streams_fills() {
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut config = Config::default();
+        config.order_entry_api_keys.insert("bin-key".to_string(), buyer);
+        let config = Arc::new(config);
+        let engine = Arc::new(TradingEngine::new(config.clone()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, AppState::new(engine.clone(), config)));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        stream.write_all(&enter(7, b'B', 100_000_000_000, 99_500_000)).await.unwrap();
+        let reply = read_frame(&mut stream).await;
+        assert_eq!((reply[0], &reply[1..5]), (b'J', &7u32.to_be_bytes()[..]));
+
+        let mut logon = vec![b'L'];
+        put_string(&mut logon, "bin-key");
+        stream.write_all(&frame(&logon)).await.unwrap();
+        let reply = read_frame(&mut stream).await;
+        assert_eq!((reply[0], Uuid::from_slice(&reply[1..17]).unwrap()), (b'A', buyer));
+
+        stream.write_all(&enter(8, b'B', 100_000_000_000, 99_500_000)).await.unwrap();
+        let ack = read_frame(&mut stream).await;
+        assert_eq!((ack[0], &ack[1..5], ack[21]), (b'K', &8u32.to_be_bytes()[..], b'N'));
+        let order_id = Uuid::from_slice(&ack[5..21]).unwrap();
+
+        let sell = OrderBuilder::new(OrderSide::Sell).quantity(dec!(40000)).account(seller).build();
+        engine.submit_order(sell).await.unwrap();
+        let fill = read_frame(&mut stream).await;
+        assert_eq!((fill[0], Uuid::from_slice(&fill[1..17]).unwrap()), (b'F', order_id));
+        let mut fields = Reader { bytes: &fill[33..57] };
+        let quantities = [fields.decimal().unwrap(), fields.decimal().unwrap(), fields.decimal().unwrap()];
+        assert_eq!(quantities, [dec!(40000), dec!(99.5), dec!(60000)]);
+
+        let mut cancel = vec![b'X'];
+        cancel.extend_from_slice(&9u32.to_be_bytes());
+        cancel.extend_from_slice(order_id.as_bytes());
+        stream.write_all(&frame(&cancel)).await.unwrap();
+        let ack = read_frame(&mut stream).await;
+        assert_eq!((ack[0], ack[21]), (b'K', b'C'));
+    }
+
+    #[tokio::test]
+    async fn test_lagging_session_is_told_and_closed() {
+        let account_id = Uuid::new_v4();
+        let mut config = Config {
+            event_channel_size: 2,
+            ..Config::default()
+        };
+        config.order_entry_api_keys.insert("bin-key".to_string(), account_id);
+        let config = Arc::new(config);
+        let engine = Arc::new(TradingEngine::new(config.clone()).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, AppState::new(engine.clone(), config)));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut logon = vec![b'L'];
+        put_string(&mut logon, "bin-key");
+        stream.write_all(&frame(&logon)).await.unwrap();
+        assert_eq!(read_frame(&mut stream).await[0], b'A');
+
+        // More events than the channel holds, before the session can run
+        for _ in 0..4 {
+            let order = OrderBuilder::new(OrderSide::Buy).build();
+            engine.submit_order(order).await.unwrap();
+        }
+        let reject = read_frame(&mut stream).await;
+        assert_eq!((reject[0], &reject[1..5]), (b'J', &0u32.to_be_bytes()[..]));
+        assert_eq!(stream.read(&mut [0; 1]).await.unwrap(), 0);
+    }
+}
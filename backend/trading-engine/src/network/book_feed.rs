@@ -0,0 +1,648 @@
+use crate::{engine::matching::BookOrder, types::*};
+use flate2::{write::DeflateEncoder, Compression};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+};
+use uuid::Uuid;
+
+/// One aggregated price level on the book feed. Direct and implied
+/// liquidity at a price are combined. In an update, a zero quantity
+/// removes the level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderAction {
+    Add,
+    Modify,
+    Delete,
+}
+
+/// A change to one resting order on the market-by-order feed. A deleted
+/// order carries its last state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderChange {
+    pub action: OrderAction,
+    pub side: OrderSide,
+    #[serde(flatten)]
+    pub order: BookOrder,
+}
+
+/// Messages on `/ws/book`. `sequence` counts messages on the connection,
+/// so a gap means an update was missed. `book_sequence` identifies the
+/// state of the books a message reflects and is shared by both flavors,
+/// so a market-by-order and a market-by-price client can line up their
+/// books. `checksum` covers the top levels as they stand after the message
+/// is applied, aggregated from the orders on the by-order flavor; see
+/// [`book_checksum`]. The two agree at the same `book_sequence` unless
+/// implied liquidity, which has no orders, sits in the top levels.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum BookFeedMessage {
+    /// `depth` is the levels per side the client should keep until the
+    /// next snapshot; it drops to one while the feed is conflated.
+    Snapshot {
+        symbol: String,
+        sequence: u64,
+        book_sequence: u64,
+        depth: usize,
+        bids: Vec<FeedLevel>,
+        asks: Vec<FeedLevel>,
+        checksum: u32,
+    },
+    Update {
+        symbol: String,
+        sequence: u64,
+        book_sequence: u64,
+        bids: Vec<FeedLevel>,
+        asks: Vec<FeedLevel>,
+        checksum: u32,
+    },
+    /// Every resting order at the best `depth` prices of each side, best
+    /// price first and in time priority within a price.
+    OrderSnapshot {
+        symbol: String,
+        sequence: u64,
+        book_sequence: u64,
+        depth: usize,
+        bids: Vec<BookOrder>,
+        asks: Vec<BookOrder>,
+        checksum: u32,
+    },
+    /// Orders that entered, changed or left the best `depth` prices.
+    OrderUpdate {
+        symbol: String,
+        sequence: u64,
+        book_sequence: u64,
+        changes: Vec<OrderChange>,
+        checksum: u32,
+    },
+    /// Trades in the symbol since the previous summary, sent alongside the
+    /// periodic snapshots of a conflated feed. Prices are clean.
+    Trades {
+        symbol: String,
+        sequence: u64,
+        count: u64,
+        volume: Decimal,
+        vwap: Decimal,
+        high: Decimal,
+        low: Decimal,
+        last: Decimal,
+    },
+}
+
+/// Client messages on `/ws/book`. A client whose local book no longer
+/// matches a checksum reports it and is sent a fresh snapshot.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum BookFeedRequest {
+    ChecksumMismatch {
+        sequence: Option<u64>,
+        checksum: Option<u32>,
+    },
+    /// Replaces the connection's settings. Full depth is restored and a
+    /// fresh snapshot follows.
+    Configure(FeedSettings),
+}
+
+/// How frames on the book feed are encoded. The WebSocket layer does not
+/// offer permessage-deflate, so compression is chosen in the subscription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedCompression {
+    /// JSON in text frames.
+    #[default]
+    None,
+    /// JSON compressed with raw deflate (RFC 1951) in binary frames.
+    Deflate,
+}
+
+/// Which book a feed connection carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFlavor {
+    /// Market by price: aggregated levels, implied liquidity included.
+    #[default]
+    Mbp,
+    /// Market by order: each resting order with its time priority.
+    Mbo,
+}
+
+/// The books as one flavor of the feed reads them.
+pub enum BookContent {
+    Levels(Option<OrderBook>),
+    Orders { bids: Vec<BookOrder>, asks: Vec<BookOrder> },
+}
+
+/// A read of the books at one book sequence.
+pub struct BookRead {
+    pub book_sequence: u64,
+    pub content: BookContent,
+}
+
+/// Delivery settings of one book feed connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedSettings {
+    #[serde(default)]
+    pub flavor: FeedFlavor,
+    /// Book changes within this window go out as one update. Zero sends
+    /// each change as it happens.
+    #[serde(default)]
+    pub batch_ms: u64,
+    /// Drop to top of book, rather than keep sending full depth, once the
+    /// client falls behind the engine's events.
+    #[serde(default)]
+    pub conflate: bool,
+    #[serde(default)]
+    pub compression: FeedCompression,
+    /// Sends a snapshot, when the book changed, and a summary of the trades
+    /// since the last one this often instead of each change. Zero keeps the
+    /// full-tick feed.
+    #[serde(default)]
+    pub snapshot_ms: u64,
+}
+
+/// Raw deflate of `bytes`, for [`FeedCompression::Deflate`] frames.
+pub fn deflate(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(bytes.len() / 4), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// CRC32 of the top levels, interleaved best bid, best ask, second bid and
+/// so on, each written `price:quantity` in plain decimal without trailing
+/// zeros and joined with `:`. A side that runs out is skipped.
+pub fn book_checksum(bids: &[FeedLevel], asks: &[FeedLevel]) -> u32 {
+    let mut parts = Vec::with_capacity(bids.len() + asks.len());
+    for index in 0..bids.len().max(asks.len()) {
+        for level in [bids.get(index), asks.get(index)].into_iter().flatten() {
+            parts.push(format!("{}:{}", level.price.normalize(), level.quantity.normalize()));
+        }
+    }
+    crc32fast::hash(parts.join(":").as_bytes())
+}
+
+/// Top `depth` levels of one side, best first, with same-price levels merged.
+fn aggregate(levels: &[PriceLevel], depth: usize) -> Vec<FeedLevel> {
+    let mut merged: Vec<FeedLevel> = Vec::new();
+    for level in levels {
+        match merged.iter().position(|merged| merged.price == level.price) {
+            Some(index) => merged[index].quantity += level.quantity,
+            None if merged.len() < depth => merged.push(FeedLevel {
+                price: level.price,
+                quantity: level.quantity,
+            }),
+            None => {}
+        }
+    }
+    merged
+}
+
+/// Price levels of orders already ordered best first.
+fn order_levels(orders: &[BookOrder]) -> Vec<FeedLevel> {
+    let mut levels: Vec<FeedLevel> = Vec::new();
+    for order in orders {
+        match levels.last_mut() {
+            Some(level) if level.price == order.price => level.quantity += order.quantity,
+            _ => levels.push(FeedLevel {
+                price: order.price,
+                quantity: order.quantity,
+            }),
+        }
+    }
+    levels
+}
+
+/// Order changes that turn one side's `previous` orders into `current`,
+/// deletions first and then in book order.
+fn order_diff(side: OrderSide, previous: &[BookOrder], current: &[BookOrder]) -> Vec<OrderChange> {
+    let change = |action, order: &BookOrder| OrderChange {
+        action,
+        side: side.clone(),
+        order: order.clone(),
+    };
+    let before: HashMap<Uuid, &BookOrder> = previous.iter().map(|order| (order.order_id, order)).collect();
+    let remaining: HashSet<Uuid> = current.iter().map(|order| order.order_id).collect();
+    let mut changes: Vec<OrderChange> = previous
+        .iter()
+        .filter(|order| !remaining.contains(&order.order_id))
+        .map(|order| change(OrderAction::Delete, order))
+        .collect();
+    for order in current {
+        match before.get(&order.order_id) {
+            None => changes.push(change(OrderAction::Add, order)),
+            Some(previous) if *previous != order => changes.push(change(OrderAction::Modify, order)),
+            Some(_) => {}
+        }
+    }
+    changes
+}
+
+/// Changes that turn `previous` into `current`, including removals.
+fn diff(previous: &[FeedLevel], current: &[FeedLevel]) -> Vec<FeedLevel> {
+    let before: BTreeMap<Decimal, Decimal> = previous.iter().map(|level| (level.price, level.quantity)).collect();
+    let mut changes: Vec<FeedLevel> = current
+        .iter()
+        .filter(|level| before.get(&level.price) != Some(&level.quantity))
+        .cloned()
+        .collect();
+    changes.extend(
+        previous
+            .iter()
+            .filter(|level| !current.iter().any(|current| current.price == level.price))
+            .map(|level| FeedLevel {
+                price: level.price,
+                quantity: Decimal::ZERO,
+            }),
+    );
+    changes
+}
+
+/// Trades seen since the last summary on a conflated feed.
+#[derive(Debug, Default)]
+struct TradeTally {
+    count: u64,
+    volume: Decimal,
+    weighted_price: Decimal,
+    high: Decimal,
+    low: Decimal,
+    last: Decimal,
+}
+
+impl TradeTally {
+    fn add(&mut self, quantity: Decimal, price: Decimal) {
+        if self.count == 0 {
+            (self.high, self.low) = (price, price);
+        }
+        self.count += 1;
+        self.volume += quantity;
+        self.weighted_price += quantity * price;
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.last = price;
+    }
+}
+
+/// Resting orders of both sides, best first.
+type SideOrders = (Vec<BookOrder>, Vec<BookOrder>);
+
+/// Book feed state of one connection: the levels, and on the by-order
+/// flavor the orders, the client was last sent.
+pub struct BookFeed {
+    symbol: String,
+    depth: usize,
+    sequence: u64,
+    bids: Vec<FeedLevel>,
+    asks: Vec<FeedLevel>,
+    orders: Option<SideOrders>,
+    trades: TradeTally,
+}
+
+impl BookFeed {
+    pub fn new(symbol: String, depth: usize) -> Self {
+        Self {
+            symbol,
+            depth,
+            sequence: 0,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            orders: None,
+            trades: TradeTally::default(),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Changes the levels sent per side. The client needs a snapshot
+    /// afterwards, as updates are diffs at the old depth until then.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    pub fn snapshot(&mut self, read: BookRead) -> BookFeedMessage {
+        let book_sequence = read.book_sequence;
+        self.apply(read);
+        self.current_snapshot(book_sequence)
+    }
+
+    /// A snapshot, if the top of the book changed since the last message.
+    pub fn changed_snapshot(&mut self, read: BookRead) -> Option<BookFeedMessage> {
+        let book_sequence = read.book_sequence;
+        let (previous_bids, previous_asks, previous_orders) = self.apply(read);
+        if previous_bids == self.bids && previous_asks == self.asks && previous_orders == self.orders {
+            return None;
+        }
+        Some(self.current_snapshot(book_sequence))
+    }
+
+    pub fn record_trade(&mut self, quantity: Decimal, price: Decimal) {
+        self.trades.add(quantity, price);
+    }
+
+    /// Summary of the trades recorded since the last one, if any.
+    pub fn trade_summary(&mut self) -> Option<BookFeedMessage> {
+        let tally = std::mem::take(&mut self.trades);
+        if tally.count == 0 {
+            return None;
+        }
+        self.sequence += 1;
+        Some(BookFeedMessage::Trades {
+            symbol: self.symbol.clone(),
+            sequence: self.sequence,
+            count: tally.count,
+            volume: tally.volume,
+            vwap: tally.weighted_price / tally.volume,
+            high: tally.high,
+            low: tally.low,
+            last: tally.last,
+        })
+    }
+
+    fn current_snapshot(&mut self, book_sequence: u64) -> BookFeedMessage {
+        self.sequence += 1;
+        let checksum = book_checksum(&self.bids, &self.asks);
+        match &self.orders {
+            Some((bids, asks)) => BookFeedMessage::OrderSnapshot {
+                symbol: self.symbol.clone(),
+                sequence: self.sequence,
+                book_sequence,
+                depth: self.depth,
+                bids: bids.clone(),
+                asks: asks.clone(),
+                checksum,
+            },
+            None => BookFeedMessage::Snapshot {
+                symbol: self.symbol.clone(),
+                sequence: self.sequence,
+                book_sequence,
+                depth: self.depth,
+                bids: self.bids.clone(),
+                asks: self.asks.clone(),
+                checksum,
+            },
+        }
+    }
+
+    /// The update since the last message, if the top of the book changed.
+    /// A read in the other flavor than the last message is sent whole.
+    pub fn update(&mut self, read: BookRead) -> Option<BookFeedMessage> {
+        let book_sequence = read.book_sequence;
+        let (previous_bids, previous_asks, previous_orders) = self.apply(read);
+        let checksum = book_checksum(&self.bids, &self.asks);
+        let message = match (&self.orders, previous_orders) {
+            (Some((bids, asks)), Some((previous_bid_orders, previous_ask_orders))) => {
+                let mut changes = order_diff(OrderSide::Buy, &previous_bid_orders, bids);
+                changes.extend(order_diff(OrderSide::Sell, &previous_ask_orders, asks));
+                if changes.is_empty() {
+                    return None;
+                }
+                BookFeedMessage::OrderUpdate {
+                    symbol: self.symbol.clone(),
+                    sequence: self.sequence + 1,
+                    book_sequence,
+                    changes,
+                    checksum,
+                }
+            }
+            (None, None) => {
+                let bids = diff(&previous_bids, &self.bids);
+                let asks = diff(&previous_asks, &self.asks);
+                if bids.is_empty() && asks.is_empty() {
+                    return None;
+                }
+                BookFeedMessage::Update {
+                    symbol: self.symbol.clone(),
+                    sequence: self.sequence + 1,
+                    book_sequence,
+                    bids,
+                    asks,
+                    checksum,
+                }
+            }
+            _ => return Some(self.current_snapshot(book_sequence)),
+        };
+        self.sequence += 1;
+        Some(message)
+    }
+
+    /// Replaces what the client was sent with the read, returning the old
+    /// levels and orders.
+    fn apply(&mut self, read: BookRead) -> (Vec<FeedLevel>, Vec<FeedLevel>, Option<SideOrders>) {
+        let (bids, asks, orders) = match read.content {
+            BookContent::Levels(Some(book)) => {
+                (aggregate(&book.bids, self.depth), aggregate(&book.asks, self.depth), None)
+            }
+            BookContent::Levels(None) => (Vec::new(), Vec::new(), None),
+            BookContent::Orders { bids, asks } => (order_levels(&bids), order_levels(&asks), Some((bids, asks))),
+        };
+        (
+            std::mem::replace(&mut self.bids, bids),
+            std::mem::replace(&mut self.asks, asks),
+            std::mem::replace(&mut self.orders, orders),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+
+    fn book(bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> OrderBook {
+        let levels = |levels: &[(Decimal, Decimal)]| {
+            levels
+                .iter()
+                .map(|&(price, quantity)| PriceLevel {
+                    price,
+                    quantity,
+                    order_count: 1,
+                    implied: false,
+                })
+                .collect()
+        };
+        OrderBook {
+            symbol: "GSEC10Y".to_string(),
+            bids: levels(bids),
+            asks: levels(asks),
+            last_update: Utc::now(),
+        }
+    }
+
+    fn levels(book: &OrderBook) -> BookRead {
+        BookRead {
+            book_sequence: 0,
+            content: BookContent::Levels(Some(book.clone())),
+        }
+    }
+
+    /// What a client does with an update: apply each change, drop zero
+    /// quantities, re-sort and truncate to the feed depth.
+    fn apply_changes(local: &mut Vec<FeedLevel>, changes: &[FeedLevel], descending: bool, depth: usize) {
+        for change in changes {
+            local.retain(|level| level.price != change.price);
+            if change.quantity > Decimal::ZERO {
+                local.push(change.clone());
+            }
+        }
+        local.sort_by(|a, b| if descending { b.price.cmp(&a.price) } else { a.price.cmp(&b.price) });
+        local.truncate(depth);
+    }
+
+    #[test]
+    fn test_client_book_rebuilt_from_updates_matches_checksum() {
+        let mut feed = BookFeed::new("GSEC10Y".to_string(), 2);
+        let initial = book(
+            &[(dec!(99.00), dec!(100000)), (dec!(98.90), dec!(200000))],
+            &[(dec!(99.10), dec!(100000))],
+        );
+        let BookFeedMessage::Snapshot { mut bids, mut asks, checksum, .. } = feed.snapshot(levels(&initial)) else {
+            unreachable!()
+        };
+        assert_eq!(checksum, book_checksum(&bids, &asks));
+        assert!(feed.update(levels(&initial)).is_none());
+
+        // A better bid pushes 98.90 out of the top two; the offer is lifted
+        let next = book(
+            &[(dec!(99.05), dec!(50000)), (dec!(99.00), dec!(100000)), (dec!(98.90), dec!(200000))],
+            &[(dec!(99.20), dec!(300000))],
+        );
+        let Some(BookFeedMessage::Update {
+            bids: bid_changes,
+            asks: ask_changes,
+            checksum,
+            sequence,
+            ..
+        }) = feed.update(levels(&next))
+        else {
+            panic!("book change produced no update");
+        };
+        assert_eq!(sequence, 2);
+        apply_changes(&mut bids, &bid_changes, true, 2);
+        apply_changes(&mut asks, &ask_changes, false, 2);
+        assert_eq!(book_checksum(&bids, &asks), checksum);
+
+        // A client that missed the update no longer matches
+        let stale = aggregate(&initial.bids, 2);
+        assert_ne!(book_checksum(&stale, &asks), checksum);
+
+        // Trailing zeros do not change the checksum
+        let padded = [FeedLevel { price: dec!(99.050), quantity: dec!(50000.00) }];
+        let plain = [FeedLevel { price: dec!(99.05), quantity: dec!(50000) }];
+        assert_eq!(book_checksum(&padded, &[]), book_checksum(&plain, &[]));
+    }
+
+    #[test]
+    fn test_conflated_snapshot_carries_top_of_book_and_deflates() {
+        let mut feed = BookFeed::new("GSEC10Y".to_string(), 10);
+        let deep = book(
+            &[(dec!(99.00), dec!(100000)), (dec!(98.90), dec!(200000))],
+            &[(dec!(99.10), dec!(100000)), (dec!(99.20), dec!(300000))],
+        );
+        feed.snapshot(levels(&deep));
+        feed.set_depth(1);
+        let conflated = feed.snapshot(levels(&deep));
+        let BookFeedMessage::Snapshot { depth, ref bids, ref asks, .. } = conflated else {
+            unreachable!()
+        };
+        assert_eq!((depth, bids.len(), asks.len()), (1, 1, 1));
+        assert_eq!(bids[0].price, dec!(99.00));
+
+        let text = serde_json::to_string(&conflated).unwrap();
+        let compressed = deflate(text.as_bytes()).unwrap();
+        let mut inflated = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::DeflateDecoder::new(&compressed[..]), &mut inflated).unwrap();
+        assert_eq!(inflated, text);
+
+        let request: BookFeedRequest =
+            serde_json::from_str(r#"{"type":"Configure","batch_ms":250,"compression":"deflate"}"#).unwrap();
+        let BookFeedRequest::Configure(settings) = request else {
+            panic!("expected a Configure request");
+        };
+        assert_eq!((settings.batch_ms, settings.conflate), (250, false));
+    }
+
+    #[test]
+    fn test_conflated_tier_skips_unchanged_snapshots_and_summarizes_trades() {
+        let mut feed = BookFeed::new("GSEC10Y".to_string(), 10);
+        let resting = book(&[(dec!(99.00), dec!(100000))], &[(dec!(99.10), dec!(100000))]);
+        assert!(feed.changed_snapshot(levels(&resting)).is_some());
+        assert!(feed.changed_snapshot(levels(&resting)).is_none());
+        assert!(feed.trade_summary().is_none());
+
+        feed.record_trade(dec!(100000), dec!(99.10));
+        feed.record_trade(dec!(300000), dec!(99.00));
+        let Some(BookFeedMessage::Trades { sequence, count, volume, vwap, high, low, last, .. }) = feed.trade_summary()
+        else {
+            panic!("recorded trades produced no summary");
+        };
+        assert_eq!(sequence, 2);
+        assert_eq!((count, volume, vwap), (2, dec!(400000), dec!(99.025)));
+        assert_eq!((high, low, last), (dec!(99.10), dec!(99.00), dec!(99.00)));
+        assert!(feed.trade_summary().is_none());
+    }
+
+    #[test]
+    fn test_order_feed_checksum_matches_price_feed_at_the_same_book_sequence() {
+        let order = |price, quantity, priority| BookOrder {
+            order_id: Uuid::from_u128(priority as u128),
+            price,
+            quantity,
+            priority,
+        };
+        let orders = |book_sequence, bids, asks| BookRead {
+            book_sequence,
+            content: BookContent::Orders { bids, asks },
+        };
+        let at = |book_sequence, book: &OrderBook| BookRead {
+            book_sequence,
+            ..levels(book)
+        };
+        let mut by_order = BookFeed::new("GSEC10Y".to_string(), 2);
+        let mut by_price = BookFeed::new("GSEC10Y".to_string(), 2);
+        let asks = vec![order(dec!(99.10), dec!(100000), 4)];
+
+        let bids = vec![
+            order(dec!(99.00), dec!(100000), 1),
+            order(dec!(99.00), dec!(50000), 3),
+            order(dec!(98.90), dec!(200000), 2),
+        ];
+        let BookFeedMessage::OrderSnapshot { book_sequence, checksum, .. } =
+            by_order.snapshot(orders(7, bids, asks.clone()))
+        else {
+            panic!("expected an order snapshot");
+        };
+        let priced = book(&[(dec!(99.00), dec!(150000)), (dec!(98.90), dec!(200000))], &[(dec!(99.10), dec!(100000))]);
+        let BookFeedMessage::Snapshot { checksum: level_checksum, .. } = by_price.snapshot(at(7, &priced)) else {
+            unreachable!()
+        };
+        assert_eq!((book_sequence, checksum), (7, level_checksum));
+
+        // The first bid at 99.00 is partly filled and the 98.90 bid cancelled
+        let bids = vec![order(dec!(99.00), dec!(40000), 1), order(dec!(99.00), dec!(50000), 3)];
+        let Some(BookFeedMessage::OrderUpdate { changes, checksum, .. }) = by_order.update(orders(8, bids, asks)) else {
+            panic!("book change produced no order update");
+        };
+        let summary: Vec<(OrderAction, u64)> =
+            changes.iter().map(|change| (change.action, change.order.priority)).collect();
+        assert_eq!(summary, vec![(OrderAction::Delete, 2), (OrderAction::Modify, 1)]);
+        let priced = book(&[(dec!(99.00), dec!(90000))], &[(dec!(99.10), dec!(100000))]);
+        let Some(BookFeedMessage::Update { checksum: level_checksum, .. }) = by_price.update(at(8, &priced)) else {
+            panic!("book change produced no update");
+        };
+        assert_eq!(checksum, level_checksum);
+    }
+}
@@ -0,0 +1,458 @@
+//! GraphQL over the same engine state as the REST API, for front ends that
+//! want orders, their fills and positions in one round trip. Served from
+//! the engine's own router, so tenant API keys apply exactly as for REST,
+//! and the live event subscription is narrowed to an account with the same
+//! visibility rule as the WebSocket stream.
+
+use crate::{
+    engine::{event_journal::SequencedEvent, instruments::Instrument, trade_store::TradeQuery, TradingEngine},
+    types::{CancelReason, Order, OrderStatus, OrderType, Position, Trade},
+    AppState,
+};
+use async_graphql::{
+    futures_util::{stream, Stream},
+    http::GraphiQLSource,
+    Context, EmptyMutation, Json, Object, Schema,
+};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+use uuid::Uuid;
+
+pub type GraphQLSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+/// Most trades a single `trades` field returns.
+const MAX_TRADES: usize = 1000;
+
+pub fn schema(engine: Arc<TradingEngine>) -> GraphQLSchema {
+    Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot)
+        .data(engine)
+        .limit_depth(8)
+        .finish()
+}
+
+fn engine<'a>(ctx: &Context<'a>) -> &'a Arc<TradingEngine> {
+    ctx.data_unchecked::<Arc<TradingEngine>>()
+}
+
+/// The serde name of an enum value, as the REST API shows it.
+fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+async fn trades(engine: &TradingEngine, query: TradeQuery) -> async_graphql::Result<Vec<TradeNode>> {
+    Ok(engine.query_trades(&query).await?.into_iter().map(TradeNode).collect())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn order(&self, ctx: &Context<'_>, id: Uuid) -> Option<OrderNode> {
+        engine(ctx).get_order(&id).map(OrderNode)
+    }
+
+    /// Newest first.
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        account_id: Option<Uuid>,
+        symbol: Option<String>,
+        limit: Option<usize>,
+    ) -> Vec<OrderNode> {
+        let mut orders: Vec<Order> = engine(ctx)
+            .iter_orders()
+            .filter(|order| account_id.is_none_or(|id| order.account_id == id))
+            .filter(|order| symbol.as_ref().is_none_or(|symbol| order.symbol == *symbol))
+            .map(|order| order.clone())
+            .collect();
+        orders.sort_by_key(|order| std::cmp::Reverse(order.timestamp));
+        orders.truncate(limit.unwrap_or(usize::MAX));
+        orders.into_iter().map(OrderNode).collect()
+    }
+
+    /// `key` is either the trade id or the trade number.
+    async fn trade(&self, ctx: &Context<'_>, key: String) -> Option<TradeNode> {
+        engine(ctx).get_trade(&key).map(TradeNode)
+    }
+
+    async fn trades(
+        &self,
+        ctx: &Context<'_>,
+        account_id: Option<Uuid>,
+        symbol: Option<String>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: Option<usize>,
+    ) -> async_graphql::Result<Vec<TradeNode>> {
+        let query = TradeQuery {
+            from,
+            to,
+            symbol,
+            account_id,
+            limit: Some(limit.unwrap_or(MAX_TRADES).min(MAX_TRADES)),
+        };
+        trades(engine(ctx), query).await
+    }
+
+    async fn positions(&self, ctx: &Context<'_>, account_id: Option<Uuid>) -> Vec<PositionNode> {
+        engine(ctx).get_positions(account_id).await.into_iter().map(PositionNode).collect()
+    }
+
+    async fn instrument(&self, ctx: &Context<'_>, symbol: String) -> Option<InstrumentNode> {
+        engine(ctx).instruments().instrument(&symbol).map(InstrumentNode)
+    }
+
+    async fn instruments(&self, ctx: &Context<'_>) -> Vec<InstrumentNode> {
+        let mut instruments = engine(ctx).instruments().list();
+        instruments.sort_by(|a, b| a.bond.symbol.cmp(&b.bond.symbol));
+        instruments.into_iter().map(InstrumentNode).collect()
+    }
+}
+
+pub struct OrderNode(Order);
+
+#[Object(name = "Order")]
+impl OrderNode {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn client_order_id(&self) -> &str {
+        &self.0.client_order_id
+    }
+
+    async fn account_id(&self) -> Uuid {
+        self.0.account_id
+    }
+
+    async fn symbol(&self) -> &str {
+        &self.0.symbol
+    }
+
+    async fn side(&self) -> String {
+        label(&self.0.side)
+    }
+
+    /// The order type, with any parameters it carries.
+    async fn order_type(&self) -> Json<&OrderType> {
+        Json(&self.0.order_type)
+    }
+
+    async fn quantity(&self) -> Decimal {
+        self.0.quantity
+    }
+
+    async fn price(&self) -> Option<Decimal> {
+        self.0.price
+    }
+
+    async fn filled_quantity(&self) -> Decimal {
+        self.0.filled_quantity
+    }
+
+    async fn remaining_quantity(&self) -> Decimal {
+        self.0.remaining_quantity
+    }
+
+    async fn avg_fill_price(&self) -> Option<Decimal> {
+        self.0.fills.avg_fill_price
+    }
+
+    async fn last_fill_price(&self) -> Option<Decimal> {
+        self.0.fills.last_fill_price
+    }
+
+    async fn last_fill_quantity(&self) -> Decimal {
+        self.0.fills.last_fill_quantity
+    }
+
+    async fn cumulative_notional(&self) -> Decimal {
+        self.0.fills.cumulative_notional
+    }
+
+    async fn status(&self) -> Json<&OrderStatus> {
+        Json(&self.0.status)
+    }
+
+    async fn time_in_force(&self) -> String {
+        label(&self.0.time_in_force)
+    }
+
+    async fn cancel_reason(&self) -> Option<Json<CancelReason>> {
+        self.0.cancel_reason.map(Json)
+    }
+
+    async fn timestamp(&self) -> DateTime<Utc> {
+        self.0.timestamp
+    }
+
+    async fn strategy_id(&self) -> Option<&str> {
+        self.0.strategy_id.as_deref()
+    }
+
+    /// Trades this order took part in, oldest first.
+    async fn fills(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TradeNode>> {
+        let query = TradeQuery {
+            from: Some(self.0.timestamp),
+            to: None,
+            symbol: Some(self.0.symbol.clone()),
+            account_id: Some(self.0.account_id),
+            limit: None,
+        };
+        let mut fills = trades(engine(ctx), query).await?;
+        fills.retain(|trade| trade.0.buyer_order_id == self.0.id || trade.0.seller_order_id == self.0.id);
+        Ok(fills)
+    }
+
+    /// The account's position in the order's instrument.
+    async fn position(&self, ctx: &Context<'_>) -> Option<PositionNode> {
+        engine(ctx).get_position(self.0.account_id, &self.0.symbol).await.map(PositionNode)
+    }
+}
+
+pub struct TradeNode(Trade);
+
+#[Object(name = "Trade")]
+impl TradeNode {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn trade_number(&self) -> &str {
+        &self.0.trade_number
+    }
+
+    async fn symbol(&self) -> &str {
+        &self.0.symbol
+    }
+
+    async fn buyer_order_id(&self) -> Uuid {
+        self.0.buyer_order_id
+    }
+
+    async fn seller_order_id(&self) -> Uuid {
+        self.0.seller_order_id
+    }
+
+    async fn buyer_account_id(&self) -> Uuid {
+        self.0.buyer_account_id
+    }
+
+    async fn seller_account_id(&self) -> Uuid {
+        self.0.seller_account_id
+    }
+
+    async fn quantity(&self) -> Decimal {
+        self.0.quantity
+    }
+
+    /// Clean price.
+    async fn price(&self) -> Decimal {
+        self.0.price
+    }
+
+    async fn accrued_interest(&self) -> Decimal {
+        self.0.accrued_interest
+    }
+
+    async fn settlement_amount(&self) -> Decimal {
+        self.0.settlement_amount
+    }
+
+    async fn timestamp(&self) -> DateTime<Utc> {
+        self.0.timestamp
+    }
+
+    async fn settlement_date(&self) -> NaiveDate {
+        self.0.settlement_date
+    }
+
+    async fn trade_type(&self) -> String {
+        label(&self.0.trade_type)
+    }
+
+    async fn aggressor_side(&self) -> String {
+        label(&self.0.aggressor_side)
+    }
+
+    async fn instrument(&self, ctx: &Context<'_>) -> Option<InstrumentNode> {
+        engine(ctx).instruments().instrument(&self.0.symbol).map(InstrumentNode)
+    }
+}
+
+pub struct PositionNode(Position);
+
+#[Object(name = "Position")]
+impl PositionNode {
+    async fn account_id(&self) -> Uuid {
+        self.0.account_id
+    }
+
+    async fn symbol(&self) -> &str {
+        &self.0.symbol
+    }
+
+    async fn quantity(&self) -> Decimal {
+        self.0.quantity
+    }
+
+    async fn average_price(&self) -> Decimal {
+        self.0.average_price
+    }
+
+    async fn market_value(&self) -> Decimal {
+        self.0.market_value
+    }
+
+    async fn unrealized_pnl(&self) -> Decimal {
+        self.0.unrealized_pnl
+    }
+
+    async fn realized_pnl(&self) -> Decimal {
+        self.0.realized_pnl
+    }
+
+    async fn accrued_interest(&self) -> Decimal {
+        self.0.accrued_interest
+    }
+
+    async fn last_updated(&self) -> DateTime<Utc> {
+        self.0.last_updated
+    }
+
+    async fn instrument(&self, ctx: &Context<'_>) -> Option<InstrumentNode> {
+        engine(ctx).instruments().instrument(&self.0.symbol).map(InstrumentNode)
+    }
+}
+
+pub struct InstrumentNode(Instrument);
+
+#[Object(name = "Instrument")]
+impl InstrumentNode {
+    async fn symbol(&self) -> &str {
+        &self.0.bond.symbol
+    }
+
+    async fn isin(&self) -> &str {
+        &self.0.bond.isin
+    }
+
+    async fn issuer(&self) -> &str {
+        &self.0.bond.issuer
+    }
+
+    async fn maturity_date(&self) -> DateTime<Utc> {
+        self.0.bond.maturity_date
+    }
+
+    async fn coupon_rate(&self) -> Decimal {
+        self.0.bond.coupon_rate
+    }
+
+    async fn face_value(&self) -> Decimal {
+        self.0.bond.face_value
+    }
+
+    async fn bond_type(&self) -> String {
+        label(&self.0.bond.bond_type)
+    }
+
+    async fn rating(&self) -> Option<&str> {
+        self.0.bond.rating.as_deref()
+    }
+
+    async fn status(&self) -> String {
+        label(&self.0.status)
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[async_graphql::Subscription]
+impl SubscriptionRoot {
+    /// Live engine events, as on the WebSocket stream. With an account,
+    /// only its own events and public market data are sent.
+    async fn events(&self, ctx: &Context<'_>, account_id: Option<Uuid>) -> impl Stream<Item = Json<SequencedEvent>> {
+        let receiver = engine(ctx).subscribe_events();
+        stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if account_id.is_none_or(|id| event.visible_to(id)) => {
+                        return Some((Json(event), receiver));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("GraphQL subscriber lagged by {} events", skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+pub async fn graphql_handler(State(state): State<AppState>, request: GraphQLRequest) -> GraphQLResponse {
+    state.graphql.execute(request.into_inner()).await.into()
+}
+
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").subscription_endpoint("/graphql/ws").finish())
+}
+
+pub async fn graphql_ws_handler(
+    State(state): State<AppState>,
+    protocol: GraphQLProtocol,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let schema = state.graphql.clone();
+    ws.protocols(async_graphql::http::ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| GraphQLWebSocket::new(socket, schema, protocol).serve())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::OrderBuilder,
+        types::OrderSide,
+    };
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_orders_fills_and_positions_in_one_query() {
+        let engine = Arc::new(TradingEngine::new(Arc::new(Config::default())).await.unwrap());
+        let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Sell, seller)).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+
+        let query = format!(
+            r#"{{ orders(accountId: "{}") {{ side fills {{ price tradeType }} position {{ quantity }} }} }}"#,
+            buyer
+        );
+        let response = schema(engine).execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let order = &data["orders"][0];
+        assert_eq!(order["side"], "Buy");
+        assert_eq!(order["fills"][0]["price"], "99.5");
+        assert_eq!(order["fills"][0]["tradeType"], "Regular");
+        assert_eq!(order["position"]["quantity"], "100000");
+    }
+}
@@ -0,0 +1,2494 @@
+use crate::{
+    engine::{
+        account_groups::AccountGroup,
+        allocation::{FirmPreference, MatchingAlgorithm},
+        approvals::{ApprovalDecision, ApprovalPolicyRequest},
+        baskets::BasketStatus,
+        benchmark_orders::BenchmarkOrderRequest,
+        calendar::SettlementQuery,
+        cash::{CashAccountType, CashMovement, InterestRate},
+        settlement_export::InstructionStatus,
+        settlement_fails::FailRequest,
+        conditional::TriggerCondition,
+        confirmations::{AffirmRequest, ExceptionResolution, ExceptionStatus},
+        credit_lines::CreditLineRequest,
+        book_limits::BookLimits,
+        speed_bump::SpeedBump,
+        corporate_actions::CorporateActionRequest,
+        d2c::{DealerQuoteRequest, HitDecision, HitRequest},
+        executions::ExecutionQuery,
+        feature_flags::{FeatureFlag, FlagRollout},
+        fees::{AccountTier, FeeSchedule},
+        fixed_point::TickScale,
+        fixings::Fixing,
+        inflation::IndexLevel,
+        instruments::AliasRequest,
+        lending::LoanRequest,
+        market_makers::MarketMaker,
+        matrix_pricing::{CurvePoint, SpreadGrid},
+        notifications::{DeliveryFilter, SubscriptionRequest},
+        order_import::ImportFormat,
+        liquidation::LiquidationPolicy,
+        permissions::TradingPermissions,
+        pnl::PnlGrouping,
+        price_improvement::ImprovementResponse,
+        position_close::ClosePositionRequest,
+        retention::ArchiveQuery,
+        risk_manager::OverrideRequest,
+        signing::{OrderSignature, SigningKeyRequest},
+        spreads::SpreadOrderRequest,
+        strategies::StrategyRequest,
+        synthetic_indices::SyntheticIndex,
+        trade_aggregates::{TradeGrouping, TradeMetric},
+        trade_store::TradeQuery,
+        webhooks::WebhookRequest,
+        what_if::WhatIfRequest,
+        EngineEvent, TradingEngine,
+    },
+    network::{
+        book_feed::{
+            self, BookContent, BookFeed, BookFeedMessage, BookFeedRequest, BookRead, FeedCompression, FeedFlavor,
+            FeedSettings,
+        },
+        order_entry::OrderEntrySession,
+        snapshots::{json_response, json_seq},
+    },
+    persistence::archive::DataClass,
+    types::*,
+    utils::{calendar::Holiday, logging},
+    AppState,
+};
+use axum::{
+    async_trait,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRequestParts, Path, Query, State,
+    },
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::json;
+use std::{borrow::Cow, collections::HashMap, ops::Deref};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+const DEFAULT_EVENT_PAGE_SIZE: usize = 1000;
+
+impl IntoResponse for TradingError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            TradingError::OrderNotFound(_) | TradingError::InstrumentNotFound(_) => StatusCode::NOT_FOUND,
+            TradingError::InsufficientBalance { .. }
+            | TradingError::RiskLimitExceeded { .. }
+            | TradingError::InvalidOrder(_)
+            | TradingError::InvalidOrderField { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            TradingError::MarketClosed | TradingError::InstrumentNotTradable(_) => StatusCode::CONFLICT,
+            TradingError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            TradingError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            TradingError::MinimumQuoteLife { .. } | TradingError::BookCapacityExceeded(_) => StatusCode::CONFLICT,
+            TradingError::QuoteRateExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            TradingError::ConsumerStalled { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            TradingError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            TradingError::DatabaseError(_)
+            | TradingError::RedisError(_)
+            | TradingError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = json!({ "error": self.to_string(), "reject_reason": self.reject_reason() });
+        (status, Json(body)).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitOrderRequest {
+    pub client_order_id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub quantity: Decimal,
+    pub price: Option<Decimal>,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    pub strategy_id: Option<String>,
+    /// Client signature over the order, checked against the account's
+    /// registered keys.
+    pub signature: Option<OrderSignature>,
+}
+
+impl SubmitOrderRequest {
+    /// The order as entered at `now` on the engine clock.
+    pub fn into_order(self, now: DateTime<Utc>) -> Order {
+        Order {
+            id: Uuid::new_v4(),
+            client_order_id: self.client_order_id,
+            symbol: self.symbol,
+            side: self.side,
+            order_type: self.order_type,
+            quantity: self.quantity,
+            price: self.price,
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: self.quantity,
+            status: OrderStatus::Pending,
+            timestamp: now,
+            user_id: self.user_id,
+            account_id: self.account_id,
+            time_in_force: self.time_in_force.unwrap_or(TimeInForce::GoodTillCancel),
+            metadata: self.metadata,
+            strategy_id: self.strategy_id,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        }
+    }
+
+    pub fn into_signed_order(mut self, now: DateTime<Utc>) -> (Order, Option<OrderSignature>) {
+        let signature = self.signature.take();
+        (self.into_order(now), signature)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountFilter {
+    pub account_id: Option<Uuid>,
+}
+
+/// A `:symbol` path segment resolved to the internal symbol, so clients
+/// may give an ISIN or other alias instead.
+pub struct Symbol(pub String);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Symbol {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> std::result::Result<Self, Self::Rejection> {
+        let Path(key) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Symbol(state.engine.instruments().canonical(&key)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    pub from_seq: Option<u64>,
+    pub account_id: Option<Uuid>,
+    pub limit: Option<usize>,
+}
+
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "status": "healthy",
+        "environment": state.config.environment,
+        "metrics": state.engine.get_metrics().snapshot(),
+    }))
+}
+
+/// Ready once startup warm-up has rebuilt and verified the books; until
+/// then, or if it failed, 503 with its progress.
+pub async fn readiness_check(State(state): State<AppState>) -> impl IntoResponse {
+    let warmup = state.engine.warmup_status();
+    let code = if warmup.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(json!({ "ready": warmup.is_ready(), "warmup": warmup })))
+}
+
+/// An order as returned by the order queries, with the instant it lapses
+/// if it has one and its price as entered. `price` is the clean price the
+/// order rests at.
+#[derive(Debug, Serialize)]
+pub struct OrderView<'a> {
+    #[serde(flatten)]
+    pub order: Cow<'a, Order>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub quoted_price: Option<Decimal>,
+}
+
+impl From<Order> for OrderView<'static> {
+    fn from(order: Order) -> Self {
+        OrderView::new(Cow::Owned(order))
+    }
+}
+
+impl<'a> From<&'a Order> for OrderView<'a> {
+    fn from(order: &'a Order) -> Self {
+        OrderView::new(Cow::Borrowed(order))
+    }
+}
+
+/// Serializes an order held elsewhere, such as under a map guard, as an
+/// `OrderView` without copying it.
+struct OrderRef<R>(R);
+
+impl<R: Deref<Target = Order>> Serialize for OrderRef<R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        OrderView::from(&*self.0).serialize(serializer)
+    }
+}
+
+impl<'a> OrderView<'a> {
+    fn new(order: Cow<'a, Order>) -> Self {
+        let expires_at = order.expires_at();
+        let quoted_price = order
+            .metadata
+            .get("quoted_price")
+            .and_then(|quoted| quoted.parse().ok())
+            .or(order.price);
+        Self {
+            order,
+            expires_at,
+            quoted_price,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserFilter {
+    pub user_id: Option<Uuid>,
+}
+
+pub async fn get_notification_subscriptions(
+    State(state): State<AppState>,
+    Query(filter): Query<UserFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.notifications().subscriptions(filter.user_id))
+}
+
+pub async fn create_notification_subscription(
+    State(state): State<AppState>,
+    Json(request): Json<SubscriptionRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let subscription = state.engine.subscribe_notifications(request).await?;
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+pub async fn delete_notification_subscription(
+    State(state): State<AppState>,
+    Path(subscription_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let removed = state.engine.unsubscribe_notifications(subscription_id).await?;
+    Ok(Json(json!({ "subscription_id": subscription_id, "removed": removed })))
+}
+
+pub async fn get_notification_deliveries(
+    State(state): State<AppState>,
+    Query(filter): Query<DeliveryFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.notifications().deliveries(&filter))
+}
+
+pub async fn get_webhooks(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.webhooks().list(filter.account_id))
+}
+
+pub async fn register_webhook(
+    State(state): State<AppState>,
+    Json(request): Json<WebhookRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let endpoint = state.engine.register_webhook(request)?;
+    Ok((StatusCode::CREATED, Json(endpoint)))
+}
+
+pub async fn get_signing_keys(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.signing_keys().list(filter.account_id))
+}
+
+pub async fn register_signing_key(
+    State(state): State<AppState>,
+    Json(request): Json<SigningKeyRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let key = state.engine.register_signing_key(request)?;
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+pub async fn revoke_signing_key(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    let key = state
+        .engine
+        .revoke_signing_key(&key_id)
+        .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown signing key {}", key_id)))?;
+    Ok(Json(key))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BasketRequest {
+    pub orders: Vec<SubmitOrderRequest>,
+    #[serde(default)]
+    pub all_or_nothing: bool,
+}
+
+pub async fn submit_basket(
+    State(state): State<AppState>,
+    Json(request): Json<BasketRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let now = state.engine.time_provider().now();
+    let orders = request.orders.into_iter().map(|order| order.into_order(now)).collect();
+    let basket = state.engine.submit_basket(orders, request.all_or_nothing).await?;
+    let status = match basket.status {
+        BasketStatus::Rejected => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::CREATED,
+    };
+    Ok((status, Json(basket)))
+}
+
+pub async fn get_basket(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.basket(id)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConditionalOrderRequest {
+    #[serde(flatten)]
+    pub order: SubmitOrderRequest,
+    pub condition: TriggerCondition,
+}
+
+pub async fn submit_conditional_order(
+    State(state): State<AppState>,
+    Json(request): Json<ConditionalOrderRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let held = state
+        .engine
+        .submit_conditional_order(request.order.into_order(state.engine.time_provider().now()), request.condition)
+        .await?;
+    Ok((StatusCode::CREATED, Json(held)))
+}
+
+pub async fn get_conditional_orders(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.conditional_orders().list(filter.account_id))
+}
+
+pub async fn cancel_conditional_order(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.cancel_conditional_order(id)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApprovalQueueFilter {
+    pub account_id: Option<Uuid>,
+    /// Only orders this user may decide.
+    pub approver_id: Option<Uuid>,
+}
+
+pub async fn get_pending_approvals(
+    State(state): State<AppState>,
+    Query(filter): Query<ApprovalQueueFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.approvals().pending(filter.account_id, filter.approver_id))
+}
+
+pub async fn get_staged_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let staged = state
+        .engine
+        .approvals()
+        .get(order_id)
+        .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+    Ok(Json(staged))
+}
+
+pub async fn decide_staged_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+    Json(decision): Json<ApprovalDecision>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.decide_staged_order(order_id, decision).await?))
+}
+
+pub async fn get_approval_policies(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.approvals().policies())
+}
+
+pub async fn set_approval_policy(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<ApprovalPolicyRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.set_approval_policy(account_id, request)?))
+}
+
+pub async fn delete_approval_policy(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let policy = state
+        .engine
+        .approvals()
+        .remove_policy(account_id)
+        .ok_or_else(|| TradingError::InvalidRequest(format!("Account {} has no approval policy", account_id)))?;
+    Ok(Json(policy))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClientSegmentRequest {
+    pub segment: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DealerSettingsRequest {
+    pub last_look_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClientQuoteQuery {
+    pub account_id: Uuid,
+}
+
+pub async fn set_client_segment(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<ClientSegmentRequest>,
+) -> impl IntoResponse {
+    state.engine.d2c().set_segment(account_id, request.segment);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn set_dealer_settings(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<DealerSettingsRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.set_dealer_last_look(account_id, request.last_look_ms)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn stream_dealer_quote(
+    State(state): State<AppState>,
+    Json(request): Json<DealerQuoteRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let quote = state.engine.stream_dealer_quote(request)?;
+    Ok((StatusCode::CREATED, Json(quote)))
+}
+
+pub async fn get_client_quotes(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Query(query): Query<ClientQuoteQuery>,
+) -> impl IntoResponse {
+    Json(state.engine.client_quotes(query.account_id, &symbol))
+}
+
+pub async fn hit_dealer_quote(
+    State(state): State<AppState>,
+    Json(request): Json<HitRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let hit = state.engine.hit_dealer_quote(request).await?;
+    Ok((StatusCode::ACCEPTED, Json(hit)))
+}
+
+pub async fn get_dealer_hits(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.d2c().hits(filter.account_id))
+}
+
+pub async fn decide_dealer_hit(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(decision): Json<HitDecision>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.decide_hit(id, decision).await?))
+}
+
+pub async fn get_dealer_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.d2c().stats())
+}
+
+pub async fn get_improvement_auctions(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.price_improvement().open_auctions())
+}
+
+pub async fn respond_to_improvement_auction(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(response): Json<ImprovementResponse>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.respond_to_improvement_auction(id, response)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn get_improvement_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.price_improvement().stats())
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(webhook_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let removed = state.engine.remove_webhook(webhook_id);
+    Json(json!({ "webhook_id": webhook_id, "removed": removed }))
+}
+
+pub async fn get_overview(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.overview().await)
+}
+
+pub async fn get_orders(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> Response {
+    json_seq(
+        state
+            .engine
+            .iter_orders()
+            .filter(|order| filter.account_id.is_none_or(|id| order.account_id == id))
+            .map(OrderRef),
+    )
+}
+
+pub async fn submit_order(
+    State(state): State<AppState>,
+    Json(request): Json<SubmitOrderRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let (order, signature) = request.into_signed_order(state.engine.time_provider().now());
+    let order_id = state.engine.submit_signed_order(order, signature).await?;
+    Ok((StatusCode::CREATED, Json(json!({ "order_id": order_id }))))
+}
+
+/// Runs the order through entry checks without submitting it.
+pub async fn validate_order(
+    State(state): State<AppState>,
+    Json(request): Json<SubmitOrderRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.preview_order(request.into_order(state.engine.time_provider().now())).await?))
+}
+
+pub async fn get_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+) -> crate::types::Result<Json<OrderView<'static>>> {
+    state
+        .engine
+        .get_order(&order_id)
+        .map(|order| Json(order.into()))
+        .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))
+}
+
+pub async fn cancel_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let cancelled = state.engine.cancel_order(order_id).await?;
+    Ok(Json(json!({ "order_id": order_id, "cancelled": cancelled })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReduceOrderRequest {
+    pub quantity: Decimal,
+}
+
+/// Reduces an order's quantity in place; it keeps its time priority.
+pub async fn reduce_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+    Json(request): Json<ReduceOrderRequest>,
+) -> crate::types::Result<Json<OrderView<'static>>> {
+    Ok(Json(state.engine.reduce_order(order_id, request.quantity).await?.into()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub format: Option<ImportFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstrumentImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Loads an ISIN master CSV. `?dry_run=true` returns the diff without
+/// applying it.
+pub async fn import_instruments(
+    State(state): State<AppState>,
+    Query(query): Query<InstrumentImportQuery>,
+    body: String,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.import_instruments(&body, query.dry_run)?))
+}
+
+pub async fn get_instrument_imports(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.instrument_imports().history())
+}
+
+/// Bulk order load. The format comes from `?format=` or, failing that, the
+/// request content type.
+pub async fn import_orders(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> crate::types::Result<impl IntoResponse> {
+    let format = query.format.unwrap_or_else(|| {
+        ImportFormat::from_content_type(
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok()),
+        )
+    });
+    Ok(Json(state.engine.import_orders(format, &body).await?))
+}
+
+pub async fn submit_spread_order(
+    State(state): State<AppState>,
+    Json(request): Json<SpreadOrderRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let execution = state.engine.submit_spread_order(request).await?;
+    Ok((StatusCode::CREATED, Json(execution)))
+}
+
+pub async fn get_index_tickers(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.synthetic_indices().tickers())
+}
+
+pub async fn get_index_ticker(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    let ticker = state
+        .engine
+        .synthetic_indices()
+        .ticker(&symbol)
+        .ok_or_else(|| TradingError::InstrumentNotFound(format!("No synthetic index {}", symbol)))?;
+    Ok(Json(ticker))
+}
+
+pub async fn define_index(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Json(mut index): Json<SyntheticIndex>,
+) -> crate::types::Result<impl IntoResponse> {
+    index.symbol = symbol;
+    Ok(Json(state.engine.define_index(index)?))
+}
+
+pub async fn remove_index(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+) -> impl IntoResponse {
+    let removed = state.engine.remove_index(&symbol);
+    Json(json!({ "symbol": symbol, "removed": removed }))
+}
+
+pub async fn get_index_exposure(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.index_exposure(account_id).await)
+}
+
+pub async fn get_spread_orders(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.get_spread_orders(filter.account_id))
+}
+
+pub async fn cancel_spread_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let cancelled = state.engine.cancel_spread_order(order_id)?;
+    Ok(Json(json!({ "order_id": order_id, "cancelled": cancelled })))
+}
+
+pub async fn get_spread_books(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.spread_books())
+}
+
+pub async fn submit_benchmark_order(
+    State(state): State<AppState>,
+    Json(request): Json<BenchmarkOrderRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let execution = state.engine.submit_benchmark_order(request).await?;
+    Ok((StatusCode::CREATED, Json(execution)))
+}
+
+pub async fn get_benchmark_orders(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.get_benchmark_orders(filter.account_id))
+}
+
+pub async fn cancel_benchmark_order(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let cancelled = state.engine.cancel_benchmark_order(order_id)?;
+    Ok(Json(json!({ "order_id": order_id, "cancelled": cancelled })))
+}
+
+pub async fn get_benchmark_books(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.benchmark_books())
+}
+
+pub async fn get_reference_quotes(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.reference_quotes())
+}
+
+/// Without `from` only the in-memory window is searched; with it, older
+/// trades come from the state store.
+pub async fn get_trades(
+    State(state): State<AppState>,
+    Query(mut query): Query<TradeQuery>,
+) -> crate::types::Result<Response> {
+    query.symbol = query.symbol.map(|symbol| state.engine.instruments().canonical(&symbol));
+    if let Some(response) = state.engine.with_recent_trades(&query, |trades| json_seq(trades))? {
+        return Ok(response);
+    }
+    Ok(Json(state.engine.query_trades(&query).await?).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradeAggregateQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub symbol: Option<String>,
+    pub account_id: Option<Uuid>,
+    #[serde(default)]
+    pub group_by: TradeGrouping,
+    #[serde(default)]
+    pub metric: TradeMetric,
+}
+
+pub async fn get_trade_aggregates(
+    State(state): State<AppState>,
+    Query(query): Query<TradeAggregateQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    let trades = TradeQuery {
+        from: query.from,
+        to: query.to,
+        symbol: query.symbol.map(|symbol| state.engine.instruments().canonical(&symbol)),
+        account_id: query.account_id,
+        limit: None,
+    };
+    Ok(Json(
+        state
+            .engine
+            .aggregate_trades(&trades, query.group_by, query.metric)
+            .await?,
+    ))
+}
+
+pub async fn get_executions(
+    State(state): State<AppState>,
+    Query(query): Query<ExecutionQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.query_executions(&query).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    pub year: Option<i32>,
+}
+
+pub async fn get_calendar(
+    State(state): State<AppState>,
+    Path(market): Path<String>,
+    Query(query): Query<CalendarQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.calendar_year(&market, query.year)?))
+}
+
+pub async fn get_settlement_date(
+    State(state): State<AppState>,
+    Query(query): Query<SettlementQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.settlement_date_for(&query)?))
+}
+
+/// Replaces the market's holidays in the years the list covers.
+pub async fn set_holidays(
+    State(state): State<AppState>,
+    Path(market): Path<String>,
+    Json(holidays): Json<Vec<Holiday>>,
+) -> impl IntoResponse {
+    state.engine.set_holidays(&market, holidays);
+    StatusCode::NO_CONTENT
+}
+
+/// `key` is either the trade id or the trade number.
+pub async fn get_trade(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> Response {
+    match state.engine.get_trade(&key) {
+        Some(trade) => Json(trade).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No trade {}", key) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_orderbook(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> Response {
+    match state.book_snapshots.orderbook(&state.engine, &symbol) {
+        Some(body) => json_response(body),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No order book for {}", symbol) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_book_usage(State(state): State<AppState>, Symbol(symbol): Symbol) -> impl IntoResponse {
+    Json(state.engine.book_usage(&symbol))
+}
+
+pub async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.config.redacted())
+}
+
+pub async fn get_clock(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.clock_report())
+}
+
+pub async fn get_session(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.session_status())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// Filter directives, e.g. `debug` or `info,vedhavriddhi_trading_engine::engine::matching=trace`.
+    pub level: String,
+}
+
+pub async fn get_log_level() -> impl IntoResponse {
+    Json(json!({ "level": logging::log_level() }))
+}
+
+pub async fn set_log_level(Json(request): Json<LogLevelRequest>) -> crate::types::Result<impl IntoResponse> {
+    let level = logging::set_log_level(&request.level)?;
+    info!(level = %level, "Log level changed");
+    Ok(Json(json!({ "level": level })))
+}
+
+pub async fn release_pre_open_orders(State(state): State<AppState>) -> impl IntoResponse {
+    let released = state.engine.release_pre_open_orders().await;
+    Json(json!({ "released": released }))
+}
+
+pub async fn get_retention(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.retention_status())
+}
+
+pub async fn run_retention(State(state): State<AppState>) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.run_retention().await?))
+}
+
+pub async fn query_archive(
+    State(state): State<AppState>,
+    Path(class): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    let class = DataClass::from_name(&class)
+        .ok_or_else(|| TradingError::InvalidRequest(format!("Unknown data class: {}", class)))?;
+    Ok(Json(state.engine.query_archive(class, &query).await?))
+}
+
+pub async fn list_feature_flags(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.feature_flags().states())
+}
+
+pub async fn set_feature_flag(
+    State(state): State<AppState>,
+    Path(flag): Path<FeatureFlag>,
+    Json(rollout): Json<FlagRollout>,
+) -> crate::types::Result<impl IntoResponse> {
+    if rollout.percent > 100 {
+        return Err(TradingError::InvalidRequest("Percent must be at most 100".to_string()));
+    }
+    info!("Feature flag {:?} overridden: {:?}", flag, rollout);
+    state.engine.feature_flags().set_override(flag, rollout);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn clear_feature_flag(State(state): State<AppState>, Path(flag): Path<FeatureFlag>) -> impl IntoResponse {
+    if state.engine.feature_flags().clear_override(flag) {
+        info!("Feature flag {:?} returned to its configured rollout", flag);
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Order validators in the order they run.
+pub async fn get_validators(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.validation_pipeline().validators())
+}
+
+pub async fn get_matching_topology(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.matching_shards().topology())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MigrateSymbolRequest {
+    pub shard: usize,
+}
+
+pub async fn migrate_symbol(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<MigrateSymbolRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.matching_shards().migrate(&symbol, request.shard)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_book_usage(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.book_usages())
+}
+
+pub async fn set_book_limits(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(limits): Json<BookLimits>,
+) -> impl IntoResponse {
+    state.engine.book_limits().set(&symbol, limits);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn clear_book_limits(State(state): State<AppState>, Symbol(symbol): Symbol) -> impl IntoResponse {
+    if state.engine.book_limits().clear(&symbol) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub async fn list_speed_bumps(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.speed_bumps().list())
+}
+
+pub async fn set_speed_bump(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(bump): Json<SpeedBump>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.set_speed_bump(&symbol, bump)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn clear_speed_bump(State(state): State<AppState>, Symbol(symbol): Symbol) -> impl IntoResponse {
+    if state.engine.speed_bumps().clear(&symbol) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeBookRequest {
+    /// Good-till-cancel orders entered longer ago than this are cancelled.
+    pub max_age_secs: i64,
+}
+
+pub async fn purge_book(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<PurgeBookRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let purge = state
+        .engine
+        .purge_stale_orders(&symbol, chrono::Duration::seconds(request.max_age_secs))
+        .await?;
+    Ok(Json(purge))
+}
+
+pub async fn get_positions(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.get_positions(filter.account_id).await)
+}
+
+pub async fn close_position(
+    State(state): State<AppState>,
+    Path((account_id, symbol)): Path<(Uuid, String)>,
+    Json(request): Json<ClosePositionRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let result = state.engine.close_position(account_id, &symbol, request).await?;
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+pub async fn get_portfolio_valuation(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.value_portfolio(account_id).await)
+}
+
+pub async fn get_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(DEFAULT_EVENT_PAGE_SIZE).min(DEFAULT_EVENT_PAGE_SIZE);
+    Json(state.engine.replay_events(query.from_seq.unwrap_or(1), query.account_id, limit))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncQuery {
+    #[serde(default)]
+    pub since_seq: u64,
+    pub account_id: Option<Uuid>,
+    pub limit: Option<usize>,
+}
+
+/// Order, trade and position changes since a cursor, for OMSes that poll.
+pub async fn get_sync_changes(
+    State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(DEFAULT_EVENT_PAGE_SIZE).min(DEFAULT_EVENT_PAGE_SIZE);
+    Json(state.engine.sync_changes(query.since_seq, query.account_id, limit).await)
+}
+
+pub async fn get_account_fees(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.fee_engine().account_summary(account_id))
+}
+
+pub async fn get_account_stats(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let stats = state.engine.get_account_stats(account_id);
+    let maker_ratio = stats.maker_ratio();
+    Json(json!({
+        "stats": stats,
+        "maker_ratio": maker_ratio,
+    }))
+}
+
+pub async fn get_fee_schedule(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.fee_engine().schedule())
+}
+
+pub async fn set_fee_schedule(
+    State(state): State<AppState>,
+    Json(schedule): Json<FeeSchedule>,
+) -> impl IntoResponse {
+    state.engine.fee_engine().set_schedule(schedule);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn get_account_activity(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.get_account_activity(account_id))
+}
+
+pub async fn get_risk_limits(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.risk_manager().risk_limits(account_id).await?))
+}
+
+pub async fn get_tenor_exposure(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.tenor_exposure(account_id).await?))
+}
+
+/// Margin against the account's liquidation policy; 404 without one.
+pub async fn get_margin_status(State(state): State<AppState>, Path(account_id): Path<Uuid>) -> Response {
+    match state.engine.margin_status(account_id).await {
+        Some(status) => Json(status).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn get_liquidations(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.liquidations().log(filter.account_id))
+}
+
+/// Projected positions, P&L, VaR and limit use under hypothetical fills and
+/// a yield shock. Nothing is executed.
+pub async fn evaluate_what_if(
+    State(state): State<AppState>,
+    Json(request): Json<WhatIfRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.what_if(&request).await?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverridesQuery {
+    pub account_id: Option<Uuid>,
+}
+
+pub async fn get_permissions(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.permissions().all())
+}
+
+pub async fn set_permissions(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(permissions): Json<TradingPermissions>,
+) -> impl IntoResponse {
+    state.engine.permissions().set(account_id, permissions);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn delete_permissions(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if state.engine.permissions().remove(account_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub async fn get_account_groups(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.account_groups().all())
+}
+
+pub async fn set_account_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    Json(group): Json<AccountGroup>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.account_groups().upsert(AccountGroup { id: group_id, ..group })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_account_group(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> impl IntoResponse {
+    match state.engine.account_groups().remove(&group_id) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+pub async fn get_group_exposure(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.group_exposure(&group_id).await?))
+}
+
+pub async fn get_market_makers(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.market_makers().all())
+}
+
+pub async fn register_market_maker(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(maker): Json<MarketMaker>,
+) -> impl IntoResponse {
+    state.engine.market_makers().register(MarketMaker { account_id, ..maker });
+    StatusCode::NO_CONTENT
+}
+
+pub async fn deregister_market_maker(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.engine.market_makers().deregister(account_id) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ComplianceQuery {
+    /// Trading day; today when omitted.
+    pub date: Option<NaiveDate>,
+}
+
+pub async fn get_market_maker_compliance(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Query(query): Query<ComplianceQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.market_maker_compliance(account_id, query.date)?))
+}
+
+pub async fn get_risk_overrides(
+    State(state): State<AppState>,
+    Query(query): Query<OverridesQuery>,
+) -> impl IntoResponse {
+    Json(state.engine.risk_manager().overrides(query.account_id))
+}
+
+pub async fn grant_risk_override(
+    State(state): State<AppState>,
+    Json(request): Json<OverrideRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let grant = state.engine.grant_risk_override(request)?;
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+pub async fn revoke_risk_override(
+    State(state): State<AppState>,
+    Path(token): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.revoke_risk_override(token)?))
+}
+
+pub async fn set_risk_limits(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(limits): Json<RiskLimits>,
+) -> crate::types::Result<impl IntoResponse> {
+    let limits = RiskLimits { account_id, ..limits };
+    state.engine.risk_manager().set_risk_limits(limits).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_liquidation_policy(State(state): State<AppState>, Path(account_id): Path<Uuid>) -> Response {
+    match state.engine.liquidations().policy(account_id) {
+        Some(policy) => Json(policy).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn set_liquidation_policy(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(policy): Json<LiquidationPolicy>,
+) -> crate::types::Result<impl IntoResponse> {
+    let policy = state.engine.liquidations().set_policy(account_id, policy)?;
+    info!("Liquidation policy for {} set: {:?}", account_id, policy);
+    Ok(Json(policy))
+}
+
+pub async fn remove_liquidation_policy(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    match state.engine.liquidations().remove_policy(account_id) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+pub async fn get_reference_price(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.reference_price(&symbol)?))
+}
+
+pub async fn get_matrix_price(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.matrix_price(&symbol)?))
+}
+
+pub async fn get_benchmark_curve(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.benchmark_curve())
+}
+
+/// An empty curve reverts to the one built from government securities.
+pub async fn set_benchmark_curve(
+    State(state): State<AppState>,
+    Json(points): Json<Vec<CurvePoint>>,
+) -> crate::types::Result<impl IntoResponse> {
+    if points.iter().any(|point| point.tenor_years < Decimal::ZERO) {
+        return Err(TradingError::InvalidRequest("Curve tenors must not be negative".to_string()));
+    }
+    state.engine.pricing_matrix().set_curve(points);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_spread_grid(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.pricing_matrix().spreads())
+}
+
+pub async fn set_spread_grid(State(state): State<AppState>, Json(grid): Json<SpreadGrid>) -> impl IntoResponse {
+    state.engine.pricing_matrix().set_spreads(grid);
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReferenceObservationRequest {
+    pub price: Decimal,
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+pub async fn set_previous_close(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<ReferenceObservationRequest>,
+) -> impl IntoResponse {
+    let as_of = request.as_of.unwrap_or_else(|| state.engine.time_provider().now());
+    state
+        .engine
+        .reference_prices()
+        .set_previous_close(&symbol, request.price, as_of);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn get_feed_health(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.feed_monitor().health(state.engine.time_provider().now()))
+}
+
+pub async fn set_external_reference_price(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<ReferenceObservationRequest>,
+) -> impl IntoResponse {
+    let as_of = request.as_of.unwrap_or_else(|| state.engine.time_provider().now());
+    state
+        .engine
+        .reference_prices()
+        .set_external(&symbol, request.price, as_of);
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountTierRequest {
+    pub tier: AccountTier,
+}
+
+pub async fn set_account_tier(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<AccountTierRequest>,
+) -> impl IntoResponse {
+    state.engine.fee_engine().set_account_tier(account_id, request.tier);
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountFirmRequest {
+    pub firm_id: Option<String>,
+}
+
+pub async fn set_account_firm(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<AccountFirmRequest>,
+) -> impl IntoResponse {
+    state.engine.firms().set_firm(account_id, request.firm_id);
+    StatusCode::NO_CONTENT
+}
+
+/// Dry-run report of the offsetting trades compression would book.
+pub async fn get_compression_proposal(
+    State(state): State<AppState>,
+    Path(firm_id): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.compress_positions(&firm_id, false).await?))
+}
+
+pub async fn execute_compression(
+    State(state): State<AppState>,
+    Path(firm_id): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.compress_positions(&firm_id, true).await?))
+}
+
+pub async fn get_instruments(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.instruments().list())
+}
+
+pub async fn get_symbol_aliases(State(state): State<AppState>, Symbol(symbol): Symbol) -> impl IntoResponse {
+    Json(state.engine.instruments().aliases(&symbol))
+}
+
+pub async fn add_symbol_alias(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<AliasRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let alias = state.engine.instruments().add_alias(&symbol, request)?;
+    Ok((StatusCode::CREATED, Json(alias)))
+}
+
+pub async fn remove_symbol_alias(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.instruments().remove_alias(&alias)?))
+}
+
+pub async fn get_instrument(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    state
+        .engine
+        .instruments()
+        .instrument(&symbol)
+        .map(Json)
+        .ok_or(TradingError::InstrumentNotFound(symbol))
+}
+
+pub async fn get_fixings(
+    State(state): State<AppState>,
+    Path(index): Path<String>,
+) -> impl IntoResponse {
+    Json(state.engine.fixings().history(&index))
+}
+
+pub async fn record_fixing(
+    State(state): State<AppState>,
+    Path(index): Path<String>,
+    Json(fixing): Json<Fixing>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.record_fixing(&index, fixing)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_index_levels(
+    State(state): State<AppState>,
+    Path(index): Path<String>,
+) -> impl IntoResponse {
+    Json(state.engine.inflation_indices().history(&index))
+}
+
+pub async fn record_index_level(
+    State(state): State<AppState>,
+    Path(index): Path<String>,
+    Json(level): Json<IndexLevel>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.record_index_level(&index, level)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexRatioQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+pub async fn get_index_ratios(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Query(query): Query<IndexRatioQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.index_ratios(&symbol, query.from, query.to)?))
+}
+
+pub async fn get_market_data(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.market_data(&symbol)?))
+}
+
+pub async fn get_symbol_stats(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.symbol_stats(&symbol)?))
+}
+
+pub async fn get_market_summary(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.market_summary())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    /// In the instrument's quoting convention.
+    pub price: Option<Decimal>,
+}
+
+pub async fn get_bond_analytics(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Query(query): Query<AnalyticsQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.bond_analytics(&symbol, query.price)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListInstrumentRequest {
+    #[serde(flatten)]
+    pub bond: Bond,
+    #[serde(default)]
+    pub matching_algorithm: MatchingAlgorithm,
+    pub tick_scale: Option<TickScale>,
+}
+
+pub async fn list_instrument(
+    State(state): State<AppState>,
+    Json(request): Json<ListInstrumentRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let mut instrument = state
+        .engine
+        .list_instrument(request.bond, request.matching_algorithm)?;
+    if let Some(tick_scale) = request.tick_scale {
+        instrument = state.engine.set_tick_scale(&instrument.bond.symbol, tick_scale)?;
+    }
+    Ok((StatusCode::CREATED, Json(instrument)))
+}
+
+pub async fn set_tick_scale(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(tick_scale): Json<TickScale>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.set_tick_scale(&symbol, tick_scale)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FirmPreferenceRequest {
+    pub firm_preference: FirmPreference,
+}
+
+pub async fn set_firm_preference(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<FirmPreferenceRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.set_firm_preference(&symbol, request.firm_preference)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatchingAlgorithmRequest {
+    pub matching_algorithm: MatchingAlgorithm,
+}
+
+pub async fn set_matching_algorithm(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<MatchingAlgorithmRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(
+        state
+            .engine
+            .set_matching_algorithm(&symbol, request.matching_algorithm)?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuspendInstrumentRequest {
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub cancel_resting: bool,
+}
+
+pub async fn suspend_instrument(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<SuspendInstrumentRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let (instrument, cancelled_orders) = state
+        .engine
+        .suspend_instrument(&symbol, request.reason, request.cancel_resting)
+        .await?;
+    Ok(Json(json!({
+        "instrument": instrument,
+        "cancelled_orders": cancelled_orders,
+    })))
+}
+
+pub async fn resume_instrument(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.resume_instrument(&symbol)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DelistInstrumentRequest {
+    pub reason: Option<String>,
+}
+
+pub async fn delist_instrument(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<DelistInstrumentRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.delist_instrument(&symbol, request.reason)?))
+}
+
+pub async fn schedule_corporate_action(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+    Json(request): Json<CorporateActionRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let action = state.engine.schedule_corporate_action(&symbol, request).await?;
+    Ok((StatusCode::CREATED, Json(action)))
+}
+
+pub async fn get_corporate_actions(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    if state.engine.instruments().status(&symbol).is_none() {
+        return Err(TradingError::InstrumentNotFound(symbol));
+    }
+    Ok(Json(state.engine.corporate_actions().history(&symbol)))
+}
+
+pub async fn get_instrument_archive(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    state
+        .engine
+        .get_instrument_archive(&symbol)
+        .map(Json)
+        .ok_or(TradingError::InstrumentNotFound(symbol))
+}
+
+pub async fn start_auction(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.start_auction(&symbol)?))
+}
+
+pub async fn uncross_auction(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.uncross_auction(&symbol).await?))
+}
+
+pub async fn get_auction_indicative(
+    State(state): State<AppState>,
+    Symbol(symbol): Symbol,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.get_auction_indicative(&symbol)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LendableInventoryRequest {
+    pub account_id: Uuid,
+    pub symbol: String,
+    pub lendable: Decimal,
+}
+
+pub async fn get_lending_inventory(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.lending().inventory(filter.account_id))
+}
+
+pub async fn set_lendable_inventory(
+    State(state): State<AppState>,
+    Json(request): Json<LendableInventoryRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.lending().set_lendable(
+        request.account_id,
+        &request.symbol,
+        request.lendable,
+    )?))
+}
+
+pub async fn get_loan_contracts(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.lending().contracts(filter.account_id))
+}
+
+pub async fn create_loan(
+    State(state): State<AppState>,
+    Json(request): Json<LoanRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let contract = state.engine.lending().create_loan(request)?;
+    Ok((StatusCode::CREATED, Json(contract)))
+}
+
+pub async fn return_loan(
+    State(state): State<AppState>,
+    Path(contract_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.lending().return_loan(contract_id)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettlementInstructionQuery {
+    pub account_id: Option<Uuid>,
+    pub status: Option<InstructionStatus>,
+}
+
+pub async fn get_settlement_instructions(
+    State(state): State<AppState>,
+    Query(query): Query<SettlementInstructionQuery>,
+) -> impl IntoResponse {
+    Json(state.engine.settlement_exporter().list(query.account_id, query.status))
+}
+
+pub async fn export_settlement_instructions(
+    State(state): State<AppState>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.export_settlement_instructions().await?))
+}
+
+pub async fn get_settlement_fails(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.settlement_fails().list(filter.account_id))
+}
+
+pub async fn mark_settlement_failed(
+    State(state): State<AppState>,
+    Json(request): Json<FailRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let fail = state.engine.mark_settlement_failed(request)?;
+    Ok((StatusCode::CREATED, Json(fail)))
+}
+
+pub async fn get_fails_aging(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.settlement_fails().aging())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettleFailRequest {
+    pub quantity: Decimal,
+}
+
+pub async fn settle_failed_quantity(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<SettleFailRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.settle_failed_quantity(id, request.quantity)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuyInRequest {
+    pub price: Decimal,
+}
+
+pub async fn complete_buy_in(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<BuyInRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.complete_buy_in(id, request.price)?))
+}
+
+pub async fn get_confirmations(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.confirmations().list(filter.account_id))
+}
+
+pub async fn get_confirmation(
+    State(state): State<AppState>,
+    Path(trade_id): Path<Uuid>,
+) -> crate::types::Result<impl IntoResponse> {
+    let confirmation = state
+        .engine
+        .confirmations()
+        .get(trade_id)
+        .ok_or_else(|| TradingError::InvalidRequest(format!("No confirmation for trade {}", trade_id)))?;
+    Ok(Json(confirmation))
+}
+
+pub async fn affirm_trade(
+    State(state): State<AppState>,
+    Path(trade_id): Path<Uuid>,
+    Json(request): Json<AffirmRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.affirm_trade(trade_id, request)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmationExceptionQuery {
+    pub status: Option<ExceptionStatus>,
+}
+
+pub async fn get_confirmation_exceptions(
+    State(state): State<AppState>,
+    Query(query): Query<ConfirmationExceptionQuery>,
+) -> impl IntoResponse {
+    Json(state.engine.confirmations().exceptions(query.status))
+}
+
+pub async fn resolve_confirmation_exception(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(resolution): Json<ExceptionResolution>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.resolve_confirmation_exception(id, resolution)?))
+}
+
+pub async fn get_credit_lines(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.credit_lines().lines(account_id))
+}
+
+pub async fn set_credit_line(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<CreditLineRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.credit_lines().set_line(account_id, request)?))
+}
+
+pub async fn remove_credit_line(
+    State(state): State<AppState>,
+    Path((account_id, counterparty_account_id)): Path<(Uuid, Uuid)>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.credit_lines().remove_line(account_id, counterparty_account_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_cash_balances(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.cash().balances(Some(account_id)))
+}
+
+pub async fn post_cash_movement(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(movement): Json<CashMovement>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.cash().post_movement(account_id, movement)?))
+}
+
+pub async fn get_cash_entries(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.cash().entries(Some(account_id)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CashAccountTypeRequest {
+    pub account_type: CashAccountType,
+}
+
+pub async fn set_cash_account_type(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<CashAccountTypeRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    state.engine.cash().set_account_type(account_id, request.account_type)?;
+    Ok(Json(state.engine.cash().balances(Some(account_id))))
+}
+
+pub async fn get_interest_rates(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.cash().rates())
+}
+
+pub async fn set_interest_rate(
+    State(state): State<AppState>,
+    Json(rate): Json<InterestRate>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.cash().set_rate(rate)?))
+}
+
+pub async fn get_deliverable_quantity(
+    State(state): State<AppState>,
+    Path((account_id, symbol)): Path<(Uuid, String)>,
+) -> impl IntoResponse {
+    let symbol = state.engine.instruments().canonical(&symbol);
+    let deliverable = state.engine.deliverable_quantity(account_id, &symbol).await;
+    Json(json!({
+        "account_id": account_id,
+        "symbol": symbol,
+        "deliverable_quantity": deliverable,
+    }))
+}
+
+pub async fn get_clearing_exposures(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.clearing().exposures())
+}
+
+pub async fn get_member_exposure(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+) -> impl IntoResponse {
+    Json(state.engine.clearing().member_exposure(account_id))
+}
+
+pub async fn get_clearing_legs(
+    State(state): State<AppState>,
+    Query(filter): Query<AccountFilter>,
+) -> impl IntoResponse {
+    Json(state.engine.clearing().legs(filter.account_id))
+}
+
+pub async fn get_default_fund(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.clearing().default_fund())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExposureLimitRequest {
+    pub limit: Decimal,
+}
+
+pub async fn set_member_exposure_limit(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Json(request): Json<ExposureLimitRequest>,
+) -> impl IntoResponse {
+    state.engine.clearing().set_exposure_limit(account_id, request.limit);
+    StatusCode::NO_CONTENT
+}
+
+pub async fn get_strategies(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.engine.strategies().list())
+}
+
+pub async fn register_strategy(
+    State(state): State<AppState>,
+    Json(request): Json<StrategyRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    let strategy = state.engine.strategies().register(request)?;
+    Ok((StatusCode::CREATED, Json(strategy)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StrategyStatusRequest {
+    pub active: bool,
+}
+
+pub async fn set_strategy_status(
+    State(state): State<AppState>,
+    Path(strategy_id): Path<String>,
+    Json(request): Json<StrategyStatusRequest>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.strategies().set_active(&strategy_id, request.active)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PnlQuery {
+    #[serde(default)]
+    pub group_by: PnlGrouping,
+    pub account_id: Option<Uuid>,
+}
+
+pub async fn get_pnl(
+    State(state): State<AppState>,
+    Query(query): Query<PnlQuery>,
+) -> impl IntoResponse {
+    Json(state.engine.pnl().summarize(query.group_by, query.account_id))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PnlTimeseriesQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Bucket width in seconds.
+    pub interval: Option<i64>,
+}
+
+pub async fn get_pnl_timeseries(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    Query(query): Query<PnlTimeseriesQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    let interval = query.interval.map(chrono::Duration::seconds);
+    Ok(Json(
+        state
+            .engine
+            .pnl_timeseries(account_id, query.from, query.to, interval)
+            .await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReconcileQuery {
+    #[serde(default)]
+    pub apply: bool,
+}
+
+pub async fn reconcile_state(
+    State(state): State<AppState>,
+    Query(query): Query<ReconcileQuery>,
+) -> crate::types::Result<impl IntoResponse> {
+    if query.apply && !state.config.reconcile_apply_enabled {
+        return Err(TradingError::InvalidRequest(
+            "Reconciliation apply mode is disabled; set RECONCILE_APPLY_ENABLED to enable it".to_string(),
+        ));
+    }
+    Ok(Json(state.engine.reconcile(query.apply).await?))
+}
+
+pub async fn get_eod_reconciliation(
+    State(state): State<AppState>,
+    Path(date): Path<NaiveDate>,
+) -> crate::types::Result<impl IntoResponse> {
+    Ok(Json(state.engine.eod_reconciliation(date).await?))
+}
+
+pub async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state, query))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: AppState, query: EventsQuery) {
+    // Subscribe before replaying so nothing published in between is lost;
+    // anything already replayed is skipped on the live stream by sequence.
+    let mut receiver = state.engine.subscribe_events();
+    let mut last_seq = match query.from_seq {
+        Some(from_seq) => from_seq.saturating_sub(1),
+        None => state.engine.last_event_seq(),
+    };
+
+    if query.from_seq.is_some()
+        && replay_missed(&mut socket, &state, query.account_id, &mut last_seq).await.is_err()
+    {
+        return;
+    }
+
+    // Order entry shares the connection; once logged on, the stream
+    // narrows to the session's account unless a filter was given.
+    let mut session = OrderEntrySession::default();
+    loop {
+        let account_id = query.account_id.or(session.account_id());
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) => {
+                    if event.seq <= last_seq {
+                        continue;
+                    }
+                    last_seq = event.seq;
+                    if account_id.is_some_and(|id| !event.visible_to(id)) {
+                        continue;
+                    }
+                    if send_json(&mut socket, &event).await.is_err() {
+                        debug!("WebSocket client disconnected");
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    // Fill the gap from the journal rather than dropping events.
+                    warn!("WebSocket subscriber lagged by {} events, replaying", skipped);
+                    if replay_missed(&mut socket, &state, account_id, &mut last_seq).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            inbound = socket.recv() => match inbound {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = session.handle(&state, &text).await;
+                    if send_json(&mut socket, &reply).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    debug!("WebSocket client disconnected");
+                    break;
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuctionStreamQuery {
+    pub symbol: Option<String>,
+}
+
+/// Indicative price updates only, optionally for a single symbol.
+pub async fn auction_websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<AuctionStreamQuery>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_auction_socket(socket, state, query))
+}
+
+async fn handle_auction_socket(mut socket: WebSocket, state: AppState, query: AuctionStreamQuery) {
+    let mut receiver = state.engine.subscribe_events();
+
+    if let Some(symbol) = &query.symbol {
+        if let Ok(indicative) = state.engine.get_auction_indicative(symbol) {
+            if send_json(&mut socket, &indicative).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                let EngineEvent::AuctionIndicative(indicative) = event.event else {
+                    continue;
+                };
+                if query.symbol.as_ref().is_some_and(|symbol| *symbol != indicative.symbol) {
+                    continue;
+                }
+                if send_json(&mut socket, &indicative).await.is_err() {
+                    debug!("Auction WebSocket client disconnected");
+                    break;
+                }
+            }
+            // Indicatives supersede each other, so a lagging client just
+            // picks up from the next one.
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Auction subscriber lagged by {} events", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BookStreamQuery {
+    pub symbol: String,
+    #[serde(default)]
+    pub feed: FeedFlavor,
+    pub depth: Option<usize>,
+    pub batch_ms: Option<u64>,
+    #[serde(default)]
+    pub conflate: bool,
+    #[serde(default)]
+    pub compression: FeedCompression,
+    pub snapshot_ms: Option<u64>,
+}
+
+/// Incremental book updates for one symbol, each carrying a checksum of
+/// the top levels, by price level or with `feed=mbo` by order. With
+/// `snapshot_ms`, periodic snapshots and trade summaries instead, for
+/// dashboards that do not need every change.
+pub async fn book_websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<BookStreamQuery>,
+) -> Response {
+    let depth = query
+        .depth
+        .unwrap_or(state.config.book_feed_depth)
+        .clamp(1, state.config.book_feed_depth);
+    let symbol = state.engine.instruments().canonical(&query.symbol);
+    let settings = FeedSettings {
+        flavor: query.feed,
+        batch_ms: query.batch_ms.unwrap_or_default(),
+        conflate: query.conflate,
+        compression: query.compression,
+        snapshot_ms: query.snapshot_ms.unwrap_or_default(),
+    };
+    ws.on_upgrade(move |socket| handle_book_socket(socket, state, BookFeed::new(symbol, depth), settings))
+}
+
+/// Holds requested book feed settings to the configured minimum windows.
+fn book_feed_settings(config: &crate::config::Config, requested: FeedSettings) -> FeedSettings {
+    FeedSettings {
+        batch_ms: requested.batch_ms.max(config.book_feed_batch_ms),
+        snapshot_ms: match requested.snapshot_ms {
+            0 => 0,
+            interval => interval.max(config.book_feed_min_snapshot_ms),
+        },
+        ..requested
+    }
+}
+
+/// The books as the connection's flavor carries them.
+fn read_book(engine: &TradingEngine, feed: &BookFeed, flavor: FeedFlavor) -> BookRead {
+    let (book_sequence, content) = match flavor {
+        FeedFlavor::Mbp => {
+            let (book_sequence, book) = engine.quoted_orderbook_at(feed.symbol());
+            (book_sequence, BookContent::Levels(book))
+        }
+        FeedFlavor::Mbo => {
+            let (book_sequence, (bids, asks)) = engine.quoted_resting_orders(feed.symbol(), feed.depth());
+            (book_sequence, BookContent::Orders { bids, asks })
+        }
+    };
+    BookRead { book_sequence, content }
+}
+
+/// Ticks for a conflated feed's snapshots; none on the full-tick feed.
+fn book_snapshot_timer(settings: &FeedSettings) -> Option<tokio::time::Interval> {
+    (settings.snapshot_ms > 0).then(|| {
+        let period = std::time::Duration::from_millis(settings.snapshot_ms);
+        let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        timer
+    })
+}
+
+async fn handle_book_socket(mut socket: WebSocket, state: AppState, mut feed: BookFeed, settings: FeedSettings) {
+    let full_depth = feed.depth();
+    let mut settings = book_feed_settings(&state.config, settings);
+    let mut snapshot_timer = book_snapshot_timer(&settings);
+    let mut receiver = state.engine.subscribe_events();
+    let snapshot = feed.snapshot(read_book(&state.engine, &feed, settings.flavor));
+    if send_book_message(&mut socket, &snapshot, settings.compression).await.is_err() {
+        return;
+    }
+
+    // When the changes since the last update go out; unset while the
+    // client is up to date.
+    let mut flush_at: Option<tokio::time::Instant> = None;
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) => {
+                    // Conflated feeds only need the symbol's trades between snapshots
+                    if snapshot_timer.is_some() {
+                        if let EngineEvent::TradeExecuted(trade) = &event.event {
+                            if trade.symbol == feed.symbol() {
+                                feed.record_trade(trade.quantity, trade.price);
+                            }
+                        }
+                        continue;
+                    }
+                    if matches!(
+                        event.event,
+                        EngineEvent::PositionUpdated(_)
+                            | EngineEvent::RiskViolation { .. }
+                            | EngineEvent::RiskWarning { .. }
+                            | EngineEvent::RiskOverrideGranted(_)
+                            | EngineEvent::RiskOverrideRevoked(_)
+                            | EngineEvent::OrderRejected { .. }
+                            | EngineEvent::AuctionIndicative(_)
+                            | EngineEvent::CorporateActionApplied(_)
+                            | EngineEvent::ClockQualityChanged(_)
+                            | EngineEvent::OrderSignatureVerified { .. }
+                            | EngineEvent::DealerHitUpdated(_)
+                            | EngineEvent::ImprovementAuctionUpdated(_)
+                            | EngineEvent::IndexValueUpdated(_)
+                            | EngineEvent::BenchmarkOrderUpdated(_)
+                            | EngineEvent::OrderApprovalUpdated(_)
+                            | EngineEvent::ConditionalOrderUpdated(_)
+                            | EngineEvent::Liquidation(_)
+                    ) {
+                        continue;
+                    }
+                    flush_at.get_or_insert_with(|| {
+                        tokio::time::Instant::now() + std::time::Duration::from_millis(settings.batch_ms)
+                    });
+                }
+                // Updates are diffs against what the client was last sent,
+                // so the next one covers anything skipped. A client that
+                // asked for conflation is cut back to top of book.
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Book subscriber lagged by {} events", skipped);
+                    if settings.conflate && feed.depth() > 1 {
+                        info!("Conflating book feed for {} to top of book", feed.symbol());
+                        feed.set_depth(1);
+                        let snapshot = feed.snapshot(read_book(&state.engine, &feed, settings.flavor));
+                        if send_book_message(&mut socket, &snapshot, settings.compression).await.is_err() {
+                            break;
+                        }
+                        flush_at = None;
+                    } else {
+                        flush_at.get_or_insert_with(tokio::time::Instant::now);
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = tokio::time::sleep_until(flush_at.unwrap_or_else(tokio::time::Instant::now)), if flush_at.is_some() => {
+                flush_at = None;
+                if let Some(update) = feed.update(read_book(&state.engine, &feed, settings.flavor)) {
+                    if send_book_message(&mut socket, &update, settings.compression).await.is_err() {
+                        debug!("Book WebSocket client disconnected");
+                        break;
+                    }
+                }
+            }
+            _ = async { snapshot_timer.as_mut().expect("timer checked by precondition").tick().await },
+                if snapshot_timer.is_some() =>
+            {
+                let book = read_book(&state.engine, &feed, settings.flavor);
+                let messages: Vec<BookFeedMessage> =
+                    feed.changed_snapshot(book).into_iter().chain(feed.trade_summary()).collect();
+                if send_book_messages(&mut socket, &messages, settings.compression).await.is_err() {
+                    debug!("Book WebSocket client disconnected");
+                    break;
+                }
+            }
+            inbound = socket.recv() => match inbound {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str(&text) {
+                        Ok(BookFeedRequest::ChecksumMismatch { sequence, checksum }) => warn!(
+                            "Book client reported checksum mismatch on {} at sequence {:?} (checksum {:?}), resyncing",
+                            feed.symbol(),
+                            sequence,
+                            checksum
+                        ),
+                        Ok(BookFeedRequest::Configure(requested)) => {
+                            settings = book_feed_settings(&state.config, requested);
+                            snapshot_timer = book_snapshot_timer(&settings);
+                            feed.set_depth(full_depth);
+                        }
+                        Err(_) => continue,
+                    }
+                    let snapshot = feed.snapshot(read_book(&state.engine, &feed, settings.flavor));
+                    if send_book_message(&mut socket, &snapshot, settings.compression).await.is_err() {
+                        break;
+                    }
+                    flush_at = None;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    debug!("Book WebSocket client disconnected");
+                    break;
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send_book_messages(
+    socket: &mut WebSocket,
+    messages: &[BookFeedMessage],
+    compression: FeedCompression,
+) -> std::result::Result<(), axum::Error> {
+    for message in messages {
+        send_book_message(socket, message, compression).await?;
+    }
+    Ok(())
+}
+
+/// Sends a book feed message as JSON text, or deflated in a binary frame.
+async fn send_book_message(
+    socket: &mut WebSocket,
+    message: &BookFeedMessage,
+    compression: FeedCompression,
+) -> std::result::Result<(), axum::Error> {
+    match compression {
+        FeedCompression::None => send_json(socket, message).await,
+        FeedCompression::Deflate => {
+            match serde_json::to_vec(message).map_err(std::io::Error::from).and_then(|json| book_feed::deflate(&json)) {
+                Ok(frame) => socket.send(Message::Binary(frame)).await,
+                Err(e) => {
+                    warn!("Failed to encode book feed message: {}", e);
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValuationStreamQuery {
+    pub account_id: Uuid,
+    pub interval_ms: Option<u64>,
+}
+
+/// Portfolio valuation for one account, sent on connect and then at most
+/// once per interval whenever a figure changes.
+pub async fn valuation_websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<ValuationStreamQuery>,
+) -> Response {
+    let interval_ms = query
+        .interval_ms
+        .unwrap_or(state.config.valuation_stream_interval_ms)
+        .max(state.config.valuation_stream_interval_ms)
+        .max(1);
+    ws.on_upgrade(move |socket| {
+        handle_valuation_socket(socket, state, query.account_id, std::time::Duration::from_millis(interval_ms))
+    })
+}
+
+async fn handle_valuation_socket(mut socket: WebSocket, state: AppState, account_id: Uuid, period: std::time::Duration) {
+    let mut last = state.engine.value_portfolio(account_id).await;
+    if send_json(&mut socket, &last).await.is_err() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let valuation = state.engine.value_portfolio(account_id).await;
+                if valuation.same_values(&last) {
+                    continue;
+                }
+                if send_json(&mut socket, &valuation).await.is_err() {
+                    debug!("Valuation WebSocket client disconnected");
+                    break;
+                }
+                last = valuation;
+            }
+            inbound = socket.recv() => match inbound {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    debug!("Valuation WebSocket client disconnected");
+                    break;
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn replay_missed(
+    socket: &mut WebSocket,
+    state: &AppState,
+    account_id: Option<Uuid>,
+    last_seq: &mut u64,
+) -> std::result::Result<(), axum::Error> {
+    let replay = state.engine.replay_events(*last_seq + 1, account_id, usize::MAX);
+
+    if replay.truncated {
+        let notice = json!({
+            "type": "ReplayTruncated",
+            "requested_seq": *last_seq + 1,
+            "oldest_available_seq": replay.oldest_available_seq,
+        });
+        socket.send(Message::Text(notice.to_string())).await?;
+    }
+
+    for event in &replay.events {
+        send_json(socket, event).await?;
+    }
+    *last_seq = (*last_seq).max(replay.next_seq.saturating_sub(1));
+
+    Ok(())
+}
+
+async fn send_json<T: serde::Serialize>(socket: &mut WebSocket, value: &T) -> std::result::Result<(), axum::Error> {
+    match serde_json::to_string(value) {
+        Ok(text) => socket.send(Message::Text(text)).await,
+        Err(e) => {
+            warn!("Failed to serialize WebSocket message: {}", e);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod binary_entry;
+pub mod book_feed;
+pub mod graphql;
+pub mod handlers;
+pub mod notifier;
+pub mod order_entry;
+pub mod snapshots;
+pub mod sse;
@@ -0,0 +1,262 @@
+use crate::{
+    config::Config,
+    engine::{
+        event_journal::SequencedEvent,
+        notifications::{DeliveryStatus, DeliveryTarget, Notification, NotificationSubscription},
+        webhooks::{self, WebhookEndpoint, WebhookEventType, WebhookPayload},
+        TradingEngine,
+    },
+};
+use chrono::Utc;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Delivery loop: matches engine events against notification
+/// subscriptions and webhooks and sends each match on its own task, so a
+/// slow target holds up nobody else.
+pub async fn run(engine: Arc<TradingEngine>, config: Arc<Config>) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_millis(config.notification_timeout_ms))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Notifications disabled, HTTP client failed to start: {}", e);
+            return;
+        }
+    };
+    let mut events = engine.subscribe_events();
+
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                for (subscription, kind) in engine.notifications().matching(&event) {
+                    let record = engine
+                        .notifications()
+                        .record_pending(&subscription, kind, event.seq, engine.time_provider().now());
+                    let notification = Notification {
+                        delivery_id: record.id,
+                        subscription_id: subscription.id,
+                        kind,
+                        seq: event.seq,
+                        timestamp: event.timestamp,
+                        event: event.event.clone(),
+                    };
+                    tokio::spawn(deliver(
+                        engine.clone(),
+                        config.clone(),
+                        client.clone(),
+                        subscription,
+                        notification,
+                    ));
+                }
+                for (endpoint, event_type) in engine.webhooks().matching(&event) {
+                    tokio::spawn(send_webhook(
+                        engine.clone(),
+                        config.clone(),
+                        client.clone(),
+                        endpoint,
+                        event_type,
+                        event.clone(),
+                    ));
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Notifier lagged, {} events were not checked for notifications", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Sends one notification, retrying failures with exponential backoff up
+/// to the configured number of attempts.
+pub async fn deliver(
+    engine: Arc<TradingEngine>,
+    config: Arc<Config>,
+    client: reqwest::Client,
+    subscription: NotificationSubscription,
+    notification: Notification,
+) {
+    let notifications = engine.notifications();
+    let url = match &subscription.target {
+        DeliveryTarget::Webhook { url } => url,
+        DeliveryTarget::Email { address } => {
+            info!(
+                "No mail transport, skipping {:?} notification {} for {}",
+                notification.kind, notification.delivery_id, address
+            );
+            notifications.record_attempt(notification.delivery_id, DeliveryStatus::Skipped, None, engine.time_provider().now());
+            return;
+        }
+    };
+
+    let body = match serde_json::to_string(&notification) {
+        Ok(body) => body,
+        Err(e) => {
+            let error = Some(e.to_string());
+            notifications.record_attempt(notification.delivery_id, DeliveryStatus::Failed, error, engine.time_provider().now());
+            return;
+        }
+    };
+
+    let delivered = post_with_retry(&config, || client.post(url).body(body.clone()), |result, last| {
+        let (status, error) = match result {
+            Ok(()) => (DeliveryStatus::Delivered, None),
+            Err(e) if last => (DeliveryStatus::Failed, Some(e.to_string())),
+            Err(e) => (DeliveryStatus::Pending, Some(e.to_string())),
+        };
+        notifications.record_attempt(notification.delivery_id, status, error, engine.time_provider().now());
+    })
+    .await;
+    if !delivered {
+        warn!("Notification {} to {} failed after all attempts", notification.delivery_id, url);
+    }
+}
+
+/// Sends one signed webhook request, with the same retry schedule as
+/// notifications. Every attempt is signed afresh with its own timestamp.
+pub async fn send_webhook(
+    engine: Arc<TradingEngine>,
+    config: Arc<Config>,
+    client: reqwest::Client,
+    endpoint: WebhookEndpoint,
+    event_type: WebhookEventType,
+    event: SequencedEvent,
+) {
+    let payload = WebhookPayload {
+        delivery_id: Uuid::new_v4(),
+        webhook_id: endpoint.id,
+        event_type,
+        seq: event.seq,
+        timestamp: event.timestamp,
+        data: event.event,
+    };
+    let body = match serde_json::to_string(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to serialize webhook payload: {}", e);
+            return;
+        }
+    };
+
+    let metrics = engine.get_metrics();
+    let request = || {
+        // Signed with the wall clock, which is what receivers check against
+        let timestamp = Utc::now().timestamp();
+        client
+            .post(&endpoint.url)
+            .header(webhooks::TIMESTAMP_HEADER, timestamp)
+            .header(webhooks::WEBHOOK_ID_HEADER, endpoint.id.to_string())
+            .header(
+                webhooks::SIGNATURE_HEADER,
+                format!("sha256={}", webhooks::sign(&endpoint.secret, timestamp, &body)),
+            )
+            .body(body.clone())
+    };
+    let delivered = post_with_retry(&config, request, |result, last| {
+        let error = result.err().map(|e| e.to_string());
+        match (&error, last) {
+            (None, _) => metrics.increment_webhook_deliveries(),
+            (Some(_), false) => metrics.increment_webhook_retries(),
+            (Some(_), true) => metrics.increment_webhook_failures(),
+        }
+        engine.webhooks().record_attempt(endpoint.id, error, last, engine.time_provider().now());
+    })
+    .await;
+    if !delivered {
+        warn!(
+            "Webhook {} delivery {} to {} failed after all attempts",
+            endpoint.id, payload.delivery_id, endpoint.url
+        );
+    }
+}
+
+/// POSTs a JSON request built by `request` until it gets a success
+/// status, sleeping `notification_retry_base_ms` before the first retry and
+/// doubling the wait each time after. `on_attempt` sees every outcome and
+/// whether it was the last attempt. Returns whether a request succeeded.
+pub async fn post_with_retry(
+    config: &Config,
+    request: impl Fn() -> reqwest::RequestBuilder,
+    mut on_attempt: impl FnMut(Result<(), &reqwest::Error>, bool),
+) -> bool {
+    let max_attempts = config.notification_max_attempts.max(1);
+    let mut delay = Duration::from_millis(config.notification_retry_base_ms);
+    for attempt in 1..=max_attempts {
+        let result = request()
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        let last = attempt == max_attempts;
+        match &result {
+            Ok(_) => {
+                on_attempt(Ok(()), last);
+                return true;
+            }
+            Err(e) => on_attempt(Err(e), last),
+        }
+        if !last {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::notifications::{NotificationFilters, NotificationKind, SubscriptionRequest};
+    use crate::engine::EngineEvent;
+    use crate::types::CancelReason;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_unreachable_webhook_is_retried_then_marked_failed() {
+        let config = Arc::new(Config {
+            notification_max_attempts: 3,
+            notification_retry_base_ms: 1,
+            ..Config::default()
+        });
+        let engine = Arc::new(TradingEngine::new(config.clone()).await.unwrap());
+        let subscription = engine
+            .subscribe_notifications(SubscriptionRequest {
+                user_id: Uuid::new_v4(),
+                account_id: Uuid::new_v4(),
+                // Nothing listens on the discard port
+                target: DeliveryTarget::Webhook {
+                    url: "http://127.0.0.1:9/hook".to_string(),
+                },
+                filters: NotificationFilters {
+                    session_events: true,
+                    ..NotificationFilters::default()
+                },
+            })
+            .await
+            .unwrap();
+
+        let record = engine
+            .notifications()
+            .record_pending(&subscription, NotificationKind::SessionEvent, 1, Utc::now());
+        let notification = Notification {
+            delivery_id: record.id,
+            subscription_id: subscription.id,
+            kind: NotificationKind::SessionEvent,
+            seq: 1,
+            timestamp: Utc::now(),
+            event: EngineEvent::OrderCancelled {
+                order_id: Uuid::new_v4(),
+                cancel_reason: CancelReason::ClientRequest,
+            },
+        };
+        deliver(engine.clone(), config, reqwest::Client::new(), subscription, notification).await;
+
+        let record = engine.notifications().delivery(record.id).unwrap();
+        assert_eq!((record.status, record.attempts), (DeliveryStatus::Failed, 3));
+        assert!(record.last_error.is_some());
+    }
+}
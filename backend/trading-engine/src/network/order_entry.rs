@@ -0,0 +1,228 @@
+use crate::{network::handlers::SubmitOrderRequest, types::*, AppState};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Inbound order-entry messages on `/ws`, and what binary order-entry
+/// frames decode to. Everything except `Logon`
+/// requires a logged-on session and may only touch that session's account.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    Logon {
+        api_key: String,
+    },
+    Submit {
+        request_id: String,
+        order: Box<SubmitOrderRequest>,
+    },
+    Cancel {
+        request_id: String,
+        order_id: Uuid,
+    },
+    Modify {
+        request_id: String,
+        order_id: Uuid,
+        quantity: Option<Decimal>,
+        price: Option<Decimal>,
+    },
+}
+
+/// Replies are sent before the connection reads its next message. `seq` is
+/// the journal sequence at the time of the reply: every event caused by the
+/// request has a sequence number at or below it.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    LogonAck {
+        account_id: Uuid,
+        seq: u64,
+    },
+    Ack {
+        request_id: String,
+        order_id: Uuid,
+        status: Option<OrderStatus>,
+        seq: u64,
+    },
+    Reject {
+        request_id: Option<String>,
+        reason: String,
+        reject_reason: RejectReason,
+        seq: u64,
+    },
+}
+
+/// Order-entry state of one WebSocket connection.
+#[derive(Debug, Default)]
+pub struct OrderEntrySession {
+    account_id: Option<Uuid>,
+}
+
+impl OrderEntrySession {
+    pub fn account_id(&self) -> Option<Uuid> {
+        self.account_id
+    }
+
+    pub async fn handle(&mut self, state: &AppState, text: &str) -> ServerMessage {
+        match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => self.handle_message(state, message).await,
+            Err(e) => {
+                let error = TradingError::InvalidRequest(format!("Malformed message: {}", e));
+                reject(state, None, error)
+            }
+        }
+    }
+
+    pub async fn handle_message(&mut self, state: &AppState, message: ClientMessage) -> ServerMessage {
+        match message {
+            ClientMessage::Logon { api_key } => match state.config.order_entry_api_keys.get(&api_key) {
+                Some(&account_id) => {
+                    self.account_id = Some(account_id);
+                    ServerMessage::LogonAck {
+                        account_id,
+                        seq: state.engine.last_event_seq(),
+                    }
+                }
+                None => reject(state, None, TradingError::Unauthorized("Unknown API key".to_string())),
+            },
+            ClientMessage::Submit { request_id, order } => {
+                let result = match self.authorize(order.account_id) {
+                    Ok(()) => {
+                        let (order, signature) = (*order).into_signed_order(state.engine.time_provider().now());
+                        state.engine.submit_signed_order(order, signature).await
+                    }
+                    Err(e) => Err(e),
+                };
+                respond(state, request_id, result)
+            }
+            ClientMessage::Cancel { request_id, order_id } => {
+                let result = self.cancel(state, order_id).await;
+                respond(state, request_id, result)
+            }
+            ClientMessage::Modify {
+                request_id,
+                order_id,
+                quantity,
+                price,
+            } => {
+                let result = match self.authorize_order(state, order_id) {
+                    Ok(()) => state.engine.modify_order(order_id, quantity, price).await,
+                    Err(e) => Err(e),
+                };
+                respond(state, request_id, result)
+            }
+        }
+    }
+
+    async fn cancel(&self, state: &AppState, order_id: Uuid) -> crate::types::Result<Uuid> {
+        self.authorize_order(state, order_id)?;
+        if state.engine.cancel_order(order_id).await? {
+            Ok(order_id)
+        } else {
+            Err(TradingError::InvalidOrder("Order is no longer open".to_string()))
+        }
+    }
+
+    fn authorize(&self, account_id: Uuid) -> crate::types::Result<()> {
+        match self.account_id {
+            None => Err(TradingError::InvalidRequest("Log on before entering orders".to_string())),
+            Some(session_account) if session_account != account_id => Err(TradingError::InvalidRequest(
+                "Session is not logged on for this account".to_string(),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn authorize_order(&self, state: &AppState, order_id: Uuid) -> crate::types::Result<()> {
+        let order = state
+            .engine
+            .get_order(&order_id)
+            .ok_or_else(|| TradingError::OrderNotFound(order_id.to_string()))?;
+        self.authorize(order.account_id)
+    }
+}
+
+fn respond(state: &AppState, request_id: String, result: crate::types::Result<Uuid>) -> ServerMessage {
+    match result {
+        Ok(order_id) => ServerMessage::Ack {
+            request_id,
+            order_id,
+            status: state.engine.get_order(&order_id).map(|order| order.status),
+            seq: state.engine.last_event_seq(),
+        },
+        Err(e) => reject(state, Some(request_id), e),
+    }
+}
+
+pub(crate) fn reject(state: &AppState, request_id: Option<String>, error: TradingError) -> ServerMessage {
+    ServerMessage::Reject {
+        request_id,
+        reason: error.to_string(),
+        reject_reason: error.reject_reason(),
+        seq: state.engine.last_event_seq(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::TradingEngine};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_session_enters_orders_only_after_logon() {
+        let account_id = Uuid::new_v4();
+        let mut config = Config::default();
+        config
+            .order_entry_api_keys
+            .insert("algo-key".to_string(), account_id);
+        let config = Arc::new(config);
+        let state = AppState::new(Arc::new(TradingEngine::new(config.clone()).await.unwrap()), config);
+
+        let submit = |account_id: Uuid| {
+            json!({
+                "type": "Submit",
+                "request_id": "r1",
+                "order": {
+                    "client_order_id": "ALGO-1",
+                    "symbol": "GSEC10Y",
+                    "side": "Buy",
+                    "order_type": "Limit",
+                    "quantity": "100000",
+                    "price": "99.00",
+                    "user_id": Uuid::new_v4(),
+                    "account_id": account_id,
+                    "time_in_force": null,
+                    "strategy_id": null,
+                }
+            })
+            .to_string()
+        };
+
+        let mut session = OrderEntrySession::default();
+        let reply = session.handle(&state, &submit(account_id)).await;
+        assert!(matches!(reply, ServerMessage::Reject { .. }));
+
+        let logon = json!({ "type": "Logon", "api_key": "algo-key" }).to_string();
+        assert!(matches!(session.handle(&state, &logon).await, ServerMessage::LogonAck { .. }));
+
+        let reply = session.handle(&state, &submit(Uuid::new_v4())).await;
+        assert!(matches!(reply, ServerMessage::Reject { .. }));
+
+        let ServerMessage::Ack { order_id, seq, .. } = session.handle(&state, &submit(account_id)).await else {
+            panic!("submit was not acknowledged");
+        };
+        assert_eq!(seq, state.engine.last_event_seq());
+
+        let modify = json!({ "type": "Modify", "request_id": "r2", "order_id": order_id, "price": "98.50" });
+        let ServerMessage::Ack { order_id: replacement, .. } = session.handle(&state, &modify.to_string()).await else {
+            panic!("modify was not acknowledged");
+        };
+        assert_eq!(state.engine.get_order(&order_id).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(
+            state.engine.get_order(&replacement).unwrap().price,
+            Some(Decimal::new(9850, 2))
+        );
+    }
+}
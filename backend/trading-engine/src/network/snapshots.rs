@@ -0,0 +1,86 @@
+//! Response bodies for hot endpoints: collections serialized straight from
+//! engine state, and order books serialized once per change to the books.
+
+use crate::{
+    engine::{accrued::BookVersion, TradingEngine},
+    types::TradingError,
+};
+use axum::{
+    body::Bytes,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::{Serialize, Serializer};
+
+/// A JSON array of `items`, written without collecting them first.
+pub fn json_seq<I>(items: I) -> Response
+where
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    let mut body = Vec::with_capacity(4096);
+    match serde_json::Serializer::new(&mut body).collect_seq(items) {
+        Ok(()) => json_response(Bytes::from(body)),
+        Err(e) => TradingError::InternalError(format!("Serialization failed: {}", e)).into_response(),
+    }
+}
+
+pub fn json_response(body: Bytes) -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+/// Serialized quoted order books, each reused until the books change or
+/// the day rolls over.
+#[derive(Default)]
+pub struct BookSnapshotCache {
+    books: DashMap<String, (BookVersion, Bytes)>,
+}
+
+impl BookSnapshotCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn orderbook(&self, engine: &TradingEngine, symbol: &str) -> Option<Bytes> {
+        // Read before the book so a change made meanwhile is not cached
+        // under this version
+        let version = engine.book_version();
+        if let Some(cached) = self.books.get(symbol).filter(|cached| cached.0 == version) {
+            return Some(cached.1.clone());
+        }
+        let body = Bytes::from(serde_json::to_vec(&engine.quoted_orderbook(symbol)?).ok()?);
+        self.books.insert(symbol.to_string(), (version, body.clone()));
+        Some(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support::OrderBuilder, types::*};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn order(side: OrderSide, price: rust_decimal::Decimal) -> Order {
+        OrderBuilder::new(side).price(price).build()
+    }
+
+    #[tokio::test]
+    async fn test_orderbook_body_is_reused_until_the_book_changes() {
+        let engine = TradingEngine::new(Arc::new(Config::default())).await.unwrap();
+        let cache = BookSnapshotCache::new();
+        engine.submit_order(order(OrderSide::Sell, dec!(100.00))).await.unwrap();
+        engine.submit_order(order(OrderSide::Buy, dec!(99.00))).await.unwrap();
+
+        let first = cache.orderbook(&engine, "GSEC10Y").unwrap();
+        let again = cache.orderbook(&engine, "GSEC10Y").unwrap();
+        assert_eq!(first.as_ptr(), again.as_ptr());
+
+        engine.submit_order(order(OrderSide::Buy, dec!(99.25))).await.unwrap();
+        let changed = cache.orderbook(&engine, "GSEC10Y").unwrap();
+        let book: serde_json::Value = serde_json::from_slice(&changed).unwrap();
+        assert_eq!(book["bids"].as_array().unwrap().len(), 2);
+        assert!(cache.orderbook(&engine, "GSEC5Y").is_none());
+    }
+}
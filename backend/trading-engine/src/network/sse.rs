@@ -0,0 +1,178 @@
+//! Server-sent events fallback for networks that block WebSockets. Carries
+//! the same events as `/ws`, filtered the same way, with each event's
+//! journal sequence as its SSE id so a reconnecting client resumes from
+//! `Last-Event-ID` without gaps.
+
+use crate::{
+    engine::{event_journal::SequencedEvent, TradingEngine},
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, convert::Infallible, sync::Arc};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use uuid::Uuid;
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+#[derive(Debug, Deserialize)]
+pub struct SseQuery {
+    pub account_id: Option<Uuid>,
+    /// First sequence to send, for clients that have no `Last-Event-ID`.
+    pub from_seq: Option<u64>,
+}
+
+/// What the stream sends: journal events, and a notice when the journal
+/// no longer holds everything the client asked to resume from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum SseMessage {
+    Event(Box<SequencedEvent>),
+    ReplayTruncated {
+        requested_seq: u64,
+        oldest_available_seq: u64,
+    },
+}
+
+impl SseMessage {
+    fn into_event(self) -> Event {
+        match &self {
+            SseMessage::Event(event) => Event::default()
+                .id(event.seq.to_string())
+                .json_data(event)
+                .unwrap_or_else(|_| Event::default().comment("unserializable event")),
+            SseMessage::ReplayTruncated { .. } => Event::default()
+                .event("ReplayTruncated")
+                .json_data(&self)
+                .unwrap_or_else(|_| Event::default().comment("replay truncated")),
+        }
+    }
+}
+
+struct StreamState {
+    engine: Arc<TradingEngine>,
+    receiver: broadcast::Receiver<SequencedEvent>,
+    account_id: Option<Uuid>,
+    last_seq: u64,
+    pending: VecDeque<SseMessage>,
+}
+
+impl StreamState {
+    /// Queues journal events after `last_seq`.
+    fn replay(&mut self) {
+        let replay = self.engine.replay_events(self.last_seq + 1, self.account_id, usize::MAX);
+        if replay.truncated {
+            self.pending.push_back(SseMessage::ReplayTruncated {
+                requested_seq: self.last_seq + 1,
+                oldest_available_seq: replay.oldest_available_seq,
+            });
+        }
+        self.pending.extend(replay.events.into_iter().map(|event| SseMessage::Event(Box::new(event))));
+        self.last_seq = self.last_seq.max(replay.next_seq.saturating_sub(1));
+    }
+}
+
+/// Events after `resume_after`, then live ones; without it, only live
+/// events.
+pub fn event_stream(
+    engine: Arc<TradingEngine>,
+    account_id: Option<Uuid>,
+    resume_after: Option<u64>,
+) -> impl Stream<Item = SseMessage> {
+    // Subscribe before replaying so nothing published in between is lost
+    let receiver = engine.subscribe_events();
+    let mut state = StreamState {
+        last_seq: resume_after.unwrap_or_else(|| engine.last_event_seq()),
+        engine,
+        receiver,
+        account_id,
+        pending: VecDeque::new(),
+    };
+    if resume_after.is_some() {
+        state.replay();
+    }
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(message) = state.pending.pop_front() {
+                return Some((message, state));
+            }
+            match state.receiver.recv().await {
+                Ok(event) => {
+                    if event.seq <= state.last_seq {
+                        continue;
+                    }
+                    state.last_seq = event.seq;
+                    if state.account_id.is_none_or(|id| event.visible_to(id)) {
+                        return Some((SseMessage::Event(Box::new(event)), state));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("SSE subscriber lagged by {} events, replaying", skipped);
+                    state.replay();
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+pub async fn sse_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<SseQuery>,
+) -> impl IntoResponse {
+    let resume_after = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .or(query.from_seq.map(|from_seq| from_seq.saturating_sub(1)));
+    let events = event_stream(state.engine.clone(), query.account_id, resume_after)
+        .map(|message| Ok::<_, Infallible>(message.into_event()));
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, engine::EngineEvent, test_support::OrderBuilder, types::*};
+
+    fn order(side: OrderSide, account_id: Uuid) -> Order {
+        OrderBuilder::new(side).account(account_id).build()
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_after_last_event_id_for_the_account() {
+        let engine = Arc::new(TradingEngine::new(Arc::new(Config::default())).await.unwrap());
+        let (buyer, other) = (Uuid::new_v4(), Uuid::new_v4());
+        engine.submit_order(order(OrderSide::Buy, buyer)).await.unwrap();
+        let resume_after = engine.last_event_seq();
+        engine.submit_order(order(OrderSide::Buy, other)).await.unwrap();
+        engine.submit_order(order(OrderSide::Sell, other)).await.unwrap();
+
+        let mut events = Box::pin(event_stream(engine.clone(), Some(buyer), Some(resume_after)));
+        let mut last_seq = resume_after;
+        let trade = loop {
+            let Some(SseMessage::Event(event)) = events.next().await else {
+                panic!("stream ended before the trade");
+            };
+            assert!(event.seq > last_seq);
+            last_seq = event.seq;
+            match event.event {
+                EngineEvent::TradeExecuted(trade) => break trade,
+                // The other account's own orders are not the buyer's business
+                EngineEvent::OrderSubmitted(order) => assert_eq!(order.account_id, buyer),
+                _ => {}
+            }
+        };
+        assert_eq!((trade.buyer_account_id, trade.seller_account_id), (buyer, other));
+    }
+}
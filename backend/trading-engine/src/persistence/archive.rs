@@ -0,0 +1,272 @@
+//! Long-term storage for data that has aged out of the engine's live
+//! stores. Each archival batch is one gzip-compressed JSON-lines object
+//! whose key names its data class and the time range it covers, so a
+//! query only has to open the objects that can hold what it asks for.
+
+use crate::{config::Config, types::*};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use dashmap::DashMap;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClass {
+    Orders,
+    Trades,
+    /// The event journal.
+    Audit,
+    /// Point-in-time copies of the order books.
+    BookSnapshots,
+}
+
+impl DataClass {
+    pub const ALL: [DataClass; 4] = [
+        DataClass::Orders,
+        DataClass::Trades,
+        DataClass::Audit,
+        DataClass::BookSnapshots,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataClass::Orders => "orders",
+            DataClass::Trades => "trades",
+            DataClass::Audit => "audit",
+            DataClass::BookSnapshots => "book_snapshots",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.name() == name)
+    }
+}
+
+/// One archived record and the time it is filed under.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedRecord {
+    pub at: DateTime<Utc>,
+    pub record: serde_json::Value,
+}
+
+/// Where archives are kept. Keys are `/`-separated paths.
+#[async_trait]
+pub trait ArchiveSink: Send + Sync {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+    /// Keys starting with `prefix`, sorted.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// The sink named by `ARCHIVE_SINK`: `file:<directory>`, `memory`, or
+/// `none`.
+pub fn connect(config: &Config) -> Option<Arc<dyn ArchiveSink>> {
+    connect_to(&config.archive_sink)
+}
+
+/// A sink by its spec: `file:<directory>`, `memory`, or `none`.
+pub fn connect_to(spec: &str) -> Option<Arc<dyn ArchiveSink>> {
+    match spec {
+        "memory" => Some(Arc::new(InMemoryArchiveSink::default())),
+        other => other
+            .strip_prefix("file:")
+            .map(|root| Arc::new(FileArchiveSink::new(root)) as Arc<dyn ArchiveSink>),
+    }
+}
+
+fn io_error(e: std::io::Error) -> TradingError {
+    TradingError::InternalError(format!("Archive I/O failed: {}", e))
+}
+
+/// Archives as files under a directory, one per batch.
+pub struct FileArchiveSink {
+    root: PathBuf,
+}
+
+impl FileArchiveSink {
+    pub fn new(root: &str) -> Self {
+        Self { root: PathBuf::from(root) }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(TradingError::InvalidRequest(format!("Invalid archive key: {}", key)));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl ArchiveSink for FileArchiveSink {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(io_error)?;
+        }
+        // Written aside and renamed so a reader never sees half a batch
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, body).await.map_err(io_error)?;
+        tokio::fs::rename(&partial, &path).await.map_err(io_error)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        tokio::fs::read(self.path(key)?).await.map_err(io_error)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        // Walk down from the deepest directory the prefix names
+        let directory = prefix.rsplit_once('/').map_or("", |(directory, _)| directory);
+        let mut pending = vec![directory.to_string()];
+        let mut keys = Vec::new();
+        while let Some(directory) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(self.root.join(&directory)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error(e)),
+            };
+            while let Some(entry) = entries.next_entry().await.map_err(io_error)? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let key = if directory.is_empty() { name } else { format!("{}/{}", directory, name) };
+                if entry.file_type().await.map_err(io_error)?.is_dir() {
+                    pending.push(key);
+                } else if key.starts_with(prefix) && !key.ends_with(".partial") {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        tokio::fs::remove_file(self.path(key)?).await.map_err(io_error)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryArchiveSink {
+    objects: DashMap<String, Vec<u8>>,
+}
+
+#[async_trait]
+impl ArchiveSink for InMemoryArchiveSink {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        self.objects.insert(key.to_string(), body);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.objects
+            .get(key)
+            .map(|body| body.clone())
+            .ok_or_else(|| TradingError::InvalidRequest(format!("No archive {}", key)))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = self
+            .objects
+            .iter()
+            .filter(|object| object.key().starts_with(prefix))
+            .map(|object| object.key().clone())
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.remove(key);
+        Ok(())
+    }
+}
+
+const KEY_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Key for a batch of `class` records filed between `first` and `last`.
+pub fn batch_key(class: DataClass, first: DateTime<Utc>, last: DateTime<Utc>) -> String {
+    format!(
+        "{}/{}/{}_{}_{}.jsonl.gz",
+        class.name(),
+        first.format("%Y-%m"),
+        first.format(KEY_TIME_FORMAT),
+        last.format(KEY_TIME_FORMAT),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// The time range a batch key covers, to whole seconds.
+pub fn batch_range(key: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let name = key.rsplit('/').next()?.strip_suffix(".jsonl.gz")?;
+    let mut parts = name.split('_');
+    let mut time = || {
+        NaiveDateTime::parse_from_str(parts.next()?, KEY_TIME_FORMAT)
+            .ok()
+            .map(|time| time.and_utc())
+    };
+    let first = time()?;
+    // Keys are truncated to the second
+    Some((first, time()? + chrono::Duration::seconds(1)))
+}
+
+pub fn encode_batch(records: &[ArchivedRecord]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for record in records {
+        serde_json::to_writer(&mut encoder, record)
+            .map_err(|e| TradingError::InternalError(e.to_string()))?;
+        encoder.write_all(b"\n").map_err(io_error)?;
+    }
+    encoder.finish().map_err(io_error)
+}
+
+pub fn decode_batch(body: &[u8]) -> Result<Vec<ArchivedRecord>> {
+    let mut text = String::new();
+    GzDecoder::new(body).read_to_string(&mut text).map_err(io_error)?;
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| TradingError::InternalError(e.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_file_sink_round_trips_compressed_batches() {
+        let root = std::env::temp_dir().join(format!("vv-archive-{}", Uuid::new_v4()));
+        let sink = FileArchiveSink::new(root.to_str().unwrap());
+        let first = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2024, 3, 1, 15, 30, 0).unwrap();
+        let records: Vec<ArchivedRecord> = (0..100)
+            .map(|i| ArchivedRecord {
+                at: first,
+                record: serde_json::json!({ "symbol": "GSEC10Y", "sequence": i }),
+            })
+            .collect();
+
+        let key = batch_key(DataClass::Trades, first, last);
+        let body = encode_batch(&records).unwrap();
+        assert!(body.len() < serde_json::to_vec(&records).unwrap().len() / 4);
+        sink.put(&key, body).await.unwrap();
+
+        assert_eq!(sink.list("trades/2024-03/").await.unwrap(), vec![key.clone()]);
+        assert!(sink.list("orders/").await.unwrap().is_empty());
+        assert_eq!(batch_range(&key), Some((first, last + chrono::Duration::seconds(1))));
+        let restored = decode_batch(&sink.get(&key).await.unwrap()).unwrap();
+        assert_eq!(restored.len(), 100);
+        assert_eq!(restored[99].record["sequence"], 99);
+        assert!(sink.get("../escape").await.is_err());
+
+        sink.delete(&key).await.unwrap();
+        assert!(sink.list("trades/").await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    config::Config,
+    engine::{
+        book_snapshot::BookSnapshot,
+        executions::{ExecutionQuery, ExecutionReport},
+        notifications::NotificationSubscription,
+        pnl_timeseries::PnlSnapshot,
+        trade_store::TradeQuery,
+    },
+    types::*,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+pub mod archive;
+pub mod postgres;
+pub mod writer;
+
+use postgres::PostgresStateStore;
+
+/// Durable copy of the engine's live state. The engine stays authoritative;
+/// the store is written behind it and read back for recovery and
+/// reconciliation.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn load_orders(&self) -> Result<Vec<Order>>;
+    async fn load_positions(&self) -> Result<Vec<Position>>;
+    async fn save_order(&self, order: &Order) -> Result<()>;
+    async fn delete_order(&self, order_id: Uuid) -> Result<()>;
+    async fn save_position(&self, position: &Position) -> Result<()>;
+    async fn delete_position(&self, account_id: Uuid, symbol: &str) -> Result<()>;
+    async fn save_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> Result<()>;
+    async fn load_pnl_snapshots(
+        &self,
+        account_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<PnlSnapshot>>;
+    async fn load_notification_subscriptions(&self) -> Result<Vec<NotificationSubscription>>;
+    async fn save_notification_subscription(&self, subscription: &NotificationSubscription) -> Result<()>;
+    async fn delete_notification_subscription(&self, subscription_id: Uuid) -> Result<()>;
+    /// Trades that have left the engine's in-memory window.
+    async fn save_trades(&self, trades: &[Trade]) -> Result<()>;
+    /// Stored trades matching `query`, oldest first. The limit is applied
+    /// by the caller.
+    async fn load_trades(&self, query: &TradeQuery) -> Result<Vec<Trade>>;
+    /// Removes stored trades once they have been archived.
+    async fn delete_trades(&self, trade_ids: &[Uuid]) -> Result<()>;
+    /// Replaces the stored order books.
+    async fn save_book_snapshot(&self, snapshot: &BookSnapshot) -> Result<()>;
+    async fn load_book_snapshot(&self) -> Result<Option<BookSnapshot>>;
+    async fn save_executions(&self, reports: &[ExecutionReport]) -> Result<()>;
+    /// Stored execution reports matching `query`, in the order saved.
+    async fn load_executions(&self, query: &ExecutionQuery) -> Result<Vec<ExecutionReport>>;
+}
+
+pub fn connect(config: &Config) -> anyhow::Result<Arc<dyn StateStore>> {
+    match config.persistence_backend.as_str() {
+        "postgres" => Ok(Arc::new(PostgresStateStore::connect_lazy(&config.database_url)?)),
+        "memory" => Ok(Arc::new(InMemoryStateStore::new())),
+        other => anyhow::bail!("Unknown persistence backend: {}", other),
+    }
+}
+
+/// Creates a tenant's schema and the engine's tables in it, where state
+/// is kept in Postgres. `config` is the tenant's own.
+pub async fn prepare_tenant(config: &Config, tenant: &str) -> anyhow::Result<()> {
+    if config.persistence_backend != "postgres" {
+        return Ok(());
+    }
+    let applied = PostgresStateStore::connect_lazy(&config.database_url)?
+        .migrate_schema(tenant)
+        .await?;
+    info!("Tenant {} schema ready, {} migrations applied", tenant, applied);
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct InMemoryStateStore {
+    orders: DashMap<Uuid, Order>,
+    positions: DashMap<(Uuid, String), Position>,
+    pnl_snapshots: DashMap<Uuid, Vec<PnlSnapshot>>,
+    notification_subscriptions: DashMap<Uuid, NotificationSubscription>,
+    trades: DashMap<Uuid, Trade>,
+    book_snapshot: RwLock<Option<BookSnapshot>>,
+    executions: RwLock<Vec<ExecutionReport>>,
+}
+
+impl InMemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StateStore for InMemoryStateStore {
+    async fn load_orders(&self) -> Result<Vec<Order>> {
+        Ok(self.orders.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    async fn load_positions(&self) -> Result<Vec<Position>> {
+        Ok(self.positions.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    async fn save_order(&self, order: &Order) -> Result<()> {
+        self.orders.insert(order.id, order.clone());
+        Ok(())
+    }
+
+    async fn delete_order(&self, order_id: Uuid) -> Result<()> {
+        self.orders.remove(&order_id);
+        Ok(())
+    }
+
+    async fn save_position(&self, position: &Position) -> Result<()> {
+        self.positions
+            .insert((position.account_id, position.symbol.clone()), position.clone());
+        Ok(())
+    }
+
+    async fn delete_position(&self, account_id: Uuid, symbol: &str) -> Result<()> {
+        self.positions.remove(&(account_id, symbol.to_string()));
+        Ok(())
+    }
+
+    async fn save_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> Result<()> {
+        for snapshot in snapshots {
+            self.pnl_snapshots
+                .entry(snapshot.account_id)
+                .or_default()
+                .push(snapshot.clone());
+        }
+        Ok(())
+    }
+
+    async fn load_pnl_snapshots(
+        &self,
+        account_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<PnlSnapshot>> {
+        Ok(self
+            .pnl_snapshots
+            .get(&account_id)
+            .map(|snapshots| {
+                snapshots
+                    .iter()
+                    .filter(|snapshot| snapshot.timestamp >= from && snapshot.timestamp <= to)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn load_notification_subscriptions(&self) -> Result<Vec<NotificationSubscription>> {
+        Ok(self
+            .notification_subscriptions
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+
+    async fn save_notification_subscription(&self, subscription: &NotificationSubscription) -> Result<()> {
+        self.notification_subscriptions
+            .insert(subscription.id, subscription.clone());
+        Ok(())
+    }
+
+    async fn delete_notification_subscription(&self, subscription_id: Uuid) -> Result<()> {
+        self.notification_subscriptions.remove(&subscription_id);
+        Ok(())
+    }
+
+    async fn save_trades(&self, trades: &[Trade]) -> Result<()> {
+        for trade in trades {
+            self.trades.insert(trade.id, trade.clone());
+        }
+        Ok(())
+    }
+
+    async fn load_trades(&self, query: &TradeQuery) -> Result<Vec<Trade>> {
+        let mut trades: Vec<Trade> = self
+            .trades
+            .iter()
+            .filter(|entry| query.matches(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        trades.sort_by_key(|trade| trade.timestamp);
+        Ok(trades)
+    }
+
+    async fn delete_trades(&self, trade_ids: &[Uuid]) -> Result<()> {
+        for trade_id in trade_ids {
+            self.trades.remove(trade_id);
+        }
+        Ok(())
+    }
+
+    async fn save_book_snapshot(&self, snapshot: &BookSnapshot) -> Result<()> {
+        *self.book_snapshot.write() = Some(snapshot.clone());
+        Ok(())
+    }
+
+    async fn load_book_snapshot(&self) -> Result<Option<BookSnapshot>> {
+        Ok(self.book_snapshot.read().clone())
+    }
+
+    async fn save_executions(&self, reports: &[ExecutionReport]) -> Result<()> {
+        self.executions.write().extend_from_slice(reports);
+        Ok(())
+    }
+
+    async fn load_executions(&self, query: &ExecutionQuery) -> Result<Vec<ExecutionReport>> {
+        Ok(self
+            .executions
+            .read()
+            .iter()
+            .filter(|report| query.matches(report))
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{
+    engine::{
+        book_snapshot::BookSnapshot,
+        executions::{ExecutionQuery, ExecutionReport},
+        notifications::NotificationSubscription,
+        pnl_timeseries::PnlSnapshot,
+        trade_store::TradeQuery,
+    },
+    persistence::StateStore,
+    types::*,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{postgres::PgPoolOptions, Executor, PgPool, Row};
+use uuid::Uuid;
+
+/// The engine's own tables, applied in order to a tenant's schema. The
+/// platform's shared tables (instruments, users) stay in the public schema
+/// the database was initialised with.
+const ENGINE_MIGRATIONS: [(i32, &str); 6] = [
+    (3, include_str!("../../../../database/postgresql/schema/003_create_trading_state.sql")),
+    (4, include_str!("../../../../database/postgresql/schema/004_create_pnl_snapshots.sql")),
+    (5, include_str!("../../../../database/postgresql/schema/005_create_notification_subscriptions.sql")),
+    (6, include_str!("../../../../database/postgresql/schema/006_create_trades.sql")),
+    (7, include_str!("../../../../database/postgresql/schema/007_create_executions.sql")),
+    (8, include_str!("../../../../database/postgresql/schema/008_create_book_snapshots.sql")),
+];
+
+/// State store backed by the `engine_orders` / `engine_positions` tables
+/// and their neighbours.
+/// Records are stored whole as JSONB alongside a few indexed columns.
+pub struct PostgresStateStore {
+    pool: PgPool,
+}
+
+impl PostgresStateStore {
+    pub fn connect_lazy(database_url: &str) -> anyhow::Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_lazy(database_url)?;
+        Ok(Self { pool })
+    }
+
+    /// Creates `schema` if it is missing and applies the engine migrations
+    /// it has not had yet, each in its own transaction. The store's
+    /// connections must already search `schema`. Returns how many were
+    /// applied.
+    pub async fn migrate_schema(&self, schema: &str) -> anyhow::Result<usize> {
+        anyhow::ensure!(
+            !schema.is_empty()
+                && !schema.starts_with(|c: char| c.is_ascii_digit())
+                && schema.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+            "Tenant schema {:?} must be a lowercase identifier",
+            schema
+        );
+        self.pool
+            .execute(format!("CREATE SCHEMA IF NOT EXISTS {}", schema).as_str())
+            .await?;
+        self.pool
+            .execute(
+                "CREATE TABLE IF NOT EXISTS engine_schema_migrations (
+                     version INTEGER PRIMARY KEY,
+                     applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+                 )",
+            )
+            .await?;
+
+        let mut applied = 0;
+        for (version, sql) in ENGINE_MIGRATIONS {
+            let mut tx = self.pool.begin().await?;
+            // Engines starting side by side apply each migration once
+            tx.execute("LOCK TABLE engine_schema_migrations IN EXCLUSIVE MODE").await?;
+            let done = sqlx::query("SELECT 1 FROM engine_schema_migrations WHERE version = $1")
+                .bind(version)
+                .fetch_optional(&mut *tx)
+                .await?
+                .is_some();
+            if !done {
+                tx.execute(sql).await?;
+                sqlx::query("INSERT INTO engine_schema_migrations (version) VALUES ($1)")
+                    .bind(version)
+                    .execute(&mut *tx)
+                    .await?;
+                applied += 1;
+            }
+            tx.commit().await?;
+        }
+        Ok(applied)
+    }
+
+    async fn load_payloads<T: DeserializeOwned>(&self, sql: &str) -> Result<Vec<T>> {
+        let rows = sqlx::query(sql).fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| decode(&row.try_get::<String, _>("payload")?))
+            .collect()
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| TradingError::InternalError(e.to_string()))
+}
+
+fn decimal(row: &sqlx::postgres::PgRow, column: &str) -> Result<Decimal> {
+    row.try_get::<String, _>(column)?
+        .parse()
+        .map_err(|e: rust_decimal::Error| TradingError::InternalError(e.to_string()))
+}
+
+fn decode<T: DeserializeOwned>(payload: &str) -> Result<T> {
+    serde_json::from_str(payload).map_err(|e| TradingError::InternalError(e.to_string()))
+}
+
+#[async_trait]
+impl StateStore for PostgresStateStore {
+    async fn load_orders(&self) -> Result<Vec<Order>> {
+        self.load_payloads("SELECT payload::text AS payload FROM engine_orders")
+            .await
+    }
+
+    async fn load_positions(&self) -> Result<Vec<Position>> {
+        self.load_payloads("SELECT payload::text AS payload FROM engine_positions")
+            .await
+    }
+
+    async fn save_order(&self, order: &Order) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO engine_orders (id, account_id, symbol, status, payload, updated_at)
+             VALUES ($1, $2, $3, $4, $5::jsonb, NOW())
+             ON CONFLICT (id) DO UPDATE
+             SET status = EXCLUDED.status, payload = EXCLUDED.payload, updated_at = NOW()",
+        )
+        .bind(order.id)
+        .bind(order.account_id)
+        .bind(&order.symbol)
+        .bind(format!("{:?}", order.status))
+        .bind(encode(order)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_order(&self, order_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM engine_orders WHERE id = $1")
+            .bind(order_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_position(&self, position: &Position) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO engine_positions (account_id, symbol, payload, updated_at)
+             VALUES ($1, $2, $3::jsonb, NOW())
+             ON CONFLICT (account_id, symbol) DO UPDATE
+             SET payload = EXCLUDED.payload, updated_at = NOW()",
+        )
+        .bind(position.account_id)
+        .bind(&position.symbol)
+        .bind(encode(position)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_position(&self, account_id: Uuid, symbol: &str) -> Result<()> {
+        sqlx::query("DELETE FROM engine_positions WHERE account_id = $1 AND symbol = $2")
+            .bind(account_id)
+            .bind(symbol)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> Result<()> {
+        for snapshot in snapshots {
+            sqlx::query(
+                "INSERT INTO engine_pnl_snapshots
+                 (account_id, captured_at, realized_pnl, unrealized_pnl, total_pnl, market_value)
+                 VALUES ($1, $2, $3::numeric, $4::numeric, $5::numeric, $6::numeric)
+                 ON CONFLICT (account_id, captured_at) DO NOTHING",
+            )
+            .bind(snapshot.account_id)
+            .bind(snapshot.timestamp)
+            .bind(snapshot.realized_pnl.to_string())
+            .bind(snapshot.unrealized_pnl.to_string())
+            .bind(snapshot.total_pnl.to_string())
+            .bind(snapshot.market_value.to_string())
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn load_pnl_snapshots(
+        &self,
+        account_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<PnlSnapshot>> {
+        let rows = sqlx::query(
+            "SELECT captured_at, realized_pnl::text AS realized_pnl, unrealized_pnl::text AS unrealized_pnl,
+                    total_pnl::text AS total_pnl, market_value::text AS market_value
+             FROM engine_pnl_snapshots
+             WHERE account_id = $1 AND captured_at BETWEEN $2 AND $3
+             ORDER BY captured_at",
+        )
+        .bind(account_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(PnlSnapshot {
+                    account_id,
+                    timestamp: row.try_get("captured_at")?,
+                    realized_pnl: decimal(row, "realized_pnl")?,
+                    unrealized_pnl: decimal(row, "unrealized_pnl")?,
+                    total_pnl: decimal(row, "total_pnl")?,
+                    market_value: decimal(row, "market_value")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn load_notification_subscriptions(&self) -> Result<Vec<NotificationSubscription>> {
+        self.load_payloads("SELECT payload::text AS payload FROM engine_notification_subscriptions")
+            .await
+    }
+
+    async fn save_notification_subscription(&self, subscription: &NotificationSubscription) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO engine_notification_subscriptions (id, user_id, account_id, payload, updated_at)
+             VALUES ($1, $2, $3, $4::jsonb, NOW())
+             ON CONFLICT (id) DO UPDATE
+             SET payload = EXCLUDED.payload, updated_at = NOW()",
+        )
+        .bind(subscription.id)
+        .bind(subscription.user_id)
+        .bind(subscription.account_id)
+        .bind(encode(subscription)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_notification_subscription(&self, subscription_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM engine_notification_subscriptions WHERE id = $1")
+            .bind(subscription_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_trades(&self, trades: &[Trade]) -> Result<()> {
+        for trade in trades {
+            sqlx::query(
+                "INSERT INTO engine_trades
+                 (id, trade_number, symbol, buyer_account_id, seller_account_id, executed_at, payload)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7::jsonb)
+                 ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(trade.id)
+            .bind(&trade.trade_number)
+            .bind(&trade.symbol)
+            .bind(trade.buyer_account_id)
+            .bind(trade.seller_account_id)
+            .bind(trade.timestamp)
+            .bind(encode(trade)?)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn load_trades(&self, query: &TradeQuery) -> Result<Vec<Trade>> {
+        let rows = sqlx::query(
+            "SELECT payload::text AS payload
+             FROM engine_trades
+             WHERE ($1::timestamptz IS NULL OR executed_at >= $1)
+               AND ($2::timestamptz IS NULL OR executed_at <= $2)
+               AND ($3::text IS NULL OR symbol = $3)
+               AND ($4::uuid IS NULL OR buyer_account_id = $4 OR seller_account_id = $4)
+             ORDER BY executed_at",
+        )
+        .bind(query.from)
+        .bind(query.to)
+        .bind(&query.symbol)
+        .bind(query.account_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| decode(&row.try_get::<String, _>("payload")?))
+            .collect()
+    }
+
+    async fn delete_trades(&self, trade_ids: &[Uuid]) -> Result<()> {
+        sqlx::query("DELETE FROM engine_trades WHERE id = ANY($1)")
+            .bind(trade_ids)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_book_snapshot(&self, snapshot: &BookSnapshot) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO engine_book_snapshots (id, payload, updated_at)
+             VALUES (1, $1::jsonb, NOW())
+             ON CONFLICT (id) DO UPDATE
+             SET payload = EXCLUDED.payload, updated_at = NOW()",
+        )
+        .bind(encode(snapshot)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn load_book_snapshot(&self) -> Result<Option<BookSnapshot>> {
+        Ok(self
+            .load_payloads("SELECT payload::text AS payload FROM engine_book_snapshots WHERE id = 1")
+            .await?
+            .pop())
+    }
+
+    async fn save_executions(&self, reports: &[ExecutionReport]) -> Result<()> {
+        for report in reports {
+            sqlx::query(
+                "INSERT INTO engine_executions (exec_id, order_id, account_id, transact_time, payload)
+                 VALUES ($1, $2, $3, $4, $5::jsonb)
+                 ON CONFLICT (exec_id) DO NOTHING",
+            )
+            .bind(report.exec_id)
+            .bind(report.order_id)
+            .bind(report.account_id)
+            .bind(report.transact_time)
+            .bind(encode(report)?)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn load_executions(&self, query: &ExecutionQuery) -> Result<Vec<ExecutionReport>> {
+        let rows = sqlx::query(
+            "SELECT payload::text AS payload
+             FROM engine_executions
+             WHERE ($1::uuid IS NULL OR account_id = $1)
+               AND ($2::timestamptz IS NULL OR transact_time >= $2)
+               AND ($3::timestamptz IS NULL OR transact_time <= $3)
+             ORDER BY id",
+        )
+        .bind(query.account_id)
+        .bind(query.from)
+        .bind(query.to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| decode(&row.try_get::<String, _>("payload")?))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support::OrderBuilder};
+    use std::collections::HashMap;
+
+    /// Runs against the Postgres database at `TEST_DATABASE_URL`, in
+    /// schemas of its own that it drops afterwards.
+    #[tokio::test]
+    #[ignore = "needs a Postgres database at TEST_DATABASE_URL"]
+    async fn test_each_tenant_gets_its_own_migrated_schema() {
+        let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+        let run = Uuid::new_v4().simple().to_string()[..8].to_string();
+        let tenants = [format!("venue_a_{}", run), format!("venue_b_{}", run)];
+        let config = Config {
+            persistence_backend: "postgres".to_string(),
+            database_url: database_url.clone(),
+            tenant_api_keys: HashMap::from([
+                ("key-a".to_string(), tenants[0].clone()),
+                ("key-b".to_string(), tenants[1].clone()),
+            ]),
+            ..Config::default()
+        };
+
+        let mut stores = Vec::new();
+        for tenant in config.tenants() {
+            let tenant_config = config.for_tenant(&tenant).unwrap();
+            crate::persistence::prepare_tenant(&tenant_config, &tenant).await.unwrap();
+            let store = PostgresStateStore::connect_lazy(&tenant_config.database_url).unwrap();
+            // Already up to date the second time round
+            assert_eq!(store.migrate_schema(&tenant).await.unwrap(), 0);
+            stores.push(store);
+        }
+
+        let order = OrderBuilder::new(OrderSide::Buy).build();
+        stores[0].save_order(&order).await.unwrap();
+        assert_eq!(stores[0].load_orders().await.unwrap().len(), 1);
+        assert!(stores[1].load_orders().await.unwrap().is_empty());
+        assert!(stores[1].load_book_snapshot().await.unwrap().is_none());
+
+        let admin = PostgresStateStore::connect_lazy(&database_url).unwrap();
+        for tenant in &tenants {
+            let tables: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = $1 AND table_name LIKE 'engine_%'",
+            )
+            .bind(tenant)
+            .fetch_one(&admin.pool)
+            .await
+            .unwrap();
+            assert_eq!(tables, 8);
+            admin
+                .pool
+                .execute(format!("DROP SCHEMA {} CASCADE", tenant).as_str())
+                .await
+                .unwrap();
+        }
+        assert!(PostgresStateStore::connect_lazy(&database_url)
+            .unwrap()
+            .migrate_schema("Venue-C")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::engine::{
+    event_journal::{Delivery, SequencedEvent},
+    EngineEvent, TradingEngine,
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct DirtySet {
+    orders: HashSet<Uuid>,
+    positions: HashSet<(Uuid, String)>,
+    full_resync: bool,
+}
+
+impl DirtySet {
+    fn mark(&mut self, event: &SequencedEvent) {
+        match &event.event {
+            EngineEvent::OrderSubmitted(order) => {
+                self.orders.insert(order.id);
+            }
+            EngineEvent::OrderCancelled { order_id, .. }
+            | EngineEvent::OrderExpired { order_id, .. }
+            | EngineEvent::OrderReduced { order_id, .. } => {
+                self.orders.insert(*order_id);
+            }
+            EngineEvent::OrderFilled { order_id, .. } | EngineEvent::OrderRejected { order_id, .. } => {
+                self.orders.insert(*order_id);
+            }
+            EngineEvent::OrderApprovalUpdated(staged) => {
+                self.orders.insert(staged.order.id);
+            }
+            EngineEvent::TradeExecuted(trade) => {
+                self.positions
+                    .insert((trade.buyer_account_id, trade.symbol.clone()));
+                self.positions
+                    .insert((trade.seller_account_id, trade.symbol.clone()));
+            }
+            EngineEvent::PositionUpdated(position) => {
+                self.positions
+                    .insert((position.account_id, position.symbol.clone()));
+            }
+            // Delisting moves a symbol's orders out of the live map, which
+            // the flush turns into deletes.
+            EngineEvent::InstrumentStatusChanged { .. } => self.full_resync = true,
+            EngineEvent::RiskViolation { .. }
+            | EngineEvent::RiskWarning { .. }
+            | EngineEvent::RiskOverrideGranted(_)
+            | EngineEvent::RiskOverrideRevoked(_)
+            | EngineEvent::AuctionIndicative(_)
+            | EngineEvent::SpreadOrderUpdated(_)
+            | EngineEvent::BenchmarkOrderUpdated(_)
+            | EngineEvent::CorporateActionApplied(_)
+            | EngineEvent::ClockQualityChanged(_)
+            | EngineEvent::OrderSignatureVerified { .. }
+            | EngineEvent::DealerHitUpdated(_)
+            | EngineEvent::ImprovementAuctionUpdated(_)
+            | EngineEvent::IndexValueUpdated(_)
+            | EngineEvent::ConditionalOrderUpdated(_)
+            | EngineEvent::Liquidation(_) => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.full_resync && self.orders.is_empty() && self.positions.is_empty()
+    }
+}
+
+/// Write-behind loop: collects the orders and positions touched by engine
+/// events and periodically copies their current engine state to the store.
+/// Events arrive losslessly; a full resync remains the fallback should any
+/// be reported skipped. Flushes run on their own task so events keep
+/// draining, and order entry is not held back, while the store is slow.
+pub async fn run(engine: Arc<TradingEngine>, flush_interval: Duration) {
+    let mut events = engine.subscribe_events_with(Delivery::Lossless);
+    let mut interval = tokio::time::interval(flush_interval);
+    let mut dirty = DirtySet::default();
+    let mut flushing: Option<JoinHandle<()>> = None;
+
+    loop {
+        tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) => dirty.mark(&event),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("State writer lagged by {} events, scheduling full resync", skipped);
+                    dirty.full_resync = true;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = interval.tick() => {
+                // Changes made meanwhile wait for the next flush
+                if flushing.as_ref().is_some_and(|flush| !flush.is_finished()) {
+                    continue;
+                }
+                if !dirty.is_empty() {
+                    flushing = Some(tokio::spawn(flush(engine.clone(), std::mem::take(&mut dirty))));
+                }
+            }
+        }
+    }
+
+    if let Some(flush) = flushing {
+        let _ = flush.await;
+    }
+}
+
+async fn flush(engine: Arc<TradingEngine>, dirty: DirtySet) {
+    if dirty.full_resync || !dirty.orders.is_empty() {
+        if let Err(e) = engine.save_book_snapshot().await {
+            error!("Failed to persist order books: {}", e);
+        }
+    }
+    if dirty.full_resync {
+        if let Err(e) = engine.reconcile(true).await {
+            error!("State resync failed: {}", e);
+        }
+        return;
+    }
+
+    let store = engine.state_store();
+    for order_id in dirty.orders {
+        let result = match engine.get_order(&order_id) {
+            Some(order) => store.save_order(&order).await,
+            None => store.delete_order(order_id).await,
+        };
+        if let Err(e) = result {
+            error!("Failed to persist order {}: {}", order_id, e);
+        }
+    }
+
+    for (account_id, symbol) in dirty.positions {
+        let result = match engine.get_position(account_id, &symbol).await {
+            Some(position) => store.save_position(&position).await,
+            None => store.delete_position(account_id, &symbol).await,
+        };
+        if let Err(e) = result {
+            error!("Failed to persist position {}/{}: {}", account_id, symbol, e);
+        }
+    }
+}
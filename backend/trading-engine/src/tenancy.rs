@@ -0,0 +1,198 @@
+//! Hosting several venues in one process. Each tenant has its own engine,
+//! so instruments, books, accounts and event streams never mix; a request
+//! only ever reaches the router of the tenant its API key belongs to.
+//! Liveness and readiness probes are answered for the whole process,
+//! without a key and without any tenant's data.
+
+use crate::{engine::TradingEngine, types::TradingError};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tower::Service;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Per-tenant routers and the API keys that select them.
+pub struct TenantRouter {
+    api_keys: HashMap<String, String>,
+    routers: HashMap<String, Router>,
+    engines: Vec<Arc<TradingEngine>>,
+}
+
+impl TenantRouter {
+    /// `api_keys` maps each key to its tenant.
+    pub fn new(api_keys: HashMap<String, String>) -> Self {
+        Self {
+            api_keys,
+            routers: HashMap::new(),
+            engines: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, tenant: &str, engine: Arc<TradingEngine>, router: Router) {
+        self.routers.insert(tenant.to_string(), router);
+        self.engines.push(engine);
+    }
+
+    /// The tenant named by the request's `X-Api-Key` header or bearer
+    /// token.
+    pub fn resolve(&self, headers: &HeaderMap) -> Option<&str> {
+        let key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+            })?;
+        self.api_keys.get(key.trim()).map(String::as_str)
+    }
+}
+
+pub async fn health(State(tenants): State<Arc<TenantRouter>>) -> impl IntoResponse {
+    Json(json!({ "status": "healthy", "tenants": tenants.routers.len() }))
+}
+
+/// Ready once every tenant's engine has finished warm-up.
+pub async fn readiness(State(tenants): State<Arc<TenantRouter>>) -> impl IntoResponse {
+    let warming_up = tenants
+        .engines
+        .iter()
+        .filter(|engine| !engine.warmup_status().is_ready())
+        .count();
+    let code = if warming_up == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(json!({ "ready": warming_up == 0, "tenants_warming_up": warming_up })))
+}
+
+/// The process-wide probes in front of the tenants' own routes.
+pub fn routes(tenants: TenantRouter) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/readyz", get(readiness))
+        .fallback(dispatch)
+        .with_state(Arc::new(tenants))
+}
+
+pub async fn dispatch(State(tenants): State<Arc<TenantRouter>>, request: Request) -> Response {
+    let Some(router) = tenants
+        .resolve(request.headers())
+        .and_then(|tenant| tenants.routers.get(tenant))
+    else {
+        return TradingError::Unauthorized("A valid tenant API key is required".to_string()).into_response();
+    };
+    // Routers are always ready, so there is no need to poll first
+    match router.clone().call(request).await {
+        Ok(response) => response,
+        Err(infallible) => match infallible {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, test_support, AppState};
+    use axum::{body::Body, http::Method};
+    use rust_decimal_macros::dec;
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    fn config() -> Config {
+        Config {
+            tenant_api_keys: HashMap::from([
+                ("key-a".to_string(), "venue_a".to_string()),
+                ("key-b".to_string(), "venue_b".to_string()),
+            ]),
+            archive_sink: "file:/var/lib/vedhavriddhi/archive".to_string(),
+            settlement_outbound: "file:/var/lib/vedhavriddhi/outbound".to_string(),
+            audit_log: "file:/var/log/vedhavriddhi/audit.jsonl".to_string(),
+            ..Config::default()
+        }
+    }
+
+    async fn tenants(config: &Config) -> Router {
+        let mut tenants = TenantRouter::new(config.tenant_api_keys.clone());
+        for tenant in config.tenants() {
+            let tenant_config = Arc::new(config.for_tenant(&tenant).unwrap());
+            let engine = Arc::new(TradingEngine::new(tenant_config.clone()).await.unwrap());
+            tenants.insert(&tenant, engine.clone(), crate::routes().with_state(AppState::new(engine, tenant_config)));
+        }
+        routes(tenants)
+    }
+
+    async fn call(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        key: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+        let request = match body {
+            Some(body) => request.header("content-type", "application/json").body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        let response = router.clone().call(request.unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_tenants_see_only_their_own_data() {
+        let config = config();
+        let router = tenants(&config).await;
+        let account = test_support::TestAccount {
+            account_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+        };
+        let (status, instrument) =
+            call(&router, Method::POST, "/admin/instruments", Some("key-a"), Some(test_support::bond("GSEC10Y"))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", instrument);
+        let order = test_support::limit_order(account, "GSEC10Y", "Buy", dec!(100000), dec!(99.50));
+        let (status, body) = call(&router, Method::POST, "/orders", Some("key-c"), Some(order.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+        let (status, body) = call(&router, Method::POST, "/orders", Some("key-a"), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        let order_id = body["order_id"].as_str().unwrap().to_string();
+
+        let uri = format!("/orders/{}", order_id);
+        assert_eq!(call(&router, Method::GET, &uri, Some("key-a"), None).await.0, StatusCode::OK);
+        assert_eq!(call(&router, Method::GET, &uri, Some("key-b"), None).await.0, StatusCode::NOT_FOUND);
+        let listed = |key| call(&router, Method::GET, "/orders", Some(key), None);
+        assert!(listed("key-a").await.0.is_success());
+        let (_, venue_b_orders) = listed("key-b").await;
+        assert!(!venue_b_orders.to_string().contains(&order_id));
+
+        // Archives, settlement files and audit records land apart too
+        let (venue_a, venue_b) = (config.for_tenant("venue_a").unwrap(), config.for_tenant("venue_b").unwrap());
+        assert_eq!(venue_a.archive_sink, "file:/var/lib/vedhavriddhi/archive/venue_a");
+        assert_eq!(venue_b.settlement_outbound, "file:/var/lib/vedhavriddhi/outbound/venue_b");
+        assert_eq!(venue_a.audit_log, "file:/var/log/vedhavriddhi/audit.venue_a.jsonl");
+        assert_ne!(venue_a.engine_instance_id, venue_b.engine_instance_id);
+    }
+
+    #[tokio::test]
+    async fn test_probes_need_no_key_and_show_no_tenant_data() {
+        let router = tenants(&config()).await;
+        let (status, health) = call(&router, Method::GET, "/health", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health, json!({ "status": "healthy", "tenants": 2 }));
+        let (status, ready) = call(&router, Method::GET, "/readyz", None, None).await;
+        assert_eq!(ready["ready"], status == StatusCode::OK);
+        assert_eq!(call(&router, Method::GET, "/orders", None, None).await.0, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,76 @@
+//! End-to-end flows through the HTTP API.
+
+use super::*;
+use futures_util::StreamExt;
+use rust_decimal_macros::dec;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+#[tokio::test]
+async fn test_crossing_orders_match_into_positions() {
+    let app = TestApp::new().await;
+    app.list_bond("GS2034").await;
+    let (seller, buyer) = (app.account(), app.account());
+
+    let sell = app.limit_order(seller, "GS2034", "Sell", dec!(500000), dec!(99.75)).await;
+    let buy = app.limit_order(buyer, "GS2034", "Buy", dec!(200000), dec!(99.80)).await;
+
+    let (status, order) = app.get(&format!("/orders/{}", buy)).await;
+    assert_eq!((status, order["status"].as_str()), (StatusCode::OK, Some("Filled")));
+    let (_, order) = app.get(&format!("/orders/{}", sell)).await;
+    assert_eq!(order["status"], "PartiallyFilled");
+    assert_eq!(order["remaining_quantity"], "300000");
+
+    let (_, positions) = app.get(&format!("/positions?account_id={}", buyer.account_id)).await;
+    assert_eq!(positions[0]["quantity"], "200000");
+    assert_eq!(positions[0]["average_price"], "99.75");
+    let (_, positions) = app.get(&format!("/positions?account_id={}", seller.account_id)).await;
+    assert_eq!(positions[0]["quantity"], "-200000");
+}
+
+#[tokio::test]
+async fn test_invalid_orders_are_rejected_with_a_reason() {
+    let app = TestApp::new().await;
+    app.list_bond("GS2034").await;
+    let account = app.account();
+
+    let order = limit_order(account, "GS2034", "Buy", dec!(0), dec!(99.75));
+    let (status, body) = app.post("/orders", order).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["reject_reason"]["Validation"]["field"], "Quantity");
+
+    let (status, _) = app.get(&format!("/orders/{}", Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_trades_reach_websocket_subscribers() {
+    let app = TestApp::new().await;
+    app.list_bond("GS2034").await;
+    let (seller, buyer) = (app.account(), app.account());
+    let addr = app.serve().await;
+
+    // Subscribing from the next sequence replays anything published before
+    // the socket attached, so the trade cannot be missed
+    let from_seq = app.engine.last_event_seq() + 1;
+    let url = format!("ws://{}/ws?account_id={}&from_seq={}", addr, buyer.account_id, from_seq);
+    let (mut socket, _) = connect_async(url).await.expect("websocket connects");
+
+    app.limit_order(seller, "GS2034", "Sell", dec!(100000), dec!(99.50)).await;
+    let buy = app.limit_order(buyer, "GS2034", "Buy", dec!(100000), dec!(99.50)).await;
+
+    let trade = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(Ok(message)) = socket.next().await {
+            let Message::Text(text) = message else { continue };
+            let event: Value = serde_json::from_str(&text).expect("events are JSON");
+            if let Some(trade) = event["event"].get("TradeExecuted") {
+                return trade.clone();
+            }
+        }
+        panic!("socket closed before the trade arrived");
+    })
+    .await
+    .expect("trade event within 5s");
+    assert_eq!(trade["buyer_order_id"], buy.to_string());
+    assert_eq!(trade["quantity"], "100000");
+}
@@ -0,0 +1,226 @@
+//! Boots the full HTTP API over an in-memory engine so flows can be tested
+//! end to end without Postgres, Redis or a network listener. Requests go
+//! straight through the router; [`TestApp::serve`] binds a loopback port
+//! for clients that need a real socket, such as WebSocket subscribers.
+
+use crate::{config::Config, engine::TradingEngine, routes, types::*, AppState};
+use axum::{
+    body::{to_bytes, Body},
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::{json, Value};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use tower::Service;
+use uuid::Uuid;
+
+mod golden_path;
+
+pub struct TestApp {
+    pub engine: Arc<TradingEngine>,
+    router: Router,
+}
+
+/// A trading account and the user entering its orders.
+#[derive(Debug, Clone, Copy)]
+pub struct TestAccount {
+    pub account_id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_config(Config::default()).await
+    }
+
+    pub async fn with_config(config: Config) -> Self {
+        let config = Arc::new(config);
+        let engine = Arc::new(TradingEngine::new(config.clone()).await.expect("engine starts"));
+        let router = routes().with_state(AppState::new(engine.clone(), config.clone()));
+        Self { engine, router }
+    }
+
+    /// Sends one request through the router, returning the status and the
+    /// body as JSON (`Null` when empty).
+    pub async fn request(&self, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .expect("valid request");
+        // A router is always ready, so it can be called without polling
+        let response = self.router.clone().call(request).await.expect("router is infallible");
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.expect("body reads");
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    pub async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        self.request(Method::GET, uri, None).await
+    }
+
+    pub async fn post(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.request(Method::POST, uri, Some(body)).await
+    }
+
+    /// Serves the app on a loopback port for the rest of the test.
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("loopback binds");
+        let addr = listener.local_addr().expect("bound address");
+        let router = self.router.clone();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    pub fn account(&self) -> TestAccount {
+        TestAccount {
+            account_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+        }
+    }
+
+    /// Lists a ten-year government bond under `symbol`.
+    pub async fn list_bond(&self, symbol: &str) -> Value {
+        let (status, instrument) = self.post("/admin/instruments", bond(symbol)).await;
+        assert_eq!(status, StatusCode::CREATED, "listing {}: {}", symbol, instrument);
+        instrument
+    }
+
+    /// Submits a GTC limit order, returning its id.
+    pub async fn limit_order(
+        &self,
+        account: TestAccount,
+        symbol: &str,
+        side: &str,
+        quantity: Decimal,
+        price: Decimal,
+    ) -> Uuid {
+        let (status, body) = self.post("/orders", limit_order(account, symbol, side, quantity, price)).await;
+        assert_eq!(status, StatusCode::CREATED, "order rejected: {}", body);
+        body["order_id"].as_str().and_then(|id| id.parse().ok()).expect("order id")
+    }
+}
+
+/// Listing request for a plain semi-annual government bond.
+pub fn bond(symbol: &str) -> Value {
+    json!({
+        "isin": format!("IN00{}", symbol),
+        "symbol": symbol,
+        "issuer": "Government of India",
+        "maturity_date": "2034-06-15T00:00:00Z",
+        "coupon_rate": "7.10",
+        "face_value": "100",
+        "bond_type": "GovernmentSecurity",
+        "rating": "SOV",
+        "is_active": true,
+    })
+}
+
+pub fn limit_order(account: TestAccount, symbol: &str, side: &str, quantity: Decimal, price: Decimal) -> Value {
+    json!({
+        "client_order_id": format!("TEST-{}", Uuid::new_v4().simple()),
+        "symbol": symbol,
+        "side": side,
+        "order_type": "Limit",
+        "quantity": quantity,
+        "price": price,
+        "user_id": account.user_id,
+        "account_id": account.account_id,
+    })
+}
+
+/// Builds an engine `Order` for unit tests. Unless told otherwise it is a
+/// GTC limit order for 100,000 of GSEC10Y at 99.50, from a fresh account
+/// and user, stamped now.
+pub struct OrderBuilder(Order);
+
+impl OrderBuilder {
+    pub fn new(side: OrderSide) -> Self {
+        Self(Order {
+            id: Uuid::new_v4(),
+            client_order_id: format!("TEST-{}", Uuid::new_v4().simple()),
+            symbol: "GSEC10Y".to_string(),
+            side,
+            order_type: OrderType::Limit,
+            quantity: dec!(100000),
+            price: Some(dec!(99.50)),
+            filled_quantity: Decimal::ZERO,
+            remaining_quantity: dec!(100000),
+            status: OrderStatus::Pending,
+            time_in_force: TimeInForce::GoodTillCancel,
+            timestamp: Utc::now(),
+            user_id: Uuid::new_v4(),
+            account_id: Uuid::new_v4(),
+            metadata: HashMap::new(),
+            strategy_id: None,
+            fills: OrderFills::default(),
+            cancel_reason: None,
+        })
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.0.symbol = symbol.to_string();
+        self
+    }
+
+    /// Sets the quantity, all of it still open.
+    pub fn quantity(mut self, quantity: Decimal) -> Self {
+        self.0.quantity = quantity;
+        self.0.remaining_quantity = quantity;
+        self
+    }
+
+    /// Sets the limit price; `None` leaves a limit order without one.
+    pub fn price(mut self, price: impl Into<Option<Decimal>>) -> Self {
+        self.0.price = price.into();
+        self
+    }
+
+    /// Makes it a market order, without a price.
+    pub fn market(mut self) -> Self {
+        self.0.order_type = OrderType::Market;
+        self.0.price = None;
+        self
+    }
+
+    pub fn order_type(mut self, order_type: OrderType) -> Self {
+        self.0.order_type = order_type;
+        self
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.0.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn account(mut self, account_id: Uuid) -> Self {
+        self.0.account_id = account_id;
+        self
+    }
+
+    pub fn user(mut self, user_id: Uuid) -> Self {
+        self.0.user_id = user_id;
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.0.timestamp = timestamp;
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.0.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn build(self) -> Order {
+        self.0
+    }
+}
@@ -1,9 +1,18 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
+pub use crate::utils::daycount::DayCount;
+
+/// Bond prices are quoted per 100 of face value; quantities are face value.
+pub const PRICE_QUOTE_BASIS: Decimal = Decimal::ONE_HUNDRED;
+
+pub fn notional_value(quantity: Decimal, price: Decimal) -> Decimal {
+    quantity * price / PRICE_QUOTE_BASIS
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderSide {
     Buy,
@@ -29,8 +38,126 @@ pub enum OrderStatus {
     PartiallyFilled,
     Filled,
     Cancelled,
-    Rejected,
+    Rejected(RejectReason),
+    Expired,
+    /// Held for a supervisor's approval before it may trade.
+    PendingApproval,
+}
+
+/// Why an order or request was turned away, for clients and dashboards
+/// to classify rejects by. The accompanying message has the detail.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    RiskLimit { limit: RiskLimitKind },
+    /// The order or request itself was malformed, naming the field at
+    /// fault where there is one.
+    Validation { field: Option<OrderField> },
+    /// The market or instrument is not open for this order.
+    SessionState,
+    Permission,
+    Throttle,
+    /// The engine failed; the order was not at fault.
+    Internal,
+    /// A dealer turned down the client's hit in its last-look window.
+    LastLook,
+}
+
+/// Why an order left the market before it filled.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum CancelReason {
+    /// The owner cancelled it, or withdrew it from approval.
+    ClientRequest,
+    /// The cancel leg of an amend.
+    Replaced,
+    /// Its time in force ran out.
     Expired,
+    InstrumentSuspended,
+    InstrumentDelisted,
+    /// Left unfilled when its auction uncrossed.
+    AuctionUncrossed,
+    /// Purged as a stale good-till-cancel order to free book capacity.
+    StalePurge,
+    /// Cancelled by the stop-out of an account below its margin.
+    Liquidation,
+    /// What an immediate market order could not fill within its collar.
+    MarketCollar,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RiskLimitKind {
+    OrderValue,
+    PositionSize,
+    OpenOrders,
+    OrderToTradeRatio,
+    /// A soft limit breached without an approved override.
+    SoftLimit,
+    /// A limit of the account's group.
+    Group,
+    ClearingExposure,
+    Balance,
+    BookCapacity,
+    TenorBucket,
+    Concentration,
+    /// A bilateral credit line between the two sides of a trade.
+    CreditLine,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum OrderField {
+    Symbol,
+    Quantity,
+    Price,
+    OrderType,
+    TimeInForce,
+    Strategy,
+}
+
+impl RejectReason {
+    /// Metrics label, e.g. `risk_limit.order_value` or `validation.price`.
+    pub fn label(&self) -> String {
+        match self {
+            RejectReason::RiskLimit { limit } => format!("risk_limit.{}", limit.name()),
+            RejectReason::Validation { field: Some(field) } => format!("validation.{}", field.name()),
+            RejectReason::Validation { field: None } => "validation".to_string(),
+            RejectReason::SessionState => "session_state".to_string(),
+            RejectReason::Permission => "permission".to_string(),
+            RejectReason::Throttle => "throttle".to_string(),
+            RejectReason::Internal => "internal".to_string(),
+            RejectReason::LastLook => "last_look".to_string(),
+        }
+    }
+}
+
+impl RiskLimitKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RiskLimitKind::OrderValue => "order_value",
+            RiskLimitKind::PositionSize => "position_size",
+            RiskLimitKind::OpenOrders => "open_orders",
+            RiskLimitKind::OrderToTradeRatio => "order_to_trade_ratio",
+            RiskLimitKind::SoftLimit => "soft_limit",
+            RiskLimitKind::Group => "group",
+            RiskLimitKind::ClearingExposure => "clearing_exposure",
+            RiskLimitKind::Balance => "balance",
+            RiskLimitKind::BookCapacity => "book_capacity",
+            RiskLimitKind::TenorBucket => "tenor_bucket",
+            RiskLimitKind::Concentration => "concentration",
+            RiskLimitKind::CreditLine => "credit_line",
+        }
+    }
+}
+
+impl OrderField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrderField::Symbol => "symbol",
+            OrderField::Quantity => "quantity",
+            OrderField::Price => "price",
+            OrderField::OrderType => "order_type",
+            OrderField::TimeInForce => "time_in_force",
+            OrderField::Strategy => "strategy",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +177,60 @@ pub struct Order {
     pub account_id: Uuid,
     pub time_in_force: TimeInForce,
     pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub strategy_id: Option<String>,
+    #[serde(flatten)]
+    pub fills: OrderFills,
+    /// Why the order stopped working, once cancelled or expired.
+    #[serde(default)]
+    pub cancel_reason: Option<CancelReason>,
+}
+
+/// Prices of an order's fills so far, kept alongside its filled quantity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct OrderFills {
+    /// Average clean price of the fills; unset before the first.
+    pub avg_fill_price: Option<Decimal>,
+    pub last_fill_price: Option<Decimal>,
+    pub last_fill_quantity: Decimal,
+    /// Notional value of the fills, as `notional_value` gives it.
+    pub cumulative_notional: Decimal,
+}
+
+impl OrderFills {
+    /// Adds a fill, given the order's filled quantity including it.
+    pub fn record(&mut self, quantity: Decimal, price: Decimal, filled_quantity: Decimal) {
+        self.last_fill_quantity = quantity;
+        self.last_fill_price = Some(price);
+        self.cumulative_notional += notional_value(quantity, price);
+        if !filled_quantity.is_zero() {
+            self.avg_fill_price = Some(self.cumulative_notional * PRICE_QUOTE_BASIS / filled_quantity);
+        }
+    }
+}
+
+impl Order {
+    /// Absolute time at which a resting order lapses, if its order type or
+    /// time in force gives it one. Day orders lapse at the next UTC midnight.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        if let OrderType::GoodTillDate { expiry } = &self.order_type {
+            return Some(*expiry);
+        }
+
+        match &self.time_in_force {
+            TimeInForce::GoodTillDate(expiry) | TimeInForce::GoodTillTime(expiry) => Some(*expiry),
+            TimeInForce::GoodForDay => {
+                let next_day = self.timestamp.date_naive() + Duration::days(1);
+                Some(next_day.and_hms_opt(0, 0, 0)?.and_utc())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, OrderStatus::Pending | OrderStatus::PartiallyFilled)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -58,27 +239,125 @@ pub enum TimeInForce {
     ImmediateOrCancel,
     FillOrKill,
     GoodTillDate(DateTime<Utc>),
+    /// Lapses at an exact instant, removed within the configured expiry
+    /// tolerance.
+    GoodTillTime(DateTime<Utc>),
     GoodForDay,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub id: Uuid,
+    /// `<engine instance>-<sequence>`, unique alongside `id`.
+    #[serde(default)]
+    pub trade_number: String,
     pub symbol: String,
     pub buyer_order_id: Uuid,
     pub seller_order_id: Uuid,
+    pub buyer_account_id: Uuid,
+    pub seller_account_id: Uuid,
     pub quantity: Decimal,
+    /// Clean price.
     pub price: Decimal,
+    /// Per 100 face, as of the settlement date.
+    #[serde(default)]
+    pub accrued_interest: Decimal,
+    /// The price in the instrument's quoting convention.
+    #[serde(default)]
+    pub quoted_price: Decimal,
+    /// Cash the buyer pays on the settlement date: face value at the clean
+    /// price plus accrued interest, indexed for inflation-linked bonds.
+    #[serde(default)]
+    pub settlement_amount: Decimal,
+    /// Index ratio on the settlement date, for inflation-linked bonds.
+    #[serde(default)]
+    pub index_ratio: Option<Decimal>,
     pub timestamp: DateTime<Utc>,
+    pub settlement_date: NaiveDate,
     pub trade_type: TradeType,
+    pub aggressor_side: OrderSide,
+    pub maker_order_id: Uuid,
+    #[serde(default)]
+    pub buyer_fees: FeeBreakdown,
+    #[serde(default)]
+    pub seller_fees: FeeBreakdown,
+    #[serde(default)]
+    pub buyer_strategy_id: Option<String>,
+    #[serde(default)]
+    pub seller_strategy_id: Option<String>,
+    /// Crossed against the same firm's resting interest.
+    #[serde(default)]
+    pub internalized: bool,
+}
+
+impl Trade {
+    pub fn notional(&self) -> Decimal {
+        notional_value(self.quantity, self.price)
+    }
+
+    pub fn liquidity_role(&self, side: &OrderSide) -> LiquidityRole {
+        if *side == self.aggressor_side {
+            LiquidityRole::Taker
+        } else {
+            LiquidityRole::Maker
+        }
+    }
+
+    pub fn account_for(&self, side: &OrderSide) -> Uuid {
+        match side {
+            OrderSide::Buy => self.buyer_account_id,
+            OrderSide::Sell => self.seller_account_id,
+        }
+    }
+
+    pub fn fees_for(&self, side: &OrderSide) -> &FeeBreakdown {
+        match side {
+            OrderSide::Buy => &self.buyer_fees,
+            OrderSide::Sell => &self.seller_fees,
+        }
+    }
+
+    /// Cash the buyer pays or the seller receives at settlement, net of fees.
+    pub fn settlement_amount(&self, side: &OrderSide) -> Decimal {
+        match side {
+            OrderSide::Buy => self.notional() + self.buyer_fees.total,
+            OrderSide::Sell => self.notional() - self.seller_fees.total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LiquidityRole {
+    Maker,
+    Taker,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FeeBreakdown {
+    pub role: Option<LiquidityRole>,
+    pub exchange_fee: Decimal,
+    pub brokerage: Decimal,
+    pub tax: Decimal,
+    pub stamp_duty: Decimal,
+    pub total: Decimal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TradeType {
     Regular,
     Block,
+    Auction,
+    SpreadLeg,
     Repo,
     ReverseRepo,
+    /// Internal transfer between accounts of one firm to offset positions.
+    Compression,
+    /// A client hit on a dealer's streamed quote.
+    DealerToClient,
+    /// A retail order filled by a provider's improvement on the touch.
+    PriceImprovement,
+    /// Matched on a spread to a benchmark, priced from the benchmark's mark.
+    BenchmarkSpread,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,9 +366,13 @@ pub struct Position {
     pub account_id: Uuid,
     pub quantity: Decimal,
     pub average_price: Decimal,
+    /// Indexed for inflation-linked bonds.
     pub market_value: Decimal,
     pub unrealized_pnl: Decimal,
     pub realized_pnl: Decimal,
+    /// Interest accrued on the position as of the next settlement date.
+    #[serde(default)]
+    pub accrued_interest: Decimal,
     pub last_updated: DateTime<Utc>,
 }
 
@@ -106,6 +389,9 @@ pub struct PriceLevel {
     pub price: Decimal,
     pub quantity: Decimal,
     pub order_count: u32,
+    /// Liquidity derived from spread orders rather than resting outrights.
+    #[serde(default)]
+    pub implied: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,9 +405,85 @@ pub struct Bond {
     pub bond_type: BondType,
     pub rating: Option<String>,
     pub is_active: bool,
+    /// Whether order entry and market data for this bond are in clean or
+    /// dirty prices. Matching is always on clean prices.
+    #[serde(default)]
+    pub quote_convention: QuoteConvention,
+    /// Coupons per year; zero for discount instruments.
+    #[serde(default = "default_coupon_frequency")]
+    pub coupon_frequency: u32,
+    #[serde(default = "default_day_count")]
+    pub day_count: DayCount,
+    /// Set for floating-rate bonds, whose coupon is reset from a reference
+    /// index every coupon period. `coupon_rate` is then only the rate used
+    /// before the index has any fixings.
+    #[serde(default)]
+    pub floating_rate: Option<FloatingRateTerms>,
+    /// Set for inflation-linked bonds, whose principal and coupons are
+    /// scaled by the index ratio. Prices and coupon rates stay real.
+    #[serde(default)]
+    pub inflation_linked: Option<InflationTerms>,
+    /// Dates from which the issuer may redeem early, and at what price.
+    #[serde(default)]
+    pub call_schedule: Vec<RedemptionOption>,
+    /// Dates on which holders may sell the bond back to the issuer.
+    #[serde(default)]
+    pub put_schedule: Vec<RedemptionOption>,
+}
+
+/// One exercise date of an embedded call or put, redeeming at `price` per
+/// 100 face plus accrued interest.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RedemptionOption {
+    pub date: NaiveDate,
+    pub price: Decimal,
 }
 
+/// Coupon terms of a floating-rate bond. Each coupon period pays the index
+/// fixing taken `fixing_lag_days` before the period starts, plus the spread.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FloatingRateTerms {
+    /// Reference index name, e.g. MIBOR or TBILL91.
+    pub index: String,
+    pub spread_bps: Decimal,
+    #[serde(default)]
+    pub fixing_lag_days: u32,
+}
+
+/// Indexation terms of an inflation-linked bond. The reference index for a
+/// date is interpolated between the monthly levels `lag_months` and one
+/// month less before it; the index ratio is that over `base_index`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InflationTerms {
+    /// Price index name, e.g. CPI-C.
+    pub index: String,
+    /// Reference index at the issue date.
+    pub base_index: Decimal,
+    #[serde(default = "default_index_lag_months")]
+    pub lag_months: u32,
+}
+
+fn default_index_lag_months() -> u32 {
+    3
+}
+
+fn default_coupon_frequency() -> u32 {
+    2
+}
+
+fn default_day_count() -> DayCount {
+    DayCount::ThirtyE360
+}
+
+/// Clean prices exclude accrued interest, dirty prices include it.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum QuoteConvention {
+    #[default]
+    Clean,
+    Dirty,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum BondType {
     GovernmentSecurity,
     TreasuryBill,
@@ -132,6 +494,17 @@ pub enum BondType {
     CommercialPaper,
 }
 
+impl BondType {
+    /// Discount instruments priced on a money-market yield rather than as
+    /// coupon bonds.
+    pub fn is_money_market(&self) -> bool {
+        matches!(
+            self,
+            BondType::TreasuryBill | BondType::CertificateOfDeposit | BondType::CommercialPaper
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketData {
     pub symbol: String,
@@ -141,10 +514,58 @@ pub struct MarketData {
     pub volume: Decimal,
     pub timestamp: DateTime<Utc>,
     pub yield_to_maturity: Option<Decimal>,
+    /// Lowest of the yields to maturity and to each remaining call date.
+    #[serde(default)]
+    pub yield_to_worst: Option<Decimal>,
     pub duration: Option<Decimal>,
     pub accrued_interest: Option<Decimal>,
 }
 
+/// Remaining-maturity band that a bond's exposure counts toward. Each
+/// bucket includes its upper bound.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TenorBucket {
+    #[serde(rename = "0-1y")]
+    UpToOneYear,
+    #[serde(rename = "1-5y")]
+    OneToFiveYears,
+    #[serde(rename = "5-10y")]
+    FiveToTenYears,
+    #[serde(rename = "10y+")]
+    OverTenYears,
+}
+
+impl TenorBucket {
+    pub const ALL: [TenorBucket; 4] = [
+        TenorBucket::UpToOneYear,
+        TenorBucket::OneToFiveYears,
+        TenorBucket::FiveToTenYears,
+        TenorBucket::OverTenYears,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TenorBucket::UpToOneYear => "0-1y",
+            TenorBucket::OneToFiveYears => "1-5y",
+            TenorBucket::FiveToTenYears => "5-10y",
+            TenorBucket::OverTenYears => "10y+",
+        }
+    }
+
+    pub fn classify(maturity: NaiveDate, as_of: NaiveDate) -> Self {
+        let years_out = |years: u32| as_of.checked_add_months(chrono::Months::new(12 * years));
+        if years_out(1).is_none_or(|bound| maturity <= bound) {
+            TenorBucket::UpToOneYear
+        } else if years_out(5).is_none_or(|bound| maturity <= bound) {
+            TenorBucket::OneToFiveYears
+        } else if years_out(10).is_none_or(|bound| maturity <= bound) {
+            TenorBucket::FiveToTenYears
+        } else {
+            TenorBucket::OverTenYears
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RiskLimits {
     pub account_id: Uuid,
@@ -153,6 +574,52 @@ pub struct RiskLimits {
     pub max_daily_loss: Decimal,
     pub concentration_limit: Decimal,
     pub var_limit: Decimal,
+    /// Orders resting on the book across all symbols.
+    #[serde(default = "default_max_open_orders")]
+    pub max_open_orders: u32,
+    #[serde(default = "default_max_open_orders_per_symbol")]
+    pub max_open_orders_per_symbol: u32,
+    /// Order messages (submits, cancels, amends) per executed trade.
+    #[serde(default = "default_max_order_to_trade_ratio")]
+    pub max_order_to_trade_ratio: Decimal,
+    /// How long an order must rest before it may be cancelled or amended;
+    /// zero turns the check off.
+    #[serde(default = "default_min_quote_life_ms")]
+    pub min_quote_life_ms: u64,
+    /// Cancels and amends per symbol in any one second; zero is no cap.
+    #[serde(default = "default_max_quote_updates_per_second")]
+    pub max_quote_updates_per_second: u32,
+    /// Soft limits sit below the hard ones above. Breaching one needs an
+    /// override granted by a second person; breaching a hard limit always
+    /// rejects.
+    #[serde(default)]
+    pub soft_max_order_value: Option<Decimal>,
+    #[serde(default)]
+    pub soft_max_position_size: Option<Decimal>,
+    /// Net face value the account could reach in each tenor bucket if its
+    /// resting orders on one side filled. Buckets not listed are unlimited.
+    #[serde(default)]
+    pub max_tenor_bucket_position: BTreeMap<TenorBucket, Decimal>,
+}
+
+fn default_max_open_orders() -> u32 {
+    crate::config::Config::default().default_max_open_orders
+}
+
+fn default_max_open_orders_per_symbol() -> u32 {
+    crate::config::Config::default().default_max_open_orders_per_symbol
+}
+
+fn default_max_order_to_trade_ratio() -> Decimal {
+    crate::config::Config::default().default_max_order_to_trade_ratio
+}
+
+fn default_min_quote_life_ms() -> u64 {
+    crate::config::Config::default().default_min_quote_life_ms
+}
+
+fn default_max_quote_updates_per_second() -> u32 {
+    crate::config::Config::default().default_max_quote_updates_per_second
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -161,12 +628,32 @@ pub enum TradingError {
     OrderNotFound(String),
     #[error("Insufficient balance: required {required}, available {available}")]
     InsufficientBalance { required: Decimal, available: Decimal },
-    #[error("Risk limit exceeded: {0}")]
-    RiskLimitExceeded(String),
+    #[error("Risk limit exceeded: {message}")]
+    RiskLimitExceeded { limit: RiskLimitKind, message: String },
     #[error("Invalid order: {0}")]
     InvalidOrder(String),
+    #[error("Invalid order: {message}")]
+    InvalidOrderField { field: OrderField, message: String },
     #[error("Market closed")]
     MarketClosed,
+    #[error("Instrument not found: {0}")]
+    InstrumentNotFound(String),
+    #[error("Instrument not tradable: {0}")]
+    InstrumentNotTradable(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("Minimum quote life not reached: {remaining_ms}ms left")]
+    MinimumQuoteLife { remaining_ms: i64 },
+    #[error("Quote update rate exceeded: {0}")]
+    QuoteRateExceeded(String),
+    #[error("Book capacity exceeded: {0}")]
+    BookCapacityExceeded(String),
+    #[error("Event consumer stalled: order entry held back for {waited_ms}ms")]
+    ConsumerStalled { waited_ms: u64 },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
     #[error("Redis error: {0}")]
@@ -175,4 +662,68 @@ pub enum TradingError {
     InternalError(String),
 }
 
+impl TradingError {
+    pub fn reject_reason(&self) -> RejectReason {
+        match self {
+            TradingError::RiskLimitExceeded { limit, .. } => RejectReason::RiskLimit { limit: *limit },
+            TradingError::InsufficientBalance { .. } => RejectReason::RiskLimit {
+                limit: RiskLimitKind::Balance,
+            },
+            TradingError::BookCapacityExceeded(_) => RejectReason::RiskLimit {
+                limit: RiskLimitKind::BookCapacity,
+            },
+            TradingError::InvalidOrderField { field, .. } => RejectReason::Validation { field: Some(*field) },
+            TradingError::InstrumentNotFound(_) => RejectReason::Validation {
+                field: Some(OrderField::Symbol),
+            },
+            TradingError::InvalidOrder(_) | TradingError::InvalidRequest(_) | TradingError::OrderNotFound(_) => {
+                RejectReason::Validation { field: None }
+            }
+            TradingError::MarketClosed | TradingError::InstrumentNotTradable(_) => RejectReason::SessionState,
+            TradingError::Unauthorized(_) | TradingError::PermissionDenied(_) => RejectReason::Permission,
+            TradingError::MinimumQuoteLife { .. }
+            | TradingError::QuoteRateExceeded(_)
+            | TradingError::ConsumerStalled { .. } => RejectReason::Throttle,
+            TradingError::DatabaseError(_) | TradingError::RedisError(_) | TradingError::InternalError(_) => {
+                RejectReason::Internal
+            }
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, TradingError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_fills_track_notional_and_average_price() {
+        // A bond quoted per 100 of face value
+        let mut bond = OrderFills::default();
+        bond.record(dec!(60000), dec!(99.50), dec!(60000));
+        assert_eq!(bond.cumulative_notional, dec!(59700));
+        assert_eq!(bond.avg_fill_price, Some(dec!(99.50)));
+        bond.record(dec!(40000), dec!(100.125), dec!(100000));
+        // 59,700 + 40,050
+        assert_eq!(bond.cumulative_notional, dec!(99750));
+        assert_eq!(bond.avg_fill_price, Some(dec!(99.75)));
+        assert_eq!((bond.last_fill_quantity, bond.last_fill_price), (dec!(40000), Some(dec!(100.125))));
+
+        // Whole units at a price per unit: the notional is still on the
+        // engine's per-100 basis, and the average is the plain
+        // volume-weighted price
+        let mut equity = OrderFills::default();
+        equity.record(dec!(250), dec!(1520.35), dec!(250));
+        equity.record(dec!(150), dec!(1519.90), dec!(400));
+        // (380,087.50 + 227,985.00) / 100
+        assert_eq!(equity.cumulative_notional, dec!(6080.725));
+        // 608,072.50 / 400
+        assert_eq!(equity.avg_fill_price, Some(dec!(1520.18125)));
+
+        let mut unfilled = OrderFills::default();
+        unfilled.record(Decimal::ZERO, dec!(99.50), Decimal::ZERO);
+        assert_eq!(unfilled.avg_fill_price, None);
+    }
+}
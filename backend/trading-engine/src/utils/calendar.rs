@@ -0,0 +1,164 @@
+//! Market holiday calendars. Each market has its own holidays on top of
+//! Saturday and Sunday; settlement dates are counted in business days of
+//! the settlement market, so every date the engine settles or accrues to
+//! lands on a day that market is open.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+/// A market's holidays and business days in one year.
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarYear {
+    pub market: String,
+    pub year: i32,
+    pub holidays: Vec<Holiday>,
+    pub business_days: u32,
+}
+
+pub struct TradingCalendar {
+    holidays: DashMap<String, BTreeMap<NaiveDate, String>>,
+    settlement_market: RwLock<String>,
+}
+
+impl TradingCalendar {
+    pub fn new() -> Self {
+        Self {
+            holidays: DashMap::new(),
+            settlement_market: RwLock::new(String::new()),
+        }
+    }
+
+    pub fn settlement_market(&self) -> String {
+        self.settlement_market.read().clone()
+    }
+
+    pub fn set_settlement_market(&self, market: &str) {
+        *self.settlement_market.write() = market.to_uppercase();
+    }
+
+    /// Replaces the market's holidays in the years `holidays` covers,
+    /// leaving other years as they were.
+    pub fn set_holidays(&self, market: &str, holidays: Vec<Holiday>) {
+        let mut calendar = self.holidays.entry(market.to_uppercase()).or_default();
+        let years: Vec<i32> = holidays.iter().map(|holiday| holiday.date.year()).collect();
+        calendar.retain(|date, _| !years.contains(&date.year()));
+        calendar.extend(holidays.into_iter().map(|holiday| (holiday.date, holiday.name)));
+    }
+
+    pub fn markets(&self) -> Vec<String> {
+        let mut markets: Vec<String> = self.holidays.iter().map(|entry| entry.key().clone()).collect();
+        markets.sort();
+        markets
+    }
+
+    pub fn is_business_day(&self, market: &str, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && self
+                .holidays
+                .get(&market.to_uppercase())
+                .is_none_or(|calendar| !calendar.contains_key(&date))
+    }
+
+    /// `cycle_days` business days of `market` after `trade_date`.
+    pub fn settlement_date(&self, market: &str, trade_date: NaiveDate, cycle_days: u32) -> NaiveDate {
+        let mut date = trade_date;
+        let mut remaining = cycle_days;
+        while remaining > 0 {
+            date += Duration::days(1);
+            if self.is_business_day(market, date) {
+                remaining -= 1;
+            }
+        }
+        date
+    }
+
+    pub fn year(&self, market: &str, year: i32) -> Option<CalendarYear> {
+        let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let last = NaiveDate::from_ymd_opt(year, 12, 31)?;
+        let market = market.to_uppercase();
+        let holidays = self
+            .holidays
+            .get(&market)
+            .map(|calendar| {
+                calendar
+                    .range(first..=last)
+                    .map(|(date, name)| Holiday {
+                        date: *date,
+                        name: name.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let business_days = first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .filter(|date| self.is_business_day(&market, *date))
+            .count() as u32;
+        Some(CalendarYear {
+            market,
+            year,
+            holidays,
+            business_days,
+        })
+    }
+}
+
+impl Default for TradingCalendar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A settlement cycle written as `T+1` or `1`. A `+` left unescaped in a
+/// query string arrives as a space, so `T 1` is read the same.
+pub fn parse_cycle(cycle: &str) -> Option<u32> {
+    let cycle = cycle.trim();
+    let days = match cycle.strip_prefix(['T', 't']) {
+        Some(rest) => rest.trim_start().strip_prefix('+').unwrap_or(rest).trim_start(),
+        None => cycle,
+    };
+    days.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_settlement_skips_market_holidays() {
+        let calendar = TradingCalendar::new();
+        calendar.set_holidays(
+            "in",
+            vec![Holiday {
+                date: date(2024, 1, 26),
+                name: "Republic Day".to_string(),
+            }],
+        );
+
+        // Thursday trade, Friday holiday, settles Monday
+        assert_eq!(calendar.settlement_date("IN", date(2024, 1, 25), 1), date(2024, 1, 29));
+        assert_eq!(calendar.settlement_date("US", date(2024, 1, 25), 1), date(2024, 1, 26));
+
+        let year = calendar.year("IN", 2024).unwrap();
+        assert_eq!(year.holidays.len(), 1);
+        assert_eq!(year.business_days, 261);
+
+        assert_eq!(parse_cycle("T+2"), Some(2));
+        assert_eq!(parse_cycle("T 1"), Some(1));
+        assert_eq!(parse_cycle("3"), Some(3));
+        assert_eq!(parse_cycle("T-1"), None);
+    }
+}
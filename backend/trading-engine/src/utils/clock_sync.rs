@@ -0,0 +1,412 @@
+//! How far the engine's clock may be from UTC. A `TimeSource` measures the
+//! local clock against a reference; `ClockQuality` keeps the latest
+//! measurement, estimates drift between measurements, and grades the clock
+//! against the configured tolerances so every timestamp can carry the
+//! grade it was taken under.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ClockStatus {
+    /// Within tolerance of the reference.
+    Synchronized,
+    /// Measured, but off by more than the tolerances allow.
+    Degraded,
+    /// Not disciplined, or the reference could not be reached.
+    Unsynchronized,
+    /// Never measured.
+    #[default]
+    Unknown,
+}
+
+/// Clock quality attached to a timestamp.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Default)]
+pub struct ClockStamp {
+    pub status: ClockStatus,
+    /// Bound on the distance between the timestamp and UTC.
+    pub max_error_us: Option<u64>,
+}
+
+/// One comparison of the local clock with a reference. `offset_us` is
+/// reference minus local.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockSample {
+    pub offset_us: i64,
+    pub uncertainty_us: u64,
+    pub synchronized: bool,
+}
+
+#[async_trait]
+pub trait TimeSource: Send + Sync {
+    fn name(&self) -> String;
+    async fn sample(&self) -> anyhow::Result<ClockSample>;
+}
+
+/// The kernel's own view of its synchronization, as maintained by
+/// whichever NTP or PTP daemon disciplines the system clock.
+pub struct KernelTimeSource;
+
+#[async_trait]
+impl TimeSource for KernelTimeSource {
+    fn name(&self) -> String {
+        "kernel".to_string()
+    }
+
+    async fn sample(&self) -> anyhow::Result<ClockSample> {
+        kernel_sample()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn kernel_sample() -> anyhow::Result<ClockSample> {
+    // SAFETY: a zeroed timex with no mode bits set only reads the state
+    let (state, timex) = unsafe {
+        let mut timex: libc::timex = std::mem::zeroed();
+        (libc::adjtimex(&mut timex), timex)
+    };
+    if state < 0 {
+        anyhow::bail!("adjtimex failed: {}", std::io::Error::last_os_error());
+    }
+    let offset_us = if timex.status & libc::STA_NANO != 0 {
+        timex.offset / 1000
+    } else {
+        timex.offset
+    };
+    Ok(ClockSample {
+        offset_us: -offset_us,
+        uncertainty_us: timex.esterror.max(0) as u64,
+        synchronized: state != libc::TIME_ERROR && timex.status & libc::STA_UNSYNC == 0,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_sample() -> anyhow::Result<ClockSample> {
+    anyhow::bail!("Kernel clock state is only available on Linux")
+}
+
+/// Simple NTP query against one server.
+pub struct SntpTimeSource {
+    server: String,
+    timeout: Duration,
+}
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch.
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+impl SntpTimeSource {
+    pub fn new(server: &str) -> Self {
+        let server = if server.contains(':') {
+            server.to_string()
+        } else {
+            format!("{}:123", server)
+        };
+        Self {
+            server,
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+#[async_trait]
+impl TimeSource for SntpTimeSource {
+    fn name(&self) -> String {
+        format!("ntp:{}", self.server)
+    }
+
+    async fn sample(&self) -> anyhow::Result<ClockSample> {
+        let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(&self.server).await?;
+        let mut request = [0u8; 48];
+        // Leap indicator 0, version 4, client mode
+        request[0] = 0x23;
+
+        let sent = Utc::now();
+        socket.send(&request).await?;
+        let mut response = [0u8; 48];
+        let received = tokio::time::timeout(self.timeout, socket.recv(&mut response)).await??;
+        let arrived = Utc::now();
+        if received < 48 {
+            anyhow::bail!("Short NTP response from {}", self.server);
+        }
+        sntp_sample(&response, sent, arrived)
+    }
+}
+
+fn sntp_sample(response: &[u8; 48], sent: DateTime<Utc>, arrived: DateTime<Utc>) -> anyhow::Result<ClockSample> {
+    let leap = response[0] >> 6;
+    let stratum = response[1];
+    if stratum == 0 {
+        anyhow::bail!("NTP server refused the request");
+    }
+    let micros = |at: usize| -> i64 {
+        let seconds = u32::from_be_bytes([response[at], response[at + 1], response[at + 2], response[at + 3]]);
+        let fraction = u32::from_be_bytes([response[at + 4], response[at + 5], response[at + 6], response[at + 7]]);
+        (i64::from(seconds) - NTP_UNIX_OFFSET) * 1_000_000 + ((u64::from(fraction) * 1_000_000) >> 32) as i64
+    };
+    let (server_received, server_sent) = (micros(32), micros(40));
+    let (sent, arrived) = (sent.timestamp_micros(), arrived.timestamp_micros());
+
+    let offset_us = ((server_received - sent) + (server_sent - arrived)) / 2;
+    let delay_us = (arrived - sent) - (server_sent - server_received);
+    Ok(ClockSample {
+        offset_us,
+        uncertainty_us: (delay_us.max(0) / 2) as u64,
+        synchronized: leap != 3,
+    })
+}
+
+/// The source named by `CLOCK_SOURCE`: `kernel`, `ntp:<host>[:port]`, or
+/// `none`.
+pub fn source_from_config(name: &str) -> Option<Box<dyn TimeSource>> {
+    match name {
+        "kernel" => Some(Box::new(KernelTimeSource)),
+        other => other
+            .strip_prefix("ntp:")
+            .map(|server| Box::new(SntpTimeSource::new(server)) as Box<dyn TimeSource>),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClockTolerance {
+    pub max_error_us: u64,
+    pub max_drift_ppm: f64,
+}
+
+impl Default for ClockTolerance {
+    fn default() -> Self {
+        Self {
+            max_error_us: 1000,
+            max_drift_ppm: 50.0,
+        }
+    }
+}
+
+/// A change of clock grade, worth an alert.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockAlert {
+    pub source: String,
+    pub previous: ClockStatus,
+    pub status: ClockStatus,
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClockReport {
+    pub source: Option<String>,
+    pub status: ClockStatus,
+    pub offset_us: Option<i64>,
+    pub uncertainty_us: Option<u64>,
+    pub max_error_us: Option<u64>,
+    pub drift_ppm: Option<f64>,
+    pub last_sample_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub samples: u64,
+    pub failures: u64,
+    pub tolerance: Option<ClockTolerance>,
+}
+
+struct QualityState {
+    tolerance: ClockTolerance,
+    report: ClockReport,
+    stamp: ClockStamp,
+}
+
+pub struct ClockQuality {
+    state: RwLock<QualityState>,
+}
+
+impl Default for ClockQuality {
+    fn default() -> Self {
+        Self::new(ClockTolerance::default())
+    }
+}
+
+impl ClockQuality {
+    pub fn new(tolerance: ClockTolerance) -> Self {
+        Self {
+            state: RwLock::new(QualityState {
+                tolerance,
+                report: ClockReport {
+                    tolerance: Some(tolerance),
+                    ..ClockReport::default()
+                },
+                stamp: ClockStamp::default(),
+            }),
+        }
+    }
+
+    pub fn set_tolerance(&self, tolerance: ClockTolerance) {
+        let mut state = self.state.write();
+        state.tolerance = tolerance;
+        state.report.tolerance = Some(tolerance);
+    }
+
+    pub fn stamp(&self) -> ClockStamp {
+        self.state.read().stamp
+    }
+
+    pub fn report(&self) -> ClockReport {
+        self.state.read().report.clone()
+    }
+
+    /// Grades a new measurement taken at `at`, returning an alert if the
+    /// grade changed.
+    pub fn record(&self, source: &str, sample: ClockSample, at: DateTime<Utc>) -> Option<ClockAlert> {
+        let mut state = self.state.write();
+        let tolerance = state.tolerance;
+        let report = &mut state.report;
+
+        let drift_ppm = match (report.offset_us, report.last_sample_at) {
+            // Offset change in microseconds per second elapsed is ppm.
+            // Over less than a second it would be mostly measurement noise.
+            (Some(previous), Some(previous_at)) if at - previous_at >= chrono::Duration::seconds(1) => {
+                let elapsed = (at - previous_at).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6;
+                Some((sample.offset_us - previous) as f64 / elapsed)
+            }
+            _ => report.drift_ppm,
+        };
+        let max_error_us = sample.offset_us.unsigned_abs() + sample.uncertainty_us;
+
+        let (status, reason) = if !sample.synchronized {
+            (ClockStatus::Unsynchronized, "Clock is not synchronized to its reference".to_string())
+        } else if max_error_us > tolerance.max_error_us {
+            (
+                ClockStatus::Degraded,
+                format!("Error bound {}us exceeds {}us", max_error_us, tolerance.max_error_us),
+            )
+        } else if let Some(drift) = drift_ppm.filter(|drift| drift.abs() > tolerance.max_drift_ppm) {
+            (
+                ClockStatus::Degraded,
+                format!("Drift {:.1}ppm exceeds {}ppm", drift, tolerance.max_drift_ppm),
+            )
+        } else {
+            (ClockStatus::Synchronized, "Clock is within tolerance".to_string())
+        };
+
+        *report = ClockReport {
+            source: Some(source.to_string()),
+            status,
+            offset_us: Some(sample.offset_us),
+            uncertainty_us: Some(sample.uncertainty_us),
+            max_error_us: Some(max_error_us),
+            drift_ppm,
+            last_sample_at: Some(at),
+            last_error: None,
+            samples: report.samples + 1,
+            failures: report.failures,
+            tolerance: Some(tolerance),
+        };
+        let stamp = ClockStamp {
+            status,
+            max_error_us: Some(max_error_us),
+        };
+        Self::transition(&mut state, source, stamp, reason, at)
+    }
+
+    /// Records a failed measurement; the clock can no longer be traced to
+    /// its reference.
+    pub fn record_failure(&self, source: &str, error: String, at: DateTime<Utc>) -> Option<ClockAlert> {
+        let mut state = self.state.write();
+        state.report.source = Some(source.to_string());
+        state.report.status = ClockStatus::Unsynchronized;
+        state.report.last_error = Some(error.clone());
+        state.report.failures += 1;
+        let stamp = ClockStamp {
+            status: ClockStatus::Unsynchronized,
+            max_error_us: None,
+        };
+        Self::transition(&mut state, source, stamp, error, at)
+    }
+
+    fn transition(
+        state: &mut QualityState,
+        source: &str,
+        stamp: ClockStamp,
+        reason: String,
+        at: DateTime<Utc>,
+    ) -> Option<ClockAlert> {
+        let previous = std::mem::replace(&mut state.stamp, stamp).status;
+        (previous != stamp.status).then(|| ClockAlert {
+            source: source.to_string(),
+            previous,
+            status: stamp.status,
+            reason,
+            at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn sample(offset_us: i64) -> ClockSample {
+        ClockSample {
+            offset_us,
+            uncertainty_us: 100,
+            synchronized: true,
+        }
+    }
+
+    #[test]
+    fn test_clock_is_graded_on_error_bound_and_drift() {
+        let quality = ClockQuality::new(ClockTolerance {
+            max_error_us: 1000,
+            max_drift_ppm: 5.0,
+        });
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        assert_eq!(quality.stamp(), ClockStamp::default());
+
+        let alert = quality.record("ntp:test", sample(200), start).unwrap();
+        assert_eq!((alert.previous, alert.status), (ClockStatus::Unknown, ClockStatus::Synchronized));
+        assert_eq!(quality.stamp().max_error_us, Some(300));
+        // Unchanged grade, no alert
+        assert!(quality.record("ntp:test", sample(210), start + chrono::Duration::seconds(10)).is_none());
+
+        // 600us in 60s is 10ppm, over the drift tolerance
+        let alert = quality.record("ntp:test", sample(810), start + chrono::Duration::seconds(70)).unwrap();
+        assert_eq!(alert.status, ClockStatus::Degraded);
+        assert_eq!(quality.report().drift_ppm, Some(10.0));
+
+        let alert = quality
+            .record_failure("ntp:test", "timed out".to_string(), start + chrono::Duration::seconds(80))
+            .unwrap();
+        assert_eq!(alert.status, ClockStatus::Unsynchronized);
+        assert_eq!(quality.report().failures, 1);
+        assert_eq!(quality.report().samples, 3);
+    }
+
+    #[test]
+    fn test_sntp_offset_from_response_timestamps() {
+        let sent = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let arrived = sent + chrono::Duration::milliseconds(20);
+        let ntp = |at: DateTime<Utc>| {
+            let micros = at.timestamp_micros() + NTP_UNIX_OFFSET * 1_000_000;
+            let seconds = (micros / 1_000_000) as u32;
+            let fraction = (((micros % 1_000_000) as u64) << 32) / 1_000_000;
+            let mut bytes = seconds.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&(fraction as u32).to_be_bytes());
+            bytes
+        };
+        // Server is 5ms ahead and takes 2ms to answer
+        let mut response = [0u8; 48];
+        response[0] = 0x24;
+        response[1] = 1;
+        response[32..40].copy_from_slice(&ntp(sent + chrono::Duration::milliseconds(14)));
+        response[40..48].copy_from_slice(&ntp(sent + chrono::Duration::milliseconds(16)));
+
+        let sample = sntp_sample(&response, sent, arrived).unwrap();
+        assert!((sample.offset_us - 5000).abs() <= 1, "{}", sample.offset_us);
+        assert!((sample.uncertainty_us as i64 - 9000).abs() <= 1);
+        assert!(sample.synchronized);
+
+        response[1] = 0;
+        assert!(sntp_sample(&response, sent, arrived).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Log output. Operational logs go to stdout at a verbosity that can be
+//! changed while running; events logged to the `audit` target also go to
+//! the audit sink as one JSON object a line, flushed at once for trades so
+//! a crash cannot lose a fill that was logged. Audit events carry their
+//! own timestamp, read from the engine clock by whoever logs them, and the
+//! engine they came from; a tenant's events go to that tenant's sink.
+
+use crate::{config::Config, types::*};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets, layer::Context, prelude::*, reload, EnvFilter, Layer, Registry,
+};
+
+/// The target audit events are logged to.
+pub const AUDIT: &str = "audit";
+
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Audit events the sink failed to write since the process started.
+static AUDIT_WRITE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+pub fn audit_write_failures() -> u64 {
+    AUDIT_WRITE_FAILURES.load(Ordering::Relaxed)
+}
+
+/// Installs the global subscriber. `RUST_LOG` overrides the configured level.
+pub fn init(config: &Config) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(&config.log_level))?;
+    let (filter, handle) = reload::Layer::new(filter);
+    let audit = match audit_sink(&config.audit_log)? {
+        Some(sink) => {
+            let mut layer = AuditLayer::new(sink);
+            for tenant in config.tenants() {
+                let tenant = config.for_tenant(&tenant)?;
+                if let Some(sink) = audit_sink(&tenant.audit_log)? {
+                    layer = layer.with_engine(&tenant.engine_instance_id, sink);
+                }
+            }
+            Some(layer.with_filter(Targets::new().with_target(AUDIT, Level::TRACE)))
+        }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(filter))
+        .with(audit)
+        .try_init()?;
+    let _ = LOG_FILTER.set(handle);
+    Ok(())
+}
+
+fn audit_sink(target: &str) -> anyhow::Result<Option<Box<dyn Write + Send>>> {
+    Ok(match target {
+        "none" => None,
+        "stdout" => Some(Box::new(io::stdout())),
+        _ => {
+            let path = target
+                .strip_prefix("file:")
+                .ok_or_else(|| anyhow::anyhow!("Unknown audit log {}", target))?;
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Some(Box::new(BufWriter::new(file)))
+        }
+    })
+}
+
+/// The verbosity in effect, as filter directives. `None` before `init`.
+pub fn log_level() -> Option<String> {
+    LOG_FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Replaces the verbosity with `directives`, e.g. `debug` or
+/// `info,vedhavriddhi_trading_engine::engine::matching=trace`. Audit events
+/// are written to the audit sink whatever the level.
+pub fn set_log_level(directives: &str) -> Result<String> {
+    let handle = LOG_FILTER
+        .get()
+        .ok_or_else(|| TradingError::InternalError("Logging is not initialised".to_string()))?;
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| TradingError::InvalidRequest(format!("Invalid log level {}: {}", directives, e)))?;
+    let level = filter.to_string();
+    handle.reload(filter).map_err(|e| TradingError::InternalError(e.to_string()))?;
+    Ok(level)
+}
+
+/// Records an order's accepted, cancelled, rejected or expired state;
+/// `engine` is the instance id of the engine it happened on, `sequence`
+/// that event's place on its event journal and `at` the engine time it
+/// happened.
+pub fn audit_order(engine: &str, kind: &str, order: &Order, sequence: u64, at: DateTime<Utc>) {
+    tracing::info!(
+        target: AUDIT,
+        timestamp = %at.to_rfc3339(),
+        engine,
+        kind,
+        sequence,
+        order_id = %order.id,
+        client_order_id = %order.client_order_id,
+        account_id = %order.account_id,
+        symbol = %order.symbol,
+        side = ?order.side,
+        quantity = %order.quantity,
+        price = ?order.price,
+        status = ?order.status,
+    );
+}
+
+pub fn audit_trade(engine: &str, trade: &Trade, sequence: u64) {
+    tracing::info!(
+        target: AUDIT,
+        timestamp = %trade.timestamp.to_rfc3339(),
+        engine,
+        kind = "trade",
+        sequence,
+        trade_id = %trade.id,
+        trade_number = %trade.trade_number,
+        symbol = %trade.symbol,
+        buyer_order_id = %trade.buyer_order_id,
+        seller_order_id = %trade.seller_order_id,
+        buyer_account_id = %trade.buyer_account_id,
+        seller_account_id = %trade.seller_account_id,
+        quantity = %trade.quantity,
+        price = %trade.price,
+        trade_type = ?trade.trade_type,
+    );
+}
+
+struct AuditLayer {
+    sink: Mutex<Box<dyn Write + Send>>,
+    /// Sinks of tenant engines, by engine instance id. Events from any
+    /// other engine go to `sink`.
+    engines: HashMap<String, Mutex<Box<dyn Write + Send>>>,
+}
+
+impl AuditLayer {
+    fn new(sink: Box<dyn Write + Send>) -> Self {
+        Self {
+            sink: Mutex::new(sink),
+            engines: HashMap::new(),
+        }
+    }
+
+    fn with_engine(mut self, engine: &str, sink: Box<dyn Write + Send>) -> Self {
+        self.engines.insert(engine.to_string(), Mutex::new(sink));
+        self
+    }
+}
+
+impl<S: Subscriber> Layer<S> for AuditLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = JsonFields(Map::new());
+        event.record(&mut fields);
+        let trade = fields.0.get("kind").and_then(Value::as_str) == Some("trade");
+
+        let sink = fields
+            .0
+            .get("engine")
+            .and_then(Value::as_str)
+            .and_then(|engine| self.engines.get(engine))
+            .unwrap_or(&self.sink);
+        let written = {
+            let mut sink = sink.lock();
+            serde_json::to_writer(&mut *sink, &fields.0)
+                .map_err(io::Error::from)
+                .and_then(|_| sink.write_all(b"\n"))
+                .and_then(|_| if trade { sink.flush() } else { Ok(()) })
+        };
+        if let Err(e) = written {
+            AUDIT_WRITE_FAILURES.fetch_add(1, Ordering::Relaxed);
+            tracing::error!("Failed to write audit event: {}", e);
+        }
+    }
+}
+
+/// An event's fields as JSON, keeping numbers and flags typed.
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::String(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::String(value.to_string()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[derive(Clone, Default)]
+    struct SharedSink {
+        written: Arc<Mutex<Vec<u8>>>,
+        flushes: Arc<Mutex<usize>>,
+    }
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushes.lock() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_audit_events_are_json_lines_flushed_on_trades() {
+        let sink = SharedSink::default();
+        let subscriber = tracing_subscriber::registry().with(
+            AuditLayer::new(Box::new(sink.clone())).with_filter(Targets::new().with_target(AUDIT, Level::TRACE)),
+        );
+        let order_id = Uuid::new_v4();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                target: AUDIT,
+                timestamp = "2024-03-04T10:00:00+00:00",
+                kind = "order_accepted",
+                order_id = %order_id,
+                sequence = 7u64
+            );
+            tracing::info!("Not an audit event");
+            assert_eq!(*sink.flushes.lock(), 0);
+            tracing::info!(target: AUDIT, kind = "trade", trade_id = "T-1", symbol = "GSEC10Y");
+        });
+
+        let written = String::from_utf8(sink.written.lock().clone()).unwrap();
+        let lines: Vec<Value> = written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["timestamp"], "2024-03-04T10:00:00+00:00");
+        assert_eq!(lines[0]["order_id"], order_id.to_string());
+        assert_eq!(lines[0]["sequence"], 7);
+        assert_eq!(lines[1]["symbol"], "GSEC10Y");
+        assert_eq!(*sink.flushes.lock(), 1);
+    }
+
+    #[test]
+    fn test_tenant_events_go_to_their_own_sink() {
+        let (shared, venue_a) = (SharedSink::default(), SharedSink::default());
+        let layer = AuditLayer::new(Box::new(shared.clone())).with_engine("VV-VENUE_A", Box::new(venue_a.clone()));
+        let subscriber =
+            tracing_subscriber::registry().with(layer.with_filter(Targets::new().with_target(AUDIT, Level::TRACE)));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: AUDIT, engine = "VV-VENUE_A", kind = "order_accepted", symbol = "GSEC10Y");
+            tracing::info!(target: AUDIT, engine = "VV-VENUE_B", kind = "order_accepted", symbol = "GSEC5Y");
+        });
+
+        let lines = |sink: &SharedSink| -> Vec<Value> {
+            let written = String::from_utf8(sink.written.lock().clone()).unwrap();
+            written.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        };
+        let (shared, venue_a) = (lines(&shared), lines(&venue_a));
+        assert_eq!(venue_a.len(), 1);
+        assert_eq!(venue_a[0]["symbol"], "GSEC10Y");
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0]["engine"], "VV-VENUE_B");
+    }
+
+    struct FailingSink;
+
+    impl Write for FailingSink {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lost_audit_writes_are_counted() {
+        let subscriber = tracing_subscriber::registry()
+            .with(AuditLayer::new(Box::new(FailingSink)).with_filter(Targets::new().with_target(AUDIT, Level::TRACE)));
+        let before = audit_write_failures();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: AUDIT, kind = "trade", trade_id = "T-1");
+        });
+        assert!(audit_write_failures() > before);
+    }
+}
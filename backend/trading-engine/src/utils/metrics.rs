@@ -0,0 +1,119 @@
+use crate::{types::RejectReason, utils::logging};
+use chrono::NaiveDate;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Default)]
+pub struct Metrics {
+    orders_submitted: AtomicU64,
+    orders_cancelled: AtomicU64,
+    trades_executed: AtomicU64,
+    risk_violations: AtomicU64,
+    webhook_deliveries: AtomicU64,
+    webhook_retries: AtomicU64,
+    webhook_failures: AtomicU64,
+    consumer_stalls: AtomicU64,
+    /// Violations on the most recent day one was recorded.
+    daily_risk_violations: Mutex<Option<(NaiveDate, u64)>>,
+    /// Rejected orders by reason label.
+    order_rejects: Mutex<BTreeMap<String, u64>>,
+    /// Rejected orders by the validator that turned them away.
+    validator_rejects: Mutex<BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub orders_submitted: u64,
+    pub orders_cancelled: u64,
+    pub trades_executed: u64,
+    pub risk_violations: u64,
+    pub webhook_deliveries: u64,
+    pub webhook_retries: u64,
+    /// Webhook requests abandoned after the last retry.
+    pub webhook_failures: u64,
+    /// Orders turned away because a lossless event consumer fell too far
+    /// behind.
+    pub consumer_stalls: u64,
+    pub order_rejects: BTreeMap<String, u64>,
+    pub validator_rejects: BTreeMap<String, u64>,
+    /// Audit events lost to a failing audit sink.
+    pub audit_write_failures: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment_orders_submitted(&self) {
+        self.orders_submitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_orders_cancelled(&self) {
+        self.orders_cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_trades_executed(&self) {
+        self.trades_executed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_risk_violations(&self, day: NaiveDate) {
+        self.risk_violations.fetch_add(1, Ordering::Relaxed);
+        let mut daily = self.daily_risk_violations.lock();
+        match daily.as_mut() {
+            Some((current, count)) if *current == day => *count += 1,
+            _ => *daily = Some((day, 1)),
+        }
+    }
+
+    pub fn increment_order_rejects(&self, reason: &RejectReason) {
+        *self.order_rejects.lock().entry(reason.label()).or_insert(0) += 1;
+    }
+
+    pub fn increment_validator_rejects(&self, validator: &str) {
+        *self.validator_rejects.lock().entry(validator.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn increment_webhook_deliveries(&self) {
+        self.webhook_deliveries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_webhook_retries(&self) {
+        self.webhook_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_webhook_failures(&self) {
+        self.webhook_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn increment_consumer_stalls(&self) {
+        self.consumer_stalls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn risk_violations_on(&self, day: NaiveDate) -> u64 {
+        match *self.daily_risk_violations.lock() {
+            Some((current, count)) if current == day => count,
+            _ => 0,
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            orders_submitted: self.orders_submitted.load(Ordering::Relaxed),
+            orders_cancelled: self.orders_cancelled.load(Ordering::Relaxed),
+            trades_executed: self.trades_executed.load(Ordering::Relaxed),
+            risk_violations: self.risk_violations.load(Ordering::Relaxed),
+            webhook_deliveries: self.webhook_deliveries.load(Ordering::Relaxed),
+            webhook_retries: self.webhook_retries.load(Ordering::Relaxed),
+            webhook_failures: self.webhook_failures.load(Ordering::Relaxed),
+            consumer_stalls: self.consumer_stalls.load(Ordering::Relaxed),
+            order_rejects: self.order_rejects.lock().clone(),
+            validator_rejects: self.validator_rejects.lock().clone(),
+            audit_write_failures: logging::audit_write_failures(),
+        }
+    }
+}
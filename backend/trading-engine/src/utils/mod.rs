@@ -0,0 +1,7 @@
+pub mod calendar;
+pub mod clock_sync;
+pub mod daycount;
+pub mod logging;
+pub mod metrics;
+pub mod time;
+pub mod versioned;
@@ -0,0 +1,138 @@
+use crate::utils::{
+    calendar::TradingCalendar,
+    clock_sync::{ClockQuality, ClockStamp},
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Source of wall-clock time for the engine. Everything time-dependent goes
+/// through a `Clock` so tests can drive it deterministically.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Manually controlled clock for tests and simulations.
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct SimulatedClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: RwLock::new(start),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.write() += duration;
+    }
+
+    pub fn jump_to(&self, time: DateTime<Utc>) {
+        *self.now.write() = time;
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.read()
+    }
+}
+
+#[derive(Clone)]
+pub struct TimeProvider {
+    clock: Arc<dyn Clock>,
+    quality: Arc<ClockQuality>,
+    calendar: Arc<TradingCalendar>,
+}
+
+impl TimeProvider {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            quality: Arc::new(ClockQuality::default()),
+            calendar: Arc::new(TradingCalendar::new()),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// The current time with the quality of the clock it was read from,
+    /// for timestamps that must be traceable to UTC.
+    pub fn stamped_now(&self) -> (DateTime<Utc>, ClockStamp) {
+        (self.clock.now(), self.quality.stamp())
+    }
+
+    pub fn clock_quality(&self) -> &ClockQuality {
+        &self.quality
+    }
+
+    pub fn calendar(&self) -> &Arc<TradingCalendar> {
+        &self.calendar
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+
+    /// Settlement date `cycle_days` business days after `trade_date`,
+    /// skipping weekends and the settlement market's holidays.
+    pub fn settlement_date(&self, trade_date: NaiveDate, cycle_days: u32) -> NaiveDate {
+        let market = self.calendar.settlement_market();
+        self.calendar.settlement_date(&market, trade_date, cycle_days)
+    }
+}
+
+impl Default for TimeProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_simulated_clock_advance_and_jump() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 5, 9, 0, 0).unwrap();
+        let clock = Arc::new(SimulatedClock::new(start));
+        let time_provider = TimeProvider::with_clock(clock.clone());
+
+        clock.advance(Duration::minutes(30));
+        assert_eq!(time_provider.now(), start + Duration::minutes(30));
+
+        let later = Utc.with_ymd_and_hms(2024, 1, 8, 15, 30, 0).unwrap();
+        clock.jump_to(later);
+        assert_eq!(time_provider.now(), later);
+    }
+
+    #[test]
+    fn test_settlement_date_skips_weekends() {
+        let time_provider = TimeProvider::new();
+        let friday = NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+
+        assert_eq!(
+            time_provider.settlement_date(friday, 1),
+            NaiveDate::from_ymd_opt(2024, 1, 8).unwrap()
+        );
+        assert_eq!(time_provider.settlement_date(friday, 0), friday);
+    }
+}
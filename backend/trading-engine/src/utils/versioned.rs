@@ -0,0 +1,42 @@
+use parking_lot::{RwLock, RwLockWriteGuard};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Write guard that advances `version` when released, while the lock is
+/// still held. Anything read after seeing a version is at least as new as
+/// that version, so it can be cached under it.
+pub struct VersionedWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    version: &'a AtomicU64,
+}
+
+impl<'a, T> VersionedWriteGuard<'a, T> {
+    pub fn new(lock: &'a RwLock<T>, version: &'a AtomicU64) -> Self {
+        Self {
+            guard: lock.write(),
+            version,
+        }
+    }
+}
+
+impl<T> Deref for VersionedWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for VersionedWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for VersionedWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
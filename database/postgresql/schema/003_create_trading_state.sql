@@ -0,0 +1,25 @@
+-- VedhaVriddhi - Trading Engine State Schema
+-- Write-behind copy of the matching engine's live orders and positions,
+-- used for recovery and reconciliation after incidents
+
+-- Orders as last seen by the engine
+CREATE TABLE engine_orders (
+    id UUID PRIMARY KEY,
+    account_id UUID NOT NULL,
+    symbol VARCHAR(50) NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    payload JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_engine_orders_account ON engine_orders(account_id);
+CREATE INDEX idx_engine_orders_symbol_status ON engine_orders(symbol, status);
+
+-- Positions per account and symbol
+CREATE TABLE engine_positions (
+    account_id UUID NOT NULL,
+    symbol VARCHAR(50) NOT NULL,
+    payload JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (account_id, symbol)
+);
@@ -0,0 +1,12 @@
+-- VedhaVriddhi - Intraday P&L Snapshots
+-- Periodic per-account P&L samples backing the equity curve API
+
+CREATE TABLE engine_pnl_snapshots (
+    account_id UUID NOT NULL,
+    captured_at TIMESTAMPTZ NOT NULL,
+    realized_pnl NUMERIC(20, 4) NOT NULL,
+    unrealized_pnl NUMERIC(20, 4) NOT NULL,
+    total_pnl NUMERIC(20, 4) NOT NULL,
+    market_value NUMERIC(20, 4) NOT NULL,
+    PRIMARY KEY (account_id, captured_at)
+);
@@ -0,0 +1,13 @@
+-- VedhaVriddhi - Notification Subscriptions
+-- Users' delivery targets and event filters for fill, risk and session
+-- notifications, restored by the engine at startup
+
+CREATE TABLE engine_notification_subscriptions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    account_id UUID NOT NULL,
+    payload JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_engine_notification_subscriptions_user ON engine_notification_subscriptions(user_id);
@@ -0,0 +1,18 @@
+-- VedhaVriddhi - Trade History
+-- Trades older than the engine's in-memory window, queried by time range,
+-- symbol and account
+
+CREATE TABLE engine_trades (
+    id UUID PRIMARY KEY,
+    trade_number VARCHAR(40) NOT NULL,
+    symbol VARCHAR(50) NOT NULL,
+    buyer_account_id UUID NOT NULL,
+    seller_account_id UUID NOT NULL,
+    executed_at TIMESTAMPTZ NOT NULL,
+    payload JSONB NOT NULL
+);
+
+CREATE INDEX idx_engine_trades_executed_at ON engine_trades(executed_at);
+CREATE INDEX idx_engine_trades_symbol_executed_at ON engine_trades(symbol, executed_at);
+CREATE INDEX idx_engine_trades_buyer ON engine_trades(buyer_account_id, executed_at);
+CREATE INDEX idx_engine_trades_seller ON engine_trades(seller_account_id, executed_at);
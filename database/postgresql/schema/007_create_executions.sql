@@ -0,0 +1,16 @@
+-- VedhaVriddhi - Execution Reports
+-- One row per acknowledgement, fill, cancel, expiry or reject of an order,
+-- queried by account and time range
+
+CREATE TABLE engine_executions (
+    id BIGSERIAL PRIMARY KEY,
+    exec_id UUID NOT NULL UNIQUE,
+    order_id UUID NOT NULL,
+    account_id UUID NOT NULL,
+    transact_time TIMESTAMPTZ NOT NULL,
+    payload JSONB NOT NULL
+);
+
+CREATE INDEX idx_engine_executions_account ON engine_executions(account_id, transact_time);
+CREATE INDEX idx_engine_executions_order ON engine_executions(order_id);
+CREATE INDEX idx_engine_executions_transact_time ON engine_executions(transact_time);
@@ -0,0 +1,9 @@
+-- VedhaVriddhi - Order Book Snapshots
+-- The engine's latest snapshot of its resting orders with their time
+-- priority, restored on restart. Only one snapshot is kept.
+
+CREATE TABLE engine_book_snapshots (
+    id SMALLINT PRIMARY KEY CHECK (id = 1),
+    payload JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);